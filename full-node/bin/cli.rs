@@ -36,7 +36,7 @@ use smoldot::{
         PeerId,
     },
 };
-use std::{io, net::SocketAddr, path::PathBuf};
+use std::{io, net::SocketAddr, num::NonZero, path::PathBuf};
 
 // Note: the doc-comments applied to this struct and its field are visible when the binary is
 // started with `--help`.
@@ -91,6 +91,30 @@ pub struct CliOptionsRun {
     /// Maximum number of JSON-RPC clients that can be connected simultaneously. Ignored if no server.
     #[arg(long, default_value = "64")]
     pub json_rpc_max_clients: u32,
+    /// Maximum number of subscriptions that each JSON-RPC client can have active simultaneously.
+    #[arg(long, default_value = "128")]
+    pub json_rpc_max_subscriptions_per_client: u32,
+    /// Maximum number of blocks that each `chainHead_v1_follow` subscription can keep pinned simultaneously. 0 for no limit.
+    #[arg(long, default_value = "512")]
+    pub json_rpc_max_chain_head_pinned_blocks: u32,
+    /// Maximum number of calls per second to the JSON-RPC functions that execute the runtime, such as `state_call`. 0 for no limit.
+    #[arg(long, default_value = "16")]
+    pub json_rpc_max_expensive_requests_per_second: u32,
+    /// Maximum number of JSON-RPC requests waiting to be processed. When this limit is reached, the oldest waiting request is answered with a "server busy" error.
+    #[arg(long, default_value = "256")]
+    pub json_rpc_max_queued_requests: NonZero<u32>,
+    /// Maximum size, in bytes, of a JSON-RPC request. Larger requests are rejected without being parsed.
+    #[arg(long, default_value = "15728640")]
+    pub json_rpc_max_request_size: u32,
+    /// Log the name and duration of each JSON-RPC request.
+    #[arg(long)]
+    pub json_rpc_log_requests_durations: bool,
+    /// Use the finalized block rather than the best block when a JSON-RPC function that accepts an optional block hash is called without one.
+    #[arg(long)]
+    pub json_rpc_default_finalized: bool,
+    /// Maximum number of seconds that the database accesses of a JSON-RPC request can take before the request fails.
+    #[arg(long, default_value = "60")]
+    pub json_rpc_database_timeout_secs: u64,
     /// List of secret phrases to insert in the keystore of the node. Used to author blocks.
     #[arg(long, value_parser = decode_sr25519_private_key)]
     // TODO: also automatically add the same keys through ed25519?
@@ -108,6 +132,13 @@ pub struct CliOptionsRun {
     /// chain is not a parachain.
     #[arg(long, default_value = "256M", value_parser = parse_max_bytes)]
     pub relay_chain_database_cache_size: MaxBytes,
+    /// Number of finalized blocks whose storage is kept in the database, counting down from the
+    /// latest finalized block. The storage of older blocks is removed. Keeps everything if absent.
+    #[arg(long)]
+    pub blocks_pruning: Option<u64>,
+    /// Do not check, when opening an existing database, that the state root of its genesis block matches the one of the chain specification. The hash of the genesis block is always checked.
+    #[arg(long)]
+    pub skip_database_genesis_check: bool,
 }
 
 #[derive(Debug, clap::Parser)]
@@ -19,6 +19,7 @@
 // TODO: #![deny(unused_crate_dependencies)] doesn't work because some deps are used only by the library, figure if this can be fixed?
 
 use std::{
+    collections::BTreeMap,
     fs, io,
     sync::Arc,
     thread,
@@ -262,6 +263,10 @@ async fn run(cli_options: cli::CliOptionsRun) {
                     .as_ref()
                     .map(|path| path.join(parsed_relay_spec.id()).join("keys")),
                 json_rpc_listen: None,
+                code_substitutes: BTreeMap::new(),
+                blocks_pruning: cli_options.blocks_pruning,
+                genesis_storage: None,
+                verify_database_genesis: !cli_options.skip_database_genesis_check,
             };
 
             (Some(cfg), Some(relay_chain_name.to_owned()))
@@ -375,10 +380,27 @@ async fn run(cli_options: cli::CliOptionsRun) {
                 Some(smoldot_full_node::JsonRpcListenConfig {
                     address,
                     max_json_rpc_clients: cli_options.json_rpc_max_clients,
+                    max_json_rpc_subscriptions_per_client: cli_options
+                        .json_rpc_max_subscriptions_per_client,
+                    max_json_rpc_chain_head_pinned_blocks: cli_options
+                        .json_rpc_max_chain_head_pinned_blocks,
+                    max_json_rpc_expensive_requests_per_second: cli_options
+                        .json_rpc_max_expensive_requests_per_second,
+                    max_json_rpc_queued_requests: cli_options.json_rpc_max_queued_requests,
+                    max_json_rpc_request_size: cli_options.json_rpc_max_request_size,
+                    log_json_rpc_requests_durations: cli_options.json_rpc_log_requests_durations,
+                    json_rpc_default_to_finalized_block: cli_options.json_rpc_default_finalized,
+                    json_rpc_database_timeout: Duration::from_secs(
+                        cli_options.json_rpc_database_timeout_secs,
+                    ),
                 })
             } else {
                 None
             },
+            code_substitutes: BTreeMap::new(),
+            blocks_pruning: cli_options.blocks_pruning,
+            genesis_storage: None,
+            verify_database_genesis: !cli_options.skip_database_genesis_check,
         },
         relay_chain,
         libp2p_key,
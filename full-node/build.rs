@@ -0,0 +1,57 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Build script that exposes the hash of the git commit the node is built from in the
+//! `SMOLDOT_GIT_COMMIT` environment variable.
+//!
+//! The environment variable isn't set if the source code doesn't come from a git repository or
+//! if `git` isn't available.
+
+use std::{path::Path, process::Command};
+
+fn main() {
+    let Some(git_dir) = git(&["rev-parse", "--git-dir"]) else {
+        return;
+    };
+
+    // Build again whenever the current commit changes.
+    for path in ["HEAD", "refs/heads", "packed-refs"] {
+        let path = Path::new(&git_dir).join(path);
+        if path.exists() {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+
+    if let Some(commit) = git(&["rev-parse", "--short=10", "HEAD"]) {
+        println!("cargo:rustc-env=SMOLDOT_GIT_COMMIT={commit}");
+    }
+}
+
+/// Runs `git` with the given arguments and returns its trimmed output, or `None` if it fails.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let output = String::from_utf8(output.stdout).ok()?;
+    Some(output.trim().to_owned())
+}
@@ -31,13 +31,14 @@ use futures_lite::FutureExt as _;
 use futures_util::{
     future,
     stream::{self, FuturesUnordered},
-    SinkExt as _, StreamExt as _,
+    SinkExt as _, Stream, StreamExt as _,
 };
 use hashbrown::HashSet;
 use rand::seq::IteratorRandom;
 use smol::lock::Mutex;
 use smoldot::{
     author,
+    chain::chain_information,
     database::full_sqlite,
     executor::{self, host, runtime_call},
     header,
@@ -46,15 +47,17 @@ use smoldot::{
     libp2p,
     network::{self, codec::BlockData},
     sync::all,
+    transactions::validate,
     trie,
-    verify::body_only,
+    verify::{aura, babe, body_only, header_only},
 };
 use std::{
     array,
     borrow::Cow,
     cmp,
+    collections::BTreeMap,
     future::Future,
-    iter,
+    iter, mem,
     num::NonZero,
     pin::Pin,
     sync::Arc,
@@ -115,8 +118,25 @@ pub struct Config {
     /// Note that this value doesn't determine the moment when creating the block has ended, but
     /// the moment when creating the block should start its final phase.
     pub slot_duration_author_ratio: u16,
+
+    /// Runtime code to use instead of the on-chain runtime code starting from the blocks with
+    /// the given numbers.
+    ///
+    /// A substitute applies to the block with the given number and to all its descendants,
+    /// until one of them modifies the on-chain runtime code. A substitute that fails to compile
+    /// is ignored and the on-chain runtime code is used instead.
+    pub code_substitutes: BTreeMap<u64, Vec<u8>>,
+
+    /// If `Some`, the storage of the finalized blocks that are more than this number of blocks
+    /// below the latest finalized block is removed from the database. If `None`, the storage of
+    /// all blocks is kept.
+    pub blocks_pruning: Option<u64>,
 }
 
+/// Reputation change applied, through [`network_service::NetworkService::report_peer`], to the
+/// peers that have announced or sent a block whose header fails to verify.
+const INVALID_BLOCK_REPUTATION_CHANGE: i32 = network_service::BAN_REPUTATION_THRESHOLD / 2;
+
 /// Identifier for a blocks request to be performed.
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub struct BlocksRequestId(usize);
@@ -138,8 +158,18 @@ pub struct ConsensusService {
 
     /// See [`Config::block_number_bytes`].
     block_number_bytes: usize,
+
+    /// See [`ConsensusService::starting_block_number`].
+    starting_block_number: u64,
+
+    /// See [`Config::database`].
+    database: Arc<database_thread::DatabaseThread>,
 }
 
+/// List of keys and their new values, as returned by
+/// [`ConsensusService::block_storage_changes`].
+type StorageChangesList = Vec<(Vec<u8>, Option<Vec<u8>>)>;
+
 enum ToBackground {
     SubscribeAll {
         buffer_size: usize,
@@ -147,9 +177,27 @@ enum ToBackground {
         _max_finalized_pinned_blocks: NonZero<usize>,
         result_tx: oneshot::Sender<SubscribeAll>,
     },
+    SubscribeImportedBlocks {
+        result_tx: oneshot::Sender<SubscribeImportedBlocks>,
+    },
+    SubscribeGrandpaAuthoritySetChanges {
+        result_tx: oneshot::Sender<async_channel::Receiver<GrandpaAuthoritySetChange>>,
+    },
     GetSyncState {
         result_tx: oneshot::Sender<SyncState>,
     },
+    GetLeaves {
+        result_tx: oneshot::Sender<Vec<([u8; 32], u64)>>,
+    },
+    GetBlockTree {
+        max_depth: u64,
+        max_blocks_per_height: usize,
+        result_tx: oneshot::Sender<BlockTree>,
+    },
+    GetBlockStorageChanges {
+        block_hash: [u8; 32],
+        result_tx: oneshot::Sender<Option<StorageChangesList>>,
+    },
     Unpin {
         // TODO: unused field
         _subscription_id: SubscriptionId,
@@ -162,6 +210,17 @@ enum ToBackground {
     IsMajorSyncingHint {
         result_tx: oneshot::Sender<bool>,
     },
+    SubmitAndWatchTransaction {
+        scale_encoded_transaction: Vec<u8>,
+        hash: [u8; 32],
+        status_tx: async_channel::Sender<TxStatus>,
+    },
+    DownloadFinalizedStorage {
+        start_key: Vec<u8>,
+        result_tx: oneshot::Sender<
+            async_channel::Receiver<Result<StorageDownloadChunk, StorageDownloadError>>,
+        >,
+    },
 }
 
 /// Potential error when calling [`ConsensusService::new`].
@@ -230,7 +289,8 @@ impl ConsensusService {
                                 return Err(InitError::DatabaseCorruption(err))
                             }
                             Err(full_sqlite::StorageAccessError::IncompleteStorage)
-                            | Err(full_sqlite::StorageAccessError::UnknownBlock) => unreachable!(),
+                            | Err(full_sqlite::StorageAccessError::UnknownBlock)
+                            | Err(full_sqlite::StorageAccessError::StoragePruned) => unreachable!(),
                         };
                     let finalized_code = match database.block_storage_get(
                         &finalized_block_hash,
@@ -243,7 +303,8 @@ impl ConsensusService {
                             return Err(InitError::DatabaseCorruption(err))
                         }
                         Err(full_sqlite::StorageAccessError::IncompleteStorage)
-                        | Err(full_sqlite::StorageAccessError::UnknownBlock) => unreachable!(),
+                        | Err(full_sqlite::StorageAccessError::UnknownBlock)
+                        | Err(full_sqlite::StorageAccessError::StoragePruned) => unreachable!(),
                     };
                     let finalized_heap_pages = match database.block_storage_get(
                         &finalized_block_hash,
@@ -256,7 +317,8 @@ impl ConsensusService {
                             return Err(InitError::DatabaseCorruption(err))
                         }
                         Err(full_sqlite::StorageAccessError::IncompleteStorage)
-                        | Err(full_sqlite::StorageAccessError::UnknownBlock) => unreachable!(),
+                        | Err(full_sqlite::StorageAccessError::UnknownBlock)
+                        | Err(full_sqlite::StorageAccessError::StoragePruned) => unreachable!(),
                     };
                     Ok((
                         finalized_block_number,
@@ -333,6 +395,24 @@ impl ConsensusService {
             .map_err(InitError::FinalizedRuntimeInit)?
         };
 
+        let finalized_runtime = match finalized_code_substitute_runtime(
+            &config.code_substitutes,
+            finalized_block_number,
+            &finalized_runtime,
+        ) {
+            Some(Ok(substitute)) => substitute,
+            Some(Err(error)) => {
+                config.log_callback.log(
+                    LogLevel::Warn,
+                    format!("invalid-code-substitute; error={error}"),
+                );
+                finalized_runtime
+            }
+            None => finalized_runtime,
+        };
+
+        let finalized_grandpa_set_id = grandpa_set_id(&sync);
+
         let block_author_sync_source = sync
             .prepare_add_source(best_block_number, best_block_hash)
             .add_source(None, NonFinalizedBlock::NotVerified);
@@ -345,8 +425,11 @@ impl ConsensusService {
             block_authoring: None,
             authored_block: None,
             slot_duration_author_ratio: config.slot_duration_author_ratio,
+            code_substitutes: config.code_substitutes,
+            blocks_pruning: config.blocks_pruning,
             keystore: config.keystore,
             finalized_runtime: Arc::new(finalized_runtime),
+            finalized_storage_changes: None,
             network_service: config.network_service.0,
             network_chain_id: config.network_service.1,
             network_local_chain_update_needed: true,
@@ -354,8 +437,11 @@ impl ConsensusService {
             to_background_rx,
             blocks_notifications: Vec::with_capacity(8),
             pending_notification: None,
+            imported_blocks_notifications: Vec::with_capacity(4),
+            grandpa_authority_set_changes_notifications: Vec::with_capacity(2),
+            finalized_grandpa_set_id,
             from_network_service: config.network_events_receiver,
-            database: config.database,
+            database: config.database.clone(),
             database_catch_up_download: DatabaseCatchUpDownload::NoDownloadInProgress,
             database_catch_up_download_block_verification:
                 DatabaseCatchUpDownloadBlockVerification::None,
@@ -363,12 +449,16 @@ impl ConsensusService {
             sub_tasks: FuturesUnordered::new(),
             log_callback: config.log_callback,
             jaeger_service: config.jaeger_service,
+            watched_transactions: Default::default(),
+            next_watched_transaction_id: 0,
         };
 
         (config.tasks_executor)(Box::pin(background_sync.run()));
 
         Ok(Arc::new(ConsensusService {
             block_number_bytes: config.block_number_bytes,
+            starting_block_number: best_block_number,
+            database: config.database,
             to_background_tx: Mutex::new(to_background_tx),
         }))
     }
@@ -378,6 +468,11 @@ impl ConsensusService {
         self.block_number_bytes
     }
 
+    /// Returns the number of the best block at the time when the service was started.
+    pub fn starting_block_number(&self) -> u64 {
+        self.starting_block_number
+    }
+
     /// Returns a summary of the state of the service.
     ///
     /// > **Important**: This doesn't represent the content of the database.
@@ -393,6 +488,91 @@ impl ConsensusService {
         result_rx.await.unwrap()
     }
 
+    /// Returns the hashes and numbers of all the leaves of the tree of blocks, in other words
+    /// the blocks that don't have any child.
+    ///
+    /// The current best block is always first. The other leaves are ordered by decreasing block
+    /// number. If there isn't any non-finalized block, the only leaf is the finalized block.
+    ///
+    /// > **Important**: This reflects the blocks known to the syncing in memory, not the content
+    /// >                of the database.
+    pub async fn leaves(&self) -> Vec<([u8; 32], u64)> {
+        let (result_tx, result_rx) = oneshot::channel();
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::GetLeaves { result_tx })
+            .await;
+        result_rx.await.unwrap()
+    }
+
+    /// Returns the finalized block and the non-finalized blocks known to the syncing. The
+    /// finalized block comes first, followed with the blocks of the best chain, followed with
+    /// the other blocks, in an order where parents always come before their children.
+    ///
+    /// The finalized block and the best chain are always returned. In order to bound the size
+    /// of the result, only the other blocks whose number is at most `max_depth` above the
+    /// finalized block number are returned, and at most `max_blocks_per_height` blocks are
+    /// returned for each block number. The descendants of an omitted block are omitted as well.
+    /// [`BlockTree::truncated`] indicates whether any block has been omitted.
+    ///
+    /// > **Important**: This reflects the blocks known to the syncing in memory, not the content
+    /// >                of the database.
+    pub async fn block_tree(&self, max_depth: u64, max_blocks_per_height: usize) -> BlockTree {
+        let (result_tx, result_rx) = oneshot::channel();
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::GetBlockTree {
+                max_depth,
+                max_blocks_per_height,
+                result_tx,
+            })
+            .await;
+        result_rx.await.unwrap()
+    }
+
+    /// Returns the changes to the main trie of the storage performed by the given block compared
+    /// to its parent, ordered by key. Each value is `None` if the block has erased the entry.
+    ///
+    /// The changes of the blocks verified by this service are kept in memory until they are
+    /// finalized. For other blocks, such as older finalized blocks, the changes are instead
+    /// calculated by comparing the storage of the block with the storage of its parent in the
+    /// database, which can be expensive if the block has modified a lot of entries.
+    ///
+    /// Returns `None` if the block is unknown, if it hasn't been verified yet, if it is the
+    /// genesis block, or if the storage of the block or of its parent isn't in the database.
+    ///
+    /// > **Note**: For the blocks whose changes are kept in memory, the changes are those
+    /// >           reported by the runtime, and an entry overwritten with the value it already
+    /// >           had in the parent is included in the list. The changes calculated from the
+    /// >           database only include the entries whose value is different.
+    pub async fn block_storage_changes(&self, block_hash: [u8; 32]) -> Option<StorageChangesList> {
+        let (result_tx, result_rx) = oneshot::channel();
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::GetBlockStorageChanges {
+                block_hash,
+                result_tx,
+            })
+            .await;
+        if let Some(changes) = result_rx.await.unwrap() {
+            return Some(changes);
+        }
+
+        self.database
+            .with_database(move |database| {
+                database_storage_changes(database, &block_hash)
+                    .ok()
+                    .flatten()
+            })
+            .await
+    }
+
     /// Subscribes to the state of the chain: the current state and the new blocks.
     ///
     /// Only up to `buffer_size` notifications are buffered in the channel. If the channel is full
@@ -431,6 +611,50 @@ impl ConsensusService {
         result_rx.await.unwrap()
     }
 
+    /// Subscribes to the blocks that are imported from now on.
+    ///
+    /// Contrary to [`ConsensusService::subscribe_all`], no block is pinned, and the finalization
+    /// of blocks isn't reported. Blocks are reported only once they have been verified and
+    /// stored in the database.
+    ///
+    /// See [`SubscribeImportedBlocks`] for information about the return value.
+    pub async fn subscribe_imported_blocks(&self) -> SubscribeImportedBlocks {
+        let (result_tx, result_rx) = oneshot::channel();
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::SubscribeImportedBlocks { result_tx })
+            .await;
+        result_rx.await.unwrap()
+    }
+
+    /// Subscribes to the changes of Grandpa authority set from now on.
+    ///
+    /// A notification is generated whenever a block that changes the Grandpa authority set gets
+    /// finalized by a justification. This is meant to be used by bridge relayers, which need
+    /// the header, justification, and authority set of each such block.
+    ///
+    /// > **Note**: Authority set changes that are finalized through a Grandpa commit message
+    /// >           rather than a justification aren't reported, as no justification is
+    /// >           available for them.
+    ///
+    /// Only up to 8 notifications are buffered in the stream. If the stream is full when a new
+    /// change needs to be reported, or if a gap in the chain happens (for example after a warp
+    /// sync), the stream ends.
+    pub async fn subscribe_grandpa_authority_set_changes(
+        &self,
+    ) -> impl Stream<Item = GrandpaAuthoritySetChange> {
+        let (result_tx, result_rx) = oneshot::channel();
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::SubscribeGrandpaAuthoritySetChanges { result_tx })
+            .await;
+        result_rx.await.unwrap()
+    }
+
     /// Unpins a block that was reported as part of a subscription.
     ///
     /// Has no effect if the [`SubscriptionId`] is not or no longer valid (as the consensus service
@@ -470,6 +694,240 @@ impl ConsensusService {
             .await;
         result_rx.await.unwrap()
     }
+
+    /// Submits a transaction and watches its status.
+    ///
+    /// Returns the BLAKE2 hash of the transaction, and a stream of status updates. The
+    /// transaction is first validated against the runtime of the current best block, then sent
+    /// to the peers we are connected to.
+    ///
+    /// Afterwards, the stream yields [`TxStatus::InBlock`] whenever a block of the best chain
+    /// that contains the transaction is found. If this block stops being part of the best chain
+    /// following a re-organization, [`TxStatus::Retracted`] is yielded, possibly followed with a
+    /// new [`TxStatus::InBlock`] if the new best chain also contains the transaction.
+    ///
+    /// The stream ends after [`TxStatus::Finalized`], [`TxStatus::Dropped`], or
+    /// [`TxStatus::Invalid`] has been yielded. Only up to 16 status updates are buffered in the
+    /// stream. If the stream is full when a new status needs to be reported, the stream ends.
+    ///
+    /// Destroying the stream stops watching the transaction. If the transaction hasn't been sent
+    /// to peers yet, it is then no longer sent.
+    pub async fn submit_and_watch(
+        &self,
+        scale_encoded_transaction: Vec<u8>,
+    ) -> ([u8; 32], impl Stream<Item = TxStatus>) {
+        let hash = transaction_hash(&scale_encoded_transaction);
+
+        let (status_tx, status_rx) = async_channel::bounded(16);
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::SubmitAndWatchTransaction {
+                scale_encoded_transaction,
+                hash,
+                status_tx,
+            })
+            .await;
+
+        (hash, status_rx)
+    }
+
+    /// Downloads from the network the storage of the current finalized block, starting at the
+    /// given key.
+    ///
+    /// This is meant to be used after a warp sync, in order to download the storage of the
+    /// block that has been warp synced to.
+    ///
+    /// The storage is downloaded through storage proofs, each of them verified against the state
+    /// trie root found in the header of the finalized block. The stream yields the downloaded
+    /// entries in lexicographic order of keys, in batches of [`StorageDownloadChunk`]s. Only
+    /// entries whose key is superior or equal to `start_key` are yielded.
+    ///
+    /// The stream ends after a [`StorageDownloadChunk`] whose
+    /// [`StorageDownloadChunk::next_start_key`] is `None` has been yielded, or after an error
+    /// has been yielded. Entries that have been yielded are always verified, and it is
+    /// consequently possible to continue a failed download by calling this function again with
+    /// [`StorageDownloadError::resume_key`] as `start_key`. Keep in mind that the finalized block
+    /// might have changed in the meanwhile, in which case [`StorageDownloadChunk::block_hash`]
+    /// will be different.
+    ///
+    /// The peers to download from are the sources of the syncing at the time when this function
+    /// is called.
+    ///
+    /// > **Note**: The content of child tries isn't downloaded. Only the entries of the main
+    /// >           trie, including the entries under `:child_storage:` that contain the root of
+    /// >           each child trie, are yielded.
+    pub async fn download_finalized_storage(
+        &self,
+        start_key: Vec<u8>,
+    ) -> impl Stream<Item = Result<StorageDownloadChunk, StorageDownloadError>> {
+        let (result_tx, result_rx) = oneshot::channel();
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::DownloadFinalizedStorage {
+                start_key,
+                result_tx,
+            })
+            .await;
+        result_rx.await.unwrap()
+    }
+}
+
+#[cfg(test)]
+impl ConsensusService {
+    /// Builds a [`ConsensusService`] that doesn't perform any syncing, for testing purposes.
+    ///
+    /// Either [`ConsensusService::subscribe_all`] or [`ConsensusService::subscribe_imported_blocks`]
+    /// can be called once on the returned service. The subscription reports the given finalized
+    /// block, without any non-finalized block, as the best block, then the notifications received
+    /// on `new_blocks`.
+    /// [`ConsensusService::unpin_block`] has no effect. Calling any other function panics.
+    pub fn from_notifications(
+        database: Arc<database_thread::DatabaseThread>,
+        block_number_bytes: usize,
+        finalized_block_scale_encoded_header: Vec<u8>,
+        finalized_block_runtime: host::HostVmPrototype,
+        new_blocks: async_channel::Receiver<Notification>,
+    ) -> Arc<Self> {
+        let mut subscription = Some((
+            finalized_block_scale_encoded_header,
+            finalized_block_runtime,
+            new_blocks,
+        ));
+
+        let (to_background_tx, mut to_background_rx) = mpsc::channel(4);
+        smol::spawn(async move {
+            while let Some(message) = to_background_rx.next().await {
+                match message {
+                    ToBackground::SubscribeAll { result_tx, .. } => {
+                        let (header, runtime, new_blocks) = subscription.take().unwrap();
+                        let _ = result_tx.send(SubscribeAll {
+                            id: SubscriptionId(0),
+                            finalized_block_hash: header::hash_from_scale_encoded_header(&header),
+                            finalized_block_scale_encoded_header: header,
+                            finalized_block_runtime: Arc::new(runtime),
+                            non_finalized_blocks_ancestry_order: Vec::new(),
+                            new_blocks,
+                        });
+                    }
+                    ToBackground::SubscribeImportedBlocks { result_tx } => {
+                        let (header, _, new_blocks) = subscription.take().unwrap();
+                        let (tx, rx) = async_channel::bounded(32);
+                        smol::spawn(async move {
+                            while let Ok(notification) = new_blocks.recv().await {
+                                let Notification::Block { block, .. } = notification else {
+                                    continue;
+                                };
+                                let imported_block = ImportedBlock {
+                                    hash: block.block_hash,
+                                    number: header::decode(
+                                        &block.scale_encoded_header,
+                                        block_number_bytes,
+                                    )
+                                    .unwrap()
+                                    .number,
+                                    scale_encoded_header: block.scale_encoded_header,
+                                    is_new_best: block.is_new_best,
+                                };
+                                if tx.send(imported_block).await.is_err() {
+                                    break;
+                                }
+                            }
+                        })
+                        .detach();
+                        let _ = result_tx.send(SubscribeImportedBlocks {
+                            best_block_scale_encoded_header: header,
+                            new_blocks: rx,
+                        });
+                    }
+                    ToBackground::Unpin { result_tx, .. } => {
+                        let _ = result_tx.send(());
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        })
+        .detach();
+
+        Arc::new(ConsensusService {
+            block_number_bytes,
+            starting_block_number: 0,
+            database,
+            to_background_tx: Mutex::new(to_background_tx),
+        })
+    }
+}
+
+/// Batch of storage entries yielded by [`ConsensusService::download_finalized_storage`].
+#[derive(Debug, Clone)]
+pub struct StorageDownloadChunk {
+    /// Hash of the block whose storage is being downloaded.
+    pub block_hash: [u8; 32],
+
+    /// List of keys and values of the storage of the block, ordered by key. Has been verified
+    /// against the state trie root of the block.
+    ///
+    /// Can be empty if no key exists within the range covered by this chunk.
+    pub entries: Vec<(Vec<u8>, Vec<u8>)>,
+
+    /// Key to start the rest of the download at, or `None` if this is the last chunk.
+    ///
+    /// All the keys strictly inferior to this key have been yielded.
+    pub next_start_key: Option<Vec<u8>>,
+}
+
+/// Error yielded by [`ConsensusService::download_finalized_storage`].
+#[derive(Debug, Clone, derive_more::Display)]
+#[display(fmt = "{detail}")]
+pub struct StorageDownloadError {
+    /// Key to pass back to [`ConsensusService::download_finalized_storage`] in order to resume
+    /// the download. All the keys strictly inferior to this key have been yielded before the
+    /// error.
+    pub resume_key: Vec<u8>,
+
+    /// Reason why the download has failed.
+    pub detail: StorageDownloadErrorDetail,
+}
+
+/// See [`StorageDownloadError::detail`].
+#[derive(Debug, Clone, derive_more::Display)]
+pub enum StorageDownloadErrorDetail {
+    /// No peer is available to download the storage from.
+    NoPeer,
+    /// All the peers have failed to provide a valid storage proof.
+    AllPeersFailed,
+}
+
+/// Status of a transaction, as reported by [`ConsensusService::submit_and_watch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxStatus {
+    /// The runtime of the best block has considered the transaction as valid.
+    Validated,
+    /// The transaction has been sent to at least one peer.
+    Broadcasted,
+    /// The transaction is included in the block with the given hash, which is part of the best
+    /// chain.
+    InBlock([u8; 32]),
+    /// The block with the given hash, previously reported in a [`TxStatus::InBlock`], is no
+    /// longer part of the best chain.
+    Retracted([u8; 32]),
+    /// The block with the given hash, which includes the transaction, has been finalized.
+    ///
+    /// This is always the last status.
+    Finalized([u8; 32]),
+    /// The transaction is no longer being watched, for example because its validity couldn't be
+    /// determined or because of a gap in the chain.
+    ///
+    /// This is always the last status.
+    Dropped,
+    /// The runtime of the best block has considered the transaction as invalid, for the given
+    /// reason.
+    ///
+    /// This is always the last status.
+    Invalid(validate::InvalidTransaction),
 }
 
 /// Return value of [`ConsensusService::subscribe_all`].
@@ -547,6 +1005,110 @@ pub enum Notification {
     },
 }
 
+/// Return value of [`ConsensusService::subscribe_imported_blocks`].
+pub struct SubscribeImportedBlocks {
+    /// SCALE-encoded header of the best block at the time of the subscription.
+    pub best_block_scale_encoded_header: Vec<u8>,
+
+    /// Channel onto which the blocks imported after the subscription are sent.
+    ///
+    /// Only up to 32 blocks are buffered in the channel. If the channel is full when a new block
+    /// needs to be reported, or if a gap in the chain happens (for example after a warp sync),
+    /// the channel gets closed.
+    pub new_blocks: async_channel::Receiver<ImportedBlock>,
+}
+
+/// Block reported by [`ConsensusService::subscribe_imported_blocks`].
+#[derive(Debug, Clone)]
+pub struct ImportedBlock {
+    /// BLAKE2 hash of the header of the block.
+    pub hash: [u8; 32],
+
+    /// Height of the block.
+    pub number: u64,
+
+    /// SCALE-encoded header of the block.
+    pub scale_encoded_header: Vec<u8>,
+
+    /// True if this block has become the best block of the chain.
+    pub is_new_best: bool,
+}
+
+/// Tree of blocks returned by [`ConsensusService::block_tree`].
+#[derive(Debug, Clone)]
+pub struct BlockTree {
+    /// Blocks of the tree. The first block is the finalized block, followed with the blocks of
+    /// the best chain, followed with the other blocks. Parents always come before their
+    /// children.
+    pub blocks: Vec<BlockTreeEntry>,
+
+    /// `true` if some blocks have been omitted from [`BlockTree::blocks`] because of the limits
+    /// passed to [`ConsensusService::block_tree`].
+    pub truncated: bool,
+}
+
+/// Block of a [`BlockTree`].
+#[derive(Debug, Clone)]
+pub struct BlockTreeEntry {
+    /// BLAKE2 hash of the header of the block.
+    pub hash: [u8; 32],
+
+    /// BLAKE2 hash of the header of the parent of the block.
+    pub parent_hash: [u8; 32],
+
+    /// Height of the block.
+    pub number: u64,
+
+    /// True if this block is the current best block of the chain.
+    pub is_best: bool,
+
+    /// True if this block is the current finalized block of the chain.
+    pub is_finalized: bool,
+}
+
+/// Change of Grandpa authority set reported by
+/// [`ConsensusService::subscribe_grandpa_authority_set_changes`].
+#[derive(Debug, Clone)]
+pub struct GrandpaAuthoritySetChange {
+    /// BLAKE2 hash of the header of the finalized block at which the authority set has changed.
+    pub block_hash: [u8; 32],
+
+    /// SCALE-encoded header of the block.
+    pub scale_encoded_header: Vec<u8>,
+
+    /// SCALE-encoded Grandpa justification that has finalized the block.
+    pub scale_encoded_justification: Vec<u8>,
+
+    /// Identifier of the new authority set.
+    pub new_set_id: u64,
+
+    /// Merkle proof of the storage entries of the block listed in
+    /// [`GRANDPA_AUTHORITY_SET_KEYS`], or `None` if the storage of the block isn't available in
+    /// the database.
+    pub authority_set_proof: Option<Vec<u8>>,
+}
+
+/// Storage keys whose values are proven in [`GrandpaAuthoritySetChange::authority_set_proof`].
+///
+/// Contains the `Authorities` and `CurrentSetId` storage items of the Grandpa pallet, and the
+/// `:grandpa_authorities` well-known key where older versions of this pallet store the list of
+/// authorities instead.
+pub const GRANDPA_AUTHORITY_SET_KEYS: [&[u8]; 3] = [
+    // `twox128("Grandpa") ++ twox128("Authorities")`
+    &[
+        0x5f, 0x9c, 0xc4, 0x5b, 0x7a, 0x00, 0xc5, 0x89, 0x93, 0x61, 0xe1, 0xc6, 0x09, 0x96, 0x78,
+        0xdc, 0x5e, 0x06, 0x21, 0xc4, 0x86, 0x9a, 0xa6, 0x0c, 0x02, 0xbe, 0x9a, 0xdc, 0xc9, 0x8a,
+        0x0d, 0x1d,
+    ],
+    // `twox128("Grandpa") ++ twox128("CurrentSetId")`
+    &[
+        0x5f, 0x9c, 0xc4, 0x5b, 0x7a, 0x00, 0xc5, 0x89, 0x93, 0x61, 0xe1, 0xc6, 0x09, 0x96, 0x78,
+        0xdc, 0x8a, 0x2d, 0x09, 0x46, 0x3e, 0xff, 0xcc, 0x78, 0xa2, 0x2d, 0x75, 0xb9, 0xcb, 0x87,
+        0xdf, 0xfc,
+    ],
+    b":grandpa_authorities",
+];
+
 /// Notification about a new block.
 ///
 /// See [`ConsensusService::subscribe_all`].
@@ -621,6 +1183,12 @@ struct SyncBackground {
     /// See [`Config::slot_duration_author_ratio`].
     slot_duration_author_ratio: u16,
 
+    /// See [`Config::code_substitutes`].
+    code_substitutes: BTreeMap<u64, Vec<u8>>,
+
+    /// See [`Config::blocks_pruning`].
+    blocks_pruning: Option<u64>,
+
     /// After a block has been authored, it is inserted here while waiting for the `sync` to
     /// import it. Contains the block height, the block hash, the SCALE-encoded block header, and
     /// the list of SCALE-encoded extrinsics of the block.
@@ -636,6 +1204,13 @@ struct SyncBackground {
     /// The `Arc` is shared with [`NonFinalizedBlock::Verified::runtime`].
     finalized_runtime: Arc<executor::host::HostVmPrototype>,
 
+    /// Changes to the storage performed by the latest finalized block compared to its parent.
+    /// `None` if the finalized block hasn't been verified by this service, for example at startup
+    /// or after a warp sync.
+    ///
+    /// The `Arc` is shared with [`NonFinalizedBlock::Verified::storage_changes`].
+    finalized_storage_changes: Option<Arc<runtime_call::StorageChanges>>,
+
     /// Used to receive messages from the frontend service, and to detect when it shuts down.
     to_background_rx: mpsc::Receiver<ToBackground>,
 
@@ -645,6 +1220,19 @@ struct SyncBackground {
     /// Notification ready to be sent to [`SyncBackground::blocks_notifications`].
     pending_notification: Option<Notification>,
 
+    /// List of senders to report imported blocks to.
+    /// See [`ConsensusService::subscribe_imported_blocks`].
+    imported_blocks_notifications: Vec<async_channel::Sender<ImportedBlock>>,
+
+    /// List of senders to report Grandpa authority set changes to.
+    /// See [`ConsensusService::subscribe_grandpa_authority_set_changes`].
+    grandpa_authority_set_changes_notifications:
+        Vec<async_channel::Sender<GrandpaAuthoritySetChange>>,
+
+    /// Grandpa authority set id after the latest finalized block of [`SyncBackground::sync`],
+    /// or `None` if the chain doesn't use Grandpa.
+    finalized_grandpa_set_id: Option<u64>,
+
     /// Service managing the connections to the networking peers.
     network_service: Arc<network_service::NetworkService>,
 
@@ -690,6 +1278,100 @@ struct SyncBackground {
 
     /// How to report events about blocks.
     jaeger_service: Arc<jaeger_service::JaegerService>,
+
+    /// Transactions submitted through [`ConsensusService::submit_and_watch`] whose status is
+    /// still being reported. Keys are allocated from
+    /// [`SyncBackground::next_watched_transaction_id`].
+    watched_transactions: hashbrown::HashMap<u64, WatchedTransaction, fnv::FnvBuildHasher>,
+
+    /// Identifier to assign to the next entry of [`SyncBackground::watched_transactions`].
+    next_watched_transaction_id: u64,
+}
+
+/// Transaction submitted through [`ConsensusService::submit_and_watch`].
+struct WatchedTransaction {
+    /// BLAKE2 hash of the SCALE-encoded transaction.
+    hash: [u8; 32],
+    /// Sender of the status updates. The watch is stopped if the channel is full or closed.
+    status_tx: async_channel::Sender<TxStatus>,
+    /// Hashes of the non-finalized blocks whose body contains the transaction.
+    included_in: Vec<[u8; 32]>,
+    /// Block that has last been reported through a [`TxStatus::InBlock`], if it hasn't been
+    /// retracted since then.
+    reported_in_block: Option<[u8; 32]>,
+}
+
+impl WatchedTransaction {
+    /// Reports [`TxStatus::Retracted`] and [`TxStatus::InBlock`] if the block of the best chain
+    /// that includes the transaction has changed. `best_chain` must contain the hashes of the
+    /// non-finalized blocks of the best chain.
+    ///
+    /// Returns `false` if the watch must be stopped because the channel is full or closed.
+    fn update_best_chain(&mut self, best_chain: &HashSet<[u8; 32], fnv::FnvBuildHasher>) -> bool {
+        let in_block = self
+            .included_in
+            .iter()
+            .find(|hash| best_chain.contains(*hash))
+            .copied();
+        if in_block == self.reported_in_block {
+            return true;
+        }
+
+        if let Some(retracted) = self.reported_in_block.take() {
+            if self
+                .status_tx
+                .try_send(TxStatus::Retracted(retracted))
+                .is_err()
+            {
+                return false;
+            }
+        }
+
+        if let Some(in_block) = in_block {
+            if self
+                .status_tx
+                .try_send(TxStatus::InBlock(in_block))
+                .is_err()
+            {
+                return false;
+            }
+            self.reported_in_block = Some(in_block);
+        }
+
+        true
+    }
+
+    /// Reports [`TxStatus::Finalized`] if the transaction is included in one of the blocks that
+    /// have been finalized, and forgets about the blocks that have been pruned.
+    ///
+    /// Returns `false` if the watch must be stopped because the transaction has been finalized.
+    fn update_finalized(
+        &mut self,
+        finalized_blocks_hashes: &[[u8; 32]],
+        pruned_blocks_hashes: &[[u8; 32]],
+    ) -> bool {
+        let Some(finalized) = self
+            .included_in
+            .iter()
+            .find(|hash| finalized_blocks_hashes.contains(hash))
+            .copied()
+        else {
+            self.included_in
+                .retain(|hash| !pruned_blocks_hashes.contains(hash));
+            return true;
+        };
+
+        // A finalized block is always part of the best chain, but it is possible for the
+        // best chain to not have been reported yet.
+        if self.reported_in_block != Some(finalized) {
+            if let Some(retracted) = self.reported_in_block {
+                let _ = self.status_tx.try_send(TxStatus::Retracted(retracted));
+            }
+            let _ = self.status_tx.try_send(TxStatus::InBlock(finalized));
+        }
+        let _ = self.status_tx.try_send(TxStatus::Finalized(finalized));
+        false
+    }
 }
 
 #[derive(Clone)]
@@ -701,6 +1383,14 @@ enum NonFinalizedBlock {
         ///
         /// The `Arc` is shared with [`SyncBackground::finalized_runtime`].
         runtime: Arc<executor::host::HostVmPrototype>,
+        /// Changes to the storage performed by the block compared to its parent.
+        ///
+        /// These changes are kept in memory for every non-finalized block and for the latest
+        /// finalized block. Their total size is thus bounded by the number of non-finalized
+        /// blocks that the syncing keeps in memory.
+        ///
+        /// The `Arc` is shared with [`SyncBackground::finalized_storage_changes`].
+        storage_changes: Arc<runtime_call::StorageChanges>,
     },
 }
 
@@ -738,6 +1428,32 @@ enum SubtaskFinished {
         source_id: all::SourceId,
         result: Result<network::service::EncodedMerkleProof, ()>,
     },
+    TransactionValidationFinished {
+        watched_transaction_id: u64,
+        scale_encoded_transaction: Vec<u8>,
+        result: Result<validate::ValidTransaction, TransactionValidationError>,
+    },
+    TransactionAnnounceFinished {
+        watched_transaction_id: u64,
+        num_peers: usize,
+    },
+    StorageDownloadFinished,
+}
+
+/// Error while validating a transaction submitted through [`ConsensusService::submit_and_watch`].
+#[derive(Debug, derive_more::Display)]
+enum TransactionValidationError {
+    /// Error while calling the runtime.
+    #[display(fmt = "{_0}")]
+    RuntimeCall(RuntimeCallError),
+    /// Failed to decode the output of the runtime call.
+    OutputDecode,
+    /// The runtime doesn't support version 3 of the `TaggedTransactionQueue` runtime API.
+    #[display(fmt = "Runtime doesn't support version 3 of the TaggedTransactionQueue API")]
+    ApiVersionRequirementUnfulfilled,
+    /// The runtime has reported the transaction as invalid or unknown.
+    #[display(fmt = "{_0}")]
+    Validity(validate::TransactionValidityError),
 }
 
 #[derive(Debug, Clone)]
@@ -842,6 +1558,7 @@ impl SyncBackground {
                                 chain_information::ChainInformationConsensusRef::Aura {
                                     finalized_authorities_list, // TODO: field name not appropriate; should probably change the chain_information module
                                     slot_duration,
+                                    ..
                                 },
                             ) => Some(
                                 block_authoring.insert((
@@ -1235,7 +1952,7 @@ impl SyncBackground {
                         for (number, scale_encoding, parent_hash) in blocks_in {
                             let hash = header::hash_from_scale_encoded_header(&scale_encoding);
                             let runtime = match &self.sync[(number, &hash)] {
-                                NonFinalizedBlock::Verified { runtime } => runtime.clone(),
+                                NonFinalizedBlock::Verified { runtime, .. } => runtime.clone(),
                                 _ => unreachable!(),
                             };
                             let runtime_update = if Arc::ptr_eq(&self.finalized_runtime, &runtime) {
@@ -1289,13 +2006,120 @@ impl SyncBackground {
                         finalized_block_number: self.sync.finalized_block_number(),
                     });
                 }
-                WakeUpReason::FrontendEvent(ToBackground::Unpin { result_tx, .. }) => {
-                    // TODO: check whether block was indeed pinned, and prune blocks that aren't pinned anymore from the database
-                    let _ = result_tx.send(());
+                WakeUpReason::FrontendEvent(ToBackground::GetLeaves { result_tx }) => {
+                    let block_number_bytes = self.sync.block_number_bytes();
+                    let blocks = self
+                        .sync
+                        .non_finalized_blocks_unordered()
+                        .map(|h| (h.hash(block_number_bytes), h.number, *h.parent_hash))
+                        .collect::<Vec<_>>();
+                    let parents = blocks
+                        .iter()
+                        .map(|(_, _, parent_hash)| *parent_hash)
+                        .collect::<HashSet<_, fnv::FnvBuildHasher>>();
+
+                    let mut leaves = blocks
+                        .into_iter()
+                        .filter(|(hash, _, _)| !parents.contains(hash))
+                        .map(|(hash, number, _)| (hash, number))
+                        .collect::<Vec<_>>();
+                    if leaves.is_empty() {
+                        leaves.push((
+                            *self.sync.finalized_block_hash(),
+                            self.sync.finalized_block_number(),
+                        ));
+                    }
+
+                    let best_block_hash = *self.sync.best_block_hash();
+                    leaves.sort_unstable_by(|(hash_a, num_a), (hash_b, num_b)| {
+                        (*hash_b == best_block_hash)
+                            .cmp(&(*hash_a == best_block_hash))
+                            .then(num_b.cmp(num_a))
+                            .then(hash_a.cmp(hash_b))
+                    });
+
+                    let _ = result_tx.send(leaves);
                 }
-                WakeUpReason::FrontendEvent(ToBackground::IsMajorSyncingHint { result_tx }) => {
-                    // As documented, the value returned doesn't need to be precise.
-                    let result = match self.sync.status() {
+                WakeUpReason::FrontendEvent(ToBackground::GetBlockTree {
+                    max_depth,
+                    max_blocks_per_height,
+                    result_tx,
+                }) => {
+                    let block_number_bytes = self.sync.block_number_bytes();
+                    let finalized_block_hash = *self.sync.finalized_block_hash();
+                    let finalized_block_parent_hash =
+                        *header::decode(self.sync.finalized_block_header(), block_number_bytes)
+                            .unwrap()
+                            .parent_hash;
+
+                    let tree = build_block_tree(
+                        (
+                            finalized_block_hash,
+                            finalized_block_parent_hash,
+                            self.sync.finalized_block_number(),
+                        ),
+                        self.sync
+                            .non_finalized_blocks_ancestry_order()
+                            .map(|block| {
+                                (
+                                    block.hash(block_number_bytes),
+                                    *block.parent_hash,
+                                    block.number,
+                                )
+                            })
+                            .collect(),
+                        *self.sync.best_block_hash(),
+                        max_depth,
+                        max_blocks_per_height,
+                    );
+                    let _ = result_tx.send(tree);
+                }
+                WakeUpReason::FrontendEvent(ToBackground::GetBlockStorageChanges {
+                    block_hash,
+                    result_tx,
+                }) => {
+                    let storage_changes = if block_hash == *self.sync.finalized_block_hash() {
+                        self.finalized_storage_changes.as_deref()
+                    } else {
+                        let block_number_bytes = self.sync.block_number_bytes();
+                        self.sync
+                            .non_finalized_blocks_unordered()
+                            .find(|block| block.hash(block_number_bytes) == block_hash)
+                            .map(|block| block.number)
+                            .and_then(|number| match &self.sync[(number, &block_hash)] {
+                                NonFinalizedBlock::Verified {
+                                    storage_changes, ..
+                                } => Some(&**storage_changes),
+                                NonFinalizedBlock::NotVerified => None,
+                            })
+                    };
+
+                    let _ = result_tx.send(storage_changes.map(main_trie_changes_ordered));
+                }
+                WakeUpReason::FrontendEvent(ToBackground::SubscribeImportedBlocks {
+                    result_tx,
+                }) => {
+                    let (tx, rx) = async_channel::bounded(32);
+                    self.imported_blocks_notifications.push(tx);
+                    let _ = result_tx.send(SubscribeImportedBlocks {
+                        best_block_scale_encoded_header: self.sync.best_block_header().to_vec(),
+                        new_blocks: rx,
+                    });
+                }
+                WakeUpReason::FrontendEvent(
+                    ToBackground::SubscribeGrandpaAuthoritySetChanges { result_tx },
+                ) => {
+                    let (tx, rx) = async_channel::bounded(8);
+                    self.grandpa_authority_set_changes_notifications.push(tx);
+                    let _ = result_tx.send(rx);
+                }
+                WakeUpReason::FrontendEvent(ToBackground::Unpin { result_tx, .. }) => {
+                    // TODO: check whether block was indeed pinned, and prune blocks that aren't pinned anymore from the database
+                    let _ = result_tx.send(());
+                }
+                WakeUpReason::FrontendEvent(ToBackground::IsMajorSyncingHint { result_tx }) => {
+                    // As documented, the value returned doesn't need to be precise.
+                    let result = match self.sync.status() {
                         all::Status::Sync => false,
                         all::Status::WarpSyncFragments { .. }
                         | all::Status::WarpSyncChainInformation { .. } => true,
@@ -1303,6 +2127,134 @@ impl SyncBackground {
 
                     let _ = result_tx.send(result);
                 }
+                WakeUpReason::FrontendEvent(ToBackground::SubmitAndWatchTransaction {
+                    scale_encoded_transaction,
+                    hash,
+                    status_tx,
+                }) => {
+                    // Clean up the transactions that are no longer watched, in order to not
+                    // accumulate them.
+                    self.watched_transactions
+                        .retain(|_, watched| !watched.status_tx.is_closed());
+
+                    let watched_transaction_id = self.next_watched_transaction_id;
+                    self.next_watched_transaction_id += 1;
+                    self.watched_transactions.insert(
+                        watched_transaction_id,
+                        WatchedTransaction {
+                            hash,
+                            status_tx,
+                            included_in: Vec::new(),
+                            reported_in_block: None,
+                        },
+                    );
+
+                    // The transaction is validated against the current best block.
+                    let best_block_hash = *self.sync.best_block_hash();
+                    let runtime = if best_block_hash == *self.sync.finalized_block_hash() {
+                        self.finalized_runtime.clone()
+                    } else {
+                        match &self.sync[(self.sync.best_block_number(), &best_block_hash)] {
+                            NonFinalizedBlock::Verified { runtime, .. } => runtime.clone(),
+                            NonFinalizedBlock::NotVerified => unreachable!(),
+                        }
+                    };
+
+                    let database = self.database.clone();
+                    self.sub_tasks.push(Box::pin(async move {
+                        // Only version 3 of the runtime API is supported, as the parameters of
+                        // the runtime call depend on it.
+                        if !runtime
+                            .runtime_version()
+                            .decode()
+                            .apis
+                            .contains("TaggedTransactionQueue", 3..=3)
+                        {
+                            return SubtaskFinished::TransactionValidationFinished {
+                                watched_transaction_id,
+                                scale_encoded_transaction,
+                                result: Err(
+                                    TransactionValidationError::ApiVersionRequirementUnfulfilled,
+                                ),
+                            };
+                        }
+
+                        let parameter = validate::validate_transaction_runtime_parameters_v3(
+                            iter::once(&scale_encoded_transaction),
+                            validate::TransactionSource::External,
+                            &best_block_hash,
+                        )
+                        .fold(Vec::new(), |mut a, b| {
+                            a.extend_from_slice(b.as_ref());
+                            a
+                        });
+
+                        let result = match runtime_call(
+                            &database,
+                            &best_block_hash,
+                            (*runtime).clone(),
+                            validate::VALIDATION_FUNCTION_NAME,
+                            &parameter,
+                            runtime_call::StorageProofSizeBehavior::proof_recording_disabled(),
+                            runtime_call::StorageChanges::empty(),
+                        )
+                        .await
+                        {
+                            Ok(success) => {
+                                match validate::decode_validate_transaction_return_value(
+                                    &success.output,
+                                ) {
+                                    Ok(Ok(valid)) => Ok(valid),
+                                    Ok(Err(error)) => {
+                                        Err(TransactionValidationError::Validity(error))
+                                    }
+                                    Err(_) => Err(TransactionValidationError::OutputDecode),
+                                }
+                            }
+                            Err(error) => Err(TransactionValidationError::RuntimeCall(error)),
+                        };
+
+                        SubtaskFinished::TransactionValidationFinished {
+                            watched_transaction_id,
+                            scale_encoded_transaction,
+                            result,
+                        }
+                    }));
+                }
+                WakeUpReason::FrontendEvent(ToBackground::DownloadFinalizedStorage {
+                    start_key,
+                    result_tx,
+                }) => {
+                    let (tx, rx) = async_channel::bounded(4);
+                    let _ = result_tx.send(rx);
+
+                    let block_hash = *self.sync.finalized_block_hash();
+                    let state_trie_root = *header::decode(
+                        self.sync.finalized_block_header(),
+                        self.sync.block_number_bytes(),
+                    )
+                    .unwrap()
+                    .state_root;
+                    let peers = self
+                        .sync
+                        .sources()
+                        .filter(|s| *s != self.block_author_sync_source)
+                        .filter_map(|source_id| match &self.sync[source_id] {
+                            Some(info) if !info.is_disconnected => Some(info.peer_id.clone()),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+
+                    self.sub_tasks.push(Box::pin(download_storage(
+                        self.network_service.clone(),
+                        self.network_chain_id,
+                        peers,
+                        block_hash,
+                        state_trie_root,
+                        start_key,
+                        tx,
+                    )));
+                }
 
                 WakeUpReason::NetworkLocalChainUpdate => {
                     self.network_service
@@ -2147,6 +3099,99 @@ impl SyncBackground {
                     process_sync = true;
                 }
 
+                WakeUpReason::SubtaskFinished(SubtaskFinished::TransactionValidationFinished {
+                    watched_transaction_id,
+                    scale_encoded_transaction,
+                    result,
+                }) => {
+                    let Some(watched) = self.watched_transactions.get(&watched_transaction_id)
+                    else {
+                        continue;
+                    };
+
+                    // The transaction might have stopped being watched while it was being
+                    // validated, in which case it must not be sent to peers.
+                    if watched.status_tx.is_closed() {
+                        self.log_callback.log(
+                            LogLevel::Debug,
+                            format!("transaction-unwatched; hash={}", HashDisplay(&watched.hash)),
+                        );
+                        self.watched_transactions.remove(&watched_transaction_id);
+                        continue;
+                    }
+
+                    self.log_callback.log(
+                        LogLevel::Debug,
+                        format!(
+                            "transaction-validation; hash={}; outcome={}",
+                            HashDisplay(&watched.hash),
+                            match &result {
+                                Ok(_) => "valid".to_owned(),
+                                Err(error) => error.to_string(),
+                            }
+                        ),
+                    );
+
+                    match result {
+                        Ok(_) => {
+                            self.report_transaction_status(
+                                watched_transaction_id,
+                                TxStatus::Validated,
+                            );
+                            if !self
+                                .watched_transactions
+                                .contains_key(&watched_transaction_id)
+                            {
+                                continue;
+                            }
+
+                            let network_service = self.network_service.clone();
+                            let network_chain_id = self.network_chain_id;
+                            self.sub_tasks.push(Box::pin(async move {
+                                let peers = network_service
+                                    .announce_transaction(
+                                        network_chain_id,
+                                        scale_encoded_transaction,
+                                    )
+                                    .await;
+                                SubtaskFinished::TransactionAnnounceFinished {
+                                    watched_transaction_id,
+                                    num_peers: peers.len(),
+                                }
+                            }));
+                        }
+                        Err(TransactionValidationError::Validity(
+                            validate::TransactionValidityError::Invalid(error),
+                        )) => {
+                            self.report_transaction_status(
+                                watched_transaction_id,
+                                TxStatus::Invalid(error),
+                            );
+                        }
+                        Err(_) => {
+                            self.report_transaction_status(
+                                watched_transaction_id,
+                                TxStatus::Dropped,
+                            );
+                        }
+                    }
+                }
+
+                WakeUpReason::SubtaskFinished(SubtaskFinished::TransactionAnnounceFinished {
+                    watched_transaction_id,
+                    num_peers,
+                }) => {
+                    // TODO: try again later if no peer was available
+                    if num_peers != 0 {
+                        self.report_transaction_status(
+                            watched_transaction_id,
+                            TxStatus::Broadcasted,
+                        );
+                    }
+                }
+
+                WakeUpReason::SubtaskFinished(SubtaskFinished::StorageDownloadFinished) => {}
+
                 WakeUpReason::SyncProcess => {
                     // Given that processing blocks might generate a notification, and that
                     // only one notification can be queued at a time, this path must never be
@@ -2171,6 +3216,69 @@ impl SyncBackground {
         }
     }
 
+    /// Sends a status update to an entry of [`SyncBackground::watched_transactions`].
+    ///
+    /// The entry is removed if the status is final or if the channel is full or closed. Does
+    /// nothing if the entry no longer exists.
+    fn report_transaction_status(&mut self, watched_transaction_id: u64, status: TxStatus) {
+        let Some(watched) = self.watched_transactions.get(&watched_transaction_id) else {
+            return;
+        };
+
+        let is_final = matches!(
+            status,
+            TxStatus::Finalized(_) | TxStatus::Dropped | TxStatus::Invalid(_)
+        );
+        if watched.status_tx.try_send(status).is_err() || is_final {
+            self.watched_transactions.remove(&watched_transaction_id);
+        }
+    }
+
+    /// Compares the best chain with the blocks reported for each entry of
+    /// [`SyncBackground::watched_transactions`], and reports [`TxStatus::Retracted`] and
+    /// [`TxStatus::InBlock`] where the best chain has changed.
+    ///
+    /// Must be called after the best block has potentially changed.
+    fn update_watched_transactions_best_chain(&mut self) {
+        if self.watched_transactions.is_empty() {
+            return;
+        }
+
+        // Set of the hashes of the non-finalized blocks of the best chain.
+        let best_chain = {
+            let block_number_bytes = self.sync.block_number_bytes();
+            let parents = self
+                .sync
+                .non_finalized_blocks_ancestry_order()
+                .map(|h| (h.hash(block_number_bytes), *h.parent_hash))
+                .collect::<hashbrown::HashMap<_, _, fnv::FnvBuildHasher>>();
+            let mut best_chain = HashSet::<_, fnv::FnvBuildHasher>::default();
+            let mut iter = *self.sync.best_block_hash();
+            while let Some(parent) = parents.get(&iter) {
+                best_chain.insert(iter);
+                iter = *parent;
+            }
+            best_chain
+        };
+
+        self.watched_transactions
+            .retain(|_, watched| watched.update_best_chain(&best_chain));
+    }
+
+    /// Updates [`SyncBackground::watched_transactions`] after some blocks have been finalized
+    /// and some others pruned, and reports [`TxStatus::Finalized`] where appropriate.
+    fn update_watched_transactions_finalized(
+        &mut self,
+        finalized_blocks_hashes: &[[u8; 32]],
+        pruned_blocks_hashes: &[[u8; 32]],
+    ) {
+        self.watched_transactions.retain(|_, watched| {
+            watched.update_finalized(finalized_blocks_hashes, pruned_blocks_hashes)
+        });
+
+        self.update_watched_transactions_best_chain();
+    }
+
     /// Authors a block, then imports it and gossips it out.
     ///
     /// # Panic
@@ -2230,6 +3338,7 @@ impl SyncBackground {
                 if self.sync.best_block_number() != self.sync.finalized_block_number() {
                     let NonFinalizedBlock::Verified {
                         runtime: parent_runtime_arc,
+                        ..
                     } = &self.sync[(self.sync.best_block_number(), self.sync.best_block_hash())]
                     else {
                         unreachable!()
@@ -2583,8 +3692,31 @@ impl SyncBackground {
                 // Destory all existing subscriptions due to the gap in the chain.
                 self.pending_notification = None;
                 self.blocks_notifications.clear();
+                self.imported_blocks_notifications.clear();
+                self.grandpa_authority_set_changes_notifications.clear();
+                self.finalized_grandpa_set_id = grandpa_set_id(&self.sync);
+                for (_, watched) in self.watched_transactions.drain() {
+                    let _ = watched.status_tx.try_send(TxStatus::Dropped);
+                }
 
-                self.finalized_runtime = Arc::new(finalized_block_runtime);
+                self.finalized_runtime = Arc::new(
+                    match finalized_code_substitute_runtime(
+                        &self.code_substitutes,
+                        self.sync.finalized_block_number(),
+                        &finalized_block_runtime,
+                    ) {
+                        Some(Ok(substitute)) => substitute,
+                        Some(Err(error)) => {
+                            self.log_callback.log(
+                                LogLevel::Warn,
+                                format!("invalid-code-substitute; error={error}"),
+                            );
+                            finalized_block_runtime
+                        }
+                        None => finalized_block_runtime,
+                    },
+                );
+                self.finalized_storage_changes = None;
                 let finalized_block_header = self
                     .sync
                     .as_chain_information()
@@ -2620,9 +3752,9 @@ impl SyncBackground {
                 unreachable!()
             }
             all::ProcessOne::VerifyBlock(verify) => {
-                // TODO: ban peer in case of verification failure
                 let when_verification_started = Instant::now();
                 let hash_to_verify = verify.hash();
+                let height_to_verify = verify.height();
 
                 let _jaeger_span = self.jaeger_service.block_verify_span(&hash_to_verify);
 
@@ -2644,13 +3776,35 @@ impl SyncBackground {
                                     error
                                 ),
                             );
+
+                            // Only the peers that have sent a block that is provably invalid
+                            // are reported, as other errors might be caused by the local node.
+                            if header_verify_error_is_provable(&error) {
+                                let culprits = sync
+                                    .knows_non_finalized_block(height_to_verify, &hash_to_verify)
+                                    .filter_map(|source_id| {
+                                        sync[source_id].as_ref().map(|s| s.peer_id.clone())
+                                    })
+                                    .collect::<Vec<_>>();
+                                for peer_id in culprits {
+                                    self.network_service
+                                        .report_peer(
+                                            peer_id,
+                                            self.network_chain_id,
+                                            INVALID_BLOCK_REPUTATION_CHANGE,
+                                            "invalid-block-header",
+                                        )
+                                        .await;
+                                }
+                            }
+
                             self.sync = sync;
                             return (self, true);
                         }
                     };
 
                 let parent_info = header_verification_success.parent_user_data().map(|b| {
-                    let NonFinalizedBlock::Verified { runtime } = b else {
+                    let NonFinalizedBlock::Verified { runtime, .. } = b else {
                         unreachable!()
                     };
                     runtime.clone()
@@ -2666,9 +3820,46 @@ impl SyncBackground {
                 let scale_encoded_header =
                     header_verification_success.scale_encoded_header().to_vec();
 
+                // If the chain specification or the configuration provides a code substitute for
+                // this block, execute the block with it rather than with the parent's runtime.
+                // The substitute is then passed down to the children of this block, until one of
+                // them modifies the runtime.
+                let substitute_runtime = match code_substitute_runtime(
+                    &self.code_substitutes,
+                    height,
+                    &parent_runtime_arc,
+                ) {
+                    Some(Ok(runtime)) => {
+                        self.log_callback.log(
+                            LogLevel::Debug,
+                            format!(
+                                "block-verification-code-substitute; hash={}; height={}",
+                                HashDisplay(&hash_to_verify),
+                                height
+                            ),
+                        );
+                        Some(Arc::new(runtime))
+                    }
+                    Some(Err(error)) => {
+                        self.log_callback.log(
+                            LogLevel::Warn,
+                            format!(
+                                "block-verification-invalid-code-substitute; hash={}; \
+                                height={}; error={error}",
+                                HashDisplay(&hash_to_verify),
+                                height
+                            ),
+                        );
+                        None
+                    }
+                    None => None,
+                };
+                let execution_runtime =
+                    (**substitute_runtime.as_ref().unwrap_or(&parent_runtime_arc)).clone();
+
                 let execute_block_success = match execute_block_and_insert(
                     &self.database,
-                    (*parent_runtime_arc).clone(),
+                    execution_runtime,
                     &header_verification_success.parent_hash(),
                     header_verification_success.scale_encoded_header(),
                     block_number_bytes,
@@ -2794,12 +3985,38 @@ impl SyncBackground {
                         runtime_update: execute_block_success
                             .new_runtime
                             .as_ref()
-                            .map(|new_runtime| Arc::new(new_runtime.clone())),
+                            .map(|new_runtime| Arc::new(new_runtime.clone()))
+                            .or_else(|| substitute_runtime.clone()),
                         parent_hash: *header_verification_success.parent_hash(),
                     },
                     storage_changes: execute_block_success.storage_changes.clone(),
                 });
 
+                report_imported_block(
+                    &mut self.imported_blocks_notifications,
+                    ImportedBlock {
+                        hash: hash_to_verify,
+                        number: height,
+                        scale_encoded_header: scale_encoded_header.clone(),
+                        is_new_best,
+                    },
+                );
+
+                // Keep track of the watched transactions that this block includes.
+                if !self.watched_transactions.is_empty() {
+                    for extrinsic in header_verification_success
+                        .scale_encoded_extrinsics()
+                        .unwrap()
+                    {
+                        let extrinsic_hash = transaction_hash(extrinsic.as_ref());
+                        for watched in self.watched_transactions.values_mut() {
+                            if watched.hash == extrinsic_hash {
+                                watched.included_in.push(hash_to_verify);
+                            }
+                        }
+                    }
+                }
+
                 // Processing has made a step forward.
 
                 self.sync = header_verification_success.finish(NonFinalizedBlock::NotVerified);
@@ -2808,11 +4025,16 @@ impl SyncBackground {
                 self.sync[(height, &hash_to_verify)] = NonFinalizedBlock::Verified {
                     runtime: if let Some(new_runtime) = execute_block_success.new_runtime {
                         Arc::new(new_runtime)
+                    } else if let Some(substitute_runtime) = substitute_runtime {
+                        substitute_runtime
                     } else {
                         parent_runtime_arc
                     },
+                    storage_changes: execute_block_success.storage_changes,
                 };
 
+                self.update_watched_transactions_best_chain();
+
                 if is_new_best {
                     // Update the networking.
                     self.network_local_chain_update_needed = true;
@@ -2837,6 +4059,18 @@ impl SyncBackground {
                     .map(|s| s.peer_id.clone())
                     .unwrap();
 
+                // Grandpa justification being verified, if any, in case it needs to be reported
+                // to the authority set changes subscribers.
+                let grandpa_justification =
+                    if self.grandpa_authority_set_changes_notifications.is_empty() {
+                        None
+                    } else {
+                        verify
+                            .justification()
+                            .filter(|(engine_id, _)| *engine_id == *b"FRNK")
+                            .map(|(_, justification)| justification.to_vec())
+                    };
+
                 match verify.perform(rand::random()) {
                     (
                         sync_out,
@@ -2868,25 +4102,74 @@ impl SyncBackground {
                             self.block_authoring = None;
                         }
 
-                        self.finalized_runtime =
+                        (self.finalized_runtime, self.finalized_storage_changes) =
                             match &finalized_blocks_newest_to_oldest.first().unwrap().user_data {
-                                NonFinalizedBlock::Verified { runtime } => runtime.clone(),
+                                NonFinalizedBlock::Verified {
+                                    runtime,
+                                    storage_changes,
+                                } => (runtime.clone(), Some(storage_changes.clone())),
                                 _ => unreachable!(),
                             };
                         // TODO: what if best block changed?
+                        let blocks_pruning = self.blocks_pruning;
                         self.database
                             .with_database_detached(move |database| {
                                 database.set_finalized(&new_finalized_hash).unwrap();
+                                if let Some(num_blocks_kept) = blocks_pruning {
+                                    database.prune_finalized_storage(num_blocks_kept).unwrap();
+                                }
                             })
                             .await;
 
+                        let finalized_blocks_hashes = finalized_blocks_newest_to_oldest
+                            .iter()
+                            .map(|b| b.block_hash)
+                            .collect::<Vec<_>>();
+                        self.update_watched_transactions_finalized(
+                            &finalized_blocks_hashes,
+                            &pruned_blocks,
+                        );
+
+                        // Report the change of authority set, if any.
+                        let new_grandpa_set_id = grandpa_set_id(&self.sync);
+                        if new_grandpa_set_id != self.finalized_grandpa_set_id {
+                            self.finalized_grandpa_set_id = new_grandpa_set_id;
+                            self.grandpa_authority_set_changes_notifications
+                                .retain(|s| !s.is_closed());
+                            if let (Some(new_set_id), Some(scale_encoded_justification), false) = (
+                                new_grandpa_set_id,
+                                grandpa_justification,
+                                self.grandpa_authority_set_changes_notifications.is_empty(),
+                            ) {
+                                // Building the proof requires accessing the database. It is
+                                // done in the database thread, after the block has been
+                                // marked as finalized, in order to not block the syncing.
+                                let senders =
+                                    self.grandpa_authority_set_changes_notifications.clone();
+                                let scale_encoded_header = finalized_blocks_newest_to_oldest
+                                    .first()
+                                    .unwrap()
+                                    .header
+                                    .clone();
+                                self.database
+                                    .with_database_detached(move |database| {
+                                        report_grandpa_authority_set_change(
+                                            database,
+                                            &senders,
+                                            new_finalized_hash,
+                                            scale_encoded_header,
+                                            scale_encoded_justification,
+                                            new_set_id,
+                                        )
+                                    })
+                                    .await;
+                            }
+                        }
+
                         // Notify the subscribers.
                         debug_assert!(self.pending_notification.is_none());
                         self.pending_notification = Some(Notification::Finalized {
-                            finalized_blocks_newest_to_oldest: finalized_blocks_newest_to_oldest
-                                .iter()
-                                .map(|b| b.block_hash)
-                                .collect::<Vec<_>>(),
+                            finalized_blocks_newest_to_oldest: finalized_blocks_hashes,
                             pruned_blocks_hashes: pruned_blocks.clone(),
                             best_block_hash: *self.sync.best_block_hash(),
                         });
@@ -2962,6 +4245,634 @@ impl SyncBackground {
     }
 }
 
+/// Builds the proof of the authority set of the given block, then sends a
+/// [`GrandpaAuthoritySetChange`] to each of the given senders.
+///
+/// Senders whose channel is full are closed, as documented in
+/// [`ConsensusService::subscribe_grandpa_authority_set_changes`].
+fn report_grandpa_authority_set_change(
+    database: &full_sqlite::SqliteFullDatabase,
+    senders: &[async_channel::Sender<GrandpaAuthoritySetChange>],
+    block_hash: [u8; 32],
+    scale_encoded_header: Vec<u8>,
+    scale_encoded_justification: Vec<u8>,
+    new_set_id: u64,
+) {
+    let authority_set_proof = database
+        .block_storage_proof(&block_hash, GRANDPA_AUTHORITY_SET_KEYS.iter())
+        .ok();
+
+    let notification = GrandpaAuthoritySetChange {
+        block_hash,
+        scale_encoded_header,
+        scale_encoded_justification,
+        new_set_id,
+        authority_set_proof,
+    };
+
+    for sender in senders {
+        if sender.try_send(notification.clone()).is_err() {
+            sender.close();
+        }
+    }
+}
+
+/// Sends the given [`ImportedBlock`] to each of the given senders.
+///
+/// Senders whose channel is full or closed are removed from the list, which closes their
+/// channel, as documented in [`SubscribeImportedBlocks::new_blocks`].
+fn report_imported_block(
+    senders: &mut Vec<async_channel::Sender<ImportedBlock>>,
+    imported_block: ImportedBlock,
+) {
+    senders.retain(|sender| sender.try_send(imported_block.clone()).is_ok());
+}
+
+/// Returns the Grandpa authority set id after the finalized block of the given sync state
+/// machine, or `None` if the chain doesn't use Grandpa.
+fn grandpa_set_id<TRq, TSrc, TBl>(sync: &all::AllSync<TRq, TSrc, TBl>) -> Option<u64> {
+    match sync.as_chain_information().as_ref().finality {
+        chain_information::ChainInformationFinalityRef::Grandpa {
+            after_finalized_block_authorities_set_id,
+            ..
+        } => Some(after_finalized_block_authorities_set_id),
+        chain_information::ChainInformationFinalityRef::Outsourced => None,
+    }
+}
+
+/// Downloads the storage of the given block from the given peers, and sends the result on
+/// `results_tx`. See [`ConsensusService::download_finalized_storage`].
+///
+/// The trie is walked in lexicographic order through a [`StorageDownload`]. One chunk is sent
+/// for each storage proof that makes the download progress.
+async fn download_storage(
+    network_service: Arc<network_service::NetworkService>,
+    network_chain_id: network_service::ChainId,
+    mut peers: Vec<libp2p::PeerId>,
+    block_hash: [u8; 32],
+    state_trie_root: [u8; 32],
+    start_key: Vec<u8>,
+    results_tx: async_channel::Sender<Result<StorageDownloadChunk, StorageDownloadError>>,
+) -> SubtaskFinished {
+    let no_peer_from_the_start = peers.is_empty();
+    let mut download = StorageDownload::new(state_trie_root, start_key);
+
+    while let Some(resume_key) = download.resume_key() {
+        let keys = download.requested_keys().collect::<Vec<_>>();
+
+        // Try the peers in a random order until one of them sends back a proof that makes the
+        // download progress. Peers that fail are no longer used for this download.
+        let entries = loop {
+            if peers.is_empty() {
+                let _ = results_tx
+                    .send(Err(StorageDownloadError {
+                        resume_key,
+                        detail: if no_peer_from_the_start {
+                            StorageDownloadErrorDetail::NoPeer
+                        } else {
+                            StorageDownloadErrorDetail::AllPeersFailed
+                        },
+                    }))
+                    .await;
+                return SubtaskFinished::StorageDownloadFinished;
+            }
+
+            let peer_index = rand::Rng::gen_range(&mut rand::thread_rng(), 0..peers.len());
+            let Ok(proof) = network_service
+                .clone()
+                .storage_request(
+                    peers[peer_index].clone(),
+                    network_chain_id,
+                    network::codec::StorageProofRequestConfig {
+                        block_hash,
+                        keys: keys.iter(),
+                    },
+                )
+                .await
+            else {
+                peers.swap_remove(peer_index);
+                continue;
+            };
+
+            match download.inject_proof(proof.decode()) {
+                Some(entries) => break entries,
+                None => {
+                    peers.swap_remove(peer_index);
+                }
+            }
+        };
+
+        let chunk = StorageDownloadChunk {
+            block_hash,
+            entries,
+            next_start_key: download.resume_key(),
+        };
+
+        if results_tx.send(Ok(chunk)).await.is_err() {
+            break;
+        }
+    }
+
+    SubtaskFinished::StorageDownloadFinished
+}
+
+/// Walk of the main trie of a block, in lexicographic order, through storage proofs.
+///
+/// The walk keeps a list of items still to be yielded, ordered by key. Each item is either a
+/// verified storage entry or the nibbles of a trie node whose content isn't known yet. Each
+/// proof expands the nodes that it contains, and the verified entries that precede the first
+/// unknown node are then yielded.
+struct StorageDownload {
+    /// State trie root of the block whose storage is being downloaded.
+    state_trie_root: [u8; 32],
+
+    /// Nibbles of the key passed to [`StorageDownload::new`]. Entries whose key is strictly
+    /// inferior to this one are ignored.
+    start_key: Vec<trie::Nibble>,
+
+    /// Items still to be yielded, in reverse lexicographic order. The last item is the next one
+    /// to be yielded.
+    pending: Vec<StorageDownloadItem>,
+}
+
+/// See [`StorageDownload::pending`].
+enum StorageDownloadItem {
+    /// Storage entry whose value has been verified.
+    Entry(Vec<u8>, Vec<u8>),
+    /// All the keys that start with these nibbles are still to be downloaded.
+    Node(Vec<trie::Nibble>),
+}
+
+impl StorageDownload {
+    /// Maximum number of keys to request in a single storage proof, in order to keep the size
+    /// of the proofs reasonable.
+    const MAX_KEYS_PER_REQUEST: usize = 64;
+
+    /// Starts a new walk of the trie with the given root. Only entries whose key is superior or
+    /// equal to `start_key` are yielded.
+    fn new(state_trie_root: [u8; 32], start_key: Vec<u8>) -> Self {
+        StorageDownload {
+            state_trie_root,
+            start_key: trie::bytes_to_nibbles(start_key.into_iter()).collect(),
+            pending: vec![StorageDownloadItem::Node(Vec::new())],
+        }
+    }
+
+    /// Returns the key to start the rest of the download at, or `None` if the download is
+    /// finished. All the keys strictly inferior to this key have been yielded.
+    fn resume_key(&self) -> Option<Vec<u8>> {
+        let nibbles = match self.pending.last()? {
+            StorageDownloadItem::Entry(key, _) => return Some(key.clone()),
+            // Keys that start with the nibbles of the start key but are inferior to it are
+            // never yielded, hence the `max`.
+            StorageDownloadItem::Node(nibbles) => cmp::max(nibbles, &self.start_key),
+        };
+
+        Some(trie::nibbles_to_bytes_suffix_extend(nibbles.iter().copied()).collect())
+    }
+
+    /// Returns the keys whose storage proof must be requested.
+    fn requested_keys(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.pending
+            .iter()
+            .rev()
+            .filter_map(|item| match item {
+                StorageDownloadItem::Entry(..) => None,
+                StorageDownloadItem::Node(nibbles) => Some(
+                    trie::nibbles_to_bytes_suffix_extend(nibbles.iter().copied())
+                        .collect::<Vec<_>>(),
+                ),
+            })
+            .take(Self::MAX_KEYS_PER_REQUEST)
+    }
+
+    /// Injects a storage proof of the keys returned by [`StorageDownload::requested_keys`], and
+    /// returns the entries that can now be yielded, ordered by key.
+    ///
+    /// Returns `None` if the proof is invalid or doesn't make the download progress, in which
+    /// case `self` isn't modified.
+    fn inject_proof(&mut self, proof: &[u8]) -> Option<Vec<(Vec<u8>, Vec<u8>)>> {
+        let decoded_proof =
+            trie::proof_decode::decode_and_verify_proof(trie::proof_decode::Config { proof })
+                .ok()?;
+
+        let mut progress = false;
+        let mut to_process = mem::take(&mut self.pending);
+        let mut processed = Vec::with_capacity(to_process.len());
+
+        while let Some(item) = to_process.pop() {
+            let StorageDownloadItem::Node(nibbles) = item else {
+                processed.push(item);
+                continue;
+            };
+
+            let Ok(info) =
+                decoded_proof.trie_node_info(&self.state_trie_root, nibbles.iter().copied())
+            else {
+                processed.push(StorageDownloadItem::Node(nibbles));
+                continue;
+            };
+
+            let storage_value = match info.storage_value {
+                trie::proof_decode::StorageValue::Known { value, .. } => Some(value.to_vec()),
+                trie::proof_decode::StorageValue::None => None,
+                trie::proof_decode::StorageValue::HashKnownValueMissing(_) => {
+                    processed.push(StorageDownloadItem::Node(nibbles));
+                    continue;
+                }
+            };
+
+            progress = true;
+
+            // Push the children then the entry of the node itself, so that they are popped in
+            // lexicographic order. Children whose keys are all inferior to the start key are
+            // skipped.
+            for (child_index, child) in info.children.children().enumerate().rev() {
+                let child_nibbles = match child {
+                    trie::proof_decode::Child::NoChild => continue,
+                    trie::proof_decode::Child::InProof { child_key, .. } => {
+                        child_key.collect::<Vec<_>>()
+                    }
+                    trie::proof_decode::Child::AbsentFromProof { .. } => {
+                        let mut child_nibbles = nibbles.clone();
+                        child_nibbles.push(
+                            trie::Nibble::try_from(u8::try_from(child_index).unwrap()).unwrap(),
+                        );
+                        child_nibbles
+                    }
+                };
+
+                if child_nibbles < self.start_key && !self.start_key.starts_with(&child_nibbles) {
+                    continue;
+                }
+
+                to_process.push(StorageDownloadItem::Node(child_nibbles));
+            }
+
+            if let Some(storage_value) = storage_value {
+                // Trie nodes with a value are always aligned to bytes.
+                debug_assert_eq!(nibbles.len() % 2, 0);
+                if nibbles >= self.start_key {
+                    let key = trie::nibbles_to_bytes_suffix_extend(nibbles.into_iter()).collect();
+                    to_process.push(StorageDownloadItem::Entry(key, storage_value));
+                }
+            }
+        }
+
+        // `processed` is in lexicographic order.
+        if !progress {
+            processed.reverse();
+            self.pending = processed;
+            return None;
+        }
+
+        let num_ready = processed
+            .iter()
+            .take_while(|item| matches!(item, StorageDownloadItem::Entry(..)))
+            .count();
+        let mut pending = processed.split_off(num_ready);
+        pending.reverse();
+        self.pending = pending;
+
+        Some(
+            processed
+                .into_iter()
+                .map(|item| match item {
+                    StorageDownloadItem::Entry(key, value) => (key, value),
+                    StorageDownloadItem::Node(_) => unreachable!(),
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Builds the [`BlockTree`] returned by [`ConsensusService::block_tree`].
+///
+/// `finalized_block` and `non_finalized_blocks` contain the hash, parent hash, and number of
+/// the blocks. `non_finalized_blocks` must be in an order where parents come before their
+/// children.
+fn build_block_tree(
+    finalized_block: ([u8; 32], [u8; 32], u64),
+    non_finalized_blocks: Vec<([u8; 32], [u8; 32], u64)>,
+    best_block_hash: [u8; 32],
+    max_depth: u64,
+    max_blocks_per_height: usize,
+) -> BlockTree {
+    let (finalized_block_hash, finalized_block_parent_hash, finalized_block_number) =
+        finalized_block;
+
+    // Set of the hashes of the non-finalized blocks of the best chain.
+    let best_chain = {
+        let parents = non_finalized_blocks
+            .iter()
+            .map(|(hash, parent_hash, _)| (*hash, *parent_hash))
+            .collect::<hashbrown::HashMap<_, _, fnv::FnvBuildHasher>>();
+        let mut best_chain = HashSet::<_, fnv::FnvBuildHasher>::default();
+        let mut iter = best_block_hash;
+        while let Some(parent) = parents.get(&iter) {
+            best_chain.insert(iter);
+            iter = *parent;
+        }
+        best_chain
+    };
+
+    let mut blocks = vec![BlockTreeEntry {
+        hash: finalized_block_hash,
+        parent_hash: finalized_block_parent_hash,
+        number: finalized_block_number,
+        is_best: finalized_block_hash == best_block_hash,
+        is_finalized: true,
+    }];
+    let mut truncated = false;
+
+    // The best chain is always included, and takes precedence over the other blocks in the
+    // limits.
+    let mut included = HashSet::with_capacity_and_hasher(
+        non_finalized_blocks.len() + 1,
+        fnv::FnvBuildHasher::default(),
+    );
+    included.insert(finalized_block_hash);
+    let mut blocks_per_height = hashbrown::HashMap::<u64, usize, fnv::FnvBuildHasher>::default();
+
+    for &(hash, parent_hash, number) in &non_finalized_blocks {
+        if !best_chain.contains(&hash) {
+            continue;
+        }
+
+        *blocks_per_height.entry(number).or_insert(0) += 1;
+        included.insert(hash);
+        blocks.push(BlockTreeEntry {
+            hash,
+            parent_hash,
+            number,
+            is_best: hash == best_block_hash,
+            is_finalized: false,
+        });
+    }
+
+    // Since the blocks are in ancestry order, the parent of each block has already been either
+    // included or omitted.
+    for &(hash, parent_hash, number) in &non_finalized_blocks {
+        if best_chain.contains(&hash) {
+            continue;
+        }
+
+        let blocks_at_height = blocks_per_height.entry(number).or_insert(0);
+        if number - finalized_block_number > max_depth
+            || *blocks_at_height >= max_blocks_per_height
+            || !included.contains(&parent_hash)
+        {
+            truncated = true;
+            continue;
+        }
+
+        *blocks_at_height += 1;
+        included.insert(hash);
+        blocks.push(BlockTreeEntry {
+            hash,
+            parent_hash,
+            number,
+            is_best: false,
+            is_finalized: false,
+        });
+    }
+
+    BlockTree { blocks, truncated }
+}
+
+/// Returns `true` if the given error proves that the header is invalid, in which case the peers
+/// that have sent it can be blamed.
+///
+/// Errors that might instead be caused by the local clock, the local state of the chain, or the
+/// configuration of the chain return `false`.
+fn header_verify_error_is_provable(error: &all::HeaderVerifyError) -> bool {
+    match error {
+        all::HeaderVerifyError::UnknownConsensusEngine
+        | all::HeaderVerifyError::ConsensusMismatch => false,
+        all::HeaderVerifyError::VerificationFailed(error) => match error {
+            header_only::Error::NonSequentialBlockNumber
+            | header_only::Error::MultipleConsensusEngines
+            | header_only::Error::GrandpaChangesOverlap => true,
+            header_only::Error::BadParentHash
+            | header_only::Error::UnknownConsensusEngine { .. }
+            | header_only::Error::FinalityEngineMismatch => false,
+            header_only::Error::AuraVerification(error) => match error {
+                aura::VerifyError::MissingSeal
+                | aura::VerifyError::MissingPreRuntimeDigest
+                | aura::VerifyError::SlotNumberNotIncreasing
+                | aura::VerifyError::BadSignature
+                | aura::VerifyError::BadPublicKey
+                | aura::VerifyError::MultipleAuthoritiesChangeDigestItems => true,
+                aura::VerifyError::ParentIsntAuraConsensus
+                | aura::VerifyError::TooFarInFuture
+                | aura::VerifyError::EmptyAuthorities => false,
+            },
+            header_only::Error::BabeVerification(error) => match error {
+                babe::VerifyError::MissingSeal
+                | babe::VerifyError::MissingPreRuntimeDigest
+                | babe::VerifyError::SlotNumberNotIncreasing
+                | babe::VerifyError::UnexpectedEpochChangeLog
+                | babe::VerifyError::MissingEpochChangeLog
+                | babe::VerifyError::InvalidBabeParametersChange(_)
+                | babe::VerifyError::InvalidAuthorityIndex
+                | babe::VerifyError::BadPublicKey
+                | babe::VerifyError::BadSignature
+                | babe::VerifyError::BadVrfProof
+                | babe::VerifyError::BadSecondarySlotAuthor
+                | babe::VerifyError::OverPrimaryClaimThreshold
+                | babe::VerifyError::ForbiddenSlotType => true,
+                babe::VerifyError::ParentIsntBabeConsensus
+                | babe::VerifyError::NextEpochStartSlotNumberOverflow
+                | babe::VerifyError::EpochIndexOverflow
+                | babe::VerifyError::InvalidChainConfiguration(_) => false,
+            },
+        },
+    }
+}
+
+/// Builds the runtime to use in order to execute the block of the given height, in case
+/// `code_substitutes` contains an entry for this height. Returns `None` if the block must be
+/// executed with the runtime of its parent.
+///
+/// The substitute runtime uses the same number of heap pages as the runtime of the parent.
+fn code_substitute_runtime(
+    code_substitutes: &BTreeMap<u64, Vec<u8>>,
+    block_number: u64,
+    parent_runtime: &host::HostVmPrototype,
+) -> Option<Result<host::HostVmPrototype, host::NewErr>> {
+    let code = code_substitutes.get(&block_number)?;
+    Some(host::HostVmPrototype::new(host::Config {
+        module: code,
+        heap_pages: parent_runtime.heap_pages(),
+        exec_hint: executor::vm::ExecHint::ValidateAndCompile,
+        allow_unresolved_imports: false,
+    }))
+}
+
+/// Builds the runtime to use for the given finalized block, in case `code_substitutes` contains
+/// a substitute that applies to this block. Returns `None` if the on-chain runtime must be used.
+///
+/// The latest substitute at or below the finalized block applies if the runtime has not been
+/// modified on chain since. As the finalized runtime doesn't keep track of the block where it
+/// was modified, a substitute is assumed to still apply if its `spec_version` is the same as the
+/// one of the on-chain runtime.
+fn finalized_code_substitute_runtime(
+    code_substitutes: &BTreeMap<u64, Vec<u8>>,
+    finalized_block_number: u64,
+    finalized_runtime: &host::HostVmPrototype,
+) -> Option<Result<host::HostVmPrototype, host::NewErr>> {
+    let (substitute_block_number, _) = code_substitutes
+        .range(..=finalized_block_number)
+        .next_back()?;
+    match code_substitute_runtime(
+        code_substitutes,
+        *substitute_block_number,
+        finalized_runtime,
+    )? {
+        Ok(substitute)
+            if substitute.runtime_version().decode().spec_version
+                != finalized_runtime.runtime_version().decode().spec_version =>
+        {
+            None
+        }
+        result => Some(result),
+    }
+}
+
+/// Returns the hash of the given SCALE-encoded transaction or extrinsic, as reported by
+/// [`ConsensusService::submit_and_watch`].
+///
+/// Like the hash of block headers, it is always calculated with Blake2-256, as the chains that
+/// use a different hashing algorithm aren't supported.
+pub fn transaction_hash(scale_encoded_transaction: &[u8]) -> [u8; 32] {
+    <[u8; 32]>::try_from(
+        blake2_rfc::blake2b::blake2b(32, &[], scale_encoded_transaction).as_bytes(),
+    )
+    .unwrap()
+}
+
+/// Returns the list of changes to the main trie found in `storage_changes`, ordered by key.
+fn main_trie_changes_ordered(storage_changes: &runtime_call::StorageChanges) -> StorageChangesList {
+    let mut changes = storage_changes
+        .main_trie_storage_changes_iter_unordered()
+        .map(|(key, value)| (key.to_vec(), value.map(|v| v.to_vec())))
+        .collect::<Vec<_>>();
+    changes.sort_unstable_by(|(key_a, _), (key_b, _)| key_a.cmp(key_b));
+    changes
+}
+
+/// Calculates the changes to the main trie performed by the given block by comparing its
+/// storage with the storage of its parent in the database. The changes are ordered by key.
+///
+/// Returns `Ok(None)` if the given block is the genesis block.
+///
+/// Sub-tries whose Merkle value is identical in both blocks are skipped, meaning that the cost
+/// of this function is proportional to the number of changes rather than to the size of the
+/// storage.
+fn database_storage_changes(
+    database: &full_sqlite::SqliteFullDatabase,
+    block_hash: &[u8; 32],
+) -> Result<Option<StorageChangesList>, full_sqlite::StorageAccessError> {
+    let parent_hash = match database.block_parent(block_hash) {
+        Ok(Some(parent_hash)) if parent_hash != [0; 32] => parent_hash,
+        Ok(Some(_)) => return Ok(None),
+        Ok(None) => return Err(full_sqlite::StorageAccessError::UnknownBlock),
+        Err(err) => return Err(full_sqlite::StorageAccessError::Corrupted(err)),
+    };
+
+    let mut changes = Vec::new();
+
+    // Prefixes, in nibbles, that remain to be compared. Prefixes are pushed in reverse order in
+    // order for the changes to be found ordered by key.
+    let mut to_compare = vec![Vec::<u8>::new()];
+
+    while let Some(prefix) = to_compare.pop() {
+        let merkle_value = |hash: &[u8; 32]| {
+            database.block_storage_closest_descendant_merkle_value(
+                hash,
+                iter::empty::<iter::Empty<u8>>(),
+                prefix.iter().copied(),
+            )
+        };
+        let parent_merkle_value = merkle_value(&parent_hash)?;
+        let block_merkle_value = merkle_value(block_hash)?;
+
+        match (parent_merkle_value, block_merkle_value) {
+            (parent, block) if parent == block => {}
+            (Some(_), Some(_)) => {
+                if prefix.len() % 2 == 0 {
+                    let value = |hash: &[u8; 32]| {
+                        database.block_storage_get(
+                            hash,
+                            iter::empty::<iter::Empty<u8>>(),
+                            prefix.iter().copied(),
+                        )
+                    };
+                    let parent_value = value(&parent_hash)?.map(|(value, _)| value);
+                    let block_value = value(block_hash)?.map(|(value, _)| value);
+                    if parent_value != block_value {
+                        changes.push((
+                            trie::nibbles_to_bytes_suffix_extend(
+                                prefix.iter().map(|n| trie::Nibble::try_from(*n).unwrap()),
+                            )
+                            .collect(),
+                            block_value,
+                        ));
+                    }
+                }
+
+                for nibble in (0..16).rev() {
+                    let mut child_prefix = prefix.clone();
+                    child_prefix.push(nibble);
+                    to_compare.push(child_prefix);
+                }
+            }
+            (parent, _) => {
+                // One of the two blocks doesn't have any entry with this prefix, in which case
+                // all the entries of the other block with this prefix have been either added
+                // or removed.
+                let (source_hash, is_erased) = if parent.is_some() {
+                    (&parent_hash, true)
+                } else {
+                    (block_hash, false)
+                };
+
+                let mut key = prefix.clone();
+                while let Some(next_key) = database.block_storage_next_key(
+                    source_hash,
+                    iter::empty::<iter::Empty<u8>>(),
+                    key.iter().copied(),
+                    prefix.iter().copied(),
+                    false,
+                )? {
+                    let new_value = if is_erased {
+                        None
+                    } else {
+                        database
+                            .block_storage_get(
+                                source_hash,
+                                iter::empty::<iter::Empty<u8>>(),
+                                next_key.iter().copied(),
+                            )?
+                            .map(|(value, _)| value)
+                    };
+                    changes.push((
+                        trie::nibbles_to_bytes_suffix_extend(
+                            next_key.iter().map(|n| trie::Nibble::try_from(*n).unwrap()),
+                        )
+                        .collect(),
+                        new_value,
+                    ));
+                    key = next_key;
+                    key.push(0);
+                }
+            }
+        }
+    }
+
+    Ok(Some(changes))
+}
+
 /// Executes the given block. On success, inserts it and its storage into the database.
 // TODO: use a config struct for the parameters?
 pub async fn execute_block_and_insert(
@@ -3546,3 +5457,613 @@ pub enum RuntimeCallError {
     /// Runtime has tried to call a forbidden host function.
     ForbiddenHostFunction,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{build_block_tree, TxStatus, WatchedTransaction};
+    use crate::test_util;
+    use smoldot::{chain_spec, database::full_sqlite, executor, header, trie};
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        iter,
+        time::Duration,
+    };
+
+    #[test]
+    fn block_tree_multiple_forks() {
+        // The finalized block is 0x00. Block 0x01 has three children 0x02, 0x03, and 0x04.
+        // 0x03 has a child 0x05, which is the best block, and 0x04 has a child 0x06.
+        let non_finalized_blocks = vec![
+            ([1; 32], [0; 32], 1),
+            ([2; 32], [1; 32], 2),
+            ([3; 32], [1; 32], 2),
+            ([4; 32], [1; 32], 2),
+            ([5; 32], [3; 32], 3),
+            ([6; 32], [4; 32], 3),
+        ];
+        let summary = |tree: &super::BlockTree| {
+            tree.blocks
+                .iter()
+                .map(|block| (block.hash[0], block.is_best, block.is_finalized))
+                .collect::<Vec<_>>()
+        };
+
+        // Without limits, the finalized block comes first, then the best chain, then the forks.
+        let tree = build_block_tree(
+            ([0; 32], [0xff; 32], 0),
+            non_finalized_blocks.clone(),
+            [5; 32],
+            10,
+            10,
+        );
+        assert!(!tree.truncated);
+        assert_eq!(
+            summary(&tree),
+            vec![
+                (0, false, true),
+                (1, false, false),
+                (3, false, false),
+                (5, true, false),
+                (2, false, false),
+                (4, false, false),
+                (6, false, false),
+            ]
+        );
+        assert_eq!(tree.blocks[0].parent_hash, [0xff; 32]);
+        assert_eq!(tree.blocks[2].parent_hash, [1; 32]);
+
+        // With one block per height, the best chain is kept and all the forks are omitted.
+        let tree = build_block_tree(
+            ([0; 32], [0xff; 32], 0),
+            non_finalized_blocks.clone(),
+            [5; 32],
+            10,
+            1,
+        );
+        assert!(tree.truncated);
+        assert_eq!(
+            summary(&tree),
+            vec![
+                (0, false, true),
+                (1, false, false),
+                (3, false, false),
+                (5, true, false),
+            ]
+        );
+
+        // The best chain is kept even if it is deeper than the maximum depth.
+        let tree = build_block_tree(
+            ([0; 32], [0xff; 32], 0),
+            non_finalized_blocks.clone(),
+            [5; 32],
+            2,
+            2,
+        );
+        assert!(tree.truncated);
+        assert_eq!(
+            summary(&tree),
+            vec![
+                (0, false, true),
+                (1, false, false),
+                (3, false, false),
+                (5, true, false),
+                (2, false, false),
+            ]
+        );
+
+        // The finalized block can be the best block.
+        let tree = build_block_tree(([0; 32], [0xff; 32], 0), Vec::new(), [0; 32], 10, 10);
+        assert!(!tree.truncated);
+        assert_eq!(summary(&tree), vec![(0, true, true)]);
+    }
+
+    #[test]
+    fn watched_transaction_reorg() {
+        let (status_tx, status_rx) = async_channel::bounded(16);
+        let mut watched = WatchedTransaction {
+            hash: [0; 32],
+            status_tx,
+            included_in: Vec::new(),
+            reported_in_block: None,
+        };
+        let next_statuses = || iter::from_fn(|| status_rx.try_recv().ok()).collect::<Vec<_>>();
+        let best_chain = |blocks: &[[u8; 32]]| blocks.iter().copied().collect();
+
+        // The transaction is included in block 0x01 of a first fork and block 0x02 of a second
+        // fork. Block 0x03 is a child of 0x01.
+        watched.included_in = vec![[1; 32], [2; 32]];
+
+        assert!(watched.update_best_chain(&best_chain(&[[1; 32]])));
+        assert_eq!(next_statuses(), vec![TxStatus::InBlock([1; 32])]);
+
+        // Extending the best chain doesn't change anything.
+        assert!(watched.update_best_chain(&best_chain(&[[1; 32], [3; 32]])));
+        assert_eq!(next_statuses(), vec![]);
+
+        // Re-organization towards the second fork.
+        assert!(watched.update_best_chain(&best_chain(&[[2; 32]])));
+        assert_eq!(
+            next_statuses(),
+            vec![TxStatus::Retracted([1; 32]), TxStatus::InBlock([2; 32])]
+        );
+
+        // Re-organization towards a fork that doesn't contain the transaction.
+        assert!(watched.update_best_chain(&best_chain(&[[4; 32]])));
+        assert_eq!(next_statuses(), vec![TxStatus::Retracted([2; 32])]);
+
+        // Block 0x05, common ancestor of 0x02 and 0x04, is finalized, and the first fork is
+        // pruned.
+        assert!(watched.update_finalized(&[[5; 32]], &[[1; 32], [3; 32]]));
+        assert_eq!(next_statuses(), vec![]);
+        assert_eq!(watched.included_in, vec![[2; 32]]);
+
+        // Block 0x02 is finalized after all, before the best chain has been updated.
+        assert!(!watched.update_finalized(&[[2; 32]], &[[4; 32]]));
+        assert_eq!(
+            next_statuses(),
+            vec![TxStatus::InBlock([2; 32]), TxStatus::Finalized([2; 32])]
+        );
+    }
+
+    #[test]
+    fn code_substitute_runtime() {
+        let chain_spec = chain_spec::ChainSpec::from_json_bytes(include_bytes!(
+            "../tests/substrate-node-template.json"
+        ))
+        .unwrap();
+        let genesis_storage = chain_spec.genesis_storage().into_genesis_items().unwrap();
+
+        let parent_runtime = executor::host::HostVmPrototype::new(executor::host::Config {
+            module: genesis_storage.value(b":code").unwrap(),
+            heap_pages: executor::DEFAULT_HEAP_PAGES,
+            exec_hint: executor::vm::ExecHint::ValidateAndCompile,
+            allow_unresolved_imports: false,
+        })
+        .unwrap();
+        assert_eq!(
+            parent_runtime.runtime_version().decode().spec_name,
+            "node-template"
+        );
+
+        let code_substitutes = [(
+            5,
+            include_bytes!("../../lib/src/executor/host/westend-runtime-v9300.wasm").to_vec(),
+        )]
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+
+        // The block with a substitute is executed with the override code.
+        let substitute = super::code_substitute_runtime(&code_substitutes, 5, &parent_runtime)
+            .unwrap()
+            .unwrap();
+        assert_eq!(substitute.runtime_version().decode().spec_name, "westend");
+        assert_eq!(substitute.heap_pages(), parent_runtime.heap_pages());
+
+        // Other blocks are executed with the runtime of their parent.
+        assert!(super::code_substitute_runtime(&code_substitutes, 4, &parent_runtime).is_none());
+        assert!(super::code_substitute_runtime(&code_substitutes, 6, &parent_runtime).is_none());
+
+        // A substitute whose `spec_version` differs from the on-chain one no longer applies
+        // after a restart.
+        assert!(
+            super::finalized_code_substitute_runtime(&code_substitutes, 10, &parent_runtime)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn finalized_code_substitute_runtime() {
+        let chain_spec = chain_spec::ChainSpec::from_json_bytes(include_bytes!(
+            "../tests/substrate-node-template.json"
+        ))
+        .unwrap();
+        let genesis_storage = chain_spec.genesis_storage().into_genesis_items().unwrap();
+        let code = genesis_storage.value(b":code").unwrap();
+
+        let finalized_runtime = executor::host::HostVmPrototype::new(executor::host::Config {
+            module: code,
+            heap_pages: executor::DEFAULT_HEAP_PAGES,
+            exec_hint: executor::vm::ExecHint::ValidateAndCompile,
+            allow_unresolved_imports: false,
+        })
+        .unwrap();
+
+        let code_substitutes = [(5, code.to_vec())].into_iter().collect::<BTreeMap<_, _>>();
+
+        // The substitute applies to the blocks after the one it was registered for.
+        assert!(
+            super::finalized_code_substitute_runtime(&code_substitutes, 5, &finalized_runtime)
+                .unwrap()
+                .is_ok()
+        );
+        assert!(super::finalized_code_substitute_runtime(
+            &code_substitutes,
+            1000,
+            &finalized_runtime
+        )
+        .unwrap()
+        .is_ok());
+        assert!(
+            super::finalized_code_substitute_runtime(&code_substitutes, 4, &finalized_runtime)
+                .is_none()
+        );
+
+        // A substitute that can't be compiled is reported as an error rather than ignored.
+        let invalid_substitutes = [(5, b"not wasm".to_vec())]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        assert!(super::finalized_code_substitute_runtime(
+            &invalid_substitutes,
+            5,
+            &finalized_runtime
+        )
+        .unwrap()
+        .is_err());
+    }
+
+    #[test]
+    fn block_executed_with_code_substitute() {
+        smol::block_on(async {
+            let (database, genesis_hash, genesis_runtime, slot_duration) =
+                test_util::chain_spec_database(include_bytes!(
+                    "../tests/substrate-node-template.json"
+                ))
+                .await;
+            let (block_header, block_body) = test_util::build_aura_block(
+                &database,
+                &genesis_hash,
+                0,
+                genesis_runtime.clone(),
+                slot_duration,
+                1,
+            )
+            .await;
+            let block_hash = header::hash_from_scale_encoded_header(&block_header);
+            let now = Duration::from_millis(slot_duration);
+
+            // The block is executed with the substitute, which fails here as the substitute is
+            // the runtime of a different chain.
+            let incompatible_substitutes = [(
+                1,
+                include_bytes!("../../lib/src/executor/host/westend-runtime-v9300.wasm").to_vec(),
+            )]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+            let substitute =
+                super::code_substitute_runtime(&incompatible_substitutes, 1, &genesis_runtime)
+                    .unwrap()
+                    .unwrap();
+            assert!(super::execute_block_and_insert(
+                &database,
+                substitute,
+                &genesis_hash,
+                &block_header,
+                4,
+                block_body.iter(),
+                now,
+                true,
+            )
+            .await
+            .is_err());
+            assert!(database
+                .with_database(move |db| db.block_scale_encoded_header(&block_hash))
+                .await
+                .unwrap()
+                .is_none());
+
+            // With a substitute compatible with the chain, the block is successfully executed
+            // and inserted in the database.
+            let compatible_substitutes = [(
+                1,
+                genesis_runtime_code(include_bytes!("../tests/substrate-node-template.json")),
+            )]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+            let substitute =
+                super::code_substitute_runtime(&compatible_substitutes, 1, &genesis_runtime)
+                    .unwrap()
+                    .unwrap();
+            let success = super::execute_block_and_insert(
+                &database,
+                substitute,
+                &genesis_hash,
+                &block_header,
+                4,
+                block_body.iter(),
+                now,
+                true,
+            )
+            .await
+            .unwrap();
+            success.block_insertion.unwrap();
+            // The block doesn't modify the on-chain runtime, meaning that the substitute is
+            // passed down to its children.
+            assert!(success.new_runtime.is_none());
+            assert!(database
+                .with_database(move |db| db.block_scale_encoded_header(&block_hash))
+                .await
+                .unwrap()
+                .is_some());
+        });
+    }
+
+    fn genesis_runtime_code(chain_spec_json: &[u8]) -> Vec<u8> {
+        let chain_spec = chain_spec::ChainSpec::from_json_bytes(chain_spec_json).unwrap();
+        let genesis_storage = chain_spec.genesis_storage().into_genesis_items().unwrap();
+        genesis_storage.value(b":code").unwrap().to_vec()
+    }
+
+    #[test]
+    fn block_storage_changes_match_parent_child_diff() {
+        smol::block_on(async {
+            let (database, genesis_hash, genesis_runtime, slot_duration) =
+                test_util::chain_spec_database(include_bytes!(
+                    "../tests/substrate-node-template.json"
+                ))
+                .await;
+            let (block_header, block_body) = test_util::build_aura_block(
+                &database,
+                &genesis_hash,
+                0,
+                genesis_runtime.clone(),
+                slot_duration,
+                1,
+            )
+            .await;
+            let block_hash = header::hash_from_scale_encoded_header(&block_header);
+
+            let success = super::execute_block_and_insert(
+                &database,
+                genesis_runtime,
+                &genesis_hash,
+                &block_header,
+                4,
+                block_body.iter(),
+                Duration::from_millis(slot_duration),
+                true,
+            )
+            .await
+            .unwrap();
+            success.block_insertion.unwrap();
+
+            let (parent_storage, block_storage, database_changes, genesis_changes) = database
+                .with_database(move |db| {
+                    (
+                        full_main_trie(db, &genesis_hash),
+                        full_main_trie(db, &block_hash),
+                        super::database_storage_changes(db, &block_hash).unwrap(),
+                        super::database_storage_changes(db, &genesis_hash).unwrap(),
+                    )
+                })
+                .await;
+
+            let expected_changes = parent_storage
+                .keys()
+                .chain(block_storage.keys())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .filter(|key| parent_storage.get(*key) != block_storage.get(*key))
+                .map(|key| (key.clone(), block_storage.get(key).cloned()))
+                .collect::<Vec<_>>();
+            assert!(!expected_changes.is_empty());
+
+            // The changes reported by the runtime also contain the entries that have been
+            // overwritten with the same value.
+            let runtime_changes = super::main_trie_changes_ordered(&success.storage_changes)
+                .into_iter()
+                .filter(|(key, value)| parent_storage.get(key) != value.as_ref())
+                .collect::<Vec<_>>();
+            assert_eq!(runtime_changes, expected_changes);
+
+            assert_eq!(database_changes, Some(expected_changes));
+            assert_eq!(genesis_changes, None);
+        });
+    }
+
+    #[test]
+    fn grandpa_authority_set_change_reported_with_proof() {
+        smol::block_on(async {
+            let chain_spec_json = include_bytes!("../tests/substrate-node-template.json");
+            let (database, genesis_hash, _, _) =
+                test_util::chain_spec_database(chain_spec_json).await;
+
+            let (tx, rx) = async_channel::bounded(8);
+            let (full_tx, full_rx) = async_channel::bounded(1);
+            let state_root = database
+                .with_database(move |db| {
+                    for _ in 0..2 {
+                        super::report_grandpa_authority_set_change(
+                            db,
+                            &[tx.clone(), full_tx.clone()],
+                            genesis_hash,
+                            vec![1, 2, 3],
+                            vec![4, 5, 6],
+                            7,
+                        );
+                    }
+                    let header = db
+                        .block_scale_encoded_header(&genesis_hash)
+                        .unwrap()
+                        .unwrap();
+                    *header::decode(&header, 4).unwrap().state_root
+                })
+                .await;
+
+            let chain_spec = chain_spec::ChainSpec::from_json_bytes(chain_spec_json).unwrap();
+            let genesis_storage = chain_spec.genesis_storage().into_genesis_items().unwrap();
+
+            // Both changes are received by the subscriber whose channel has enough space.
+            for _ in 0..2 {
+                let change = rx.recv().await.unwrap();
+                assert_eq!(change.block_hash, genesis_hash);
+                assert_eq!(change.scale_encoded_header, [1, 2, 3]);
+                assert_eq!(change.scale_encoded_justification, [4, 5, 6]);
+                assert_eq!(change.new_set_id, 7);
+
+                let proof = change.authority_set_proof.unwrap();
+                let decoded =
+                    trie::proof_decode::decode_and_verify_proof(trie::proof_decode::Config {
+                        proof: &proof[..],
+                    })
+                    .unwrap();
+                for key in super::GRANDPA_AUTHORITY_SET_KEYS {
+                    assert_eq!(
+                        decoded
+                            .storage_value(&state_root, key)
+                            .unwrap()
+                            .map(|(value, _)| value),
+                        genesis_storage.value(key)
+                    );
+                }
+            }
+
+            // The subscriber whose channel is full is closed.
+            assert!(full_rx.recv().await.is_ok());
+            assert!(full_rx.recv().await.is_err());
+        });
+    }
+
+    #[test]
+    fn imported_blocks_reported_to_subscribers() {
+        smol::block_on(async {
+            let (tx, rx) = async_channel::bounded(8);
+            let (full_tx, full_rx) = async_channel::bounded(1);
+            let (closed_tx, closed_rx) = async_channel::bounded(8);
+            drop(closed_rx);
+
+            let mut senders = vec![tx, full_tx, closed_tx];
+            for number in 1..=2 {
+                super::report_imported_block(
+                    &mut senders,
+                    super::ImportedBlock {
+                        hash: [number; 32],
+                        number: u64::from(number),
+                        scale_encoded_header: vec![number],
+                        is_new_best: number == 1,
+                    },
+                );
+            }
+
+            // Only the subscriber whose channel has enough space remains.
+            assert_eq!(senders.len(), 1);
+            for number in 1..=2 {
+                let block = rx.try_recv().unwrap();
+                assert_eq!(block.hash, [number; 32]);
+                assert_eq!(block.number, u64::from(number));
+                assert_eq!(block.scale_encoded_header, [number]);
+                assert_eq!(block.is_new_best, number == 1);
+            }
+
+            // The subscriber whose channel is full is closed.
+            assert_eq!(full_rx.recv().await.unwrap().number, 1);
+            assert!(full_rx.recv().await.is_err());
+        });
+    }
+
+    #[test]
+    fn storage_download_resumes_from_resume_key() {
+        smol::block_on(async {
+            let chain_spec_json = include_bytes!("../tests/substrate-node-template.json");
+            let (database, genesis_hash, _, _) =
+                test_util::chain_spec_database(chain_spec_json).await;
+
+            let (expected, downloaded) = database
+                .with_database(move |db| {
+                    let header = db
+                        .block_scale_encoded_header(&genesis_hash)
+                        .unwrap()
+                        .unwrap();
+                    let state_root = *header::decode(&header, 4).unwrap().state_root;
+
+                    // Downloads with the given state machine, stopping once `max_entries`
+                    // entries have been yielded in total. Returns the resume key, or `None` if
+                    // the download has finished.
+                    let mut downloaded = Vec::new();
+                    let mut run = |download: &mut super::StorageDownload, max_entries: usize| {
+                        while downloaded.len() < max_entries {
+                            let resume_key = download.resume_key()?;
+                            let keys = download.requested_keys().collect::<Vec<_>>();
+                            assert!(!keys.is_empty());
+
+                            // Invalid proofs are rejected without affecting the download.
+                            assert!(download.inject_proof(&[1, 2, 3]).is_none());
+                            assert_eq!(download.resume_key().unwrap(), resume_key);
+
+                            // Only the first key is proven, like a peer that would send a
+                            // partial proof, in order for the download to take many steps.
+                            let proof = db
+                                .block_storage_proof(&genesis_hash, keys.into_iter().take(1))
+                                .unwrap();
+                            let entries = download.inject_proof(&proof).unwrap();
+
+                            // Yielded entries are always between the previous and next resume
+                            // keys.
+                            let next_resume_key = download.resume_key();
+                            for (key, _) in &entries {
+                                assert!(*key >= resume_key);
+                                assert!(next_resume_key.as_ref().map_or(true, |k| key < k));
+                            }
+                            downloaded.extend(entries);
+                        }
+                        download.resume_key()
+                    };
+
+                    let start_key = b":c".to_vec();
+                    let mut download = super::StorageDownload::new(state_root, start_key.clone());
+                    let resume_key = run(&mut download, 1).unwrap();
+                    assert!(resume_key > start_key);
+
+                    // Resuming the download with a fresh state machine.
+                    let mut download = super::StorageDownload::new(state_root, resume_key);
+                    assert!(run(&mut download, usize::MAX).is_none());
+
+                    let expected = full_main_trie(db, &genesis_hash)
+                        .into_iter()
+                        .filter(|(key, _)| *key >= start_key)
+                        .collect::<Vec<_>>();
+                    (expected, downloaded)
+                })
+                .await;
+
+            assert!(expected.iter().any(|(key, _)| key == b":code"));
+            assert_eq!(downloaded, expected);
+        });
+    }
+
+    fn full_main_trie(
+        database: &full_sqlite::SqliteFullDatabase,
+        block_hash: &[u8; 32],
+    ) -> BTreeMap<Vec<u8>, Vec<u8>> {
+        let mut storage = BTreeMap::new();
+        let mut key = Vec::new();
+        while let Some(next_key) = database
+            .block_storage_next_key(
+                block_hash,
+                iter::empty::<iter::Empty<u8>>(),
+                key.iter().copied(),
+                iter::empty(),
+                false,
+            )
+            .unwrap()
+        {
+            let (value, _) = database
+                .block_storage_get(
+                    block_hash,
+                    iter::empty::<iter::Empty<u8>>(),
+                    next_key.iter().copied(),
+                )
+                .unwrap()
+                .unwrap();
+            storage.insert(
+                trie::nibbles_to_bytes_suffix_extend(
+                    next_key.iter().map(|n| trie::Nibble::try_from(*n).unwrap()),
+                )
+                .collect(),
+                value,
+            );
+            key = next_key;
+            key.push(0);
+        }
+        storage
+    }
+}
@@ -19,11 +19,12 @@
 //! For this reason, it is undesirable to access it from an asynchronous context.
 
 use futures_channel::oneshot;
+use futures_lite::FutureExt as _;
 use smol::{channel, lock::Mutex, stream::StreamExt as _};
 use smoldot::database::full_sqlite::SqliteFullDatabase;
-use std::{pin::pin, thread};
+use std::{pin::pin, sync::Arc, thread, time::Duration};
 
-pub use smoldot::database::full_sqlite::StorageAccessError;
+pub use smoldot::database::full_sqlite::{CorruptedError, StorageAccessError};
 
 /// Handle to the thread were the database accesses are performed.
 ///
@@ -32,10 +33,35 @@ pub use smoldot::database::full_sqlite::StorageAccessError;
 /// Use the `From` trait implementation to build a [`DatabaseThread`].
 pub struct DatabaseThread {
     sender: Mutex<channel::Sender<Exec>>,
+
+    /// The database itself, shared with the database thread.
+    ///
+    /// Must only be accessed directly for operations that don't perform any I/O, such as
+    /// [`SqliteFullDatabase::unpin_block_storage`].
+    database: Arc<SqliteFullDatabase>,
+
+    /// Off-chain storage of the [`OffchainStorageKind::Local`] kind, indexed by prefix and key.
+    local_offchain_storage: Mutex<LocalOffchainStorage>,
+}
+
+/// Error returned by [`DatabaseThread::with_database_timeout`].
+#[derive(Debug, Clone, derive_more::Display)]
+#[display(fmt = "Database access timed out")]
+pub struct DatabaseTimeoutError;
+
+/// Kind of off-chain storage.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OffchainStorageKind {
+    /// Storage stored in the database, and thus kept across restarts.
+    Persistent,
+    /// Storage kept in memory, and lost when the node shuts down.
+    Local,
 }
 
 type Exec = Box<dyn FnOnce(&SqliteFullDatabase) + Send>;
 
+type LocalOffchainStorage = hashbrown::HashMap<(Vec<u8>, Vec<u8>), Vec<u8>, fnv::FnvBuildHasher>;
+
 impl DatabaseThread {
     /// Sends a closure to the database thread, executes it, then returns the value that the
     /// closure returned.
@@ -55,6 +81,41 @@ impl DatabaseThread {
         rx.await.unwrap()
     }
 
+    /// Similar to [`DatabaseThread::with_database`], but returns an error if the closure hasn't
+    /// finished executing after the given duration, for example because the database thread is
+    /// stuck or busy with other closures.
+    ///
+    /// In case of timeout, the closure is no longer executed if it hasn't started yet. If it has
+    /// already started, its return value is discarded.
+    pub async fn with_database_timeout<T: Send + 'static>(
+        &self,
+        timeout: Duration,
+        closure: impl FnOnce(&SqliteFullDatabase) -> T + Send + 'static,
+    ) -> Result<T, DatabaseTimeoutError> {
+        let (tx, rx) = oneshot::channel();
+
+        async {
+            self.sender
+                .lock()
+                .await
+                .send(Box::new(move |db| {
+                    // The receiver is destroyed when the timeout is reached.
+                    if tx.is_canceled() {
+                        return;
+                    }
+                    let _ = tx.send(closure(db));
+                }))
+                .await
+                .unwrap();
+            Ok(rx.await.unwrap())
+        }
+        .or(async {
+            smol::Timer::after(timeout).await;
+            Err(DatabaseTimeoutError)
+        })
+        .await
+    }
+
     /// Similar to [`DatabaseThread::with_database`], but without any return value. This function
     /// is slightly more optimized for this use case.
     pub async fn with_database_detached(
@@ -70,26 +131,245 @@ impl DatabaseThread {
             .await
             .unwrap();
     }
+
+    /// Returns a handle to the storage of the given block.
+    ///
+    /// As long as the returned [`SnapshotHandle`] is alive, the storage of the block is pinned in
+    /// the database. All the storage accesses performed through the handle are thus guaranteed
+    /// to see the same consistent state, even if the best or finalized block changes in the
+    /// meanwhile or if other reads are interleaved.
+    ///
+    /// > **Note**: Pinning a block prevents the database from removing this block and its
+    /// >           storage from the disk when the block becomes obsolete, such as when a
+    /// >           competing fork gets finalized. Holding a [`SnapshotHandle`] for a long time
+    /// >           can thus lead to an increase in disk usage. The memory cost of a snapshot, on
+    /// >           the other hand, is negligible. Snapshots are meant to be held for the duration
+    /// >           of a single request.
+    ///
+    /// Returns an error if the block or its storage can't be found in the database.
+    pub async fn snapshot_at(
+        self: &Arc<Self>,
+        block_hash: [u8; 32],
+    ) -> Result<SnapshotHandle, StorageAccessError> {
+        self.with_database(move |db| db.pin_block_storage(&block_hash))
+            .await?;
+        Ok(SnapshotHandle {
+            database: self.clone(),
+            block_hash,
+        })
+    }
+
+    /// Similar to [`DatabaseThread::snapshot_at`], but returns an error if the database doesn't
+    /// answer after the given duration. See [`DatabaseThread::with_database_timeout`].
+    pub async fn snapshot_at_timeout(
+        self: &Arc<Self>,
+        timeout: Duration,
+        block_hash: [u8; 32],
+    ) -> Result<Result<SnapshotHandle, StorageAccessError>, DatabaseTimeoutError> {
+        // The handle is built on the database thread, so that the storage is unpinned if the
+        // timeout is reached after the storage has been pinned.
+        let database = self.clone();
+        self.with_database_timeout(timeout, move |db| {
+            db.pin_block_storage(&block_hash)?;
+            Ok(SnapshotHandle {
+                database,
+                block_hash,
+            })
+        })
+        .await
+    }
+
+    /// Returns the value associated with the given prefix and key in the off-chain storage of
+    /// the given kind, or `None` if there is no such value.
+    pub async fn offchain_storage_get(
+        &self,
+        kind: OffchainStorageKind,
+        prefix: Vec<u8>,
+        key: Vec<u8>,
+    ) -> Result<Option<Vec<u8>>, CorruptedError> {
+        match kind {
+            OffchainStorageKind::Persistent => {
+                self.with_database(move |db| db.offchain_storage_get(&prefix, &key))
+                    .await
+            }
+            OffchainStorageKind::Local => Ok(self
+                .local_offchain_storage
+                .lock()
+                .await
+                .get(&(prefix, key))
+                .cloned()),
+        }
+    }
+
+    /// Sets the value associated with the given prefix and key in the off-chain storage of the
+    /// given kind, overwriting the existing value if any.
+    pub async fn offchain_storage_set(
+        &self,
+        kind: OffchainStorageKind,
+        prefix: Vec<u8>,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> Result<(), CorruptedError> {
+        match kind {
+            OffchainStorageKind::Persistent => {
+                self.with_database(move |db| db.offchain_storage_set(&prefix, &key, &value))
+                    .await
+            }
+            OffchainStorageKind::Local => {
+                self.local_offchain_storage
+                    .lock()
+                    .await
+                    .insert((prefix, key), value);
+                Ok(())
+            }
+        }
+    }
 }
 
 impl From<SqliteFullDatabase> for DatabaseThread {
     fn from(db: SqliteFullDatabase) -> DatabaseThread {
         let (sender, rx) = channel::bounded::<Box<dyn FnOnce(&SqliteFullDatabase) + Send>>(256);
+        let database = Arc::new(db);
 
         thread::Builder::new()
             .name("sqlite-database".into())
-            .spawn(move || {
-                // When the `DatabaseThread` is dropped, the sender will close, `rx.next()`
-                // will return `None`, and the closure here will finish, ending the thread.
-                let mut rx = pin!(rx);
-                while let Some(closure) = smol::block_on(rx.next()) {
-                    closure(&db)
+            .spawn({
+                let db = database.clone();
+                move || {
+                    // When the `DatabaseThread` is dropped, the sender will close, `rx.next()`
+                    // will return `None`, and the closure here will finish, ending the thread.
+                    let mut rx = pin!(rx);
+                    while let Some(closure) = smol::block_on(rx.next()) {
+                        closure(&db)
+                    }
                 }
             })
             .unwrap();
 
         DatabaseThread {
             sender: Mutex::new(sender),
+            database,
+            local_offchain_storage: Mutex::new(hashbrown::HashMap::with_hasher(Default::default())),
         }
     }
 }
+
+/// Read-only view of the storage of a block. See [`DatabaseThread::snapshot_at`].
+///
+/// The storage of the block is unpinned when this handle is destroyed.
+pub struct SnapshotHandle {
+    database: Arc<DatabaseThread>,
+    block_hash: [u8; 32],
+}
+
+impl SnapshotHandle {
+    /// Returns the hash of the block whose storage this snapshot gives access to.
+    pub fn block_hash(&self) -> &[u8; 32] {
+        &self.block_hash
+    }
+
+    /// Similar to [`DatabaseThread::with_database`]. The closure is also passed the hash of the
+    /// block of this snapshot, whose storage is guaranteed to be available.
+    pub async fn with_database<T: Send + 'static>(
+        &self,
+        closure: impl FnOnce(&SqliteFullDatabase, &[u8; 32]) -> T + Send + 'static,
+    ) -> T {
+        let block_hash = self.block_hash;
+        self.database
+            .with_database(move |db| closure(db, &block_hash))
+            .await
+    }
+
+    /// Similar to [`SnapshotHandle::with_database`], but returns an error if the closure hasn't
+    /// finished executing after the given duration. See
+    /// [`DatabaseThread::with_database_timeout`].
+    pub async fn with_database_timeout<T: Send + 'static>(
+        &self,
+        timeout: Duration,
+        closure: impl FnOnce(&SqliteFullDatabase, &[u8; 32]) -> T + Send + 'static,
+    ) -> Result<T, DatabaseTimeoutError> {
+        let block_hash = self.block_hash;
+        self.database
+            .with_database_timeout(timeout, move |db| closure(db, &block_hash))
+            .await
+    }
+}
+
+impl Drop for SnapshotHandle {
+    fn drop(&mut self) {
+        // Unpinning doesn't perform any I/O and can thus be done without going through the
+        // database thread.
+        self.database.database.unpin_block_storage(&self.block_hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DatabaseThread, DatabaseTimeoutError};
+    use crate::test_util;
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    #[test]
+    fn slow_database_times_out() {
+        let (database, genesis_hash) = test_util::memory_database();
+        let database = DatabaseThread::from(database);
+
+        smol::block_on(async {
+            // Keeps the database thread busy, as if it was stuck.
+            database
+                .with_database_detached(|_| thread::sleep(Duration::from_millis(500)))
+                .await;
+
+            assert!(matches!(
+                database
+                    .with_database_timeout(Duration::from_millis(50), |db| db.best_block_hash())
+                    .await,
+                Err(DatabaseTimeoutError)
+            ));
+
+            // Once the database thread is available again, accesses succeed.
+            assert_eq!(
+                database
+                    .with_database_timeout(Duration::from_secs(10), |db| db.best_block_hash())
+                    .await
+                    .unwrap()
+                    .unwrap(),
+                genesis_hash
+            );
+        });
+    }
+
+    #[test]
+    fn timed_out_closure_not_executed() {
+        let (database, _) = test_util::memory_database();
+        let database = DatabaseThread::from(database);
+
+        smol::block_on(async {
+            database
+                .with_database_detached(|_| thread::sleep(Duration::from_millis(500)))
+                .await;
+
+            let executed = Arc::new(AtomicBool::new(false));
+            assert!(matches!(
+                database
+                    .with_database_timeout(Duration::from_millis(50), {
+                        let executed = executed.clone();
+                        move |_| executed.store(true, Ordering::SeqCst)
+                    })
+                    .await,
+                Err(DatabaseTimeoutError)
+            ));
+
+            // Waits for the database thread to process everything that has been sent before.
+            database.with_database(|_| ()).await;
+            assert!(!executed.load(Ordering::SeqCst));
+        });
+    }
+}
@@ -22,7 +22,13 @@ use smol::{
     future,
     net::{TcpListener, TcpStream},
 };
-use smoldot::json_rpc::{methods, service};
+use smoldot::{
+    chain_spec,
+    database::full_sqlite,
+    identity::keystore,
+    informant::HashDisplay,
+    json_rpc::{methods, service},
+};
 use std::{
     future::Future,
     io, mem,
@@ -38,8 +44,11 @@ use std::{
 
 mod chain_head_subscriptions;
 mod legacy_api_subscriptions;
+mod metadata_cache;
+mod rate_limiter;
 mod requests_handler;
 mod runtime_caches_service;
+mod runtime_versions_cache;
 
 /// Configuration for a [`JsonRpcService`].
 pub struct Config {
@@ -67,27 +76,124 @@ pub struct Config {
     /// Maximum number of requests to process in parallel.
     pub max_parallel_requests: u32,
 
+    /// Maximum number of requests, shared between all the JSON-RPC clients, that are waiting to
+    /// be processed. If a request arrives while this limit is reached, the oldest request
+    /// waiting to be processed is discarded and answered with a "server busy" error.
+    ///
+    /// Subscription starts are never discarded. Instead, they wait for the requests handlers
+    /// to be available.
+    pub max_queued_requests: NonZero<usize>,
+
+    /// Maximum size, in bytes, of a JSON-RPC request. Requests that are larger than this are
+    /// answered with an error without being parsed, both on the virtual endpoint and on the
+    /// WebSocket server. WebSocket messages larger than this are not even buffered, and the
+    /// connection is closed instead.
+    pub max_request_size: usize,
+
     /// Maximum number of JSON-RPC clients until new ones are rejected.
     pub max_json_rpc_clients: u32,
 
+    /// Maximum number of subscriptions that each JSON-RPC client can have active at the same
+    /// time. Attempts to start more subscriptions are rejected with an error.
+    pub max_json_rpc_subscriptions_per_client: u32,
+
+    /// Maximum number of blocks that each `chainHead_v1_follow` subscription can keep pinned at
+    /// the same time. If this limit is exceeded, the subscription generates a `stop` event and
+    /// is terminated.
+    pub max_chain_head_pinned_blocks: NonZero<usize>,
+
+    /// If `false`, the JSON-RPC functions that are considered unsafe (see
+    /// [`methods::MethodCall::is_unsafe`]) are rejected.
+    pub allow_unsafe_methods: bool,
+
+    /// Maximum number of JSON-RPC functions that execute the runtime (see
+    /// [`methods::MethodCall::is_expensive`]) that can be called per second, shared between all
+    /// the JSON-RPC clients. Calls above this limit are rejected. If `None`, there is no limit.
+    pub max_expensive_requests_per_second: Option<NonZero<u32>>,
+
+    /// If `true`, the name of each JSON-RPC request and the time it took to answer it are
+    /// reported to [`Config::log_callback`] at the debug level.
+    pub log_requests_durations: bool,
+
+    /// Maximum number of keys that the `state_getKeys` JSON-RPC function can return. Requests
+    /// that would return more keys fail, and callers are expected to use `state_getKeysPaged`
+    /// instead.
+    pub state_get_keys_max_keys: usize,
+
+    /// If `true`, `chain_subscribeAllHeads` subscriptions report the current best block
+    /// immediately after being created, in addition to the blocks that arrive later.
+    pub subscribe_all_heads_report_initial_best: bool,
+
+    /// Block that the JSON-RPC functions accepting an optional block hash, such as
+    /// `state_getKeys` or `chain_getHeader`, use when no block hash is passed.
+    pub default_block: DefaultBlock,
+
+    /// Maximum duration of an access to the database when answering a JSON-RPC request. Requests
+    /// whose database access takes longer fail with an error, in order to not leave JSON-RPC
+    /// clients waiting indefinitely if the database is stuck.
+    ///
+    /// Should be generous, as accessing the database can legitimately take a long time on a
+    /// busy node.
+    pub database_timeout: Duration,
+
+    /// Consensus engine used by the chain, or `None` if it is unknown. The JSON-RPC functions
+    /// that are specific to a consensus engine (see [`methods::MethodCall::consensus_engine`])
+    /// fail with an error if it doesn't match.
+    pub consensus_engine: Option<methods::ConsensusEngine>,
+
+    /// Finality engine used by the chain, or `None` if blocks are finalized through other means.
+    /// The JSON-RPC functions that are specific to a finality engine (see
+    /// [`methods::MethodCall::finality_engine`]) fail with an error if it doesn't match.
+    pub finality_engine: Option<methods::FinalityEngine>,
+
     /// Name of the chain, as found in the chain specification.
     pub chain_name: String,
 
     /// Type of the chain, as found in the chain specification.
     pub chain_type: String,
 
-    /// JSON-encoded properties of the chain, as found in the chain specification.
-    pub chain_properties_json: String,
+    /// Properties of the chain, as found in the chain specification.
+    pub chain_properties: Arc<chain_spec::ChainProperties>,
 
     /// Whether the chain is a live network. Found in the chain specification.
     pub chain_is_live: bool,
 
-    /// Hash of the genesis block.
-    // TODO: load from database maybe?
-    pub genesis_block_hash: [u8; 32],
+    /// Fork id of the chain, as found in the chain specification.
+    pub chain_fork_id: Option<String>,
+
+    /// Legacy protocol id of the chain, as found in the chain specification.
+    pub chain_protocol_id: Option<String>,
+
+    /// Chain specification of the chain. Used to generate the chain specifications returned by
+    /// `sync_state_genSyncSpec`.
+    pub chain_spec: Arc<chain_spec::ChainSpec>,
+
+    /// Hash of the genesis block, if known ahead of time.
+    ///
+    /// The hash of the genesis block is loaded from the database. If the database doesn't
+    /// contain the genesis block, which can happen if it was populated through a warp sync, then
+    /// this value is used instead. If both this value and the database contain a genesis block
+    /// hash, they must match or initialization fails.
+    pub genesis_block_hash: Option<[u8; 32]>,
 
     /// Consensus service of the chain.
     pub consensus_service: Arc<consensus_service::ConsensusService>,
+
+    /// Keystore where the `author_insertKey` JSON-RPC function inserts keys.
+    pub keystore: Arc<keystore::Keystore>,
+}
+
+/// See [`Config::default_block`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DefaultBlock {
+    /// Use the current best block.
+    ///
+    /// This is the behavior of Substrate, and is what JSON-RPC clients generally expect.
+    Best,
+    /// Use the current finalized block.
+    ///
+    /// Contrary to the best block, the state of the finalized block can never be reverted.
+    Finalized,
 }
 
 /// Running JSON-RPC service.
@@ -141,15 +247,43 @@ impl JsonRpcService {
             None => (None, None),
         };
 
+        // Load the hash of the genesis block from the database, and compare it with the one in
+        // the configuration, if any.
+        let genesis_block_hash = {
+            let database_genesis_block_hash = config
+                .database
+                .with_database(|database| {
+                    database
+                        .block_hash_by_number(0)
+                        .map(|mut hashes| hashes.next())
+                })
+                .await
+                .map_err(InitError::DatabaseCorrupted)?;
+
+            match (database_genesis_block_hash, config.genesis_block_hash) {
+                (Some(database), Some(configured)) if database != configured => {
+                    return Err(InitError::GenesisHashMismatch {
+                        configured,
+                        database,
+                    })
+                }
+                (Some(hash), _) | (None, Some(hash)) => hash,
+                (None, None) => return Err(InitError::GenesisHashUnknown),
+            }
+        };
+
         let service_dropped = event_listener::Event::new();
         let on_service_dropped = service_dropped.listen();
 
         let (to_requests_handlers, from_background) = async_channel::bounded(8);
+        let (requests_to_handlers, requests_from_background) =
+            async_channel::bounded(config.max_queued_requests.get());
 
         let (virtual_client_main_task, virtual_client_io) =
             service::client_main_task(service::Config {
                 max_active_subscriptions: u32::MAX,
                 max_pending_requests: NonZero::<u32>::new(u32::MAX).unwrap(),
+                max_request_size: config.max_request_size,
             });
 
         spawn_client_main_task(
@@ -157,6 +291,8 @@ impl JsonRpcService {
             config.consensus_service.clone(),
             config.database.clone(),
             to_requests_handlers.clone(),
+            requests_to_handlers.clone(),
+            config.max_chain_head_pinned_blocks,
             virtual_client_main_task,
         );
 
@@ -168,6 +304,18 @@ impl JsonRpcService {
             },
         ));
 
+        let metadata_cache = Arc::new(metadata_cache::MetadataCache::new(
+            NonZero::<usize>::new(4).unwrap(),
+        ));
+
+        let runtime_versions_cache = Arc::new(runtime_versions_cache::RuntimeVersionsCache::new(
+            NonZero::<usize>::new(256).unwrap(),
+        ));
+
+        let expensive_requests_rate_limiter = config
+            .max_expensive_requests_per_second
+            .map(|limit| Arc::new(rate_limiter::RateLimiter::new(limit)));
+
         for _ in 0..config.max_parallel_requests {
             requests_handler::spawn_requests_handler(requests_handler::Config {
                 tasks_executor: config.tasks_executor.clone(),
@@ -175,13 +323,30 @@ impl JsonRpcService {
                 database: config.database.clone(),
                 network_service: config.network_service.clone(),
                 receiver: from_background.clone(),
+                requests_receiver: requests_from_background.clone(),
                 chain_name: config.chain_name.clone(),
                 chain_type: config.chain_type.clone(),
-                chain_properties_json: config.chain_properties_json.clone(),
+                chain_properties: config.chain_properties.clone(),
                 chain_is_live: config.chain_is_live,
-                genesis_block_hash: config.genesis_block_hash,
+                chain_fork_id: config.chain_fork_id.clone(),
+                chain_protocol_id: config.chain_protocol_id.clone(),
+                chain_spec: config.chain_spec.clone(),
+                genesis_block_hash,
                 consensus_service: config.consensus_service.clone(),
                 runtime_caches_service: runtime_caches_service.clone(),
+                metadata_cache: metadata_cache.clone(),
+                runtime_versions_cache: runtime_versions_cache.clone(),
+                expensive_requests_rate_limiter: expensive_requests_rate_limiter.clone(),
+                log_requests_durations: config.log_requests_durations,
+                state_get_keys_max_keys: config.state_get_keys_max_keys,
+                allow_unsafe_methods: config.allow_unsafe_methods,
+                subscribe_all_heads_report_initial_best: config
+                    .subscribe_all_heads_report_initial_best,
+                default_block: config.default_block,
+                database_timeout: config.database_timeout,
+                consensus_engine: config.consensus_engine,
+                finality_engine: config.finality_engine,
+                keystore: config.keystore.clone(),
             });
         }
 
@@ -194,8 +359,12 @@ impl JsonRpcService {
                 consensus_service: config.consensus_service.clone(),
                 database: config.database.clone(),
                 to_requests_handlers,
+                requests_to_handlers,
                 num_json_rpc_clients: Arc::new(AtomicU32::new(0)),
                 max_json_rpc_clients: config.max_json_rpc_clients,
+                max_json_rpc_subscriptions_per_client: config.max_json_rpc_subscriptions_per_client,
+                max_chain_head_pinned_blocks: config.max_chain_head_pinned_blocks,
+                max_request_size: config.max_request_size,
             };
 
             (config.tasks_executor)(Box::pin(async move { background.run().await }));
@@ -218,7 +387,7 @@ impl JsonRpcService {
 
     /// Adds a JSON-RPC request to the queue of requests of the virtual endpoint.
     ///
-    /// The virtual endpoint doesn't have any limit.
+    /// The virtual endpoint doesn't have any limit, except for [`Config::max_request_size`].
     pub fn send_request(&self, request: String) {
         match self.virtual_client_io.try_send_request(request) {
             Ok(()) => (),
@@ -253,6 +422,25 @@ pub enum InitError {
         /// Error returned by the operating system.
         error: io::Error,
     },
+    /// Failed to access the database in order to load the genesis block hash.
+    #[display(fmt = "Failed to load the genesis block hash from the database: {_0}")]
+    DatabaseCorrupted(full_sqlite::CorruptedError),
+    /// The genesis block hash found in the database doesn't match [`Config::genesis_block_hash`].
+    #[display(
+        fmt = "Mismatch between configured genesis block hash ({}) and the one in the database ({})",
+        "HashDisplay(configured)",
+        "HashDisplay(database)"
+    )]
+    GenesisHashMismatch {
+        /// Value in [`Config::genesis_block_hash`].
+        configured: [u8; 32],
+        /// Hash of the block number 0 found in the database.
+        database: [u8; 32],
+    },
+    /// The database doesn't contain the genesis block and [`Config::genesis_block_hash`] is
+    /// `None`.
+    #[display(fmt = "Genesis block hash unknown")]
+    GenesisHashUnknown,
 }
 
 struct JsonRpcBackground {
@@ -274,14 +462,27 @@ struct JsonRpcBackground {
     /// Consensus service of the chain.
     consensus_service: Arc<consensus_service::ConsensusService>,
 
-    /// Channel used to send requests to the tasks that process said requests.
+    /// Channel used to send subscription starts to the tasks that process said requests.
     to_requests_handlers: async_channel::Sender<requests_handler::Message>,
 
+    /// Channel used to send requests to the tasks that process said requests. See
+    /// [`Config::max_queued_requests`].
+    requests_to_handlers: async_channel::Sender<service::RequestProcess>,
+
     /// Number of clients currently alive.
     num_json_rpc_clients: Arc<AtomicU32>,
 
     /// See [`Config::max_json_rpc_clients`].
     max_json_rpc_clients: u32,
+
+    /// See [`Config::max_json_rpc_subscriptions_per_client`].
+    max_json_rpc_subscriptions_per_client: u32,
+
+    /// See [`Config::max_chain_head_pinned_blocks`].
+    max_chain_head_pinned_blocks: NonZero<usize>,
+
+    /// See [`Config::max_request_size`].
+    max_request_size: usize,
 }
 
 impl JsonRpcBackground {
@@ -348,8 +549,9 @@ impl JsonRpcBackground {
                 format!("json-rpc-incoming-connection; address={}", address),
             );
             let (client_main_task, io) = service::client_main_task(service::Config {
-                max_active_subscriptions: 128,
+                max_active_subscriptions: self.max_json_rpc_subscriptions_per_client,
                 max_pending_requests: NonZero::<u32>::new(64).unwrap(),
+                max_request_size: self.max_request_size,
             });
             spawn_client_io_task(
                 &self.tasks_executor,
@@ -357,6 +559,7 @@ impl JsonRpcBackground {
                 tcp_socket,
                 address,
                 io,
+                self.max_request_size,
                 self.num_json_rpc_clients.clone(),
             );
             spawn_client_main_task(
@@ -364,6 +567,8 @@ impl JsonRpcBackground {
                 self.consensus_service.clone(),
                 self.database.clone(),
                 self.to_requests_handlers.clone(),
+                self.requests_to_handlers.clone(),
+                self.max_chain_head_pinned_blocks,
                 client_main_task,
             );
         }
@@ -376,6 +581,7 @@ fn spawn_client_io_task(
     tcp_socket: TcpStream,
     socket_address: SocketAddr,
     io: service::SerializedRequestsIo,
+    max_request_size: usize,
     num_json_rpc_clients: Arc<AtomicU32>,
 ) {
     let run_future = async move {
@@ -418,7 +624,11 @@ fn spawn_client_io_task(
                 }
             }
 
-            ws_server.into_builder().finish()
+            // Messages larger than the maximum request size are refused by the WebSocket layer,
+            // so that they never get entirely buffered in memory.
+            let mut builder = ws_server.into_builder();
+            builder.set_max_message_size(max_request_size);
+            builder.finish()
         };
 
         // Create a future responsible for pulling responses and sending them back.
@@ -547,6 +757,8 @@ fn spawn_client_main_task(
     consensus_service: Arc<consensus_service::ConsensusService>,
     database: Arc<database_thread::DatabaseThread>,
     to_requests_handlers: async_channel::Sender<requests_handler::Message>,
+    requests_to_handlers: async_channel::Sender<service::RequestProcess>,
+    max_chain_head_pinned_blocks: NonZero<usize>,
     mut client_main_task: service::ClientMainTask,
 ) {
     let tasks_executor2 = tasks_executor.clone();
@@ -573,17 +785,108 @@ fn spawn_client_main_task(
                             if let Some(follow_subscription) =
                                 chain_head_follow_subscriptions.get_mut(&*follow_subscription)
                             {
-                                let _ = follow_subscription
+                                if let Err(err) = follow_subscription
                                     .send(chain_head_subscriptions::Message::Header {
                                         request: request_process,
                                     })
-                                    .await;
-                                // TODO racy; doesn't handle situation where follow subscription stops
+                                    .await
+                                {
+                                    // The follow subscription has been stopped.
+                                    let chain_head_subscriptions::Message::Header { request } =
+                                        err.into_inner()
+                                    else {
+                                        unreachable!()
+                                    };
+                                    request.respond(methods::Response::chainHead_v1_header(None));
+                                }
                             } else {
                                 request_process
                                     .respond(methods::Response::chainHead_v1_header(None));
                             }
                         }
+                        methods::MethodCall::chainHead_v1_storage {
+                            follow_subscription,
+                            ..
+                        } => {
+                            if let Some(follow_subscription) =
+                                chain_head_follow_subscriptions.get_mut(&*follow_subscription)
+                            {
+                                if let Err(err) = follow_subscription
+                                    .send(chain_head_subscriptions::Message::Storage {
+                                        request: request_process,
+                                    })
+                                    .await
+                                {
+                                    // The follow subscription has been stopped.
+                                    let chain_head_subscriptions::Message::Storage { request } =
+                                        err.into_inner()
+                                    else {
+                                        unreachable!()
+                                    };
+                                    request.respond(methods::Response::chainHead_v1_storage(
+                                        methods::ChainHeadStorageReturn::LimitReached {},
+                                    ));
+                                }
+                            } else {
+                                request_process.respond(methods::Response::chainHead_v1_storage(
+                                    methods::ChainHeadStorageReturn::LimitReached {},
+                                ));
+                            }
+                        }
+                        methods::MethodCall::chainHead_v1_continue {
+                            follow_subscription,
+                            ..
+                        } => {
+                            if let Some(follow_subscription) =
+                                chain_head_follow_subscriptions.get_mut(&*follow_subscription)
+                            {
+                                if let Err(err) = follow_subscription
+                                    .send(chain_head_subscriptions::Message::Continue {
+                                        request: request_process,
+                                    })
+                                    .await
+                                {
+                                    // The follow subscription has been stopped.
+                                    let chain_head_subscriptions::Message::Continue { request } =
+                                        err.into_inner()
+                                    else {
+                                        unreachable!()
+                                    };
+                                    request.respond(methods::Response::chainHead_v1_continue(()));
+                                }
+                            } else {
+                                request_process
+                                    .respond(methods::Response::chainHead_v1_continue(()));
+                            }
+                        }
+                        methods::MethodCall::chainHead_v1_stopOperation {
+                            follow_subscription,
+                            ..
+                        } => {
+                            if let Some(follow_subscription) =
+                                chain_head_follow_subscriptions.get_mut(&*follow_subscription)
+                            {
+                                if let Err(err) = follow_subscription
+                                    .send(chain_head_subscriptions::Message::StopOperation {
+                                        request: request_process,
+                                    })
+                                    .await
+                                {
+                                    // The follow subscription has been stopped.
+                                    let chain_head_subscriptions::Message::StopOperation {
+                                        request,
+                                    } = err.into_inner()
+                                    else {
+                                        unreachable!()
+                                    };
+                                    request
+                                        .respond(methods::Response::chainHead_v1_stopOperation(()));
+                                }
+                            } else {
+                                request_process
+                                    .respond(methods::Response::chainHead_v1_stopOperation(()));
+                            }
+                        }
                         methods::MethodCall::chainHead_v1_unpin {
                             follow_subscription,
                             hash_or_hashes,
@@ -624,10 +927,7 @@ fn spawn_client_main_task(
                             }
                         }
                         _ => {
-                            to_requests_handlers
-                                .send(requests_handler::Message::Request(request_process))
-                                .await
-                                .unwrap();
+                            queue_request(&requests_to_handlers, request_process);
                         }
                     }
                 }
@@ -648,6 +948,7 @@ fn spawn_client_main_task(
                                         receiver: rx,
                                         chain_head_follow_subscription: subscription_start,
                                         with_runtime,
+                                        max_pinned_blocks: max_chain_head_pinned_blocks,
                                         consensus_service: consensus_service.clone(),
                                         database: database.clone(),
                                     },
@@ -681,3 +982,166 @@ fn spawn_client_main_task(
         }
     }));
 }
+
+/// Sends a request to the requests handlers through `requests_to_handlers`.
+///
+/// If the queue is full, the oldest request is discarded in order to make space, and is
+/// answered with an error. This guarantees that a client sending requests faster than they can
+/// be processed can't block the other clients. See [`Config::max_queued_requests`].
+fn queue_request(
+    requests_to_handlers: &async_channel::Sender<service::RequestProcess>,
+    request_process: service::RequestProcess,
+) {
+    if let Some(discarded) = requests_to_handlers.force_send(request_process).unwrap() {
+        discarded.fail(service::ErrorResponse::ServerError(
+            -32000,
+            "Server is busy; try again later",
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{queue_request, InitError};
+    use crate::{consensus_service, database_thread, test_util};
+    use smoldot::{
+        database::full_sqlite,
+        json_rpc::{methods, parse, service},
+    };
+    use std::{iter, num::NonZero, sync::Arc};
+
+    #[test]
+    fn genesis_hash_mismatch() {
+        smol::block_on(async {
+            let chain_spec_json = include_bytes!("../tests/substrate-node-template.json");
+            let (database, genesis_hash, _, _) =
+                test_util::chain_spec_database(chain_spec_json).await;
+
+            let result = test_util::try_json_rpc_service(
+                Arc::new(database),
+                chain_spec_json,
+                None,
+                Some([0xff; 32]),
+            )
+            .await;
+
+            assert!(matches!(
+                result,
+                Err(InitError::GenesisHashMismatch {
+                    configured: [0xff, ..],
+                    database,
+                }) if database == genesis_hash
+            ));
+        });
+    }
+
+    #[test]
+    fn genesis_hash_unknown() {
+        smol::block_on(async {
+            let chain_spec_json = include_bytes!("../tests/substrate-node-template.json");
+            let (genesis_database, genesis_hash, genesis_runtime, _) =
+                test_util::chain_spec_database(chain_spec_json).await;
+            let genesis_header = genesis_database
+                .with_database(move |db| db.block_scale_encoded_header(&genesis_hash))
+                .await
+                .unwrap()
+                .unwrap();
+            let (_new_blocks_tx, new_blocks_rx) = async_channel::bounded(1);
+            let consensus_service = consensus_service::ConsensusService::from_notifications(
+                Arc::new(genesis_database),
+                4,
+                genesis_header,
+                genesis_runtime,
+                new_blocks_rx,
+            );
+
+            // Database whose oldest block isn't the genesis block, as if it had been populated
+            // through a warp sync.
+            let full_sqlite::DatabaseOpen::Empty(empty_db) =
+                full_sqlite::open(full_sqlite::Config {
+                    block_number_bytes: 4,
+                    cache_size: 2 * 1024 * 1024,
+                    ty: full_sqlite::ConfigTy::Memory,
+                })
+                .unwrap()
+            else {
+                panic!()
+            };
+            let database = empty_db
+                .initialize(
+                    &test_util::block_header(&[1; 32], 5, &[1; 32]),
+                    iter::empty(),
+                    None,
+                )
+                .unwrap();
+
+            let result = test_util::try_json_rpc_service(
+                Arc::new(database_thread::DatabaseThread::from(database)),
+                chain_spec_json,
+                Some(consensus_service),
+                None,
+            )
+            .await;
+
+            assert!(matches!(result, Err(InitError::GenesisHashUnknown)));
+        });
+    }
+
+    #[test]
+    fn queue_request_discards_oldest() {
+        smol::block_on(async {
+            let (mut task, io) = service::client_main_task(service::Config {
+                max_active_subscriptions: 0,
+                max_pending_requests: NonZero::<u32>::new(8).unwrap(),
+                max_request_size: usize::MAX,
+            });
+            let (requests_to_handlers, requests_from_background) = async_channel::bounded(1);
+
+            for id in 1..=2 {
+                io.try_send_request(format!(
+                    r#"{{"jsonrpc":"2.0","id":{id},"method":"system_name","params":[]}}"#
+                ))
+                .unwrap();
+                match task.run_until_event().await {
+                    service::Event::HandleRequest {
+                        task: t,
+                        request_process,
+                    } => {
+                        queue_request(&requests_to_handlers, request_process);
+                        task = t;
+                    }
+                    _ => panic!(),
+                }
+            }
+
+            // Only the most recent request remains in the queue, while the oldest one has
+            // been answered with an error.
+            assert_eq!(requests_from_background.len(), 1);
+            let queued = requests_from_background.recv().await.unwrap();
+            futures_lite::future::or(
+                async {
+                    let response = io.wait_next_response().await.unwrap();
+                    let parse::Response::Error { id_json, .. } =
+                        parse::parse_response(&response).unwrap()
+                    else {
+                        panic!()
+                    };
+                    assert_eq!(id_json, "1");
+
+                    queued.respond(methods::Response::system_name("foo".into()));
+                    let response = io.wait_next_response().await.unwrap();
+                    let (id_json, _) = parse::parse_response(&response)
+                        .unwrap()
+                        .into_success()
+                        .unwrap();
+                    assert_eq!(id_json, "2");
+                },
+                async {
+                    let _ = task.run_until_event().await;
+                    panic!()
+                },
+            )
+            .await;
+        });
+    }
+}
@@ -21,11 +21,15 @@ use futures_channel::oneshot;
 use futures_lite::FutureExt as _;
 use smol::stream::StreamExt as _;
 use smoldot::{
+    database::full_sqlite,
     executor,
     json_rpc::{methods, service},
+    trie,
 };
 use std::{
-    future::Future,
+    cmp,
+    collections::VecDeque,
+    future::{self, Future},
     num::NonZero,
     pin::{self, Pin},
     sync::Arc,
@@ -48,6 +52,10 @@ pub struct Config {
     /// Parameter that was passed by the user when requesting `chainHead_v1_follow`.
     pub with_runtime: bool,
 
+    /// Maximum number of blocks that the JSON-RPC client can keep pinned at the same time. If
+    /// this limit is exceeded, a `stop` event is generated and the subscription is terminated.
+    pub max_pinned_blocks: NonZero<usize>,
+
     /// Consensus service of the chain.
     pub consensus_service: Arc<consensus_service::ConsensusService>,
 
@@ -59,12 +67,84 @@ pub enum Message {
     Header {
         request: service::RequestProcess,
     },
+    Storage {
+        request: service::RequestProcess,
+    },
+    Continue {
+        request: service::RequestProcess,
+    },
+    StopOperation {
+        request: service::RequestProcess,
+    },
     Unpin {
         block_hashes: Vec<[u8; 32]>,
         outcome: oneshot::Sender<Result<(), ()>>,
     },
 }
 
+/// Maximum number of items that a `chainHead_v1_storage` operation reports in a single
+/// `operationStorageItems` event. Once this limit is reached, the operation is paused until
+/// `chainHead_v1_continue` is called.
+const STORAGE_ITEMS_PER_BATCH: usize = 16;
+
+/// Maximum number of `chainHead_v1_storage` operations that can be waiting for
+/// `chainHead_v1_continue` to be called at the same time within a single `chainHead_v1_follow`
+/// subscription. Additional operations are refused with `limitReached`.
+const MAX_STORAGE_OPERATIONS: usize = 16;
+
+/// Maximum number of block-related `chainHead_v1_followEvent` notifications that are buffered
+/// while the JSON-RPC client is too slow to receive them. If this limit is exceeded, a `stop`
+/// event is generated and the subscription is terminated.
+const MAX_PENDING_FOLLOW_EVENTS: usize = 64;
+
+/// Maximum number of ancestors of the finalized block that are reported, in addition to the
+/// finalized block itself, in the `initialized` event of a `chainHead_v1_follow` subscription.
+const MAX_INITIALIZED_FINALIZED_ANCESTORS: usize = 4;
+
+/// Generates the identifiers of the operations started within a `chainHead_v1_follow`
+/// subscription.
+///
+/// The identifiers are shared between all the types of operations and are never reused, even
+/// after the operation they designate has finished or has been stopped. A stale identifier
+/// passed by the JSON-RPC client can thus never accidentally designate a newer operation.
+struct OperationIdGenerator {
+    /// Identifier of the next operation.
+    next: u64,
+}
+
+impl OperationIdGenerator {
+    fn new() -> Self {
+        OperationIdGenerator { next: 0 }
+    }
+
+    /// Returns a new identifier that has never been returned before.
+    fn next(&mut self) -> String {
+        let id = self.next.to_string();
+        self.next = self.next.checked_add(1).unwrap();
+        id
+    }
+}
+
+/// `chainHead_v1_storage` operation waiting for `chainHead_v1_continue` to be called.
+struct StorageOperation {
+    /// Keeps the storage of the queried block accessible until the operation is over.
+    snapshot: database_thread::SnapshotHandle,
+    /// Items that remain to be queried.
+    progress: StorageQueryProgress,
+}
+
+/// See [`StorageOperation::progress`].
+struct StorageQueryProgress {
+    /// Path, in nibbles, of the child trie being queried, or `None` for the main trie.
+    parent_path: Option<Vec<u8>>,
+    /// Items that remain to be queried, in the order in which they were requested.
+    items: VecDeque<methods::ChainHeadStorageRequestItem>,
+    /// If the first entry of [`StorageQueryProgress::items`] is a descendants query that has
+    /// been interrupted, contains the key, in nibbles, of the last descendant that has been
+    /// reported. The query continues with the key that immediately follows.
+    descendants_cursor: Option<Vec<u8>>,
+}
+
 /// Spawns a new tasks dedicated to handling a `chainHead_v1_follow` subscription.
 ///
 /// Returns the identifier of the subscription.
@@ -77,25 +157,73 @@ pub async fn spawn_chain_head_subscription_task(config: Config) -> String {
     tasks_executor(Box::pin(async move {
         let consensus_service_subscription = config
             .consensus_service
-            .subscribe_all(32, NonZero::<usize>::new(32).unwrap())
+            .subscribe_all(32, config.max_pinned_blocks)
             .await;
         let mut consensus_service_subscription_new_blocks =
             pin::pin!(consensus_service_subscription.new_blocks);
 
-        let mut foreground_receiver = pin::pin!(config.receiver);
+        let foreground_receiver = config.receiver;
 
         let mut pinned_blocks =
             hashbrown::HashSet::with_capacity_and_hasher(32, fnv::FnvBuildHasher::default());
         let mut current_best_block = consensus_service_subscription.finalized_block_hash;
+        let mut operation_ids = OperationIdGenerator::new();
+        let mut pending_follow_events = PendingFollowEvents::new(MAX_PENDING_FOLLOW_EVENTS);
+        let mut storage_operations =
+            hashbrown::HashMap::with_capacity_and_hasher(4, fnv::FnvBuildHasher::default());
 
+        // The storage of the blocks reported by the consensus service is pinned in the database
+        // as long as the JSON-RPC client keeps them pinned. Without this, the storage of a block
+        // that isn't part of the finalized chain would be pruned once the finalized block
+        // progresses past its height, even though the JSON-RPC client is allowed to perform
+        // operations on it until it unpins it.
+        let mut pinned_blocks_storage =
+            hashbrown::HashMap::with_capacity_and_hasher(32, fnv::FnvBuildHasher::default());
+        if let Ok(snapshot) = config
+            .database
+            .snapshot_at(consensus_service_subscription.finalized_block_hash)
+            .await
+        {
+            pinned_blocks_storage.insert(
+                consensus_service_subscription.finalized_block_hash,
+                snapshot,
+            );
+        }
+
+        // Some ancestors of the finalized block are reported alongside with it, so that the
+        // JSON-RPC client can build its tree of blocks. The consensus service doesn't pin these
+        // ancestors, and their storage is instead pinned in the database until they are unpinned
+        // by the JSON-RPC client.
+        let finalized_ancestors = pin_finalized_ancestors(
+            &config.database,
+            consensus_service_subscription.finalized_block_hash,
+            cmp::min(
+                MAX_INITIALIZED_FINALIZED_ANCESTORS,
+                config.max_pinned_blocks.get() - 1,
+            ),
+        )
+        .await;
+
+        let mut finalized_block_hashes = Vec::with_capacity(finalized_ancestors.len() + 1);
+        for ancestor in &finalized_ancestors {
+            pinned_blocks.insert(*ancestor.block_hash());
+            finalized_block_hashes.push(methods::HashHexString(*ancestor.block_hash()));
+        }
         pinned_blocks.insert(consensus_service_subscription.finalized_block_hash);
+        finalized_block_hashes.push(methods::HashHexString(
+            consensus_service_subscription.finalized_block_hash,
+        ));
+
+        let mut finalized_ancestors = finalized_ancestors
+            .into_iter()
+            .map(|snapshot| (*snapshot.block_hash(), snapshot))
+            .collect::<hashbrown::HashMap<_, _, fnv::FnvBuildHasher>>();
+
         json_rpc_subscription
             .send_notification(methods::ServerToClient::chainHead_v1_followEvent {
                 subscription: (&json_rpc_subscription_id).into(),
                 result: methods::FollowEvent::Initialized {
-                    finalized_block_hashes: vec![methods::HashHexString(
-                        consensus_service_subscription.finalized_block_hash,
-                    )],
+                    finalized_block_hashes,
                     finalized_block_runtime: if config.with_runtime {
                         Some(convert_runtime_spec(
                             consensus_service_subscription
@@ -111,6 +239,9 @@ pub async fn spawn_chain_head_subscription_task(config: Config) -> String {
 
         for block in consensus_service_subscription.non_finalized_blocks_ancestry_order {
             pinned_blocks.insert(block.block_hash);
+            if let Ok(snapshot) = config.database.snapshot_at(block.block_hash).await {
+                pinned_blocks_storage.insert(block.block_hash, snapshot);
+            }
             json_rpc_subscription
                 .send_notification(methods::ServerToClient::chainHead_v1_followEvent {
                     subscription: (&json_rpc_subscription_id).into(),
@@ -142,11 +273,18 @@ pub async fn spawn_chain_head_subscription_task(config: Config) -> String {
         }
 
         loop {
+            if pinned_blocks.len() > config.max_pinned_blocks.get() {
+                // The JSON-RPC client doesn't unpin blocks quickly enough. Rather than keeping
+                // an unbounded number of blocks pinned, the subscription is stopped.
+                break;
+            }
+
             enum WakeUpReason {
                 ConsensusNotification(consensus_service::Notification),
                 ConsensusSubscriptionStop,
                 Foreground(Message),
                 ForegroundClosed,
+                FollowEventSent,
             }
 
             let wake_up_reason = async {
@@ -160,14 +298,31 @@ pub async fn spawn_chain_head_subscription_task(config: Config) -> String {
             }
             .or(async {
                 foreground_receiver
-                    .next()
+                    .recv()
                     .await
-                    .map_or(WakeUpReason::ForegroundClosed, WakeUpReason::Foreground)
+                    .map_or_else(|_| WakeUpReason::ForegroundClosed, WakeUpReason::Foreground)
+            })
+            .or(async {
+                // The buffered events are sent one by one while the other events continue to be
+                // processed, so that a slow JSON-RPC client doesn't block this task.
+                let Some(event) = pending_follow_events.front() else {
+                    future::pending().await
+                };
+                json_rpc_subscription
+                    .send_notification(methods::ServerToClient::chainHead_v1_followEvent {
+                        subscription: (&json_rpc_subscription_id).into(),
+                        result: event.to_follow_event(config.with_runtime),
+                    })
+                    .await;
+                WakeUpReason::FollowEventSent
             })
             .await;
 
             match wake_up_reason {
                 WakeUpReason::ForegroundClosed => return,
+                WakeUpReason::FollowEventSent => {
+                    pending_follow_events.pop_front();
+                }
                 WakeUpReason::Foreground(Message::Header { request }) => {
                     let methods::MethodCall::chainHead_v1_header { hash, .. } = request.request()
                     else {
@@ -201,6 +356,136 @@ pub async fn spawn_chain_head_subscription_task(config: Config) -> String {
                         }
                     }
                 }
+                WakeUpReason::Foreground(Message::Storage { request }) => {
+                    let methods::MethodCall::chainHead_v1_storage {
+                        hash,
+                        items,
+                        child_trie,
+                        ..
+                    } = request.request()
+                    else {
+                        unreachable!()
+                    };
+
+                    if !pinned_blocks.contains(&hash.0) {
+                        request.fail(service::ErrorResponse::InvalidParams);
+                        continue;
+                    }
+
+                    if storage_operations.len() >= MAX_STORAGE_OPERATIONS {
+                        request.respond(methods::Response::chainHead_v1_storage(
+                            methods::ChainHeadStorageReturn::LimitReached {},
+                        ));
+                        continue;
+                    }
+
+                    let operation_id = operation_ids.next();
+
+                    // All the items are eventually processed, and thus none of them is ever
+                    // discarded.
+                    request.respond(methods::Response::chainHead_v1_storage(
+                        methods::ChainHeadStorageReturn::Started {
+                            operation_id: (&operation_id).into(),
+                            discarded_items: 0,
+                        },
+                    ));
+
+                    let snapshot = match config.database.snapshot_at(hash.0).await {
+                        Ok(snapshot) => snapshot,
+                        Err(full_sqlite::StorageAccessError::StoragePruned) => {
+                            json_rpc_subscription
+                                .send_notification(
+                                    methods::ServerToClient::chainHead_v1_followEvent {
+                                        subscription: (&json_rpc_subscription_id).into(),
+                                        result: methods::FollowEvent::OperationInaccessible {
+                                            operation_id: (&operation_id).into(),
+                                        },
+                                    },
+                                )
+                                .await;
+                            continue;
+                        }
+                        Err(error) => {
+                            json_rpc_subscription
+                                .send_notification(
+                                    methods::ServerToClient::chainHead_v1_followEvent {
+                                        subscription: (&json_rpc_subscription_id).into(),
+                                        result: methods::FollowEvent::OperationError {
+                                            operation_id: (&operation_id).into(),
+                                            error: error.to_string().into(),
+                                        },
+                                    },
+                                )
+                                .await;
+                            continue;
+                        }
+                    };
+
+                    let operation = StorageOperation {
+                        snapshot,
+                        progress: StorageQueryProgress {
+                            parent_path: child_trie.map(|child_trie| {
+                                trie::bytes_to_nibbles(b":child_storage:default:".iter().copied())
+                                    .chain(trie::bytes_to_nibbles(child_trie.0.into_iter()))
+                                    .map(u8::from)
+                                    .collect::<Vec<_>>()
+                            }),
+                            items: items.into_iter().collect(),
+                            descendants_cursor: None,
+                        },
+                    };
+
+                    if let Some(operation) = advance_storage_operation(
+                        &mut json_rpc_subscription,
+                        &json_rpc_subscription_id,
+                        &operation_id,
+                        operation,
+                    )
+                    .await
+                    {
+                        let _previous = storage_operations.insert(operation_id, operation);
+                        debug_assert!(_previous.is_none());
+                    }
+                }
+                WakeUpReason::Foreground(Message::Continue { request }) => {
+                    let methods::MethodCall::chainHead_v1_continue { operation_id, .. } =
+                        request.request()
+                    else {
+                        unreachable!()
+                    };
+
+                    let Some((operation_id, operation)) =
+                        storage_operations.remove_entry(&*operation_id)
+                    else {
+                        request.fail(service::ErrorResponse::InvalidParams);
+                        continue;
+                    };
+
+                    request.respond(methods::Response::chainHead_v1_continue(()));
+
+                    if let Some(operation) = advance_storage_operation(
+                        &mut json_rpc_subscription,
+                        &json_rpc_subscription_id,
+                        &operation_id,
+                        operation,
+                    )
+                    .await
+                    {
+                        storage_operations.insert(operation_id, operation);
+                    }
+                }
+                WakeUpReason::Foreground(Message::StopOperation { request }) => {
+                    let methods::MethodCall::chainHead_v1_stopOperation { operation_id, .. } =
+                        request.request()
+                    else {
+                        unreachable!()
+                    };
+
+                    // Operations that don't exist are silently ignored, as mandated by the
+                    // JSON-RPC specification.
+                    storage_operations.remove(&*operation_id);
+                    request.respond(methods::Response::chainHead_v1_stopOperation(()));
+                }
                 WakeUpReason::Foreground(Message::Unpin {
                     block_hashes,
                     outcome,
@@ -210,6 +495,11 @@ pub async fn spawn_chain_head_subscription_task(config: Config) -> String {
                     } else {
                         for block_hash in block_hashes {
                             pinned_blocks.remove(&block_hash);
+                            pinned_blocks_storage.remove(&block_hash);
+                            // Dropping the snapshot of a finalized ancestor unpins its storage.
+                            if finalized_ancestors.remove(&block_hash).is_some() {
+                                continue;
+                            }
                             config
                                 .consensus_service
                                 .unpin_block(consensus_service_subscription.id, block_hash)
@@ -223,33 +513,33 @@ pub async fn spawn_chain_head_subscription_task(config: Config) -> String {
                     ..
                 }) => {
                     pinned_blocks.insert(block.block_hash);
-                    json_rpc_subscription
-                        .send_notification(methods::ServerToClient::chainHead_v1_followEvent {
-                            subscription: (&json_rpc_subscription_id).into(),
-                            result: methods::FollowEvent::NewBlock {
-                                block_hash: methods::HashHexString(block.block_hash),
-                                new_runtime: if let (Some(new_runtime), true) =
-                                    (&block.runtime_update, config.with_runtime)
-                                {
-                                    Some(convert_runtime_spec(new_runtime.runtime_version()))
-                                } else {
-                                    None
-                                },
-                                parent_block_hash: methods::HashHexString(block.parent_hash),
-                            },
+                    // The block has been stored in the database before being reported.
+                    if let Ok(snapshot) = config.database.snapshot_at(block.block_hash).await {
+                        pinned_blocks_storage.insert(block.block_hash, snapshot);
+                    }
+                    if pending_follow_events
+                        .push(PendingFollowEvent::NewBlock {
+                            block_hash: block.block_hash,
+                            parent_hash: block.parent_hash,
+                            new_runtime: block.runtime_update,
                         })
-                        .await;
+                        .is_err()
+                    {
+                        // `newBlock` events can't be merged with each other, as the JSON-RPC
+                        // client needs to be aware of every block that is pinned.
+                        break;
+                    }
 
                     if block.is_new_best {
                         current_best_block = block.block_hash;
-                        json_rpc_subscription
-                            .send_notification(methods::ServerToClient::chainHead_v1_followEvent {
-                                subscription: (&json_rpc_subscription_id).into(),
-                                result: methods::FollowEvent::BestBlockChanged {
-                                    best_block_hash: methods::HashHexString(block.block_hash),
-                                },
+                        if pending_follow_events
+                            .push(PendingFollowEvent::BestBlockChanged {
+                                best_block_hash: block.block_hash,
                             })
-                            .await;
+                            .is_err()
+                        {
+                            break;
+                        }
                     }
                 }
                 WakeUpReason::ConsensusNotification(
@@ -259,53 +549,466 @@ pub async fn spawn_chain_head_subscription_task(config: Config) -> String {
                         best_block_hash,
                     },
                 ) => {
-                    json_rpc_subscription
-                        .send_notification(methods::ServerToClient::chainHead_v1_followEvent {
-                            subscription: (&json_rpc_subscription_id).into(),
-                            result: methods::FollowEvent::Finalized {
-                                // As specified in the JSON-RPC spec, the list must be ordered
-                                // in increasing block number. Consequently we have to reverse
-                                // the list.
-                                finalized_blocks_hashes: finalized_blocks_newest_to_oldest
-                                    .into_iter()
-                                    .map(methods::HashHexString)
-                                    .rev()
-                                    .collect(),
-                                pruned_blocks_hashes: pruned_blocks_hashes
-                                    .into_iter()
-                                    .map(methods::HashHexString)
-                                    .collect(),
-                            },
+                    if pending_follow_events
+                        .push(PendingFollowEvent::Finalized {
+                            finalized_blocks_newest_to_oldest,
+                            pruned_blocks_hashes,
                         })
-                        .await;
+                        .is_err()
+                    {
+                        break;
+                    }
 
                     if best_block_hash != current_best_block {
                         current_best_block = best_block_hash;
-                        json_rpc_subscription
-                            .send_notification(methods::ServerToClient::chainHead_v1_followEvent {
-                                subscription: (&json_rpc_subscription_id).into(),
-                                result: methods::FollowEvent::BestBlockChanged {
-                                    best_block_hash: methods::HashHexString(best_block_hash),
-                                },
-                            })
-                            .await;
+                        if pending_follow_events
+                            .push(PendingFollowEvent::BestBlockChanged { best_block_hash })
+                            .is_err()
+                        {
+                            break;
+                        }
                     }
                 }
                 WakeUpReason::ConsensusSubscriptionStop => {
-                    json_rpc_subscription
-                        .send_notification(methods::ServerToClient::chainHead_v1_followEvent {
-                            subscription: (&json_rpc_subscription_id).into(),
-                            result: methods::FollowEvent::Stop {},
-                        })
-                        .await;
+                    // The consensus service closes the subscription if the notifications aren't
+                    // processed quickly enough, in other words if this task has fallen behind.
+                    break;
                 }
             }
         }
+
+        // The subscription can no longer be kept consistent and is stopped. As required by the
+        // JSON-RPC specification, the client is notified with a `stop` event, after which it is
+        // expected to subscribe again.
+        json_rpc_subscription
+            .send_notification(methods::ServerToClient::chainHead_v1_followEvent {
+                subscription: (&json_rpc_subscription_id).into(),
+                result: methods::FollowEvent::Stop {},
+            })
+            .await;
+
+        for block_hash in pinned_blocks {
+            if finalized_ancestors.remove(&block_hash).is_some() {
+                continue;
+            }
+            config
+                .consensus_service
+                .unpin_block(consensus_service_subscription.id, block_hash)
+                .await;
+        }
+
+        // Answer the messages that were sent before the receiver is closed. The JSON-RPC
+        // specification mandates that operations that concern a stopped subscription are
+        // answered as if the subscription didn't exist.
+        foreground_receiver.close();
+        while let Ok(message) = foreground_receiver.try_recv() {
+            match message {
+                Message::Header { request } => {
+                    request.respond(methods::Response::chainHead_v1_header(None));
+                }
+                Message::Storage { request } => {
+                    request.respond(methods::Response::chainHead_v1_storage(
+                        methods::ChainHeadStorageReturn::LimitReached {},
+                    ));
+                }
+                Message::Continue { request } => {
+                    request.respond(methods::Response::chainHead_v1_continue(()));
+                }
+                Message::StopOperation { request } => {
+                    request.respond(methods::Response::chainHead_v1_stopOperation(()));
+                }
+                Message::Unpin { outcome, .. } => {
+                    let _ = outcome.send(Ok(()));
+                }
+            }
+        }
+
+        // Dropping `json_rpc_subscription` notifies the client main task that the subscription
+        // is dead.
     }));
 
     return_value
 }
 
+/// Block-related `chainHead_v1_followEvent` notification waiting to be sent to the JSON-RPC
+/// client.
+enum PendingFollowEvent {
+    NewBlock {
+        block_hash: [u8; 32],
+        parent_hash: [u8; 32],
+        new_runtime: Option<Arc<executor::host::HostVmPrototype>>,
+    },
+    BestBlockChanged {
+        best_block_hash: [u8; 32],
+    },
+    Finalized {
+        finalized_blocks_newest_to_oldest: Vec<[u8; 32]>,
+        pruned_blocks_hashes: Vec<[u8; 32]>,
+    },
+}
+
+impl PendingFollowEvent {
+    /// Builds the event to send to the JSON-RPC client.
+    fn to_follow_event(&self, with_runtime: bool) -> methods::FollowEvent<'_> {
+        match self {
+            PendingFollowEvent::NewBlock {
+                block_hash,
+                parent_hash,
+                new_runtime,
+            } => methods::FollowEvent::NewBlock {
+                block_hash: methods::HashHexString(*block_hash),
+                new_runtime: if let (Some(new_runtime), true) = (new_runtime, with_runtime) {
+                    Some(convert_runtime_spec(new_runtime.runtime_version()))
+                } else {
+                    None
+                },
+                parent_block_hash: methods::HashHexString(*parent_hash),
+            },
+            PendingFollowEvent::BestBlockChanged { best_block_hash } => {
+                methods::FollowEvent::BestBlockChanged {
+                    best_block_hash: methods::HashHexString(*best_block_hash),
+                }
+            }
+            PendingFollowEvent::Finalized {
+                finalized_blocks_newest_to_oldest,
+                pruned_blocks_hashes,
+            } => methods::FollowEvent::Finalized {
+                // As specified in the JSON-RPC spec, the list must be ordered in increasing
+                // block number. Consequently we have to reverse the list.
+                finalized_blocks_hashes: finalized_blocks_newest_to_oldest
+                    .iter()
+                    .rev()
+                    .copied()
+                    .map(methods::HashHexString)
+                    .collect(),
+                pruned_blocks_hashes: pruned_blocks_hashes
+                    .iter()
+                    .copied()
+                    .map(methods::HashHexString)
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// Bounded queue of [`PendingFollowEvent`]s.
+///
+/// Only the latest best block matters to the JSON-RPC client, and a `bestBlockChanged` event
+/// thus replaces the `bestBlockChanged` event queued since the last `finalized` event, if any.
+/// Other events can't be merged and are refused once the queue is full.
+struct PendingFollowEvents {
+    queue: VecDeque<PendingFollowEvent>,
+    max_len: usize,
+}
+
+impl PendingFollowEvents {
+    fn new(max_len: usize) -> Self {
+        PendingFollowEvents {
+            queue: VecDeque::with_capacity(max_len),
+            max_len,
+        }
+    }
+
+    /// Pushes an event at the back of the queue.
+    ///
+    /// Returns an error if the queue is full and the event couldn't be merged with an event of
+    /// the queue, in which case the subscription must be stopped.
+    fn push(&mut self, event: PendingFollowEvent) -> Result<(), ()> {
+        if matches!(event, PendingFollowEvent::BestBlockChanged { .. }) {
+            // The queued `bestBlockChanged` event is removed, and the new one pushed at the back
+            // of the queue, as it must come after the `newBlock` event of its block.
+            // `bestBlockChanged` events that precede a `finalized` event must be kept, as the
+            // JSON-RPC client would otherwise see its current best block being pruned.
+            let queued = self
+                .queue
+                .iter()
+                .rev()
+                .take_while(|ev| !matches!(ev, PendingFollowEvent::Finalized { .. }))
+                .position(|ev| matches!(ev, PendingFollowEvent::BestBlockChanged { .. }));
+            if let Some(position_from_back) = queued {
+                self.queue.remove(self.queue.len() - 1 - position_from_back);
+            }
+        }
+
+        if self.queue.len() >= self.max_len {
+            return Err(());
+        }
+
+        self.queue.push_back(event);
+        Ok(())
+    }
+
+    /// Returns the event that must be sent next, if any.
+    fn front(&self) -> Option<&PendingFollowEvent> {
+        self.queue.front()
+    }
+
+    /// Removes the event returned by [`PendingFollowEvents::front`], after it has been sent.
+    fn pop_front(&mut self) {
+        self.queue.pop_front();
+    }
+}
+
+/// Queries the next batch of items of the given `chainHead_v1_storage` operation and reports
+/// them to the JSON-RPC client.
+///
+/// Returns the operation back if it has been paused and now waits for `chainHead_v1_continue` to
+/// be called, or `None` if it is over.
+async fn advance_storage_operation(
+    json_rpc_subscription: &mut service::Subscription,
+    subscription_id: &str,
+    operation_id: &str,
+    mut operation: StorageOperation,
+) -> Option<StorageOperation> {
+    let progress = operation.progress;
+    let outcome = operation
+        .snapshot
+        .with_database(move |database, block_hash| {
+            storage_query(database, block_hash, progress, STORAGE_ITEMS_PER_BATCH)
+        })
+        .await;
+
+    let (items, progress) = match outcome {
+        Ok(outcome) => outcome,
+        Err(full_sqlite::StorageAccessError::StoragePruned) => {
+            json_rpc_subscription
+                .send_notification(methods::ServerToClient::chainHead_v1_followEvent {
+                    subscription: subscription_id.into(),
+                    result: methods::FollowEvent::OperationInaccessible {
+                        operation_id: operation_id.into(),
+                    },
+                })
+                .await;
+            return None;
+        }
+        Err(error) => {
+            json_rpc_subscription
+                .send_notification(methods::ServerToClient::chainHead_v1_followEvent {
+                    subscription: subscription_id.into(),
+                    result: methods::FollowEvent::OperationError {
+                        operation_id: operation_id.into(),
+                        error: error.to_string().into(),
+                    },
+                })
+                .await;
+            return None;
+        }
+    };
+
+    if !items.is_empty() {
+        json_rpc_subscription
+            .send_notification(methods::ServerToClient::chainHead_v1_followEvent {
+                subscription: subscription_id.into(),
+                result: methods::FollowEvent::OperationStorageItems {
+                    operation_id: operation_id.into(),
+                    items,
+                },
+            })
+            .await;
+    }
+
+    if progress.items.is_empty() {
+        json_rpc_subscription
+            .send_notification(methods::ServerToClient::chainHead_v1_followEvent {
+                subscription: subscription_id.into(),
+                result: methods::FollowEvent::OperationStorageDone {
+                    operation_id: operation_id.into(),
+                },
+            })
+            .await;
+        return None;
+    }
+
+    json_rpc_subscription
+        .send_notification(methods::ServerToClient::chainHead_v1_followEvent {
+            subscription: subscription_id.into(),
+            result: methods::FollowEvent::OperationWaitingForContinue {
+                operation_id: operation_id.into(),
+            },
+        })
+        .await;
+
+    operation.progress = progress;
+    Some(operation)
+}
+
+/// Performs the storage queries of a `chainHead_v1_storage` operation against the given block,
+/// until either all the items have been queried or `max_items` response items have been
+/// generated.
+///
+/// Response items are generated in the order of the requested items, and descendants are
+/// reported in lexicographic order of their keys. The returned [`StorageQueryProgress`] can be
+/// passed again to this function in order to continue the operation.
+fn storage_query(
+    database: &full_sqlite::SqliteFullDatabase,
+    block_hash: &[u8; 32],
+    mut progress: StorageQueryProgress,
+    max_items: usize,
+) -> Result<
+    (
+        Vec<methods::ChainHeadStorageResponseItem>,
+        StorageQueryProgress,
+    ),
+    full_sqlite::StorageAccessError,
+> {
+    let mut out = Vec::with_capacity(cmp::min(progress.items.len(), max_items));
+
+    while out.len() < max_items {
+        let Some(item) = progress.items.front() else {
+            break;
+        };
+
+        let key_nibbles = trie::bytes_to_nibbles(item.key.0.iter().copied())
+            .map(u8::from)
+            .collect::<Vec<_>>();
+
+        match item.ty {
+            methods::ChainHeadStorageType::Value | methods::ChainHeadStorageType::Hash => {
+                if let Some((value, _)) = database.block_storage_get(
+                    block_hash,
+                    progress.parent_path.iter().map(|p| p.iter().copied()),
+                    key_nibbles.iter().copied(),
+                )? {
+                    out.push(storage_response_item(item.key.0.clone(), value, &item.ty));
+                }
+            }
+            methods::ChainHeadStorageType::ClosestDescendantMerkleValue => {
+                if let Some(merkle_value) = database.block_storage_closest_descendant_merkle_value(
+                    block_hash,
+                    progress.parent_path.iter().map(|p| p.iter().copied()),
+                    key_nibbles.iter().copied(),
+                )? {
+                    out.push(methods::ChainHeadStorageResponseItem {
+                        key: item.key.clone(),
+                        value: None,
+                        hash: None,
+                        closest_descendant_merkle_value: Some(methods::HexString(merkle_value)),
+                    });
+                }
+            }
+            methods::ChainHeadStorageType::DescendantsValues
+            | methods::ChainHeadStorageType::DescendantsHashes => {
+                // The descendants are found by repeatedly asking for the next key, starting
+                // right after the last descendant that has been reported, if any.
+                // An extra nibble is pushed after the last reported key as otherwise
+                // `block_storage_next_key` would return the same key again.
+                let mut key_iter = match progress.descendants_cursor.take() {
+                    Some(mut last_reported) => {
+                        last_reported.push(0);
+                        last_reported
+                    }
+                    None => key_nibbles.clone(),
+                };
+
+                while let Some(next_key_nibbles) = database.block_storage_next_key(
+                    block_hash,
+                    progress.parent_path.iter().map(|p| p.iter().copied()),
+                    key_iter.iter().copied(),
+                    key_nibbles.iter().copied(),
+                    false,
+                )? {
+                    let Some((value, _)) = database.block_storage_get(
+                        block_hash,
+                        progress.parent_path.iter().map(|p| p.iter().copied()),
+                        next_key_nibbles.iter().copied(),
+                    )?
+                    else {
+                        // Should never happen given that `branch_nodes` is `false`.
+                        return Err(full_sqlite::StorageAccessError::IncompleteStorage);
+                    };
+
+                    let key = trie::nibbles_to_bytes_truncate(
+                        next_key_nibbles
+                            .iter()
+                            .copied()
+                            .map(|n| trie::Nibble::try_from(n).unwrap()),
+                    )
+                    .collect::<Vec<_>>();
+                    out.push(storage_response_item(key, value, &item.ty));
+
+                    // Interrupt the query if the batch is full. It later continues after the
+                    // key that has just been reported.
+                    if out.len() >= max_items {
+                        progress.descendants_cursor = Some(next_key_nibbles);
+                        return Ok((out, progress));
+                    }
+
+                    key_iter = next_key_nibbles;
+                    key_iter.push(0);
+                }
+            }
+        }
+
+        progress.items.pop_front();
+    }
+
+    Ok((out, progress))
+}
+
+/// Builds a [`methods::ChainHeadStorageResponseItem`] containing either the value or the hash
+/// of the value, depending on the type of the request.
+fn storage_response_item(
+    key: Vec<u8>,
+    value: Vec<u8>,
+    ty: &methods::ChainHeadStorageType,
+) -> methods::ChainHeadStorageResponseItem {
+    let (value, hash) = match ty {
+        methods::ChainHeadStorageType::Value | methods::ChainHeadStorageType::DescendantsValues => {
+            (Some(methods::HexString(value)), None)
+        }
+        _ => (
+            None,
+            Some(methods::HexString(
+                blake2_rfc::blake2b::blake2b(32, &[], &value)
+                    .as_bytes()
+                    .to_vec(),
+            )),
+        ),
+    };
+
+    methods::ChainHeadStorageResponseItem {
+        key: methods::HexString(key),
+        value,
+        hash,
+        closest_descendant_merkle_value: None,
+    }
+}
+
+/// Pins the storage of up to `max` ancestors of the given finalized block, starting with its
+/// parent and stopping at the first block that isn't in the database or whose storage has been
+/// pruned.
+///
+/// The returned snapshots are ordered by increasing block number. The storage of each block
+/// remains pinned until its snapshot is destroyed.
+async fn pin_finalized_ancestors(
+    database: &Arc<database_thread::DatabaseThread>,
+    finalized_block_hash: [u8; 32],
+    max: usize,
+) -> Vec<database_thread::SnapshotHandle> {
+    let mut ancestors = Vec::with_capacity(max);
+    let mut current = finalized_block_hash;
+
+    while ancestors.len() < max {
+        let Ok(Some(parent_hash)) = database
+            .with_database(move |database| database.block_parent(&current))
+            .await
+        else {
+            break;
+        };
+
+        // The parent of the genesis block isn't in the database, in which case pinning fails.
+        let Ok(snapshot) = database.snapshot_at(parent_hash).await else {
+            break;
+        };
+
+        ancestors.push(snapshot);
+        current = parent_hash;
+    }
+
+    ancestors.reverse();
+    ancestors
+}
+
 fn convert_runtime_spec(runtime: &executor::CoreVersion) -> methods::MaybeRuntimeSpec {
     let runtime = runtime.decode();
     methods::MaybeRuntimeSpec::Valid {
@@ -322,3 +1025,425 @@ fn convert_runtime_spec(runtime: &executor::CoreVersion) -> methods::MaybeRuntim
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        pin_finalized_ancestors, storage_query, OperationIdGenerator, PendingFollowEvent,
+        PendingFollowEvents, StorageQueryProgress,
+    };
+    use crate::{database_thread, test_util};
+    use smoldot::{database::full_sqlite, json_rpc::methods};
+    use std::{borrow::Cow, collections::BTreeMap, sync::Arc};
+
+    fn new_block(n: u8) -> PendingFollowEvent {
+        PendingFollowEvent::NewBlock {
+            block_hash: [n; 32],
+            parent_hash: [n.wrapping_sub(1); 32],
+            new_runtime: None,
+        }
+    }
+
+    fn best_block_changed(n: u8) -> PendingFollowEvent {
+        PendingFollowEvent::BestBlockChanged {
+            best_block_hash: [n; 32],
+        }
+    }
+
+    #[test]
+    fn best_block_changed_coalesced() {
+        let mut queue = PendingFollowEvents::new(3);
+        queue.push(new_block(1)).unwrap();
+        queue.push(best_block_changed(1)).unwrap();
+        queue.push(new_block(2)).unwrap();
+
+        // The queue is full, but `bestBlockChanged` events are still accepted as long as they
+        // can be merged with a queued one.
+        queue.push(best_block_changed(2)).unwrap();
+        assert!(queue.push(new_block(3)).is_err());
+        queue.pop_front();
+        queue.push(new_block(3)).unwrap();
+        queue.push(best_block_changed(3)).unwrap();
+
+        assert!(matches!(
+            queue.front(),
+            Some(PendingFollowEvent::NewBlock { block_hash, .. }) if *block_hash == [2; 32]
+        ));
+        queue.pop_front();
+        assert!(matches!(
+            queue.front(),
+            Some(PendingFollowEvent::NewBlock { block_hash, .. }) if *block_hash == [3; 32]
+        ));
+        queue.pop_front();
+        assert!(matches!(
+            queue.front(),
+            Some(PendingFollowEvent::BestBlockChanged { best_block_hash }) if *best_block_hash == [3; 32]
+        ));
+        queue.pop_front();
+        assert!(queue.front().is_none());
+    }
+
+    #[test]
+    fn best_block_changed_not_coalesced_across_finalized() {
+        let mut queue = PendingFollowEvents::new(8);
+        queue.push(best_block_changed(1)).unwrap();
+        queue.push(new_block(2)).unwrap();
+        queue.push(best_block_changed(2)).unwrap();
+        queue
+            .push(PendingFollowEvent::Finalized {
+                finalized_blocks_newest_to_oldest: vec![[1; 32]],
+                pruned_blocks_hashes: Vec::new(),
+            })
+            .unwrap();
+        queue.push(best_block_changed(3)).unwrap();
+        queue.push(best_block_changed(4)).unwrap();
+
+        let mut best_blocks = Vec::new();
+        while let Some(event) = queue.front() {
+            match event {
+                PendingFollowEvent::BestBlockChanged { best_block_hash } => {
+                    best_blocks.push(best_block_hash[0])
+                }
+                PendingFollowEvent::Finalized { .. } => best_blocks.push(0),
+                PendingFollowEvent::NewBlock { .. } => {}
+            }
+            queue.pop_front();
+        }
+        assert_eq!(best_blocks, vec![2, 0, 4]);
+    }
+
+    #[test]
+    fn new_block_refused_when_full() {
+        let mut queue = PendingFollowEvents::new(2);
+        queue.push(new_block(1)).unwrap();
+        queue.push(new_block(2)).unwrap();
+        assert!(queue.push(new_block(3)).is_err());
+        queue.pop_front();
+        queue.push(new_block(3)).unwrap();
+    }
+
+    #[test]
+    fn initialized_finalized_ancestors_pinned() {
+        // Builds a chain of four blocks, the last one being finalized.
+        let (db, genesis_hash) = test_util::memory_database();
+        let mut hashes = vec![genesis_hash];
+        for number in 1..=3 {
+            let parent_hash = *hashes.last().unwrap();
+            hashes.push(test_util::insert_block(&db, &parent_hash, number, &[1; 32]));
+        }
+        db.set_finalized(&hashes[3]).unwrap();
+        let database = Arc::new(database_thread::DatabaseThread::from(db));
+
+        smol::block_on(async {
+            // Reporting stops at the genesis block.
+            assert_eq!(
+                pin_finalized_ancestors(&database, hashes[3], 8).await.len(),
+                3
+            );
+
+            let ancestors = pin_finalized_ancestors(&database, hashes[3], 2).await;
+            assert_eq!(
+                ancestors
+                    .iter()
+                    .map(|snapshot| *snapshot.block_hash())
+                    .collect::<Vec<_>>(),
+                vec![hashes[1], hashes[2]]
+            );
+
+            // The storage of the reported ancestors survives pruning, contrary to the storage of
+            // the other ancestors.
+            database
+                .with_database(|database| database.prune_finalized_storage(0))
+                .await
+                .unwrap();
+            assert!(database.snapshot_at(hashes[1]).await.is_ok());
+            assert!(database.snapshot_at(hashes[2]).await.is_ok());
+            assert!(matches!(
+                database.snapshot_at(hashes[0]).await,
+                Err(full_sqlite::StorageAccessError::StoragePruned)
+            ));
+
+            // Once the JSON-RPC client unpins them, their storage can be pruned.
+            drop(ancestors);
+            database
+                .with_database(|database| database.prune_finalized_storage(0))
+                .await
+                .unwrap();
+            assert!(matches!(
+                database.snapshot_at(hashes[1]).await,
+                Err(full_sqlite::StorageAccessError::StoragePruned)
+            ));
+        });
+    }
+
+    #[test]
+    fn storage_of_pinned_fork_accessible_after_pruning() {
+        let (db, genesis_hash) = test_util::memory_database();
+
+        // Block `1a` is a fork that ends up not being finalized, while `1b` and `2b` are
+        // finalized.
+        let block1a_hash = test_util::insert_block(&db, &genesis_hash, 1, &[2; 32]);
+
+        // The key `0x11` is `hello` in the canonical chain and `fork` in block `1a`.
+        db.insert_trie_nodes(
+            [
+                full_sqlite::InsertTrieNode {
+                    merkle_value: Cow::Borrowed(&[1; 32]),
+                    partial_key_nibbles: Cow::Borrowed(&[1, 1]),
+                    children_merkle_values: Default::default(),
+                    storage_value: full_sqlite::InsertTrieNodeStorageValue::Value {
+                        value: Cow::Borrowed(b"hello"),
+                        references_merkle_value: false,
+                    },
+                },
+                full_sqlite::InsertTrieNode {
+                    merkle_value: Cow::Borrowed(&[2; 32]),
+                    partial_key_nibbles: Cow::Borrowed(&[1, 1]),
+                    children_merkle_values: Default::default(),
+                    storage_value: full_sqlite::InsertTrieNodeStorageValue::Value {
+                        value: Cow::Borrowed(b"fork"),
+                        references_merkle_value: false,
+                    },
+                },
+            ]
+            .into_iter(),
+            0,
+        )
+        .unwrap();
+
+        let database = Arc::new(database_thread::DatabaseThread::from(db));
+
+        smol::block_on(async {
+            // The block is reported to the JSON-RPC client, which pins it.
+            let pinned_storage = database.snapshot_at(block1a_hash).await.unwrap();
+
+            // A re-organization happens, then the other fork gets finalized and the storage of
+            // the blocks below the finalized block is pruned.
+            database
+                .with_database(move |db| {
+                    let mut parent_hash = genesis_hash;
+                    for number in 1..=2 {
+                        parent_hash = test_util::insert_block(db, &parent_hash, number, &[1; 32]);
+                    }
+                    db.set_finalized(&parent_hash).unwrap();
+                    db.prune_finalized_storage(0).unwrap();
+                })
+                .await;
+
+            // The fork is no longer canonical, but a storage operation can still be started
+            // against it.
+            let operation_snapshot = database.snapshot_at(block1a_hash).await.unwrap();
+            let (items, progress) = operation_snapshot
+                .with_database(|database, block_hash| {
+                    storage_query(
+                        database,
+                        block_hash,
+                        StorageQueryProgress {
+                            parent_path: None,
+                            items: [methods::ChainHeadStorageRequestItem {
+                                key: methods::HexString(vec![0x11]),
+                                ty: methods::ChainHeadStorageType::Value,
+                            }]
+                            .into_iter()
+                            .collect(),
+                            descendants_cursor: None,
+                        },
+                        16,
+                    )
+                })
+                .await
+                .unwrap();
+            assert!(progress.items.is_empty());
+            assert_eq!(items.len(), 1);
+            assert_eq!(items[0].value.as_ref().unwrap().0, b"fork");
+
+            // Once the JSON-RPC client unpins the block, its storage can be pruned.
+            drop(operation_snapshot);
+            drop(pinned_storage);
+            database
+                .with_database(|database| database.prune_finalized_storage(0))
+                .await
+                .unwrap();
+            assert!(matches!(
+                database.snapshot_at(block1a_hash).await,
+                Err(full_sqlite::StorageAccessError::StoragePruned)
+            ));
+        });
+    }
+
+    #[test]
+    fn operation_ids_never_repeat_through_json_rpc() {
+        smol::block_on(async {
+            let chain_spec_json = include_bytes!("../../tests/substrate-node-template.json");
+            let (database, genesis_hash, _, _) =
+                test_util::chain_spec_database(chain_spec_json).await;
+            let json_rpc_service =
+                test_util::json_rpc_service(Arc::new(database), chain_spec_json, None).await;
+
+            // Returns the next response or notification, parsed as JSON.
+            let next_response = || async {
+                serde_json::from_str::<serde_json::Value>(&json_rpc_service.next_response().await)
+                    .unwrap()
+            };
+
+            json_rpc_service.send_request(
+                r#"{"jsonrpc":"2.0","id":1,"method":"chainHead_v1_follow","params":[false]}"#
+                    .to_owned(),
+            );
+            let subscription = next_response().await["result"].clone();
+            let event = next_response().await;
+            assert_eq!(event["params"]["result"]["event"], "initialized");
+
+            // Operations that finish alternate with operations that are stopped by the
+            // JSON-RPC client while waiting for `chainHead_v1_continue`.
+            let mut operation_ids = hashbrown::HashSet::new();
+            for n in 0..20 {
+                let stopped = n % 2 == 1;
+                let item = if stopped {
+                    serde_json::json!({ "key": "0x", "type": "descendantsValues" })
+                } else {
+                    serde_json::json!({ "key": "0x3a636f6465", "type": "hash" })
+                };
+                json_rpc_service.send_request(
+                    serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 2,
+                        "method": "chainHead_v1_storage",
+                        "params": [
+                            subscription,
+                            format!("0x{}", hex::encode(genesis_hash)),
+                            [item],
+                        ],
+                    })
+                    .to_string(),
+                );
+                let response = next_response().await;
+                assert_eq!(response["result"]["result"], "started");
+                let operation_id = response["result"]["operationId"].clone();
+                assert!(operation_ids.insert(operation_id.as_str().unwrap().to_owned()));
+
+                let event = next_response().await;
+                assert_eq!(event["params"]["result"]["event"], "operationStorageItems");
+                assert_eq!(event["params"]["result"]["operationId"], operation_id);
+                let event = next_response().await;
+                assert_eq!(event["params"]["result"]["operationId"], operation_id);
+                if !stopped {
+                    assert_eq!(event["params"]["result"]["event"], "operationStorageDone");
+                    continue;
+                }
+
+                assert_eq!(
+                    event["params"]["result"]["event"],
+                    "operationWaitingForContinue"
+                );
+                json_rpc_service.send_request(
+                    serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 3,
+                        "method": "chainHead_v1_stopOperation",
+                        "params": [subscription, operation_id],
+                    })
+                    .to_string(),
+                );
+                assert_eq!(next_response().await["result"], serde_json::Value::Null);
+            }
+        });
+    }
+
+    #[test]
+    fn operation_ids_never_repeat() {
+        let mut generator = OperationIdGenerator::new();
+        let mut seen = hashbrown::HashSet::new();
+        for _ in 0..10_000 {
+            assert!(seen.insert(generator.next()));
+        }
+    }
+
+    #[test]
+    fn follow_subscription_keeps_storage_of_pinned_blocks() {
+        smol::block_on(async {
+            let chain_spec_json = include_bytes!("../../tests/substrate-node-template.json");
+            let (database, genesis_hash, _, _) =
+                test_util::chain_spec_database(chain_spec_json).await;
+            let database = Arc::new(database);
+            let json_rpc_service =
+                test_util::json_rpc_service(database.clone(), chain_spec_json, None).await;
+
+            // Returns the next response or notification, parsed as JSON.
+            let next_response = || async {
+                serde_json::from_str::<serde_json::Value>(&json_rpc_service.next_response().await)
+                    .unwrap()
+            };
+
+            json_rpc_service.send_request(
+                r#"{"jsonrpc":"2.0","id":1,"method":"chainHead_v1_follow","params":[false]}"#
+                    .to_owned(),
+            );
+            let subscription = next_response().await["result"].clone();
+            let event = next_response().await;
+            assert_eq!(event["params"]["result"]["event"], "initialized");
+            assert_eq!(
+                event["params"]["result"]["finalizedBlockHashes"],
+                serde_json::json!([format!("0x{}", hex::encode(genesis_hash))])
+            );
+
+            // A new block is finalized and the storage of the genesis block is pruned, while the
+            // subscription still has the genesis block pinned.
+            database
+                .with_database(move |db| {
+                    let block1_hash = test_util::insert_block_with_storage(
+                        db,
+                        &genesis_hash,
+                        1,
+                        &BTreeMap::from([(b"foo".to_vec(), b"bar".to_vec())]),
+                        false,
+                    );
+                    db.set_finalized(&block1_hash).unwrap();
+                    db.prune_finalized_storage(0).unwrap();
+                })
+                .await;
+
+            // The storage of the genesis block remains accessible through the subscription.
+            json_rpc_service.send_request(
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 2,
+                    "method": "chainHead_v1_storage",
+                    "params": [
+                        subscription,
+                        format!("0x{}", hex::encode(genesis_hash)),
+                        [{ "key": "0x3a636f6465", "type": "hash" }],
+                    ],
+                })
+                .to_string(),
+            );
+            let response = next_response().await;
+            assert_eq!(response["result"]["result"], "started");
+            let event = next_response().await;
+            assert_eq!(event["params"]["result"]["event"], "operationStorageItems");
+            assert_eq!(event["params"]["result"]["items"][0]["key"], "0x3a636f6465");
+            let event = next_response().await;
+            assert_eq!(event["params"]["result"]["event"], "operationStorageDone");
+
+            // Once the JSON-RPC client unpins the block, its storage can be pruned.
+            json_rpc_service.send_request(
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 3,
+                    "method": "chainHead_v1_unpin",
+                    "params": [subscription, format!("0x{}", hex::encode(genesis_hash))],
+                })
+                .to_string(),
+            );
+            assert_eq!(next_response().await["result"], serde_json::Value::Null);
+            database
+                .with_database(|db| db.prune_finalized_storage(0))
+                .await
+                .unwrap();
+            assert!(matches!(
+                database.snapshot_at(genesis_hash).await,
+                Err(full_sqlite::StorageAccessError::StoragePruned)
+            ));
+        });
+    }
+}
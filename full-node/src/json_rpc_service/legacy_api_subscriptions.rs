@@ -19,10 +19,19 @@ use hashbrown::HashMap;
 use smol::stream::StreamExt as _;
 use smoldot::{
     chain::fork_tree,
+    database::full_sqlite,
     executor::{host::HostVmPrototype, CoreVersion},
     trie,
 };
-use std::{collections::BTreeSet, iter, mem, num::NonZero, ops, pin::Pin, sync::Arc};
+use std::{
+    collections::{BTreeSet, VecDeque},
+    iter, mem,
+    num::NonZero,
+    ops,
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
 
 use crate::{consensus_service, database_thread};
 
@@ -30,75 +39,51 @@ use crate::{consensus_service, database_thread};
 pub struct SubscribeAllHeads {
     consensus_service: Arc<consensus_service::ConsensusService>,
 
-    /// Active subscription to the consensus service blocks. `None` if not subscribed yet or if
-    /// the subscription has stopped.
-    subscription: Option<SubscribeAllHeadsSubscription>,
-}
+    /// Active subscription to the blocks imported by the consensus service. `None` if not
+    /// subscribed yet or if the subscription has stopped.
+    new_blocks: Option<Pin<Box<async_channel::Receiver<consensus_service::ImportedBlock>>>>,
 
-struct SubscribeAllHeadsSubscription {
-    subscription_id: consensus_service::SubscriptionId,
-    new_blocks: Pin<Box<async_channel::Receiver<consensus_service::Notification>>>,
-    blocks_to_unpin: Vec<[u8; 32]>,
+    /// If `true`, the current best block must be reported the next time the subscription to
+    /// the consensus service is created. Set to `false` afterwards.
+    report_initial_best: bool,
 }
 
 impl SubscribeAllHeads {
     /// Builds a new [`SubscribeAllHeads`].
-    pub fn new(consensus_service: Arc<consensus_service::ConsensusService>) -> Self {
+    ///
+    /// If `report_initial_best` is `true`, the first header returned by
+    /// [`SubscribeAllHeads::next_scale_encoded_header`] is the one of the current best block.
+    pub fn new(
+        consensus_service: Arc<consensus_service::ConsensusService>,
+        report_initial_best: bool,
+    ) -> Self {
         SubscribeAllHeads {
             consensus_service,
-            subscription: None,
+            new_blocks: None,
+            report_initial_best,
         }
     }
 
     /// Returns the SCALE-encoded header of the next block to provide as part of the subscription.
     pub async fn next_scale_encoded_header(&mut self) -> Vec<u8> {
         loop {
-            let subscription = match &mut self.subscription {
-                Some(s) => s,
+            let new_blocks = match &mut self.new_blocks {
+                Some(new_blocks) => new_blocks,
                 None => {
-                    let subscribe_all = self
-                        .consensus_service
-                        .subscribe_all(32, NonZero::<usize>::new(usize::MAX).unwrap())
-                        .await;
-
-                    let blocks_to_unpin = iter::once(subscribe_all.finalized_block_hash)
-                        .chain(
-                            subscribe_all
-                                .non_finalized_blocks_ancestry_order
-                                .into_iter()
-                                .map(|b| b.block_hash),
-                        )
-                        .collect();
-
-                    self.subscription.insert(SubscribeAllHeadsSubscription {
-                        subscription_id: subscribe_all.id,
-                        new_blocks: Box::pin(subscribe_all.new_blocks),
-                        blocks_to_unpin,
-                    })
+                    let subscription = self.consensus_service.subscribe_imported_blocks().await;
+                    let new_blocks = self.new_blocks.insert(Box::pin(subscription.new_blocks));
+                    if mem::take(&mut self.report_initial_best) {
+                        return subscription.best_block_scale_encoded_header;
+                    }
+                    new_blocks
                 }
             };
 
-            while let Some(block_to_unpin) = subscription.blocks_to_unpin.last() {
-                self.consensus_service
-                    .unpin_block(subscription.subscription_id, *block_to_unpin)
-                    .await;
-                let _ = subscription.blocks_to_unpin.pop();
-            }
-
-            loop {
-                match subscription.new_blocks.next().await {
-                    None => {
-                        self.subscription = None;
-                        break;
-                    }
-                    Some(consensus_service::Notification::Block { block, .. }) => {
-                        subscription.blocks_to_unpin.push(block.block_hash);
-                        return block.scale_encoded_header;
-                    }
-                    Some(consensus_service::Notification::Finalized { .. }) => {
-                        // Ignore event.
-                    }
-                }
+            match new_blocks.next().await {
+                // Contrary to `chain_subscribeNewHeads`, every new block is reported, including
+                // the blocks on a fork.
+                Some(block) => return block.scale_encoded_header,
+                None => self.new_blocks = None,
             }
         }
     }
@@ -213,6 +198,200 @@ impl SubscribeFinalizedHeads {
     }
 }
 
+/// Helper that provides the blocks of a `chain_subscribeFinalizedExtrinsics` subscription.
+///
+/// Every finalized block is provided, in increasing block number order, even when finalization
+/// skips blocks or when the subscription to the consensus service has to be re-created.
+pub struct SubscribeFinalizedExtrinsics {
+    consensus_service: Arc<consensus_service::ConsensusService>,
+    database: Arc<database_thread::DatabaseThread>,
+
+    /// Maximum duration of an access to the database.
+    database_timeout: Duration,
+
+    /// Active subscription to the consensus service blocks. `None` if not subscribed yet or if
+    /// the subscription has stopped.
+    subscription: Option<SubscribeFinalizedExtrinsicsSubscription>,
+
+    /// Hash of the latest finalized block that has been provided, or of the finalized block at
+    /// the time when the first subscription has been created. `None` if not subscribed yet.
+    last_reported: Option<[u8; 32]>,
+
+    /// Latest finalized block whose descendants of [`SubscribeFinalizedExtrinsics::last_reported`]
+    /// must be loaded from the database. `None` if there is nothing to load.
+    load_until: Option<[u8; 32]>,
+
+    /// Blocks loaded from the database that remain to be provided, with the hashes of their
+    /// extrinsics, in increasing block number order.
+    blocks_to_report: VecDeque<FinalizedBlockExtrinsics>,
+}
+
+/// Hash of a finalized block, and hashes of its extrinsics in the order in which they are found
+/// in the block body.
+type FinalizedBlockExtrinsics = ([u8; 32], Vec<[u8; 32]>);
+
+struct SubscribeFinalizedExtrinsicsSubscription {
+    subscription_id: consensus_service::SubscriptionId,
+    new_blocks: Pin<Box<async_channel::Receiver<consensus_service::Notification>>>,
+}
+
+/// Error potentially returned by [`SubscribeFinalizedExtrinsics::next_block`].
+#[derive(Debug, derive_more::Display)]
+pub enum FinalizedExtrinsicsError {
+    /// Accessing the database has taken too long.
+    #[display(fmt = "timeout")]
+    Timeout,
+    /// Error while accessing the database.
+    #[display(fmt = "{_0}")]
+    Corrupted(full_sqlite::CorruptedError),
+    /// One of the finalized blocks is missing from the database.
+    #[display(fmt = "missing-block")]
+    MissingBlock,
+}
+
+impl SubscribeFinalizedExtrinsics {
+    /// Builds a new [`SubscribeFinalizedExtrinsics`].
+    pub fn new(
+        consensus_service: Arc<consensus_service::ConsensusService>,
+        database: Arc<database_thread::DatabaseThread>,
+        database_timeout: Duration,
+    ) -> Self {
+        SubscribeFinalizedExtrinsics {
+            consensus_service,
+            database,
+            database_timeout,
+            subscription: None,
+            last_reported: None,
+            load_until: None,
+            blocks_to_report: VecDeque::new(),
+        }
+    }
+
+    /// Returns the hash of the next finalized block to provide as part of the subscription,
+    /// alongside with the hashes of its extrinsics.
+    ///
+    /// If an error is returned, the blocks that couldn't be loaded are loaded again the next
+    /// time a block is finalized.
+    pub async fn next_block(
+        &mut self,
+    ) -> Result<FinalizedBlockExtrinsics, FinalizedExtrinsicsError> {
+        loop {
+            if let Some((block_hash, extrinsics_hashes)) = self.blocks_to_report.pop_front() {
+                self.last_reported = Some(block_hash);
+                return Ok((block_hash, extrinsics_hashes));
+            }
+
+            if let (Some(last_reported), Some(load_until)) =
+                (self.last_reported, self.load_until.take())
+            {
+                let blocks = self
+                    .database
+                    .with_database_timeout(self.database_timeout, move |database| {
+                        finalized_blocks_extrinsics(database, &last_reported, &load_until)
+                    })
+                    .await;
+                match blocks {
+                    Ok(Ok(Some(blocks))) => self.blocks_to_report = blocks.into(),
+                    Ok(Ok(None)) => return Err(FinalizedExtrinsicsError::MissingBlock),
+                    Ok(Err(err)) => return Err(FinalizedExtrinsicsError::Corrupted(err)),
+                    Err(database_thread::DatabaseTimeoutError) => {
+                        return Err(FinalizedExtrinsicsError::Timeout)
+                    }
+                }
+                continue;
+            }
+
+            let subscription = match &mut self.subscription {
+                Some(s) => s,
+                None => {
+                    let subscribe_all = self
+                        .consensus_service
+                        .subscribe_all(32, NonZero::<usize>::new(usize::MAX).unwrap())
+                        .await;
+
+                    // The bodies of the finalized blocks are read from the database, where they
+                    // are kept no matter whether the blocks are pinned. Blocks are thus unpinned
+                    // as soon as they are reported.
+                    for block_hash in iter::once(subscribe_all.finalized_block_hash).chain(
+                        subscribe_all
+                            .non_finalized_blocks_ancestry_order
+                            .iter()
+                            .map(|b| b.block_hash),
+                    ) {
+                        self.consensus_service
+                            .unpin_block(subscribe_all.id, block_hash)
+                            .await;
+                    }
+
+                    // If this isn't the first subscription, the blocks that have been finalized
+                    // in the meantime are loaded from the database.
+                    if self.last_reported.is_none() {
+                        self.last_reported = Some(subscribe_all.finalized_block_hash);
+                    } else {
+                        self.load_until = Some(subscribe_all.finalized_block_hash);
+                    }
+
+                    self.subscription
+                        .insert(SubscribeFinalizedExtrinsicsSubscription {
+                            subscription_id: subscribe_all.id,
+                            new_blocks: Box::pin(subscribe_all.new_blocks),
+                        })
+                }
+            };
+
+            match subscription.new_blocks.next().await {
+                None => self.subscription = None,
+                Some(consensus_service::Notification::Block { block, .. }) => {
+                    self.consensus_service
+                        .unpin_block(subscription.subscription_id, block.block_hash)
+                        .await;
+                }
+                Some(consensus_service::Notification::Finalized {
+                    finalized_blocks_newest_to_oldest,
+                    ..
+                }) => {
+                    debug_assert!(!finalized_blocks_newest_to_oldest.is_empty());
+                    self.load_until = finalized_blocks_newest_to_oldest.first().copied();
+                }
+            }
+        }
+    }
+}
+
+/// Returns the hashes of the finalized blocks that descend from `last_reported`, up to and
+/// including `finalized_block_hash`, alongside with the hashes of their extrinsics, in
+/// increasing block number order.
+///
+/// Returns `Ok(None)` if one of these blocks is missing from the database.
+fn finalized_blocks_extrinsics(
+    database: &full_sqlite::SqliteFullDatabase,
+    last_reported: &[u8; 32],
+    finalized_block_hash: &[u8; 32],
+) -> Result<Option<Vec<FinalizedBlockExtrinsics>>, full_sqlite::CorruptedError> {
+    let mut blocks = Vec::new();
+    let mut block_hash = *finalized_block_hash;
+    while block_hash != *last_reported {
+        let Some(extrinsics) = database.block_extrinsics(&block_hash)? else {
+            return Ok(None);
+        };
+        blocks.push((
+            block_hash,
+            extrinsics
+                .map(|extrinsic| consensus_service::transaction_hash(&extrinsic))
+                .collect(),
+        ));
+
+        // The parent of the genesis block is reported as all zeroes, in which case
+        // `last_reported` isn't an ancestor of `finalized_block_hash`.
+        match database.block_parent(&block_hash)? {
+            Some(parent_hash) if parent_hash != [0; 32] => block_hash = parent_hash,
+            _ => return Ok(None),
+        }
+    }
+    blocks.reverse();
+    Ok(Some(blocks))
+}
+
 /// Helper that provides the blocks of a `chain_subscribeNewHeads` subscription.
 pub struct SubscribeNewHeads {
     consensus_service: Arc<consensus_service::ConsensusService>,
@@ -600,6 +779,12 @@ pub struct SubscribeStorage {
     database: Arc<database_thread::DatabaseThread>,
     /// List of keys that was passed to [`SubscribeStorage::new`].
     keys: Vec<Vec<u8>>,
+    /// Values of the keys found in [`SubscribeStorage::keys`] as they were last reported. Used
+    /// in order to not report keys whose value is identical to the one last reported. Always
+    /// empty if subscribing to all keys, as it would otherwise grow indefinitely.
+    ///
+    /// Kept across re-subscriptions to the consensus service.
+    last_reported_values: HashMap<Vec<u8>, Option<Vec<u8>>, fnv::FnvBuildHasher>,
 
     /// Active subscription to the consensus service blocks. `None` if not subscribed yet or if
     /// the subscription has stopped.
@@ -630,9 +815,11 @@ struct SubscribeStorageSubscription {
     /// in [`SubscribeStorageSubscription::pinned_blocks`].
     ///
     /// Because the storage changes of blocks that were already present at the time when the
-    /// subscription starts are unknown, they are also not in this list. This leads to corner
-    /// cases where some changes aren't provided, but we don't really care
-    /// as `state_subscribeStorage` is not properly defined anyway.
+    /// subscription starts are unknown, they are also not in this list. In case of a re-org,
+    /// all the keys of [`SubscribeStorage::keys`] are fetched again in order to work around
+    /// this. When subscribing to all keys, this leads to corner cases where some changes aren't
+    /// provided, but we don't really care as `state_subscribeStorage` is not properly defined
+    /// anyway.
     pinned_blocks_storage_changes: BTreeSet<(fork_tree::NodeIndex, Vec<u8>)>,
     /// Hash of the current finalized block. Not found
     /// in [`SubscribeStorageSubscription::pinned_blocks`].
@@ -655,6 +842,10 @@ impl SubscribeStorage {
         SubscribeStorage {
             consensus_service,
             database,
+            last_reported_values: HashMap::with_capacity_and_hasher(
+                subscribed_keys.len(),
+                Default::default(),
+            ),
             keys: subscribed_keys,
             subscription: None,
         }
@@ -758,11 +949,17 @@ impl SubscribeStorage {
                 subscription.new_report_remaining_keys.remove(&key);
 
                 match result {
-                    Ok(value) => subscription
-                        .new_report_preparation
-                        .push((key, value.map(|(v, _)| v))),
+                    Ok(value) => {
+                        let value = value.map(|(v, _)| v);
+                        if self.keys.is_empty()
+                            || self.last_reported_values.get(&key) != Some(&value)
+                        {
+                            subscription.new_report_preparation.push((key, value));
+                        }
+                    }
                     Err(database_thread::StorageAccessError::UnknownBlock)
-                    | Err(database_thread::StorageAccessError::IncompleteStorage) => {
+                    | Err(database_thread::StorageAccessError::IncompleteStorage)
+                    | Err(database_thread::StorageAccessError::StoragePruned) => {
                         self.subscription = None;
                         continue 'main_subscription;
                     }
@@ -781,14 +978,16 @@ impl SubscribeStorage {
                     .map_or(subscription.current_finalized_block_hash, |idx| {
                         *subscription.pinned_blocks.get(idx).unwrap()
                     });
-                return (
-                    best_block_hash,
-                    mem::replace(
-                        &mut subscription.new_report_preparation,
-                        Vec::with_capacity(self.keys.len()),
-                    )
-                    .into_iter(),
+                let report = mem::replace(
+                    &mut subscription.new_report_preparation,
+                    Vec::with_capacity(self.keys.len()),
                 );
+                if !self.keys.is_empty() {
+                    for (key, value) in &report {
+                        self.last_reported_values.insert(key.clone(), value.clone());
+                    }
+                }
+                return (best_block_hash, report.into_iter());
             }
 
             // Process the next incoming consensus service notification.
@@ -862,35 +1061,16 @@ impl SubscribeStorage {
                     .get(best_block_hash)
                     .unwrap();
 
-                let ascend_descend_iter = match subscription.current_best_block_index {
-                    Some(prev_best_idx) => {
-                        let (a, d) = subscription
-                            .pinned_blocks
-                            .ascend_and_descend(prev_best_idx, new_best_block_node_index);
-                        either::Left(a.chain(d))
-                    }
-                    None => either::Right(
-                        subscription
-                            .pinned_blocks
-                            .root_to_node_path(new_best_block_node_index),
-                    ),
-                };
-
-                for block in ascend_descend_iter {
-                    let storage_changes = subscription.pinned_blocks_storage_changes.range((
-                        ops::Bound::Included((block, Vec::new())),
-                        if let Some(block_plus_one) = block.inc() {
-                            ops::Bound::Excluded((block_plus_one, Vec::new()))
-                        } else {
-                            ops::Bound::Unbounded
-                        },
+                subscription
+                    .new_report_remaining_keys
+                    .extend(keys_to_refresh_on_new_best(
+                        &subscription.pinned_blocks,
+                        &subscription.pinned_blocks_storage_changes,
+                        &self.keys,
+                        subscription.current_best_block_index,
+                        new_best_block_node_index,
                     ));
 
-                    for (_, key) in storage_changes {
-                        subscription.new_report_remaining_keys.insert(key.clone());
-                    }
-                }
-
                 subscription.current_best_block_index = Some(new_best_block_node_index);
             }
 
@@ -931,3 +1111,386 @@ impl SubscribeStorage {
         }
     }
 }
+
+/// Returns the list of keys of a [`SubscribeStorage`] whose value must be fetched again after
+/// the best block has changed from `previous_best` (`None` meaning the finalized block) to
+/// `new_best`.
+///
+/// If `new_best` isn't a descendant of `previous_best`, in other words in case of a re-org, all
+/// the keys of `subscribed_keys` are returned. The storage changes of the blocks that were
+/// already present when the subscription started aren't known, and the value of any key might
+/// thus differ on the new best chain.
+fn keys_to_refresh_on_new_best(
+    pinned_blocks: &fork_tree::ForkTree<[u8; 32]>,
+    pinned_blocks_storage_changes: &BTreeSet<(fork_tree::NodeIndex, Vec<u8>)>,
+    subscribed_keys: &[Vec<u8>],
+    previous_best: Option<fork_tree::NodeIndex>,
+    new_best: fork_tree::NodeIndex,
+) -> Vec<Vec<u8>> {
+    let (ascend, descend) = match previous_best {
+        Some(previous_best) => {
+            let (a, d) = pinned_blocks.ascend_and_descend(previous_best, new_best);
+            (either::Left(a), either::Left(d))
+        }
+        None => (
+            either::Right(iter::empty()),
+            either::Right(pinned_blocks.root_to_node_path(new_best)),
+        ),
+    };
+
+    if !subscribed_keys.is_empty() && ascend.clone().next().is_some() {
+        return subscribed_keys.to_vec();
+    }
+
+    let mut keys = Vec::new();
+    for block in ascend.chain(descend) {
+        let storage_changes = pinned_blocks_storage_changes.range((
+            ops::Bound::Included((block, Vec::new())),
+            if let Some(block_plus_one) = block.inc() {
+                ops::Bound::Excluded((block_plus_one, Vec::new()))
+            } else {
+                ops::Bound::Unbounded
+            },
+        ));
+
+        for (_, key) in storage_changes {
+            keys.push(key.clone());
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{consensus_service, test_util};
+    use smoldot::{chain::fork_tree, executor::runtime_call, header};
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        sync::Arc,
+    };
+
+    #[test]
+    fn all_heads_include_forks() {
+        smol::block_on(async {
+            let chain_spec_json = include_bytes!("../../tests/substrate-node-template.json");
+            let (database, genesis_hash, genesis_runtime, _) =
+                test_util::chain_spec_database(chain_spec_json).await;
+            let database = Arc::new(database);
+            let genesis_header = database
+                .with_database(move |db| db.block_scale_encoded_header(&genesis_hash))
+                .await
+                .unwrap()
+                .unwrap();
+
+            let (new_blocks_tx, new_blocks_rx) = async_channel::bounded(8);
+            let consensus_service = consensus_service::ConsensusService::from_notifications(
+                database.clone(),
+                4,
+                genesis_header,
+                genesis_runtime,
+                new_blocks_rx,
+            );
+            let json_rpc_service =
+                test_util::json_rpc_service(database, chain_spec_json, Some(consensus_service))
+                    .await;
+
+            // Returns the next response or notification, parsed as JSON.
+            let next_response = || async {
+                serde_json::from_str::<serde_json::Value>(&json_rpc_service.next_response().await)
+                    .unwrap()
+            };
+
+            json_rpc_service.send_request(
+                r#"{"jsonrpc":"2.0","id":1,"method":"chain_subscribeAllHeads","params":[]}"#
+                    .to_owned(),
+            );
+            assert!(next_response().await["result"].is_string());
+
+            // The finalized block has two children `1a` and `1b`. `1b` is imported while `1a` is
+            // the best block, and thus isn't the new best block.
+            for (state_root, is_new_best) in [([1; 32], true), ([2; 32], false)] {
+                let scale_encoded_header = test_util::block_header(&genesis_hash, 1, &state_root);
+                new_blocks_tx
+                    .send(consensus_service::Notification::Block {
+                        block: consensus_service::BlockNotification {
+                            is_new_best,
+                            block_hash: header::hash_from_scale_encoded_header(
+                                &scale_encoded_header,
+                            ),
+                            scale_encoded_header,
+                            runtime_update: None,
+                            parent_hash: genesis_hash,
+                        },
+                        storage_changes: Arc::new(runtime_call::StorageChanges::empty()),
+                    })
+                    .await
+                    .unwrap();
+            }
+
+            // Both blocks are reported.
+            for state_root in [[1; 32], [2; 32]] {
+                let notification = next_response().await;
+                assert_eq!(notification["method"], "chain_allHead");
+                assert_eq!(
+                    notification["params"]["result"]["stateRoot"],
+                    format!("0x{}", hex::encode(state_root))
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn storage_subscription_follows_reorgs() {
+        smol::block_on(async {
+            let chain_spec_json = include_bytes!("../../tests/substrate-node-template.json");
+            let (database, genesis_hash, genesis_runtime, _) =
+                test_util::chain_spec_database(chain_spec_json).await;
+            let database = Arc::new(database);
+
+            // The finalized block has two children `1a` and `1b`, and `1a` has a child `2a`.
+            // The key `foo` has a different value on each fork, while the key `bar` has the same
+            // value on both forks.
+            let (genesis_header, block1a, block1b, block2a) = database
+                .with_database(move |db| {
+                    let storage = |foo: &[u8]| {
+                        BTreeMap::from([
+                            (b"foo".to_vec(), foo.to_vec()),
+                            (b"bar".to_vec(), b"same".to_vec()),
+                        ])
+                    };
+                    let block1a = test_util::insert_block_with_storage(
+                        db,
+                        &genesis_hash,
+                        1,
+                        &storage(b"a"),
+                        false,
+                    );
+                    let block1b = test_util::insert_block_with_storage(
+                        db,
+                        &genesis_hash,
+                        1,
+                        &storage(b"b"),
+                        false,
+                    );
+                    let block2a = test_util::insert_block_with_storage(
+                        db,
+                        &block1a,
+                        2,
+                        &storage(b"a"),
+                        false,
+                    );
+                    let genesis_header = db
+                        .block_scale_encoded_header(&genesis_hash)
+                        .unwrap()
+                        .unwrap();
+                    (genesis_header, block1a, block1b, block2a)
+                })
+                .await;
+
+            let (new_blocks_tx, new_blocks_rx) = async_channel::bounded(8);
+            let consensus_service = consensus_service::ConsensusService::from_notifications(
+                database.clone(),
+                4,
+                genesis_header,
+                genesis_runtime,
+                new_blocks_rx,
+            );
+            let json_rpc_service = test_util::json_rpc_service(
+                database.clone(),
+                chain_spec_json,
+                Some(consensus_service),
+            )
+            .await;
+
+            // Returns the block hash and the changes of the next storage notification.
+            let next_storage_changes = || async {
+                let notification = serde_json::from_str::<serde_json::Value>(
+                    &json_rpc_service.next_response().await,
+                )
+                .unwrap();
+                assert_eq!(notification["method"], "state_storage");
+                let mut changes = notification["params"]["result"]["changes"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|change| (change[0].clone(), change[1].clone()))
+                    .collect::<Vec<_>>();
+                changes.sort_by_key(|(key, _)| key.to_string());
+                (notification["params"]["result"]["block"].clone(), changes)
+            };
+            let hex = |value: &[u8]| serde_json::Value::from(format!("0x{}", hex::encode(value)));
+
+            json_rpc_service.send_request(
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "state_subscribeStorage",
+                    "params": [[hex(b"foo"), hex(b"bar")]],
+                })
+                .to_string(),
+            );
+            let response =
+                serde_json::from_str::<serde_json::Value>(&json_rpc_service.next_response().await)
+                    .unwrap();
+            assert!(response["result"].is_string());
+
+            // The initial values are reported.
+            assert_eq!(
+                next_storage_changes().await,
+                (
+                    hex(&genesis_hash),
+                    vec![
+                        (hex(b"bar"), serde_json::Value::Null),
+                        (hex(b"foo"), serde_json::Value::Null)
+                    ]
+                )
+            );
+
+            // `1a` becomes the best block, then a re-org happens towards `1b`, then towards `2a`.
+            // The storage changes of the blocks aren't included in the notifications, in order to
+            // make sure that the values after a re-org are loaded from the new best block.
+            for (block_hash, parent_hash) in [
+                (block1a, genesis_hash),
+                (block1b, genesis_hash),
+                (block2a, block1a),
+            ] {
+                let scale_encoded_header = database
+                    .with_database(move |db| db.block_scale_encoded_header(&block_hash))
+                    .await
+                    .unwrap()
+                    .unwrap();
+                new_blocks_tx
+                    .send(consensus_service::Notification::Block {
+                        block: consensus_service::BlockNotification {
+                            is_new_best: true,
+                            block_hash,
+                            scale_encoded_header,
+                            runtime_update: None,
+                            parent_hash,
+                        },
+                        storage_changes: Arc::new(runtime_call::StorageChanges::empty()),
+                    })
+                    .await
+                    .unwrap();
+            }
+
+            // Switching to `1b` reports the values of `1b`. Switching to `2a` reports the value
+            // of `foo` in `2a`, but not `bar`, as its value is the same as the one last
+            // reported.
+            assert_eq!(
+                next_storage_changes().await,
+                (
+                    hex(&block1b),
+                    vec![(hex(b"bar"), hex(b"same")), (hex(b"foo"), hex(b"b"))]
+                )
+            );
+            assert_eq!(
+                next_storage_changes().await,
+                (hex(&block2a), vec![(hex(b"foo"), hex(b"a"))])
+            );
+        });
+    }
+
+    #[test]
+    fn keys_to_refresh_on_reorg() {
+        // Tree of blocks: the finalized block has two children `a1` and `b1`, and `a1` has
+        // a child `a2`.
+        let mut pinned_blocks = fork_tree::ForkTree::new();
+        let a1 = pinned_blocks.insert(None, [1; 32]);
+        let a2 = pinned_blocks.insert(Some(a1), [2; 32]);
+        let b1 = pinned_blocks.insert(None, [3; 32]);
+
+        let mut storage_changes = BTreeSet::new();
+        storage_changes.insert((a1, b"foo".to_vec()));
+        storage_changes.insert((a2, b"bar".to_vec()));
+        storage_changes.insert((b1, b"baz".to_vec()));
+
+        let subscribed_keys = [b"foo".to_vec(), b"bar".to_vec(), b"unchanged".to_vec()];
+
+        // Going from the finalized block to `a2` only concerns the keys modified on the way.
+        let mut keys = super::keys_to_refresh_on_new_best(
+            &pinned_blocks,
+            &storage_changes,
+            &subscribed_keys,
+            None,
+            a2,
+        );
+        keys.sort();
+        assert_eq!(keys, vec![b"bar".to_vec(), b"foo".to_vec()]);
+
+        // Re-org from `a2` to `b1`. All the keys must be fetched again.
+        let mut keys = super::keys_to_refresh_on_new_best(
+            &pinned_blocks,
+            &storage_changes,
+            &subscribed_keys,
+            Some(a2),
+            b1,
+        );
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![b"bar".to_vec(), b"foo".to_vec(), b"unchanged".to_vec()]
+        );
+
+        // When subscribing to all keys, the keys modified on both sides of the re-org are
+        // fetched again.
+        let mut keys =
+            super::keys_to_refresh_on_new_best(&pinned_blocks, &storage_changes, &[], Some(a2), b1);
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![b"bar".to_vec(), b"baz".to_vec(), b"foo".to_vec()]
+        );
+    }
+
+    #[test]
+    fn finalized_blocks_extrinsics_in_order() {
+        let (database, genesis_hash) = test_util::memory_database();
+
+        // Block `n` contains `n` extrinsics.
+        let mut hashes = vec![genesis_hash];
+        for number in 1..=3 {
+            let header = test_util::block_header(hashes.last().unwrap(), number, &[0; 32]);
+            let body = (0..number as u8).map(|n| vec![number as u8, n]);
+            database.insert(&header, true, body).unwrap();
+            hashes.push(header::hash_from_scale_encoded_header(&header));
+        }
+
+        // Blocks `2` and `3` are reported, in this order, even though they've been finalized
+        // at once.
+        let blocks = super::finalized_blocks_extrinsics(&database, &hashes[1], &hashes[3])
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            blocks,
+            vec![
+                (
+                    hashes[2],
+                    vec![
+                        consensus_service::transaction_hash(&[2, 0]),
+                        consensus_service::transaction_hash(&[2, 1])
+                    ]
+                ),
+                (
+                    hashes[3],
+                    vec![
+                        consensus_service::transaction_hash(&[3, 0]),
+                        consensus_service::transaction_hash(&[3, 1]),
+                        consensus_service::transaction_hash(&[3, 2])
+                    ]
+                ),
+            ]
+        );
+
+        assert_eq!(
+            super::finalized_blocks_extrinsics(&database, &hashes[3], &hashes[3]).unwrap(),
+            Some(Vec::new())
+        );
+
+        // `[1; 32]` isn't an ancestor of the block.
+        assert_eq!(
+            super::finalized_blocks_extrinsics(&database, &[1; 32], &hashes[3]).unwrap(),
+            None
+        );
+    }
+}
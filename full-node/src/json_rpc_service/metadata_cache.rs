@@ -0,0 +1,112 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use smol::lock::Mutex;
+use smoldot::executor;
+use std::{num::NonZero, sync::Arc};
+
+/// Cache of the metadata returned by runtimes, shared between all the requests handlers.
+///
+/// The metadata of a runtime only changes when the runtime is upgraded, in which case its spec
+/// version is increased. Entries are therefore indexed by spec name and spec version.
+pub struct MetadataCache {
+    entries: Mutex<lru::LruCache<CacheKey, Arc<[u8]>>>,
+}
+
+/// Key of an entry of the cache.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    /// Name of the specification of the runtime.
+    pub spec_name: String,
+    /// Version of the specification of the runtime.
+    pub spec_version: u32,
+}
+
+impl CacheKey {
+    /// Builds the key corresponding to the given runtime.
+    pub fn from_runtime(runtime: &executor::host::HostVmPrototype) -> Self {
+        let version = runtime.runtime_version().decode();
+        CacheKey {
+            spec_name: version.spec_name.to_owned(),
+            spec_version: version.spec_version,
+        }
+    }
+}
+
+impl MetadataCache {
+    /// Builds a new empty cache that holds at most `num_entries` metadata.
+    pub fn new(num_entries: NonZero<usize>) -> Self {
+        MetadataCache {
+            entries: Mutex::new(lru::LruCache::new(num_entries)),
+        }
+    }
+
+    /// Returns the metadata, without its length prefix, stored for the given key, if any.
+    pub async fn get(&self, key: &CacheKey) -> Option<Arc<[u8]>> {
+        self.entries.lock().await.get(key).cloned()
+    }
+
+    /// Stores the metadata, without its length prefix, of the runtime with the given key.
+    pub async fn insert(&self, key: CacheKey, metadata: Arc<[u8]>) {
+        self.entries.lock().await.put(key, metadata);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CacheKey, MetadataCache};
+    use std::{num::NonZero, sync::Arc};
+
+    #[test]
+    fn runtime_upgrade_invalidates() {
+        smol::block_on(async {
+            let cache = MetadataCache::new(NonZero::<usize>::new(4).unwrap());
+
+            let before_upgrade = CacheKey {
+                spec_name: "node-template".to_owned(),
+                spec_version: 100,
+            };
+            let after_upgrade = CacheKey {
+                spec_version: 101,
+                ..before_upgrade.clone()
+            };
+
+            cache
+                .insert(before_upgrade.clone(), Arc::from(&b"old metadata"[..]))
+                .await;
+            assert_eq!(
+                cache.get(&before_upgrade).await.as_deref(),
+                Some(&b"old metadata"[..])
+            );
+
+            // A block using the upgraded runtime must not be served the stale metadata.
+            assert!(cache.get(&after_upgrade).await.is_none());
+
+            cache
+                .insert(after_upgrade.clone(), Arc::from(&b"new metadata"[..]))
+                .await;
+            assert_eq!(
+                cache.get(&after_upgrade).await.as_deref(),
+                Some(&b"new metadata"[..])
+            );
+            assert_eq!(
+                cache.get(&before_upgrade).await.as_deref(),
+                Some(&b"old metadata"[..])
+            );
+        });
+    }
+}
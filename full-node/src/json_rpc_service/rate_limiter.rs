@@ -0,0 +1,123 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    num::NonZero,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Token bucket rate limiter, shared between all the requests handlers.
+///
+/// The bucket holds at most as many tokens as the number of requests allowed per second, and
+/// is refilled continuously. Each request consumes one token, and is rejected if the bucket is
+/// empty.
+pub struct RateLimiter {
+    /// Maximum number of tokens in the bucket.
+    max_tokens: NonZero<u32>,
+    /// Duration after which one token is added back to the bucket.
+    refill_interval: Duration,
+    /// State of the bucket.
+    inner: Mutex<Bucket>,
+}
+
+struct Bucket {
+    /// Number of tokens currently available.
+    tokens: u32,
+    /// Moment when the bucket was last refilled.
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Builds a new rate limiter allowing `requests_per_second` requests per second on average.
+    /// The bucket is initially full.
+    pub fn new(requests_per_second: NonZero<u32>) -> Self {
+        Self::with_start(requests_per_second, Instant::now())
+    }
+
+    fn with_start(requests_per_second: NonZero<u32>, now: Instant) -> Self {
+        RateLimiter {
+            max_tokens: requests_per_second,
+            refill_interval: Duration::from_secs(1) / requests_per_second.get(),
+            inner: Mutex::new(Bucket {
+                tokens: requests_per_second.get(),
+                last_refill: now,
+            }),
+        }
+    }
+
+    /// Tries to consume one token. Returns `false` if the request must be rejected.
+    pub fn try_acquire(&self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> bool {
+        let mut bucket = self.inner.lock().unwrap();
+
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        let new_tokens = elapsed.as_nanos() / self.refill_interval.as_nanos();
+        if u128::from(bucket.tokens) + new_tokens >= u128::from(self.max_tokens.get()) {
+            bucket.tokens = self.max_tokens.get();
+            bucket.last_refill = now;
+        } else {
+            // `new_tokens` is strictly inferior to `max_tokens` and thus fits in a `u32`.
+            let new_tokens = u32::try_from(new_tokens).unwrap();
+            bucket.tokens += new_tokens;
+            bucket.last_refill += self.refill_interval * new_tokens;
+        }
+
+        if bucket.tokens == 0 {
+            return false;
+        }
+
+        bucket.tokens -= 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RateLimiter;
+    use std::{
+        num::NonZero,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn burst_then_refill() {
+        let start = Instant::now();
+        let limiter = RateLimiter::with_start(NonZero::<u32>::new(4).unwrap(), start);
+
+        // The bucket is initially full.
+        for _ in 0..4 {
+            assert!(limiter.try_acquire_at(start));
+        }
+        assert!(!limiter.try_acquire_at(start));
+
+        // One token is added back every 250ms.
+        assert!(!limiter.try_acquire_at(start + Duration::from_millis(200)));
+        assert!(limiter.try_acquire_at(start + Duration::from_millis(250)));
+        assert!(!limiter.try_acquire_at(start + Duration::from_millis(300)));
+
+        // The bucket never holds more than its capacity.
+        let later = start + Duration::from_secs(60);
+        for _ in 0..4 {
+            assert!(limiter.try_acquire_at(later));
+        }
+        assert!(!limiter.try_acquire_at(later));
+    }
+}
@@ -18,20 +18,28 @@
 use futures_lite::future;
 use smol::stream::StreamExt as _;
 use smoldot::{
-    executor,
+    chain_spec,
+    database::full_sqlite,
+    executor, header,
+    identity::keystore,
     json_rpc::{methods, parse, service},
-    trie,
+    libp2p, trie,
 };
 use std::{
+    cmp,
     future::Future,
     iter,
     pin::{self, Pin},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use crate::{
     consensus_service, database_thread,
-    json_rpc_service::{legacy_api_subscriptions, runtime_caches_service},
+    json_rpc_service::{
+        legacy_api_subscriptions, metadata_cache, rate_limiter, runtime_caches_service,
+        runtime_versions_cache, DefaultBlock,
+    },
     network_service, LogCallback, LogLevel,
 };
 
@@ -44,8 +52,12 @@ pub struct Config {
     /// Function called in order to notify of something.
     pub log_callback: Arc<dyn LogCallback + Send + Sync>,
 
+    /// Receives the subscription starts to process.
     pub receiver: async_channel::Receiver<Message>,
 
+    /// Receives the requests to process.
+    pub requests_receiver: async_channel::Receiver<service::RequestProcess>,
+
     /// Database to access blocks.
     pub database: Arc<database_thread::DatabaseThread>,
 
@@ -62,14 +74,22 @@ pub struct Config {
     /// Type of the chain, as found in the chain specification.
     pub chain_type: String,
 
-    /// JSON-encoded properties of the chain, as found in the chain specification.
-    pub chain_properties_json: String,
+    /// Properties of the chain, as found in the chain specification.
+    pub chain_properties: Arc<chain_spec::ChainProperties>,
 
     /// Whether the chain is a live network. Found in the chain specification.
     pub chain_is_live: bool,
 
+    /// Fork id of the chain, as found in the chain specification.
+    pub chain_fork_id: Option<String>,
+
+    /// Legacy protocol id of the chain, as found in the chain specification.
+    pub chain_protocol_id: Option<String>,
+
+    /// Chain specification of the chain.
+    pub chain_spec: Arc<chain_spec::ChainSpec>,
+
     /// Hash of the genesis block.
-    // TODO: load from database maybe?
     pub genesis_block_hash: [u8; 32],
 
     /// Consensus service of the chain.
@@ -77,27 +97,312 @@ pub struct Config {
 
     /// Runtime caches service of the JSON-RPC service.
     pub runtime_caches_service: Arc<runtime_caches_service::RuntimeCachesService>,
+
+    /// Cache of the metadata of the runtimes, shared between all the requests handlers.
+    pub metadata_cache: Arc<metadata_cache::MetadataCache>,
+
+    /// Cache of the runtime versions of blocks, shared between all the requests handlers.
+    pub runtime_versions_cache: Arc<runtime_versions_cache::RuntimeVersionsCache>,
+
+    /// Rate limiter applied to the JSON-RPC functions that execute the runtime, shared between
+    /// all the requests handlers. If `None`, these functions aren't rate-limited.
+    pub expensive_requests_rate_limiter: Option<Arc<rate_limiter::RateLimiter>>,
+
+    /// If `true`, the name of each request and the time it took to answer it are reported to
+    /// [`Config::log_callback`].
+    pub log_requests_durations: bool,
+
+    /// Maximum number of keys that `state_getKeys` can return. If more keys would be returned,
+    /// the request fails.
+    pub state_get_keys_max_keys: usize,
+
+    /// If `false`, the JSON-RPC functions that are considered unsafe are rejected.
+    pub allow_unsafe_methods: bool,
+
+    /// Keystore where the `author_insertKey` JSON-RPC function inserts keys.
+    pub keystore: Arc<keystore::Keystore>,
+
+    /// If `true`, `chain_subscribeAllHeads` subscriptions immediately report the current best
+    /// block as their first notification. If `false`, only blocks that arrive after the
+    /// subscription are reported, which is what the specification mandates.
+    pub subscribe_all_heads_report_initial_best: bool,
+
+    /// Block to use when no block hash is passed to a function that accepts an optional one.
+    pub default_block: DefaultBlock,
+
+    /// Maximum duration of an access to the database. Requests whose database access takes
+    /// longer fail with an error rather than leave the JSON-RPC client waiting indefinitely.
+    pub database_timeout: Duration,
+
+    /// Consensus engine of the chain, or `None` if unknown.
+    pub consensus_engine: Option<methods::ConsensusEngine>,
+
+    /// Finality engine of the chain, or `None` if blocks are finalized through other means.
+    pub finality_engine: Option<methods::FinalityEngine>,
 }
 
+/// Number of blocks sent in each notification of a `chain_subscribeExportBlocks` subscription.
+const EXPORT_BLOCKS_CHUNK_SIZE: u64 = 16;
+
+/// Prefix under which the `offchain_localStorage*` JSON-RPC functions read and write in the
+/// off-chain storage. Matches the prefix used by Substrate.
+const OFFCHAIN_STORAGE_PREFIX: &[u8] = b"storage";
+
+/// Minimum amount of time between two notifications of a `system_subscribePeerCount`
+/// subscription. Changes to the number of peers that happen in between are coalesced, and only
+/// the latest number is sent.
+const PEER_COUNT_NOTIFICATIONS_MIN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Message of the error returned by the JSON-RPC functions that access the storage of a block
+/// whose storage has been pruned from the database.
+const STORAGE_PRUNED_ERROR_MESSAGE: &str =
+    "State unavailable: the storage of this block has been pruned";
+
+/// Message of the error returned by the JSON-RPC functions whose access to the database takes
+/// longer than [`Config::database_timeout`].
+const DATABASE_TIMEOUT_ERROR_MESSAGE: &str = "Database timeout";
+
+/// Message of the error returned by the JSON-RPC functions that are specific to a consensus
+/// engine the chain doesn't use, including when the consensus engine of the chain is unknown.
+const CONSENSUS_ENGINE_MISMATCH_ERROR_MESSAGE: &str = "Not available for this consensus engine";
+
+/// Number of blocks by which the best block announced by the peers must be ahead of the local
+/// best block for `system_health` to report that the node is syncing.
+const IS_SYNCING_BLOCKS_THRESHOLD: u64 = 5;
+
+/// Maximum number of blocks that a single `state_unstable_runtimeUpgrades` request can scan.
+const RUNTIME_UPGRADES_MAX_RANGE: u64 = 100_000;
+
+/// Maximum number of block headers read in a single database access when
+/// `state_unstable_runtimeUpgrades` looks for runtime changes in blocks whose storage has been
+/// pruned.
+const RUNTIME_UPGRADES_CHUNK_SIZE: u64 = 1024;
+
+/// Maximum difference between the number of a block returned by `chain_unstable_blockTree` and
+/// the number of the finalized block.
+const BLOCK_TREE_MAX_DEPTH: u64 = 256;
+
+/// Maximum number of blocks with the same number returned by `chain_unstable_blockTree`.
+const BLOCK_TREE_MAX_BLOCKS_PER_HEIGHT: usize = 8;
+
 pub enum Message {
     Request(service::RequestProcess),
     SubscriptionStart(service::SubscriptionStartProcess),
 }
 
+/// Reports the name and duration of a request to the logs when destroyed.
+struct RequestDurationLog {
+    log_callback: Arc<dyn LogCallback + Send + Sync>,
+    method: &'static str,
+    start: Instant,
+}
+
+impl Drop for RequestDurationLog {
+    fn drop(&mut self) {
+        self.log_callback.log(
+            LogLevel::Debug,
+            format!(
+                "json-rpc-request-duration; method={}; duration={:?}",
+                self.method,
+                self.start.elapsed()
+            ),
+        );
+    }
+}
+
 pub fn spawn_requests_handler(config: Config) {
     let tasks_executor = config.tasks_executor.clone();
     tasks_executor(Box::pin(async move {
         let mut receiver = pin::pin!(config.receiver);
+        let mut requests_receiver = pin::pin!(config.requests_receiver);
+        let default_block = config.default_block;
         loop {
-            match receiver.next().await {
+            // Subscription starts are polled first, as they can't be discarded and are
+            // expected to be rare.
+            let message = future::or(async { receiver.next().await }, async {
+                requests_receiver.next().await.map(Message::Request)
+            })
+            .await;
+
+            // Reports the duration of the request when dropped, in other words at the end of
+            // this iteration of the loop, no matter how the request has been handled. Requests
+            // that are answered by a separate task move it to this task instead.
+            let mut duration_log = match &message {
+                Some(Message::Request(request)) if config.log_requests_durations => {
+                    Some(RequestDurationLog {
+                        log_callback: config.log_callback.clone(),
+                        method: request.request().name(),
+                        start: Instant::now(),
+                    })
+                }
+                _ => None,
+            };
+
+            match message {
+                Some(Message::Request(request))
+                    if !config.allow_unsafe_methods && request.request().is_unsafe() =>
+                {
+                    request.fail(service::ErrorResponse::ServerError(
+                        -32000,
+                        "RPC call is unsafe to be called externally",
+                    ));
+                }
+                Some(Message::Request(request))
+                    if request
+                        .request()
+                        .consensus_engine()
+                        .is_some_and(|engine| config.consensus_engine != Some(engine))
+                        || request
+                            .request()
+                            .finality_engine()
+                            .is_some_and(|engine| config.finality_engine != Some(engine)) =>
+                {
+                    request.fail(service::ErrorResponse::ServerError(
+                        -32000,
+                        CONSENSUS_ENGINE_MISMATCH_ERROR_MESSAGE,
+                    ));
+                }
+                Some(Message::Request(request))
+                    if request.request().is_expensive()
+                        && config
+                            .expensive_requests_rate_limiter
+                            .as_ref()
+                            .is_some_and(|limiter| !limiter.try_acquire()) =>
+                {
+                    request.fail(service::ErrorResponse::ServerError(
+                        -32000,
+                        "Too many requests; try again later",
+                    ));
+                }
                 Some(Message::Request(request)) => match request.request() {
-                    methods::MethodCall::rpc_methods {} => {
-                        request.respond(methods::Response::rpc_methods(methods::RpcMethods {
-                            methods: methods::MethodCall::method_names()
-                                .map(|n| n.into())
-                                .collect(),
+                    methods::MethodCall::author_insertKey {
+                        key_type,
+                        suri,
+                        public_key,
+                    } => {
+                        let Some(namespace) = keystore::KeyNamespace::from_string(&key_type) else {
+                            request.fail(service::ErrorResponse::InvalidParams);
+                            continue;
+                        };
+                        let Ok(public_key) = <[u8; 32]>::try_from(&public_key.0[..]) else {
+                            request.fail(service::ErrorResponse::InvalidParams);
+                            continue;
+                        };
+
+                        match config
+                            .keystore
+                            .insert_from_secret_uri(namespace, &suri, &public_key)
+                            .await
+                        {
+                            Ok(()) => request.respond(methods::Response::author_insertKey(())),
+                            Err(keystore::InsertFromSecretUriError::InvalidSecretUri(_)) => {
+                                request.fail(service::ErrorResponse::InvalidParams)
+                            }
+                            Err(keystore::InsertFromSecretUriError::PublicKeyMismatch) => request
+                                .fail(service::ErrorResponse::ServerError(
+                                    -32000,
+                                    "Public key doesn't match the secret URI",
+                                )),
+                            Err(keystore::InsertFromSecretUriError::Io(_)) => {
+                                request.fail(service::ErrorResponse::InternalError)
+                            }
+                        }
+                    }
+                    methods::MethodCall::author_submitExtrinsic { transaction } => {
+                        let consensus_service = config.consensus_service.clone();
+                        let duration_log = duration_log.take();
+
+                        (config.tasks_executor)(Box::pin(async move {
+                            let (hash, status_updates) =
+                                consensus_service.submit_and_watch(transaction.0).await;
+                            let mut status_updates = Box::pin(status_updates);
+
+                            // The transaction is validated against the runtime of the best block
+                            // before being sent to peers, which is always the first status.
+                            match status_updates.next().await {
+                                Some(consensus_service::TxStatus::Validated) => {}
+                                Some(consensus_service::TxStatus::Invalid(error)) => {
+                                    request.fail_with_attached_json(
+                                        service::ErrorResponse::ApplicationDefined(
+                                            1010,
+                                            "Invalid transaction",
+                                        ),
+                                        &serde_json::to_string(&error.to_string()).unwrap(),
+                                    );
+                                    drop(duration_log);
+                                    return;
+                                }
+                                _ => {
+                                    request.fail(service::ErrorResponse::ApplicationDefined(
+                                        1011,
+                                        "Transaction validity couldn't be determined",
+                                    ));
+                                    drop(duration_log);
+                                    return;
+                                }
+                            }
+
+                            request.respond(methods::Response::author_submitExtrinsic(
+                                methods::HashHexString(hash),
+                            ));
+                            drop(duration_log);
+
+                            // Destroying the stream would stop the transaction from being sent to
+                            // peers. Keep watching it until it is either finalized or dropped,
+                            // after which the stream ends.
+                            while status_updates.next().await.is_some() {}
                         }));
                     }
+                    methods::MethodCall::offchain_localStorageGet { kind, key } => {
+                        let Some(kind) = offchain_storage_kind_from_str(&kind) else {
+                            request.fail(service::ErrorResponse::InvalidParams);
+                            continue;
+                        };
+
+                        match config
+                            .database
+                            .offchain_storage_get(kind, OFFCHAIN_STORAGE_PREFIX.to_vec(), key.0)
+                            .await
+                        {
+                            Ok(value) => {
+                                request.respond(methods::Response::offchain_localStorageGet(
+                                    value.map(methods::HexString),
+                                ))
+                            }
+                            Err(_) => request.fail(service::ErrorResponse::InternalError),
+                        }
+                    }
+                    methods::MethodCall::offchain_localStorageSet { kind, key, value } => {
+                        let Some(kind) = offchain_storage_kind_from_str(&kind) else {
+                            request.fail(service::ErrorResponse::InvalidParams);
+                            continue;
+                        };
+
+                        match config
+                            .database
+                            .offchain_storage_set(
+                                kind,
+                                OFFCHAIN_STORAGE_PREFIX.to_vec(),
+                                key.0,
+                                value.0,
+                            )
+                            .await
+                        {
+                            Ok(()) => {
+                                request.respond(methods::Response::offchain_localStorageSet(()))
+                            }
+                            Err(_) => request.fail(service::ErrorResponse::InternalError),
+                        }
+                    }
+
+                    methods::MethodCall::rpc_methods {} => {
+                        request.respond(methods::Response::rpc_methods(
+                            methods::RpcMethods::from_method_names(
+                                methods::MethodCall::method_names()
+                                    .chain(methods::MethodCall::method_aliases()),
+                            ),
+                        ));
+                    }
 
                     methods::MethodCall::chainSpec_v1_chainName {} => {
                         request.respond(methods::Response::chainSpec_v1_chainName(
@@ -111,10 +416,141 @@ pub fn spawn_requests_handler(config: Config) {
                     }
                     methods::MethodCall::chainSpec_v1_properties {} => {
                         request.respond(methods::Response::chainSpec_v1_properties(
-                            serde_json::from_str(&config.chain_properties_json).unwrap(),
+                            serde_json::from_str(config.chain_properties.as_json()).unwrap(),
+                        ));
+                    }
+                    methods::MethodCall::chainSpec_unstable_relayChain {} => {
+                        request.respond(methods::Response::chainSpec_unstable_relayChain(
+                            config.chain_spec.relay_chain().map(|(id, _)| id.into()),
                         ));
                     }
 
+                    methods::MethodCall::chain_getBlock {
+                        hash,
+                        decode_extrinsics,
+                    } => {
+                        let Some((hash, request)) = block_hash_or_default(
+                            &config.database,
+                            config.database_timeout,
+                            default_block,
+                            &*config.log_callback,
+                            hash,
+                            request,
+                        )
+                        .await
+                        else {
+                            continue;
+                        };
+
+                        let result = match config
+                            .database
+                            .with_database_timeout(config.database_timeout, move |db| {
+                                let Some(header) = db.block_scale_encoded_header(&hash)? else {
+                                    return Ok(None);
+                                };
+                                let Some(extrinsics) = db.block_extrinsics(&hash)? else {
+                                    return Ok(None);
+                                };
+                                Ok::<_, full_sqlite::CorruptedError>(Some((
+                                    header,
+                                    extrinsics.collect::<Vec<_>>(),
+                                )))
+                            })
+                            .await
+                        {
+                            Ok(result) => result,
+                            Err(database_thread::DatabaseTimeoutError) => {
+                                fail_database_timeout(&*config.log_callback, request);
+                                continue;
+                            }
+                        };
+
+                        let (header, extrinsics) = match result {
+                            Ok(Some(block)) => block,
+                            Ok(None) => {
+                                request.respond_null();
+                                continue;
+                            }
+                            Err(_) => {
+                                request.fail(service::ErrorResponse::InternalError);
+                                continue;
+                            }
+                        };
+
+                        let Ok(header) = methods::Header::from_scale_encoded_header(
+                            &header,
+                            config.consensus_service.block_number_bytes(),
+                        ) else {
+                            request.fail(service::ErrorResponse::InternalError);
+                            continue;
+                        };
+
+                        // Decoding the extrinsics requires the metadata of the runtime of the
+                        // block, which is expensive to obtain. It is only done if explicitly
+                        // requested.
+                        let decoded_extrinsics = if decode_extrinsics == Some(true) {
+                            let metadata = match block_metadata(
+                                &config.database,
+                                &config.runtime_caches_service,
+                                &config.metadata_cache,
+                                hash,
+                                config.database_timeout,
+                            )
+                            .await
+                            {
+                                Ok(metadata) => metadata,
+                                Err(BlockMetadataError::Timeout) => {
+                                    fail_database_timeout(&*config.log_callback, request);
+                                    continue;
+                                }
+                                Err(BlockMetadataError::UnknownBlock) => {
+                                    request.respond_null();
+                                    continue;
+                                }
+                                Err(BlockMetadataError::Pruned) => {
+                                    request.fail(service::ErrorResponse::ServerError(
+                                        -32000,
+                                        STORAGE_PRUNED_ERROR_MESSAGE,
+                                    ));
+                                    continue;
+                                }
+                                Err(BlockMetadataError::Other) => {
+                                    request.fail(service::ErrorResponse::InternalError);
+                                    continue;
+                                }
+                            };
+
+                            let Ok(metadata) = smoldot::metadata::Metadata::decode(&metadata)
+                            else {
+                                request.fail(service::ErrorResponse::ServerError(
+                                    -32000,
+                                    "Unsupported runtime metadata format",
+                                ));
+                                continue;
+                            };
+
+                            Some(
+                                extrinsics
+                                    .iter()
+                                    .map(|extrinsic| {
+                                        metadata
+                                            .decode_extrinsic(extrinsic)
+                                            .ok()
+                                            .map(convert_decoded_extrinsic)
+                                    })
+                                    .collect(),
+                            )
+                        } else {
+                            None
+                        };
+
+                        request.respond(methods::Response::chain_getBlock(methods::Block {
+                            extrinsics: extrinsics.into_iter().map(methods::HexString).collect(),
+                            header,
+                            justifications: None,
+                            decoded_extrinsics,
+                        }));
+                    }
                     methods::MethodCall::chain_getBlockHash { height: Some(0) } => {
                         // In the case where the database was populated through a warp sync, it
                         // might not store block 0 in it. However, the hash of block 0 is
@@ -126,13 +562,50 @@ pub fn spawn_requests_handler(config: Config) {
                         ))
                     }
                     methods::MethodCall::chain_getBlockHash { height } => {
-                        let outcome = config
+                        let Some(height) = height else {
+                            if let Some((hash, request)) = block_hash_or_default(
+                                &config.database,
+                                config.database_timeout,
+                                default_block,
+                                &*config.log_callback,
+                                None,
+                                request,
+                            )
+                            .await
+                            {
+                                request.respond(methods::Response::chain_getBlockHash(
+                                    methods::HashHexString(hash),
+                                ));
+                            }
+                            continue;
+                        };
+
+                        // Blocks above the current best block can't be part of the best chain.
+                        // Respond `null` to them without querying the database.
+                        if height
+                            > config
+                                .consensus_service
+                                .sync_state()
+                                .await
+                                .best_block_number
+                        {
+                            request.respond_null();
+                            continue;
+                        }
+
+                        let outcome = match config
                             .database
-                            .with_database(move |database| match height {
-                                Some(height) => database.best_block_hash_by_number(height),
-                                None => database.best_block_hash().map(Some),
+                            .with_database_timeout(config.database_timeout, move |database| {
+                                database.best_block_hash_by_number(height)
                             })
-                            .await;
+                            .await
+                        {
+                            Ok(result) => result,
+                            Err(database_thread::DatabaseTimeoutError) => {
+                                fail_database_timeout(&*config.log_callback, request);
+                                continue;
+                            }
+                        };
                         match outcome {
                             Ok(Some(hash)) => request.respond(
                                 methods::Response::chain_getBlockHash(methods::HashHexString(hash)),
@@ -144,26 +617,83 @@ pub fn spawn_requests_handler(config: Config) {
                             }
                         }
                     }
+                    methods::MethodCall::chain_getBlockStats { hash } => {
+                        let Some((hash, request)) = block_hash_or_default(
+                            &config.database,
+                            config.database_timeout,
+                            default_block,
+                            &*config.log_callback,
+                            hash,
+                            request,
+                        )
+                        .await
+                        else {
+                            continue;
+                        };
+
+                        let result = match config
+                            .database
+                            .with_database_timeout(config.database_timeout, move |db| {
+                                let Some(encoded_block) = db.block_scale_encoded(&hash)? else {
+                                    return Ok(None);
+                                };
+                                let Some(extrinsics) = db.block_extrinsics(&hash)? else {
+                                    return Ok(None);
+                                };
+                                Ok::<_, full_sqlite::CorruptedError>(Some(methods::BlockStats {
+                                    encoded_size: u64::try_from(encoded_block.len()).unwrap(),
+                                    num_extrinsics: u64::try_from(extrinsics.len()).unwrap(),
+                                }))
+                            })
+                            .await
+                        {
+                            Ok(result) => result,
+                            Err(database_thread::DatabaseTimeoutError) => {
+                                fail_database_timeout(&*config.log_callback, request);
+                                continue;
+                            }
+                        };
+
+                        match result {
+                            Ok(Some(stats)) => {
+                                request.respond(methods::Response::chain_getBlockStats(stats))
+                            }
+                            Ok(None) => {
+                                request.respond_null();
+                            }
+                            Err(_) => {
+                                request.fail(service::ErrorResponse::InternalError);
+                            }
+                        }
+                    }
+
                     methods::MethodCall::chain_getHeader { hash } => {
-                        let hash = match hash {
-                            Some(h) => h.0,
-                            None => match config
-                                .database
-                                .with_database(|db| db.best_block_hash())
-                                .await
-                            {
-                                Ok(b) => b,
-                                Err(_) => {
-                                    request.fail(service::ErrorResponse::InternalError);
-                                    continue;
-                                }
-                            },
+                        let Some((hash, request)) = block_hash_or_default(
+                            &config.database,
+                            config.database_timeout,
+                            default_block,
+                            &*config.log_callback,
+                            hash,
+                            request,
+                        )
+                        .await
+                        else {
+                            continue;
                         };
 
-                        let result = config
+                        let result = match config
                             .database
-                            .with_database(move |db| db.block_scale_encoded_header(&hash))
-                            .await;
+                            .with_database_timeout(config.database_timeout, move |db| {
+                                db.block_scale_encoded_header(&hash)
+                            })
+                            .await
+                        {
+                            Ok(result) => result,
+                            Err(database_thread::DatabaseTimeoutError) => {
+                                fail_database_timeout(&*config.log_callback, request);
+                                continue;
+                            }
+                        };
 
                         match result {
                             Ok(Some(encoded_header)) => {
@@ -187,6 +717,185 @@ pub fn spawn_requests_handler(config: Config) {
                             }
                         }
                     }
+                    methods::MethodCall::state_getKeys { prefix, hash } => {
+                        let prefix_nibbles = trie::bytes_to_nibbles(prefix.0.iter().copied())
+                            .map(u8::from)
+                            .collect::<Vec<_>>();
+
+                        // One more key than the maximum is requested in order to detect whether
+                        // the limit is exceeded.
+                        let max_keys = config.state_get_keys_max_keys;
+                        let result = match config
+                            .database
+                            .with_database_timeout(
+                                config.database_timeout,
+                                move |db| -> Result<_, database_thread::StorageAccessError> {
+                                    let hash = match hash {
+                                        Some(h) => h.0,
+                                        None => default_block_hash(db, default_block)?,
+                                    };
+
+                                    storage_keys_with_prefix(
+                                        db,
+                                        &hash,
+                                        &prefix_nibbles,
+                                        prefix_nibbles.clone(),
+                                        max_keys.saturating_add(1),
+                                    )
+                                },
+                            )
+                            .await
+                        {
+                            Ok(result) => result,
+                            Err(database_thread::DatabaseTimeoutError) => {
+                                fail_database_timeout(&*config.log_callback, request);
+                                continue;
+                            }
+                        };
+
+                        match result {
+                            Ok(out) if out.len() > max_keys => {
+                                request.fail(service::ErrorResponse::ServerError(
+                                    -32000,
+                                    "Too many keys to return; use state_getKeysPaged instead",
+                                ));
+                            }
+                            Ok(out) => {
+                                request.respond(methods::Response::state_getKeys(out));
+                            }
+                            Err(database_thread::StorageAccessError::IncompleteStorage)
+                            | Err(database_thread::StorageAccessError::UnknownBlock) => {
+                                // Note that it is unclear how the function should behave in
+                                // that situation.
+                                request.fail(service::ErrorResponse::InvalidParams);
+                            }
+                            Err(database_thread::StorageAccessError::StoragePruned) => {
+                                request.fail(service::ErrorResponse::ServerError(
+                                    -32000,
+                                    STORAGE_PRUNED_ERROR_MESSAGE,
+                                ));
+                            }
+                            Err(database_thread::StorageAccessError::Corrupted(_)) => {
+                                request.fail(service::ErrorResponse::InternalError);
+                            }
+                        }
+                    }
+                    methods::MethodCall::state_unstable_getRuntimeCode { hash } => {
+                        let result = match config
+                            .database
+                            .with_database_timeout(
+                                config.database_timeout,
+                                move |db| -> Result<_, database_thread::StorageAccessError> {
+                                    let hash = match hash {
+                                        Some(h) => h.0,
+                                        None => default_block_hash(db, default_block)?,
+                                    };
+
+                                    Ok(db
+                                        .block_storage_get(
+                                            &hash,
+                                            iter::empty::<iter::Empty<_>>(),
+                                            trie::bytes_to_nibbles(b":code".iter().copied())
+                                                .map(u8::from),
+                                        )?
+                                        .map(|(code, _)| code))
+                                },
+                            )
+                            .await
+                        {
+                            Ok(result) => result,
+                            Err(database_thread::DatabaseTimeoutError) => {
+                                fail_database_timeout(&*config.log_callback, request);
+                                continue;
+                            }
+                        };
+
+                        match result {
+                            Ok(Some(code)) => {
+                                match executor::host::decompress_runtime_code(&code) {
+                                    Ok(code) => {
+                                        request.respond(
+                                            methods::Response::state_unstable_getRuntimeCode(Some(
+                                                methods::HexString(code.into_owned()),
+                                            )),
+                                        );
+                                    }
+                                    Err(_) => {
+                                        request.fail(service::ErrorResponse::ServerError(
+                                            -32000,
+                                            "Failed to decompress the runtime code",
+                                        ));
+                                    }
+                                }
+                            }
+                            Ok(None) => {
+                                request.respond(methods::Response::state_unstable_getRuntimeCode(
+                                    None,
+                                ));
+                            }
+                            Err(database_thread::StorageAccessError::IncompleteStorage)
+                            | Err(database_thread::StorageAccessError::UnknownBlock) => {
+                                request.fail(service::ErrorResponse::InvalidParams);
+                            }
+                            Err(database_thread::StorageAccessError::StoragePruned) => {
+                                request.fail(service::ErrorResponse::ServerError(
+                                    -32000,
+                                    STORAGE_PRUNED_ERROR_MESSAGE,
+                                ));
+                            }
+                            Err(database_thread::StorageAccessError::Corrupted(_)) => {
+                                request.fail(service::ErrorResponse::InternalError);
+                            }
+                        }
+                    }
+                    methods::MethodCall::state_unstable_runtimeUpgrades { from, to } => {
+                        // Only blocks of the best chain are scanned.
+                        if from > to
+                            || to - from >= RUNTIME_UPGRADES_MAX_RANGE
+                            || to
+                                > config
+                                    .consensus_service
+                                    .sync_state()
+                                    .await
+                                    .best_block_number
+                        {
+                            request.fail(service::ErrorResponse::InvalidParams);
+                            continue;
+                        }
+
+                        match runtime_upgrades(
+                            &config.database,
+                            &config.runtime_caches_service,
+                            &config.runtime_versions_cache,
+                            config.consensus_service.block_number_bytes(),
+                            config.database_timeout,
+                            from,
+                            to,
+                        )
+                        .await
+                        {
+                            Ok(upgrades) => {
+                                request.respond(methods::Response::state_unstable_runtimeUpgrades(
+                                    upgrades,
+                                ));
+                            }
+                            Err(RuntimeUpgradesError::UnknownBlock) => {
+                                request.fail(service::ErrorResponse::InvalidParams);
+                            }
+                            Err(RuntimeUpgradesError::Pruned) => {
+                                request.fail(service::ErrorResponse::ServerError(
+                                    -32000,
+                                    STORAGE_PRUNED_ERROR_MESSAGE,
+                                ));
+                            }
+                            Err(RuntimeUpgradesError::Timeout) => {
+                                fail_database_timeout(&*config.log_callback, request);
+                            }
+                            Err(RuntimeUpgradesError::Other) => {
+                                request.fail(service::ErrorResponse::InternalError);
+                            }
+                        }
+                    }
                     methods::MethodCall::state_getKeysPaged {
                         prefix,
                         count,
@@ -207,9 +916,13 @@ pub fn spawn_requests_handler(config: Config) {
                                 .map(u8::from)
                                 .collect()
                         });
+                        // The start key is exclusive, so that clients can paginate by passing the
+                        // last key of the previous page. Pushing an extra nibble to the start key
+                        // makes the database query below skip it.
                         let mut start_key_nibbles = start_key.map_or(Vec::new(), |p| {
                             trie::bytes_to_nibbles(p.0.iter().copied())
                                 .map(u8::from)
+                                .chain(iter::once(0))
                                 .collect()
                         });
 
@@ -224,55 +937,33 @@ pub fn spawn_requests_handler(config: Config) {
                         }
 
                         // Continue in the background.
-                        let result = config
+                        let result = match config
                             .database
-                            .with_database(
+                            .with_database_timeout(
+                                config.database_timeout,
                                 move |db| -> Result<_, database_thread::StorageAccessError> {
                                     let hash = match hash {
                                         Some(h) => h.0,
-                                        None => db.best_block_hash()?,
+                                        None => default_block_hash(db, default_block)?,
                                     };
 
-                                    let mut out =
-                                        Vec::with_capacity(usize::try_from(count).unwrap());
-
-                                    let mut key_iter = start_key_nibbles;
-
-                                    // The query is performed by repeatedly asking for the next
-                                    // key.
-                                    while out.len() < usize::try_from(count).unwrap() {
-                                        let next_key_nibbles = db.block_storage_next_key(
-                                            &hash,
-                                            iter::empty::<iter::Empty<_>>(),
-                                            key_iter.iter().copied(),
-                                            prefix_nibbles.iter().copied(),
-                                            false,
-                                        )?;
-
-                                        let Some(next_key_nibbles) = next_key_nibbles else {
-                                            break;
-                                        };
-
-                                        out.push(methods::HexString(
-                                            trie::nibbles_to_bytes_truncate(
-                                                next_key_nibbles
-                                                    .iter()
-                                                    .copied()
-                                                    .map(|n| trie::Nibble::try_from(n).unwrap()),
-                                            )
-                                            .collect::<Vec<_>>(),
-                                        ));
-
-                                        // Push an extra nibble as otherwise `block_storage_next_key`
-                                        // will return the same key again.
-                                        key_iter = next_key_nibbles;
-                                        key_iter.push(0);
-                                    }
-
-                                    Ok(out)
+                                    storage_keys_with_prefix(
+                                        db,
+                                        &hash,
+                                        &prefix_nibbles,
+                                        start_key_nibbles,
+                                        usize::try_from(count).unwrap(),
+                                    )
                                 },
                             )
-                            .await;
+                            .await
+                        {
+                            Ok(result) => result,
+                            Err(database_thread::DatabaseTimeoutError) => {
+                                fail_database_timeout(&*config.log_callback, request);
+                                continue;
+                            }
+                        };
 
                         // Send back outcome.
                         match result {
@@ -285,231 +976,101 @@ pub fn spawn_requests_handler(config: Config) {
                                 // that situation.
                                 request.fail(service::ErrorResponse::InvalidParams);
                             }
+                            Err(database_thread::StorageAccessError::StoragePruned) => {
+                                request.fail(service::ErrorResponse::ServerError(
+                                    -32000,
+                                    STORAGE_PRUNED_ERROR_MESSAGE,
+                                ));
+                            }
                             Err(database_thread::StorageAccessError::Corrupted(_)) => {
                                 request.fail(service::ErrorResponse::InternalError);
                             }
                         }
                     }
                     methods::MethodCall::state_getMetadata { hash } => {
-                        let hash = match hash {
-                            Some(h) => h.0,
-                            None => match config
-                                .database
-                                .with_database(|db| db.best_block_hash())
-                                .await
-                            {
-                                Ok(b) => b,
-                                Err(_) => {
-                                    request.fail(service::ErrorResponse::InternalError);
-                                    continue;
-                                }
-                            },
+                        let Some((hash, request)) = block_hash_or_default(
+                            &config.database,
+                            config.database_timeout,
+                            default_block,
+                            &*config.log_callback,
+                            hash,
+                            request,
+                        )
+                        .await
+                        else {
+                            continue;
                         };
 
-                        let runtime = match config.runtime_caches_service.get(hash).await {
-                            Ok(runtime) => (*runtime).clone(),
-                            Err(runtime_caches_service::GetError::UnknownBlock)
-                            | Err(runtime_caches_service::GetError::Pruned) => {
-                                request.respond_null();
-                                continue;
-                            } // TODO: unclear if correct error
-                            Err(runtime_caches_service::GetError::InvalidRuntime(_))
-                            | Err(runtime_caches_service::GetError::NoCode)
-                            | Err(runtime_caches_service::GetError::InvalidHeapPages)
-                            | Err(runtime_caches_service::GetError::CorruptedDatabase) => {
-                                request.fail(service::ErrorResponse::InternalError);
-                                continue;
+                        match block_metadata(
+                            &config.database,
+                            &config.runtime_caches_service,
+                            &config.metadata_cache,
+                            hash,
+                            config.database_timeout,
+                        )
+                        .await
+                        {
+                            Ok(metadata) => request.respond(methods::Response::state_getMetadata(
+                                methods::HexString(metadata.to_vec()),
+                            )),
+                            Err(BlockMetadataError::Timeout) => {
+                                fail_database_timeout(&*config.log_callback, request)
                             }
-                        };
-
-                        let mut call =
-                            match executor::runtime_call::run(executor::runtime_call::Config {
-                                virtual_machine: runtime,
-                                function_to_call: "Metadata_metadata",
-                                parameter: iter::empty::<&'static [u8]>(),
-                                max_log_level: 0,
-                                storage_proof_size_behavior: executor::runtime_call::StorageProofSizeBehavior::proof_recording_disabled(),
-                                storage_main_trie_changes: Default::default(),
-                                calculate_trie_changes: false,
-                            }) {
-                                Ok(c) => c,
-                                Err(_) => {
-                                    request.fail(service::ErrorResponse::InternalError);
-                                    continue;
-                                }
-                            };
-
-                        loop {
-                            match call {
-                                executor::runtime_call::RuntimeCall::Finished(Ok(success)) => {
-                                    match methods::remove_metadata_length_prefix(success.virtual_machine.value().as_ref()) {
-                                        Ok(m) => request.respond(methods::Response::state_getMetadata(methods::HexString(m.to_vec()))),
-                                        Err(_) => {
-                                            request.fail(service::ErrorResponse::InternalError);
-                                        }
-                                    }
-                                    break;
-                                }
-                                executor::runtime_call::RuntimeCall::Finished(Err(_)) => {
-                                    request.fail(service::ErrorResponse::InternalError);
-                                    break;
-                                }
-                                executor::runtime_call::RuntimeCall::StorageGet(req) => {
-                                    let parent_paths = req.child_trie().map(|child_trie| {
-                                        trie::bytes_to_nibbles(
-                                            b":child_storage:default:".iter().copied(),
-                                        )
-                                        .chain(trie::bytes_to_nibbles(
-                                            child_trie.as_ref().iter().copied(),
-                                        ))
-                                        .map(u8::from)
-                                        .collect::<Vec<_>>()
-                                    });
-                                    let key =
-                                        trie::bytes_to_nibbles(req.key().as_ref().iter().copied())
-                                            .map(u8::from)
-                                            .collect::<Vec<_>>();
-                                    let value = config
-                                        .database
-                                        .with_database(move |db| {
-                                            db.block_storage_get(
-                                                &hash,
-                                                parent_paths.into_iter().map(|p| p.into_iter()),
-                                                key.iter().copied(),
-                                            )
-                                        })
-                                        .await;
-                                    let Ok(value) = value else {
-                                        request.fail(service::ErrorResponse::InternalError);
-                                        break;
-                                    };
-                                    let value = value.as_ref().map(|(val, vers)| {
-                                        (
-                                            iter::once(&val[..]),
-                                            executor::runtime_call::TrieEntryVersion::try_from(*vers)
-                                                .expect("corrupted database"),
-                                        )
-                                    });
-
-                                    call = req.inject_value(value);
-                                }
-                                executor::runtime_call::RuntimeCall::ClosestDescendantMerkleValue(req) => {
-                                    let parent_paths = req.child_trie().map(|child_trie| {
-                                        trie::bytes_to_nibbles(
-                                            b":child_storage:default:".iter().copied(),
-                                        )
-                                        .chain(trie::bytes_to_nibbles(
-                                            child_trie.as_ref().iter().copied(),
-                                        ))
-                                        .map(u8::from)
-                                        .collect::<Vec<_>>()
-                                    });
-                                    let key_nibbles = req.key().map(u8::from).collect::<Vec<_>>();
-
-                                    let merkle_value = config
-                                        .database
-                                        .with_database(move |db| {
-                                            db.block_storage_closest_descendant_merkle_value(
-                                                &hash,
-                                                parent_paths.into_iter().map(|p| p.into_iter()),
-                                                key_nibbles.iter().copied(),
-                                            )
-                                        })
-                                        .await;
-
-                                    let Ok(merkle_value) = merkle_value else {
-                                        request.fail(service::ErrorResponse::InternalError);
-                                        break;
-                                    };
-
-                                    call = req
-                                        .inject_merkle_value(merkle_value.as_ref().map(|v| &v[..]));
-                                }
-                                executor::runtime_call::RuntimeCall::NextKey(req) => {
-                                    let parent_paths = req.child_trie().map(|child_trie| {
-                                        trie::bytes_to_nibbles(
-                                            b":child_storage:default:".iter().copied(),
-                                        )
-                                        .chain(trie::bytes_to_nibbles(
-                                            child_trie.as_ref().iter().copied(),
-                                        ))
-                                        .map(u8::from)
-                                        .collect::<Vec<_>>()
-                                    });
-                                    let key_nibbles = req
-                                        .key()
-                                        .map(u8::from)
-                                        .chain(if req.or_equal() { None } else { Some(0u8) })
-                                        .collect::<Vec<_>>();
-                                    let prefix_nibbles =
-                                        req.prefix().map(u8::from).collect::<Vec<_>>();
-
-                                    let branch_nodes = req.branch_nodes();
-                                    let next_key = config
-                                        .database
-                                        .with_database(move |db| {
-                                            db.block_storage_next_key(
-                                                &hash,
-                                                parent_paths.into_iter().map(|p| p.into_iter()),
-                                                key_nibbles.iter().copied(),
-                                                prefix_nibbles.iter().copied(),
-                                                branch_nodes,
-                                            )
-                                        })
-                                        .await;
-
-                                    let Ok(next_key) = next_key else {
-                                        request.fail(service::ErrorResponse::InternalError);
-                                        break;
-                                    };
-
-                                    call = req.inject_key(next_key.map(|k| {
-                                        k.into_iter().map(|b| trie::Nibble::try_from(b).unwrap())
-                                    }));
-                                }
-                                executor::runtime_call::RuntimeCall::OffchainStorageSet(req) => {
-                                    call = req.resume();
-                                }
-                                executor::runtime_call::RuntimeCall::SignatureVerification(req) => {
-                                    call = req.verify_and_resume();
-                                }
-                                executor::runtime_call::RuntimeCall::Offchain(_) => {
-                                    request.fail(service::ErrorResponse::InternalError);
-                                    break;
-                                }
-                                executor::runtime_call::RuntimeCall::LogEmit(req) => {
-                                    // Logs are ignored.
-                                    call = req.resume();
-                                }
+                            Err(BlockMetadataError::UnknownBlock) => request.respond_null(), // TODO: unclear if correct error
+                            Err(BlockMetadataError::Pruned) => {
+                                request.fail(service::ErrorResponse::ServerError(
+                                    -32000,
+                                    STORAGE_PRUNED_ERROR_MESSAGE,
+                                ))
+                            }
+                            Err(BlockMetadataError::Other) => {
+                                request.fail(service::ErrorResponse::InternalError)
                             }
                         }
                     }
                     methods::MethodCall::state_getRuntimeVersion { at } => {
-                        let at = match at {
-                            Some(h) => h.0,
-                            None => match config
-                                .database
-                                .with_database(|db| db.best_block_hash())
-                                .await
-                            {
-                                Ok(b) => b,
-                                Err(_) => {
-                                    request.fail(service::ErrorResponse::InternalError);
-                                    continue;
-                                }
-                            },
+                        let Some((at, request)) = block_hash_or_default(
+                            &config.database,
+                            config.database_timeout,
+                            default_block,
+                            &*config.log_callback,
+                            at,
+                            request,
+                        )
+                        .await
+                        else {
+                            continue;
                         };
 
-                        match config.runtime_caches_service.get(at).await {
-                            Ok(runtime) => {
+                        // The runtime is only obtained if the runtime version of this block isn't
+                        // in the cache.
+                        let runtime_version = config
+                            .runtime_versions_cache
+                            .get_or_try_insert(at, async {
+                                config
+                                    .runtime_caches_service
+                                    .get(at)
+                                    .await
+                                    .map(|runtime| runtime.runtime_version().clone())
+                            })
+                            .await;
+
+                        match runtime_version {
+                            Ok(runtime_version) => {
                                 request.respond(methods::Response::state_getRuntimeVersion(
-                                    convert_runtime_version(runtime.runtime_version()),
+                                    convert_runtime_version(&runtime_version),
                                 ));
                             }
                             Err(runtime_caches_service::GetError::UnknownBlock)
-                            | Err(runtime_caches_service::GetError::Pruned) => {
+                            | Err(runtime_caches_service::GetError::IncompleteStorage) => {
                                 request.respond_null()
                             } // TODO: unclear if correct error
+                            Err(runtime_caches_service::GetError::Pruned) => {
+                                request.fail(service::ErrorResponse::ServerError(
+                                    -32000,
+                                    STORAGE_PRUNED_ERROR_MESSAGE,
+                                ))
+                            }
                             Err(runtime_caches_service::GetError::InvalidRuntime(_))
                             | Err(runtime_caches_service::GetError::NoCode)
                             | Err(runtime_caches_service::GetError::InvalidHeapPages)
@@ -531,76 +1092,146 @@ pub fn spawn_requests_handler(config: Config) {
                             })
                             .collect::<Vec<_>>();
 
-                        // The bulk of the request is performed in the database thread.
-                        let result = config
-                            .database
-                            .with_database(move |db| {
-                                let at = match at {
-                                    Some(h) => h.0,
-                                    None => db.best_block_hash()?,
-                                };
-
-                                let parent = db
-                                    .block_parent(&at)?
-                                    .ok_or(database_thread::StorageAccessError::UnknownBlock)?;
-
-                                let mut out = methods::StorageChangeSet {
-                                    block: methods::HashHexString(at),
-                                    changes: Vec::with_capacity(keys_nibbles.len()),
-                                };
-
-                                for (key_nibbles, key) in
-                                    keys_nibbles.into_iter().zip(keys.into_iter())
-                                {
-                                    let before = match db.block_storage_get(
-                                        &parent,
-                                        iter::empty::<iter::Empty<_>>(),
-                                        key_nibbles.iter().copied(),
-                                    ) {
-                                        Ok(v) => v,
-                                        Err(database_thread::StorageAccessError::UnknownBlock)
-                                            if parent == [0; 32] =>
-                                        {
-                                            // In case where `at` is the genesis block, we
-                                            // assume that its "parent" (which doesn't exist)
-                                            // has an empty storage.
-                                            None
-                                        }
-                                        Err(err) => return Err(err),
-                                    };
-
-                                    let after = db.block_storage_get(
-                                        &at,
-                                        iter::empty::<iter::Empty<_>>(),
-                                        key_nibbles.iter().copied(),
-                                    )?;
-
-                                    if before != after {
-                                        out.changes
-                                            .push((key, after.map(|(v, _)| methods::HexString(v))));
-                                    }
-                                }
-
-                                Ok(out)
-                            })
-                            .await;
+                        // Resolve the block once, then take a snapshot of its storage in
+                        // order to guarantee that all the reads see the same state.
+                        let Some((at, request)) = block_hash_or_default(
+                            &config.database,
+                            config.database_timeout,
+                            default_block,
+                            &*config.log_callback,
+                            at,
+                            request,
+                        )
+                        .await
+                        else {
+                            continue;
+                        };
+                        // The storage of the parent of the block is read as well, and is
+                        // thus pinned for the same reason.
+                        let database_timeout = config.database_timeout;
+                        let result = async {
+                            let snapshot = match config
+                                .database
+                                .snapshot_at_timeout(database_timeout, at)
+                                .await?
+                            {
+                                Ok(snapshot) => snapshot,
+                                Err(err) => return Ok(Err(err)),
+                            };
+                            let parent_snapshot = match snapshot_parent(
+                                &config.database,
+                                &snapshot,
+                                database_timeout,
+                            )
+                            .await?
+                            {
+                                Ok(parent_snapshot) => parent_snapshot,
+                                Err(err) => return Ok(Err(err)),
+                            };
+                            query_storage_changes(
+                                &snapshot,
+                                parent_snapshot.as_ref(),
+                                keys_nibbles,
+                                keys,
+                                database_timeout,
+                            )
+                            .await
+                        }
+                        .await;
 
                         // Send back the response.
                         match result {
-                            Ok(out) => {
+                            Ok(Ok(out)) => {
                                 request.respond(methods::Response::state_queryStorageAt(vec![out]));
                             }
-                            Err(database_thread::StorageAccessError::IncompleteStorage)
-                            | Err(database_thread::StorageAccessError::UnknownBlock) => {
+                            Err(database_thread::DatabaseTimeoutError) => {
+                                fail_database_timeout(&*config.log_callback, request);
+                            }
+                            Ok(Err(database_thread::StorageAccessError::IncompleteStorage))
+                            | Ok(Err(database_thread::StorageAccessError::UnknownBlock)) => {
                                 // Note that it is unclear how the function should behave in
                                 // that situation.
                                 request.fail(service::ErrorResponse::InvalidParams);
                             }
-                            Err(database_thread::StorageAccessError::Corrupted(_)) => {
+                            Ok(Err(database_thread::StorageAccessError::StoragePruned)) => {
+                                request.fail(service::ErrorResponse::ServerError(
+                                    -32000,
+                                    STORAGE_PRUNED_ERROR_MESSAGE,
+                                ));
+                            }
+                            Ok(Err(database_thread::StorageAccessError::Corrupted(_))) => {
                                 request.fail(service::ErrorResponse::InternalError);
                             }
                         }
                     }
+                    methods::MethodCall::sync_state_genSyncSpec { raw } => {
+                        // Smoldot only knows the raw format of the genesis storage. If `raw` is
+                        // `false`, the genesis storage is replaced with its trie root hash, which
+                        // is enough for light clients that start from the light sync state.
+                        let genesis_block_hash = config.genesis_block_hash;
+                        let block_number_bytes = config.consensus_service.block_number_bytes();
+                        let result = match config
+                            .database
+                            .with_database_timeout(config.database_timeout, move |db| {
+                                let finalized_block_hash =
+                                    db.finalized_block_hash().map_err(|_| ())?;
+                                let chain_information = db
+                                    .to_chain_information(&finalized_block_hash)
+                                    .map_err(|_| ())?;
+                                let genesis_state_root = if raw {
+                                    None
+                                } else {
+                                    let genesis_header = db
+                                        .block_scale_encoded_header(&genesis_block_hash)
+                                        .map_err(|_| ())?
+                                        .ok_or(())?;
+                                    Some(
+                                        *header::decode(&genesis_header, block_number_bytes)
+                                            .map_err(|_| ())?
+                                            .state_root,
+                                    )
+                                };
+                                Ok::<_, ()>((chain_information, genesis_state_root))
+                            })
+                            .await
+                        {
+                            Ok(result) => result,
+                            Err(database_thread::DatabaseTimeoutError) => {
+                                fail_database_timeout(&*config.log_callback, request);
+                                continue;
+                            }
+                        };
+                        let Ok((chain_information, genesis_state_root)) = result else {
+                            request.fail(service::ErrorResponse::InternalError);
+                            continue;
+                        };
+
+                        match config
+                            .chain_spec
+                            .with_light_sync_state(chain_information.as_ref())
+                        {
+                            Ok(chain_spec) => {
+                                let chain_spec = match genesis_state_root {
+                                    Some(state_root) => {
+                                        chain_spec.with_genesis_state_root_hash(&state_root)
+                                    }
+                                    None => chain_spec,
+                                };
+                                request.respond(methods::Response::sync_state_genSyncSpec(
+                                    serde_json::value::RawValue::from_string(
+                                        chain_spec.serialize(),
+                                    )
+                                    .unwrap(),
+                                ));
+                            }
+                            Err(error) => {
+                                request.fail(service::ErrorResponse::ServerError(
+                                    -32000,
+                                    &error.to_string(),
+                                ));
+                            }
+                        }
+                    }
                     methods::MethodCall::system_chain {} => {
                         request
                             .respond(methods::Response::system_chain((&config.chain_name).into()));
@@ -610,36 +1241,201 @@ pub fn spawn_requests_handler(config: Config) {
                             (&config.chain_type).into(),
                         ));
                     }
-                    methods::MethodCall::system_health {} => {
-                        let (is_syncing, peers) = future::zip(
-                            config.consensus_service.is_major_syncing_hint(),
-                            config.network_service.0.num_peers(config.network_service.1),
+                    methods::MethodCall::system_health {} => {
+                        let ((sync_state, is_major_syncing), (peers, best_seen_block)) =
+                            future::zip(
+                                future::zip(
+                                    config.consensus_service.sync_state(),
+                                    config.consensus_service.is_major_syncing_hint(),
+                                ),
+                                future::zip(
+                                    config.network_service.0.num_peers(config.network_service.1),
+                                    config
+                                        .network_service
+                                        .0
+                                        .best_seen_block(config.network_service.1),
+                                ),
+                            )
+                            .await;
+
+                        request.respond(methods::Response::system_health(methods::SystemHealth {
+                            is_syncing: is_syncing(
+                                is_major_syncing,
+                                sync_state.best_block_number,
+                                best_seen_block,
+                            ),
+                            peers: u64::try_from(peers).unwrap_or(u64::MAX),
+                            should_have_peers: config.chain_is_live,
+                        }));
+                    }
+                    methods::MethodCall::chain_unstable_leaves {} => {
+                        let leaves = config.consensus_service.leaves().await;
+                        request.respond(methods::Response::chain_unstable_leaves(
+                            leaves
+                                .into_iter()
+                                .map(|(hash, number)| methods::ChainLeaf {
+                                    hash: methods::HashHexString(hash),
+                                    number,
+                                })
+                                .collect(),
+                        ));
+                    }
+                    methods::MethodCall::chain_unstable_blockTree {} => {
+                        let tree = config
+                            .consensus_service
+                            .block_tree(BLOCK_TREE_MAX_DEPTH, BLOCK_TREE_MAX_BLOCKS_PER_HEIGHT)
+                            .await;
+                        request.respond(methods::Response::chain_unstable_blockTree(
+                            methods::BlockTree {
+                                blocks: tree
+                                    .blocks
+                                    .into_iter()
+                                    .map(|block| methods::BlockTreeBlock {
+                                        hash: methods::HashHexString(block.hash),
+                                        parent_hash: methods::HashHexString(block.parent_hash),
+                                        number: block.number,
+                                        is_best: block.is_best,
+                                        is_finalized: block.is_finalized,
+                                    })
+                                    .collect(),
+                                truncated: tree.truncated,
+                            },
+                        ));
+                    }
+                    methods::MethodCall::system_unstable_networkState {} => {
+                        let (network_service, chain_id) = &config.network_service;
+                        let peers_reputation = network_service
+                            .peers_reputation(*chain_id)
+                            .await
+                            .into_iter()
+                            .map(|(peer_id, reputation)| methods::PeerReputation {
+                                peer_id: peer_id.to_base58(),
+                                reputation,
+                            })
+                            .collect();
+                        request.respond(methods::Response::system_unstable_networkState(
+                            methods::SystemNetworkState {
+                                fork_id: config.chain_fork_id.clone(),
+                                protocol_id: config.chain_protocol_id.clone(),
+                                protocol_names: network_service.protocol_names(*chain_id).to_vec(),
+                                peers_reputation,
+                            },
+                        ));
+                    }
+                    methods::MethodCall::system_unstable_status {} => {
+                        let ((sync_state, is_major_syncing), (peers, best_seen_block)) =
+                            future::zip(
+                                future::zip(
+                                    config.consensus_service.sync_state(),
+                                    config.consensus_service.is_major_syncing_hint(),
+                                ),
+                                future::zip(
+                                    config.network_service.0.num_peers(config.network_service.1),
+                                    config
+                                        .network_service
+                                        .0
+                                        .best_seen_block(config.network_service.1),
+                                ),
+                            )
+                            .await;
+
+                        request.respond(methods::Response::system_unstable_status(
+                            methods::SystemStatus {
+                                best_block_hash: methods::HashHexString(sync_state.best_block_hash),
+                                best_block_number: sync_state.best_block_number,
+                                finalized_block_hash: methods::HashHexString(
+                                    sync_state.finalized_block_hash,
+                                ),
+                                finalized_block_number: sync_state.finalized_block_number,
+                                peers: u64::try_from(peers).unwrap_or(u64::MAX),
+                                is_syncing: is_syncing(
+                                    is_major_syncing,
+                                    sync_state.best_block_number,
+                                    best_seen_block,
+                                ),
+                                highest_block_number: best_seen_block.map(|(number, _)| number),
+                            },
+                        ));
+                    }
+                    methods::MethodCall::system_localPeerId {} => {
+                        let peer_id = config.network_service.0.local_peer_id().to_base58();
+                        request.respond(methods::Response::system_localPeerId(peer_id.into()));
+                    }
+                    methods::MethodCall::system_addReservedPeer { peer } => {
+                        let Some((peer_id, address)) = parse_reserved_peer_multiaddr(&peer) else {
+                            request.fail(service::ErrorResponse::InvalidParams);
+                            continue;
+                        };
+
+                        let (network_service, chain_id) = &config.network_service;
+                        network_service
+                            .add_reserved_peer(*chain_id, peer_id, address)
+                            .await;
+                        request.respond(methods::Response::system_addReservedPeer(()));
+                    }
+                    methods::MethodCall::system_removeReservedPeer { peer_id } => {
+                        let Ok(peer_id) = peer_id.parse::<libp2p::PeerId>() else {
+                            request.fail(service::ErrorResponse::InvalidParams);
+                            continue;
+                        };
+
+                        let (network_service, chain_id) = &config.network_service;
+                        network_service
+                            .remove_reserved_peer(*chain_id, peer_id)
+                            .await;
+                        request.respond(methods::Response::system_removeReservedPeer(()));
+                    }
+                    methods::MethodCall::system_syncState {} => {
+                        let (sync_state, best_seen_block) = future::zip(
+                            config.consensus_service.sync_state(),
+                            config
+                                .network_service
+                                .0
+                                .best_seen_block(config.network_service.1),
                         )
                         .await;
 
-                        request.respond(methods::Response::system_health(methods::SystemHealth {
-                            is_syncing,
-                            peers: u64::try_from(peers).unwrap_or(u64::MAX),
-                            should_have_peers: config.chain_is_live,
-                        }));
+                        request.respond(methods::Response::system_syncState(
+                            methods::SystemSyncState {
+                                starting_block: config.consensus_service.starting_block_number(),
+                                current_block: sync_state.best_block_number,
+                                highest_block: cmp::max(
+                                    sync_state.best_block_number,
+                                    best_seen_block.map_or(0, |(number, _)| number),
+                                ),
+                            },
+                        ));
                     }
-                    methods::MethodCall::system_localPeerId {} => {
-                        let peer_id = config.network_service.0.local_peer_id().to_base58();
-                        request.respond(methods::Response::system_localPeerId(peer_id.into()));
+                    methods::MethodCall::system_reservedPeers {} => {
+                        let (network_service, chain_id) = &config.network_service;
+                        let reserved_peers = network_service
+                            .reserved_peers(*chain_id)
+                            .await
+                            .into_iter()
+                            .map(|(peer_id, address)| format!("{address}/p2p/{peer_id}"))
+                            .collect();
+                        request.respond(methods::Response::system_reservedPeers(reserved_peers));
                     }
                     methods::MethodCall::system_name {} => {
-                        request.respond(methods::Response::system_version(
+                        request.respond(methods::Response::system_name(
                             env!("CARGO_PKG_NAME").into(),
                         ));
                     }
                     methods::MethodCall::system_properties {} => {
                         request.respond(methods::Response::system_properties(
-                            serde_json::from_str(&config.chain_properties_json).unwrap(),
+                            serde_json::from_str(config.chain_properties.as_json()).unwrap(),
                         ));
                     }
                     methods::MethodCall::system_version {} => {
+                        // The git commit is unavailable if the node wasn't built from a git
+                        // repository.
                         request.respond(methods::Response::system_version(
-                            env!("CARGO_PKG_VERSION").into(),
+                            match option_env!("SMOLDOT_GIT_COMMIT") {
+                                Some(commit) => {
+                                    format!("{}-{commit}", env!("CARGO_PKG_VERSION")).into()
+                                }
+                                None => env!("CARGO_PKG_VERSION").into(),
+                            },
                         ));
                     }
 
@@ -653,6 +1449,7 @@ pub fn spawn_requests_handler(config: Config) {
                         let block_number_bytes = config.consensus_service.block_number_bytes();
                         let mut blocks_to_report = legacy_api_subscriptions::SubscribeAllHeads::new(
                             config.consensus_service.clone(),
+                            config.subscribe_all_heads_report_initial_best,
                         );
 
                         (config.tasks_executor)(Box::pin(async move {
@@ -771,8 +1568,23 @@ pub fn spawn_requests_handler(config: Config) {
                             let subscription_id = subscription.subscription_id().to_owned();
 
                             loop {
-                                let runtime_version =
-                                    runtime_versions_to_report.next_runtime_version().await;
+                                // Stop the task once the subscription is dead, otherwise the
+                                // response to the unsubscription is never sent.
+                                let Some(runtime_version) = future::or(
+                                    async {
+                                        Some(
+                                            runtime_versions_to_report.next_runtime_version().await,
+                                        )
+                                    },
+                                    async {
+                                        subscription.wait_until_stale().await;
+                                        None
+                                    },
+                                )
+                                .await
+                                else {
+                                    return;
+                                };
 
                                 subscription
                                     .send_notification(
@@ -822,6 +1634,329 @@ pub fn spawn_requests_handler(config: Config) {
                         }));
                     }
 
+                    methods::MethodCall::chain_subscribeExportBlocks { from, to } => {
+                        // Only blocks of the best chain can be exported.
+                        if from > to
+                            || to
+                                > config
+                                    .consensus_service
+                                    .sync_state()
+                                    .await
+                                    .best_block_number
+                        {
+                            request.fail(service::ErrorResponse::InvalidParams);
+                            continue;
+                        }
+
+                        let database = config.database.clone();
+                        let database_timeout = config.database_timeout;
+
+                        (config.tasks_executor)(Box::pin(async move {
+                            let mut subscription = request.accept();
+                            let subscription_id = subscription.subscription_id().to_owned();
+
+                            let mut next_block_number = from;
+
+                            let event = loop {
+                                if next_block_number > to {
+                                    break methods::ExportBlocksEvent::Done {};
+                                }
+
+                                if subscription.is_stale() {
+                                    return;
+                                }
+
+                                let chunk_end = cmp::min(
+                                    to,
+                                    next_block_number.saturating_add(EXPORT_BLOCKS_CHUNK_SIZE - 1),
+                                );
+
+                                let chunk = database
+                                    .with_database_timeout(database_timeout, move |db| {
+                                        let mut blocks = Vec::new();
+                                        for number in next_block_number..=chunk_end {
+                                            let Some(hash) =
+                                                db.best_block_hash_by_number(number)?
+                                            else {
+                                                return Ok(None);
+                                            };
+                                            let Some(block) = db.block_scale_encoded(&hash)? else {
+                                                return Ok(None);
+                                            };
+                                            blocks.push(methods::HexString(block));
+                                        }
+                                        Ok::<_, full_sqlite::CorruptedError>(Some(blocks))
+                                    })
+                                    .await;
+
+                                match chunk {
+                                    Ok(Ok(Some(blocks))) => {
+                                        subscription
+                                            .send_notification(
+                                                methods::ServerToClient::chain_exportedBlocks {
+                                                    subscription: (&subscription_id).into(),
+                                                    result: methods::ExportBlocksEvent::Blocks {
+                                                        blocks,
+                                                    },
+                                                },
+                                            )
+                                            .await;
+                                    }
+                                    Ok(Ok(None)) => {
+                                        // A reorganization has removed some of the requested
+                                        // blocks from the best chain.
+                                        break methods::ExportBlocksEvent::Error {
+                                            error: "Block no longer in the best chain".into(),
+                                        };
+                                    }
+                                    Ok(Err(_)) => {
+                                        break methods::ExportBlocksEvent::Error {
+                                            error: "Database corrupted".into(),
+                                        };
+                                    }
+                                    Err(database_thread::DatabaseTimeoutError) => {
+                                        break methods::ExportBlocksEvent::Error {
+                                            error: DATABASE_TIMEOUT_ERROR_MESSAGE.into(),
+                                        };
+                                    }
+                                }
+
+                                // Can't overflow, as `to` is inferior or equal to the best block number.
+                                next_block_number = chunk_end + 1;
+                            };
+
+                            subscription
+                                .send_notification(methods::ServerToClient::chain_exportedBlocks {
+                                    subscription: (&subscription_id).into(),
+                                    result: event,
+                                })
+                                .await;
+                        }));
+                    }
+
+                    methods::MethodCall::author_submitAndWatchExtrinsic { transaction } => {
+                        let consensus_service = config.consensus_service.clone();
+
+                        (config.tasks_executor)(Box::pin(async move {
+                            let (_, status_updates) =
+                                consensus_service.submit_and_watch(transaction.0).await;
+                            let mut status_updates = pin::pin!(status_updates);
+
+                            let mut subscription = request.accept();
+                            let subscription_id = subscription.subscription_id().to_owned();
+
+                            loop {
+                                let Some(status) = future::or(
+                                    async { Some(status_updates.next().await) },
+                                    async {
+                                        subscription.wait_until_stale().await;
+                                        None
+                                    },
+                                )
+                                .await
+                                else {
+                                    return;
+                                };
+
+                                // The stream ending without a final status means that the
+                                // transaction is no longer being watched.
+                                let (result, is_final) = match status {
+                                    Some(consensus_service::TxStatus::Validated) => {
+                                        (methods::TransactionStatus::Ready, false)
+                                    }
+                                    // The list of peers the transaction has been sent to isn't
+                                    // known.
+                                    Some(consensus_service::TxStatus::Broadcasted) => continue,
+                                    Some(consensus_service::TxStatus::InBlock(hash)) => (
+                                        methods::TransactionStatus::InBlock(
+                                            methods::HashHexString(hash),
+                                        ),
+                                        false,
+                                    ),
+                                    Some(consensus_service::TxStatus::Retracted(hash)) => (
+                                        methods::TransactionStatus::Retracted(
+                                            methods::HashHexString(hash),
+                                        ),
+                                        false,
+                                    ),
+                                    Some(consensus_service::TxStatus::Finalized(hash)) => (
+                                        methods::TransactionStatus::Finalized(
+                                            methods::HashHexString(hash),
+                                        ),
+                                        true,
+                                    ),
+                                    Some(consensus_service::TxStatus::Invalid(_)) => {
+                                        (methods::TransactionStatus::Invalid, true)
+                                    }
+                                    Some(consensus_service::TxStatus::Dropped) | None => {
+                                        (methods::TransactionStatus::Dropped, true)
+                                    }
+                                };
+
+                                subscription
+                                    .send_notification(
+                                        methods::ServerToClient::author_extrinsicUpdate {
+                                            subscription: (&subscription_id).into(),
+                                            result,
+                                        },
+                                    )
+                                    .await;
+
+                                if is_final {
+                                    return;
+                                }
+                            }
+                        }));
+                    }
+
+                    methods::MethodCall::system_subscribePeerCount {} => {
+                        let (network_service, chain_id) = config.network_service.clone();
+
+                        (config.tasks_executor)(Box::pin(async move {
+                            let num_peers_rx = network_service.subscribe_num_peers(chain_id).await;
+
+                            let mut subscription = request.accept();
+                            let subscription_id = subscription.subscription_id().to_owned();
+
+                            // Number of peers found in the latest notification, in order to not
+                            // send the same value twice in a row. Peers connecting and
+                            // disconnecting in between two notifications can lead to the count
+                            // being unchanged.
+                            let mut last_reported = None;
+
+                            loop {
+                                let Some(num_peers) =
+                                    future::or(async { num_peers_rx.recv().await.ok() }, async {
+                                        subscription.wait_until_stale().await;
+                                        None
+                                    })
+                                    .await
+                                else {
+                                    return;
+                                };
+
+                                if last_reported == Some(num_peers) {
+                                    continue;
+                                }
+                                last_reported = Some(num_peers);
+
+                                subscription
+                                    .send_notification(methods::ServerToClient::system_peerCount {
+                                        subscription: (&subscription_id).into(),
+                                        result: u64::try_from(num_peers).unwrap(),
+                                    })
+                                    .await;
+
+                                smol::Timer::after(PEER_COUNT_NOTIFICATIONS_MIN_INTERVAL).await;
+                            }
+                        }));
+                    }
+
+                    methods::MethodCall::grandpa_subscribeAuthoritySetChanges {} => {
+                        let consensus_service = config.consensus_service.clone();
+
+                        (config.tasks_executor)(Box::pin(async move {
+                            let mut changes = pin::pin!(
+                                consensus_service
+                                    .subscribe_grandpa_authority_set_changes()
+                                    .await
+                            );
+
+                            let mut subscription = request.accept();
+                            let subscription_id = subscription.subscription_id().to_owned();
+
+                            loop {
+                                let Some(change) = future::or(changes.next(), async {
+                                    subscription.wait_until_stale().await;
+                                    None
+                                })
+                                .await
+                                else {
+                                    return;
+                                };
+
+                                subscription
+                                    .send_notification(
+                                        methods::ServerToClient::grandpa_authoritySetChange {
+                                            subscription: (&subscription_id).into(),
+                                            result: methods::GrandpaAuthoritySetChange {
+                                                block_hash: methods::HashHexString(
+                                                    change.block_hash,
+                                                ),
+                                                header: methods::HexString(
+                                                    change.scale_encoded_header,
+                                                ),
+                                                justification: methods::HexString(
+                                                    change.scale_encoded_justification,
+                                                ),
+                                                set_id: change.new_set_id,
+                                                authority_set_proof: change
+                                                    .authority_set_proof
+                                                    .map(methods::HexString),
+                                            },
+                                        },
+                                    )
+                                    .await;
+                            }
+                        }));
+                    }
+
+                    methods::MethodCall::chain_subscribeFinalizedExtrinsics {} => {
+                        let mut blocks_to_report =
+                            legacy_api_subscriptions::SubscribeFinalizedExtrinsics::new(
+                                config.consensus_service.clone(),
+                                config.database.clone(),
+                                config.database_timeout,
+                            );
+                        let log_callback = config.log_callback.clone();
+
+                        (config.tasks_executor)(Box::pin(async move {
+                            let mut subscription = request.accept();
+                            let subscription_id = subscription.subscription_id().to_owned();
+
+                            loop {
+                                let Some(block) = future::or(
+                                    async { Some(blocks_to_report.next_block().await) },
+                                    async {
+                                        subscription.wait_until_stale().await;
+                                        None
+                                    },
+                                )
+                                .await
+                                else {
+                                    return;
+                                };
+
+                                match block {
+                                    Ok((block_hash, extrinsics_hashes)) => {
+                                        subscription
+                                            .send_notification(
+                                                methods::ServerToClient::chain_finalizedExtrinsics {
+                                                    subscription: (&subscription_id).into(),
+                                                    result: methods::FinalizedExtrinsics {
+                                                        block_hash: methods::HashHexString(
+                                                            block_hash,
+                                                        ),
+                                                        extrinsics: extrinsics_hashes
+                                                            .into_iter()
+                                                            .map(methods::HashHexString)
+                                                            .collect(),
+                                                    },
+                                                },
+                                            )
+                                            .await;
+                                    }
+                                    Err(error) => {
+                                        log_callback.log(
+                                            LogLevel::Warn,
+                                            format!("json-rpc; request=chain_subscribeFinalizedExtrinsics; database_error={error}"),
+                                        );
+                                    }
+                                }
+                            }
+                        }));
+                    }
+
                     _ => request.fail(service::ErrorResponse::ServerError(
                         -32000,
                         "Not implemented in smoldot yet",
@@ -833,6 +1968,710 @@ pub fn spawn_requests_handler(config: Config) {
     }));
 }
 
+/// Parses the multiaddress passed to `system_addReservedPeer`, which must end with
+/// `/p2p/<peer id>`. Returns the peer id and the address without its `/p2p` suffix.
+fn parse_reserved_peer_multiaddr(peer: &str) -> Option<(libp2p::PeerId, libp2p::Multiaddr)> {
+    let mut address = peer.parse::<libp2p::Multiaddr>().ok()?;
+    let Some(libp2p::multiaddr::Protocol::P2p(peer_id)) = address.iter().last() else {
+        return None;
+    };
+    let peer_id = libp2p::PeerId::from_bytes(peer_id.into_bytes().to_vec()).ok()?;
+    address.pop();
+    Some((peer_id, address))
+}
+
+/// Fails the given request because its access to the database has taken longer than
+/// [`Config::database_timeout`], and logs the problem.
+fn fail_database_timeout(log_callback: &dyn LogCallback, request: service::RequestProcess) {
+    log_callback.log(
+        LogLevel::Warn,
+        format!(
+            "json-rpc; request={}; database_error=timeout",
+            request.request().name()
+        ),
+    );
+    request.fail(service::ErrorResponse::ServerError(
+        -32000,
+        DATABASE_TIMEOUT_ERROR_MESSAGE,
+    ));
+}
+
+/// Parses the kind of off-chain storage passed to the `offchain_localStorage*` JSON-RPC
+/// functions.
+fn offchain_storage_kind_from_str(kind: &str) -> Option<database_thread::OffchainStorageKind> {
+    match kind {
+        "PERSISTENT" => Some(database_thread::OffchainStorageKind::Persistent),
+        "LOCAL" => Some(database_thread::OffchainStorageKind::Local),
+        _ => None,
+    }
+}
+
+/// Returns the hash of the block to use when a JSON-RPC function that accepts an optional block
+/// hash is called without one.
+fn default_block_hash(
+    database: &full_sqlite::SqliteFullDatabase,
+    default_block: DefaultBlock,
+) -> Result<[u8; 32], full_sqlite::CorruptedError> {
+    match default_block {
+        DefaultBlock::Best => database.best_block_hash(),
+        DefaultBlock::Finalized => database.finalized_block_hash(),
+    }
+}
+
+/// Returns the given block hash or, if `None`, the hash of the block designated by
+/// `default_block`, alongside with the request.
+///
+/// Fails the request and returns `None` if the database access fails or takes longer than
+/// `database_timeout`.
+async fn block_hash_or_default(
+    database: &database_thread::DatabaseThread,
+    database_timeout: Duration,
+    default_block: DefaultBlock,
+    log_callback: &(dyn LogCallback + Send + Sync),
+    hash: Option<methods::HashHexString>,
+    request: service::RequestProcess,
+) -> Option<([u8; 32], service::RequestProcess)> {
+    if let Some(hash) = hash {
+        return Some((hash.0, request));
+    }
+
+    match database
+        .with_database_timeout(database_timeout, move |db| {
+            default_block_hash(db, default_block)
+        })
+        .await
+    {
+        Ok(Ok(hash)) => Some((hash, request)),
+        Ok(Err(_)) => {
+            request.fail(service::ErrorResponse::InternalError);
+            None
+        }
+        Err(database_thread::DatabaseTimeoutError) => {
+            fail_database_timeout(log_callback, request);
+            None
+        }
+    }
+}
+
+/// Returns the list of keys of the storage of the given block that start with the given prefix
+/// and are superior or equal to `start_key_nibbles`, in lexicographic order.
+///
+/// At most `max_keys` keys are returned.
+fn storage_keys_with_prefix(
+    database: &full_sqlite::SqliteFullDatabase,
+    block_hash: &[u8; 32],
+    prefix_nibbles: &[u8],
+    start_key_nibbles: Vec<u8>,
+    max_keys: usize,
+) -> Result<Vec<methods::HexString>, database_thread::StorageAccessError> {
+    let mut out = Vec::with_capacity(cmp::min(max_keys, 1024));
+
+    let mut key_iter = start_key_nibbles;
+
+    // The query is performed by repeatedly asking for the next key.
+    while out.len() < max_keys {
+        let next_key_nibbles = database.block_storage_next_key(
+            block_hash,
+            iter::empty::<iter::Empty<_>>(),
+            key_iter.iter().copied(),
+            prefix_nibbles.iter().copied(),
+            false,
+        )?;
+
+        let Some(next_key_nibbles) = next_key_nibbles else {
+            break;
+        };
+
+        out.push(methods::HexString(
+            trie::nibbles_to_bytes_truncate(
+                next_key_nibbles
+                    .iter()
+                    .copied()
+                    .map(|n| trie::Nibble::try_from(n).unwrap()),
+            )
+            .collect::<Vec<_>>(),
+        ));
+
+        // Push an extra nibble as otherwise `block_storage_next_key` will return the same key
+        // again.
+        key_iter = next_key_nibbles;
+        key_iter.push(0);
+    }
+
+    Ok(out)
+}
+
+/// Takes a snapshot of the storage of the parent of the block of the given snapshot.
+///
+/// Returns `None` if the block is the genesis block, as its parent doesn't exist.
+async fn snapshot_parent(
+    database: &Arc<database_thread::DatabaseThread>,
+    snapshot: &database_thread::SnapshotHandle,
+    database_timeout: Duration,
+) -> Result<
+    Result<Option<database_thread::SnapshotHandle>, database_thread::StorageAccessError>,
+    database_thread::DatabaseTimeoutError,
+> {
+    let parent = match snapshot
+        .with_database_timeout(database_timeout, |db, at| db.block_parent(at))
+        .await?
+    {
+        Ok(Some(parent)) => parent,
+        Ok(None) => return Ok(Err(database_thread::StorageAccessError::UnknownBlock)),
+        Err(err) => return Ok(Err(database_thread::StorageAccessError::Corrupted(err))),
+    };
+
+    match database
+        .snapshot_at_timeout(database_timeout, parent)
+        .await?
+    {
+        Ok(parent_snapshot) => Ok(Ok(Some(parent_snapshot))),
+        // The parent hash of the genesis block is made of zeroes and doesn't match any block.
+        Err(database_thread::StorageAccessError::UnknownBlock) if parent == [0; 32] => Ok(Ok(None)),
+        Err(err) => Ok(Err(err)),
+    }
+}
+
+/// Returns the keys whose storage value differs between the block of `snapshot` and its
+/// parent, alongside with their new value.
+///
+/// `parent_snapshot` must be a snapshot of the parent of the block, or `None` if the block is
+/// the genesis block, in which case the storage of its parent is assumed to be empty. All the
+/// keys are read in a single access to the database.
+async fn query_storage_changes(
+    snapshot: &database_thread::SnapshotHandle,
+    parent_snapshot: Option<&database_thread::SnapshotHandle>,
+    keys_nibbles: Vec<Vec<u8>>,
+    keys: Vec<methods::HexString>,
+    database_timeout: Duration,
+) -> Result<
+    Result<methods::StorageChangeSet, database_thread::StorageAccessError>,
+    database_thread::DatabaseTimeoutError,
+> {
+    let parent = parent_snapshot.map(|parent_snapshot| *parent_snapshot.block_hash());
+
+    snapshot
+        .with_database_timeout(database_timeout, move |db, at| {
+            let mut out = methods::StorageChangeSet {
+                block: methods::HashHexString(*at),
+                changes: Vec::with_capacity(keys.len()),
+            };
+
+            for (key_nibbles, key) in keys_nibbles.into_iter().zip(keys) {
+                let before = match &parent {
+                    Some(parent) => db.block_storage_get(
+                        parent,
+                        iter::empty::<iter::Empty<_>>(),
+                        key_nibbles.iter().copied(),
+                    )?,
+                    None => None,
+                };
+
+                let after = db.block_storage_get(
+                    at,
+                    iter::empty::<iter::Empty<_>>(),
+                    key_nibbles.iter().copied(),
+                )?;
+
+                if before != after {
+                    out.changes
+                        .push((key, after.map(|(v, _)| methods::HexString(v))));
+                }
+            }
+
+            Ok(out)
+        })
+        .await
+}
+
+/// Error potentially returned by [`runtime_upgrades`].
+enum RuntimeUpgradesError {
+    /// One of the blocks is no longer part of the best chain, or its storage is incomplete.
+    UnknownBlock,
+    /// The storage of all the blocks using one of the runtimes has been pruned.
+    Pruned,
+    /// The database hasn't answered in time.
+    Timeout,
+    /// Failed to obtain the runtime version of a block.
+    Other,
+}
+
+impl From<database_thread::DatabaseTimeoutError> for RuntimeUpgradesError {
+    fn from(_: database_thread::DatabaseTimeoutError) -> Self {
+        RuntimeUpgradesError::Timeout
+    }
+}
+
+impl From<database_thread::StorageAccessError> for RuntimeUpgradesError {
+    fn from(error: database_thread::StorageAccessError) -> Self {
+        match error {
+            database_thread::StorageAccessError::IncompleteStorage
+            | database_thread::StorageAccessError::UnknownBlock => {
+                RuntimeUpgradesError::UnknownBlock
+            }
+            database_thread::StorageAccessError::StoragePruned => RuntimeUpgradesError::Pruned,
+            database_thread::StorageAccessError::Corrupted(_) => RuntimeUpgradesError::Other,
+        }
+    }
+}
+
+/// Returns the blocks of the best chain between `from` and `to` (both inclusive) whose runtime
+/// specification version differs from the one of their parent.
+///
+/// Instead of loading the runtime of each block, the Merkle value of the `:code` trie node is
+/// compared between blocks and the range is bisected until the blocks where it changes are
+/// found. This relies on the fact that the code of a runtime never reverts to a previous code,
+/// as the specification version must always increase. When the storage of a block has been
+/// pruned, the [`header::DigestItemRef::RuntimeEnvironmentUpdated`] items found in the headers
+/// are used instead.
+///
+/// The database is accessed many times for a short period of time rather than once for a long
+/// period of time, in order to not block the other users of the database.
+async fn runtime_upgrades(
+    database: &database_thread::DatabaseThread,
+    runtime_caches_service: &runtime_caches_service::RuntimeCachesService,
+    runtime_versions_cache: &runtime_versions_cache::RuntimeVersionsCache,
+    block_number_bytes: usize,
+    database_timeout: Duration,
+    from: u64,
+    to: u64,
+) -> Result<Vec<methods::RuntimeUpgrade>, RuntimeUpgradesError> {
+    // The parent of `from` (or `from` itself if it is the genesis block) is used as a
+    // reference.
+    let reference = from.saturating_sub(1);
+    let (reference_hash, reference_code) =
+        code_merkle_value(database, database_timeout, reference).await?;
+
+    // List of blocks whose runtime code might differ from the one of their parent, in
+    // increasing order, and starting with the reference block.
+    let mut code_changes = vec![(reference, reference_hash)];
+
+    // Ranges of blocks `(start, end]` that remain to be searched, alongside with the Merkle
+    // value of `:code` at `start` and `end`, or `None` if the storage of the block has been
+    // pruned. The last element of the list is searched first.
+    let mut ranges = Vec::new();
+    if to > reference {
+        let (to_hash, to_code) = code_merkle_value(database, database_timeout, to).await?;
+        ranges.push(((reference, reference_code), (to, to_hash, to_code)));
+    }
+
+    while let Some(((start, start_code), (end, end_hash, end_code))) = ranges.pop() {
+        match (start_code, end_code) {
+            (Some(start_code), Some(end_code)) if start_code == end_code => {}
+            (Some(_), Some(_)) if end == start + 1 => code_changes.push((end, end_hash)),
+            (start_code @ Some(_), end_code) | (start_code, end_code @ Some(_))
+                if end > start + 1 =>
+            {
+                let middle = start + (end - start) / 2;
+                let (middle_hash, middle_code) =
+                    code_merkle_value(database, database_timeout, middle).await?;
+                ranges.push(((middle, middle_code.clone()), (end, end_hash, end_code)));
+                ranges.push(((start, start_code), (middle, middle_hash, middle_code)));
+            }
+            _ => {
+                // Since the storage of blocks is pruned in increasing block number order, the
+                // storage of all the blocks of the range is pruned as well if `start` and `end`
+                // are both pruned.
+                code_changes.extend(
+                    runtime_environment_updates(
+                        database,
+                        database_timeout,
+                        block_number_bytes,
+                        start + 1,
+                        end,
+                    )
+                    .await?,
+                );
+            }
+        }
+    }
+
+    // A code change doesn't necessarily mean a change of specification version. The runtime
+    // version is only obtained at the change points.
+    let mut upgrades = Vec::new();
+    let mut previous_spec_version = None;
+
+    for (index, &(number, hash)) in code_changes.iter().enumerate() {
+        let spec_version = match runtime_spec_version(
+            runtime_caches_service,
+            runtime_versions_cache,
+            hash,
+        )
+        .await
+        {
+            Err(RuntimeUpgradesError::Pruned) => {
+                // All the blocks until the next change use the same runtime. The storage of the
+                // last of them might not have been pruned yet.
+                let last = code_changes.get(index + 1).map_or(to, |(n, _)| *n - 1);
+                if last == number {
+                    return Err(RuntimeUpgradesError::Pruned);
+                }
+                let (last_hash, _) = code_merkle_value(database, database_timeout, last).await?;
+                runtime_spec_version(runtime_caches_service, runtime_versions_cache, last_hash)
+                    .await?
+            }
+            spec_version => spec_version?,
+        };
+
+        if previous_spec_version.is_some_and(|v| v != spec_version) {
+            upgrades.push(methods::RuntimeUpgrade {
+                block_number: number,
+                block_hash: methods::HashHexString(hash),
+                spec_version: u64::from(spec_version),
+            });
+        }
+        previous_spec_version = Some(spec_version);
+    }
+
+    Ok(upgrades)
+}
+
+/// Returns the hash of the block of the best chain with the given number, and the Merkle value
+/// of the `:code` trie node of its storage, or `None` if its storage has been pruned.
+async fn code_merkle_value(
+    database: &database_thread::DatabaseThread,
+    database_timeout: Duration,
+    number: u64,
+) -> Result<([u8; 32], Option<Option<Vec<u8>>>), RuntimeUpgradesError> {
+    let result = database
+        .with_database_timeout(
+            database_timeout,
+            move |db| -> Result<_, database_thread::StorageAccessError> {
+                // The best chain might have been shortened since the request has started.
+                let Some(hash) = db.best_block_hash_by_number(number)? else {
+                    return Err(database_thread::StorageAccessError::UnknownBlock);
+                };
+
+                match db.block_storage_closest_descendant_merkle_value(
+                    &hash,
+                    iter::empty::<iter::Empty<_>>(),
+                    trie::bytes_to_nibbles(b":code".iter().copied()).map(u8::from),
+                ) {
+                    Ok(merkle_value) => Ok((hash, Some(merkle_value))),
+                    Err(database_thread::StorageAccessError::StoragePruned) => Ok((hash, None)),
+                    Err(err) => Err(err),
+                }
+            },
+        )
+        .await??;
+    Ok(result)
+}
+
+/// Returns the blocks of the best chain between `start` and `end` (both inclusive) whose
+/// header indicates that their runtime environment has been updated.
+///
+/// The headers are read [`RUNTIME_UPGRADES_CHUNK_SIZE`] at a time.
+async fn runtime_environment_updates(
+    database: &database_thread::DatabaseThread,
+    database_timeout: Duration,
+    block_number_bytes: usize,
+    start: u64,
+    end: u64,
+) -> Result<Vec<(u64, [u8; 32])>, RuntimeUpgradesError> {
+    let mut out = Vec::new();
+
+    let mut chunk_start = start;
+    while chunk_start <= end {
+        let chunk_end = end.min(chunk_start.saturating_add(RUNTIME_UPGRADES_CHUNK_SIZE - 1));
+
+        let updates = database
+            .with_database_timeout(database_timeout, move |db| {
+                let mut out = Vec::new();
+                for number in chunk_start..=chunk_end {
+                    // The best chain might have been shortened since the request has started.
+                    let Ok(Some(hash)) = db.best_block_hash_by_number(number) else {
+                        return Err(RuntimeUpgradesError::UnknownBlock);
+                    };
+                    let Ok(Some(scale_encoded_header)) = db.block_scale_encoded_header(&hash)
+                    else {
+                        return Err(RuntimeUpgradesError::Other);
+                    };
+                    let Ok(decoded_header) =
+                        header::decode(&scale_encoded_header, block_number_bytes)
+                    else {
+                        return Err(RuntimeUpgradesError::Other);
+                    };
+                    if decoded_header.digest.has_runtime_environment_updated() {
+                        out.push((number, hash));
+                    }
+                }
+                Ok(out)
+            })
+            .await??;
+        out.extend(updates);
+
+        chunk_start = chunk_end + 1;
+    }
+
+    Ok(out)
+}
+
+/// Returns the specification version of the runtime of the given block.
+///
+/// The runtime version is obtained from `runtime_versions_cache` if possible, and inserted in
+/// it otherwise.
+async fn runtime_spec_version(
+    runtime_caches_service: &runtime_caches_service::RuntimeCachesService,
+    runtime_versions_cache: &runtime_versions_cache::RuntimeVersionsCache,
+    block_hash: [u8; 32],
+) -> Result<u32, RuntimeUpgradesError> {
+    match runtime_versions_cache
+        .get_or_try_insert(block_hash, async {
+            runtime_caches_service
+                .get(block_hash)
+                .await
+                .map(|runtime| runtime.runtime_version().clone())
+        })
+        .await
+    {
+        Ok(runtime_version) => Ok(runtime_version.decode().spec_version),
+        Err(runtime_caches_service::GetError::UnknownBlock)
+        | Err(runtime_caches_service::GetError::IncompleteStorage) => {
+            Err(RuntimeUpgradesError::UnknownBlock)
+        }
+        Err(runtime_caches_service::GetError::Pruned) => Err(RuntimeUpgradesError::Pruned),
+        Err(runtime_caches_service::GetError::InvalidRuntime(_))
+        | Err(runtime_caches_service::GetError::NoCode)
+        | Err(runtime_caches_service::GetError::InvalidHeapPages)
+        | Err(runtime_caches_service::GetError::CorruptedDatabase) => {
+            Err(RuntimeUpgradesError::Other)
+        }
+    }
+}
+
+/// Error potentially returned by [`block_metadata`].
+enum BlockMetadataError {
+    /// The block is unknown, or its storage is incomplete.
+    UnknownBlock,
+    /// The storage of the block has been pruned.
+    Pruned,
+    /// The database hasn't answered in time.
+    Timeout,
+    /// Failed to obtain the metadata.
+    Other,
+}
+
+/// Returns the metadata of the runtime of the given block, without its length prefix.
+///
+/// The metadata is obtained from `metadata_cache` if possible, and inserted in it otherwise.
+async fn block_metadata(
+    database: &Arc<database_thread::DatabaseThread>,
+    runtime_caches_service: &runtime_caches_service::RuntimeCachesService,
+    metadata_cache: &metadata_cache::MetadataCache,
+    block_hash: [u8; 32],
+    database_timeout: Duration,
+) -> Result<Arc<[u8]>, BlockMetadataError> {
+    let runtime = match runtime_caches_service.get(block_hash).await {
+        Ok(runtime) => runtime,
+        Err(runtime_caches_service::GetError::UnknownBlock)
+        | Err(runtime_caches_service::GetError::IncompleteStorage) => {
+            return Err(BlockMetadataError::UnknownBlock);
+        }
+        Err(runtime_caches_service::GetError::Pruned) => {
+            return Err(BlockMetadataError::Pruned);
+        }
+        Err(runtime_caches_service::GetError::InvalidRuntime(_))
+        | Err(runtime_caches_service::GetError::NoCode)
+        | Err(runtime_caches_service::GetError::InvalidHeapPages)
+        | Err(runtime_caches_service::GetError::CorruptedDatabase) => {
+            return Err(BlockMetadataError::Other);
+        }
+    };
+
+    let cache_key = metadata_cache::CacheKey::from_runtime(&runtime);
+    if let Some(metadata) = metadata_cache.get(&cache_key).await {
+        return Ok(metadata);
+    }
+
+    // The runtime call reads the storage through multiple accesses to the database. The storage
+    // of the block is pinned for the entire duration of the call, so that it can't be pruned in
+    // between two accesses.
+    let snapshot = match database
+        .snapshot_at_timeout(database_timeout, block_hash)
+        .await
+    {
+        Ok(Ok(snapshot)) => snapshot,
+        Ok(Err(database_thread::StorageAccessError::UnknownBlock))
+        | Ok(Err(database_thread::StorageAccessError::IncompleteStorage)) => {
+            return Err(BlockMetadataError::UnknownBlock);
+        }
+        Ok(Err(database_thread::StorageAccessError::StoragePruned)) => {
+            return Err(BlockMetadataError::Pruned);
+        }
+        Ok(Err(database_thread::StorageAccessError::Corrupted(_))) => {
+            return Err(BlockMetadataError::Other);
+        }
+        Err(database_thread::DatabaseTimeoutError) => {
+            return Err(BlockMetadataError::Timeout);
+        }
+    };
+
+    let mut call = match executor::runtime_call::run(executor::runtime_call::Config {
+        virtual_machine: (*runtime).clone(),
+        function_to_call: "Metadata_metadata",
+        parameter: iter::empty::<&'static [u8]>(),
+        max_log_level: 0,
+        storage_proof_size_behavior:
+            executor::runtime_call::StorageProofSizeBehavior::proof_recording_disabled(),
+        storage_main_trie_changes: Default::default(),
+        calculate_trie_changes: false,
+    }) {
+        Ok(c) => c,
+        Err(_) => {
+            return Err(BlockMetadataError::Other);
+        }
+    };
+
+    loop {
+        match call {
+            executor::runtime_call::RuntimeCall::Finished(Ok(success)) => {
+                let output = success.virtual_machine.value();
+                let Ok(metadata) = methods::remove_metadata_length_prefix(output.as_ref()) else {
+                    return Err(BlockMetadataError::Other);
+                };
+                let metadata = Arc::<[u8]>::from(metadata);
+                metadata_cache.insert(cache_key, metadata.clone()).await;
+                return Ok(metadata);
+            }
+            executor::runtime_call::RuntimeCall::Finished(Err(_)) => {
+                return Err(BlockMetadataError::Other);
+            }
+            executor::runtime_call::RuntimeCall::StorageGet(req) => {
+                let parent_paths = req.child_trie().map(|child_trie| {
+                    trie::bytes_to_nibbles(b":child_storage:default:".iter().copied())
+                        .chain(trie::bytes_to_nibbles(child_trie.as_ref().iter().copied()))
+                        .map(u8::from)
+                        .collect::<Vec<_>>()
+                });
+                let key = trie::bytes_to_nibbles(req.key().as_ref().iter().copied())
+                    .map(u8::from)
+                    .collect::<Vec<_>>();
+                let value = snapshot
+                    .with_database_timeout(database_timeout, move |db, block_hash| {
+                        db.block_storage_get(
+                            block_hash,
+                            parent_paths.into_iter().map(|p| p.into_iter()),
+                            key.iter().copied(),
+                        )
+                    })
+                    .await;
+                let Ok(value) = value
+                    .map_err(|database_thread::DatabaseTimeoutError| BlockMetadataError::Timeout)?
+                else {
+                    return Err(BlockMetadataError::Other);
+                };
+                let value = value.as_ref().map(|(val, vers)| {
+                    (
+                        iter::once(&val[..]),
+                        executor::runtime_call::TrieEntryVersion::try_from(*vers)
+                            .expect("corrupted database"),
+                    )
+                });
+
+                call = req.inject_value(value);
+            }
+            executor::runtime_call::RuntimeCall::ClosestDescendantMerkleValue(req) => {
+                let parent_paths = req.child_trie().map(|child_trie| {
+                    trie::bytes_to_nibbles(b":child_storage:default:".iter().copied())
+                        .chain(trie::bytes_to_nibbles(child_trie.as_ref().iter().copied()))
+                        .map(u8::from)
+                        .collect::<Vec<_>>()
+                });
+                let key_nibbles = req.key().map(u8::from).collect::<Vec<_>>();
+
+                let merkle_value = snapshot
+                    .with_database_timeout(database_timeout, move |db, block_hash| {
+                        db.block_storage_closest_descendant_merkle_value(
+                            block_hash,
+                            parent_paths.into_iter().map(|p| p.into_iter()),
+                            key_nibbles.iter().copied(),
+                        )
+                    })
+                    .await;
+
+                let Ok(merkle_value) = merkle_value
+                    .map_err(|database_thread::DatabaseTimeoutError| BlockMetadataError::Timeout)?
+                else {
+                    return Err(BlockMetadataError::Other);
+                };
+
+                call = req.inject_merkle_value(merkle_value.as_ref().map(|v| &v[..]));
+            }
+            executor::runtime_call::RuntimeCall::NextKey(req) => {
+                let parent_paths = req.child_trie().map(|child_trie| {
+                    trie::bytes_to_nibbles(b":child_storage:default:".iter().copied())
+                        .chain(trie::bytes_to_nibbles(child_trie.as_ref().iter().copied()))
+                        .map(u8::from)
+                        .collect::<Vec<_>>()
+                });
+                let key_nibbles = req
+                    .key()
+                    .map(u8::from)
+                    .chain(if req.or_equal() { None } else { Some(0u8) })
+                    .collect::<Vec<_>>();
+                let prefix_nibbles = req.prefix().map(u8::from).collect::<Vec<_>>();
+
+                let branch_nodes = req.branch_nodes();
+                let next_key = snapshot
+                    .with_database_timeout(database_timeout, move |db, block_hash| {
+                        db.block_storage_next_key(
+                            block_hash,
+                            parent_paths.into_iter().map(|p| p.into_iter()),
+                            key_nibbles.iter().copied(),
+                            prefix_nibbles.iter().copied(),
+                            branch_nodes,
+                        )
+                    })
+                    .await;
+
+                let Ok(next_key) = next_key
+                    .map_err(|database_thread::DatabaseTimeoutError| BlockMetadataError::Timeout)?
+                else {
+                    return Err(BlockMetadataError::Other);
+                };
+
+                call = req.inject_key(
+                    next_key.map(|k| k.into_iter().map(|b| trie::Nibble::try_from(b).unwrap())),
+                );
+            }
+            executor::runtime_call::RuntimeCall::OffchainStorageSet(req) => {
+                call = req.resume();
+            }
+            executor::runtime_call::RuntimeCall::SignatureVerification(req) => {
+                call = req.verify_and_resume();
+            }
+            executor::runtime_call::RuntimeCall::Offchain(_) => {
+                return Err(BlockMetadataError::Other);
+            }
+            executor::runtime_call::RuntimeCall::LogEmit(req) => {
+                // Logs are ignored.
+                call = req.resume();
+            }
+        }
+    }
+}
+
+fn convert_decoded_extrinsic(
+    decoded: smoldot::metadata::DecodedExtrinsic,
+) -> methods::DecodedExtrinsic {
+    methods::DecodedExtrinsic {
+        pallet: decoded.pallet_name.to_owned(),
+        call: decoded.call_name.to_owned(),
+        args: decoded.arguments,
+        signature: decoded
+            .signature
+            .map(|signature| methods::DecodedExtrinsicSignature {
+                address: signature.address,
+                signature: signature.signature,
+                signed_extensions: signature
+                    .signed_extensions
+                    .into_iter()
+                    .map(|(identifier, value)| (identifier.to_owned(), value))
+                    .collect(),
+            }),
+    }
+}
+
 fn convert_runtime_version(runtime_spec: &executor::CoreVersion) -> methods::RuntimeVersion {
     let runtime_spec = runtime_spec.decode();
     methods::RuntimeVersion {
@@ -849,3 +2688,317 @@ fn convert_runtime_version(runtime_spec: &executor::CoreVersion) -> methods::Run
             .collect(),
     }
 }
+
+/// Returns the value of `isSyncing` to report through the JSON-RPC API.
+///
+/// The node is considered as syncing if the consensus service says so, or if its peers have
+/// announced blocks far enough ahead of the local best block.
+fn is_syncing(
+    is_major_syncing_hint: bool,
+    best_block_number: u64,
+    best_seen_block: Option<(u64, [u8; 32])>,
+) -> bool {
+    is_major_syncing_hint
+        || best_seen_block.is_some_and(|(number, _)| {
+            number > best_block_number.saturating_add(IS_SYNCING_BLOCKS_THRESHOLD)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        default_block_hash, is_syncing, query_storage_changes, runtime_upgrades, snapshot_parent,
+        DefaultBlock, RuntimeUpgradesError, IS_SYNCING_BLOCKS_THRESHOLD,
+        STORAGE_PRUNED_ERROR_MESSAGE,
+    };
+    use crate::{
+        database_thread,
+        json_rpc_service::{runtime_caches_service, runtime_versions_cache},
+        test_util,
+    };
+    use smoldot::{database::full_sqlite, json_rpc::methods};
+    use std::{borrow::Cow, collections::BTreeMap, num::NonZero, sync::Arc, time::Duration};
+
+    #[test]
+    fn is_syncing_from_peers_best_blocks() {
+        assert!(is_syncing(true, 10, None));
+        assert!(!is_syncing(false, 10, None));
+        assert!(!is_syncing(
+            false,
+            10,
+            Some((10 + IS_SYNCING_BLOCKS_THRESHOLD, [0; 32]))
+        ));
+        assert!(is_syncing(
+            false,
+            10,
+            Some((11 + IS_SYNCING_BLOCKS_THRESHOLD, [0; 32]))
+        ));
+        // Peers that are behind the local node don't matter.
+        assert!(!is_syncing(false, 10, Some((2, [0; 32]))));
+    }
+
+    #[test]
+    fn default_block_with_unfinalized_best() {
+        let (db, genesis_hash) = test_util::memory_database();
+
+        // Block 1 becomes the new best block but isn't finalized.
+        let block1_hash = test_util::insert_block(&db, &genesis_hash, 1, &[1; 32]);
+
+        assert_eq!(
+            default_block_hash(&db, DefaultBlock::Best).unwrap(),
+            block1_hash
+        );
+        assert_eq!(
+            default_block_hash(&db, DefaultBlock::Finalized).unwrap(),
+            genesis_hash
+        );
+    }
+
+    #[test]
+    fn query_storage_changes_consistent_across_best_block_change() {
+        let (db, genesis_hash) = test_util::memory_database();
+        let block1_hash = test_util::insert_block(&db, &genesis_hash, 1, &[2; 32]);
+
+        // The key `0x11` is `hello` in the genesis block and `world` in block 1.
+        db.insert_trie_nodes(
+            [
+                full_sqlite::InsertTrieNode {
+                    merkle_value: Cow::Borrowed(&[1; 32]),
+                    partial_key_nibbles: Cow::Borrowed(&[1, 1]),
+                    children_merkle_values: Default::default(),
+                    storage_value: full_sqlite::InsertTrieNodeStorageValue::Value {
+                        value: Cow::Borrowed(b"hello"),
+                        references_merkle_value: false,
+                    },
+                },
+                full_sqlite::InsertTrieNode {
+                    merkle_value: Cow::Borrowed(&[2; 32]),
+                    partial_key_nibbles: Cow::Borrowed(&[1, 1]),
+                    children_merkle_values: Default::default(),
+                    storage_value: full_sqlite::InsertTrieNodeStorageValue::Value {
+                        value: Cow::Borrowed(b"world"),
+                        references_merkle_value: false,
+                    },
+                },
+            ]
+            .into_iter(),
+            0,
+        )
+        .unwrap();
+
+        let database = Arc::new(database_thread::DatabaseThread::from(db));
+
+        // Many keys are queried, only one of which has changed.
+        let keys = (0..=255u8)
+            .map(|byte| methods::HexString(vec![byte]))
+            .collect::<Vec<_>>();
+        let keys_nibbles = keys
+            .iter()
+            .map(|key| vec![key.0[0] >> 4, key.0[0] & 0xf])
+            .collect::<Vec<_>>();
+
+        smol::block_on(async {
+            let snapshot = database.snapshot_at(block1_hash).await.unwrap();
+            let parent_snapshot = snapshot_parent(&database, &snapshot, Duration::from_secs(10))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                parent_snapshot.as_ref().map(|s| *s.block_hash()),
+                Some(genesis_hash)
+            );
+
+            // Before the storage is read, a new best block is imported and finalized, and the
+            // storage of its ancestors is pruned.
+            database
+                .with_database(move |db| {
+                    let block2_hash = test_util::insert_block(db, &block1_hash, 2, &[2; 32]);
+                    db.set_finalized(&block2_hash).unwrap();
+                    db.prune_finalized_storage(0).unwrap();
+                })
+                .await;
+
+            // All the keys are read from the storage of the snapshots.
+            let changes = query_storage_changes(
+                &snapshot,
+                parent_snapshot.as_ref(),
+                keys_nibbles.clone(),
+                keys.clone(),
+                Duration::from_secs(10),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+            assert_eq!(changes.block.0, block1_hash);
+            assert_eq!(changes.changes.len(), 1);
+            assert_eq!(changes.changes[0].0 .0, vec![0x11]);
+            assert_eq!(
+                changes.changes[0].1.as_ref().map(|v| &v.0[..]),
+                Some(&b"world"[..])
+            );
+
+            // Once the snapshots are dropped, the storage of the two blocks can be pruned.
+            drop((snapshot, parent_snapshot));
+            database
+                .with_database(|db| db.prune_finalized_storage(0))
+                .await
+                .unwrap();
+            assert!(matches!(
+                database.snapshot_at(genesis_hash).await,
+                Err(full_sqlite::StorageAccessError::StoragePruned)
+            ));
+        });
+    }
+
+    #[test]
+    fn runtime_upgrades_with_pruned_storage() {
+        let (db, genesis_hash) = test_util::memory_database();
+
+        // Blocks 1 to 4 use the Rococo genesis runtime, and blocks 5 and 6 use the Polkadot
+        // runtime. Block 5 reports the runtime change in its header.
+        let rococo_storage = BTreeMap::from([(
+            b":code".to_vec(),
+            include_bytes!("../../../lib/src/executor/host/tests/rococo-genesis.wasm").to_vec(),
+        )]);
+        let polkadot_storage = BTreeMap::from([(
+            b":code".to_vec(),
+            include_bytes!("../../../lib/src/executor/host/zstd/polkadot-runtime-v9160.wasm.zstd")
+                .to_vec(),
+        )]);
+        let mut hashes = vec![genesis_hash];
+        for number in 1..=6 {
+            let (storage, runtime_environment_updated) = if number < 5 {
+                (&rococo_storage, false)
+            } else {
+                (&polkadot_storage, number == 5)
+            };
+            hashes.push(test_util::insert_block_with_storage(
+                &db,
+                hashes.last().unwrap(),
+                number,
+                storage,
+                runtime_environment_updated,
+            ));
+        }
+        db.set_finalized(&hashes[6]).unwrap();
+
+        let database = Arc::new(database_thread::DatabaseThread::from(db));
+        let runtime_caches_service =
+            runtime_caches_service::RuntimeCachesService::new(runtime_caches_service::Config {
+                tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+                database: database.clone(),
+                num_cache_entries: NonZero::<usize>::new(4).unwrap(),
+            });
+        let runtime_versions_cache =
+            runtime_versions_cache::RuntimeVersionsCache::new(NonZero::<usize>::new(4).unwrap());
+
+        smol::block_on(async {
+            let upgrades = |from, to| {
+                runtime_upgrades(
+                    &database,
+                    &runtime_caches_service,
+                    &runtime_versions_cache,
+                    4,
+                    Duration::from_secs(10),
+                    from,
+                    to,
+                )
+            };
+
+            let Ok(result) = upgrades(2, 6).await else {
+                panic!()
+            };
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].block_number, 5);
+            assert_eq!(result[0].block_hash.0, hashes[5]);
+            assert_eq!(result[0].spec_version, 9160);
+
+            // The storage of blocks 0 to 2 is pruned. The runtime of the blocks that precede the
+            // upgrade is obtained from block 4 instead, and the range of pruned blocks is scanned
+            // through their headers.
+            database
+                .with_database(|db| db.prune_finalized_storage(3))
+                .await
+                .unwrap();
+            let Ok(result) = upgrades(2, 6).await else {
+                panic!()
+            };
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].block_number, 5);
+            assert_eq!(result[0].spec_version, 9160);
+
+            // No block of the range has any storage left.
+            assert!(matches!(
+                upgrades(1, 2).await,
+                Err(RuntimeUpgradesError::Pruned)
+            ));
+        });
+    }
+
+    #[test]
+    fn runtime_queries_distinguish_pruned_from_unknown_blocks() {
+        smol::block_on(async {
+            let chain_spec_json = include_bytes!("../../tests/substrate-node-template.json");
+            let (database, genesis_hash, _, _) =
+                test_util::chain_spec_database(chain_spec_json).await;
+
+            let database = Arc::new(database);
+            let json_rpc_service =
+                test_util::json_rpc_service(database.clone(), chain_spec_json, None).await;
+
+            // Block 1 is finalized, and the storage of the genesis block is pruned.
+            database
+                .with_database(move |db| {
+                    let block1_hash = test_util::insert_block_with_storage(
+                        db,
+                        &genesis_hash,
+                        1,
+                        &BTreeMap::new(),
+                        false,
+                    );
+                    db.set_finalized(&block1_hash).unwrap();
+                    db.prune_finalized_storage(0).unwrap();
+                })
+                .await;
+
+            for method in ["state_getRuntimeVersion", "state_getMetadata"] {
+                // The genesis block is known, but its storage has been pruned.
+                json_rpc_service.send_request(
+                    serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": method,
+                        "params": [format!("0x{}", hex::encode(genesis_hash))],
+                    })
+                    .to_string(),
+                );
+                let response = serde_json::from_str::<serde_json::Value>(
+                    &json_rpc_service.next_response().await,
+                )
+                .unwrap();
+                assert_eq!(response["error"]["code"], -32000, "{method}");
+                assert_eq!(
+                    response["error"]["message"], STORAGE_PRUNED_ERROR_MESSAGE,
+                    "{method}"
+                );
+
+                // Blocks that aren't in the database lead to a `null` result.
+                json_rpc_service.send_request(
+                    serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 2,
+                        "method": method,
+                        "params": [format!("0x{}", hex::encode([0x11; 32]))],
+                    })
+                    .to_string(),
+                );
+                let response = serde_json::from_str::<serde_json::Value>(
+                    &json_rpc_service.next_response().await,
+                )
+                .unwrap();
+                assert!(response.get("error").is_none(), "{method}");
+                assert_eq!(response["result"], serde_json::Value::Null, "{method}");
+            }
+        });
+    }
+}
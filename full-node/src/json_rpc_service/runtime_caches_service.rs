@@ -113,16 +113,25 @@ impl RuntimeCachesService {
                             (Ok(None), Ok(_)) => Err(GetError::NoCode),
                             (Err(database_thread::StorageAccessError::UnknownBlock), _)
                             | (_, Err(database_thread::StorageAccessError::UnknownBlock)) => {
-                                // Note that we don't put the `CorruptedError` in the cache, in
-                                // case the database somehow recovers.
+                                // Note that we don't put the error in the cache, as the block
+                                // might later be inserted in the database.
                                 let _ = result_tx.send(Err(GetError::UnknownBlock));
                                 continue;
                             }
+                            (Err(database_thread::StorageAccessError::StoragePruned), _)
+                            | (_, Err(database_thread::StorageAccessError::StoragePruned)) => {
+                                // Note that we don't put the error in the cache. The entries
+                                // of the cache are never invalidated, and a block whose storage
+                                // has been pruned shouldn't be queried often anyway.
+                                let _ = result_tx.send(Err(GetError::Pruned));
+                                continue;
+                            }
                             (Err(database_thread::StorageAccessError::IncompleteStorage), _)
                             | (_, Err(database_thread::StorageAccessError::IncompleteStorage)) => {
-                                // Note that we don't put the `CorruptedError` in the cache, in
-                                // case the database somehow recovers.
-                                let _ = result_tx.send(Err(GetError::Pruned));
+                                // Note that we don't put the error in the cache, as the
+                                // missing storage items might later be downloaded and inserted
+                                // in the database.
+                                let _ = result_tx.send(Err(GetError::IncompleteStorage));
                                 continue;
                             }
                             (Err(database_thread::StorageAccessError::Corrupted(_)), _)
@@ -177,6 +186,8 @@ pub enum GetError {
     UnknownBlock,
     /// Storage of requested block is no longer in the database.
     Pruned,
+    /// Some of the storage of the requested block is missing from the database.
+    IncompleteStorage,
     /// Block doesn't have any storage entry at the key `:code`.
     NoCode,
     /// Invalid storage entry at `:heappages`.
@@ -0,0 +1,132 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use smol::lock::Mutex;
+use smoldot::executor;
+use std::{future::Future, num::NonZero};
+
+/// Cache of the runtime versions of blocks, shared between all the requests handlers.
+///
+/// Entries are indexed by block hash. Since the hash of a block commits to its content,
+/// including its ancestry, entries never need to be invalidated, even in case of a re-org.
+pub struct RuntimeVersionsCache {
+    entries: Mutex<lru::LruCache<[u8; 32], executor::CoreVersion>>,
+}
+
+impl RuntimeVersionsCache {
+    /// Builds a new empty cache that holds at most `num_entries` runtime versions.
+    pub fn new(num_entries: NonZero<usize>) -> Self {
+        RuntimeVersionsCache {
+            entries: Mutex::new(lru::LruCache::new(num_entries)),
+        }
+    }
+
+    /// Returns the runtime version of the given block.
+    ///
+    /// If the cache doesn't contain the runtime version of this block, `obtain` is awaited and,
+    /// on success, its output is stored in the cache. `obtain` is dropped without being polled
+    /// if the cache contains the runtime version.
+    ///
+    /// The cache isn't locked while `obtain` is running. If multiple calls concerning the same
+    /// block are performed concurrently, they might all await their `obtain`.
+    pub async fn get_or_try_insert<E>(
+        &self,
+        block_hash: [u8; 32],
+        obtain: impl Future<Output = Result<executor::CoreVersion, E>>,
+    ) -> Result<executor::CoreVersion, E> {
+        if let Some(runtime_version) = self.entries.lock().await.get(&block_hash) {
+            return Ok(runtime_version.clone());
+        }
+
+        let runtime_version = obtain.await?;
+        self.entries
+            .lock()
+            .await
+            .put(block_hash, runtime_version.clone());
+        Ok(runtime_version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RuntimeVersionsCache;
+    use smoldot::executor;
+    use std::{
+        num::NonZero,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    fn runtime_version(spec_version: u32) -> executor::CoreVersion {
+        executor::CoreVersion::from_slice(
+            executor::CoreVersionRef {
+                spec_name: "node-template",
+                impl_name: "node-template",
+                authoring_version: 1,
+                spec_version,
+                impl_version: 1,
+                apis:
+                    executor::host::runtime_version::CoreVersionApisRefIter::from_slice_no_length(
+                        &[],
+                    )
+                    .unwrap(),
+                transaction_version: Some(1),
+                state_version: None,
+            }
+            .scale_encoding_vec(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn same_hash_obtained_once() {
+        smol::block_on(async {
+            let cache = RuntimeVersionsCache::new(NonZero::<usize>::new(4).unwrap());
+            let num_obtained = AtomicUsize::new(0);
+
+            for _ in 0..2 {
+                let version = cache
+                    .get_or_try_insert([1; 32], async {
+                        num_obtained.fetch_add(1, Ordering::SeqCst);
+                        Ok::<_, ()>(runtime_version(100))
+                    })
+                    .await
+                    .unwrap();
+                assert_eq!(version.decode().spec_version, 100);
+            }
+
+            assert_eq!(num_obtained.load(Ordering::SeqCst), 1);
+        });
+    }
+
+    #[test]
+    fn errors_not_cached() {
+        smol::block_on(async {
+            let cache = RuntimeVersionsCache::new(NonZero::<usize>::new(4).unwrap());
+
+            assert!(cache
+                .get_or_try_insert([1; 32], async { Err(()) })
+                .await
+                .is_err());
+
+            let version = cache
+                .get_or_try_insert([1; 32], async { Ok::<_, ()>(runtime_version(101)) })
+                .await
+                .unwrap();
+            assert_eq!(version.decode().spec_version, 101);
+        });
+    }
+}
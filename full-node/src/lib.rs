@@ -18,7 +18,7 @@
 #![deny(rustdoc::broken_intra_doc_links)]
 // TODO: #![deny(unused_crate_dependencies)] doesn't work because some deps are used only by the binary, figure if this can be fixed?
 
-use futures_util::{future, StreamExt as _};
+use futures_util::{future, Stream, StreamExt as _};
 use rand::RngCore as _;
 use smol::lock::Mutex;
 use smoldot::{
@@ -27,21 +27,35 @@ use smoldot::{
     executor, header,
     identity::keystore,
     informant::HashDisplay,
+    json_rpc::methods,
     libp2p::{
         connection, multiaddr,
         peer_id::{self, PeerId},
     },
     trie,
 };
-use std::{array, borrow::Cow, io, iter, mem, net::SocketAddr, path::PathBuf, sync::Arc};
+use std::{
+    array, borrow::Cow, collections::BTreeMap, io, iter, mem, net::SocketAddr, num::NonZero,
+    path::PathBuf, sync::Arc, time::Duration,
+};
 
 mod consensus_service;
 mod database_thread;
 mod jaeger_service;
 mod json_rpc_service;
 mod network_service;
+mod test_util;
 mod util;
 
+/// Maximum number of JSON-RPC requests waiting to be processed when
+/// [`ChainConfig::json_rpc_listen`] is `None`.
+///
+/// See [`JsonRpcListenConfig::max_json_rpc_queued_requests`].
+pub const DEFAULT_MAX_JSON_RPC_QUEUED_REQUESTS: NonZero<u32> = match NonZero::<u32>::new(256) {
+    Some(n) => n,
+    None => unreachable!(),
+};
+
 pub struct Config<'a> {
     /// Chain to connect to.
     pub chain: ChainConfig<'a>,
@@ -69,6 +83,38 @@ pub struct JsonRpcListenConfig {
     pub address: SocketAddr,
     /// Maximum number of JSON-RPC clients that can be connected at the same time.
     pub max_json_rpc_clients: u32,
+    /// Maximum number of subscriptions that each JSON-RPC client can have active at the same
+    /// time. Attempts to start more subscriptions are rejected.
+    pub max_json_rpc_subscriptions_per_client: u32,
+    /// Maximum number of blocks that each `chainHead_v1_follow` subscription can keep pinned at
+    /// the same time. Subscriptions that exceed this limit are stopped. `0` means no limit.
+    pub max_json_rpc_chain_head_pinned_blocks: u32,
+    /// Maximum number of calls per second, shared between all clients, to the JSON-RPC functions
+    /// that execute the runtime, such as `state_call`. Calls above this limit are rejected.
+    /// `0` means no limit.
+    pub max_json_rpc_expensive_requests_per_second: u32,
+    /// Maximum number of JSON-RPC requests, shared between all the clients, that are waiting to
+    /// be processed. When this limit is reached, the oldest request waiting to be processed is
+    /// answered with a "server busy" error. If [`ChainConfig::json_rpc_listen`] is `None`, the
+    /// limit of the virtual endpoint is [`DEFAULT_MAX_JSON_RPC_QUEUED_REQUESTS`].
+    pub max_json_rpc_queued_requests: NonZero<u32>,
+    /// Maximum size, in bytes, of a JSON-RPC request. Larger requests are answered with an error
+    /// without being parsed. Also applies to the requests sent through
+    /// [`Client::send_json_rpc_request`]. If [`ChainConfig::json_rpc_listen`] is `None`, the
+    /// limit of the virtual endpoint is 15 MiB.
+    pub max_json_rpc_request_size: u32,
+    /// If `true`, the name and duration of each JSON-RPC request is logged once it has been
+    /// answered.
+    pub log_json_rpc_requests_durations: bool,
+    /// If `true`, the JSON-RPC functions that accept an optional block hash use the finalized
+    /// block when no hash is passed. If `false`, they use the best block, which is the behavior
+    /// of Substrate.
+    pub json_rpc_default_to_finalized_block: bool,
+    /// Maximum duration of the database accesses performed in order to answer a JSON-RPC
+    /// request. The request fails with an error if the database doesn't answer in time. If
+    /// [`ChainConfig::json_rpc_listen`] is `None`, the timeout of the virtual endpoint is 60
+    /// seconds.
+    pub json_rpc_database_timeout: Duration,
 }
 
 /// Allow generating logs.
@@ -116,6 +162,38 @@ pub struct ChainConfig<'a> {
     pub keystore_path: Option<PathBuf>,
     /// Configuration of the JSON-RPC server. If `None`, no TCP server is started.
     pub json_rpc_listen: Option<JsonRpcListenConfig>,
+    /// Runtime code to use instead of the on-chain runtime code when executing the blocks with
+    /// the given numbers. Used in order to work around buggy on-chain runtimes.
+    ///
+    /// The code substitutes found in the chain specification are added to this list. In case of
+    /// conflict, the entries of this field take priority.
+    pub code_substitutes: BTreeMap<u64, Vec<u8>>,
+    /// Number of finalized blocks below the latest finalized block whose storage is kept in the
+    /// database. The storage of older blocks is removed, while their headers and bodies are
+    /// kept. If `None`, the storage of all blocks is kept.
+    pub blocks_pruning: Option<u64>,
+    /// Storage of the genesis block. If `None`, the genesis storage found in
+    /// [`ChainConfig::chain_spec`] is used.
+    pub genesis_storage: Option<GenesisStorageConfig>,
+    /// If `true` and the database already exists, the state trie root hash of the genesis block
+    /// stored in the database must be equal to the one of the genesis block of the chain,
+    /// otherwise [`start`] returns an error.
+    ///
+    /// The hash of the genesis block stored in the database is always compared with the one of
+    /// the chain, regardless of this value, which already protects against opening a database
+    /// that was built for a different chain.
+    pub verify_database_genesis: bool,
+}
+
+/// Storage of the genesis block of a chain, provided directly rather than through the chain
+/// specification.
+#[derive(Debug, Clone)]
+pub struct GenesisStorageConfig {
+    /// Storage items of the genesis block, indexed by key.
+    pub items: BTreeMap<Vec<u8>, Vec<u8>>,
+    /// If `Some`, the state trie root hash calculated from [`GenesisStorageConfig::items`] must
+    /// be equal to this value, otherwise [`start`] returns an error.
+    pub expected_state_root: Option<[u8; 32]>,
 }
 
 /// Running client. As long as this object is alive, the client reads/writes the database and has
@@ -170,6 +248,52 @@ impl Client {
         self.consensus_service.sync_state().await
     }
 
+    /// Returns a stream of the blocks of the chain that are imported from now on.
+    ///
+    /// The stream ends if it isn't polled quickly enough, or if the node has to skip a part of
+    /// the chain, for example after a warp sync.
+    pub async fn subscribe_imported_blocks(
+        &self,
+    ) -> impl Stream<Item = consensus_service::ImportedBlock> {
+        self.consensus_service
+            .subscribe_imported_blocks()
+            .await
+            .new_blocks
+    }
+
+    /// Returns the changes to the storage performed by the given block compared to its parent,
+    /// ordered by key. Each value is `None` if the block has erased the entry.
+    ///
+    /// Returns `None` if the block is unknown, if it hasn't been verified yet, if it is the
+    /// genesis block, or if its storage or the storage of its parent has been pruned.
+    pub async fn block_storage_changes(
+        &self,
+        block_hash: [u8; 32],
+    ) -> Option<Vec<(Vec<u8>, Option<Vec<u8>>)>> {
+        self.consensus_service
+            .block_storage_changes(block_hash)
+            .await
+    }
+
+    /// Downloads from the network the storage of the finalized block of the chain, starting at
+    /// the given key, in verified and ordered chunks.
+    ///
+    /// If the stream yields an error, the download can be resumed by calling this function again
+    /// with the key found in the error.
+    pub async fn download_finalized_storage(
+        &self,
+        start_key: Vec<u8>,
+    ) -> impl Stream<
+        Item = Result<
+            consensus_service::StorageDownloadChunk,
+            consensus_service::StorageDownloadError,
+        >,
+    > {
+        self.consensus_service
+            .download_finalized_storage(start_key)
+            .await
+    }
+
     // TODO: not the best API
     pub async fn relay_chain_sync_state(&self) -> Option<consensus_service::SyncState> {
         if let Some(s) = &self.relay_chain_consensus_service {
@@ -181,7 +305,8 @@ impl Client {
 
     /// Adds a JSON-RPC request to the queue of requests of the virtual endpoint of the chain.
     ///
-    /// The virtual endpoint doesn't have any limit.
+    /// The virtual endpoint doesn't have any limit, except for
+    /// [`JsonRpcListenConfig::max_json_rpc_request_size`].
     pub fn send_json_rpc_request(&self, request: String) {
         self.json_rpc_service.send_request(request)
     }
@@ -198,7 +323,8 @@ impl Client {
     /// Adds a JSON-RPC request to the queue of requests of the virtual endpoint of the
     /// relay chain.
     ///
-    /// The virtual endpoint doesn't have any limit.
+    /// The virtual endpoint doesn't have any limit, except for
+    /// [`JsonRpcListenConfig::max_json_rpc_request_size`].
     pub fn relay_chain_send_json_rpc_request(
         &self,
         request: String,
@@ -232,12 +358,32 @@ impl Client {
 pub enum StartError {
     /// Failed to parse the chain specification.
     ChainSpecParse(chain_spec::ParseError),
+    /// Failed to parse the properties found in the chain specification.
+    ChainSpecProperties(chain_spec::PropertiesParseError),
     /// Error building the chain information of the genesis block.
     InvalidGenesisInformation(chain_spec::FromGenesisStorageError),
+    /// The state trie root hash of the genesis block doesn't match
+    /// [`GenesisStorageConfig::expected_state_root`].
+    GenesisStateRootMismatch,
     /// Failed to parse the chain specification of the relay chain.
     RelayChainSpecParse(chain_spec::ParseError),
+    /// Failed to parse the properties found in the chain specification of the relay chain.
+    RelayChainSpecProperties(chain_spec::PropertiesParseError),
     /// Error building the chain information of the genesis block of the relay chain.
     InvalidRelayGenesisInformation(chain_spec::FromGenesisStorageError),
+    /// The state trie root hash of the genesis block of the relay chain doesn't match
+    /// [`GenesisStorageConfig::expected_state_root`].
+    RelayGenesisStateRootMismatch,
+    /// The genesis block stored in the database doesn't match the one of the chain. See
+    /// [`ChainConfig::verify_database_genesis`].
+    #[display(fmt = "Database was built for a different chain: genesis block mismatch")]
+    DatabaseGenesisMismatch,
+    /// The genesis block stored in the database of the relay chain doesn't match the one of the
+    /// relay chain. See [`ChainConfig::verify_database_genesis`].
+    #[display(
+        fmt = "Relay chain database was built for a different chain: genesis block mismatch"
+    )]
+    RelayChainDatabaseGenesisMismatch,
     /// Error initializing the networking service.
     NetworkInit(network_service::InitError),
     /// Error initializing the JSON-RPC service.
@@ -268,13 +414,21 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
         chain_spec::ChainSpec::from_json_bytes(&config.chain.chain_spec)
             .map_err(StartError::ChainSpecParse)?
     };
+    let chain_properties = Arc::new(
+        chain_spec::ChainProperties::from_json(chain_spec.properties())
+            .map_err(StartError::ChainSpecProperties)?,
+    );
 
-    // TODO: don't just throw away the runtime
     // TODO: building the genesis chain information is pretty expensive and we throw away most of the information
-    let genesis_chain_information = chain_spec
-        .to_chain_information()
-        .map_err(StartError::InvalidGenesisInformation)?
-        .0;
+    let (genesis_chain_information, genesis_runtime) =
+        build_genesis_chain_information(&chain_spec, config.chain.genesis_storage.as_ref())
+            .map_err(StartError::InvalidGenesisInformation)?;
+    if !genesis_state_root_matches(
+        &genesis_chain_information,
+        config.chain.genesis_storage.as_ref(),
+    ) {
+        return Err(StartError::GenesisStateRootMismatch);
+    }
 
     let relay_chain_spec = match &config.relay_chain {
         Some(cfg) => Some(
@@ -283,36 +437,28 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
         ),
         None => None,
     };
-
-    // TODO: don't just throw away the runtime
-    // TODO: building the genesis chain information is pretty expensive and we throw away most of the information
-    let relay_genesis_chain_information = match &relay_chain_spec {
-        Some(r) => Some(
-            r.to_chain_information()
-                .map_err(StartError::InvalidRelayGenesisInformation)?
-                .0,
-        ),
+    let relay_chain_properties = match &relay_chain_spec {
+        Some(relay_chain_spec) => Some(Arc::new(
+            chain_spec::ChainProperties::from_json(relay_chain_spec.properties())
+                .map_err(StartError::RelayChainSpecProperties)?,
+        )),
         None => None,
     };
 
-    // The `protocolId` field of chain specifications is deprecated. Print a warning.
-    if chain_spec.protocol_id().is_some() {
-        config.log_callback.log(
-            LogLevel::Warn,
-            format!("chain-spec-has-protocol-id; chain={}", chain_spec.id()),
-        );
-    }
-    if let Some(relay_chain_spec) = &relay_chain_spec {
-        if relay_chain_spec.protocol_id().is_some() {
-            config.log_callback.log(
-                LogLevel::Warn,
-                format!(
-                    "chain-spec-has-protocol-id; chain={}",
-                    relay_chain_spec.id()
-                ),
-            );
-        }
-    }
+    // TODO: building the genesis chain information is pretty expensive and we throw away most of the information
+    let (relay_genesis_chain_information, relay_genesis_runtime) =
+        match (&relay_chain_spec, &config.relay_chain) {
+            (Some(r), Some(cfg)) => {
+                let (chain_information, runtime) =
+                    build_genesis_chain_information(r, cfg.genesis_storage.as_ref())
+                        .map_err(StartError::InvalidRelayGenesisInformation)?;
+                if !genesis_state_root_matches(&chain_information, cfg.genesis_storage.as_ref()) {
+                    return Err(StartError::RelayGenesisStateRootMismatch);
+                }
+                (Some(chain_information), Some(runtime))
+            }
+            _ => (None, None),
+        };
 
     // The `telemetryEndpoints` field of chain specifications isn't supported.
     if chain_spec.telemetry_endpoints().count() != 0 {
@@ -346,11 +492,15 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
     let (database, database_existed) = {
         let (db, existed) = open_database(
             &chain_spec,
+            config.chain.genesis_storage.as_ref(),
             genesis_chain_information.as_ref(),
+            &genesis_runtime,
             config.chain.sqlite_database_path,
             config.chain.sqlite_cache_size,
+            config.chain.verify_database_genesis,
         )
-        .await;
+        .await
+        .map_err(|DatabaseGenesisMismatch| StartError::DatabaseGenesisMismatch)?;
 
         (Arc::new(database_thread::DatabaseThread::from(db)), existed)
     };
@@ -359,11 +509,15 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
         Some(Arc::new(database_thread::DatabaseThread::from(
             open_database(
                 relay_chain_spec.as_ref().unwrap(),
+                relay_chain.genesis_storage.as_ref(),
                 relay_genesis_chain_information.as_ref().unwrap().as_ref(),
+                relay_genesis_runtime.as_ref().unwrap(),
                 relay_chain.sqlite_database_path.clone(),
                 relay_chain.sqlite_cache_size,
+                relay_chain.verify_database_genesis,
             )
             .await
+            .map_err(|DatabaseGenesisMismatch| StartError::RelayChainDatabaseGenesisMismatch)?
             .0,
         )))
     } else {
@@ -415,6 +569,7 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
             chains: iter::once(network_service::ChainConfig {
                 log_name: chain_spec.id().to_owned(),
                 fork_id: chain_spec.fork_id().map(|n| n.to_owned()),
+                protocol_id: chain_spec.protocol_id().map(|n| n.to_owned()),
                 block_number_bytes: usize::from(chain_spec.block_number_bytes()),
                 database: database.clone(),
                 grandpa_protocol_finalized_block_height: if matches!(
@@ -487,6 +642,7 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
                     Some(network_service::ChainConfig {
                         log_name: relay_chains_specs.id().to_owned(),
                         fork_id: relay_chains_specs.fork_id().map(|n| n.to_owned()),
+                        protocol_id: relay_chains_specs.protocol_id().map(|n| n.to_owned()),
                         block_number_bytes: usize::from(relay_chains_specs.block_number_bytes()),
                         database: relay_chain_database.clone().unwrap(),
                         grandpa_protocol_finalized_block_height: if matches!(
@@ -600,13 +756,39 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
         network_service: (network_service.clone(), network_service_chain_ids[0]),
         database: database.clone(),
         block_number_bytes: usize::from(chain_spec.block_number_bytes()),
-        keystore,
+        keystore: keystore.clone(),
         jaeger_service: jaeger_service.clone(),
         slot_duration_author_ratio: 43691_u16,
+        code_substitutes: chain_spec
+            .code_substitutes()
+            .map(|(block_number, code)| (block_number, code.to_vec()))
+            .chain(mem::take(&mut config.chain.code_substitutes))
+            .collect(),
+        blocks_pruning: config.chain.blocks_pruning,
     })
     .await
     .map_err(StartError::ConsensusServiceInit)?;
 
+    let relay_chain_keystore = if relay_chain_database.is_some() {
+        Some(Arc::new({
+            let mut keystore = keystore::Keystore::new(
+                config.relay_chain.as_ref().unwrap().keystore_path.clone(),
+                rand::random(),
+            )
+            .await
+            .map_err(StartError::RelayChainKeystoreInit)?;
+            for mut private_key in
+                mem::take(&mut config.relay_chain.as_mut().unwrap().keystore_memory)
+            {
+                keystore.insert_sr25519_memory(keystore::KeyNamespace::all(), &private_key);
+                zeroize::Zeroize::zeroize(&mut *private_key);
+            }
+            keystore
+        }))
+    } else {
+        None
+    };
+
     let relay_chain_consensus_service = if let Some(relay_chain_database) = &relay_chain_database {
         Some(
             consensus_service::ConsensusService::new(consensus_service::Config {
@@ -629,23 +811,19 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
                 block_number_bytes: usize::from(
                     relay_chain_spec.as_ref().unwrap().block_number_bytes(),
                 ),
-                keystore: Arc::new({
-                    let mut keystore = keystore::Keystore::new(
-                        config.relay_chain.as_ref().unwrap().keystore_path.clone(),
-                        rand::random(),
-                    )
-                    .await
-                    .map_err(StartError::RelayChainKeystoreInit)?;
-                    for mut private_key in
-                        mem::take(&mut config.relay_chain.as_mut().unwrap().keystore_memory)
-                    {
-                        keystore.insert_sr25519_memory(keystore::KeyNamespace::all(), &private_key);
-                        zeroize::Zeroize::zeroize(&mut *private_key);
-                    }
-                    keystore
-                }),
+                keystore: relay_chain_keystore.clone().unwrap(),
                 jaeger_service, // TODO: consider passing a different jaeger service with a different service name
                 slot_duration_author_ratio: 43691_u16,
+                code_substitutes: relay_chain_spec
+                    .as_ref()
+                    .unwrap()
+                    .code_substitutes()
+                    .map(|(block_number, code)| (block_number, code.to_vec()))
+                    .chain(mem::take(
+                        &mut config.relay_chain.as_mut().unwrap().code_substitutes,
+                    ))
+                    .collect(),
+                blocks_pruning: config.relay_chain.as_ref().unwrap().blocks_pruning,
             })
             .await
             .map_err(StartError::RelayChainConsensusServiceInit)?,
@@ -666,21 +844,83 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
         log_callback: config.log_callback.clone(),
         database,
         consensus_service: consensus_service.clone(),
+        keystore,
         network_service: (network_service.clone(), network_service_chain_ids[0]),
         bind_address: config.chain.json_rpc_listen.as_ref().map(|cfg| cfg.address),
         max_parallel_requests: 32,
+        max_queued_requests: json_rpc_max_queued_requests(config.chain.json_rpc_listen.as_ref()),
+        state_get_keys_max_keys: 10_000,
+        subscribe_all_heads_report_initial_best: false,
+        default_block: if config
+            .chain
+            .json_rpc_listen
+            .as_ref()
+            .is_some_and(|cfg| cfg.json_rpc_default_to_finalized_block)
+        {
+            json_rpc_service::DefaultBlock::Finalized
+        } else {
+            json_rpc_service::DefaultBlock::Best
+        },
+        database_timeout: config
+            .chain
+            .json_rpc_listen
+            .as_ref()
+            .map_or(Duration::from_secs(60), |cfg| cfg.json_rpc_database_timeout),
+        consensus_engine: consensus_engine(&genesis_chain_information),
+        finality_engine: finality_engine(&genesis_chain_information),
+        allow_unsafe_methods: config
+            .chain
+            .json_rpc_listen
+            .as_ref()
+            .map_or(true, |cfg| cfg.address.ip().is_loopback()),
+        max_json_rpc_subscriptions_per_client: config
+            .chain
+            .json_rpc_listen
+            .as_ref()
+            .map_or(0, |cfg| cfg.max_json_rpc_subscriptions_per_client),
+        max_chain_head_pinned_blocks: config.chain.json_rpc_listen.as_ref().map_or(
+            NonZero::<usize>::new(512).unwrap(),
+            |cfg| {
+                NonZero::<usize>::new(
+                    usize::try_from(cfg.max_json_rpc_chain_head_pinned_blocks).unwrap(),
+                )
+                .unwrap_or(NonZero::<usize>::MAX)
+            },
+        ),
+        max_request_size: config
+            .chain
+            .json_rpc_listen
+            .as_ref()
+            .map_or(15 * 1024 * 1024, |cfg| {
+                usize::try_from(cfg.max_json_rpc_request_size).unwrap_or(usize::MAX)
+            }),
+        max_expensive_requests_per_second: config
+            .chain
+            .json_rpc_listen
+            .as_ref()
+            .and_then(|cfg| NonZero::<u32>::new(cfg.max_json_rpc_expensive_requests_per_second)),
+        log_requests_durations: config
+            .chain
+            .json_rpc_listen
+            .as_ref()
+            .is_some_and(|cfg| cfg.log_json_rpc_requests_durations),
         max_json_rpc_clients: config
             .chain
             .json_rpc_listen
             .map_or(0, |cfg| cfg.max_json_rpc_clients),
         chain_name: chain_spec.name().to_owned(),
         chain_type: chain_spec.chain_type().to_owned(),
-        chain_properties_json: chain_spec.properties().to_owned(),
+        chain_properties,
         chain_is_live: chain_spec.has_live_network(),
-        genesis_block_hash: genesis_chain_information
-            .as_ref()
-            .finalized_block_header
-            .hash(usize::from(chain_spec.block_number_bytes())),
+        chain_fork_id: chain_spec.fork_id().map(|n| n.to_owned()),
+        chain_protocol_id: chain_spec.protocol_id().map(|n| n.to_owned()),
+        chain_spec: Arc::new(chain_spec.clone()),
+        genesis_block_hash: Some(
+            genesis_chain_information
+                .as_ref()
+                .finalized_block_header
+                .hash(usize::from(chain_spec.block_number_bytes())),
+        ),
     })
     .await
     .map_err(StartError::JsonRpcServiceInit)?;
@@ -695,25 +935,86 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
                 log_callback: config.log_callback.clone(),
                 database: relay_chain_database.clone().unwrap(),
                 consensus_service: relay_chain_consensus_service.clone().unwrap(),
+                keystore: relay_chain_keystore.unwrap(),
                 network_service: (network_service.clone(), network_service_chain_ids[1]),
                 bind_address: relay_chain_cfg
                     .json_rpc_listen
                     .as_ref()
                     .map(|cfg| cfg.address),
                 max_parallel_requests: 32,
+                max_queued_requests: json_rpc_max_queued_requests(
+                    relay_chain_cfg.json_rpc_listen.as_ref(),
+                ),
+                state_get_keys_max_keys: 10_000,
+                subscribe_all_heads_report_initial_best: false,
+                default_block: if relay_chain_cfg
+                    .json_rpc_listen
+                    .as_ref()
+                    .is_some_and(|cfg| cfg.json_rpc_default_to_finalized_block)
+                {
+                    json_rpc_service::DefaultBlock::Finalized
+                } else {
+                    json_rpc_service::DefaultBlock::Best
+                },
+                database_timeout: relay_chain_cfg
+                    .json_rpc_listen
+                    .as_ref()
+                    .map_or(Duration::from_secs(60), |cfg| cfg.json_rpc_database_timeout),
+                consensus_engine: consensus_engine(
+                    relay_genesis_chain_information.as_ref().unwrap(),
+                ),
+                finality_engine: finality_engine(relay_genesis_chain_information.as_ref().unwrap()),
+                allow_unsafe_methods: relay_chain_cfg
+                    .json_rpc_listen
+                    .as_ref()
+                    .map_or(true, |cfg| cfg.address.ip().is_loopback()),
+                max_json_rpc_subscriptions_per_client: relay_chain_cfg
+                    .json_rpc_listen
+                    .as_ref()
+                    .map_or(0, |cfg| cfg.max_json_rpc_subscriptions_per_client),
+                max_chain_head_pinned_blocks: relay_chain_cfg.json_rpc_listen.as_ref().map_or(
+                    NonZero::<usize>::new(512).unwrap(),
+                    |cfg| {
+                        NonZero::<usize>::new(
+                            usize::try_from(cfg.max_json_rpc_chain_head_pinned_blocks).unwrap(),
+                        )
+                        .unwrap_or(NonZero::<usize>::MAX)
+                    },
+                ),
+                max_request_size: relay_chain_cfg
+                    .json_rpc_listen
+                    .as_ref()
+                    .map_or(15 * 1024 * 1024, |cfg| {
+                        usize::try_from(cfg.max_json_rpc_request_size).unwrap_or(usize::MAX)
+                    }),
+                max_expensive_requests_per_second: relay_chain_cfg
+                    .json_rpc_listen
+                    .as_ref()
+                    .and_then(|cfg| {
+                        NonZero::<u32>::new(cfg.max_json_rpc_expensive_requests_per_second)
+                    }),
+                log_requests_durations: relay_chain_cfg
+                    .json_rpc_listen
+                    .as_ref()
+                    .is_some_and(|cfg| cfg.log_json_rpc_requests_durations),
                 max_json_rpc_clients: relay_chain_cfg
                     .json_rpc_listen
                     .map_or(0, |cfg| cfg.max_json_rpc_clients),
                 chain_name: relay_chain_spec.name().to_owned(),
                 chain_type: relay_chain_spec.chain_type().to_owned(),
-                chain_properties_json: relay_chain_spec.properties().to_owned(),
+                chain_properties: relay_chain_properties.unwrap(),
                 chain_is_live: relay_chain_spec.has_live_network(),
-                genesis_block_hash: relay_genesis_chain_information
-                    .as_ref()
-                    .unwrap()
-                    .as_ref()
-                    .finalized_block_header
-                    .hash(usize::from(relay_chain_spec.block_number_bytes())),
+                chain_fork_id: relay_chain_spec.fork_id().map(|n| n.to_owned()),
+                chain_protocol_id: relay_chain_spec.protocol_id().map(|n| n.to_owned()),
+                chain_spec: Arc::new(relay_chain_spec.clone()),
+                genesis_block_hash: Some(
+                    relay_genesis_chain_information
+                        .as_ref()
+                        .unwrap()
+                        .as_ref()
+                        .finalized_block_header
+                        .hash(usize::from(relay_chain_spec.block_number_bytes())),
+                ),
             })
             .await
             .map_err(StartError::JsonRpcServiceInit)?,
@@ -802,6 +1103,10 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
 ///
 /// The returned boolean is `true` if the database existed before.
 ///
+/// If the database already existed, returns an error if the hash of its genesis block doesn't
+/// match the one of `genesis_chain_information`. If `verify_genesis` is `true`, the state trie
+/// root hash of its genesis block is additionally compared.
+///
 /// # Panic
 ///
 /// Panics if the database can't be open. This function is expected to be called from the `main`
@@ -809,10 +1114,13 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
 ///
 async fn open_database(
     chain_spec: &chain_spec::ChainSpec,
+    genesis_storage: Option<&GenesisStorageConfig>,
     genesis_chain_information: chain::chain_information::ChainInformationRef<'_>,
+    genesis_runtime: &executor::host::HostVmPrototype,
     db_path: Option<PathBuf>,
     sqlite_cache_size: usize,
-) -> (full_sqlite::SqliteFullDatabase, bool) {
+    verify_genesis: bool,
+) -> Result<(full_sqlite::SqliteFullDatabase, bool), DatabaseGenesisMismatch> {
     // The `unwrap()` here can panic for example in case of access denied.
     match full_sqlite::open(full_sqlite::Config {
         block_number_bytes: chain_spec.block_number_bytes().into(),
@@ -830,168 +1138,64 @@ async fn open_database(
     {
         // Database already exists and contains data.
         full_sqlite::DatabaseOpen::Open(database) => {
-            if database.block_hash_by_number(0).unwrap().next().unwrap()
+            let database_genesis_hash = database.block_hash_by_number(0).unwrap().next().unwrap();
+            if database_genesis_hash
                 != genesis_chain_information
                     .finalized_block_header
                     .hash(chain_spec.block_number_bytes().into())
             {
-                panic!("Mismatch between database and chain specification. Shutting down node.");
+                return Err(DatabaseGenesisMismatch);
             }
 
-            (database, true)
-        }
-
-        // The database doesn't exist or is empty.
-        full_sqlite::DatabaseOpen::Empty(empty) => {
-            let genesis_storage = chain_spec.genesis_storage().into_genesis_items().unwrap(); // TODO: return error instead
-
-            // In order to determine the state_version of the genesis block, we need to compile
-            // the runtime.
-            // TODO: return errors instead of panicking
-            // TODO: consider not throwing away the runtime
-            let state_version = executor::host::HostVmPrototype::new(executor::host::Config {
-                module: genesis_storage.value(b":code").unwrap(),
-                heap_pages: executor::storage_heap_pages_to_value(
-                    genesis_storage.value(b":heappages"),
+            if verify_genesis {
+                let database_genesis_header = database
+                    .block_scale_encoded_header(&database_genesis_hash)
+                    .unwrap()
+                    .unwrap();
+                let database_genesis_state_root = *header::decode(
+                    &database_genesis_header,
+                    chain_spec.block_number_bytes().into(),
                 )
-                .unwrap(),
-                exec_hint: executor::vm::ExecHint::ValidateAndExecuteOnce,
-                allow_unresolved_imports: true,
-            })
-            .unwrap()
-            .runtime_version()
-            .decode()
-            .state_version
-            .map(u8::from)
-            .unwrap_or(0);
-
-            // The chain specification only contains trie nodes that have a storage value attached
-            // to them, while the database needs to know all trie nodes (including branch nodes).
-            // The good news is that we can determine the latter from the former, which we do
-            // here.
-            // TODO: consider moving this block to the chain spec module
-            // TODO: poorly optimized
-            let mut trie_structure = {
-                let mut trie_structure = trie::trie_structure::TrieStructure::new();
-                for (key, value) in genesis_storage.iter() {
-                    match trie_structure.node(trie::bytes_to_nibbles(key.iter().copied())) {
-                        trie::trie_structure::Entry::Vacant(e) => {
-                            e.insert_storage_value().insert(
-                                (Some(value), None::<trie::trie_node::MerkleValueOutput>),
-                                (None, None),
-                            );
-                        }
-                        trie::trie_structure::Entry::Occupied(
-                            trie::trie_structure::NodeAccess::Branch(mut e),
-                        ) => {
-                            *e.user_data() = (Some(value), None);
-                            e.insert_storage_value();
-                        }
-                        trie::trie_structure::Entry::Occupied(
-                            trie::trie_structure::NodeAccess::Storage(_),
-                        ) => {
-                            // Duplicate entry.
-                            panic!() // TODO: don't panic?
-                        }
-                    }
-                }
+                .unwrap()
+                .state_root;
 
-                // Calculate the Merkle values of the nodes.
-                for node_index in trie_structure
-                    .iter_ordered()
-                    .collect::<Vec<_>>()
-                    .into_iter()
-                    .rev()
+                if database_genesis_state_root
+                    != *genesis_chain_information.finalized_block_header.state_root
                 {
-                    let mut node_access = trie_structure.node_by_index(node_index).unwrap();
-
-                    let children = core::array::from_fn::<_, 16, _>(|n| {
-                        node_access
-                            .child(trie::Nibble::try_from(u8::try_from(n).unwrap()).unwrap())
-                            .map(|mut child| child.user_data().1.as_ref().unwrap().clone())
-                    });
-
-                    let is_root_node = node_access.is_root_node();
-                    let partial_key = node_access.partial_key().collect::<Vec<_>>().into_iter();
-
-                    // We have to hash the storage value ahead of time if necessary due to borrow
-                    // checking difficulties.
-                    let storage_value_hashed =
-                        match (node_access.user_data().0.as_ref(), state_version) {
-                            (Some(v), 1) => {
-                                if v.len() >= 33 {
-                                    Some(blake2_rfc::blake2b::blake2b(32, &[], v))
-                                } else {
-                                    None
-                                }
-                            }
-                            _ => None,
-                        };
-                    let storage_value = match (
-                        node_access.user_data().0.as_ref(),
-                        storage_value_hashed.as_ref(),
-                    ) {
-                        (_, Some(storage_value_hashed)) => trie::trie_node::StorageValue::Hashed(
-                            <&[u8; 32]>::try_from(storage_value_hashed.as_bytes()).unwrap(),
-                        ),
-                        (Some(v), None) => trie::trie_node::StorageValue::Unhashed(&v[..]),
-                        (None, _) => trie::trie_node::StorageValue::None,
-                    };
-
-                    let merkle_value = trie::trie_node::calculate_merkle_value(
-                        trie::trie_node::Decoded {
-                            children,
-                            partial_key,
-                            storage_value,
-                        },
-                        trie::HashFunction::Blake2,
-                        is_root_node,
-                    )
-                    .unwrap();
-
-                    node_access.into_user_data().1 = Some(merkle_value);
+                    return Err(DatabaseGenesisMismatch);
                 }
+            }
+
+            Ok((database, true))
+        }
 
-                trie_structure
+        // The database doesn't exist or is empty.
+        full_sqlite::DatabaseOpen::Empty(empty) => {
+            let chain_spec_genesis_storage;
+            let genesis_storage = match genesis_storage {
+                Some(genesis_storage) => either::Left(
+                    genesis_storage
+                        .items
+                        .iter()
+                        .map(|(key, value)| (&key[..], &value[..])),
+                ),
+                None => {
+                    // Can't panic, as building the genesis chain information would have failed
+                    // if the chain specification didn't contain the genesis storage items.
+                    chain_spec_genesis_storage =
+                        chain_spec.genesis_storage().into_genesis_items().unwrap();
+                    either::Right(chain_spec_genesis_storage.iter())
+                }
             };
 
-            // Build the iterator of trie nodes.
-            let genesis_storage_full_trie = trie_structure
-                .iter_unordered()
-                .collect::<Vec<_>>()
-                .into_iter()
-                .map(|node_index| {
-                    let (storage_value, Some(merkle_value)) = &trie_structure[node_index] else {
-                        unreachable!()
-                    };
-                    // Cloning to solve borrow checker restriction. // TODO: optimize?
-                    let storage_value = if let Some(storage_value) = storage_value {
-                        // TODO: child tries support?
-                        full_sqlite::InsertTrieNodeStorageValue::Value {
-                            value: Cow::Owned(storage_value.to_vec()),
-                            references_merkle_value: false,
-                        }
-                    } else {
-                        full_sqlite::InsertTrieNodeStorageValue::NoValue
-                    };
-                    let merkle_value = merkle_value.as_ref().to_owned();
-                    let mut node_access = trie_structure.node_by_index(node_index).unwrap();
-
-                    full_sqlite::InsertTrieNode {
-                        storage_value,
-                        merkle_value: Cow::Owned(merkle_value),
-                        children_merkle_values: array::from_fn::<_, 16, _>(|n| {
-                            let child_index =
-                                trie::Nibble::try_from(u8::try_from(n).unwrap()).unwrap();
-                            node_access.child(child_index).map(|mut child| {
-                                Cow::Owned(child.user_data().1.as_ref().unwrap().as_ref().to_vec())
-                            })
-                        }),
-                        partial_key_nibbles: Cow::Owned(
-                            node_access.partial_key().map(u8::from).collect::<Vec<_>>(),
-                        ),
-                    }
-                });
+            let state_version = genesis_runtime
+                .runtime_version()
+                .decode()
+                .state_version
+                .map(u8::from)
+                .unwrap_or(0);
+
+            let genesis_storage_full_trie = storage_trie_nodes(genesis_storage, state_version);
 
             // The finalized block is the genesis block. As such, it has an empty body and
             // no justification.
@@ -1005,9 +1209,497 @@ async fn open_database(
                 )
                 .unwrap();
             database
-                .insert_trie_nodes(genesis_storage_full_trie, state_version)
+                .insert_trie_nodes(genesis_storage_full_trie.into_iter(), state_version)
                 .unwrap();
-            (database, false)
+            Ok((database, false))
+        }
+    }
+}
+
+/// Builds the list of all the trie nodes, including branch nodes, of the trie containing the
+/// given storage items, in a format suitable for
+/// [`full_sqlite::SqliteFullDatabase::insert_trie_nodes`].
+fn storage_trie_nodes<'a>(
+    storage: impl Iterator<Item = (&'a [u8], &'a [u8])>,
+    state_version: u8,
+) -> Vec<full_sqlite::InsertTrieNode<'static>> {
+    // The storage items only correspond to trie nodes that have a storage value attached to
+    // them, while the database needs to know all trie nodes (including branch nodes). The good
+    // news is that we can determine the latter from the former, which we do here.
+    // TODO: consider moving this block to the chain spec module
+    // TODO: poorly optimized
+    let mut trie_structure = {
+        let mut trie_structure = trie::trie_structure::TrieStructure::new();
+        for (key, value) in storage {
+            match trie_structure.node(trie::bytes_to_nibbles(key.iter().copied())) {
+                trie::trie_structure::Entry::Vacant(e) => {
+                    e.insert_storage_value().insert(
+                        (Some(value), None::<trie::trie_node::MerkleValueOutput>),
+                        (None, None),
+                    );
+                }
+                trie::trie_structure::Entry::Occupied(
+                    trie::trie_structure::NodeAccess::Branch(mut e),
+                ) => {
+                    *e.user_data() = (Some(value), None);
+                    e.insert_storage_value();
+                }
+                trie::trie_structure::Entry::Occupied(
+                    trie::trie_structure::NodeAccess::Storage(_),
+                ) => {
+                    // Duplicate entry.
+                    panic!() // TODO: don't panic?
+                }
+            }
+        }
+
+        // Calculate the Merkle values of the nodes.
+        for node_index in trie_structure
+            .iter_ordered()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+        {
+            let mut node_access = trie_structure.node_by_index(node_index).unwrap();
+
+            let children = core::array::from_fn::<_, 16, _>(|n| {
+                node_access
+                    .child(trie::Nibble::try_from(u8::try_from(n).unwrap()).unwrap())
+                    .map(|mut child| child.user_data().1.as_ref().unwrap().clone())
+            });
+
+            let is_root_node = node_access.is_root_node();
+            let partial_key = node_access.partial_key().collect::<Vec<_>>().into_iter();
+
+            // We have to hash the storage value ahead of time if necessary due to borrow
+            // checking difficulties.
+            let storage_value_hashed = match (node_access.user_data().0.as_ref(), state_version) {
+                (Some(v), 1) => {
+                    if v.len() >= 33 {
+                        Some(blake2_rfc::blake2b::blake2b(32, &[], v))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+            let storage_value = match (
+                node_access.user_data().0.as_ref(),
+                storage_value_hashed.as_ref(),
+            ) {
+                (_, Some(storage_value_hashed)) => trie::trie_node::StorageValue::Hashed(
+                    <&[u8; 32]>::try_from(storage_value_hashed.as_bytes()).unwrap(),
+                ),
+                (Some(v), None) => trie::trie_node::StorageValue::Unhashed(&v[..]),
+                (None, _) => trie::trie_node::StorageValue::None,
+            };
+
+            let merkle_value = trie::trie_node::calculate_merkle_value(
+                trie::trie_node::Decoded {
+                    children,
+                    partial_key,
+                    storage_value,
+                },
+                trie::HashFunction::Blake2,
+                is_root_node,
+            )
+            .unwrap();
+
+            node_access.into_user_data().1 = Some(merkle_value);
+        }
+
+        trie_structure
+    };
+
+    trie_structure
+        .iter_unordered()
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|node_index| {
+            let (storage_value, Some(merkle_value)) = &trie_structure[node_index] else {
+                unreachable!()
+            };
+            // Cloning to solve borrow checker restriction. // TODO: optimize?
+            let storage_value = if let Some(storage_value) = storage_value {
+                // TODO: child tries support?
+                full_sqlite::InsertTrieNodeStorageValue::Value {
+                    value: Cow::Owned(storage_value.to_vec()),
+                    references_merkle_value: false,
+                }
+            } else {
+                full_sqlite::InsertTrieNodeStorageValue::NoValue
+            };
+            let merkle_value = merkle_value.as_ref().to_owned();
+            let mut node_access = trie_structure.node_by_index(node_index).unwrap();
+
+            full_sqlite::InsertTrieNode {
+                storage_value,
+                merkle_value: Cow::Owned(merkle_value),
+                children_merkle_values: array::from_fn::<_, 16, _>(|n| {
+                    let child_index = trie::Nibble::try_from(u8::try_from(n).unwrap()).unwrap();
+                    node_access.child(child_index).map(|mut child| {
+                        Cow::Owned(child.user_data().1.as_ref().unwrap().as_ref().to_vec())
+                    })
+                }),
+                partial_key_nibbles: Cow::Owned(
+                    node_access.partial_key().map(u8::from).collect::<Vec<_>>(),
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Returns the value to pass as [`json_rpc_service::Config::max_queued_requests`].
+fn json_rpc_max_queued_requests(json_rpc_listen: Option<&JsonRpcListenConfig>) -> NonZero<usize> {
+    let max = json_rpc_listen.map_or(DEFAULT_MAX_JSON_RPC_QUEUED_REQUESTS, |cfg| {
+        cfg.max_json_rpc_queued_requests
+    });
+    NonZero::<usize>::try_from(max).unwrap_or(NonZero::<usize>::MAX)
+}
+
+/// Error potentially returned by [`open_database`].
+struct DatabaseGenesisMismatch;
+
+/// Builds the chain information of the genesis block of a chain, alongside with its runtime.
+///
+/// The genesis storage is read from `genesis_storage` if it is `Some`, and from the chain
+/// specification otherwise.
+fn build_genesis_chain_information(
+    chain_spec: &chain_spec::ChainSpec,
+    genesis_storage: Option<&GenesisStorageConfig>,
+) -> Result<
+    (
+        chain::chain_information::ValidChainInformation,
+        executor::host::HostVmPrototype,
+    ),
+    chain_spec::FromGenesisStorageError,
+> {
+    match genesis_storage {
+        Some(genesis_storage) => chain_spec::chain_information_from_genesis_storage(
+            genesis_storage
+                .items
+                .iter()
+                .map(|(key, value)| (&key[..], &value[..])),
+            |key| genesis_storage.items.get(key).map(|value| &value[..]),
+            usize::from(chain_spec.block_number_bytes()),
+        ),
+        None => chain_spec.to_chain_information(),
+    }
+}
+
+/// Returns the consensus engine of the chain whose chain information is passed, or `None` if
+/// it is unknown.
+fn consensus_engine(
+    chain_information: &chain::chain_information::ValidChainInformation,
+) -> Option<methods::ConsensusEngine> {
+    match chain_information.as_ref().consensus {
+        chain::chain_information::ChainInformationConsensusRef::Aura { .. } => {
+            Some(methods::ConsensusEngine::Aura)
+        }
+        chain::chain_information::ChainInformationConsensusRef::Babe { .. } => {
+            Some(methods::ConsensusEngine::Babe)
+        }
+        chain::chain_information::ChainInformationConsensusRef::Unknown => None,
+    }
+}
+
+/// Returns the finality engine of the chain whose chain information is passed, or `None` if
+/// its blocks are finalized through other means.
+fn finality_engine(
+    chain_information: &chain::chain_information::ValidChainInformation,
+) -> Option<methods::FinalityEngine> {
+    match chain_information.as_ref().finality {
+        chain::chain_information::ChainInformationFinalityRef::Grandpa { .. } => {
+            Some(methods::FinalityEngine::Grandpa)
+        }
+        chain::chain_information::ChainInformationFinalityRef::Outsourced => None,
+    }
+}
+
+/// Returns `false` if [`GenesisStorageConfig::expected_state_root`] is `Some` and doesn't match
+/// the state trie root hash of the given genesis block.
+fn genesis_state_root_matches(
+    genesis_chain_information: &chain::chain_information::ValidChainInformation,
+    genesis_storage: Option<&GenesisStorageConfig>,
+) -> bool {
+    match genesis_storage.and_then(|storage| storage.expected_state_root) {
+        Some(expected) => {
+            *genesis_chain_information
+                .as_ref()
+                .finalized_block_header
+                .state_root
+                == expected
+        }
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        finality_engine, json_rpc_max_queued_requests, open_database, DatabaseGenesisMismatch,
+        GenesisStorageConfig, JsonRpcListenConfig, DEFAULT_MAX_JSON_RPC_QUEUED_REQUESTS,
+    };
+    use crate::test_util;
+    use smoldot::{chain_spec, json_rpc};
+    use std::{fs, num::NonZero, sync::Arc, time::Duration};
+
+    #[test]
+    fn json_rpc_max_queued_requests_configurable() {
+        assert_eq!(
+            json_rpc_max_queued_requests(None).get(),
+            usize::try_from(DEFAULT_MAX_JSON_RPC_QUEUED_REQUESTS.get()).unwrap()
+        );
+
+        let config = JsonRpcListenConfig {
+            address: "127.0.0.1:0".parse().unwrap(),
+            max_json_rpc_clients: 1,
+            max_json_rpc_subscriptions_per_client: 1,
+            max_json_rpc_chain_head_pinned_blocks: 0,
+            max_json_rpc_expensive_requests_per_second: 0,
+            max_json_rpc_queued_requests: NonZero::<u32>::new(3).unwrap(),
+            max_json_rpc_request_size: 1024,
+            log_json_rpc_requests_durations: false,
+            json_rpc_default_to_finalized_block: false,
+            json_rpc_database_timeout: Duration::from_secs(60),
+        };
+        assert_eq!(json_rpc_max_queued_requests(Some(&config)).get(), 3);
+    }
+
+    #[test]
+    fn finality_engine_of_chains() {
+        let chain_information = |chain_spec_json: &[u8]| {
+            chain_spec::ChainSpec::from_json_bytes(chain_spec_json)
+                .unwrap()
+                .to_chain_information()
+                .unwrap()
+                .0
+        };
+
+        assert_eq!(
+            finality_engine(&chain_information(include_bytes!(
+                "../tests/substrate-node-template.json"
+            ))),
+            Some(json_rpc::methods::FinalityEngine::Grandpa)
+        );
+
+        // Parachains are finalized by their relay chain.
+        assert_eq!(
+            finality_engine(&chain_information(include_bytes!(
+                "../../demo-chain-specs/polkadot-asset-hub.json"
+            ))),
+            None
+        );
+    }
+
+    #[test]
+    fn database_genesis_hash_always_compared() {
+        let directory = std::env::temp_dir().join(format!(
+            "smoldot-full-node-test-database-genesis-hash-always-compared-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+        let database_path = directory.join("database.sqlite");
+
+        let chain_spec = chain_spec::ChainSpec::from_json_bytes(include_bytes!(
+            "../tests/substrate-node-template.json"
+        ))
+        .unwrap();
+        let (chain_information, runtime) =
+            super::build_genesis_chain_information(&chain_spec, None).unwrap();
+
+        // Genesis storage of a different chain.
+        let mut other_items = chain_spec
+            .genesis_storage()
+            .into_genesis_items()
+            .unwrap()
+            .iter()
+            .map(|(key, value)| (key.to_vec(), value.to_vec()))
+            .collect::<std::collections::BTreeMap<_, _>>();
+        other_items.insert(b"other".to_vec(), b"chain".to_vec());
+        let other_storage = GenesisStorageConfig {
+            items: other_items,
+            expected_state_root: None,
+        };
+        let (other_chain_information, other_runtime) =
+            super::build_genesis_chain_information(&chain_spec, Some(&other_storage)).unwrap();
+
+        let database_genesis_hash = smol::block_on(async {
+            let (database, existed) = open_database(
+                &chain_spec,
+                None,
+                chain_information.as_ref(),
+                &runtime,
+                Some(database_path.clone()),
+                2 * 1024 * 1024,
+                true,
+            )
+            .await
+            .unwrap_or_else(|DatabaseGenesisMismatch| panic!());
+            assert!(!existed);
+            drop(database);
+
+            assert!(open_database(
+                &chain_spec,
+                Some(&other_storage),
+                other_chain_information.as_ref(),
+                &other_runtime,
+                Some(database_path.clone()),
+                2 * 1024 * 1024,
+                true,
+            )
+            .await
+            .is_err());
+
+            // The hash of the genesis block is compared even if the database isn't verified.
+            assert!(open_database(
+                &chain_spec,
+                Some(&other_storage),
+                other_chain_information.as_ref(),
+                &other_runtime,
+                Some(database_path.clone()),
+                2 * 1024 * 1024,
+                false,
+            )
+            .await
+            .is_err());
+
+            let (database, existed) = open_database(
+                &chain_spec,
+                None,
+                chain_information.as_ref(),
+                &runtime,
+                Some(database_path.clone()),
+                2 * 1024 * 1024,
+                false,
+            )
+            .await
+            .unwrap_or_else(|DatabaseGenesisMismatch| panic!());
+            assert!(existed);
+            database.block_hash_by_number(0).unwrap().next().unwrap()
+        });
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            database_genesis_hash,
+            chain_information.as_ref().finalized_block_header.hash(4)
+        );
+    }
+
+    #[test]
+    fn json_rpc_default_block_with_unfinalized_best() {
+        let chain_spec_json = include_bytes!("../tests/substrate-node-template.json");
+        let chain_spec = chain_spec::ChainSpec::from_json_bytes(chain_spec_json).unwrap();
+        let (chain_information, runtime) =
+            super::build_genesis_chain_information(&chain_spec, None).unwrap();
+        let genesis_hash = chain_information.as_ref().finalized_block_header.hash(4);
+
+        for default_to_finalized in [false, true] {
+            let directory = std::env::temp_dir().join(format!(
+                "smoldot-full-node-test-json-rpc-default-block-{default_to_finalized}-{}",
+                std::process::id()
+            ));
+            fs::create_dir_all(&directory).unwrap();
+            let database_path = directory.join("database.sqlite");
+
+            smol::block_on(async {
+                // Block 1 becomes the best block of the database but isn't finalized. It has the
+                // same storage as the genesis block.
+                let (database, _) = open_database(
+                    &chain_spec,
+                    None,
+                    chain_information.as_ref(),
+                    &runtime,
+                    Some(database_path.clone()),
+                    2 * 1024 * 1024,
+                    true,
+                )
+                .await
+                .unwrap_or_else(|DatabaseGenesisMismatch| panic!());
+                let block1_hash = test_util::insert_block(
+                    &database,
+                    &genesis_hash,
+                    1,
+                    chain_information.as_ref().finalized_block_header.state_root,
+                );
+                drop(database);
+
+                let client = super::start(super::Config {
+                    chain: super::ChainConfig {
+                        chain_spec: (&chain_spec_json[..]).into(),
+                        additional_bootnodes: Vec::new(),
+                        keystore_memory: vec![],
+                        sqlite_database_path: Some(database_path.clone()),
+                        sqlite_cache_size: 2 * 1024 * 1024,
+                        keystore_path: None,
+                        json_rpc_listen: Some(JsonRpcListenConfig {
+                            address: "127.0.0.1:0".parse().unwrap(),
+                            max_json_rpc_clients: 1,
+                            max_json_rpc_subscriptions_per_client: 1,
+                            max_json_rpc_chain_head_pinned_blocks: 0,
+                            max_json_rpc_expensive_requests_per_second: 0,
+                            max_json_rpc_queued_requests: DEFAULT_MAX_JSON_RPC_QUEUED_REQUESTS,
+                            max_json_rpc_request_size: 1024,
+                            log_json_rpc_requests_durations: false,
+                            json_rpc_default_to_finalized_block: default_to_finalized,
+                            json_rpc_database_timeout: Duration::from_secs(60),
+                        }),
+                        code_substitutes: Default::default(),
+                        blocks_pruning: None,
+                        genesis_storage: None,
+                        verify_database_genesis: true,
+                    },
+                    relay_chain: None,
+                    libp2p_key: Box::new([0; 32]),
+                    listen_addresses: Vec::new(),
+                    tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+                    log_callback: Arc::new(move |_, _| {}),
+                    jaeger_agent: None,
+                })
+                .await
+                .unwrap();
+
+                let expected_hash = if default_to_finalized {
+                    genesis_hash
+                } else {
+                    block1_hash
+                };
+
+                client.send_json_rpc_request(
+                    r#"{"jsonrpc":"2.0","id":1,"method":"chain_getBlockHash","params":[]}"#
+                        .to_owned(),
+                );
+                let response_raw = client.next_json_rpc_response().await;
+                let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+                    .unwrap()
+                    .into_success()
+                    .unwrap();
+                assert_eq!(
+                    serde_json::from_str::<String>(result_json).unwrap(),
+                    format!("0x{}", hex::encode(expected_hash))
+                );
+
+                client.send_json_rpc_request(
+                    r#"{"jsonrpc":"2.0","id":2,"method":"chain_getHeader","params":[]}"#.to_owned(),
+                );
+                let response_raw = client.next_json_rpc_response().await;
+                let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+                    .unwrap()
+                    .into_success()
+                    .unwrap();
+                let header = serde_json::from_str::<serde_json::Value>(result_json).unwrap();
+                if default_to_finalized {
+                    assert_eq!(header["number"], "0x0");
+                } else {
+                    assert_eq!(header["number"], "0x1");
+                    assert_eq!(
+                        header["parentHash"],
+                        format!("0x{}", hex::encode(genesis_hash))
+                    );
+                }
+            });
+
+            fs::remove_dir_all(&directory).unwrap();
         }
     }
 }
@@ -115,6 +115,13 @@ pub struct ChainConfig {
     /// between chains with the same genesis hash.
     pub fork_id: Option<String>,
 
+    /// Legacy protocol id found in the chain specification, if any.
+    ///
+    /// Protocol names are always derived from the genesis hash and the fork id, and never from
+    /// this value. Peers that only support the legacy protocol names can't be connected to. For
+    /// this reason, a warning is printed if this value is `Some`.
+    pub protocol_id: Option<String>,
+
     /// Number of bytes of the block number in the networking protocol.
     pub block_number_bytes: usize,
 
@@ -169,6 +176,9 @@ pub struct NetworkService {
 
     /// Name of all the chains that have been registered, for logging purposes.
     chain_names: hashbrown::HashMap<ChainId, String, fnv::FnvBuildHasher>,
+
+    /// Names of the networking protocols of each chain that has been registered.
+    chain_protocol_names: hashbrown::HashMap<ChainId, Vec<String>, fnv::FnvBuildHasher>,
 }
 
 enum ToBackground {
@@ -178,6 +188,12 @@ enum ToBackground {
         severity: BanSeverity,
         reason: &'static str,
     },
+    ForegroundReportPeer {
+        peer_id: PeerId,
+        chain_id: ChainId,
+        reputation_delta: i32,
+        reason: &'static str,
+    },
     ForegroundAnnounceBlock {
         target: PeerId,
         chain_id: ChainId,
@@ -185,6 +201,11 @@ enum ToBackground {
         is_best: bool,
         result_tx: oneshot::Sender<Result<(), service::QueueNotificationError>>,
     },
+    ForegroundAnnounceTransaction {
+        chain_id: ChainId,
+        transaction: Vec<u8>,
+        result_tx: oneshot::Sender<Vec<PeerId>>,
+    },
     ForegroundSetLocalBestBlock {
         chain_id: ChainId,
         best_hash: [u8; 32],
@@ -225,6 +246,31 @@ enum ToBackground {
     ForegroundGetNumTotalPeers {
         result_tx: oneshot::Sender<usize>,
     },
+    ForegroundSubscribeNumPeers {
+        chain_id: ChainId,
+        result_tx: oneshot::Sender<channel::Receiver<usize>>,
+    },
+    ForegroundAddReservedPeer {
+        chain_id: ChainId,
+        peer_id: PeerId,
+        address: Multiaddr,
+    },
+    ForegroundRemoveReservedPeer {
+        chain_id: ChainId,
+        peer_id: PeerId,
+    },
+    ForegroundGetReservedPeers {
+        chain_id: ChainId,
+        result_tx: oneshot::Sender<Vec<(PeerId, Multiaddr)>>,
+    },
+    ForegroundGetPeersReputation {
+        chain_id: ChainId,
+        result_tx: oneshot::Sender<Vec<(PeerId, i32)>>,
+    },
+    ForegroundGetBestSeenBlock {
+        chain_id: ChainId,
+        result_tx: oneshot::Sender<Option<(u64, [u8; 32])>>,
+    },
 }
 
 struct Inner {
@@ -243,6 +289,10 @@ struct Inner {
     /// Event about to be sent on the senders of [`Inner::event_senders`].
     event_pending_send: Option<Event>,
 
+    /// List of senders to report the number of peers of a chain to.
+    /// See [`NetworkService::subscribe_num_peers`].
+    num_peers_subscriptions: Vec<(ChainId, channel::Sender<usize>)>,
+
     /// Identity of the local node.
     noise_key: service::NoiseKey,
 
@@ -342,6 +392,23 @@ struct Chain {
     /// Maximum number of peers that have gossip links open but without having slots attributed
     /// to them.
     max_in_peers: usize,
+
+    /// List of peers that have been added through [`NetworkService::add_reserved_peer`], and
+    /// the address they have been added with.
+    ///
+    /// This list is tracked separately from the peers known to the peering strategy, as the
+    /// latter can forget about peers and addresses at any moment.
+    reserved_peers: hashbrown::HashMap<PeerId, Multiaddr, fnv::FnvBuildHasher>,
+
+    /// Reputation of the peers that have been reported through [`NetworkService::report_peer`],
+    /// and when it was last modified. Peers whose reputation is `0` aren't in this list.
+    ///
+    /// Entries are removed when the peer disconnects or is banned.
+    peers_reputation: hashbrown::HashMap<PeerId, (i32, Instant), fnv::FnvBuildHasher>,
+
+    /// Number and hash of the best block of each peer we have a gossip link with, as reported in
+    /// its handshake and later block announces. See [`NetworkService::best_seen_block`].
+    peers_best_blocks: hashbrown::HashMap<PeerId, (u64, [u8; 32]), fnv::FnvBuildHasher>,
 }
 
 /// Severity of a ban. See [`NetworkService::ban_and_disconnect`].
@@ -351,6 +418,14 @@ pub enum BanSeverity {
     High,
 }
 
+/// When the reputation of a peer reaches this value or below, it is banned with a
+/// [`BanSeverity::High`] and its reputation reset to `0`. See [`NetworkService::report_peer`].
+pub const BAN_REPUTATION_THRESHOLD: i32 = -100;
+
+/// Duration after which the reputation of a peer that hasn't been reported again has been
+/// halved. See [`NetworkService::report_peer`].
+pub const REPUTATION_HALF_LIFE: Duration = Duration::from_secs(60);
+
 impl NetworkService {
     /// Initializes the network service with the given configuration.
     pub async fn new(
@@ -383,6 +458,8 @@ impl NetworkService {
 
         let mut chain_names =
             hashbrown::HashMap::with_capacity_and_hasher(config.chains.len(), Default::default());
+        let mut chain_protocol_names =
+            hashbrown::HashMap::with_capacity_and_hasher(config.chains.len(), Default::default());
 
         for chain in config.chains {
             let chain_id = network
@@ -407,6 +484,9 @@ impl NetworkService {
                         database: chain.database,
                         max_in_peers: chain.max_in_peers,
                         max_slots: chain.max_slots,
+                        reserved_peers: hashbrown::HashMap::default(),
+                        peers_reputation: hashbrown::HashMap::default(),
+                        peers_best_blocks: hashbrown::HashMap::default(),
                     },
                 })
                 .unwrap(); // TODO: don't unwrap?
@@ -418,7 +498,25 @@ impl NetworkService {
                 peering_strategy.insert_address(&peer_id, addr.into_bytes(), usize::MAX);
             }
 
+            let protocol_names = network.chain_protocol_names(chain_id).collect::<Vec<_>>();
+
+            // The `protocolId` field of chain specifications is deprecated, and peers that only
+            // support the protocol names derived from it can't be connected to. Print a warning
+            // indicating the protocol names that are used instead.
+            if let Some(protocol_id) = &chain.protocol_id {
+                config.log_callback.log(
+                    LogLevel::Warn,
+                    format!(
+                        "chain-spec-has-protocol-id; chain={}; protocol_id={}; protocols={}",
+                        chain.log_name,
+                        protocol_id,
+                        protocol_names.join(",")
+                    ),
+                );
+            }
+
             chain_names.insert(chain_id, chain.log_name);
+            chain_protocol_names.insert(chain_id, protocol_names);
         }
 
         let (to_background_tx, to_background_rx) = channel::bounded(16);
@@ -496,6 +594,7 @@ impl NetworkService {
             identify_agent_version: config.identify_agent_version,
             event_senders: either::Left(event_senders),
             event_pending_send: None,
+            num_peers_subscriptions: Vec::new(),
             num_pending_out_attempts: 0,
             to_background_rx: Box::pin(to_background_rx),
             from_connections_rx: Box::pin(from_connections_rx),
@@ -531,6 +630,7 @@ impl NetworkService {
         let network_service = Arc::new(NetworkService {
             local_peer_id,
             chain_names,
+            chain_protocol_names,
             _jaeger_service: config.jaeger_service,
             to_background_tx: Mutex::new(to_background_tx),
         });
@@ -558,6 +658,18 @@ impl NetworkService {
         &self.local_peer_id
     }
 
+    /// Returns the names of the networking protocols of the given chain. These names are
+    /// derived from the genesis hash and fork id of the chain. Peers that use different names
+    /// can't be connected to.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`ChainId`] is invalid.
+    ///
+    pub fn protocol_names(&self, chain_id: ChainId) -> &[String] {
+        &self.chain_protocol_names[&chain_id]
+    }
+
     /// Returns the number of connections, both handshaking or established, both incoming and
     /// outgoing.
     pub async fn num_connections(&self) -> usize {
@@ -604,6 +716,79 @@ impl NetworkService {
         result_rx.await.unwrap()
     }
 
+    /// Subscribes to the number of peers we have a substream with.
+    ///
+    /// The current number of peers is immediately available on the returned receiver, then a
+    /// new value is sent whenever a peer connects or disconnects. Only the latest value is kept
+    /// in the channel: if the receiver isn't polled, intermediary values are discarded.
+    pub async fn subscribe_num_peers(&self, chain_id: ChainId) -> channel::Receiver<usize> {
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundSubscribeNumPeers {
+                chain_id,
+                result_tx,
+            })
+            .await;
+
+        result_rx.await.unwrap()
+    }
+
+    /// Adds a peer to the list of reserved peers of the given chain, and inserts its address in
+    /// the list of known addresses.
+    ///
+    /// Reserved peers are always assigned a slot, unless they are temporarily banned, and are
+    /// always accepted when they open a gossip link. They don't count towards the limits
+    /// passed through [`ChainConfig::max_slots`] and [`ChainConfig::max_in_peers`].
+    ///
+    /// If the peer is already reserved, its address is replaced with the new one.
+    pub async fn add_reserved_peer(&self, chain_id: ChainId, peer_id: PeerId, address: Multiaddr) {
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundAddReservedPeer {
+                chain_id,
+                peer_id,
+                address,
+            })
+            .await;
+    }
+
+    /// Removes a peer from the list of reserved peers of the given chain. Has no effect if the
+    /// peer isn't reserved.
+    ///
+    /// The peer and its address remain known to the networking, and the peer isn't disconnected.
+    pub async fn remove_reserved_peer(&self, chain_id: ChainId, peer_id: PeerId) {
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundRemoveReservedPeer { chain_id, peer_id })
+            .await;
+    }
+
+    /// Returns the list of reserved peers of the given chain and their address, in no
+    /// particular order. Independent of whether these peers are currently connected.
+    pub async fn reserved_peers(&self, chain_id: ChainId) -> Vec<(PeerId, Multiaddr)> {
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundGetReservedPeers {
+                chain_id,
+                result_tx,
+            })
+            .await;
+
+        result_rx.await.unwrap()
+    }
+
     pub async fn set_local_best_block(
         &self,
         chain_id: ChainId,
@@ -653,6 +838,77 @@ impl NetworkService {
             .await;
     }
 
+    /// Modifies the reputation of the given peer by adding `reputation_delta` to it.
+    ///
+    /// The reputation of a peer decays towards `0` over time, and is halved every
+    /// [`REPUTATION_HALF_LIFE`]. It is reset when the peer disconnects.
+    ///
+    /// If the reputation of the peer reaches [`BAN_REPUTATION_THRESHOLD`] or below, the peer is
+    /// disconnected and banned in the same way as [`NetworkService::ban_and_disconnect`] with a
+    /// [`BanSeverity::High`], and its reputation is reset.
+    ///
+    /// `reason` is a human-readable string printed in the logs.
+    pub async fn report_peer(
+        &self,
+        peer_id: PeerId,
+        chain_id: ChainId,
+        reputation_delta: i32,
+        reason: &'static str,
+    ) {
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundReportPeer {
+                peer_id,
+                chain_id,
+                reputation_delta,
+                reason,
+            })
+            .await;
+    }
+
+    /// Returns the list of peers whose reputation isn't `0`, alongside with their reputation.
+    ///
+    /// See [`NetworkService::report_peer`].
+    pub async fn peers_reputation(&self, chain_id: ChainId) -> Vec<(PeerId, i32)> {
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundGetPeersReputation {
+                chain_id,
+                result_tx,
+            })
+            .await;
+
+        result_rx.await.unwrap()
+    }
+
+    /// Returns the number and hash of the highest best block among the peers of the given chain
+    /// we have a gossip link with, or `None` if there isn't any such peer.
+    ///
+    /// The best block of each peer is the one reported when the gossip link opens, then updated
+    /// whenever the peer announces a new best block. Peers are no longer taken into account
+    /// once their gossip link closes.
+    pub async fn best_seen_block(&self, chain_id: ChainId) -> Option<(u64, [u8; 32])> {
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundGetBestSeenBlock {
+                chain_id,
+                result_tx,
+            })
+            .await;
+
+        result_rx.await.unwrap()
+    }
+
     pub async fn send_block_announce(
         self: Arc<Self>,
         target: PeerId,
@@ -678,6 +934,32 @@ impl NetworkService {
         result_rx.await.unwrap()
     }
 
+    /// Sends the given SCALE-encoded transaction to all the peers we are gossiping with on the
+    /// given chain.
+    ///
+    /// Returns the list of peers the transaction has been sent to, which can be empty. The
+    /// remotes don't confirm that they have received the transaction.
+    pub async fn announce_transaction(
+        self: Arc<Self>,
+        chain_id: ChainId,
+        transaction: Vec<u8>,
+    ) -> Vec<PeerId> {
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundAnnounceTransaction {
+                chain_id,
+                transaction,
+                result_tx,
+            })
+            .await;
+
+        result_rx.await.unwrap()
+    }
+
     /// Sends a blocks request to the given peer.
     // TODO: more docs
     // TODO: proper error type
@@ -911,10 +1193,43 @@ async fn background_task(mut inner: Inner) {
                         let mut earlier_unban = None;
 
                         for chain_id in network.chains().collect::<Vec<_>>() {
-                            if network.gossip_desired_num(
-                                chain_id,
-                                service::GossipKind::ConsensusTransactions,
-                            ) >= network[chain_id].max_slots
+                            // Reserved peers are assigned a slot whenever they don't have one
+                            // and aren't banned, and don't count towards `max_slots`.
+                            let now = Instant::now();
+                            for peer_id in network[chain_id].reserved_peers.keys() {
+                                if network
+                                    .gossip_desired_iter(
+                                        chain_id,
+                                        service::GossipKind::ConsensusTransactions,
+                                    )
+                                    .any(|p| p == peer_id)
+                                {
+                                    continue;
+                                }
+
+                                if let Some(next_unban) =
+                                    peering_strategy.ban_expiration(&chain_id, peer_id, &now)
+                                {
+                                    if earlier_unban.as_ref().map_or(true, |b| b > next_unban) {
+                                        earlier_unban = Some(next_unban.clone());
+                                    }
+                                    continue;
+                                }
+
+                                break 'search WakeUpReason::CanAssignSlot(
+                                    peer_id.clone(),
+                                    chain_id,
+                                );
+                            }
+
+                            if network
+                                .gossip_desired_iter(
+                                    chain_id,
+                                    service::GossipKind::ConsensusTransactions,
+                                )
+                                .filter(|p| !network[chain_id].reserved_peers.contains_key(*p))
+                                .count()
+                                >= network[chain_id].max_slots
                             {
                                 continue;
                             }
@@ -1103,52 +1418,38 @@ async fn background_task(mut inner: Inner) {
                 severity,
                 reason,
             }) => {
-                // Note that peer doesn't necessarily have an out slot.
-                inner.peering_strategy.unassign_slot_and_ban(
-                    &chain_id,
-                    &peer_id,
-                    Instant::now()
-                        + Duration::from_secs(match severity {
-                            BanSeverity::Low => 10,
-                            BanSeverity::High => 40,
-                        }),
-                );
-                if inner.network.gossip_remove_desired(
-                    chain_id,
-                    &peer_id,
-                    service::GossipKind::ConsensusTransactions,
-                ) {
-                    inner.log_callback.log(
-                        LogLevel::Debug,
-                        format!(
-                            "slot-unassigned; peer_id={}; chain={}; reason=user-ban; user-reason={}",
-                            peer_id, inner.network[chain_id].log_name, reason
-                        ),
-                    );
-                }
+                disconnect_and_ban(&mut inner, chain_id, peer_id, severity, reason);
+            }
 
-                if inner.network.gossip_is_connected(
-                    chain_id,
+            WakeUpReason::Message(ToBackground::ForegroundReportPeer {
+                peer_id,
+                chain_id,
+                reputation_delta,
+                reason,
+            }) => {
+                let reputation = apply_reputation_change(
+                    &mut inner.network[chain_id].peers_reputation,
                     &peer_id,
-                    service::GossipKind::ConsensusTransactions,
-                ) {
-                    let _close_result = inner.network.gossip_close(
-                        chain_id,
-                        &peer_id,
-                        service::GossipKind::ConsensusTransactions,
-                    );
-                    debug_assert!(_close_result.is_ok());
+                    reputation_delta,
+                    Instant::now(),
+                );
 
-                    inner.log_callback.log(
-                        LogLevel::Debug,
-                        format!(
-                            "chain-disconnected; peer_id={}; chain={}",
-                            peer_id, inner.network[chain_id].log_name
-                        ),
-                    );
+                inner.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "peer-reported; peer_id={}; chain={}; delta={}; reputation={}; reason={}",
+                        peer_id,
+                        inner.network[chain_id].log_name,
+                        reputation_delta,
+                        reputation,
+                        reason
+                    ),
+                );
 
-                    debug_assert!(inner.event_pending_send.is_none());
-                    inner.event_pending_send = Some(Event::Disconnected { chain_id, peer_id });
+                if reputation <= BAN_REPUTATION_THRESHOLD {
+                    // The reputation is reset by `disconnect_and_ban`, so that the peer starts
+                    // afresh once the ban is over.
+                    disconnect_and_ban(&mut inner, chain_id, peer_id, BanSeverity::High, reason);
                 }
             }
 
@@ -1166,6 +1467,31 @@ async fn background_task(mut inner: Inner) {
                     is_best,
                 ));
             }
+            WakeUpReason::Message(ToBackground::ForegroundAnnounceTransaction {
+                chain_id,
+                transaction,
+                result_tx,
+            }) => {
+                // TODO: keep track of which peer knows about which transaction, and don't send it again
+                let peers_to_send = inner
+                    .network
+                    .gossip_connected_peers(chain_id, service::GossipKind::ConsensusTransactions)
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                // Peers whose queue is full are silently skipped.
+                let peers_sent = peers_to_send
+                    .into_iter()
+                    .filter(|peer| {
+                        inner
+                            .network
+                            .gossip_send_transaction(peer, chain_id, &transaction)
+                            .is_ok()
+                    })
+                    .collect::<Vec<_>>();
+
+                let _ = result_tx.send(peers_sent);
+            }
             WakeUpReason::Message(ToBackground::ForegroundSetLocalBestBlock {
                 chain_id,
                 best_hash,
@@ -1398,6 +1724,112 @@ async fn background_task(mut inner: Inner) {
                     .sum();
                 let _ = result_tx.send(total);
             }
+            WakeUpReason::Message(ToBackground::ForegroundSubscribeNumPeers {
+                chain_id,
+                result_tx,
+            }) => {
+                let (tx, rx) = channel::bounded(1);
+                let num_peers = inner
+                    .network
+                    .gossip_connected_peers(chain_id, service::GossipKind::ConsensusTransactions)
+                    .count();
+                let _ = tx.force_send(num_peers);
+                if result_tx.send(rx).is_ok() {
+                    inner.num_peers_subscriptions.push((chain_id, tx));
+                }
+            }
+            WakeUpReason::Message(ToBackground::ForegroundAddReservedPeer {
+                chain_id,
+                peer_id,
+                address,
+            }) => {
+                // Note that we must call this function before `insert_address`, as documented
+                // in `basic_peering_strategy`.
+                inner
+                    .peering_strategy
+                    .insert_chain_peer(chain_id, peer_id.clone(), usize::MAX);
+                inner.peering_strategy.insert_address(
+                    &peer_id,
+                    address.clone().into_bytes(),
+                    usize::MAX,
+                );
+                inner.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "reserved-peer-added; peer_id={}; chain={}; address={}",
+                        peer_id, inner.network[chain_id].log_name, address
+                    ),
+                );
+                inner.network[chain_id]
+                    .reserved_peers
+                    .insert(peer_id, address);
+            }
+            WakeUpReason::Message(ToBackground::ForegroundRemoveReservedPeer {
+                chain_id,
+                peer_id,
+            }) => {
+                if inner.network[chain_id]
+                    .reserved_peers
+                    .remove(&peer_id)
+                    .is_some()
+                {
+                    inner.log_callback.log(
+                        LogLevel::Debug,
+                        format!(
+                            "reserved-peer-removed; peer_id={}; chain={}",
+                            peer_id, inner.network[chain_id].log_name
+                        ),
+                    );
+                }
+            }
+            WakeUpReason::Message(ToBackground::ForegroundGetReservedPeers {
+                chain_id,
+                result_tx,
+            }) => {
+                let _ = result_tx.send(
+                    inner.network[chain_id]
+                        .reserved_peers
+                        .iter()
+                        .map(|(peer_id, address)| (peer_id.clone(), address.clone()))
+                        .collect(),
+                );
+            }
+
+            WakeUpReason::Message(ToBackground::ForegroundGetPeersReputation {
+                chain_id,
+                result_tx,
+            }) => {
+                let now = Instant::now();
+                let _ = result_tx.send(
+                    inner.network[chain_id]
+                        .peers_reputation
+                        .iter()
+                        .map(|(peer_id, (reputation, last_update))| {
+                            (
+                                peer_id.clone(),
+                                decayed_reputation(
+                                    *reputation,
+                                    now.saturating_duration_since(*last_update),
+                                ),
+                            )
+                        })
+                        .filter(|(_, reputation)| *reputation != 0)
+                        .collect(),
+                );
+            }
+
+            WakeUpReason::Message(ToBackground::ForegroundGetBestSeenBlock {
+                chain_id,
+                result_tx,
+            }) => {
+                let _ = result_tx.send(
+                    inner.network[chain_id]
+                        .peers_best_blocks
+                        .values()
+                        .max_by_key(|(number, _)| *number)
+                        .copied(),
+                );
+            }
 
             WakeUpReason::EventSendersReady => {
                 // Dispatch the pending event, if any, to the various senders.
@@ -1408,6 +1840,26 @@ async fn background_task(mut inner: Inner) {
                 };
 
                 if let Some(event_to_dispatch) = inner.event_pending_send.take() {
+                    if let Event::Connected { chain_id, .. }
+                    | Event::Disconnected { chain_id, .. } = event_to_dispatch
+                    {
+                        // The channels have a capacity of one, and the value in the channel is
+                        // overwritten, so that subscribers always receive the latest number.
+                        // Subscribers whose channel is closed are removed.
+                        let num_peers = inner
+                            .network
+                            .gossip_connected_peers(
+                                chain_id,
+                                service::GossipKind::ConsensusTransactions,
+                            )
+                            .count();
+                        inner
+                            .num_peers_subscriptions
+                            .retain(|(sub_chain_id, sender)| {
+                                *sub_chain_id != chain_id || sender.force_send(num_peers).is_ok()
+                            });
+                    }
+
                     let mut event_senders = mem::take(event_senders);
                     inner.event_senders = either::Right(Box::pin(async move {
                         // Elements in `event_senders` are removed one by one and inserted
@@ -1589,6 +2041,12 @@ async fn background_task(mut inner: Inner) {
                             peer_id, inner.network[chain_id].log_name, HashDisplay(&header_hash), decoded_header.number, decoded.is_best
                         ));
 
+                        if decoded.is_best {
+                            inner.network[chain_id]
+                                .peers_best_blocks
+                                .insert(peer_id.clone(), (decoded_header.number, header_hash));
+                        }
+
                         debug_assert!(inner.event_pending_send.is_none());
                         inner.event_pending_send = Some(Event::BlockAnnounce {
                             chain_id,
@@ -1626,6 +2084,7 @@ async fn background_task(mut inner: Inner) {
                             &peer_id,
                             service::GossipKind::ConsensusTransactions,
                         ); // TODO: what is the return value?
+                        inner.network[chain_id].peers_best_blocks.remove(&peer_id);
                     }
                 }
             }
@@ -1646,6 +2105,10 @@ async fn background_task(mut inner: Inner) {
                         HashDisplay(&best_hash),
                     ),
                 );
+                inner.network[chain_id]
+                    .peers_best_blocks
+                    .insert(peer_id.clone(), (best_number, best_hash));
+
                 debug_assert!(inner.event_pending_send.is_none());
                 inner.event_pending_send = Some(Event::Connected {
                     peer_id,
@@ -1688,6 +2151,9 @@ async fn background_task(mut inner: Inner) {
                     );
                 }
 
+                inner.network[chain_id].peers_best_blocks.remove(&peer_id);
+                inner.network[chain_id].peers_reputation.remove(&peer_id);
+
                 debug_assert!(inner.event_pending_send.is_none());
                 inner.event_pending_send = Some(Event::Disconnected { chain_id, peer_id });
             }
@@ -1721,7 +2187,13 @@ async fn background_task(mut inner: Inner) {
                     );
                 }
 
-                if let service::GossipConnectError::GenesisMismatch { .. } = error {
+                // Reserved peers are never removed from the chain, otherwise they would
+                // immediately be assigned a slot again.
+                if matches!(error, service::GossipConnectError::GenesisMismatch { .. })
+                    && !inner.network[chain_id]
+                        .reserved_peers
+                        .contains_key(&peer_id)
+                {
                     inner
                         .peering_strategy
                         .unassign_slot_and_remove_chain_peer(&chain_id, &peer_id);
@@ -1743,11 +2215,15 @@ async fn background_task(mut inner: Inner) {
                 // can't happen if we are already opening an out slot, which we do
                 // immediately.
                 // TODO: add debug_assert! ^
-                if inner
-                    .network
-                    .opened_gossip_undesired_by_chain(chain_id)
-                    .count()
-                    < inner.network[chain_id].max_in_peers
+                // Reserved peers are always accepted, and don't count towards `max_in_peers`.
+                let reserved_peers = &inner.network[chain_id].reserved_peers;
+                if reserved_peers.contains_key(&peer_id)
+                    || inner
+                        .network
+                        .opened_gossip_undesired_by_chain(chain_id)
+                        .filter(|(p, _)| !reserved_peers.contains_key(*p))
+                        .count()
+                        < inner.network[chain_id].max_in_peers
                 {
                     inner
                         .network
@@ -2114,6 +2590,16 @@ async fn background_task(mut inner: Inner) {
             WakeUpReason::CanAssignSlot(peer_id, chain_id) => {
                 inner.peering_strategy.assign_slot(&chain_id, &peer_id);
 
+                // The address of a reserved peer might have been removed from the peering
+                // strategy in the meanwhile, in which case we insert it back.
+                if let Some(address) = inner.network[chain_id].reserved_peers.get(&peer_id) {
+                    inner.peering_strategy.insert_address(
+                        &peer_id,
+                        address.clone().into_bytes(),
+                        usize::MAX,
+                    );
+                }
+
                 inner.log_callback.log(
                     LogLevel::Debug,
                     format!(
@@ -2256,6 +2742,102 @@ async fn background_task(mut inner: Inner) {
     }
 }
 
+/// Adds `reputation_delta` to the reputation of the given peer in `peers_reputation`, after
+/// having applied the decay since its last modification. Returns the new reputation.
+///
+/// The entry of the peer is removed if its new reputation is `0`.
+fn apply_reputation_change(
+    peers_reputation: &mut hashbrown::HashMap<PeerId, (i32, Instant), fnv::FnvBuildHasher>,
+    peer_id: &PeerId,
+    reputation_delta: i32,
+    now: Instant,
+) -> i32 {
+    let previous = peers_reputation
+        .get(peer_id)
+        .map_or(0, |(reputation, last_update)| {
+            decayed_reputation(*reputation, now.saturating_duration_since(*last_update))
+        });
+    let reputation = previous.saturating_add(reputation_delta);
+
+    if reputation == 0 {
+        peers_reputation.remove(peer_id);
+    } else {
+        peers_reputation.insert(peer_id.clone(), (reputation, now));
+    }
+
+    reputation
+}
+
+/// Returns the value of a reputation after `elapsed` has passed. See [`REPUTATION_HALF_LIFE`].
+fn decayed_reputation(reputation: i32, elapsed: Duration) -> i32 {
+    let factor = 0.5f64.powf(elapsed.as_secs_f64() / REPUTATION_HALF_LIFE.as_secs_f64());
+    // Truncating towards `0` guarantees that the reputation eventually reaches `0`.
+    (f64::from(reputation) * factor) as i32
+}
+
+/// Unassigns the slot of the given peer, bans it for a duration that depends on `severity`, and
+/// closes the gossip link with it if any.
+///
+/// See [`NetworkService::ban_and_disconnect`].
+fn disconnect_and_ban(
+    inner: &mut Inner,
+    chain_id: ChainId,
+    peer_id: PeerId,
+    severity: BanSeverity,
+    reason: &str,
+) {
+    inner.network[chain_id].peers_reputation.remove(&peer_id);
+    inner.network[chain_id].peers_best_blocks.remove(&peer_id);
+
+    // Note that peer doesn't necessarily have an out slot.
+    inner.peering_strategy.unassign_slot_and_ban(
+        &chain_id,
+        &peer_id,
+        Instant::now()
+            + Duration::from_secs(match severity {
+                BanSeverity::Low => 10,
+                BanSeverity::High => 40,
+            }),
+    );
+    if inner.network.gossip_remove_desired(
+        chain_id,
+        &peer_id,
+        service::GossipKind::ConsensusTransactions,
+    ) {
+        inner.log_callback.log(
+            LogLevel::Debug,
+            format!(
+                "slot-unassigned; peer_id={}; chain={}; reason=user-ban; user-reason={}",
+                peer_id, inner.network[chain_id].log_name, reason
+            ),
+        );
+    }
+
+    if inner.network.gossip_is_connected(
+        chain_id,
+        &peer_id,
+        service::GossipKind::ConsensusTransactions,
+    ) {
+        let _close_result = inner.network.gossip_close(
+            chain_id,
+            &peer_id,
+            service::GossipKind::ConsensusTransactions,
+        );
+        debug_assert!(_close_result.is_ok());
+
+        inner.log_callback.log(
+            LogLevel::Debug,
+            format!(
+                "chain-disconnected; peer_id={}; chain={}",
+                peer_id, inner.network[chain_id].log_name
+            ),
+        );
+
+        debug_assert!(inner.event_pending_send.is_none());
+        inner.event_pending_send = Some(Event::Disconnected { chain_id, peer_id });
+    }
+}
+
 /// Builds the response to a block request by reading from the given database.
 async fn blocks_request_response(
     database: &database_thread::DatabaseThread,
@@ -2334,3 +2916,62 @@ async fn blocks_request_response(
         })
         .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_reputation_change, BAN_REPUTATION_THRESHOLD, REPUTATION_HALF_LIFE};
+    use smoldot::libp2p::peer_id::{PeerId, PublicKey};
+    use std::time::Instant;
+
+    #[test]
+    fn reputation_reaches_ban_threshold() {
+        let peer_id = PeerId::from_public_key(&PublicKey::Ed25519([0; 32]));
+        let mut peers_reputation = hashbrown::HashMap::default();
+        let now = Instant::now();
+
+        let delta = BAN_REPUTATION_THRESHOLD / 2;
+        assert!(
+            apply_reputation_change(&mut peers_reputation, &peer_id, delta, now)
+                > BAN_REPUTATION_THRESHOLD
+        );
+        assert!(
+            apply_reputation_change(&mut peers_reputation, &peer_id, delta, now)
+                <= BAN_REPUTATION_THRESHOLD
+        );
+    }
+
+    #[test]
+    fn reputation_decays() {
+        let peer_id = PeerId::from_public_key(&PublicKey::Ed25519([0; 32]));
+        let mut peers_reputation = hashbrown::HashMap::default();
+        let now = Instant::now();
+
+        // The same two reports spread over time don't lead to a ban.
+        let delta = BAN_REPUTATION_THRESHOLD / 2;
+        assert_eq!(
+            apply_reputation_change(&mut peers_reputation, &peer_id, delta, now),
+            delta
+        );
+        assert_eq!(
+            apply_reputation_change(
+                &mut peers_reputation,
+                &peer_id,
+                delta,
+                now + REPUTATION_HALF_LIFE
+            ),
+            delta / 2 + delta
+        );
+
+        // The entry is removed once the reputation is back to `0`.
+        assert_eq!(
+            apply_reputation_change(
+                &mut peers_reputation,
+                &peer_id,
+                0,
+                now + REPUTATION_HALF_LIFE * 100
+            ),
+            0
+        );
+        assert!(peers_reputation.is_empty());
+    }
+}
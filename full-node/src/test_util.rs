@@ -0,0 +1,408 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Helpers shared between the tests of the various modules of this crate.
+
+#![cfg(test)]
+
+use crate::{
+    consensus_service, database_thread, jaeger_service, json_rpc_service, network_service,
+};
+use smoldot::{
+    author, chain, chain_spec,
+    database::full_sqlite,
+    executor::{host, runtime_call},
+    header,
+    identity::keystore,
+    libp2p::connection,
+    trie,
+    verify::inherents,
+};
+use std::{collections::BTreeMap, iter, num::NonZero, sync::Arc, time::Duration};
+
+/// Opens an empty in-memory database and initializes it with a genesis block whose state root
+/// is `[1; 32]`. No storage item is inserted.
+///
+/// Returns the database and the hash of the genesis block.
+pub fn memory_database() -> (full_sqlite::SqliteFullDatabase, [u8; 32]) {
+    let full_sqlite::DatabaseOpen::Empty(empty_db) = full_sqlite::open(full_sqlite::Config {
+        block_number_bytes: 4,
+        cache_size: 2 * 1024 * 1024,
+        ty: full_sqlite::ConfigTy::Memory,
+    })
+    .unwrap() else {
+        panic!()
+    };
+
+    let genesis_header = block_header(&[0; 32], 0, &[1; 32]);
+    let genesis_hash = header::hash_from_scale_encoded_header(&genesis_header);
+    let database = empty_db
+        .initialize(&genesis_header, iter::empty(), None)
+        .unwrap();
+    (database, genesis_hash)
+}
+
+/// Builds the SCALE-encoded header of a block with the given parent hash, number, and state
+/// root, and an empty digest.
+pub fn block_header(parent_hash: &[u8; 32], number: u64, state_root: &[u8; 32]) -> Vec<u8> {
+    header::HeaderRef {
+        number,
+        extrinsics_root: &[0; 32],
+        parent_hash,
+        state_root,
+        digest: header::DigestRef::empty(),
+    }
+    .scale_encoding_vec(4)
+}
+
+/// Inserts in the database a block with an empty body whose header is built with
+/// [`block_header`], and makes it the new best block.
+///
+/// Returns the hash of the block.
+pub fn insert_block(
+    database: &full_sqlite::SqliteFullDatabase,
+    parent_hash: &[u8; 32],
+    number: u64,
+    state_root: &[u8; 32],
+) -> [u8; 32] {
+    let header = block_header(parent_hash, number, state_root);
+    database
+        .insert(&header, true, iter::empty::<Vec<u8>>())
+        .unwrap();
+    header::hash_from_scale_encoded_header(&header)
+}
+
+/// Inserts in the database a block with an empty body and the given storage, and makes it the
+/// new best block. The header of the block contains a
+/// [`header::DigestItemRef::RuntimeEnvironmentUpdated`] item if `runtime_environment_updated`
+/// is `true`.
+///
+/// Returns the hash of the block.
+pub fn insert_block_with_storage(
+    database: &full_sqlite::SqliteFullDatabase,
+    parent_hash: &[u8; 32],
+    number: u64,
+    storage: &BTreeMap<Vec<u8>, Vec<u8>>,
+    runtime_environment_updated: bool,
+) -> [u8; 32] {
+    let state_root = trie::trie_root(
+        trie::TrieEntryVersion::V0,
+        trie::HashFunction::Blake2,
+        storage.iter(),
+    );
+    let digest = if runtime_environment_updated {
+        vec![header::DigestItem::RuntimeEnvironmentUpdated]
+    } else {
+        Vec::new()
+    };
+
+    let header = header::HeaderRef {
+        number,
+        extrinsics_root: &[0; 32],
+        parent_hash,
+        state_root: &state_root,
+        digest: header::DigestRef::from_slice(&digest).unwrap(),
+    }
+    .scale_encoding_vec(4);
+
+    database
+        .insert(&header, true, iter::empty::<Vec<u8>>())
+        .unwrap();
+    database
+        .insert_trie_nodes(
+            crate::storage_trie_nodes(storage.iter().map(|(k, v)| (&k[..], &v[..])), 0).into_iter(),
+            0,
+        )
+        .unwrap();
+    header::hash_from_scale_encoded_header(&header)
+}
+
+/// Opens an in-memory database containing the genesis block of the given chain specification
+/// and its storage.
+///
+/// Returns the database, the hash of the genesis block, the runtime of the genesis block, and
+/// the Aura slot duration of the chain.
+pub async fn chain_spec_database(
+    chain_spec_json: &[u8],
+) -> (
+    database_thread::DatabaseThread,
+    [u8; 32],
+    host::HostVmPrototype,
+    u64,
+) {
+    let chain_spec = chain_spec::ChainSpec::from_json_bytes(chain_spec_json).unwrap();
+    let (chain_information, genesis_runtime) = chain_spec.to_chain_information().unwrap();
+    let genesis_hash = chain_information
+        .as_ref()
+        .finalized_block_header
+        .hash(chain_spec.block_number_bytes().into());
+    let chain::chain_information::ChainInformationConsensusRef::Aura { slot_duration, .. } =
+        chain_information.as_ref().consensus
+    else {
+        panic!()
+    };
+
+    let (database, _) = crate::open_database(
+        &chain_spec,
+        None,
+        chain_information.as_ref(),
+        &genesis_runtime,
+        None,
+        2 * 1024 * 1024,
+        true,
+    )
+    .await
+    .unwrap_or_else(|_| panic!());
+
+    (
+        database_thread::DatabaseThread::from(database),
+        genesis_hash,
+        genesis_runtime,
+        slot_duration.get(),
+    )
+}
+
+/// Builds a block containing only the inherent extrinsics on top of the given parent, whose
+/// storage must be found in the database.
+///
+/// The block uses an Aura pre-runtime digest with the given slot, and a timestamp corresponding
+/// to the start of this slot. No seal is added to the header.
+///
+/// Returns the SCALE-encoded header and the body of the block.
+pub async fn build_aura_block(
+    database: &database_thread::DatabaseThread,
+    parent_hash: &[u8; 32],
+    parent_number: u64,
+    parent_runtime: host::HostVmPrototype,
+    slot_duration: u64,
+    slot_number: u64,
+) -> (Vec<u8>, Vec<Vec<u8>>) {
+    let parent_hash = *parent_hash;
+
+    let mut builder = author::runtime::build_block(author::runtime::Config {
+        block_number_bytes: 4,
+        parent_hash: &parent_hash,
+        parent_number,
+        parent_runtime,
+        consensus_digest_log_item: author::runtime::ConfigPreRuntime::Aura(header::AuraPreDigest {
+            slot_number,
+        }),
+        block_body_capacity: 0,
+        max_log_level: 0,
+        calculate_trie_changes: false,
+    });
+
+    loop {
+        match builder {
+            author::runtime::BlockBuild::Finished(Ok(success)) => {
+                return (success.scale_encoded_header, success.body)
+            }
+            author::runtime::BlockBuild::Finished(Err((error, _))) => panic!("{error}"),
+            author::runtime::BlockBuild::InherentExtrinsics(req) => {
+                builder = req.inject_inherents(inherents::InherentData {
+                    timestamp: slot_number * slot_duration,
+                });
+            }
+            author::runtime::BlockBuild::ApplyExtrinsic(req) => builder = req.finish(),
+            author::runtime::BlockBuild::ApplyExtrinsicResult { .. } => unreachable!(),
+            author::runtime::BlockBuild::StorageGet(req) => {
+                assert!(req.child_trie().is_none());
+                let key = trie::bytes_to_nibbles(req.key().as_ref().iter().copied())
+                    .map(u8::from)
+                    .collect::<Vec<_>>();
+                let value = database
+                    .with_database(move |db| {
+                        db.block_storage_get(
+                            &parent_hash,
+                            iter::empty::<iter::Empty<_>>(),
+                            key.into_iter(),
+                        )
+                    })
+                    .await
+                    .unwrap();
+                builder = req.inject_value(value.as_ref().map(|(value, version)| {
+                    (
+                        iter::once(&value[..]),
+                        runtime_call::TrieEntryVersion::try_from(*version).unwrap(),
+                    )
+                }));
+            }
+            author::runtime::BlockBuild::ClosestDescendantMerkleValue(req) => {
+                builder = req.resume_unknown();
+            }
+            author::runtime::BlockBuild::NextKey(req) => {
+                assert!(req.child_trie().is_none());
+                let key = req
+                    .key()
+                    .map(u8::from)
+                    .chain(if req.or_equal() { None } else { Some(0u8) })
+                    .collect::<Vec<_>>();
+                let prefix = req.prefix().map(u8::from).collect::<Vec<_>>();
+                let branch_nodes = req.branch_nodes();
+                let next_key = database
+                    .with_database(move |db| {
+                        db.block_storage_next_key(
+                            &parent_hash,
+                            iter::empty::<iter::Empty<_>>(),
+                            key.into_iter(),
+                            prefix.into_iter(),
+                            branch_nodes,
+                        )
+                    })
+                    .await
+                    .unwrap();
+                builder = req.inject_key(
+                    next_key.map(|k| k.into_iter().map(|b| trie::Nibble::try_from(b).unwrap())),
+                );
+            }
+            author::runtime::BlockBuild::OffchainStorageSet(req) => builder = req.resume(),
+        }
+    }
+}
+
+/// Starts the services of a node without any peer on top of the given database, which must
+/// have been built with [`chain_spec_database`] from the given chain specification, and returns
+/// its JSON-RPC service.
+///
+/// If `consensus_service` is `None`, a consensus service is started on top of the database.
+/// Contrary to [`crate::start`], the database remains accessible to the caller, which can
+/// modify it while the services are running.
+pub async fn json_rpc_service(
+    database: Arc<database_thread::DatabaseThread>,
+    chain_spec_json: &[u8],
+    consensus_service: Option<Arc<consensus_service::ConsensusService>>,
+) -> json_rpc_service::JsonRpcService {
+    let chain_spec = chain_spec::ChainSpec::from_json_bytes(chain_spec_json).unwrap();
+    let (chain_information, _) = chain_spec.to_chain_information().unwrap();
+    let genesis_hash = chain_information.as_ref().finalized_block_header.hash(4);
+
+    try_json_rpc_service(
+        database,
+        chain_spec_json,
+        consensus_service,
+        Some(genesis_hash),
+    )
+    .await
+    .unwrap()
+}
+
+/// Similar to [`json_rpc_service`], but passes the given value as
+/// [`json_rpc_service::Config::genesis_block_hash`] and returns the error of the JSON-RPC service
+/// initialization, if any.
+pub async fn try_json_rpc_service(
+    database: Arc<database_thread::DatabaseThread>,
+    chain_spec_json: &[u8],
+    consensus_service: Option<Arc<consensus_service::ConsensusService>>,
+    genesis_block_hash: Option<[u8; 32]>,
+) -> Result<json_rpc_service::JsonRpcService, json_rpc_service::InitError> {
+    let chain_spec = chain_spec::ChainSpec::from_json_bytes(chain_spec_json).unwrap();
+    let (chain_information, _) = chain_spec.to_chain_information().unwrap();
+    let genesis_hash = chain_information.as_ref().finalized_block_header.hash(4);
+    let tasks_executor = Arc::new(|task| smol::spawn(task).detach());
+
+    let jaeger_service = jaeger_service::JaegerService::new(jaeger_service::Config {
+        tasks_executor: &mut |task| smol::spawn(task).detach(),
+        service_name: "test".to_owned(),
+        jaeger_agent: None,
+    })
+    .await
+    .unwrap();
+
+    let (network_service, network_service_chain_ids, mut network_events_receivers) =
+        network_service::NetworkService::new(network_service::Config {
+            listen_addresses: Vec::new(),
+            num_events_receivers: 1,
+            chains: vec![network_service::ChainConfig {
+                log_name: "test".to_owned(),
+                fork_id: None,
+                protocol_id: None,
+                block_number_bytes: 4,
+                database: database.clone(),
+                grandpa_protocol_finalized_block_height: Some(0),
+                genesis_block_hash: genesis_hash,
+                best_block: (0, genesis_hash),
+                max_in_peers: 25,
+                max_slots: 15,
+                bootstrap_nodes: Vec::new(),
+            }],
+            identify_agent_version: "test".to_owned(),
+            noise_key: connection::NoiseKey::new(&[0; 32], &[0; 32]),
+            tasks_executor: Box::new(|task| smol::spawn(task).detach()),
+            log_callback: Arc::new(|_, _| {}),
+            jaeger_service: jaeger_service.clone(),
+        })
+        .await
+        .unwrap();
+
+    let keystore = Arc::new(keystore::Keystore::new(None, [0; 32]).await.unwrap());
+
+    let consensus_service = match consensus_service {
+        Some(consensus_service) => consensus_service,
+        None => consensus_service::ConsensusService::new(consensus_service::Config {
+            tasks_executor: Box::new(|task| smol::spawn(task).detach()),
+            log_callback: Arc::new(|_, _| {}),
+            genesis_block_hash: genesis_hash,
+            network_events_receiver: network_events_receivers.pop().unwrap(),
+            network_service: (network_service.clone(), network_service_chain_ids[0]),
+            database: database.clone(),
+            block_number_bytes: 4,
+            keystore: keystore.clone(),
+            jaeger_service,
+            slot_duration_author_ratio: 43691_u16,
+            code_substitutes: BTreeMap::new(),
+            blocks_pruning: None,
+        })
+        .await
+        .unwrap(),
+    };
+
+    json_rpc_service::JsonRpcService::new(json_rpc_service::Config {
+        tasks_executor,
+        log_callback: Arc::new(|_, _| {}),
+        database,
+        consensus_service,
+        keystore,
+        network_service: (network_service, network_service_chain_ids[0]),
+        bind_address: None,
+        max_parallel_requests: 32,
+        max_queued_requests: NonZero::<usize>::new(256).unwrap(),
+        state_get_keys_max_keys: 10_000,
+        subscribe_all_heads_report_initial_best: false,
+        default_block: json_rpc_service::DefaultBlock::Best,
+        database_timeout: Duration::from_secs(60),
+        consensus_engine: crate::consensus_engine(&chain_information),
+        finality_engine: crate::finality_engine(&chain_information),
+        allow_unsafe_methods: true,
+        max_json_rpc_subscriptions_per_client: 0,
+        max_chain_head_pinned_blocks: NonZero::<usize>::new(512).unwrap(),
+        max_request_size: 15 * 1024 * 1024,
+        max_expensive_requests_per_second: None,
+        log_requests_durations: false,
+        max_json_rpc_clients: 0,
+        chain_name: chain_spec.name().to_owned(),
+        chain_type: chain_spec.chain_type().to_owned(),
+        chain_properties: Arc::new(
+            chain_spec::ChainProperties::from_json(chain_spec.properties()).unwrap(),
+        ),
+        chain_is_live: chain_spec.has_live_network(),
+        chain_fork_id: None,
+        chain_protocol_id: None,
+        chain_spec: Arc::new(chain_spec.clone()),
+        genesis_block_hash,
+    })
+    .await
+}
@@ -34,6 +34,10 @@ fn basic_block_generated() {
                 sqlite_cache_size: 256 * 1024 * 1024,
                 keystore_path: None,
                 json_rpc_listen: None,
+                code_substitutes: Default::default(),
+                blocks_pruning: None,
+                genesis_storage: None,
+                verify_database_genesis: true,
             },
             relay_chain: None,
             libp2p_key: Box::new([0; 32]),
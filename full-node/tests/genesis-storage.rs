@@ -0,0 +1,302 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use smoldot::{chain, chain_spec, database::full_sqlite, header, json_rpc};
+use std::{collections::BTreeMap, fs, iter, path::PathBuf, sync::Arc};
+
+const CHAIN_SPEC: &[u8] = include_bytes!("./substrate-node-template.json");
+
+/// Builds by hand a minimal genesis storage containing the runtime of the chain specification,
+/// one Aura authority, and one Grandpa authority, plus an additional item.
+fn hand_built_genesis_storage() -> BTreeMap<Vec<u8>, Vec<u8>> {
+    let chain_spec = chain_spec::ChainSpec::from_json_bytes(CHAIN_SPEC).unwrap();
+    let code = chain_spec
+        .genesis_storage()
+        .into_genesis_items()
+        .unwrap()
+        .value(b":code")
+        .unwrap()
+        .to_vec();
+
+    BTreeMap::from([
+        (b":code".to_vec(), code),
+        // `Aura::Authorities`, containing one authority.
+        (
+            hex::decode("57f8dc2f5ab09467896f47300f0424385e0621c4869aa60c02be9adcc98a0d1d")
+                .unwrap(),
+            [&[4][..], &[1; 32]].concat(),
+        ),
+        // Version 1 of the list of Grandpa authorities, containing one authority with a weight
+        // of 1.
+        (
+            b":grandpa_authorities".to_vec(),
+            [&[1, 4][..], &[2; 32], &1u64.to_le_bytes()].concat(),
+        ),
+        (b"hand-built".to_vec(), b"genesis".to_vec()),
+    ])
+}
+
+/// Returns the genesis storage of the chain specification.
+fn chain_spec_genesis_storage() -> BTreeMap<Vec<u8>, Vec<u8>> {
+    let chain_spec = chain_spec::ChainSpec::from_json_bytes(CHAIN_SPEC).unwrap();
+    chain_spec
+        .genesis_storage()
+        .into_genesis_items()
+        .unwrap()
+        .iter()
+        .map(|(key, value)| (key.to_vec(), value.to_vec()))
+        .collect()
+}
+
+async fn start_client(
+    genesis_storage: smoldot_full_node::GenesisStorageConfig,
+    sqlite_database_path: Option<PathBuf>,
+    verify_database_genesis: bool,
+) -> Result<smoldot_full_node::Client, smoldot_full_node::StartError> {
+    smoldot_full_node::start(smoldot_full_node::Config {
+        chain: smoldot_full_node::ChainConfig {
+            chain_spec: CHAIN_SPEC.into(),
+            additional_bootnodes: Vec::new(),
+            keystore_memory: vec![],
+            sqlite_database_path,
+            sqlite_cache_size: 256 * 1024 * 1024,
+            keystore_path: None,
+            json_rpc_listen: None,
+            code_substitutes: Default::default(),
+            blocks_pruning: None,
+            genesis_storage: Some(genesis_storage),
+            verify_database_genesis,
+        },
+        relay_chain: None,
+        libp2p_key: Box::new([0; 32]),
+        listen_addresses: Vec::new(),
+        tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+        log_callback: Arc::new(move |_, _| {}),
+        jaeger_agent: None,
+    })
+    .await
+}
+
+#[test]
+fn genesis_built_from_provided_storage() {
+    smol::block_on(async move {
+        let items = hand_built_genesis_storage();
+        let (expected_chain_information, _) = chain_spec::chain_information_from_genesis_storage(
+            items.iter().map(|(key, value)| (&key[..], &value[..])),
+            |key| items.get(key).map(|value| &value[..]),
+            4,
+        )
+        .unwrap();
+        let expected_header = expected_chain_information.as_ref().finalized_block_header;
+
+        // The authorities are the ones of the hand-built storage.
+        let chain::chain_information::ChainInformationConsensusRef::Aura {
+            finalized_authorities_list,
+            ..
+        } = expected_chain_information.as_ref().consensus
+        else {
+            panic!()
+        };
+        assert_eq!(
+            finalized_authorities_list
+                .map(|authority| *authority.public_key)
+                .collect::<Vec<_>>(),
+            vec![[1; 32]]
+        );
+        let chain::chain_information::ChainInformationFinalityRef::Grandpa {
+            finalized_triggered_authorities,
+            ..
+        } = expected_chain_information.as_ref().finality
+        else {
+            panic!()
+        };
+        assert_eq!(finalized_triggered_authorities.len(), 1);
+        assert_eq!(finalized_triggered_authorities[0].public_key, [2; 32]);
+        assert_eq!(finalized_triggered_authorities[0].weight.get(), 1);
+
+        let client = start_client(
+            smoldot_full_node::GenesisStorageConfig {
+                items: items.clone(),
+                expected_state_root: Some(*expected_header.state_root),
+            },
+            None,
+            true,
+        )
+        .await
+        .unwrap();
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chain_getBlockHash","params":[0]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let genesis_hash = serde_json::from_str::<String>(result_json).unwrap();
+        assert_eq!(
+            genesis_hash,
+            format!("0x{}", hex::encode(expected_header.hash(4)))
+        );
+        assert_ne!(
+            genesis_hash,
+            "0x6bf30d04495c16ef053de4ac74eac35dfd6473e4907810f450bea1b976ac518f"
+        );
+
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"state_getKeys","params":["0x{}",{}]}}"#,
+            hex::encode(b"hand"),
+            serde_json::to_string(&genesis_hash).unwrap()
+        ));
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<String>>(result_json).unwrap(),
+            vec![format!("0x{}", hex::encode(b"hand-built"))]
+        );
+    });
+}
+
+#[test]
+fn genesis_state_root_mismatch() {
+    smol::block_on(async move {
+        let result = start_client(
+            smoldot_full_node::GenesisStorageConfig {
+                items: hand_built_genesis_storage(),
+                expected_state_root: Some([0; 32]),
+            },
+            None,
+            true,
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(smoldot_full_node::StartError::GenesisStateRootMismatch)
+        ));
+    });
+}
+
+/// Builds a database in a new directory whose genesis block is the one of the chain
+/// specification, after `modify_header` has been applied to it. Returns the directory, to remove
+/// at the end of the test, and the path to the database.
+fn build_database(
+    name: &str,
+    modify_header: impl FnOnce(&mut header::Header),
+) -> (PathBuf, PathBuf) {
+    let directory = std::env::temp_dir().join(format!(
+        "smoldot-full-node-test-{name}-{}",
+        std::process::id()
+    ));
+    fs::create_dir_all(&directory).unwrap();
+    let database_path = directory.join("database.sqlite");
+
+    let full_sqlite::DatabaseOpen::Empty(empty_db) = full_sqlite::open(full_sqlite::Config {
+        block_number_bytes: 4,
+        cache_size: 2 * 1024 * 1024,
+        ty: full_sqlite::ConfigTy::Disk {
+            path: &database_path,
+            memory_map_size: 1000000,
+        },
+    })
+    .unwrap() else {
+        panic!()
+    };
+    let (chain_information, _) = chain_spec::ChainSpec::from_json_bytes(CHAIN_SPEC)
+        .unwrap()
+        .to_chain_information()
+        .unwrap();
+    let mut genesis_header =
+        header::Header::from(chain_information.as_ref().finalized_block_header);
+    modify_header(&mut genesis_header);
+    empty_db
+        .initialize(&genesis_header.scale_encoding_vec(4), iter::empty(), None)
+        .unwrap();
+
+    (directory, database_path)
+}
+
+#[test]
+fn database_genesis_state_root_mismatch() {
+    let (directory, database_path) = build_database("database-genesis-state-root", |_| {});
+
+    // Starting the node with a different genesis storage must fail, as the database was built
+    // for a different chain.
+    let result = smol::block_on(start_client(
+        smoldot_full_node::GenesisStorageConfig {
+            items: hand_built_genesis_storage(),
+            expected_state_root: None,
+        },
+        Some(database_path),
+        true,
+    ));
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert!(matches!(
+        result,
+        Err(smoldot_full_node::StartError::DatabaseGenesisMismatch)
+    ));
+}
+
+#[test]
+fn database_genesis_hash_mismatch() {
+    // The state root of the genesis block of the database is the right one, but not its hash.
+    let (directory, database_path) = build_database("database-genesis-hash", |header| {
+        header.extrinsics_root = [1; 32];
+    });
+
+    let result = smol::block_on(start_client(
+        smoldot_full_node::GenesisStorageConfig {
+            items: chain_spec_genesis_storage(),
+            expected_state_root: None,
+        },
+        Some(database_path),
+        true,
+    ));
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert!(matches!(
+        result,
+        Err(smoldot_full_node::StartError::DatabaseGenesisMismatch)
+    ));
+}
+
+#[test]
+fn database_genesis_hash_mismatch_unverified() {
+    // The hash of the genesis block is compared even when the genesis verification is disabled.
+    let (directory, database_path) = build_database("database-genesis-hash-unverified", |header| {
+        header.extrinsics_root = [1; 32];
+    });
+
+    let result = smol::block_on(start_client(
+        smoldot_full_node::GenesisStorageConfig {
+            items: chain_spec_genesis_storage(),
+            expected_state_root: None,
+        },
+        Some(database_path),
+        false,
+    ));
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert!(matches!(
+        result,
+        Err(smoldot_full_node::StartError::DatabaseGenesisMismatch)
+    ));
+}
@@ -30,6 +30,10 @@ fn send_request_errs_if_malformed() {
                 sqlite_cache_size: 256 * 1024 * 1024,
                 keystore_path: None,
                 json_rpc_listen: None,
+                code_substitutes: Default::default(),
+                blocks_pruning: None,
+                genesis_storage: None,
+                verify_database_genesis: true,
             },
             relay_chain: None,
             libp2p_key: Box::new([0; 32]),
@@ -50,6 +54,69 @@ fn send_request_errs_if_malformed() {
     });
 }
 
+#[test]
+fn send_request_errs_if_too_large() {
+    smol::block_on(async move {
+        let client = smoldot_full_node::start(smoldot_full_node::Config {
+            chain: smoldot_full_node::ChainConfig {
+                chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
+                additional_bootnodes: Vec::new(),
+                keystore_memory: vec![],
+                sqlite_database_path: None,
+                sqlite_cache_size: 256 * 1024 * 1024,
+                keystore_path: None,
+                json_rpc_listen: Some(smoldot_full_node::JsonRpcListenConfig {
+                    address: "127.0.0.1:0".parse().unwrap(),
+                    max_json_rpc_clients: 1,
+                    max_json_rpc_subscriptions_per_client: 1,
+                    max_json_rpc_chain_head_pinned_blocks: 512,
+                    max_json_rpc_expensive_requests_per_second: 0,
+                    log_json_rpc_requests_durations: false,
+                    json_rpc_default_to_finalized_block: false,
+                    json_rpc_database_timeout: std::time::Duration::from_secs(60),
+                    max_json_rpc_queued_requests:
+                        smoldot_full_node::DEFAULT_MAX_JSON_RPC_QUEUED_REQUESTS,
+                    max_json_rpc_request_size: 1024,
+                }),
+                code_substitutes: Default::default(),
+                blocks_pruning: None,
+                genesis_storage: None,
+                verify_database_genesis: true,
+            },
+            relay_chain: None,
+            libp2p_key: Box::new([0; 32]),
+            listen_addresses: Vec::new(),
+            tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+            log_callback: Arc::new(move |_, _| {}),
+            jaeger_agent: None,
+        })
+        .await
+        .unwrap();
+
+        // Malformed request larger than the limit. It must be rejected because of its size
+        // rather than because it can't be parsed.
+        client.send_json_rpc_request("thisisnotproperjsonrpc".repeat(100));
+        let response_raw = client.next_json_rpc_response().await;
+        // Since the request isn't parsed, the error response has a `null` identifier.
+        match json_rpc::parse::parse_response(&response_raw).unwrap() {
+            json_rpc::parse::Response::ParseError { error_code, .. } => {
+                assert_eq!(error_code, -32007);
+            }
+            _ => unreachable!(),
+        }
+
+        // Requests below the limit are still processed.
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"system_name","params":[]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Success { .. }
+        ));
+    });
+}
+
 #[test]
 fn send_request_works_if_unknown_request() {
     smol::block_on(async move {
@@ -62,6 +129,10 @@ fn send_request_works_if_unknown_request() {
                 sqlite_cache_size: 256 * 1024 * 1024,
                 keystore_path: None,
                 json_rpc_listen: None,
+                code_substitutes: Default::default(),
+                blocks_pruning: None,
+                genesis_storage: None,
+                verify_database_genesis: true,
             },
             relay_chain: None,
             libp2p_key: Box::new([0; 32]),
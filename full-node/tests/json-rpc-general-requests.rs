@@ -16,7 +16,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use smoldot::json_rpc;
-use std::sync::Arc;
+use std::{iter, sync::Arc, time::Duration};
 
 async fn start_client() -> smoldot_full_node::Client {
     smoldot_full_node::start(smoldot_full_node::Config {
@@ -28,6 +28,10 @@ async fn start_client() -> smoldot_full_node::Client {
             sqlite_cache_size: 256 * 1024 * 1024,
             keystore_path: None,
             json_rpc_listen: None,
+            code_substitutes: Default::default(),
+            blocks_pruning: None,
+            genesis_storage: None,
+            verify_database_genesis: true,
         },
         relay_chain: None,
         libp2p_key: Box::new([0; 32]),
@@ -101,6 +105,88 @@ fn chain_spec_v1_properties() {
     });
 }
 
+#[test]
+fn chain_spec_unstable_relay_chain() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chainSpec_unstable_relayChain","params":[]}"#
+                .to_owned(),
+        );
+
+        // The test chain isn't a parachain.
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "null");
+    });
+}
+
+#[test]
+fn author_insert_key() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"author_insertKey","params":["aura", "//Alice", "0xd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d"]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "null");
+    });
+}
+
+#[test]
+fn author_insert_key_public_key_mismatch() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"author_insertKey","params":["aura", "//Bob", "0xd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d"]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Error {
+                error_code: -32000,
+                ..
+            }
+        ));
+    });
+}
+
+#[test]
+fn babe_epoch_authorship_on_aura_chain() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        // The test chain uses Aura.
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"babe_epochAuthorship","params":[]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        match json_rpc::parse::parse_response(&response_raw).unwrap() {
+            json_rpc::parse::Response::Error {
+                error_code,
+                error_message,
+                ..
+            } => {
+                assert_eq!(error_code, -32000);
+                assert_eq!(error_message, "Not available for this consensus engine");
+            }
+            _ => panic!("{response_raw}"),
+        }
+    });
+}
+
 #[test]
 fn chain_get_block_hash() {
     smol::block_on(async move {
@@ -129,10 +215,233 @@ fn chain_get_block_hash() {
             .unwrap();
         assert_eq!(result_json, "null");
 
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chain_getBlockHash","params":[18446744073709551615]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "null");
+
         // TODO: test for a non-zero block?
     });
 }
 
+#[test]
+fn chain_get_block_hash_above_best_block() {
+    smol::block_on(async move {
+        // Every database access times out immediately. Heights above the best block must thus be
+        // answered without accessing the database.
+        let client = smoldot_full_node::start(smoldot_full_node::Config {
+            chain: smoldot_full_node::ChainConfig {
+                chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
+                additional_bootnodes: Vec::new(),
+                keystore_memory: vec![],
+                sqlite_database_path: None,
+                sqlite_cache_size: 256 * 1024 * 1024,
+                keystore_path: None,
+                json_rpc_listen: Some(smoldot_full_node::JsonRpcListenConfig {
+                    address: "127.0.0.1:0".parse().unwrap(),
+                    max_json_rpc_clients: 1,
+                    max_json_rpc_subscriptions_per_client: 1,
+                    max_json_rpc_chain_head_pinned_blocks: 512,
+                    max_json_rpc_expensive_requests_per_second: 0,
+                    log_json_rpc_requests_durations: false,
+                    json_rpc_default_to_finalized_block: false,
+                    json_rpc_database_timeout: Duration::ZERO,
+                    max_json_rpc_queued_requests:
+                        smoldot_full_node::DEFAULT_MAX_JSON_RPC_QUEUED_REQUESTS,
+                    max_json_rpc_request_size: 15 * 1024 * 1024,
+                }),
+                code_substitutes: Default::default(),
+                blocks_pruning: None,
+                genesis_storage: None,
+                verify_database_genesis: true,
+            },
+            relay_chain: None,
+            libp2p_key: Box::new([0; 32]),
+            listen_addresses: Vec::new(),
+            tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+            log_callback: Arc::new(move |_, _| {}),
+            jaeger_agent: None,
+        })
+        .await
+        .unwrap();
+
+        // The best block is the genesis block.
+        for height in [1, 10000, u64::MAX] {
+            client.send_json_rpc_request(format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"chain_getBlockHash","params":[{height}]}}"#
+            ));
+            let response_raw = client.next_json_rpc_response().await;
+            let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+                .unwrap()
+                .into_success()
+                .unwrap();
+            assert_eq!(result_json, "null");
+        }
+    });
+}
+
+#[test]
+fn chain_get_head() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        // `chain_getHead` is a deprecated alias of `chain_getBlockHash` without a height.
+        for params in ["[]", "[null]"] {
+            client.send_json_rpc_request(format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"chain_getHead","params":{params}}}"#
+            ));
+            let response_raw = client.next_json_rpc_response().await;
+            let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+                .unwrap()
+                .into_success()
+                .unwrap();
+            assert_eq!(
+                serde_json::from_str::<String>(result_json).unwrap(),
+                "0x6bf30d04495c16ef053de4ac74eac35dfd6473e4907810f450bea1b976ac518f"
+            );
+        }
+    });
+}
+
+#[test]
+fn chain_subscribe_export_blocks() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chain_subscribeExportBlocks","params":[0, 0]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Success { .. }
+        ));
+
+        match json_rpc::methods::parse_notification(&client.next_json_rpc_response().await).unwrap()
+        {
+            json_rpc::methods::ServerToClient::chain_exportedBlocks {
+                result: json_rpc::methods::ExportBlocksEvent::Blocks { blocks },
+                ..
+            } => {
+                assert_eq!(blocks.len(), 1);
+                // The genesis block has no extrinsic.
+                let (last, header) = blocks[0].0.split_last().unwrap();
+                assert_eq!(*last, 0);
+                assert_eq!(smoldot::header::decode(header, 4).unwrap().number, 0);
+            }
+            _ => panic!(),
+        }
+
+        assert!(matches!(
+            json_rpc::methods::parse_notification(&client.next_json_rpc_response().await).unwrap(),
+            json_rpc::methods::ServerToClient::chain_exportedBlocks {
+                result: json_rpc::methods::ExportBlocksEvent::Done {},
+                ..
+            }
+        ));
+    });
+}
+
+#[test]
+fn chain_subscribe_export_blocks_above_best() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chain_subscribeExportBlocks","params":[0, 18446744073709551615]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Error {
+                error_code: -32602, // Invalid parameter error code.
+                ..
+            }
+        ));
+    });
+}
+
+#[test]
+fn chain_get_block() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chain_getBlock","params":[]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let block = serde_json::from_str::<serde_json::Value>(result_json).unwrap();
+        assert_eq!(block["block"]["extrinsics"], serde_json::json!([]));
+        assert_eq!(block["block"]["header"]["number"], "0x0");
+        // Extrinsics are only decoded if explicitly requested.
+        assert!(block["block"].get("decodedExtrinsics").is_none());
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chain_getBlock","params":[null, true]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let block = serde_json::from_str::<serde_json::Value>(result_json).unwrap();
+        assert_eq!(block["block"]["decodedExtrinsics"], serde_json::json!([]));
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chain_getBlock","params":["0x0000000000000000000000000000000000000000000000000000000000000000", true]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "null");
+    });
+}
+
+#[test]
+fn chain_get_block_stats() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chain_getBlockStats","params":[]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let stats = serde_json::from_str::<json_rpc::methods::BlockStats>(result_json).unwrap();
+        // The genesis block consists of a header without any digest item and an empty body.
+        assert_eq!(stats.encoded_size, 99);
+        assert_eq!(stats.num_extrinsics, 0);
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chain_getBlockStats","params":["0x0000000000000000000000000000000000000000000000000000000000000000"]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "null");
+    });
+}
+
 #[test]
 fn chain_get_header() {
     smol::block_on(async move {
@@ -208,13 +517,1382 @@ fn chain_get_header() {
 }
 
 #[test]
-fn state_get_metadata() {
+fn chain_head_v1_storage() {
     smol::block_on(async move {
         let client = start_client().await;
 
-        // Query the metadata of the genesis.
         client.send_json_rpc_request(
-            r#"{"jsonrpc":"2.0","id":1,"method":"state_getMetadata","params":["0x6bf30d04495c16ef053de4ac74eac35dfd6473e4907810f450bea1b976ac518f"]}"#.to_owned(),
+            r#"{"jsonrpc":"2.0","id":1,"method":"chainHead_v1_follow","params":[false]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let follow_subscription = serde_json::from_str::<String>(result_json).unwrap();
+
+        assert!(matches!(
+            json_rpc::methods::parse_notification(&client.next_json_rpc_response().await).unwrap(),
+            json_rpc::methods::ServerToClient::chainHead_v1_followEvent {
+                result: json_rpc::methods::FollowEvent::Initialized { .. },
+                ..
+            }
+        ));
+
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":2,"method":"chainHead_v1_storage","params":["{follow_subscription}","0x6bf30d04495c16ef053de4ac74eac35dfd6473e4907810f450bea1b976ac518f",[{{"key":"0x","type":"closestDescendantMerkleValue"}},{{"key":"0xdeadbeef","type":"value"}}],null]}}"#
+        ));
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let json_rpc::methods::ChainHeadStorageReturn::Started {
+            operation_id,
+            discarded_items: 0,
+        } = serde_json::from_str(result_json).unwrap()
+        else {
+            panic!()
+        };
+
+        // The Merkle value of the closest descendant of the empty key is the state root.
+        // The key that doesn't exist in the storage isn't part of the items.
+        match json_rpc::methods::parse_notification(&client.next_json_rpc_response().await).unwrap()
+        {
+            json_rpc::methods::ServerToClient::chainHead_v1_followEvent {
+                result:
+                    json_rpc::methods::FollowEvent::OperationStorageItems {
+                        operation_id: id,
+                        items,
+                    },
+                ..
+            } => {
+                assert_eq!(id, operation_id);
+                assert_eq!(items.len(), 1);
+                assert!(items[0].key.0.is_empty());
+                assert_eq!(
+                    items[0].closest_descendant_merkle_value.as_ref().unwrap().0,
+                    [
+                        40, 162, 219, 5, 170, 164, 232, 78, 136, 198, 190, 40, 202, 73, 212, 91, 4,
+                        51, 248, 171, 238, 66, 27, 9, 45, 250, 15, 77, 216, 87, 135, 166
+                    ]
+                );
+            }
+            _ => panic!(),
+        }
+
+        assert!(matches!(
+            json_rpc::methods::parse_notification(&client.next_json_rpc_response().await).unwrap(),
+            json_rpc::methods::ServerToClient::chainHead_v1_followEvent {
+                result: json_rpc::methods::FollowEvent::OperationStorageDone { .. },
+                ..
+            }
+        ));
+    });
+}
+
+#[test]
+fn chain_head_v1_storage_descendants_continue() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        // All the keys of the `System` pallet found in the genesis storage, in lexicographic
+        // order. There are more of them than what fits in a single batch of items.
+        let prefix = [
+            0x26, 0xaa, 0x39, 0x4e, 0xea, 0x56, 0x30, 0xe0, 0x7c, 0x48, 0xae, 0x0c, 0x95, 0x58,
+            0xce, 0xf7,
+        ];
+        let expected = {
+            let chain_spec = smoldot::chain_spec::ChainSpec::from_json_bytes(include_bytes!(
+                "./substrate-node-template.json"
+            ))
+            .unwrap();
+            let mut list = chain_spec
+                .genesis_storage()
+                .into_genesis_items()
+                .unwrap()
+                .iter()
+                .filter(|(key, _)| key.starts_with(&prefix))
+                .map(|(key, value)| (key.to_vec(), value.to_vec()))
+                .collect::<Vec<_>>();
+            list.sort();
+            list
+        };
+        assert_eq!(expected.len(), 18);
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chainHead_v1_follow","params":[false]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let follow_subscription = serde_json::from_str::<String>(result_json).unwrap();
+
+        assert!(matches!(
+            json_rpc::methods::parse_notification(&client.next_json_rpc_response().await).unwrap(),
+            json_rpc::methods::ServerToClient::chainHead_v1_followEvent {
+                result: json_rpc::methods::FollowEvent::Initialized { .. },
+                ..
+            }
+        ));
+
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":2,"method":"chainHead_v1_storage","params":["{follow_subscription}","0x6bf30d04495c16ef053de4ac74eac35dfd6473e4907810f450bea1b976ac518f",[{{"key":"0x26aa394eea5630e07c48ae0c9558cef7","type":"descendantsValues"}}],null]}}"#
+        ));
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let json_rpc::methods::ChainHeadStorageReturn::Started {
+            operation_id,
+            discarded_items: 0,
+        } = serde_json::from_str(result_json).unwrap()
+        else {
+            panic!()
+        };
+
+        let mut received = Vec::new();
+        let mut num_batches = 0;
+        loop {
+            let message = client.next_json_rpc_response().await;
+            let Ok(notification) = json_rpc::methods::parse_notification(&message) else {
+                // Response to `chainHead_v1_continue`.
+                let (_, result_json) = json_rpc::parse::parse_response(&message)
+                    .unwrap()
+                    .into_success()
+                    .unwrap();
+                assert_eq!(result_json, "null");
+                continue;
+            };
+
+            match notification {
+                json_rpc::methods::ServerToClient::chainHead_v1_followEvent {
+                    result:
+                        json_rpc::methods::FollowEvent::OperationStorageItems {
+                            operation_id: id,
+                            items,
+                        },
+                    ..
+                } => {
+                    assert_eq!(id, operation_id);
+                    num_batches += 1;
+                    received.extend(
+                        items
+                            .into_iter()
+                            .map(|item| (item.key.0, item.value.unwrap().0)),
+                    );
+                }
+                json_rpc::methods::ServerToClient::chainHead_v1_followEvent {
+                    result:
+                        json_rpc::methods::FollowEvent::OperationWaitingForContinue { operation_id: id },
+                    ..
+                } => {
+                    assert_eq!(id, operation_id);
+                    client.send_json_rpc_request(format!(
+                        r#"{{"jsonrpc":"2.0","id":3,"method":"chainHead_v1_continue","params":["{follow_subscription}","{operation_id}"]}}"#
+                    ));
+                }
+                json_rpc::methods::ServerToClient::chainHead_v1_followEvent {
+                    result:
+                        json_rpc::methods::FollowEvent::OperationStorageDone { operation_id: id },
+                    ..
+                } => {
+                    assert_eq!(id, operation_id);
+                    break;
+                }
+                _ => panic!(),
+            }
+        }
+
+        // Every descendant has been delivered exactly once and in order, with the second batch
+        // starting exactly after the last key of the first one.
+        assert_eq!(num_batches, 2);
+        assert_eq!(received, expected);
+    });
+}
+
+#[test]
+fn chain_head_v1_storage_operations_limit() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chainHead_v1_follow","params":[false]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let follow_subscription = serde_json::from_str::<String>(result_json).unwrap();
+
+        // Each of these operations has more items than what fits in a single batch, and thus
+        // stays paused until `chainHead_v1_continue` is called. The node accepts up to 16 such
+        // operations at the same time.
+        for request_id in 2..=18 {
+            client.send_json_rpc_request(format!(
+                r#"{{"jsonrpc":"2.0","id":{request_id},"method":"chainHead_v1_storage","params":["{follow_subscription}","0x6bf30d04495c16ef053de4ac74eac35dfd6473e4907810f450bea1b976ac518f",[{{"key":"0x26aa394eea5630e07c48ae0c9558cef7","type":"descendantsValues"}}],null]}}"#
+            ));
+
+            let result_json = loop {
+                let message = client.next_json_rpc_response().await;
+                if json_rpc::methods::parse_notification(&message).is_ok() {
+                    continue;
+                }
+                let (id, result_json) = json_rpc::parse::parse_response(&message)
+                    .unwrap()
+                    .into_success()
+                    .unwrap();
+                assert_eq!(id, request_id.to_string());
+                break result_json.to_owned();
+            };
+
+            match serde_json::from_str(&result_json).unwrap() {
+                json_rpc::methods::ChainHeadStorageReturn::Started { .. } => {
+                    assert!(request_id <= 17)
+                }
+                json_rpc::methods::ChainHeadStorageReturn::LimitReached {} => {
+                    assert_eq!(request_id, 18)
+                }
+            }
+        }
+    });
+}
+
+#[test]
+fn state_get_metadata() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        // Query the metadata of the genesis.
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_getMetadata","params":["0x6bf30d04495c16ef053de4ac74eac35dfd6473e4907810f450bea1b976ac518f"]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<String>(result_json).unwrap(),
+            include_str!("./substrate-node-template-metadata.hex").trim()
+        );
+
+        // Querying again, this time without a block hash, is answered from the cache and must
+        // return the same metadata.
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":2,"method":"state_getMetadata","params":[]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<String>(result_json).unwrap(),
+            include_str!("./substrate-node-template-metadata.hex").trim()
+        );
+
+        // TOOD: there are no tests for the corner cases of state_getMetadata because it's unclear how the function is supposed to behave at all
+    });
+}
+
+#[test]
+fn state_get_metadata_rate_limited() {
+    smol::block_on(async move {
+        let client = smoldot_full_node::start(smoldot_full_node::Config {
+            chain: smoldot_full_node::ChainConfig {
+                chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
+                additional_bootnodes: Vec::new(),
+                keystore_memory: vec![],
+                sqlite_database_path: None,
+                sqlite_cache_size: 256 * 1024 * 1024,
+                keystore_path: None,
+                json_rpc_listen: Some(smoldot_full_node::JsonRpcListenConfig {
+                    address: "127.0.0.1:0".parse().unwrap(),
+                    max_json_rpc_clients: 1,
+                    max_json_rpc_subscriptions_per_client: 1,
+                    max_json_rpc_chain_head_pinned_blocks: 512,
+                    max_json_rpc_expensive_requests_per_second: 1,
+                    log_json_rpc_requests_durations: false,
+                    json_rpc_default_to_finalized_block: false,
+                    json_rpc_database_timeout: Duration::from_secs(60),
+                    max_json_rpc_queued_requests:
+                        smoldot_full_node::DEFAULT_MAX_JSON_RPC_QUEUED_REQUESTS,
+                    max_json_rpc_request_size: 15 * 1024 * 1024,
+                }),
+                code_substitutes: Default::default(),
+                blocks_pruning: None,
+                genesis_storage: None,
+                verify_database_genesis: true,
+            },
+            relay_chain: None,
+            libp2p_key: Box::new([0; 32]),
+            listen_addresses: Vec::new(),
+            tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+            log_callback: Arc::new(move |_, _| {}),
+            jaeger_agent: None,
+        })
+        .await
+        .unwrap();
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_getMetadata","params":[]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Success { .. }
+        ));
+
+        // The limit of one call per second has been reached.
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":2,"method":"state_getMetadata","params":[]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Error { .. }
+        ));
+
+        // Cheap functions aren't affected.
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":3,"method":"system_version","params":[]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Success { .. }
+        ));
+    });
+}
+
+#[test]
+fn requests_durations_logged() {
+    smol::block_on(async move {
+        let logs = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+
+        let client = smoldot_full_node::start(smoldot_full_node::Config {
+            chain: smoldot_full_node::ChainConfig {
+                chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
+                additional_bootnodes: Vec::new(),
+                keystore_memory: vec![],
+                sqlite_database_path: None,
+                sqlite_cache_size: 256 * 1024 * 1024,
+                keystore_path: None,
+                json_rpc_listen: Some(smoldot_full_node::JsonRpcListenConfig {
+                    address: "127.0.0.1:0".parse().unwrap(),
+                    max_json_rpc_clients: 1,
+                    max_json_rpc_subscriptions_per_client: 1,
+                    max_json_rpc_chain_head_pinned_blocks: 512,
+                    max_json_rpc_expensive_requests_per_second: 0,
+                    log_json_rpc_requests_durations: true,
+                    json_rpc_default_to_finalized_block: false,
+                    json_rpc_database_timeout: Duration::from_secs(60),
+                    max_json_rpc_queued_requests:
+                        smoldot_full_node::DEFAULT_MAX_JSON_RPC_QUEUED_REQUESTS,
+                    max_json_rpc_request_size: 15 * 1024 * 1024,
+                }),
+                code_substitutes: Default::default(),
+                blocks_pruning: None,
+                genesis_storage: None,
+                verify_database_genesis: true,
+            },
+            relay_chain: None,
+            libp2p_key: Box::new([0; 32]),
+            listen_addresses: Vec::new(),
+            tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+            log_callback: Arc::new({
+                let logs = logs.clone();
+                move |_, message| logs.lock().unwrap().push(message)
+            }),
+            jaeger_agent: None,
+        })
+        .await
+        .unwrap();
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"system_version","params":[]}"#.to_owned(),
+        );
+        let _ = client.next_json_rpc_response().await;
+
+        // The duration is logged right after the response has been sent.
+        loop {
+            if logs
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|log| log.starts_with("json-rpc-request-duration; method=system_version;"))
+            {
+                break;
+            }
+            smol::Timer::after(std::time::Duration::from_millis(10)).await;
+        }
+    });
+}
+
+#[test]
+fn state_get_runtime_version() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        // Query the runtime of the genesis.
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_getRuntimeVersion","params":["0x6bf30d04495c16ef053de4ac74eac35dfd6473e4907810f450bea1b976ac518f"]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let decoded =
+            serde_json::from_str::<json_rpc::methods::RuntimeVersion>(result_json).unwrap();
+        assert_eq!(decoded.impl_name, "node-template");
+        assert_eq!(decoded.spec_version, 100);
+        assert_eq!(decoded.apis.len(), 10);
+
+        // TOOD: there are no tests for the corner cases of state_getRuntimeVersion because it's unclear how the function is supposed to behave at all
+    });
+}
+
+#[test]
+fn chain_subscribe_runtime_version() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        // `chain_subscribeRuntimeVersion` and `chain_unsubscribeRuntimeVersion` are legacy
+        // aliases of `state_subscribeRuntimeVersion` and `state_unsubscribeRuntimeVersion`.
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chain_subscribeRuntimeVersion","params":[]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let subscription_id = serde_json::from_str::<String>(result_json).unwrap();
+
+        match json_rpc::methods::parse_notification(&client.next_json_rpc_response().await).unwrap()
+        {
+            json_rpc::methods::ServerToClient::state_runtimeVersion {
+                subscription,
+                result: Some(runtime_version),
+            } => {
+                assert_eq!(subscription, subscription_id);
+                assert_eq!(runtime_version.spec_name, "node-template");
+            }
+            _ => panic!(),
+        }
+
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":2,"method":"chain_unsubscribeRuntimeVersion","params":["{subscription_id}"]}}"#
+        ));
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert!(serde_json::from_str::<bool>(result_json).unwrap());
+    });
+}
+
+/// Builds a transfer from Alice, who is endowed at genesis, with an immortal era and a nonce
+/// of 0. The transaction is valid but can never be included, as no block is produced.
+///
+/// If `corrupt_signature` is `true`, the signature of the transaction is modified and the
+/// transaction is thus invalid.
+async fn alice_transfer(client: &smoldot_full_node::Client, corrupt_signature: bool) -> Vec<u8> {
+    let genesis_hash = [
+        0x6b, 0xf3, 0x0d, 0x04, 0x49, 0x5c, 0x16, 0xef, 0x05, 0x3d, 0xe4, 0xac, 0x74, 0xea, 0xc3,
+        0x5d, 0xfd, 0x64, 0x73, 0xe4, 0x90, 0x78, 0x10, 0xf4, 0x50, 0xbe, 0xa1, 0xb9, 0x76, 0xac,
+        0x51, 0x8f,
+    ];
+
+    client.send_json_rpc_request(
+        r#"{"jsonrpc":"2.0","id":1,"method":"state_getRuntimeVersion","params":[]}"#.to_owned(),
+    );
+    let response_raw = client.next_json_rpc_response().await;
+    let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+        .unwrap()
+        .into_success()
+        .unwrap();
+    let runtime_version =
+        serde_json::from_str::<json_rpc::methods::RuntimeVersion>(result_json).unwrap();
+
+    let mut keystore = smoldot::identity::keystore::Keystore::new(None, [0; 32])
+        .await
+        .unwrap();
+    let alice = keystore.insert_sr25519_memory(
+        iter::once(smoldot::identity::keystore::KeyNamespace::Aura),
+        &smoldot::identity::seed_phrase::decode_sr25519_private_key("//Alice").unwrap(),
+    );
+
+    // `Balances::transfer` to an arbitrary account, with a compact-encoded value of 1000.
+    let call = [&[5, 0, 0][..], &[0x42; 32][..], &[0xa1, 0x0f][..]].concat();
+    // Immortal era, nonce, and tip.
+    let extra = [0, 0, 0];
+    let mut signature = keystore
+        .sign(
+            smoldot::identity::keystore::KeyNamespace::Aura,
+            &alice,
+            &[
+                &call[..],
+                &extra[..],
+                &u32::try_from(runtime_version.spec_version)
+                    .unwrap()
+                    .to_le_bytes()[..],
+                &u32::try_from(runtime_version.transaction_version.unwrap())
+                    .unwrap()
+                    .to_le_bytes()[..],
+                &genesis_hash[..],
+                &genesis_hash[..],
+            ]
+            .concat(),
+        )
+        .await
+        .unwrap();
+    if corrupt_signature {
+        signature[0] ^= 0xff;
+    }
+
+    let transaction = [
+        &[0x84, 0][..],
+        &alice[..],
+        &[1][..],
+        &signature[..],
+        &extra[..],
+        &call[..],
+    ]
+    .concat();
+    // The length prefix uses the two-bytes mode of the SCALE compact encoding.
+    [
+        &((u16::try_from(transaction.len()).unwrap() << 2) | 1).to_le_bytes()[..],
+        &transaction[..],
+    ]
+    .concat()
+}
+
+#[test]
+fn author_submit_and_watch_extrinsic_invalid() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"author_submitAndWatchExtrinsic","params":["0x0102"]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let subscription_id = serde_json::from_str::<String>(result_json).unwrap();
+
+        // The transaction can't be decoded by the runtime, and thus can never be valid.
+        match json_rpc::methods::parse_notification(&client.next_json_rpc_response().await).unwrap()
+        {
+            json_rpc::methods::ServerToClient::author_extrinsicUpdate {
+                subscription,
+                result:
+                    json_rpc::methods::TransactionStatus::Invalid
+                    | json_rpc::methods::TransactionStatus::Dropped,
+            } => {
+                assert_eq!(subscription, subscription_id);
+            }
+            _ => panic!(),
+        }
+    });
+}
+
+#[test]
+fn author_unwatch_extrinsic() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        let transaction = alice_transfer(&client, false).await;
+
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":2,"method":"author_submitAndWatchExtrinsic","params":["0x{}"]}}"#,
+            hex::encode(&transaction)
+        ));
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let subscription_id = serde_json::from_str::<String>(result_json).unwrap();
+
+        match json_rpc::methods::parse_notification(&client.next_json_rpc_response().await).unwrap()
+        {
+            json_rpc::methods::ServerToClient::author_extrinsicUpdate {
+                subscription,
+                result: json_rpc::methods::TransactionStatus::Ready,
+            } => {
+                assert_eq!(subscription, subscription_id);
+            }
+            _ => panic!(),
+        }
+
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":3,"method":"author_unwatchExtrinsic","params":["{subscription_id}"]}}"#
+        ));
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "true");
+
+        // The subscription no longer exists.
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":4,"method":"author_unwatchExtrinsic","params":["{subscription_id}"]}}"#
+        ));
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "false");
+
+        // Watch the same transaction again. Its status updates go through the same path as the
+        // ones of the unwatched transaction would. No notification arrives for the unwatched
+        // transaction, and the next messages concern the new subscription.
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":5,"method":"author_submitAndWatchExtrinsic","params":["0x{}"]}}"#,
+            hex::encode(&transaction)
+        ));
+        let response_raw = client.next_json_rpc_response().await;
+        let (request_id, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(request_id, "5");
+        let new_subscription_id = serde_json::from_str::<String>(result_json).unwrap();
+        assert_ne!(new_subscription_id, subscription_id);
+
+        match json_rpc::methods::parse_notification(&client.next_json_rpc_response().await).unwrap()
+        {
+            json_rpc::methods::ServerToClient::author_extrinsicUpdate {
+                subscription,
+                result: json_rpc::methods::TransactionStatus::Ready,
+            } => {
+                assert_eq!(subscription, new_subscription_id);
+            }
+            _ => panic!(),
+        }
+    });
+}
+
+#[test]
+fn author_submit_extrinsic() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        let transaction = alice_transfer(&client, false).await;
+
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":2,"method":"author_submitExtrinsic","params":["0x{}"]}}"#,
+            hex::encode(&transaction)
+        ));
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<String>(result_json).unwrap(),
+            format!(
+                "0x{}",
+                hex::encode(blake2_rfc::blake2b::blake2b(32, &[], &transaction))
+            )
+        );
+    });
+}
+
+#[test]
+fn author_submit_extrinsic_bad_signature() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        let transaction = alice_transfer(&client, true).await;
+
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":2,"method":"author_submitExtrinsic","params":["0x{}"]}}"#,
+            hex::encode(&transaction)
+        ));
+        let response_raw = client.next_json_rpc_response().await;
+        match json_rpc::parse::parse_response(&response_raw).unwrap() {
+            json_rpc::parse::Response::Error {
+                error_code,
+                error_data_json,
+                ..
+            } => {
+                assert_eq!(error_code, 1010);
+                assert_eq!(
+                    serde_json::from_str::<String>(error_data_json.unwrap()).unwrap(),
+                    "BadProof"
+                );
+            }
+            _ => panic!(),
+        }
+    });
+}
+
+#[test]
+fn state_get_keys_prefix_works() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_getKeys","params":["0x26aa394e"]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let decoded = serde_json::from_str::<Vec<json_rpc::methods::HexString>>(result_json)
+            .unwrap()
+            .into_iter()
+            .map(|v| v.0)
+            .collect::<Vec<_>>();
+        assert!(decoded.len() >= 2);
+        assert!(decoded.iter().all(|k| k.starts_with(&[38, 170, 57, 78])));
+        assert!(decoded.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(
+            &decoded[..2],
+            &[
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 78,
+                    123, 144, 18, 9, 107, 65, 196, 235, 58, 175, 148, 127, 110, 164, 41
+                ][..],
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 86,
+                    132, 160, 34, 163, 77, 216, 191, 162, 186, 175, 68, 241, 114, 183, 16
+                ]
+            ]
+        );
+    });
+}
+
+#[test]
+fn state_get_keys_exact_key() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_getKeys","params":["0x26aa394eea5630e07c48ae0c9558cef74e7b9012096b41c4eb3aaf947f6ea429"]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(
+            result_json,
+            r#"["0x26aa394eea5630e07c48ae0c9558cef74e7b9012096b41c4eb3aaf947f6ea429"]"#
+        );
+    });
+}
+
+#[test]
+fn state_get_keys_unknown_block() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_getKeys","params":["0x", "0x0000000000000000000000000000000000000000000000000000000000000000"]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Error {
+                error_code: -32602, // Invalid parameter error code.
+                ..
+            }
+        ));
+    });
+}
+
+#[test]
+fn state_get_keys_paged_basic() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_getKeysPaged","params":["0x", 10]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let decoded = serde_json::from_str::<Vec<json_rpc::methods::HexString>>(result_json)
+            .unwrap()
+            .into_iter()
+            .map(|v| v.0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            decoded,
+            &[
+                &[
+                    23, 126, 104, 87, 251, 29, 14, 64, 147, 118, 18, 47, 238, 58, 212, 248, 78,
+                    123, 144, 18, 9, 107, 65, 196, 235, 58, 175, 148, 127, 110, 164, 41
+                ][..],
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 78,
+                    123, 144, 18, 9, 107, 65, 196, 235, 58, 175, 148, 127, 110, 164, 41
+                ],
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 86,
+                    132, 160, 34, 163, 77, 216, 191, 162, 186, 175, 68, 241, 114, 183, 16
+                ],
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 138,
+                    66, 243, 51, 35, 203, 92, 237, 59, 68, 221, 130, 95, 218, 159, 204
+                ],
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 164,
+                    71, 4, 181, 104, 210, 22, 103, 53, 106, 90, 5, 12, 17, 135, 70, 180, 222, 242,
+                    92, 253, 166, 239, 58, 0, 0, 0, 0
+                ],
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 167,
+                    253, 108, 40, 131, 107, 154, 40, 82, 45, 201, 36, 17, 12, 244, 57
+                ],
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 185,
+                    157, 136, 14, 198, 129, 121, 156, 12, 243, 14, 136, 134, 55, 29, 169, 0, 124,
+                    188, 18, 112, 181, 176, 145, 117, 143, 156, 66, 245, 145, 91, 62, 138, 197,
+                    158, 17, 150, 58, 241, 145, 116, 208, 185, 77, 93, 120, 4, 28, 35, 63, 85, 210,
+                    225, 147, 36, 102, 91, 175, 223, 182, 41, 37, 175, 45
+                ],
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 185,
+                    157, 136, 14, 198, 129, 121, 156, 12, 243, 14, 136, 134, 55, 29, 169, 35, 160,
+                    92, 171, 246, 211, 189, 231, 202, 62, 240, 209, 21, 150, 181, 97, 28, 189, 45,
+                    67, 83, 10, 68, 112, 90, 208, 136, 175, 49, 62, 24, 248, 11, 83, 239, 22, 179,
+                    97, 119, 205, 75, 119, 184, 70, 242, 165, 240, 124
+                ],
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 185,
+                    157, 136, 14, 198, 129, 121, 156, 12, 243, 14, 136, 134, 55, 29, 169, 50, 165,
+                    147, 95, 110, 220, 97, 122, 225, 120, 254, 249, 235, 30, 33, 31, 190, 93, 219,
+                    21, 121, 183, 46, 132, 82, 79, 194, 158, 120, 96, 158, 60, 175, 66, 232, 90,
+                    161, 24, 235, 254, 11, 10, 212, 4, 181, 189, 210, 95
+                ],
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 185,
+                    157, 136, 14, 198, 129, 121, 156, 12, 243, 14, 136, 134, 55, 29, 169, 79, 154,
+                    234, 26, 250, 121, 18, 101, 250, 227, 89, 39, 43, 173, 193, 207, 142, 175, 4,
+                    21, 22, 135, 115, 99, 38, 201, 254, 161, 126, 37, 252, 82, 135, 97, 54, 147,
+                    201, 18, 144, 156, 178, 38, 170, 71, 148, 242, 106, 72
+                ]
+            ]
+        );
+    });
+}
+
+#[test]
+fn state_get_keys_paged_prefix_works() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_getKeysPaged","params":["0x26aa394e", 2]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let decoded = serde_json::from_str::<Vec<json_rpc::methods::HexString>>(result_json)
+            .unwrap()
+            .into_iter()
+            .map(|v| v.0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            decoded,
+            &[
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 78,
+                    123, 144, 18, 9, 107, 65, 196, 235, 58, 175, 148, 127, 110, 164, 41
+                ][..],
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 86,
+                    132, 160, 34, 163, 77, 216, 191, 162, 186, 175, 68, 241, 114, 183, 16
+                ]
+            ]
+        );
+    });
+}
+
+#[test]
+fn state_get_keys_paged_start_key_exact_match() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        // This test checks whether the start key is included in the results on an exact match.
+        // `0x26aa394eea5630e07c48ae0c9558cef7` is equal to `[38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247]`.
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_getKeysPaged","params":["0x", 2, "0x26aa394eea5630e07c48ae0c9558cef7"]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let decoded = serde_json::from_str::<Vec<json_rpc::methods::HexString>>(result_json)
+            .unwrap()
+            .into_iter()
+            .map(|v| v.0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            decoded,
+            &[
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 78,
+                    123, 144, 18, 9, 107, 65, 196, 235, 58, 175, 148, 127, 110, 164, 41
+                ][..],
+                &[
+                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 86,
+                    132, 160, 34, 163, 77, 216, 191, 162, 186, 175, 68, 241, 114, 183, 16
+                ]
+            ]
+        );
+    });
+}
+
+#[test]
+fn state_get_keys_paged_pagination() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_getKeys","params":["0x26aa394e"]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let all_keys =
+            serde_json::from_str::<Vec<json_rpc::methods::HexString>>(result_json).unwrap();
+
+        // Paginate by passing the last key of the previous page as start key, until a page is
+        // shorter than the requested count.
+        let mut paginated_keys = Vec::new();
+        loop {
+            let start_key = serde_json::to_string(&paginated_keys.last()).unwrap();
+            client.send_json_rpc_request(format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"state_getKeysPaged","params":["0x26aa394e", 2, {start_key}]}}"#
+            ));
+            let response_raw = client.next_json_rpc_response().await;
+            let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+                .unwrap()
+                .into_success()
+                .unwrap();
+            let page =
+                serde_json::from_str::<Vec<json_rpc::methods::HexString>>(result_json).unwrap();
+            let page_len = page.len();
+            paginated_keys.extend(page);
+            if page_len < 2 {
+                break;
+            }
+        }
+
+        // Every key is returned exactly once.
+        assert_eq!(paginated_keys, all_keys);
+    });
+}
+
+#[test]
+fn state_get_keys_paged_count_overflow() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_getKeysPaged","params":["0x", 1000]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Success { .. }
+        ));
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":2,"method":"state_getKeysPaged","params":["0x", 1001]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Error {
+                error_code: -32602, // Invalid parameter error code.
+                ..
+            }
+        ));
+    });
+}
+
+#[test]
+fn state_get_keys_paged_unknown_block() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_getKeysPaged","params":["0x", 10, "0x", "0x0000000000000000000000000000000000000000000000000000000000000000"]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Error {
+                error_code: -32602, // Invalid parameter error code.
+                ..
+            }
+        ));
+    });
+}
+
+#[test]
+fn sync_state_gen_sync_spec_at_genesis() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        // The finalized block is the genesis block, for which no light sync state can be built.
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"sync_state_genSyncSpec","params":[true]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Error {
+                error_code: -32000,
+                ..
+            }
+        ));
+    });
+}
+
+#[test]
+fn grandpa_subscribe_authority_set_changes() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"grandpa_subscribeAuthoritySetChanges","params":[]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let subscription_id = serde_json::from_str::<String>(result_json).unwrap();
+
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":2,"method":"grandpa_unsubscribeAuthoritySetChanges","params":["{subscription_id}"]}}"#
+        ));
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "true");
+
+        // Unsubscribing a second time fails.
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":3,"method":"grandpa_unsubscribeAuthoritySetChanges","params":["{subscription_id}"]}}"#
+        ));
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "false");
+    });
+}
+
+#[test]
+fn chain_subscribe_finalized_extrinsics() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chain_subscribeFinalizedExtrinsics","params":[]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let subscription_id = serde_json::from_str::<String>(result_json).unwrap();
+
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":2,"method":"chain_unsubscribeFinalizedExtrinsics","params":["{subscription_id}"]}}"#
+        ));
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "true");
+
+        // Unsubscribing a second time fails.
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":3,"method":"chain_unsubscribeFinalizedExtrinsics","params":["{subscription_id}"]}}"#
+        ));
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "false");
+    });
+}
+
+#[test]
+fn offchain_local_storage() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        for kind in ["PERSISTENT", "LOCAL"] {
+            client.send_json_rpc_request(format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"offchain_localStorageGet","params":["{kind}","0x0102"]}}"#
+            ));
+            let response_raw = client.next_json_rpc_response().await;
+            let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+                .unwrap()
+                .into_success()
+                .unwrap();
+            assert_eq!(result_json, "null");
+
+            client.send_json_rpc_request(format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"offchain_localStorageSet","params":["{kind}","0x0102","0xaabb"]}}"#
+            ));
+            let response_raw = client.next_json_rpc_response().await;
+            assert!(matches!(
+                json_rpc::parse::parse_response(&response_raw).unwrap(),
+                json_rpc::parse::Response::Success { .. }
+            ));
+
+            client.send_json_rpc_request(format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"offchain_localStorageGet","params":["{kind}","0x0102"]}}"#
+            ));
+            let response_raw = client.next_json_rpc_response().await;
+            let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+                .unwrap()
+                .into_success()
+                .unwrap();
+            assert_eq!(result_json, r#""0xaabb""#);
+        }
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"offchain_localStorageGet","params":["foo","0x0102"]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Error {
+                error_code: -32602, // Invalid parameter error code.
+                ..
+            }
+        ));
+    });
+}
+
+#[test]
+fn rpc_methods() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"rpc_methods","params":[]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let result = serde_json::from_str::<serde_json::Value>(result_json).unwrap();
+
+        let methods = result["methods"].as_array().unwrap();
+        assert!(methods.iter().any(|m| m == "chainHead_v1_follow"));
+        assert!(methods.iter().any(|m| m == "chain_getHead"));
+        let groups = result["groups"].as_array().unwrap();
+        assert!(groups.iter().any(|g| g == "chainHead_v1"));
+        assert!(groups.iter().any(|g| g == "state"));
+    });
+}
+
+#[test]
+fn system_chain() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"system_chain","params":[]}"#.to_owned(),
+        );
+
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<String>(result_json).unwrap(),
+            "Local Testnet"
+        );
+    });
+}
+
+#[test]
+fn system_chain_type() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"system_chainType","params":[]}"#.to_owned(),
+        );
+
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<String>(result_json).unwrap(),
+            "Local"
+        );
+    });
+}
+
+#[test]
+fn system_health() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        // Query the runtime of the genesis.
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"system_health","params":[]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let decoded = serde_json::from_str::<json_rpc::methods::SystemHealth>(result_json).unwrap();
+        assert_eq!(decoded.peers, 0);
+        assert_eq!(decoded.should_have_peers, true);
+    });
+}
+
+#[test]
+fn system_sync_state() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"system_syncState","params":[]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let decoded =
+            serde_json::from_str::<json_rpc::methods::SystemSyncState>(result_json).unwrap();
+
+        // No peer is connected, meaning that the highest block is the local best block.
+        assert_eq!(decoded.starting_block, 0);
+        assert_eq!(decoded.current_block, 0);
+        assert_eq!(decoded.highest_block, 0);
+    });
+}
+
+#[test]
+fn system_unstable_network_state() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chainSpec_v1_genesisHash","params":[]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let genesis_hash = serde_json::from_str::<String>(result_json).unwrap();
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":2,"method":"system_unstable_networkState","params":[]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let decoded =
+            serde_json::from_str::<json_rpc::methods::SystemNetworkState>(result_json).unwrap();
+
+        assert_eq!(decoded.fork_id, None);
+        assert_eq!(decoded.protocol_id, None);
+        assert!(decoded.protocol_names.contains(&format!(
+            "/{}/sync/2",
+            genesis_hash.trim_start_matches("0x")
+        )));
+        assert!(decoded.peers_reputation.is_empty());
+    });
+}
+
+#[test]
+fn system_unstable_status() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"system_unstable_status","params":[]}"#.to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let decoded = serde_json::from_str::<json_rpc::methods::SystemStatus>(result_json).unwrap();
+
+        // The node isn't connected to any peer, and thus hasn't imported any block past genesis.
+        assert_eq!(decoded.best_block_number, 0);
+        assert_eq!(decoded.finalized_block_number, 0);
+        assert_eq!(decoded.best_block_hash.0, decoded.finalized_block_hash.0);
+        assert_eq!(decoded.peers, 0);
+        assert_eq!(decoded.highest_block_number, None);
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":2,"method":"chainSpec_v1_genesisHash","params":[]}"#
+                .to_owned(),
         );
         let response_raw = client.next_json_rpc_response().await;
         let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
@@ -222,22 +1900,21 @@ fn state_get_metadata() {
             .into_success()
             .unwrap();
         assert_eq!(
-            serde_json::from_str::<String>(result_json).unwrap(),
-            include_str!("./substrate-node-template-metadata.hex").trim()
+            serde_json::from_str::<json_rpc::methods::HashHexString>(result_json)
+                .unwrap()
+                .0,
+            decoded.finalized_block_hash.0
         );
-
-        // TOOD: there are no tests for the corner cases of state_getMetadata because it's unclear how the function is supposed to behave at all
     });
 }
 
 #[test]
-fn state_get_runtime_version() {
+fn chain_unstable_leaves() {
     smol::block_on(async move {
         let client = start_client().await;
 
-        // Query the runtime of the genesis.
         client.send_json_rpc_request(
-            r#"{"jsonrpc":"2.0","id":1,"method":"state_getRuntimeVersion","params":["0x6bf30d04495c16ef053de4ac74eac35dfd6473e4907810f450bea1b976ac518f"]}"#.to_owned(),
+            r#"{"jsonrpc":"2.0","id":1,"method":"chain_unstable_leaves","params":[]}"#.to_owned(),
         );
         let response_raw = client.next_json_rpc_response().await;
         let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
@@ -245,22 +1922,35 @@ fn state_get_runtime_version() {
             .into_success()
             .unwrap();
         let decoded =
-            serde_json::from_str::<json_rpc::methods::RuntimeVersion>(result_json).unwrap();
-        assert_eq!(decoded.impl_name, "node-template");
-        assert_eq!(decoded.spec_version, 100);
-        assert_eq!(decoded.apis.len(), 10);
+            serde_json::from_str::<Vec<json_rpc::methods::ChainLeaf>>(result_json).unwrap();
 
-        // TOOD: there are no tests for the corner cases of state_getRuntimeVersion because it's unclear how the function is supposed to behave at all
+        // The node isn't connected to any peer, and thus the only leaf is the genesis block.
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":2,"method":"chainSpec_v1_genesisHash","params":[]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        let genesis_hash = serde_json::from_str::<json_rpc::methods::HashHexString>(result_json)
+            .unwrap()
+            .0;
+
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].number, 0);
+        assert_eq!(decoded[0].hash.0, genesis_hash);
     });
 }
 
 #[test]
-fn state_get_keys_paged_basic() {
+fn chain_unstable_block_tree() {
     smol::block_on(async move {
         let client = start_client().await;
 
         client.send_json_rpc_request(
-            r#"{"jsonrpc":"2.0","id":1,"method":"state_getKeysPaged","params":["0x", 10]}"#
+            r#"{"jsonrpc":"2.0","id":1,"method":"chain_unstable_blockTree","params":[]}"#
                 .to_owned(),
         );
         let response_raw = client.next_json_rpc_response().await;
@@ -268,79 +1958,30 @@ fn state_get_keys_paged_basic() {
             .unwrap()
             .into_success()
             .unwrap();
-        let decoded = serde_json::from_str::<Vec<json_rpc::methods::HexString>>(result_json)
-            .unwrap()
-            .into_iter()
-            .map(|v| v.0)
-            .collect::<Vec<_>>();
+        let decoded = serde_json::from_str::<json_rpc::methods::BlockTree>(result_json).unwrap();
+
+        // The node isn't connected to any peer, and thus the tree only contains the genesis
+        // block, which is both the best and the finalized block.
+        assert!(!decoded.truncated);
+        assert_eq!(decoded.blocks.len(), 1);
+        assert_eq!(decoded.blocks[0].number, 0);
+        assert_eq!(decoded.blocks[0].parent_hash.0, [0; 32]);
         assert_eq!(
-            decoded,
-            &[
-                &[
-                    23, 126, 104, 87, 251, 29, 14, 64, 147, 118, 18, 47, 238, 58, 212, 248, 78,
-                    123, 144, 18, 9, 107, 65, 196, 235, 58, 175, 148, 127, 110, 164, 41
-                ][..],
-                &[
-                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 78,
-                    123, 144, 18, 9, 107, 65, 196, 235, 58, 175, 148, 127, 110, 164, 41
-                ],
-                &[
-                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 86,
-                    132, 160, 34, 163, 77, 216, 191, 162, 186, 175, 68, 241, 114, 183, 16
-                ],
-                &[
-                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 138,
-                    66, 243, 51, 35, 203, 92, 237, 59, 68, 221, 130, 95, 218, 159, 204
-                ],
-                &[
-                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 164,
-                    71, 4, 181, 104, 210, 22, 103, 53, 106, 90, 5, 12, 17, 135, 70, 180, 222, 242,
-                    92, 253, 166, 239, 58, 0, 0, 0, 0
-                ],
-                &[
-                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 167,
-                    253, 108, 40, 131, 107, 154, 40, 82, 45, 201, 36, 17, 12, 244, 57
-                ],
-                &[
-                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 185,
-                    157, 136, 14, 198, 129, 121, 156, 12, 243, 14, 136, 134, 55, 29, 169, 0, 124,
-                    188, 18, 112, 181, 176, 145, 117, 143, 156, 66, 245, 145, 91, 62, 138, 197,
-                    158, 17, 150, 58, 241, 145, 116, 208, 185, 77, 93, 120, 4, 28, 35, 63, 85, 210,
-                    225, 147, 36, 102, 91, 175, 223, 182, 41, 37, 175, 45
-                ],
-                &[
-                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 185,
-                    157, 136, 14, 198, 129, 121, 156, 12, 243, 14, 136, 134, 55, 29, 169, 35, 160,
-                    92, 171, 246, 211, 189, 231, 202, 62, 240, 209, 21, 150, 181, 97, 28, 189, 45,
-                    67, 83, 10, 68, 112, 90, 208, 136, 175, 49, 62, 24, 248, 11, 83, 239, 22, 179,
-                    97, 119, 205, 75, 119, 184, 70, 242, 165, 240, 124
-                ],
-                &[
-                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 185,
-                    157, 136, 14, 198, 129, 121, 156, 12, 243, 14, 136, 134, 55, 29, 169, 50, 165,
-                    147, 95, 110, 220, 97, 122, 225, 120, 254, 249, 235, 30, 33, 31, 190, 93, 219,
-                    21, 121, 183, 46, 132, 82, 79, 194, 158, 120, 96, 158, 60, 175, 66, 232, 90,
-                    161, 24, 235, 254, 11, 10, 212, 4, 181, 189, 210, 95
-                ],
-                &[
-                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 185,
-                    157, 136, 14, 198, 129, 121, 156, 12, 243, 14, 136, 134, 55, 29, 169, 79, 154,
-                    234, 26, 250, 121, 18, 101, 250, 227, 89, 39, 43, 173, 193, 207, 142, 175, 4,
-                    21, 22, 135, 115, 99, 38, 201, 254, 161, 126, 37, 252, 82, 135, 97, 54, 147,
-                    201, 18, 144, 156, 178, 38, 170, 71, 148, 242, 106, 72
-                ]
-            ]
+            hex::encode(decoded.blocks[0].hash.0),
+            "6bf30d04495c16ef053de4ac74eac35dfd6473e4907810f450bea1b976ac518f"
         );
+        assert!(decoded.blocks[0].is_best);
+        assert!(decoded.blocks[0].is_finalized);
     });
 }
 
 #[test]
-fn state_get_keys_paged_prefix_works() {
+fn state_unstable_get_runtime_code() {
     smol::block_on(async move {
         let client = start_client().await;
 
         client.send_json_rpc_request(
-            r#"{"jsonrpc":"2.0","id":1,"method":"state_getKeysPaged","params":["0x26aa394e", 2]}"#
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_unstable_getRuntimeCode","params":[]}"#
                 .to_owned(),
         );
         let response_raw = client.next_json_rpc_response().await;
@@ -348,37 +1989,34 @@ fn state_get_keys_paged_prefix_works() {
             .unwrap()
             .into_success()
             .unwrap();
-        let decoded = serde_json::from_str::<Vec<json_rpc::methods::HexString>>(result_json)
+        let code = serde_json::from_str::<json_rpc::methods::HexString>(result_json)
             .unwrap()
-            .into_iter()
-            .map(|v| v.0)
-            .collect::<Vec<_>>();
-        assert_eq!(
-            decoded,
-            &[
-                &[
-                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 78,
-                    123, 144, 18, 9, 107, 65, 196, 235, 58, 175, 148, 127, 110, 164, 41
-                ][..],
-                &[
-                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 86,
-                    132, 160, 34, 163, 77, 216, 191, 162, 186, 175, 68, 241, 114, 183, 16
-                ]
-            ]
+            .0;
+
+        // The runtime code of the chain is compressed in the chain specification, and must be
+        // returned decompressed.
+        assert!(code.starts_with(b"\0asm"));
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":2,"method":"state_unstable_getRuntimeCode","params":["0x0000000000000000000000000000000000000000000000000000000000000000"]}"#
+                .to_owned(),
         );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Error { .. }
+        ));
     });
 }
 
 #[test]
-fn state_get_keys_paged_start_key_exact_match() {
+fn state_unstable_runtime_upgrades() {
     smol::block_on(async move {
         let client = start_client().await;
 
-        // This test checks whether the start key is included in the results on an exact match.
-        // `0x26aa394eea5630e07c48ae0c9558cef7` is equal to `[38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247]`.
-
+        // The genesis block has no parent and thus can't be a runtime upgrade.
         client.send_json_rpc_request(
-            r#"{"jsonrpc":"2.0","id":1,"method":"state_getKeysPaged","params":["0x", 2, "0x26aa394eea5630e07c48ae0c9558cef7"]}"#
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_unstable_runtimeUpgrades","params":[0, 0]}"#
                 .to_owned(),
         );
         let response_raw = client.next_json_rpc_response().await;
@@ -386,71 +2024,70 @@ fn state_get_keys_paged_start_key_exact_match() {
             .unwrap()
             .into_success()
             .unwrap();
-        let decoded = serde_json::from_str::<Vec<json_rpc::methods::HexString>>(result_json)
-            .unwrap()
-            .into_iter()
-            .map(|v| v.0)
-            .collect::<Vec<_>>();
-        assert_eq!(
-            decoded,
-            &[
-                &[
-                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 78,
-                    123, 144, 18, 9, 107, 65, 196, 235, 58, 175, 148, 127, 110, 164, 41
-                ][..],
-                &[
-                    38, 170, 57, 78, 234, 86, 48, 224, 124, 72, 174, 12, 149, 88, 206, 247, 86,
-                    132, 160, 34, 163, 77, 216, 191, 162, 186, 175, 68, 241, 114, 183, 16
-                ]
-            ]
+        assert!(
+            serde_json::from_str::<Vec<json_rpc::methods::RuntimeUpgrade>>(result_json)
+                .unwrap()
+                .is_empty()
         );
-    });
-}
-
-#[test]
-fn state_get_keys_paged_count_overflow() {
-    smol::block_on(async move {
-        let client = start_client().await;
 
+        // Invalid range.
         client.send_json_rpc_request(
-            r#"{"jsonrpc":"2.0","id":1,"method":"state_getKeysPaged","params":["0x", 1000]}"#
+            r#"{"jsonrpc":"2.0","id":2,"method":"state_unstable_runtimeUpgrades","params":[1, 0]}"#
                 .to_owned(),
         );
         let response_raw = client.next_json_rpc_response().await;
         assert!(matches!(
             json_rpc::parse::parse_response(&response_raw).unwrap(),
-            json_rpc::parse::Response::Success { .. }
+            json_rpc::parse::Response::Error { .. }
         ));
 
+        // Range beyond the best block.
         client.send_json_rpc_request(
-            r#"{"jsonrpc":"2.0","id":2,"method":"state_getKeysPaged","params":["0x", 1001]}"#
+            r#"{"jsonrpc":"2.0","id":3,"method":"state_unstable_runtimeUpgrades","params":[0, 1000]}"#
                 .to_owned(),
         );
         let response_raw = client.next_json_rpc_response().await;
         assert!(matches!(
             json_rpc::parse::parse_response(&response_raw).unwrap(),
-            json_rpc::parse::Response::Error {
-                error_code: -32602, // Invalid parameter error code.
-                ..
-            }
+            json_rpc::parse::Response::Error { .. }
         ));
     });
 }
 
 #[test]
-fn state_get_keys_paged_unknown_block() {
+fn batch_request() {
     smol::block_on(async move {
         let client = start_client().await;
 
+        // The notification in the middle of the batch must not produce any response.
         client.send_json_rpc_request(
-            r#"{"jsonrpc":"2.0","id":1,"method":"state_getKeysPaged","params":["0x", 10, "0x", "0x0000000000000000000000000000000000000000000000000000000000000000"]}"#
+            r#"[{"jsonrpc":"2.0","id":1,"method":"system_version","params":[]},{"jsonrpc":"2.0","method":"system_version","params":[]},{"jsonrpc":"2.0","id":"foo","method":"chain_getBlockHash","params":[0]}]"#
                 .to_owned(),
         );
         let response_raw = client.next_json_rpc_response().await;
+        let responses = serde_json::from_str::<Vec<serde_json::Value>>(&response_raw).unwrap();
+        assert_eq!(responses.len(), 2);
+
+        // The responses are in the same order as the requests.
+        assert_eq!(responses[0]["jsonrpc"], "2.0");
+        assert_eq!(responses[0]["id"], 1);
+        assert!(responses[0]["result"]
+            .as_str()
+            .unwrap()
+            .starts_with(env!("CARGO_PKG_VERSION")));
+
+        assert_eq!(responses[1]["jsonrpc"], "2.0");
+        assert_eq!(responses[1]["id"], "foo");
+        serde_json::from_value::<json_rpc::methods::HashHexString>(responses[1]["result"].clone())
+            .unwrap();
+
+        // Empty batches are invalid.
+        client.send_json_rpc_request("[]".to_owned());
+        let response_raw = client.next_json_rpc_response().await;
         assert!(matches!(
             json_rpc::parse::parse_response(&response_raw).unwrap(),
-            json_rpc::parse::Response::Error {
-                error_code: -32602, // Invalid parameter error code.
+            json_rpc::parse::Response::ParseError {
+                error_code: -32600,
                 ..
             }
         ));
@@ -458,12 +2095,12 @@ fn state_get_keys_paged_unknown_block() {
 }
 
 #[test]
-fn system_chain() {
+fn system_local_peer_id() {
     smol::block_on(async move {
         let client = start_client().await;
 
         client.send_json_rpc_request(
-            r#"{"jsonrpc":"2.0","id":1,"method":"system_chain","params":[]}"#.to_owned(),
+            r#"{"jsonrpc":"2.0","id":1,"method":"system_localPeerId","params":[]}"#.to_owned(),
         );
 
         let response_raw = client.next_json_rpc_response().await;
@@ -473,18 +2110,18 @@ fn system_chain() {
             .unwrap();
         assert_eq!(
             serde_json::from_str::<String>(result_json).unwrap(),
-            "Local Testnet"
+            "12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN"
         );
     });
 }
 
 #[test]
-fn system_chain_type() {
+fn system_name() {
     smol::block_on(async move {
         let client = start_client().await;
 
         client.send_json_rpc_request(
-            r#"{"jsonrpc":"2.0","id":1,"method":"system_chainType","params":[]}"#.to_owned(),
+            r#"{"jsonrpc":"2.0","id":1,"method":"system_name","params":[]}"#.to_owned(),
         );
 
         let response_raw = client.next_json_rpc_response().await;
@@ -494,70 +2131,108 @@ fn system_chain_type() {
             .unwrap();
         assert_eq!(
             serde_json::from_str::<String>(result_json).unwrap(),
-            "Local"
+            "smoldot-full-node"
         );
     });
 }
 
 #[test]
-fn system_health() {
+fn system_subscribe_peer_count() {
     smol::block_on(async move {
         let client = start_client().await;
 
-        // Query the runtime of the genesis.
         client.send_json_rpc_request(
-            r#"{"jsonrpc":"2.0","id":1,"method":"system_health","params":[]}"#.to_owned(),
+            r#"{"jsonrpc":"2.0","id":1,"method":"system_subscribePeerCount","params":[]}"#
+                .to_owned(),
         );
         let response_raw = client.next_json_rpc_response().await;
-        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
-            .unwrap()
-            .into_success()
-            .unwrap();
-        let decoded = serde_json::from_str::<json_rpc::methods::SystemHealth>(result_json).unwrap();
-        assert_eq!(decoded.peers, 0);
-        assert_eq!(decoded.should_have_peers, true);
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Success { .. }
+        ));
+
+        // The current number of peers is reported immediately.
+        assert!(matches!(
+            json_rpc::methods::parse_notification(&client.next_json_rpc_response().await).unwrap(),
+            json_rpc::methods::ServerToClient::system_peerCount { result: 0, .. }
+        ));
     });
 }
 
 #[test]
-fn system_local_peer_id() {
+fn system_reserved_peers() {
     smol::block_on(async move {
         let client = start_client().await;
 
+        // No reserved peer by default.
         client.send_json_rpc_request(
-            r#"{"jsonrpc":"2.0","id":1,"method":"system_localPeerId","params":[]}"#.to_owned(),
+            r#"{"jsonrpc":"2.0","id":1,"method":"system_reservedPeers","params":[]}"#.to_owned(),
         );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "[]");
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":2,"method":"system_addReservedPeer","params":["/ip4/127.0.0.1/tcp/30333/p2p/12D3KooWEyoppNCUx8Yx66oV9fJnriXwCcXwDDUA2kj6vnc6iDEp"]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "null");
 
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":3,"method":"system_reservedPeers","params":[]}"#.to_owned(),
+        );
         let response_raw = client.next_json_rpc_response().await;
         let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
             .unwrap()
             .into_success()
             .unwrap();
         assert_eq!(
-            serde_json::from_str::<String>(result_json).unwrap(),
-            "12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN"
+            serde_json::from_str::<Vec<String>>(result_json).unwrap(),
+            vec![
+                "/ip4/127.0.0.1/tcp/30333/p2p/12D3KooWEyoppNCUx8Yx66oV9fJnriXwCcXwDDUA2kj6vnc6iDEp"
+                    .to_owned()
+            ]
         );
-    });
-}
-
-#[test]
-fn system_name() {
-    smol::block_on(async move {
-        let client = start_client().await;
 
         client.send_json_rpc_request(
-            r#"{"jsonrpc":"2.0","id":1,"method":"system_name","params":[]}"#.to_owned(),
+            r#"{"jsonrpc":"2.0","id":4,"method":"system_removeReservedPeer","params":["12D3KooWEyoppNCUx8Yx66oV9fJnriXwCcXwDDUA2kj6vnc6iDEp"]}"#
+                .to_owned(),
         );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "null");
 
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":5,"method":"system_reservedPeers","params":[]}"#.to_owned(),
+        );
         let response_raw = client.next_json_rpc_response().await;
         let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
             .unwrap()
             .into_success()
             .unwrap();
-        assert_eq!(
-            serde_json::from_str::<String>(result_json).unwrap(),
-            "smoldot-full-node"
+        assert_eq!(result_json, "[]");
+
+        // Addresses without a `/p2p` suffix are rejected.
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":6,"method":"system_addReservedPeer","params":["/ip4/127.0.0.1/tcp/30333"]}"#
+                .to_owned(),
         );
+        let response_raw = client.next_json_rpc_response().await;
+        assert!(matches!(
+            json_rpc::parse::parse_response(&response_raw).unwrap(),
+            json_rpc::parse::Response::Error { .. }
+        ));
     });
 }
 
@@ -589,15 +2264,45 @@ fn system_version() {
         );
 
         let response_raw = client.next_json_rpc_response().await;
-        // Note: we don't check the actual result, as the version changes pretty often.
-        json_rpc::parse::parse_response(&response_raw)
+        // Note: we don't check the exact result, as the version changes pretty often.
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        // The version always starts with the package version, optionally followed with the git
+        // commit.
+        assert!(serde_json::from_str::<String>(result_json)
+            .unwrap()
+            .starts_with(env!("CARGO_PKG_VERSION")));
+    });
+}
+
+#[test]
+fn state_query_storage_at_genesis() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        // The parent of the genesis block is considered as having an empty storage, and thus
+        // all the keys of the genesis storage are reported as changed.
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"state_queryStorageAt","params":[["0x3a636f6465", "0xdeadbeef"]]}"#
+                .to_owned(),
+        );
+        let response_raw = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
             .unwrap()
             .into_success()
             .unwrap();
+        let decoded = serde_json::from_str::<serde_json::Value>(result_json).unwrap();
+        let change_sets = decoded.as_array().unwrap();
+        assert_eq!(change_sets.len(), 1);
+        let changes = change_sets[0]["changes"].as_array().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0][0], "0x3a636f6465");
+        assert!(changes[0][1].as_str().unwrap().len() > 2);
     });
 }
 
 // TODO: add tests for `chain_subscribeAllHeads`
 // TODO: add tests for `chain_subscribeFinalizedHeads`
 // TODO: add tests for `chain_subscribeNewHeads`
-// TODO: add tests for `state_queryStorageAt`
@@ -0,0 +1,56 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use futures_lite::StreamExt as _;
+use std::sync::Arc;
+
+#[test]
+fn download_without_peer_can_be_resumed() {
+    smol::block_on(async move {
+        let client = smoldot_full_node::start(smoldot_full_node::Config {
+            chain: smoldot_full_node::ChainConfig {
+                chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
+                additional_bootnodes: Vec::new(),
+                keystore_memory: vec![],
+                sqlite_database_path: None,
+                sqlite_cache_size: 256 * 1024 * 1024,
+                keystore_path: None,
+                json_rpc_listen: None,
+                code_substitutes: Default::default(),
+                blocks_pruning: None,
+                genesis_storage: None,
+                verify_database_genesis: true,
+            },
+            relay_chain: None,
+            libp2p_key: Box::new([0; 32]),
+            listen_addresses: Vec::new(),
+            tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+            log_callback: Arc::new(move |_, _| {}),
+            jaeger_agent: None,
+        })
+        .await
+        .unwrap();
+
+        let mut download = Box::pin(client.download_finalized_storage(b":code".to_vec()).await);
+
+        // The node isn't connected to any peer, and the download thus fails immediately
+        // without yielding any entry.
+        let error = download.next().await.unwrap().unwrap_err();
+        assert_eq!(error.resume_key, b":code");
+        assert!(download.next().await.is_none());
+    });
+}
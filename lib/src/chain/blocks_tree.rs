@@ -62,7 +62,7 @@
 
 use crate::{
     chain::{chain_information, fork_tree},
-    header,
+    header, trie,
 };
 
 use alloc::{
@@ -120,6 +120,14 @@ pub struct NonFinalizedTree<T> {
     finalized_block_hash: [u8; 32],
     /// Number of [`NonFinalizedTree::finalized_block_header`].
     finalized_block_number: u64,
+    /// See [`chain_information::ChainInformation::header_hash_function`].
+    header_hash_function: trie::HashFunction,
+    /// See [`chain_information::ChainInformation::finalized_session_index`]. Reset to `None`
+    /// whenever a new block is finalized.
+    finalized_session_index: Option<u32>,
+    /// See [`chain_information::ChainInformation::finalized_beefy`]. Reset to `None` whenever a
+    /// new block is finalized.
+    finalized_beefy: Option<chain_information::BeefyInformation>,
     /// State of the chain finality engine.
     finality: Finality,
     /// State of the consensus of the finalized block.
@@ -166,9 +174,13 @@ impl<T> NonFinalizedTree<T> {
 
         NonFinalizedTree {
             finalized_block_number: chain_information.finalized_block_header.number,
-            finalized_block_hash: chain_information
-                .finalized_block_header
-                .hash(config.block_number_bytes),
+            finalized_block_hash: chain_information.finalized_block_header.hash_with_function(
+                config.block_number_bytes,
+                chain_information.header_hash_function,
+            ),
+            header_hash_function: chain_information.header_hash_function,
+            finalized_session_index: chain_information.finalized_session_index,
+            finalized_beefy: chain_information.finalized_beefy,
             finalized_block_header: chain_information
                 .finalized_block_header
                 .scale_encoding_vec(config.block_number_bytes),
@@ -194,16 +206,22 @@ impl<T> NonFinalizedTree<T> {
                 chain_information::ChainInformationConsensus::Aura {
                     finalized_authorities_list,
                     slot_duration,
+                    finalized_disabled_authorities,
                 } => FinalizedConsensus::Aura {
                     authorities_list: Arc::new(finalized_authorities_list),
                     slot_duration,
+                    disabled_authorities: finalized_disabled_authorities,
                 },
                 chain_information::ChainInformationConsensus::Babe {
                     finalized_block_epoch_information,
                     finalized_next_epoch_transition,
                     slots_per_epoch,
+                    slot_duration,
+                    finalized_disabled_authorities,
                 } => FinalizedConsensus::Babe {
                     slots_per_epoch,
+                    slot_duration,
+                    disabled_authorities: finalized_disabled_authorities,
                     block_epoch_information: finalized_block_epoch_information.map(Arc::from),
                     next_epoch_transition: Arc::from(finalized_next_epoch_transition),
                 },
@@ -288,6 +306,9 @@ impl<T> NonFinalizedTree<T> {
                 self.block_number_bytes,
             )
             .unwrap(),
+            header_hash_function: self.header_hash_function,
+            finalized_session_index: self.finalized_session_index,
+            finalized_beefy: self.finalized_beefy.as_ref().map(Into::into),
             consensus: match &self.finalized_consensus {
                 FinalizedConsensus::Unknown => {
                     chain_information::ChainInformationConsensusRef::Unknown
@@ -295,18 +316,24 @@ impl<T> NonFinalizedTree<T> {
                 FinalizedConsensus::Aura {
                     authorities_list,
                     slot_duration,
+                    disabled_authorities,
                 } => chain_information::ChainInformationConsensusRef::Aura {
                     finalized_authorities_list: header::AuraAuthoritiesIter::from_slice(
                         authorities_list,
                     ),
                     slot_duration: *slot_duration,
+                    finalized_disabled_authorities: disabled_authorities,
                 },
                 FinalizedConsensus::Babe {
                     block_epoch_information,
                     next_epoch_transition,
                     slots_per_epoch,
+                    slot_duration,
+                    disabled_authorities,
                 } => chain_information::ChainInformationConsensusRef::Babe {
                     slots_per_epoch: *slots_per_epoch,
+                    slot_duration: *slot_duration,
+                    finalized_disabled_authorities: disabled_authorities,
                     finalized_block_epoch_information: block_epoch_information
                         .as_ref()
                         .map(|info| From::from(&**info)),
@@ -390,10 +417,17 @@ impl<T> NonFinalizedTree<T> {
                 FinalizedConsensus::Aura {
                     authorities_list,
                     slot_duration,
+                    disabled_authorities,
                 },
                 None,
-            )
-            | (
+            ) => chain_information::ChainInformationConsensusRef::Aura {
+                finalized_authorities_list: header::AuraAuthoritiesIter::from_slice(
+                    authorities_list,
+                ),
+                slot_duration: *slot_duration,
+                finalized_disabled_authorities: disabled_authorities,
+            },
+            (
                 FinalizedConsensus::Aura { slot_duration, .. },
                 Some(BlockConsensus::Aura { authorities_list }),
             ) => chain_information::ChainInformationConsensusRef::Aura {
@@ -401,16 +435,22 @@ impl<T> NonFinalizedTree<T> {
                     authorities_list,
                 ),
                 slot_duration: *slot_duration,
+                // Disabled authorities are only tracked for the finalized block.
+                finalized_disabled_authorities: &[],
             },
             (
                 FinalizedConsensus::Babe {
                     block_epoch_information,
                     next_epoch_transition,
                     slots_per_epoch,
+                    slot_duration,
+                    disabled_authorities,
                 },
                 None,
             ) => chain_information::ChainInformationConsensusRef::Babe {
                 slots_per_epoch: *slots_per_epoch,
+                slot_duration: *slot_duration,
+                finalized_disabled_authorities: disabled_authorities,
                 finalized_block_epoch_information: block_epoch_information
                     .as_ref()
                     .map(|info| From::from(&**info)),
@@ -418,7 +458,9 @@ impl<T> NonFinalizedTree<T> {
             },
             (
                 FinalizedConsensus::Babe {
-                    slots_per_epoch, ..
+                    slots_per_epoch,
+                    slot_duration,
+                    ..
                 },
                 Some(BlockConsensus::Babe {
                     current_epoch,
@@ -426,6 +468,9 @@ impl<T> NonFinalizedTree<T> {
                 }),
             ) => chain_information::ChainInformationConsensusRef::Babe {
                 slots_per_epoch: *slots_per_epoch,
+                slot_duration: *slot_duration,
+                // Disabled authorities are only tracked for the finalized block.
+                finalized_disabled_authorities: &[],
                 finalized_block_epoch_information: current_epoch
                     .as_ref()
                     .map(|info| From::from(&**info)),
@@ -496,12 +541,7 @@ where
         f.debug_struct("NonFinalizedTree")
             .field(
                 "finalized_block_hash",
-                &format!(
-                    "0x{}",
-                    hex::encode(header::hash_from_scale_encoded_header(
-                        &self.finalized_block_header
-                    ))
-                ),
+                &format!("0x{}", hex::encode(self.finalized_block_hash)),
             )
             .field("non_finalized_blocks", &Blocks(self))
             .finish()
@@ -550,6 +590,9 @@ enum FinalizedConsensus {
 
         /// Duration, in milliseconds, of a slot.
         slot_duration: NonZero<u64>,
+
+        /// See [`chain_information::ChainInformationConsensus::Aura::finalized_disabled_authorities`].
+        disabled_authorities: Vec<u32>,
     },
     Babe {
         /// See [`chain_information::ChainInformationConsensus::Babe::finalized_block_epoch_information`].
@@ -560,6 +603,12 @@ enum FinalizedConsensus {
 
         /// See [`chain_information::ChainInformationConsensus::Babe::slots_per_epoch`].
         slots_per_epoch: NonZero<u64>,
+
+        /// See [`chain_information::ChainInformationConsensus::Babe::slot_duration`].
+        slot_duration: Option<NonZero<u64>>,
+
+        /// See [`chain_information::ChainInformationConsensus::Babe::finalized_disabled_authorities`].
+        disabled_authorities: Vec<u32>,
     },
 }
 
@@ -373,18 +373,25 @@ impl<T> NonFinalizedTree<T> {
         ) {
             (
                 FinalizedConsensus::Aura {
-                    authorities_list, ..
+                    authorities_list,
+                    disabled_authorities,
+                    ..
                 },
                 BlockConsensus::Aura {
                     authorities_list: new_list,
                 },
             ) => {
+                // Authorities are only disabled until the next change of authorities.
+                if **authorities_list != **new_list {
+                    disabled_authorities.clear();
+                }
                 *authorities_list = new_list.clone();
             }
             (
                 FinalizedConsensus::Babe {
                     block_epoch_information,
                     next_epoch_transition,
+                    disabled_authorities,
                     ..
                 },
                 BlockConsensus::Babe {
@@ -392,6 +399,12 @@ impl<T> NonFinalizedTree<T> {
                     next_epoch,
                 },
             ) => {
+                // Authorities are only disabled until the end of the epoch.
+                if block_epoch_information.as_ref().map(|e| e.epoch_index)
+                    != current_epoch.as_ref().map(|e| e.epoch_index)
+                {
+                    disabled_authorities.clear();
+                }
                 *block_epoch_information = current_epoch.clone();
                 *next_epoch_transition = next_epoch.clone();
             }
@@ -410,6 +423,11 @@ impl<T> NonFinalizedTree<T> {
         self.finalized_block_number = new_finalized_block.number;
         self.finalized_best_score = new_finalized_block.best_score;
 
+        // The session index and the BEEFY validator set aren't tracked by the tree and are
+        // unknown for the new finalized block.
+        self.finalized_session_index = None;
+        self.finalized_beefy = None;
+
         debug_assert_eq!(self.blocks.len(), self.blocks_by_hash.len());
         debug_assert_eq!(self.blocks.len(), self.blocks_by_best_score.len());
         debug_assert!(self.blocks.len() >= self.blocks_trigger_gp_change.len());
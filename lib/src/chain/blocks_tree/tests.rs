@@ -22,7 +22,7 @@
 use core::{num::NonZero, time::Duration};
 
 use super::{Config, HeaderVerifySuccess, NonFinalizedTree};
-use crate::{chain::chain_information, header};
+use crate::{chain::chain_information, header, trie};
 
 #[test]
 fn polkadot_blocks_0_to_2() {
@@ -44,8 +44,13 @@ fn polkadot_blocks_0_to_2() {
                 ],
                 digest: header::Digest::from(header::DigestRef::empty()),
             }),
+            header_hash_function: trie::HashFunction::Blake2,
+            finalized_session_index: None,
+            finalized_beefy: None,
             consensus: chain_information::ChainInformationConsensus::Babe {
                 slots_per_epoch: NonZero::<u64>::new(2400).unwrap(),
+                slot_duration: None,
+                finalized_disabled_authorities: Vec::new(),
                 finalized_block_epoch_information: None,
                 finalized_next_epoch_transition: Box::new(
                     chain_information::BabeEpochInformation {
@@ -257,8 +262,13 @@ fn kusama_blocks_0_to_2() {
                 ],
                 digest: header::Digest::from(header::DigestRef::empty()),
             }),
+            header_hash_function: trie::HashFunction::Blake2,
+            finalized_session_index: None,
+            finalized_beefy: None,
             consensus: chain_information::ChainInformationConsensus::Babe {
                 slots_per_epoch: NonZero::<u64>::new(600).unwrap(),
+                slot_duration: None,
+                finalized_disabled_authorities: Vec::new(),
                 finalized_block_epoch_information: None,
                 finalized_next_epoch_transition: Box::new(
                     chain_information::BabeEpochInformation {
@@ -440,3 +450,87 @@ fn kusama_blocks_0_to_2() {
 
     tree.insert_verified_header(verified_header2, ());
 }
+
+#[test]
+fn keccak_header_hashes() {
+    let keypair = schnorrkel::MiniSecretKey::from_bytes(&[1; 32])
+        .unwrap()
+        .expand_to_keypair(schnorrkel::ExpansionMode::Ed25519);
+
+    let genesis = header::Header {
+        parent_hash: [0; 32],
+        number: 0,
+        state_root: [2; 32],
+        extrinsics_root: [3; 32],
+        digest: header::DigestRef::empty().into(),
+    };
+    let genesis_hash = genesis.hash_with_function(4, trie::HashFunction::Keccak256);
+
+    let mut tree = NonFinalizedTree::<()>::new(Config {
+        chain_information: chain_information::ChainInformation {
+            finalized_block_header: Box::new(genesis.clone()),
+            header_hash_function: trie::HashFunction::Keccak256,
+            finalized_session_index: None,
+            finalized_beefy: None,
+            consensus: chain_information::ChainInformationConsensus::Aura {
+                finalized_authorities_list: vec![header::AuraAuthority {
+                    public_key: keypair.public.to_bytes(),
+                }],
+                slot_duration: NonZero::<u64>::new(6000).unwrap(),
+                finalized_disabled_authorities: Vec::new(),
+            },
+            finality: chain_information::ChainInformationFinality::Outsourced,
+        }
+        .try_into()
+        .unwrap(),
+        blocks_capacity: 8,
+        block_number_bytes: 4,
+        allow_unknown_consensus_engines: false,
+    });
+    assert_eq!(*tree.finalized_block_hash(), genesis_hash);
+
+    // Builds a child of the genesis block whose parent hash and seal are calculated with the
+    // given hash function.
+    let build_block1 = |hash_function: trie::HashFunction| {
+        let mut digest = vec![header::DigestItem::AuraPreDigest(header::AuraPreDigest {
+            slot_number: 1,
+        })];
+        let unsealed = header::Header {
+            parent_hash: genesis.hash_with_function(4, hash_function),
+            number: 1,
+            state_root: [4; 32],
+            extrinsics_root: [5; 32],
+            digest: header::DigestRef::from_slice(&digest).unwrap().into(),
+        };
+        let signature =
+            keypair.sign_simple(b"substrate", &unsealed.hash_with_function(4, hash_function));
+        digest.push(header::DigestItem::AuraSeal(signature.to_bytes()));
+        header::Header {
+            digest: header::DigestRef::from_slice(&digest).unwrap().into(),
+            ..unsealed
+        }
+    };
+
+    assert!(tree
+        .verify_header(
+            build_block1(trie::HashFunction::Blake2).scale_encoding_vec(4),
+            Duration::from_secs(60),
+        )
+        .is_err());
+
+    let block1 = build_block1(trie::HashFunction::Keccak256);
+    let verified_header = match tree
+        .verify_header(block1.scale_encoding_vec(4), Duration::from_secs(60))
+        .unwrap()
+    {
+        HeaderVerifySuccess::Verified {
+            verified_header, ..
+        } => verified_header,
+        _ => panic!(),
+    };
+    tree.insert_verified_header(verified_header, ());
+    assert_eq!(
+        *tree.best_block_hash(),
+        block1.hash_with_function(4, trie::HashFunction::Keccak256)
+    );
+}
@@ -47,7 +47,10 @@ impl<T> NonFinalizedTree<T> {
             Err(err) => return Err(HeaderVerifyError::InvalidHeader(err)),
         };
 
-        let hash = header::hash_from_scale_encoded_header(&scale_encoded_header);
+        let hash = header::hash_from_scale_encoded_header_vectored_with_function(
+            self.header_hash_function,
+            core::iter::once(&scale_encoded_header),
+        );
 
         // Check for duplicates.
         if self.blocks_by_hash.contains_key(&hash) {
@@ -175,6 +178,7 @@ impl<T> NonFinalizedTree<T> {
                 allow_unknown_consensus_engines: self.allow_unknown_consensus_engines,
                 block_header: decoded_header.clone(),
                 block_number_bytes: self.block_number_bytes,
+                header_hash_function: self.header_hash_function,
                 parent_block_header: {
                     // All headers inserted in `self` are necessarily valid, and thus this
                     // `unwrap()` can't panic.
@@ -575,6 +579,12 @@ impl VerifiedHeader {
     pub fn into_scale_encoded_header(self) -> Vec<u8> {
         self.scale_encoded_header
     }
+
+    /// Returns the hash of the block header, calculated with the header hash function of the
+    /// chain.
+    pub fn hash(&self) -> &[u8; 32] {
+        &self.hash
+    }
 }
 
 impl fmt::Debug for VerifiedHeader {
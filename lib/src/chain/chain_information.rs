@@ -38,17 +38,27 @@
 //! They also do not contain the past history of the chain. It is, however, similarly possible to
 //! for instance download the history from other nodes.
 
-use crate::header;
+use crate::{
+    finality::{decode, verify},
+    header,
+    informant::HashDisplay,
+    trie,
+    verify::{aura, babe},
+};
 
-use alloc::{boxed::Box, vec::Vec};
-use core::num::NonZero;
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{fmt::Write as _, iter, num::NonZero, time::Duration};
 
 pub mod build;
+pub mod replay;
+mod scale;
+
+pub use scale::DecodeError;
 
 /// Information about the latest finalized block and state found in its ancestors.
 ///
 /// Similar to [`ChainInformation`], but guaranteed to be coherent.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ValidChainInformation {
     inner: ChainInformation,
 }
@@ -64,6 +74,120 @@ impl ValidChainInformation {
     pub fn as_ref(&self) -> ChainInformationRef {
         From::from(&self.inner)
     }
+
+    /// Returns the number of the finalized block.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smoldot::{chain::chain_information, header, trie};
+    ///
+    /// let info = chain_information::ValidChainInformation::try_from(
+    ///     chain_information::ChainInformation {
+    ///         finalized_block_header: Box::new(header::Header {
+    ///             parent_hash: [0; 32],
+    ///             number: 5,
+    ///             state_root: [0; 32],
+    ///             extrinsics_root: [0; 32],
+    ///             digest: header::DigestRef::empty().into(),
+    ///         }),
+    ///         header_hash_function: trie::HashFunction::Blake2,
+    ///         finalized_session_index: None,
+    ///         finalized_beefy: None,
+    ///         consensus: chain_information::ChainInformationConsensus::Unknown,
+    ///         finality: chain_information::ChainInformationFinality::Outsourced,
+    ///     },
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(info.finalized_block_number(), 5);
+    /// ```
+    pub fn finalized_block_number(&self) -> u64 {
+        self.inner.finalized_block_header.number
+    }
+
+    /// Returns the randomness of the epoch that follows the epoch of the finalized block.
+    ///
+    /// Returns `None` if the chain doesn't use Babe.
+    pub fn babe_next_epoch_randomness(&self) -> Option<&[u8; 32]> {
+        match &self.inner.consensus {
+            ChainInformationConsensus::Babe {
+                finalized_next_epoch_transition,
+                ..
+            } => Some(&finalized_next_epoch_transition.randomness),
+            _ => None,
+        }
+    }
+
+    /// Returns the list of authorities of the epoch that follows the epoch of the finalized
+    /// block.
+    ///
+    /// Returns `None` if the chain doesn't use Babe.
+    pub fn babe_next_epoch_authorities(&self) -> Option<header::BabeAuthoritiesIter<'_>> {
+        match &self.inner.consensus {
+            ChainInformationConsensus::Babe {
+                finalized_next_epoch_transition,
+                ..
+            } => Some(header::BabeAuthoritiesIter::from_slice(
+                &finalized_next_epoch_transition.authorities,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Returns the public key of the Babe authority at the given index, in the epoch the given
+    /// slot belongs to.
+    ///
+    /// The authority index is typically found in the pre-runtime digest of a Babe header. Only
+    /// the epoch of the finalized block and the one that follows are known. `None` is returned
+    /// if the slot isn't within one of these two epochs, if the index is out of range, or if
+    /// the chain doesn't use Babe.
+    ///
+    /// If the finalized block is the genesis block, the first epoch starts at the slot of block
+    /// #1, which isn't known yet. It is therefore impossible to determine which epoch any slot
+    /// belongs to, and `None` is always returned. Use
+    /// [`ValidChainInformation::babe_next_epoch_authorities`] to obtain the authorities of the
+    /// first epoch.
+    pub fn babe_authority_public_key(&self, slot: u64, authority_index: u32) -> Option<&[u8; 32]> {
+        let ChainInformationConsensus::Babe {
+            slots_per_epoch,
+            finalized_block_epoch_information,
+            finalized_next_epoch_transition,
+            ..
+        } = &self.inner.consensus
+        else {
+            return None;
+        };
+
+        let epoch = match (
+            finalized_block_epoch_information,
+            finalized_next_epoch_transition.start_slot_number,
+        ) {
+            // The start slot of the next epoch is unknown. This is only possible when the
+            // finalized block is the genesis block, in which case the next epoch starts at the
+            // slot of block #1. Any slot could belong to this epoch or to one of the epochs that
+            // follow.
+            (_, None) => return None,
+            (_, Some(next_start)) if slot >= next_start => {
+                if slot - next_start >= slots_per_epoch.get() {
+                    return None;
+                }
+                finalized_next_epoch_transition
+            }
+            (Some(current), Some(_)) => {
+                if current.start_slot_number.is_some_and(|start| slot < start) {
+                    return None;
+                }
+                current
+            }
+            (None, Some(_)) => return None,
+        };
+
+        epoch
+            .authorities
+            .get(usize::try_from(authority_index).ok()?)
+            .map(|authority| &authority.public_key)
+    }
 }
 
 impl<'a> From<ValidChainInformationRef<'a>> for ValidChainInformation {
@@ -86,7 +210,7 @@ impl TryFrom<ChainInformation> for ValidChainInformation {
 /// Information about the latest finalized block and state found in its ancestors.
 ///
 /// Similar to [`ChainInformationRef`], but guaranteed to be coherent.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ValidChainInformationRef<'a> {
     inner: ChainInformationRef<'a>,
 }
@@ -113,14 +237,405 @@ impl<'a> ValidChainInformationRef<'a> {
     pub fn as_ref(&self) -> ChainInformationRef<'a> {
         self.inner.clone()
     }
+
+    /// Returns the number of the finalized block.
+    ///
+    /// See also [`ValidChainInformation::finalized_block_number`].
+    pub fn finalized_block_number(&self) -> u64 {
+        self.inner.finalized_block_header.number
+    }
+
+    /// Returns the number of the block that is expected to be the child of the finalized block,
+    /// in other words the finalized block number plus one.
+    ///
+    /// Saturates at `u64::MAX` if the finalized block number is `u64::MAX`.
+    pub fn next_block_number(&self) -> u64 {
+        self.inner.finalized_block_header.number.saturating_add(1)
+    }
+
+    /// Returns the index of the Babe epoch the finalized block belongs to.
+    ///
+    /// Returns `None` if the chain doesn't use Babe, or if the finalized block is the genesis
+    /// block, as the genesis block doesn't belong to any epoch.
+    pub fn babe_finalized_epoch_index(&self) -> Option<u64> {
+        match &self.inner.consensus {
+            ChainInformationConsensusRef::Babe {
+                finalized_block_epoch_information: Some(epoch),
+                ..
+            } => Some(epoch.epoch_index),
+            _ => None,
+        }
+    }
+
+    /// Returns a one-line human-readable description of the information, suitable for logging.
+    ///
+    /// The format is `consensus=<engine>[; epoch=<index>]; finalized_block_number=<number>;
+    /// finalized_block_hash=<hash>; finality=<engine>[; grandpa_set_id=<id>]`, where `epoch` is
+    /// only present for Babe and `grandpa_set_id` only for Grandpa. The epoch is `none` if the
+    /// finalized block is the genesis block.
+    ///
+    /// `block_number_bytes` is the number of bytes used to encode the block number in the
+    /// header, and is necessary in order to calculate the hash of the finalized block.
+    pub fn summary(&self, block_number_bytes: usize) -> String {
+        let mut out = String::new();
+
+        match &self.inner.consensus {
+            ChainInformationConsensusRef::Unknown => out.push_str("consensus=unknown"),
+            ChainInformationConsensusRef::Aura { .. } => out.push_str("consensus=aura"),
+            ChainInformationConsensusRef::Babe {
+                finalized_block_epoch_information,
+                ..
+            } => {
+                out.push_str("consensus=babe; epoch=");
+                match finalized_block_epoch_information {
+                    Some(epoch) => {
+                        let _ = write!(out, "{}", epoch.epoch_index);
+                    }
+                    None => out.push_str("none"),
+                }
+            }
+        }
+
+        let _ = write!(
+            out,
+            "; finalized_block_number={}; finalized_block_hash={}",
+            self.inner.finalized_block_header.number,
+            HashDisplay(&self.inner.finalized_block_hash(block_number_bytes))
+        );
+
+        match &self.inner.finality {
+            ChainInformationFinalityRef::Outsourced => out.push_str("; finality=outsourced"),
+            ChainInformationFinalityRef::Grandpa {
+                after_finalized_block_authorities_set_id,
+                ..
+            } => {
+                let _ = write!(
+                    out,
+                    "; finality=grandpa; grandpa_set_id={after_finalized_block_authorities_set_id}"
+                );
+            }
+        }
+
+        out
+    }
+
+    /// Verifies that the seal of the given header, which must be a child of the finalized block,
+    /// has been produced by an authority that was allowed to author this block.
+    ///
+    /// For Aura, the seal must be a signature of the authority whose index is the slot number
+    /// modulo the number of authorities. For Babe, the seal must be a signature of the authority
+    /// found in the pre-runtime digest, and the VRF proof and primary slot threshold are checked
+    /// against the epoch the block belongs to. Nothing is checked if the consensus algorithm is
+    /// unknown.
+    ///
+    /// Blocks authored by one of the authorities that are disabled as of the finalized block are
+    /// rejected, unless the block belongs to a different Babe epoch than the finalized block, as
+    /// the list of disabled authorities only applies to the authorities of the finalized block.
+    ///
+    /// Only the authenticity of the authorship is checked. This function doesn't verify whether
+    /// the block can be finalized, nor whether its content is correct.
+    ///
+    /// `block_number_bytes` is the number of bytes used to encode the block number in the
+    /// header, and is necessary in order to calculate the hash that the seal signs.
+    pub fn verify_header_seal(
+        &self,
+        header: header::HeaderRef,
+        block_number_bytes: usize,
+    ) -> Result<(), SealError> {
+        match &self.inner.consensus {
+            ChainInformationConsensusRef::Unknown => Ok(()),
+            ChainInformationConsensusRef::Aura {
+                finalized_authorities_list,
+                finalized_disabled_authorities,
+                ..
+            } => {
+                let slot_number = header
+                    .digest
+                    .aura_pre_runtime()
+                    .ok_or(SealError::Aura(aura::VerifyError::MissingPreRuntimeDigest))?
+                    .slot_number;
+                aura::verify_seal(
+                    header,
+                    block_number_bytes,
+                    self.inner.header_hash_function,
+                    slot_number,
+                    finalized_authorities_list.clone(),
+                )
+                .map_err(SealError::Aura)?;
+
+                // `verify_seal` has succeeded, meaning that the list of authorities isn't empty.
+                let authority_index = slot_number
+                    % u64::try_from(finalized_authorities_list.len()).unwrap_or(u64::MAX);
+                if let Ok(authority_index) = u32::try_from(authority_index) {
+                    if finalized_disabled_authorities.contains(&authority_index) {
+                        return Err(SealError::DisabledAuthority { authority_index });
+                    }
+                }
+
+                Ok(())
+            }
+            ChainInformationConsensusRef::Babe {
+                slots_per_epoch,
+                finalized_block_epoch_information,
+                finalized_next_epoch_transition,
+                finalized_disabled_authorities,
+                ..
+            } => {
+                // The block belongs to the same epoch as the finalized block if and only if the
+                // finalized block belongs to an epoch and the block doesn't start a new epoch.
+                let same_epoch = finalized_block_epoch_information.is_some()
+                    && header.digest.babe_epoch_information().is_none();
+                let authority_index = header
+                    .digest
+                    .babe_pre_runtime()
+                    .map(|pre_runtime| pre_runtime.authority_index());
+
+                babe::verify_header(babe::VerifyConfig {
+                    header,
+                    block_number_bytes,
+                    header_hash_function: self.inner.header_hash_function,
+                    parent_block_header: self.inner.finalized_block_header.clone(),
+                    // Not used by the Babe verification.
+                    now_from_unix_epoch: Duration::ZERO,
+                    slots_per_epoch: *slots_per_epoch,
+                    parent_block_epoch: finalized_block_epoch_information.clone(),
+                    parent_block_next_epoch: finalized_next_epoch_transition.clone(),
+                })
+                .map_err(SealError::Babe)?;
+
+                if let (true, Some(authority_index)) = (same_epoch, authority_index) {
+                    if finalized_disabled_authorities.contains(&authority_index) {
+                        return Err(SealError::DisabledAuthority { authority_index });
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the Blake2-256 hash of the SCALE encoding of the information, as returned by
+    /// [`ChainInformationRef::scale_encoding_vec`], together with the version of the encoding
+    /// that has been hashed.
+    ///
+    /// This hash is meant to be stored alongside the information in order to later detect
+    /// accidental corruption or naive tampering using
+    /// [`ValidChainInformationRef::verify_integrity_hash`]. Since anyone capable of modifying the
+    /// information is also capable of recalculating the hash, it doesn't provide any form of
+    /// authentication.
+    ///
+    /// `block_number_bytes` is the number of bytes used to encode the block number in the
+    /// header.
+    pub fn integrity_hash(&self, block_number_bytes: usize) -> IntegrityHash {
+        IntegrityHash {
+            encoding_version: scale::CURRENT_VERSION,
+            hash: self.integrity_hash_with_version(block_number_bytes, scale::CURRENT_VERSION),
+        }
+    }
+
+    /// Verifies that the information matches a hash previously returned by
+    /// [`ValidChainInformationRef::integrity_hash`].
+    ///
+    /// The hash is calculated again using the version of the SCALE encoding found in `expected`,
+    /// which makes it possible to verify hashes calculated by older versions of this library.
+    pub fn verify_integrity_hash(
+        &self,
+        block_number_bytes: usize,
+        expected: &IntegrityHash,
+    ) -> Result<(), IntegrityHashError> {
+        if !(1..=scale::CURRENT_VERSION).contains(&expected.encoding_version) {
+            return Err(IntegrityHashError::UnsupportedVersion(
+                expected.encoding_version,
+            ));
+        }
+
+        if self.integrity_hash_with_version(block_number_bytes, expected.encoding_version)
+            != expected.hash
+        {
+            return Err(IntegrityHashError::Mismatch);
+        }
+
+        Ok(())
+    }
+
+    fn integrity_hash_with_version(&self, block_number_bytes: usize, version: u8) -> [u8; 32] {
+        let encoded = self
+            .inner
+            .scale_encoding_vec_with_version(block_number_bytes, version);
+        let mut out = [0; 32];
+        out.copy_from_slice(blake2_rfc::blake2b::blake2b(32, &[], &encoded).as_bytes());
+        out
+    }
+
+    /// Verifies that the given storage of the finalized block matches the state root found in
+    /// the finalized block header.
+    ///
+    /// `top_trie_entries` contains the entries of the main trie, excluding the ones that
+    /// correspond to child tries. `child_tries` contains, for each child trie, its key (without
+    /// the `:child_storage:default:` prefix) and its entries. The Merkle value of each non-empty
+    /// child trie is inserted in the main trie before the state root is calculated.
+    pub fn verify_storage(
+        &self,
+        state_version: trie::TrieEntryVersion,
+        top_trie_entries: &[(impl AsRef<[u8]>, impl AsRef<[u8]>)],
+        child_tries: &[(impl AsRef<[u8]>, &[(impl AsRef<[u8]>, impl AsRef<[u8]>)])],
+    ) -> Result<(), VerifyStorageError> {
+        let mut entries = top_trie_entries
+            .iter()
+            .map(|(k, v)| (k.as_ref().to_vec(), v.as_ref().to_vec()))
+            .collect::<Vec<_>>();
+
+        if entries
+            .iter()
+            .any(|(k, _)| k.starts_with(CHILD_STORAGE_DEFAULT_PREFIX))
+        {
+            return Err(VerifyStorageError::ChildTrieKeyInTopTrie);
+        }
+
+        for (child_trie, child_trie_entries) in child_tries {
+            if child_trie_entries.is_empty() {
+                continue;
+            }
+
+            let child_root = trie::trie_root(
+                state_version,
+                trie::HashFunction::Blake2,
+                child_trie_entries.iter().map(|(k, v)| (k, v)),
+            );
+            let mut key = CHILD_STORAGE_DEFAULT_PREFIX.to_vec();
+            key.extend_from_slice(child_trie.as_ref());
+            entries.push((key, child_root.to_vec()));
+        }
+
+        let obtained = trie::trie_root(state_version, trie::HashFunction::Blake2, entries);
+        let expected = *self.inner.finalized_block_header.state_root;
+        if obtained != expected {
+            return Err(VerifyStorageError::StateRootMismatch { expected, obtained });
+        }
+
+        Ok(())
+    }
+
+    /// Verifies that the given SCALE-encoded Grandpa justification finalizes the given block,
+    /// which must be a descendant of the finalized block.
+    ///
+    /// The pre-commits of the justification must be signed by at least two thirds of the
+    /// authorities that are in charge of finalizing the target block, using the authorities set
+    /// id of these authorities.
+    ///
+    /// If a Grandpa authorities change is scheduled at a height strictly inferior to the
+    /// target block, then the block that triggers this change must be finalized first, and
+    /// [`JustificationError::TooFarAhead`] is returned.
+    ///
+    /// The headers found in the votes ancestries of the justification are used in order to check
+    /// that the target block descends from the finalized block. If these headers don't go back
+    /// all the way to the finalized block, only the target block height is checked.
+    ///
+    /// A randomness seed must be provided and will be used during the verification. Note that the
+    /// verification is nonetheless deterministic.
+    pub fn verify_grandpa_justification(
+        &self,
+        block_hash: &[u8; 32],
+        block_number: u64,
+        scale_encoded_justification: &[u8],
+        block_number_bytes: usize,
+        randomness_seed: [u8; 32],
+    ) -> Result<(), JustificationError> {
+        let ChainInformationFinalityRef::Grandpa {
+            after_finalized_block_authorities_set_id,
+            finalized_triggered_authorities,
+            finalized_scheduled_change,
+        } = self.inner.finality
+        else {
+            return Err(JustificationError::NotGrandpa);
+        };
+
+        let decoded =
+            decode::decode_grandpa_justification(scale_encoded_justification, block_number_bytes)
+                .map_err(JustificationError::InvalidJustification)?;
+
+        if decoded.target_hash != block_hash || decoded.target_number != block_number {
+            return Err(JustificationError::TargetMismatch);
+        }
+
+        let finalized_block_number = self.inner.finalized_block_header.number;
+        if block_number <= finalized_block_number {
+            return Err(JustificationError::NotDescendant);
+        }
+
+        // Walk the votes ancestries backwards from the target block. If the finalized block
+        // height is reached, the parent of the earliest header must be the finalized block.
+        let mut current = *block_hash;
+        for _ in 0..decoded.votes_ancestries.len() {
+            let Some(header) = decoded.votes_ancestries.clone().find(|h| {
+                h.hash_with_function(block_number_bytes, self.inner.header_hash_function) == current
+            }) else {
+                break;
+            };
+
+            if header.number <= finalized_block_number {
+                return Err(JustificationError::NotDescendant);
+            }
+
+            if header.number == finalized_block_number + 1 {
+                if *header.parent_hash != self.inner.finalized_block_hash(block_number_bytes) {
+                    return Err(JustificationError::NotDescendant);
+                }
+                break;
+            }
+
+            current = *header.parent_hash;
+        }
+
+        if let Some((trigger_block_number, _)) = finalized_scheduled_change {
+            if trigger_block_number < block_number {
+                return Err(JustificationError::TooFarAhead {
+                    block_to_finalize_number: trigger_block_number,
+                });
+            }
+        }
+
+        verify::verify_justification(verify::JustificationVerifyConfig {
+            justification: scale_encoded_justification,
+            block_number_bytes,
+            authorities_set_id: after_finalized_block_authorities_set_id,
+            authorities_list: finalized_triggered_authorities
+                .iter()
+                .map(|a| &a.public_key[..]),
+            randomness_seed,
+        })
+        .map_err(JustificationError::VerificationFailed)
+    }
 }
 
+/// Prefix of the keys of the main trie under which the roots of the child tries are stored.
+const CHILD_STORAGE_DEFAULT_PREFIX: &[u8] = b":child_storage:default:";
+
 /// Information about the latest finalized block and state found in its ancestors.
 #[derive(Debug, Clone)]
 pub struct ChainInformation {
     /// Header of the highest known finalized block.
     pub finalized_block_header: Box<header::Header>,
 
+    /// Hash function used to calculate the hash of the block headers of the chain. This is
+    /// [`trie::HashFunction::Blake2`] for the overwhelming majority of the chains, but some
+    /// chains, such as Frontier-based chains, might use [`trie::HashFunction::Keccak256`].
+    pub header_hash_function: trie::HashFunction,
+
+    /// Index of the session of the `session` pallet the finalized block belongs to, or `None`
+    /// if unknown or if the chain doesn't use sessions.
+    ///
+    /// This value isn't used when verifying blocks and is purely informative. It can be used
+    /// for example in order to correlate Babe epochs or staking eras with sessions.
+    pub finalized_session_index: Option<u32>,
+
+    /// Information about the BEEFY validator set, or `None` if unknown or if the chain doesn't
+    /// use BEEFY.
+    ///
+    /// BEEFY runs on top of the finality engine, and this value isn't used when verifying or
+    /// finalizing blocks.
+    pub finalized_beefy: Option<BeefyInformation>,
+
     /// Extra items that depend on the consensus engine.
     pub consensus: ChainInformationConsensus,
 
@@ -128,27 +643,109 @@ pub struct ChainInformation {
     pub finality: ChainInformationFinality,
 }
 
+impl PartialEq for ChainInformation {
+    fn eq(&self, other: &ChainInformation) -> bool {
+        ChainInformationRef::from(self) == ChainInformationRef::from(other)
+    }
+}
+
+impl Eq for ChainInformation {}
+
+impl ChainInformation {
+    /// Converts information about a chain whose block numbers are `from_block_number_bytes`
+    /// bytes wide into information about a chain whose block numbers are `to_block_number_bytes`
+    /// bytes wide.
+    ///
+    /// The number of the finalized block, the block numbers found in the Grandpa digest items
+    /// of its header, and the block number of the pending Grandpa scheduled change, are checked
+    /// to fit within both widths. The decoded header doesn't depend on the width, and is copied
+    /// as is. It is encoded with `to_block_number_bytes` when calling
+    /// [`header::Header::scale_encoding`] or [`header::Header::hash`].
+    ///
+    /// > **Note**: Since the Grandpa digest items encode block numbers using a fixed number of
+    /// >           bytes, the hash of the finalized block might differ between the two widths.
+    pub fn recode(
+        &self,
+        from_block_number_bytes: usize,
+        to_block_number_bytes: usize,
+    ) -> Result<ChainInformation, RecodeError> {
+        let header = header::HeaderRef::from(&*self.finalized_block_header);
+
+        let block_numbers = iter::once(header.number)
+            .chain(header.digest.logs().flat_map(|log| {
+                let (first, second) = match log {
+                    header::DigestItemRef::GrandpaConsensus(
+                        header::GrandpaConsensusLogRef::ScheduledChange(change),
+                    ) => (Some(change.delay), None),
+                    header::DigestItemRef::GrandpaConsensus(
+                        header::GrandpaConsensusLogRef::ForcedChange {
+                            reset_block_height,
+                            change,
+                        },
+                    ) => (Some(reset_block_height), Some(change.delay)),
+                    header::DigestItemRef::GrandpaConsensus(
+                        header::GrandpaConsensusLogRef::Pause(delay)
+                        | header::GrandpaConsensusLogRef::Resume(delay),
+                    ) => (Some(delay), None),
+                    _ => (None, None),
+                };
+                first.into_iter().chain(second)
+            }))
+            .chain(match &self.finality {
+                ChainInformationFinality::Grandpa {
+                    finalized_scheduled_change: Some((trigger_block_height, _)),
+                    ..
+                } => Some(*trigger_block_height),
+                _ => None,
+            });
+
+        let fits = |block_number: u64, block_number_bytes: usize| {
+            block_number_bytes >= 8 || block_number >> (block_number_bytes * 8) == 0
+        };
+
+        for block_number in block_numbers {
+            if !fits(block_number, from_block_number_bytes) {
+                return Err(RecodeError::SourceBlockNumberOverflow(block_number));
+            }
+            if !fits(block_number, to_block_number_bytes) {
+                return Err(RecodeError::TargetBlockNumberOverflow(block_number));
+            }
+        }
+
+        Ok(self.clone())
+    }
+}
+
 impl<'a> From<ChainInformationRef<'a>> for ChainInformation {
     fn from(info: ChainInformationRef<'a>) -> ChainInformation {
         ChainInformation {
             finalized_block_header: Box::new(info.finalized_block_header.into()),
+            header_hash_function: info.header_hash_function,
+            finalized_session_index: info.finalized_session_index,
+            finalized_beefy: info.finalized_beefy.map(Into::into),
             consensus: match info.consensus {
                 ChainInformationConsensusRef::Unknown => ChainInformationConsensus::Unknown,
                 ChainInformationConsensusRef::Aura {
                     finalized_authorities_list,
                     slot_duration,
+                    finalized_disabled_authorities,
                 } => ChainInformationConsensus::Aura {
                     finalized_authorities_list: finalized_authorities_list
                         .map(|a| a.into())
                         .collect(),
                     slot_duration,
+                    finalized_disabled_authorities: finalized_disabled_authorities.to_vec(),
                 },
                 ChainInformationConsensusRef::Babe {
                     slots_per_epoch,
+                    slot_duration,
+                    finalized_disabled_authorities,
                     finalized_next_epoch_transition,
                     finalized_block_epoch_information,
                 } => ChainInformationConsensus::Babe {
                     slots_per_epoch,
+                    slot_duration,
+                    finalized_disabled_authorities: finalized_disabled_authorities.to_vec(),
                     finalized_block_epoch_information: finalized_block_epoch_information
                         .map(|i| Box::new(i.into())),
                     finalized_next_epoch_transition: Box::new(
@@ -162,7 +759,7 @@ impl<'a> From<ChainInformationRef<'a>> for ChainInformation {
 }
 
 /// Extra items that depend on the consensus engine.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ChainInformationConsensus {
     /// Any node on the chain is allowed to produce blocks.
     ///
@@ -179,6 +776,13 @@ pub enum ChainInformationConsensus {
 
         /// Duration, in milliseconds, of an Aura slot.
         slot_duration: NonZero<u64>,
+
+        /// Indices within [`ChainInformationConsensus::Aura::finalized_authorities_list`] of the
+        /// authorities that are temporarily disabled, for example following a slashing. Blocks
+        /// authored by these authorities must be rejected.
+        ///
+        /// Empty if no authority is disabled.
+        finalized_disabled_authorities: Vec<u32>,
     },
 
     /// Chain is using the Babe consensus engine.
@@ -186,6 +790,25 @@ pub enum ChainInformationConsensus {
         /// Number of slots per epoch. Configured at the genesis block and never touched later.
         slots_per_epoch: NonZero<u64>,
 
+        /// Duration, in milliseconds, of a Babe slot, as found by calling the
+        /// `BabeApi_configuration` runtime function. Configured at the genesis block and never
+        /// touched later.
+        ///
+        /// `None` if unknown, for example if the chain information was built by an older version
+        /// of this library.
+        slot_duration: Option<NonZero<u64>>,
+
+        /// Indices within the authorities of the epoch of the finalized block of the authorities
+        /// that are temporarily disabled, for example following a slashing. Blocks authored by
+        /// these authorities must be rejected.
+        ///
+        /// If [`ChainInformationConsensus::Babe::finalized_block_epoch_information`] is `None`,
+        /// the indices refer to the authorities of
+        /// [`ChainInformationConsensus::Babe::finalized_next_epoch_transition`] instead.
+        ///
+        /// Empty if no authority is disabled.
+        finalized_disabled_authorities: Vec<u32>,
+
         /// Babe epoch information about the epoch the finalized block belongs to.
         ///
         /// If the finalized block belongs to epoch #0, which starts at block #1, then this must
@@ -214,8 +837,45 @@ pub enum ChainInformationConsensus {
     },
 }
 
+impl ChainInformationConsensus {
+    /// Builds a [`ChainInformationConsensus::Babe`] suitable for a chain information whose
+    /// finalized block is the genesis block, from the output of the `BabeApi_configuration`
+    /// runtime call.
+    ///
+    /// The configuration is turned into the future epoch number 0, whose start slot isn't known
+    /// yet.
+    ///
+    /// Returns an error if the configuration doesn't pass the checks performed by
+    /// [`ChainInformationRef::validate`].
+    pub fn babe_from_configuration(
+        config: build::BabeGenesisConfiguration,
+    ) -> Result<Self, ValidityError> {
+        let epoch0 = BabeEpochInformation {
+            epoch_index: 0,
+            start_slot_number: None,
+            authorities: config.epoch0_information.authorities,
+            randomness: config.epoch0_information.randomness,
+            c: config.epoch0_configuration.c,
+            allowed_slots: config.epoch0_configuration.allowed_slots,
+        };
+
+        epoch0.validate().map_err(ValidityError::InvalidBabe)?;
+        if contains_duplicate_public_key(epoch0.authorities.iter().map(|a| &a.public_key)) {
+            return Err(ValidityError::DuplicateBabeAuthority);
+        }
+
+        Ok(ChainInformationConsensus::Babe {
+            slots_per_epoch: config.slots_per_epoch,
+            slot_duration: config.slot_duration,
+            finalized_disabled_authorities: Vec::new(),
+            finalized_block_epoch_information: None,
+            finalized_next_epoch_transition: Box::new(epoch0),
+        })
+    }
+}
+
 /// Information about a Babe epoch.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BabeEpochInformation {
     /// Index of the epoch.
     ///
@@ -269,8 +929,30 @@ impl<'a> From<BabeEpochInformationRef<'a>> for BabeEpochInformation {
     }
 }
 
+/// Information about the BEEFY validator set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BeefyInformation {
+    /// BEEFY validator set ID of the block right after the finalized block.
+    ///
+    /// If the finalized block is the genesis block, should be 0.
+    pub validator_set_id: u64,
+
+    /// SEC1-compressed ECDSA public keys of the BEEFY validators of the block right after the
+    /// finalized block. Must never be empty.
+    pub validators: Vec<[u8; 33]>,
+}
+
+impl<'a> From<BeefyInformationRef<'a>> for BeefyInformation {
+    fn from(info: BeefyInformationRef<'a>) -> BeefyInformation {
+        BeefyInformation {
+            validator_set_id: info.validator_set_id,
+            validators: info.validators.to_vec(),
+        }
+    }
+}
+
 /// Extra items that depend on the finality engine.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ChainInformationFinality {
     /// Blocks themselves don't contain any information concerning finality. Finality is provided
     /// by a mechanism that is entirely external to the chain.
@@ -328,12 +1010,94 @@ impl<'a> From<ChainInformationFinalityRef<'a>> for ChainInformationFinality {
     }
 }
 
+impl ChainInformationFinality {
+    /// See [`ChainInformationFinalityRef::is_self_contained`].
+    pub fn is_self_contained(&self) -> bool {
+        ChainInformationFinalityRef::from(self).is_self_contained()
+    }
+
+    /// Applies a list of Grandpa authorities changes, each in the format of
+    /// [`ChainInformationFinality::Grandpa::finalized_scheduled_change`], and returns the
+    /// resulting finality information.
+    ///
+    /// `finalized_block_number` is the number of the block that is now finalized. The changes
+    /// must be ordered by strictly increasing block number, and must all be triggered by blocks
+    /// whose number is inferior or equal to `finalized_block_number`. The authorities set id is
+    /// incremented by one for each change.
+    ///
+    /// If [`ChainInformationFinality::Grandpa::finalized_scheduled_change`] is `Some` and is
+    /// triggered by a block whose number is inferior or equal to `finalized_block_number`, then
+    /// the first element of `changes` must be equal to it, as Grandpa doesn't allow scheduling a
+    /// change while another one is pending.
+    pub fn apply_scheduled_changes(
+        &self,
+        finalized_block_number: u64,
+        changes: &[(u64, Vec<header::GrandpaAuthority>)],
+    ) -> Result<ChainInformationFinality, ApplyScheduledChangesError> {
+        let ChainInformationFinality::Grandpa {
+            after_finalized_block_authorities_set_id,
+            finalized_scheduled_change,
+            ..
+        } = self
+        else {
+            return if changes.is_empty() {
+                Ok(ChainInformationFinality::Outsourced)
+            } else {
+                Err(ApplyScheduledChangesError::Outsourced)
+            };
+        };
+
+        if changes
+            .iter()
+            .any(|(trigger_block_height, _)| *trigger_block_height > finalized_block_number)
+        {
+            return Err(ApplyScheduledChangesError::NotFinalized);
+        }
+
+        if let Some(pending) = finalized_scheduled_change {
+            let mismatch = match changes.first() {
+                Some(first) => first != pending,
+                None => pending.0 <= finalized_block_number,
+            };
+            if mismatch {
+                return Err(ApplyScheduledChangesError::PendingChangeMismatch);
+            }
+        }
+
+        if changes.windows(2).any(|w| w[0].0 >= w[1].0) {
+            return Err(ApplyScheduledChangesError::NotStrictlyIncreasing);
+        }
+
+        let Some((_, last_authorities)) = changes.last() else {
+            return Ok(self.clone());
+        };
+
+        Ok(ChainInformationFinality::Grandpa {
+            after_finalized_block_authorities_set_id: u64::try_from(changes.len())
+                .ok()
+                .and_then(|n| after_finalized_block_authorities_set_id.checked_add(n))
+                .ok_or(ApplyScheduledChangesError::SetIdOverflow)?,
+            finalized_triggered_authorities: last_authorities.clone(),
+            finalized_scheduled_change: None,
+        })
+    }
+}
+
 /// Equivalent to a [`ChainInformation`] but referencing an existing structure. Cheap to copy.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ChainInformationRef<'a> {
     /// See equivalent field in [`ChainInformation`].
     pub finalized_block_header: header::HeaderRef<'a>,
 
+    /// See equivalent field in [`ChainInformation`].
+    pub header_hash_function: trie::HashFunction,
+
+    /// See equivalent field in [`ChainInformation`].
+    pub finalized_session_index: Option<u32>,
+
+    /// See equivalent field in [`ChainInformation`].
+    pub finalized_beefy: Option<BeefyInformationRef<'a>>,
+
     /// Extra items that depend on the consensus engine.
     pub consensus: ChainInformationConsensusRef<'a>,
 
@@ -342,18 +1106,58 @@ pub struct ChainInformationRef<'a> {
 }
 
 impl<'a> ChainInformationRef<'a> {
+    /// Returns the hash of the finalized block, calculated using
+    /// [`ChainInformationRef::header_hash_function`].
+    pub fn finalized_block_hash(&self, block_number_bytes: usize) -> [u8; 32] {
+        self.finalized_block_header
+            .hash_with_function(block_number_bytes, self.header_hash_function)
+    }
+
+    /// Returns `true` if this chain information is equal to the given [`ChainInformation`].
+    ///
+    /// Only the logical content is compared: the finalized block header, the consensus
+    /// algorithm and its authorities, and the finality algorithm and its authorities. Whether
+    /// the fields are borrowed or owned doesn't matter.
+    pub fn eq_owned(&self, other: &ChainInformation) -> bool {
+        *self == ChainInformationRef::from(other)
+    }
+
     /// Checks whether the information is coherent.
     pub fn validate(&self) -> Result<(), ValidityError> {
         if let ChainInformationConsensusRef::Babe {
             finalized_next_epoch_transition,
             finalized_block_epoch_information,
+            finalized_disabled_authorities,
+            slots_per_epoch,
             ..
         } = &self.consensus
         {
+            let num_authorities = finalized_block_epoch_information
+                .as_ref()
+                .unwrap_or(finalized_next_epoch_transition)
+                .authorities
+                .len();
+            if let Some(index) = finalized_disabled_authorities
+                .iter()
+                .find(|idx| usize::try_from(**idx).map_or(true, |idx| idx >= num_authorities))
+            {
+                return Err(ValidityError::DisabledAuthorityOutOfRange(*index));
+            }
+
             if let Err(err) = finalized_next_epoch_transition.validate() {
                 return Err(ValidityError::InvalidBabe(err));
             }
 
+            if finalized_block_epoch_information
+                .iter()
+                .chain(iter::once(finalized_next_epoch_transition))
+                .any(|epoch| {
+                    contains_duplicate_public_key(epoch.authorities.clone().map(|a| a.public_key))
+                })
+            {
+                return Err(ValidityError::DuplicateBabeAuthority);
+            }
+
             if finalized_next_epoch_transition.start_slot_number.is_some()
                 && (finalized_next_epoch_transition.epoch_index == 0)
             {
@@ -365,6 +1169,26 @@ impl<'a> ChainInformationRef<'a> {
                 return Err(ValidityError::MissingBabeSlotStartNumber);
             }
 
+            // The next epoch must start at the end of the current epoch or, if no block has been
+            // produced during one or more entire epochs, at the end of a later epoch. In other
+            // words, the two start slots must be separated by a non-zero whole number of epochs.
+            if let (Some(current_epoch_start_slot), Some(next_epoch_start_slot)) = (
+                finalized_block_epoch_information
+                    .as_ref()
+                    .and_then(|epoch| epoch.start_slot_number),
+                finalized_next_epoch_transition.start_slot_number,
+            ) {
+                if next_epoch_start_slot <= current_epoch_start_slot
+                    || (next_epoch_start_slot - current_epoch_start_slot) % slots_per_epoch.get()
+                        != 0
+                {
+                    return Err(ValidityError::BabeEpochSlotDiscontinuity {
+                        current_epoch_start_slot,
+                        next_epoch_start_slot,
+                    });
+                }
+            }
+
             if let Some(finalized_block_epoch_information) = &finalized_block_epoch_information {
                 if let Err(err) = finalized_block_epoch_information.validate() {
                     return Err(ValidityError::InvalidBabe(err));
@@ -389,9 +1213,16 @@ impl<'a> ChainInformationRef<'a> {
                     } else if self.finalized_block_header.number != 0 {
                         return Err(ValidityError::ConsensusAlgorithmMismatch);
                     }
+                    // Note that Aura consensus log items are tolerated, as the chain might have
+                    // switched from Aura to Babe. Only the authorship of the block matters.
                     if (self.finalized_block_header.digest.babe_seal().is_some()
                         != (self.finalized_block_header.number != 0))
-                        || self.finalized_block_header.digest.has_any_aura()
+                        || self
+                            .finalized_block_header
+                            .digest
+                            .aura_pre_runtime()
+                            .is_some()
+                        || self.finalized_block_header.digest.aura_seal().is_some()
                     {
                         return Err(ValidityError::ConsensusAlgorithmMismatch);
                     }
@@ -416,7 +1247,29 @@ impl<'a> ChainInformationRef<'a> {
             }
         }
 
-        if let ChainInformationConsensusRef::Aura { .. } = &self.consensus {
+        if let ChainInformationConsensusRef::Aura {
+            finalized_authorities_list,
+            finalized_disabled_authorities,
+            ..
+        } = &self.consensus
+        {
+            let num_authorities = finalized_authorities_list.len();
+            if let Some(index) = finalized_disabled_authorities
+                .iter()
+                .find(|idx| usize::try_from(**idx).map_or(true, |idx| idx >= num_authorities))
+            {
+                return Err(ValidityError::DisabledAuthorityOutOfRange(*index));
+            }
+
+            if contains_duplicate_public_key(
+                finalized_authorities_list.clone().map(|a| a.public_key),
+            ) {
+                return Err(ValidityError::DuplicateAuraAuthority);
+            }
+
+            // Note that Babe consensus log items are tolerated, as the block where a chain
+            // switches from Aura to Babe is authored using Aura but announces the first Babe
+            // epoch. Only the authorship of the block matters.
             if (self
                 .finalized_block_header
                 .digest
@@ -425,7 +1278,12 @@ impl<'a> ChainInformationRef<'a> {
                 != (self.finalized_block_header.number != 0))
                 || (self.finalized_block_header.digest.aura_seal().is_some()
                     != (self.finalized_block_header.number != 0))
-                || self.finalized_block_header.digest.has_any_babe()
+                || self
+                    .finalized_block_header
+                    .digest
+                    .babe_pre_runtime()
+                    .is_some()
+                || self.finalized_block_header.digest.babe_seal().is_some()
             {
                 return Err(ValidityError::ConsensusAlgorithmMismatch);
             }
@@ -433,14 +1291,63 @@ impl<'a> ChainInformationRef<'a> {
 
         if let ChainInformationFinalityRef::Grandpa {
             after_finalized_block_authorities_set_id,
+            finalized_triggered_authorities,
             finalized_scheduled_change,
-            ..
         } = &self.finality
         {
-            // TODO: check consistency with the finalized block header
-            if let Some(change) = finalized_scheduled_change.as_ref() {
-                if change.0 <= self.finalized_block_header.number {
-                    return Err(ValidityError::ScheduledGrandPaChangeBeforeFinalized);
+            if finalized_scheduled_change
+                .iter()
+                .map(|(_, authorities)| *authorities)
+                .chain(iter::once(*finalized_triggered_authorities))
+                .any(|authorities| {
+                    contains_duplicate_public_key(authorities.iter().map(|a| &a.public_key))
+                })
+            {
+                return Err(ValidityError::DuplicateGrandpaAuthority);
+            }
+
+            // If the finalized block header schedules a Grandpa authorities change that isn't
+            // triggered immediately, and that the chain information knows about a scheduled
+            // change, then they must be the same. Note that the chain information is allowed to
+            // not know about the change.
+            if let (Some((trigger_block_height, scheduled_authorities)), Some(in_header)) = (
+                finalized_scheduled_change.as_ref(),
+                grandpa_scheduled_change_in_header(self.finalized_block_header.clone()),
+            ) {
+                if in_header.0 > self.finalized_block_header.number
+                    && (*trigger_block_height != in_header.0
+                        || **scheduled_authorities != in_header.1[..])
+                {
+                    return Err(ValidityError::GrandpaScheduledChangeMismatch);
+                }
+            }
+
+            if let Some((trigger_block_height, _)) = finalized_scheduled_change.as_ref() {
+                // A change whose trigger block is the finalized block or one of its ancestors
+                // has already been applied, meaning that the set id has already been incremented
+                // and that the change shouldn't be scheduled anymore.
+                if *trigger_block_height <= self.finalized_block_header.number {
+                    return Err(ValidityError::ScheduledGrandPaChangeBeforeFinalized {
+                        trigger_block_height: *trigger_block_height,
+                        finalized_block_height: self.finalized_block_header.number,
+                    });
+                }
+
+                // The chain information doesn't keep track of forced changes, but the header of
+                // the finalized block might signal a forced change that isn't triggered yet.
+                // Both changes increase the authorities set id by one. If they are triggered by
+                // the same block, it is ambiguous which list of authorities and which set id
+                // apply after this block.
+                if let Some(forced_trigger_block_height) =
+                    grandpa_forced_change_trigger_in_header(self.finalized_block_header.clone())
+                {
+                    if forced_trigger_block_height > self.finalized_block_header.number
+                        && forced_trigger_block_height == *trigger_block_height
+                    {
+                        return Err(ValidityError::ConflictingGrandpaChanges {
+                            trigger_block_height: *trigger_block_height,
+                        });
+                    }
                 }
             }
             if self.finalized_block_header.number == 0
@@ -448,6 +1355,23 @@ impl<'a> ChainInformationRef<'a> {
             {
                 return Err(ValidityError::FinalizedZeroButNonZeroAuthoritiesSetId);
             }
+        } else if self
+            .finalized_block_header
+            .digest
+            .logs()
+            .any(|item| matches!(item, header::DigestItemRef::GrandpaConsensus(_)))
+        {
+            // Grandpa log items can only be emitted by chains that use Grandpa.
+            return Err(ValidityError::FinalityAlgorithmMismatch);
+        }
+
+        if let Some(beefy) = &self.finalized_beefy {
+            if beefy.validators.is_empty() {
+                return Err(ValidityError::BeefyEmptyValidatorSet);
+            }
+            if self.finalized_block_header.number == 0 && beefy.validator_set_id != 0 {
+                return Err(ValidityError::BeefyZeroButNonZeroSetId);
+            }
         }
 
         Ok(())
@@ -458,23 +1382,32 @@ impl<'a> From<&'a ChainInformation> for ChainInformationRef<'a> {
     fn from(info: &'a ChainInformation) -> ChainInformationRef<'a> {
         ChainInformationRef {
             finalized_block_header: (&*info.finalized_block_header).into(),
+            header_hash_function: info.header_hash_function,
+            finalized_session_index: info.finalized_session_index,
+            finalized_beefy: info.finalized_beefy.as_ref().map(Into::into),
             consensus: match &info.consensus {
                 ChainInformationConsensus::Unknown => ChainInformationConsensusRef::Unknown,
                 ChainInformationConsensus::Aura {
                     finalized_authorities_list,
                     slot_duration,
+                    finalized_disabled_authorities,
                 } => ChainInformationConsensusRef::Aura {
                     finalized_authorities_list: header::AuraAuthoritiesIter::from_slice(
                         finalized_authorities_list,
                     ),
                     slot_duration: *slot_duration,
+                    finalized_disabled_authorities,
                 },
                 ChainInformationConsensus::Babe {
                     slots_per_epoch,
+                    slot_duration,
+                    finalized_disabled_authorities,
                     finalized_block_epoch_information,
                     finalized_next_epoch_transition,
                 } => ChainInformationConsensusRef::Babe {
                     slots_per_epoch: *slots_per_epoch,
+                    slot_duration: *slot_duration,
+                    finalized_disabled_authorities,
                     finalized_block_epoch_information: finalized_block_epoch_information
                         .as_ref()
                         .map(|i| (&**i).into()),
@@ -487,7 +1420,7 @@ impl<'a> From<&'a ChainInformation> for ChainInformationRef<'a> {
 }
 
 /// Extra items that depend on the consensus engine.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ChainInformationConsensusRef<'a> {
     /// See [`ChainInformationConsensus::Unknown`].
     Unknown,
@@ -499,6 +1432,9 @@ pub enum ChainInformationConsensusRef<'a> {
 
         /// See equivalent field in [`ChainInformationConsensus`].
         slot_duration: NonZero<u64>,
+
+        /// See equivalent field in [`ChainInformationConsensus`].
+        finalized_disabled_authorities: &'a [u32],
     },
 
     /// Chain is using the Babe consensus engine.
@@ -506,6 +1442,12 @@ pub enum ChainInformationConsensusRef<'a> {
         /// See equivalent field in [`ChainInformationConsensus`].
         slots_per_epoch: NonZero<u64>,
 
+        /// See equivalent field in [`ChainInformationConsensus`].
+        slot_duration: Option<NonZero<u64>>,
+
+        /// See equivalent field in [`ChainInformationConsensus`].
+        finalized_disabled_authorities: &'a [u32],
+
         /// See equivalent field in [`ChainInformationConsensus`].
         finalized_block_epoch_information: Option<BabeEpochInformationRef<'a>>,
 
@@ -514,13 +1456,39 @@ pub enum ChainInformationConsensusRef<'a> {
     },
 }
 
-/// Information about a Babe epoch.
-#[derive(Debug, Clone)]
-pub struct BabeEpochInformationRef<'a> {
-    /// See equivalent field in [`BabeEpochInformation`].
-    pub epoch_index: u64,
+impl<'a> ChainInformationConsensusRef<'a> {
+    /// Returns the duration, in milliseconds, of a slot, if known.
+    ///
+    /// Always returns `Some` for Aura. For Babe, returns `Some` if the slot duration was
+    /// provided when building the chain information.
+    pub fn slot_duration(&self) -> Option<NonZero<u64>> {
+        match self {
+            ChainInformationConsensusRef::Unknown => None,
+            ChainInformationConsensusRef::Aura { slot_duration, .. } => Some(*slot_duration),
+            ChainInformationConsensusRef::Babe { slot_duration, .. } => *slot_duration,
+        }
+    }
 
-    /// See equivalent field in [`BabeEpochInformation`].
+    /// Returns the time, relative to the UNIX epoch, at which the given slot starts.
+    ///
+    /// Both Aura and Babe slots start at `slot_number * slot_duration` after the UNIX epoch.
+    ///
+    /// Returns `None` if the slot duration isn't known, or if the calculation overflows.
+    pub fn slot_start_unix_time(&self, slot_number: u64) -> Option<Duration> {
+        let slot_duration = self.slot_duration()?;
+        Some(Duration::from_millis(
+            slot_number.checked_mul(slot_duration.get())?,
+        ))
+    }
+}
+
+/// Information about a Babe epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BabeEpochInformationRef<'a> {
+    /// See equivalent field in [`BabeEpochInformation`].
+    pub epoch_index: u64,
+
+    /// See equivalent field in [`BabeEpochInformation`].
     pub start_slot_number: Option<u64>,
 
     /// See equivalent field in [`BabeEpochInformation`].
@@ -560,8 +1528,27 @@ impl<'a> From<&'a BabeEpochInformation> for BabeEpochInformationRef<'a> {
     }
 }
 
+/// Equivalent to a [`BeefyInformation`] but referencing an existing structure. Cheap to copy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BeefyInformationRef<'a> {
+    /// See equivalent field in [`BeefyInformation`].
+    pub validator_set_id: u64,
+
+    /// See equivalent field in [`BeefyInformation`].
+    pub validators: &'a [[u8; 33]],
+}
+
+impl<'a> From<&'a BeefyInformation> for BeefyInformationRef<'a> {
+    fn from(info: &'a BeefyInformation) -> BeefyInformationRef<'a> {
+        BeefyInformationRef {
+            validator_set_id: info.validator_set_id,
+            validators: &info.validators,
+        }
+    }
+}
+
 /// Extra items that depend on the finality engine.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ChainInformationFinalityRef<'a> {
     /// See equivalent variant in [`ChainInformationFinality`].
     Outsourced,
@@ -598,6 +1585,155 @@ impl<'a> From<&'a ChainInformationFinality> for ChainInformationFinalityRef<'a>
     }
 }
 
+impl<'a> ChainInformationFinalityRef<'a> {
+    /// Returns `true` if the finality of the chain can be verified using only the chain itself,
+    /// in other words if blocks are finalized through justifications that can be verified
+    /// locally. This is the case for Grandpa.
+    ///
+    /// Returns `false` for [`ChainInformationFinalityRef::Outsourced`]. This is the case of
+    /// parachains, whose blocks are finalized when the relay chain finalizes a block that
+    /// includes them. Justifications and warp sync proofs can't be used for these chains, and
+    /// the finalized block must instead be obtained from the relay chain.
+    pub fn is_self_contained(&self) -> bool {
+        match self {
+            ChainInformationFinalityRef::Outsourced => false,
+            ChainInformationFinalityRef::Grandpa { .. } => true,
+        }
+    }
+
+    /// Returns the identifier of the Grandpa authorities set in charge of finalizing the block
+    /// of the given height, in other words the set whose signatures are found in the
+    /// justification of this block.
+    ///
+    /// The block must be a descendant of the finalized block. The block that triggers the
+    /// [`ChainInformationFinalityRef::Grandpa::finalized_scheduled_change`] is still finalized by
+    /// the current set, and only its descendants are finalized by the next set.
+    ///
+    /// Returns `None` if the chain doesn't use Grandpa.
+    pub fn set_id_at(&self, block_number: u64) -> Option<u64> {
+        match self {
+            ChainInformationFinalityRef::Outsourced => None,
+            ChainInformationFinalityRef::Grandpa {
+                after_finalized_block_authorities_set_id,
+                finalized_scheduled_change: Some((trigger_block_height, _)),
+                ..
+            } if block_number > *trigger_block_height => {
+                Some(*after_finalized_block_authorities_set_id + 1)
+            }
+            ChainInformationFinalityRef::Grandpa {
+                after_finalized_block_authorities_set_id,
+                ..
+            } => Some(*after_finalized_block_authorities_set_id),
+        }
+    }
+}
+
+/// Returns the Grandpa authorities change scheduled by the given header, if any.
+///
+/// The returned value is the height of the block at which the change is triggered (i.e. the
+/// height of the header plus the delay of the change) and the new list of authorities. It has
+/// the same format as [`ChainInformationFinality::Grandpa::finalized_scheduled_change`].
+///
+/// If the header contains multiple `ScheduledChange` log items, only the first one is taken into
+/// account. Returns `None` if the height of the triggering block overflows.
+pub fn grandpa_scheduled_change_in_header(
+    header: header::HeaderRef,
+) -> Option<(u64, Vec<header::GrandpaAuthority>)> {
+    header.digest.logs().find_map(|item| match item {
+        header::DigestItemRef::GrandpaConsensus(
+            header::GrandpaConsensusLogRef::ScheduledChange(change),
+        ) => Some((
+            header.number.checked_add(change.delay)?,
+            change
+                .next_authorities
+                .map(header::GrandpaAuthority::from)
+                .collect(),
+        )),
+        _ => None,
+    })
+}
+
+/// Returns the height of the block at which the Grandpa forced authorities change signaled by
+/// the given header is triggered, if any.
+///
+/// If the header contains multiple `ForcedChange` log items, only the first one is taken into
+/// account. Returns `None` if the height of the triggering block overflows.
+fn grandpa_forced_change_trigger_in_header(header: header::HeaderRef) -> Option<u64> {
+    header.digest.logs().find_map(|item| match item {
+        header::DigestItemRef::GrandpaConsensus(header::GrandpaConsensusLogRef::ForcedChange {
+            change,
+            ..
+        }) => header.number.checked_add(change.delay),
+        _ => None,
+    })
+}
+
+/// Returns `true` if the same public key is found multiple times in the given list.
+fn contains_duplicate_public_key<'k>(public_keys: impl Iterator<Item = &'k [u8; 32]>) -> bool {
+    // The chain information might come from an untrusted source. A hasher with a randomized key
+    // is used in order to not be vulnerable to HashDoS attacks.
+    #[cfg(feature = "std")]
+    {
+        let mut found = hashbrown::HashSet::with_capacity_and_hasher(
+            public_keys.size_hint().0,
+            std::collections::hash_map::RandomState::new(),
+        );
+        for public_key in public_keys {
+            if !found.insert(public_key) {
+                return true;
+            }
+        }
+        false
+    }
+
+    // Without the standard library, no source of randomness is available. The list is sorted
+    // instead, which doesn't have any worst case that an attacker could exploit.
+    #[cfg(not(feature = "std"))]
+    {
+        let mut public_keys = public_keys.collect::<Vec<_>>();
+        public_keys.sort_unstable();
+        public_keys.windows(2).any(|keys| keys[0] == keys[1])
+    }
+}
+
+/// Hash of a chain information. See [`ValidChainInformationRef::integrity_hash`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityHash {
+    /// Version of the SCALE encoding of the chain information that has been hashed. Must be
+    /// stored alongside of [`IntegrityHash::hash`].
+    pub encoding_version: u8,
+    /// Blake2-256 hash of the SCALE encoding of the chain information.
+    pub hash: [u8; 32],
+}
+
+/// Error potentially returned by [`ValidChainInformationRef::verify_integrity_hash`].
+#[derive(Debug, derive_more::Display)]
+pub enum IntegrityHashError {
+    /// The hash has been calculated using a version of the SCALE encoding that this library
+    /// doesn't know about.
+    #[display(fmt = "Unsupported chain information encoding version: {_0}")]
+    UnsupportedVersion(u8),
+    /// The chain information doesn't match the hash.
+    Mismatch,
+}
+
+/// Error potentially returned by [`ValidChainInformationRef::verify_header_seal`].
+#[derive(Debug, derive_more::Display)]
+pub enum SealError {
+    /// Failed to verify the Aura seal of the header.
+    #[display(fmt = "Invalid Aura seal: {_0}")]
+    Aura(aura::VerifyError),
+    /// Failed to verify the Babe seal of the header.
+    #[display(fmt = "Invalid Babe seal: {_0}")]
+    Babe(babe::VerifyError),
+    /// The seal is valid, but the authority that has produced it is disabled.
+    #[display(fmt = "Header sealed by disabled authority #{authority_index}")]
+    DisabledAuthority {
+        /// Index of the authority within the list of authorities.
+        authority_index: u32,
+    },
+}
+
 /// Error when turning a [`ChainInformation`] into a [`ValidChainInformation`].
 #[derive(Debug, derive_more::Display)]
 pub enum ValidityError {
@@ -618,13 +1754,132 @@ pub enum ValidityError {
     HeaderBabeSlotInferiorToEpochStartSlot,
     /// Mismatch between the finalized block header digest and the Babe next epoch information.
     BabeEpochInfoMismatch,
-    /// Scheduled GrandPa authorities change is before finalized block.
-    ScheduledGrandPaChangeBeforeFinalized,
+    /// The start slot of the next Babe epoch isn't equal to the start slot of the current epoch
+    /// plus a non-zero multiple of the number of slots per epoch.
+    #[display(
+        fmt = "Babe next epoch start slot {next_epoch_start_slot} doesn't follow the current \
+        epoch starting at slot {current_epoch_start_slot}"
+    )]
+    BabeEpochSlotDiscontinuity {
+        /// Start slot of the epoch of the finalized block.
+        current_epoch_start_slot: u64,
+        /// Start slot of the epoch that follows.
+        next_epoch_start_slot: u64,
+    },
+    /// Scheduled GrandPa authorities change is triggered at or before the finalized block, and
+    /// should thus have already been applied.
+    #[display(
+        fmt = "Scheduled GrandPa change at block #{trigger_block_height} isn't after the \
+        finalized block #{finalized_block_height}"
+    )]
+    ScheduledGrandPaChangeBeforeFinalized {
+        /// Height of the block at which the change is scheduled to be triggered.
+        trigger_block_height: u64,
+        /// Height of the finalized block.
+        finalized_block_height: u64,
+    },
+    /// The scheduled GrandPa change and the forced GrandPa change signaled by the finalized
+    /// block header are both triggered by the same block, making it ambiguous which authorities
+    /// and which set id apply after this block.
+    #[display(
+        fmt = "Scheduled and forced GrandPa changes are both triggered at block \
+        #{trigger_block_height}"
+    )]
+    ConflictingGrandpaChanges {
+        /// Height of the block at which both changes are triggered.
+        trigger_block_height: u64,
+    },
     /// The finalized block is block number 0, but the GrandPa authorities set id is not 0.
     FinalizedZeroButNonZeroAuthoritiesSetId,
+    /// The finalized block header contains GrandPa log items, but the chain information doesn't
+    /// use GrandPa.
+    FinalityAlgorithmMismatch,
+    /// The GrandPa authorities change scheduled by the finalized block header doesn't match the
+    /// scheduled change of the chain information.
+    GrandpaScheduledChangeMismatch,
     /// Error in a Babe epoch information.
     #[display(fmt = "Error in a Babe epoch information: {_0}")]
     InvalidBabe(BabeValidityError),
+    /// One of the indices of disabled authorities is out of range of the list of authorities.
+    #[display(fmt = "Disabled authority index {_0} is out of range")]
+    DisabledAuthorityOutOfRange(u32),
+    /// The same public key is found multiple times in the list of Aura authorities.
+    DuplicateAuraAuthority,
+    /// The same public key is found multiple times in the list of authorities of a Babe epoch.
+    DuplicateBabeAuthority,
+    /// The same public key is found multiple times in a list of GrandPa authorities.
+    DuplicateGrandpaAuthority,
+    /// The list of BEEFY validators is empty.
+    BeefyEmptyValidatorSet,
+    /// The finalized block is block number 0, but the BEEFY validator set id is not 0.
+    BeefyZeroButNonZeroSetId,
+}
+
+/// Error potentially returned by [`ChainInformation::recode`].
+#[derive(Debug, derive_more::Display)]
+pub enum RecodeError {
+    /// A block number doesn't fit within the source number of bytes.
+    #[display(fmt = "Block number {_0} doesn't fit within the source number of bytes")]
+    SourceBlockNumberOverflow(u64),
+    /// A block number doesn't fit within the target number of bytes.
+    #[display(fmt = "Block number {_0} doesn't fit within the target number of bytes")]
+    TargetBlockNumberOverflow(u64),
+}
+
+/// Error potentially returned by [`ChainInformationFinality::apply_scheduled_changes`].
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::Display)]
+pub enum ApplyScheduledChangesError {
+    /// Finality is [`ChainInformationFinality::Outsourced`] and doesn't have any authorities.
+    Outsourced,
+    /// Block numbers of the changes aren't strictly increasing.
+    NotStrictlyIncreasing,
+    /// A change is triggered by a block that isn't finalized yet.
+    NotFinalized,
+    /// The change that was already scheduled is triggered by a finalized block, but the first
+    /// change doesn't match it.
+    PendingChangeMismatch,
+    /// The authorities set id overflowed.
+    SetIdOverflow,
+}
+
+/// Error potentially returned by [`ValidChainInformationRef::verify_storage`].
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::Display)]
+pub enum VerifyStorageError {
+    /// One of the entries of the main trie is in the child tries namespace. Child tries must be
+    /// passed separately.
+    ChildTrieKeyInTopTrie,
+    /// The storage doesn't match the state root of the finalized block.
+    #[display(fmt = "State root mismatch")]
+    StateRootMismatch {
+        /// State root found in the finalized block header.
+        expected: [u8; 32],
+        /// State root calculated from the storage.
+        obtained: [u8; 32],
+    },
+}
+
+/// Error potentially returned by [`ValidChainInformationRef::verify_grandpa_justification`].
+#[derive(Debug, derive_more::Display)]
+pub enum JustificationError {
+    /// Chain doesn't use the Grandpa algorithm.
+    NotGrandpa,
+    /// Error while decoding the justification.
+    #[display(fmt = "Error while decoding the justification: {_0}")]
+    InvalidJustification(decode::JustificationDecodeError),
+    /// The block targeted by the justification isn't the one that was expected.
+    TargetMismatch,
+    /// The block targeted by the justification isn't a descendant of the finalized block.
+    NotDescendant,
+    /// A Grandpa authorities change is triggered by a block between the finalized block and the
+    /// target block. This block must be finalized first.
+    #[display(fmt = "Block #{block_to_finalize_number} must be finalized first")]
+    TooFarAhead {
+        /// Number of the block to finalize first.
+        block_to_finalize_number: u64,
+    },
+    /// The justification verification has failed.
+    #[display(fmt = "{_0}")]
+    VerificationFailed(verify::JustificationVerifyError),
 }
 
 /// Error when checking the validity of a Babe epoch.
@@ -634,3 +1889,1340 @@ pub enum BabeValidityError {
     /// denominator.
     InvalidConstant,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ApplyScheduledChangesError, BabeEpochInformation, BeefyInformation, ChainInformation,
+        ChainInformationConsensus, ChainInformationFinality, ChainInformationFinalityRef,
+        ChainInformationRef, IntegrityHash, IntegrityHashError, JustificationError, RecodeError,
+        SealError, ValidChainInformation, ValidChainInformationRef, ValidityError,
+        VerifyStorageError,
+    };
+    use crate::{header, informant::HashDisplay, trie, verify::aura};
+    use alloc::{boxed::Box, format, vec, vec::Vec};
+    use core::{num::NonZero, time::Duration};
+
+    fn example() -> ChainInformation {
+        ChainInformation {
+            finalized_block_header: Box::new(header::Header {
+                parent_hash: [1; 32],
+                number: 12,
+                state_root: [2; 32],
+                extrinsics_root: [3; 32],
+                digest: header::DigestRef::empty().into(),
+            }),
+            header_hash_function: trie::HashFunction::Blake2,
+            finalized_session_index: None,
+            finalized_beefy: None,
+            consensus: ChainInformationConsensus::Aura {
+                finalized_authorities_list: vec![
+                    header::AuraAuthority {
+                        public_key: [4; 32],
+                    },
+                    header::AuraAuthority {
+                        public_key: [5; 32],
+                    },
+                ],
+                slot_duration: NonZero::<u64>::new(6000).unwrap(),
+                finalized_disabled_authorities: Vec::new(),
+            },
+            finality: ChainInformationFinality::Grandpa {
+                after_finalized_block_authorities_set_id: 3,
+                finalized_triggered_authorities: vec![header::GrandpaAuthority {
+                    public_key: [6; 32],
+                    weight: NonZero::<u64>::new(1).unwrap(),
+                }],
+                finalized_scheduled_change: None,
+            },
+        }
+    }
+
+    #[test]
+    fn recode() {
+        let info = example();
+        assert_eq!(info.recode(4, 8).unwrap(), info);
+
+        let mut info = example();
+        info.finalized_block_header.number = 1 << 32;
+        assert!(matches!(
+            info.recode(8, 4),
+            Err(RecodeError::TargetBlockNumberOverflow(n)) if n == 1 << 32
+        ));
+        assert!(matches!(
+            info.recode(4, 8),
+            Err(RecodeError::SourceBlockNumberOverflow(n)) if n == 1 << 32
+        ));
+    }
+
+    #[test]
+    fn recode_grandpa_digest() {
+        // Polkadot block #512271, which schedules a Grandpa change.
+        let mut info = example();
+        *info.finalized_block_header =
+            header::decode(include_bytes!("../header/tests/header-polkadot-512271"), 4)
+                .unwrap()
+                .into();
+
+        let recoded = info.recode(4, 8).unwrap();
+        assert_eq!(recoded, info);
+        assert_ne!(
+            recoded.finalized_block_header.hash(8),
+            info.finalized_block_header.hash(4)
+        );
+        assert_eq!(
+            recoded.finalized_block_header.scale_encoding_vec(8).len(),
+            info.finalized_block_header.scale_encoding_vec(4).len() + 4
+        );
+    }
+
+    #[test]
+    fn grandpa_scheduled_change_in_header() {
+        // Polkadot block #512271, which schedules a Grandpa change with a delay of 0.
+        let header =
+            header::decode(include_bytes!("../header/tests/header-polkadot-512271"), 4).unwrap();
+
+        let (trigger_block, authorities) =
+            super::grandpa_scheduled_change_in_header(header).unwrap();
+        assert_eq!(trigger_block, 512271);
+        assert_eq!(authorities.len(), 197);
+        assert!(authorities.iter().all(|a| a.weight.get() == 1));
+
+        let info = example();
+        assert!(
+            super::grandpa_scheduled_change_in_header((&*info.finalized_block_header).into())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn finality_is_self_contained() {
+        let info = example();
+        assert!(info.finality.is_self_contained());
+        assert!(ChainInformationFinalityRef::from(&info.finality).is_self_contained());
+
+        assert!(!ChainInformationFinality::Outsourced.is_self_contained());
+        assert!(!ChainInformationFinalityRef::Outsourced.is_self_contained());
+    }
+
+    #[test]
+    fn finality_set_id_at() {
+        let mut info = example();
+        let finality = ChainInformationFinalityRef::from(&info.finality);
+        assert_eq!(finality.set_id_at(121), Some(3));
+        assert_eq!(finality.set_id_at(10_000), Some(3));
+
+        if let ChainInformationFinality::Grandpa {
+            finalized_scheduled_change,
+            ..
+        } = &mut info.finality
+        {
+            *finalized_scheduled_change = Some((
+                125,
+                vec![header::GrandpaAuthority {
+                    public_key: [7; 32],
+                    weight: NonZero::<u64>::new(1).unwrap(),
+                }],
+            ));
+        }
+
+        let finality = ChainInformationFinalityRef::from(&info.finality);
+        assert_eq!(finality.set_id_at(121), Some(3));
+        assert_eq!(finality.set_id_at(124), Some(3));
+        assert_eq!(finality.set_id_at(125), Some(3));
+        assert_eq!(finality.set_id_at(126), Some(4));
+        assert_eq!(finality.set_id_at(10_000), Some(4));
+
+        assert_eq!(ChainInformationFinalityRef::Outsourced.set_id_at(121), None);
+    }
+
+    #[test]
+    fn apply_scheduled_changes() {
+        let authorities = |n: u8| {
+            vec![header::GrandpaAuthority {
+                public_key: [n; 32],
+                weight: NonZero::<u64>::new(1).unwrap(),
+            }]
+        };
+
+        let finality = ChainInformationFinality::Grandpa {
+            after_finalized_block_authorities_set_id: 5,
+            finalized_triggered_authorities: authorities(1),
+            finalized_scheduled_change: Some((20, authorities(2))),
+        };
+
+        assert_eq!(
+            finality
+                .apply_scheduled_changes(30, &[(20, authorities(2)), (30, authorities(3))])
+                .unwrap(),
+            ChainInformationFinality::Grandpa {
+                after_finalized_block_authorities_set_id: 7,
+                finalized_triggered_authorities: authorities(3),
+                finalized_scheduled_change: None,
+            }
+        );
+
+        // The pending change isn't triggered yet.
+        assert_eq!(finality.apply_scheduled_changes(19, &[]).unwrap(), finality);
+
+        assert_eq!(
+            finality
+                .apply_scheduled_changes(29, &[(20, authorities(2)), (30, authorities(3))])
+                .unwrap_err(),
+            ApplyScheduledChangesError::NotFinalized
+        );
+
+        assert_eq!(
+            finality
+                .apply_scheduled_changes(30, &[(20, authorities(2)), (20, authorities(3))])
+                .unwrap_err(),
+            ApplyScheduledChangesError::NotStrictlyIncreasing
+        );
+
+        assert_eq!(
+            finality
+                .apply_scheduled_changes(30, &[(25, authorities(2))])
+                .unwrap_err(),
+            ApplyScheduledChangesError::PendingChangeMismatch
+        );
+
+        // The pending change is triggered by a finalized block but is missing.
+        assert_eq!(
+            finality.apply_scheduled_changes(20, &[]).unwrap_err(),
+            ApplyScheduledChangesError::PendingChangeMismatch
+        );
+
+        assert_eq!(
+            ChainInformationFinality::Outsourced
+                .apply_scheduled_changes(30, &[(25, authorities(2))])
+                .unwrap_err(),
+            ApplyScheduledChangesError::Outsourced
+        );
+    }
+
+    #[test]
+    fn disabled_authorities_in_range() {
+        let mut info = example();
+        info.finalized_block_header.number = 0;
+        info.finality = ChainInformationFinality::Outsourced;
+        if let ChainInformationConsensus::Aura {
+            finalized_disabled_authorities,
+            ..
+        } = &mut info.consensus
+        {
+            finalized_disabled_authorities.push(1);
+        }
+        assert!(ChainInformationRef::from(&info).validate().is_ok());
+
+        if let ChainInformationConsensus::Aura {
+            finalized_disabled_authorities,
+            ..
+        } = &mut info.consensus
+        {
+            finalized_disabled_authorities.push(2);
+        }
+        assert!(matches!(
+            ChainInformationRef::from(&info).validate(),
+            Err(ValidityError::DisabledAuthorityOutOfRange(2))
+        ));
+    }
+
+    #[test]
+    fn slot_start_unix_time() {
+        let info = example();
+        let consensus = ChainInformationRef::from(&info).consensus;
+        assert_eq!(consensus.slot_duration(), NonZero::<u64>::new(6000));
+        assert_eq!(
+            consensus.slot_start_unix_time(10),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(consensus.slot_start_unix_time(u64::MAX), None);
+    }
+
+    #[test]
+    fn equal_to_clone() {
+        let info = example();
+        assert_eq!(info, info.clone());
+        assert!(ChainInformationRef::from(&info).eq_owned(&info));
+    }
+
+    #[test]
+    fn decoded_header_equal_to_owned() {
+        let info = example();
+        let encoded = info.finalized_block_header.scale_encoding_vec(4);
+
+        let info_ref = ChainInformationRef {
+            finalized_block_header: header::decode(&encoded, 4).unwrap(),
+            ..ChainInformationRef::from(&info)
+        };
+
+        assert!(info_ref.eq_owned(&info));
+        assert_eq!(ChainInformation::from(info_ref), info);
+    }
+
+    #[test]
+    fn different_authorities() {
+        let info = example();
+
+        let mut other = info.clone();
+        if let ChainInformationConsensus::Aura {
+            finalized_authorities_list,
+            ..
+        } = &mut other.consensus
+        {
+            finalized_authorities_list.pop();
+        }
+        assert_ne!(info, other);
+        assert!(!ChainInformationRef::from(&other).eq_owned(&info));
+
+        let mut other = info.clone();
+        if let ChainInformationFinality::Grandpa {
+            after_finalized_block_authorities_set_id,
+            ..
+        } = &mut other.finality
+        {
+            *after_finalized_block_authorities_set_id += 1;
+        }
+        assert_ne!(info, other);
+    }
+
+    fn babe_epoch(epoch_index: u64, start_slot_number: Option<u64>) -> BabeEpochInformation {
+        BabeEpochInformation {
+            epoch_index,
+            start_slot_number,
+            authorities: vec![header::BabeAuthority {
+                public_key: [7; 32],
+                weight: 1,
+            }],
+            randomness: [8; 32],
+            c: (1, 4),
+            allowed_slots: header::BabeAllowedSlots::PrimaryAndSecondaryPlainSlots,
+        }
+    }
+
+    fn header_with_digest(digest: &[header::DigestItem]) -> Box<header::Header> {
+        Box::new(header::Header {
+            parent_hash: [1; 32],
+            number: 120,
+            state_root: [2; 32],
+            extrinsics_root: [3; 32],
+            digest: header::DigestRef::from_slice(digest).unwrap().into(),
+        })
+    }
+
+    /// Returns a valid chain information using Babe, whose finalized block is in the first
+    /// epoch, which starts at slot 1000.
+    fn babe_chain_information() -> ChainInformation {
+        ChainInformation {
+            finalized_block_header: header_with_digest(&[
+                header::DigestItem::BabePreDigest(header::BabePreDigest::SecondaryPlain(
+                    header::BabeSecondaryPlainPreDigest {
+                        authority_index: 0,
+                        slot_number: 1003,
+                    },
+                )),
+                header::DigestItem::BabeSeal([0; 64]),
+            ]),
+            header_hash_function: trie::HashFunction::Blake2,
+            finalized_session_index: None,
+            finalized_beefy: None,
+            consensus: ChainInformationConsensus::Babe {
+                slots_per_epoch: NonZero::<u64>::new(10).unwrap(),
+                slot_duration: None,
+                finalized_disabled_authorities: Vec::new(),
+                finalized_block_epoch_information: Some(Box::new(babe_epoch(0, Some(1000)))),
+                finalized_next_epoch_transition: Box::new(babe_epoch(1, Some(1010))),
+            },
+            finality: ChainInformationFinality::Outsourced,
+        }
+    }
+
+    /// Same as [`babe_chain_information`], but the finalized block is the genesis block.
+    fn babe_genesis_chain_information() -> ChainInformation {
+        let mut info = babe_chain_information();
+        info.finalized_block_header = Box::new(header::Header {
+            parent_hash: [0; 32],
+            number: 0,
+            state_root: [2; 32],
+            extrinsics_root: [3; 32],
+            digest: header::DigestRef::empty().into(),
+        });
+        if let ChainInformationConsensus::Babe {
+            finalized_block_epoch_information,
+            finalized_next_epoch_transition,
+            ..
+        } = &mut info.consensus
+        {
+            *finalized_block_epoch_information = None;
+            **finalized_next_epoch_transition = babe_epoch(0, None);
+        }
+        info
+    }
+
+    #[test]
+    fn babe_finalized_after_switch_from_aura() {
+        // First Babe epoch starts at slot 1000, while the ancestors of the finalized block were
+        // produced using Aura.
+        let info = babe_chain_information();
+
+        assert!(ChainInformationRef::from(&info).validate().is_ok());
+    }
+
+    #[test]
+    fn babe_authority_public_key_across_epoch_boundary() {
+        let mut info = babe_chain_information();
+        if let ChainInformationConsensus::Babe {
+            finalized_block_epoch_information: Some(current_epoch),
+            finalized_next_epoch_transition,
+            ..
+        } = &mut info.consensus
+        {
+            current_epoch.authorities.push(header::BabeAuthority {
+                public_key: [9; 32],
+                weight: 1,
+            });
+            finalized_next_epoch_transition.authorities = vec![header::BabeAuthority {
+                public_key: [10; 32],
+                weight: 1,
+            }];
+        }
+        let info = ValidChainInformation::try_from(info).unwrap();
+
+        // Last slot of the current epoch.
+        assert_eq!(info.babe_authority_public_key(1009, 0), Some(&[7; 32]));
+        assert_eq!(info.babe_authority_public_key(1009, 1), Some(&[9; 32]));
+        // First and last slots of the next epoch.
+        assert_eq!(info.babe_authority_public_key(1010, 0), Some(&[10; 32]));
+        assert_eq!(info.babe_authority_public_key(1010, 1), None);
+        assert_eq!(info.babe_authority_public_key(1019, 0), Some(&[10; 32]));
+        // Outside of the known epochs.
+        assert_eq!(info.babe_authority_public_key(999, 0), None);
+        assert_eq!(info.babe_authority_public_key(1020, 0), None);
+
+        let info = ValidChainInformation::try_from(valid_example()).unwrap();
+        assert_eq!(info.babe_authority_public_key(1000, 0), None);
+
+        // The finalized block is the genesis block, and the start of the first epoch is unknown.
+        let genesis = ValidChainInformation::try_from(babe_genesis_chain_information()).unwrap();
+        assert_eq!(genesis.babe_authority_public_key(0, 0), None);
+        assert_eq!(genesis.babe_authority_public_key(1000, 0), None);
+    }
+
+    #[test]
+    fn babe_epoch_slot_discontinuity() {
+        let mut info = babe_chain_information();
+        if let ChainInformationConsensus::Babe {
+            finalized_next_epoch_transition,
+            ..
+        } = &mut info.consensus
+        {
+            finalized_next_epoch_transition.start_slot_number = Some(1011);
+        }
+
+        assert!(matches!(
+            ChainInformationRef::from(&info).validate(),
+            Err(ValidityError::BabeEpochSlotDiscontinuity {
+                current_epoch_start_slot: 1000,
+                next_epoch_start_slot: 1011,
+            })
+        ));
+
+        // The next epoch can start right after the current one, or after some skipped epochs.
+        for next_epoch_start_slot in [1010, 1030] {
+            if let ChainInformationConsensus::Babe {
+                finalized_next_epoch_transition,
+                ..
+            } = &mut info.consensus
+            {
+                finalized_next_epoch_transition.start_slot_number = Some(next_epoch_start_slot);
+            }
+            assert!(ChainInformationRef::from(&info).validate().is_ok());
+        }
+
+        // The next epoch can't start at the same slot or before the current one.
+        for next_epoch_start_slot in [1000, 990] {
+            if let ChainInformationConsensus::Babe {
+                finalized_next_epoch_transition,
+                ..
+            } = &mut info.consensus
+            {
+                finalized_next_epoch_transition.start_slot_number = Some(next_epoch_start_slot);
+            }
+            assert!(matches!(
+                ChainInformationRef::from(&info).validate(),
+                Err(ValidityError::BabeEpochSlotDiscontinuity { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn duplicate_aura_authority() {
+        let mut info = example();
+        if let ChainInformationConsensus::Aura {
+            finalized_authorities_list,
+            ..
+        } = &mut info.consensus
+        {
+            finalized_authorities_list.push(header::AuraAuthority {
+                public_key: [4; 32],
+            });
+        }
+
+        assert!(matches!(
+            ChainInformationRef::from(&info).validate(),
+            Err(ValidityError::DuplicateAuraAuthority)
+        ));
+    }
+
+    #[test]
+    fn duplicate_babe_authority() {
+        let mut info = babe_chain_information();
+        if let ChainInformationConsensus::Babe {
+            finalized_next_epoch_transition,
+            ..
+        } = &mut info.consensus
+        {
+            finalized_next_epoch_transition
+                .authorities
+                .push(header::BabeAuthority {
+                    public_key: [7; 32],
+                    weight: 2,
+                });
+        }
+
+        assert!(matches!(
+            ChainInformationRef::from(&info).validate(),
+            Err(ValidityError::DuplicateBabeAuthority)
+        ));
+    }
+
+    #[test]
+    fn duplicate_grandpa_authority() {
+        let mut info = example();
+        info.consensus = ChainInformationConsensus::Unknown;
+        if let ChainInformationFinality::Grandpa {
+            finalized_triggered_authorities,
+            ..
+        } = &mut info.finality
+        {
+            finalized_triggered_authorities.push(header::GrandpaAuthority {
+                public_key: [6; 32],
+                weight: NonZero::<u64>::new(2).unwrap(),
+            });
+        }
+
+        assert!(matches!(
+            ChainInformationRef::from(&info).validate(),
+            Err(ValidityError::DuplicateGrandpaAuthority)
+        ));
+    }
+
+    #[test]
+    fn aura_switch_block_announcing_babe() {
+        // Block authored using Aura but that announces the first Babe epoch.
+        let info = ChainInformation {
+            finalized_block_header: header_with_digest(&[
+                header::DigestItem::AuraPreDigest(header::AuraPreDigest { slot_number: 999 }),
+                header::DigestItem::BabeConsensus(header::BabeConsensusLog::NextEpochData(
+                    header::BabeNextEpoch {
+                        authorities: vec![header::BabeAuthority {
+                            public_key: [7; 32],
+                            weight: 1,
+                        }],
+                        randomness: [8; 32],
+                    },
+                )),
+                header::DigestItem::AuraSeal([0; 64]),
+            ]),
+            header_hash_function: trie::HashFunction::Blake2,
+            finalized_session_index: None,
+            finalized_beefy: None,
+            consensus: ChainInformationConsensus::Aura {
+                finalized_authorities_list: vec![header::AuraAuthority {
+                    public_key: [4; 32],
+                }],
+                slot_duration: NonZero::<u64>::new(6000).unwrap(),
+                finalized_disabled_authorities: Vec::new(),
+            },
+            finality: ChainInformationFinality::Outsourced,
+        };
+
+        assert!(ChainInformationRef::from(&info).validate().is_ok());
+    }
+
+    #[test]
+    fn babe_finalized_block_authored_with_aura() {
+        let mut info = babe_chain_information();
+        info.finalized_block_header = header_with_digest(&[
+            header::DigestItem::BabePreDigest(header::BabePreDigest::SecondaryPlain(
+                header::BabeSecondaryPlainPreDigest {
+                    authority_index: 0,
+                    slot_number: 1003,
+                },
+            )),
+            header::DigestItem::AuraSeal([0; 64]),
+        ]);
+
+        assert!(matches!(
+            ChainInformationRef::from(&info).validate(),
+            Err(ValidityError::ConsensusAlgorithmMismatch)
+        ));
+    }
+
+    #[test]
+    fn verify_storage() {
+        let child_trie_entries: &[(&[u8], &[u8])] = &[(b"foo", b"bar"), (b"baz", b"qux")];
+        let child_root = trie::trie_root(
+            trie::TrieEntryVersion::V1,
+            trie::HashFunction::Blake2,
+            child_trie_entries.iter().copied(),
+        );
+        let state_root = trie::trie_root(
+            trie::TrieEntryVersion::V1,
+            trie::HashFunction::Blake2,
+            [
+                (&b":code"[..], &b"runtime"[..]),
+                (&b":child_storage:default:abc"[..], &child_root[..]),
+            ],
+        );
+
+        let mut info = example();
+        info.finalized_block_header.number = 0;
+        info.finalized_block_header.state_root = state_root;
+        info.finality = ChainInformationFinality::Outsourced;
+        let info = ValidChainInformation::try_from(info).unwrap();
+        let info = ValidChainInformationRef::from(&info);
+
+        let top_trie_entries: &[(&[u8], &[u8])] = &[(b":code", b"runtime")];
+        assert!(info
+            .verify_storage(
+                trie::TrieEntryVersion::V1,
+                top_trie_entries,
+                &[(b"abc", child_trie_entries)],
+            )
+            .is_ok());
+
+        assert!(matches!(
+            info.verify_storage(
+                trie::TrieEntryVersion::V1,
+                top_trie_entries,
+                &[(b"abd", child_trie_entries)],
+            ),
+            Err(VerifyStorageError::StateRootMismatch { expected, .. }) if expected == state_root
+        ));
+
+        let top_trie_entries: &[(&[u8], &[u8])] = &[
+            (b":code", b"runtime"),
+            (b":child_storage:default:abc", &child_root),
+        ];
+        assert_eq!(
+            info.verify_storage(
+                trie::TrieEntryVersion::V1,
+                top_trie_entries,
+                &[(b"abc", child_trie_entries)],
+            ),
+            Err(VerifyStorageError::ChildTrieKeyInTopTrie)
+        );
+    }
+
+    /// Same as [`example`], but with a finalized block authored with Aura, so that the chain
+    /// information is valid.
+    fn valid_example() -> ChainInformation {
+        let mut info = example();
+        info.finalized_block_header = header_with_digest(&[
+            header::DigestItem::AuraPreDigest(header::AuraPreDigest { slot_number: 1000 }),
+            header::DigestItem::AuraSeal([0; 64]),
+        ]);
+        assert!(ChainInformationRef::from(&info).validate().is_ok());
+        info
+    }
+
+    #[test]
+    fn session_index_not_validated() {
+        let mut info = valid_example();
+        info.finalized_session_index = None;
+        assert!(ChainInformationRef::from(&info).validate().is_ok());
+        info.finalized_session_index = Some(u32::MAX);
+        assert!(ChainInformationRef::from(&info).validate().is_ok());
+    }
+
+    #[test]
+    fn beefy_genesis() {
+        let mut info = babe_genesis_chain_information();
+        info.finalized_beefy = Some(BeefyInformation {
+            validator_set_id: 0,
+            validators: vec![[2; 33]],
+        });
+        assert!(ChainInformationRef::from(&info).validate().is_ok());
+
+        info.finalized_beefy = Some(BeefyInformation {
+            validator_set_id: 1,
+            validators: vec![[2; 33]],
+        });
+        assert!(matches!(
+            ChainInformationRef::from(&info).validate(),
+            Err(ValidityError::BeefyZeroButNonZeroSetId)
+        ));
+
+        info.finalized_beefy = Some(BeefyInformation {
+            validator_set_id: 0,
+            validators: Vec::new(),
+        });
+        assert!(matches!(
+            ChainInformationRef::from(&info).validate(),
+            Err(ValidityError::BeefyEmptyValidatorSet)
+        ));
+    }
+
+    #[test]
+    fn beefy_non_genesis() {
+        let mut info = valid_example();
+        for validator_set_id in [0, 5] {
+            info.finalized_beefy = Some(BeefyInformation {
+                validator_set_id,
+                validators: vec![[2; 33], [3; 33]],
+            });
+            assert!(ChainInformationRef::from(&info).validate().is_ok());
+        }
+
+        info.finalized_beefy = Some(BeefyInformation {
+            validator_set_id: 5,
+            validators: Vec::new(),
+        });
+        assert!(matches!(
+            ChainInformationRef::from(&info).validate(),
+            Err(ValidityError::BeefyEmptyValidatorSet)
+        ));
+    }
+
+    #[test]
+    fn grandpa_scheduled_change_before_finalized() {
+        for trigger_block_height in [119, 120] {
+            let mut info = valid_example();
+            if let ChainInformationFinality::Grandpa {
+                finalized_scheduled_change,
+                ..
+            } = &mut info.finality
+            {
+                *finalized_scheduled_change = Some((
+                    trigger_block_height,
+                    vec![header::GrandpaAuthority {
+                        public_key: [7; 32],
+                        weight: NonZero::<u64>::new(1).unwrap(),
+                    }],
+                ));
+            }
+
+            assert!(matches!(
+                ChainInformationRef::from(&info).validate(),
+                Err(ValidityError::ScheduledGrandPaChangeBeforeFinalized {
+                    trigger_block_height: t,
+                    finalized_block_height: 120,
+                }) if t == trigger_block_height
+            ));
+        }
+    }
+
+    #[test]
+    fn grandpa_scheduled_change_after_finalized() {
+        let mut info = valid_example();
+        if let ChainInformationFinality::Grandpa {
+            finalized_scheduled_change,
+            ..
+        } = &mut info.finality
+        {
+            *finalized_scheduled_change = Some((
+                121,
+                vec![header::GrandpaAuthority {
+                    public_key: [7; 32],
+                    weight: NonZero::<u64>::new(1).unwrap(),
+                }],
+            ));
+        }
+
+        assert!(ChainInformationRef::from(&info).validate().is_ok());
+    }
+
+    #[test]
+    fn grandpa_scheduled_change_same_authorities() {
+        // A change can legitimately keep the same list of authorities, for example in order to
+        // rotate the set id.
+        let mut info = valid_example();
+        if let ChainInformationFinality::Grandpa {
+            finalized_triggered_authorities,
+            finalized_scheduled_change,
+            ..
+        } = &mut info.finality
+        {
+            *finalized_scheduled_change = Some((121, finalized_triggered_authorities.clone()));
+        }
+
+        assert!(ChainInformationRef::from(&info).validate().is_ok());
+    }
+
+    /// Builds a valid chain information whose finalized block header signals a Grandpa forced
+    /// change with the given delay, and with a scheduled change triggered at block 121.
+    fn forced_and_scheduled_changes_example(forced_change_delay: u64) -> ChainInformation {
+        let mut info = valid_example();
+        info.finalized_block_header = header_with_digest(&[
+            header::DigestItem::AuraPreDigest(header::AuraPreDigest { slot_number: 1000 }),
+            header::DigestItem::GrandpaConsensus(header::GrandpaConsensusLog::ForcedChange {
+                reset_block_height: 100,
+                change: header::GrandpaScheduledChange {
+                    next_authorities: vec![header::GrandpaAuthority {
+                        public_key: [8; 32],
+                        weight: NonZero::<u64>::new(1).unwrap(),
+                    }],
+                    delay: forced_change_delay,
+                },
+            }),
+            header::DigestItem::AuraSeal([0; 64]),
+        ]);
+        if let ChainInformationFinality::Grandpa {
+            finalized_scheduled_change,
+            ..
+        } = &mut info.finality
+        {
+            *finalized_scheduled_change = Some((
+                121,
+                vec![header::GrandpaAuthority {
+                    public_key: [7; 32],
+                    weight: NonZero::<u64>::new(1).unwrap(),
+                }],
+            ));
+        }
+        info
+    }
+
+    #[test]
+    fn grandpa_forced_and_scheduled_changes_conflicting() {
+        let info = forced_and_scheduled_changes_example(1);
+        assert!(matches!(
+            ChainInformationRef::from(&info).validate(),
+            Err(ValidityError::ConflictingGrandpaChanges {
+                trigger_block_height: 121
+            })
+        ));
+    }
+
+    #[test]
+    fn grandpa_forced_and_scheduled_changes_coexisting() {
+        for forced_change_delay in [0, 2] {
+            let info = forced_and_scheduled_changes_example(forced_change_delay);
+            assert!(ChainInformationRef::from(&info).validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn summary() {
+        let info = valid_example();
+        let info = ValidChainInformation::try_from(info).unwrap();
+        let hash = info.as_ref().finalized_block_header.hash(4);
+
+        assert_eq!(
+            ValidChainInformationRef::from(&info).summary(4),
+            format!(
+                "consensus=aura; finalized_block_number=120; finalized_block_hash={}; \
+                finality=grandpa; grandpa_set_id=3",
+                HashDisplay(&hash)
+            )
+        );
+    }
+
+    #[test]
+    fn next_block_number() {
+        let info_at = |number| ChainInformation {
+            finalized_block_header: Box::new(header::Header {
+                parent_hash: [0; 32],
+                number,
+                state_root: [0; 32],
+                extrinsics_root: [0; 32],
+                digest: header::DigestRef::empty().into(),
+            }),
+            header_hash_function: trie::HashFunction::Blake2,
+            finalized_session_index: None,
+            finalized_beefy: None,
+            consensus: ChainInformationConsensus::Unknown,
+            finality: ChainInformationFinality::Outsourced,
+        };
+
+        let info = info_at(120);
+        let info = ValidChainInformationRef::try_from(ChainInformationRef::from(&info)).unwrap();
+        assert_eq!(info.next_block_number(), 121);
+
+        let info = info_at(u64::MAX - 1);
+        let info = ValidChainInformationRef::try_from(ChainInformationRef::from(&info)).unwrap();
+        assert_eq!(info.next_block_number(), u64::MAX);
+
+        let info = info_at(u64::MAX);
+        let info = ValidChainInformationRef::try_from(ChainInformationRef::from(&info)).unwrap();
+        assert_eq!(info.next_block_number(), u64::MAX);
+    }
+
+    #[test]
+    fn finalized_block_hash_respects_hash_function() {
+        let info = valid_example();
+        let blake2_hash = ChainInformationRef::from(&info).finalized_block_hash(4);
+        assert_eq!(blake2_hash, info.finalized_block_header.hash(4));
+
+        let info = ChainInformation {
+            header_hash_function: trie::HashFunction::Keccak256,
+            ..info
+        };
+        let keccak_hash = ChainInformationRef::from(&info).finalized_block_hash(4);
+        assert_ne!(keccak_hash, blake2_hash);
+        assert_eq!(
+            keccak_hash,
+            info.finalized_block_header
+                .hash_with_function(4, trie::HashFunction::Keccak256)
+        );
+        assert!(ChainInformationRef::from(&info).validate().is_ok());
+    }
+
+    #[test]
+    fn babe_next_epoch_accessors() {
+        let info = ValidChainInformation::try_from(valid_example()).unwrap();
+        assert!(info.babe_next_epoch_randomness().is_none());
+        assert!(info.babe_next_epoch_authorities().is_none());
+
+        let info = ValidChainInformation::try_from(babe_chain_information()).unwrap();
+
+        assert_eq!(info.babe_next_epoch_randomness(), Some(&[8; 32]));
+        let authorities = info.babe_next_epoch_authorities().unwrap();
+        assert_eq!(authorities.len(), 1);
+        assert!(authorities
+            .map(header::BabeAuthority::from)
+            .eq([header::BabeAuthority {
+                public_key: [7; 32],
+                weight: 1,
+            }]));
+    }
+
+    #[test]
+    fn babe_finalized_epoch_index() {
+        let info = ValidChainInformation::try_from(valid_example()).unwrap();
+        assert_eq!(
+            ValidChainInformationRef::from(&info).babe_finalized_epoch_index(),
+            None
+        );
+
+        let genesis = ValidChainInformation::try_from(babe_genesis_chain_information()).unwrap();
+        assert_eq!(
+            ValidChainInformationRef::from(&genesis).babe_finalized_epoch_index(),
+            None
+        );
+
+        let mut info = babe_chain_information();
+        info.finalized_block_header = header_with_digest(&[
+            header::DigestItem::BabePreDigest(header::BabePreDigest::SecondaryPlain(
+                header::BabeSecondaryPlainPreDigest {
+                    authority_index: 0,
+                    slot_number: 1013,
+                },
+            )),
+            header::DigestItem::BabeSeal([0; 64]),
+        ]);
+        if let ChainInformationConsensus::Babe {
+            finalized_block_epoch_information,
+            finalized_next_epoch_transition,
+            ..
+        } = &mut info.consensus
+        {
+            *finalized_block_epoch_information = Some(Box::new(babe_epoch(1, Some(1010))));
+            **finalized_next_epoch_transition = babe_epoch(2, Some(1020));
+        }
+        let info = ValidChainInformation::try_from(info).unwrap();
+        assert_eq!(
+            ValidChainInformationRef::from(&info).babe_finalized_epoch_index(),
+            Some(1)
+        );
+    }
+
+    /// Returns a chain information whose single Grandpa authority uses the given key.
+    fn grandpa_example(signing_key: &ed25519_zebra::SigningKey) -> ValidChainInformation {
+        let mut info = valid_example();
+        if let ChainInformationFinality::Grandpa {
+            finalized_triggered_authorities,
+            ..
+        } = &mut info.finality
+        {
+            *finalized_triggered_authorities = vec![header::GrandpaAuthority {
+                public_key: ed25519_zebra::VerificationKey::from(signing_key).into(),
+                weight: NonZero::<u64>::new(1).unwrap(),
+            }];
+        }
+        ValidChainInformation::try_from(info).unwrap()
+    }
+
+    /// Builds a SCALE-encoded justification containing a single pre-commit.
+    fn justification(
+        signing_key: &ed25519_zebra::SigningKey,
+        target_hash: [u8; 32],
+        target_number: u32,
+        set_id: u64,
+        votes_ancestries: &[header::Header],
+    ) -> Vec<u8> {
+        let round = 5u64;
+
+        let mut message = vec![1u8];
+        message.extend_from_slice(&target_hash);
+        message.extend_from_slice(&target_number.to_le_bytes());
+        message.extend_from_slice(&round.to_le_bytes());
+        message.extend_from_slice(&set_id.to_le_bytes());
+        let signature: [u8; 64] = signing_key.sign(&message).into();
+        let public_key: [u8; 32] = ed25519_zebra::VerificationKey::from(signing_key).into();
+
+        let mut out = round.to_le_bytes().to_vec();
+        out.extend_from_slice(&target_hash);
+        out.extend_from_slice(&target_number.to_le_bytes());
+        out.push(1 << 2);
+        out.extend_from_slice(&target_hash);
+        out.extend_from_slice(&target_number.to_le_bytes());
+        out.extend_from_slice(&signature);
+        out.extend_from_slice(&public_key);
+        out.push(u8::try_from(votes_ancestries.len()).unwrap() << 2);
+        for header in votes_ancestries {
+            out.extend_from_slice(&header.scale_encoding_vec(4));
+        }
+        out
+    }
+
+    #[test]
+    fn verify_grandpa_justification() {
+        let signing_key = ed25519_zebra::SigningKey::from([9; 32]);
+        let info = grandpa_example(&signing_key);
+        let info = ValidChainInformationRef::from(&info);
+
+        let justif = justification(&signing_key, [0xab; 32], 122, 3, &[]);
+        assert!(info
+            .verify_grandpa_justification(&[0xab; 32], 122, &justif, 4, [0; 32])
+            .is_ok());
+
+        assert!(matches!(
+            info.verify_grandpa_justification(&[0xab; 32], 123, &justif, 4, [0; 32]),
+            Err(JustificationError::TargetMismatch)
+        ));
+
+        // Signed using the wrong set id.
+        let justif = justification(&signing_key, [0xab; 32], 122, 4, &[]);
+        assert!(matches!(
+            info.verify_grandpa_justification(&[0xab; 32], 122, &justif, 4, [0; 32]),
+            Err(JustificationError::VerificationFailed(_))
+        ));
+
+        // Targets the finalized block itself.
+        let justif = justification(&signing_key, [0xab; 32], 120, 3, &[]);
+        assert!(matches!(
+            info.verify_grandpa_justification(&[0xab; 32], 120, &justif, 4, [0; 32]),
+            Err(JustificationError::NotDescendant)
+        ));
+    }
+
+    #[test]
+    fn verify_grandpa_justification_ancestry() {
+        let signing_key = ed25519_zebra::SigningKey::from([9; 32]);
+        let info = grandpa_example(&signing_key);
+        let info = ValidChainInformationRef::from(&info);
+
+        for (parent_hash, is_descendant) in [
+            (info.as_ref().finalized_block_hash(4), true),
+            ([0xcd; 32], false),
+        ] {
+            let child = header::Header {
+                parent_hash,
+                number: 121,
+                state_root: [0; 32],
+                extrinsics_root: [0; 32],
+                digest: header::DigestRef::empty().into(),
+            };
+            let child_hash = child.hash(4);
+            let justif = justification(&signing_key, child_hash, 121, 3, &[child]);
+
+            let result = info.verify_grandpa_justification(&child_hash, 121, &justif, 4, [0; 32]);
+            if is_descendant {
+                assert!(result.is_ok());
+            } else {
+                assert!(matches!(result, Err(JustificationError::NotDescendant)));
+            }
+        }
+    }
+
+    #[test]
+    fn verify_grandpa_justification_after_scheduled_change() {
+        let signing_key = ed25519_zebra::SigningKey::from([9; 32]);
+        let mut info = ChainInformation::from(grandpa_example(&signing_key));
+        if let ChainInformationFinality::Grandpa {
+            finalized_scheduled_change,
+            ..
+        } = &mut info.finality
+        {
+            *finalized_scheduled_change = Some((
+                121,
+                vec![header::GrandpaAuthority {
+                    public_key: [7; 32],
+                    weight: NonZero::<u64>::new(1).unwrap(),
+                }],
+            ));
+        }
+        let info = ValidChainInformation::try_from(info).unwrap();
+        let info = ValidChainInformationRef::from(&info);
+
+        let justif = justification(&signing_key, [0xab; 32], 121, 3, &[]);
+        assert!(info
+            .verify_grandpa_justification(&[0xab; 32], 121, &justif, 4, [0; 32])
+            .is_ok());
+
+        let justif = justification(&signing_key, [0xab; 32], 122, 3, &[]);
+        assert!(matches!(
+            info.verify_grandpa_justification(&[0xab; 32], 122, &justif, 4, [0; 32]),
+            Err(JustificationError::TooFarAhead {
+                block_to_finalize_number: 121
+            })
+        ));
+    }
+
+    #[test]
+    fn aura_grandpa_finalized_block_schedules_change() {
+        let new_authorities = vec![header::GrandpaAuthority {
+            public_key: [7; 32],
+            weight: NonZero::<u64>::new(1).unwrap(),
+        }];
+
+        // Aura block #120 that schedules a Grandpa change triggered at block #125.
+        let mut info = valid_example();
+        info.finalized_block_header = header_with_digest(&[
+            header::DigestItem::AuraPreDigest(header::AuraPreDigest { slot_number: 1000 }),
+            header::DigestItem::GrandpaConsensus(header::GrandpaConsensusLog::ScheduledChange(
+                header::GrandpaScheduledChange {
+                    next_authorities: new_authorities.clone(),
+                    delay: 5,
+                },
+            )),
+            header::DigestItem::AuraSeal([0; 64]),
+        ]);
+
+        // The chain information is allowed to not know about the change.
+        assert!(ChainInformationRef::from(&info).validate().is_ok());
+
+        if let ChainInformationFinality::Grandpa {
+            finalized_scheduled_change,
+            ..
+        } = &mut info.finality
+        {
+            *finalized_scheduled_change = Some((125, new_authorities.clone()));
+        }
+        assert!(ChainInformationRef::from(&info).validate().is_ok());
+
+        if let ChainInformationFinality::Grandpa {
+            finalized_scheduled_change,
+            ..
+        } = &mut info.finality
+        {
+            *finalized_scheduled_change = Some((126, new_authorities));
+        }
+        assert!(matches!(
+            ChainInformationRef::from(&info).validate(),
+            Err(ValidityError::GrandpaScheduledChangeMismatch)
+        ));
+
+        // Grandpa log items are forbidden if the chain doesn't use Grandpa.
+        info.finality = ChainInformationFinality::Outsourced;
+        assert!(matches!(
+            ChainInformationRef::from(&info).validate(),
+            Err(ValidityError::FinalityAlgorithmMismatch)
+        ));
+    }
+
+    #[test]
+    fn verify_aura_header_seal() {
+        let keypairs = [1u8, 2].map(|seed| {
+            schnorrkel::MiniSecretKey::from_bytes(&[seed; 32])
+                .unwrap()
+                .expand_to_keypair(schnorrkel::ExpansionMode::Ed25519)
+        });
+
+        let make_info = |finalized_disabled_authorities: Vec<u32>| {
+            ValidChainInformation::try_from(ChainInformation {
+                finalized_block_header: Box::new(header::Header {
+                    parent_hash: [0; 32],
+                    number: 0,
+                    state_root: [2; 32],
+                    extrinsics_root: [3; 32],
+                    digest: header::DigestRef::empty().into(),
+                }),
+                header_hash_function: trie::HashFunction::Blake2,
+                finalized_session_index: None,
+                finalized_beefy: None,
+                consensus: ChainInformationConsensus::Aura {
+                    finalized_authorities_list: keypairs
+                        .iter()
+                        .map(|keypair| header::AuraAuthority {
+                            public_key: keypair.public.to_bytes(),
+                        })
+                        .collect(),
+                    slot_duration: NonZero::<u64>::new(6000).unwrap(),
+                    finalized_disabled_authorities,
+                },
+                finality: ChainInformationFinality::Outsourced,
+            })
+            .unwrap()
+        };
+        let info = make_info(Vec::new());
+
+        // Slot 1001 must be claimed by the authority at index `1001 % 2 == 1`.
+        let sealed_header = |signer: &schnorrkel::Keypair| {
+            let mut digest = vec![header::DigestItem::AuraPreDigest(header::AuraPreDigest {
+                slot_number: 1001,
+            })];
+            let unsealed = header::Header {
+                parent_hash: info.as_ref().finalized_block_hash(4),
+                number: 1,
+                state_root: [4; 32],
+                extrinsics_root: [5; 32],
+                digest: header::DigestRef::from_slice(&digest).unwrap().into(),
+            };
+            let signature = signer.sign_simple(b"substrate", &unsealed.hash(4));
+            digest.push(header::DigestItem::AuraSeal(signature.to_bytes()));
+            header::Header {
+                digest: header::DigestRef::from_slice(&digest).unwrap().into(),
+                ..unsealed
+            }
+        };
+
+        let valid = sealed_header(&keypairs[1]);
+        assert!(ValidChainInformationRef::from(&info)
+            .verify_header_seal((&valid).into(), 4)
+            .is_ok());
+
+        let mut tampered = valid.clone();
+        tampered.extrinsics_root = [6; 32];
+        assert!(matches!(
+            ValidChainInformationRef::from(&info).verify_header_seal((&tampered).into(), 4),
+            Err(SealError::Aura(aura::VerifyError::BadSignature))
+        ));
+
+        let wrong_author = sealed_header(&keypairs[0]);
+        assert!(matches!(
+            ValidChainInformationRef::from(&info).verify_header_seal((&wrong_author).into(), 4),
+            Err(SealError::Aura(aura::VerifyError::BadSignature))
+        ));
+
+        // Disabling the other authority doesn't affect the validity of the seal.
+        assert!(ValidChainInformationRef::from(&make_info(vec![0]))
+            .verify_header_seal((&valid).into(), 4)
+            .is_ok());
+
+        // Disabling the author makes the seal invalid.
+        assert!(matches!(
+            ValidChainInformationRef::from(&make_info(vec![1]))
+                .verify_header_seal((&valid).into(), 4),
+            Err(SealError::DisabledAuthority { authority_index: 1 })
+        ));
+    }
+
+    #[test]
+    fn integrity_hash_detects_modifications() {
+        let make = |slot_duration: u64| {
+            ValidChainInformation::try_from(ChainInformation {
+                finalized_block_header: Box::new(header::Header {
+                    parent_hash: [0; 32],
+                    number: 0,
+                    state_root: [2; 32],
+                    extrinsics_root: [3; 32],
+                    digest: header::DigestRef::empty().into(),
+                }),
+                header_hash_function: trie::HashFunction::Blake2,
+                finalized_session_index: None,
+                finalized_beefy: None,
+                consensus: ChainInformationConsensus::Aura {
+                    finalized_authorities_list: vec![header::AuraAuthority {
+                        public_key: [1; 32],
+                    }],
+                    slot_duration: NonZero::<u64>::new(slot_duration).unwrap(),
+                    finalized_disabled_authorities: Vec::new(),
+                },
+                finality: ChainInformationFinality::Outsourced,
+            })
+            .unwrap()
+        };
+
+        let info = make(6000);
+        let hash = ValidChainInformationRef::from(&info).integrity_hash(4);
+        assert_eq!(
+            &hash.hash[..],
+            blake2_rfc::blake2b::blake2b(32, &[], &info.as_ref().scale_encoding_vec(4)).as_bytes()
+        );
+        assert!(ValidChainInformationRef::from(&make(6000))
+            .verify_integrity_hash(4, &hash)
+            .is_ok());
+        assert!(matches!(
+            ValidChainInformationRef::from(&make(12000)).verify_integrity_hash(4, &hash),
+            Err(IntegrityHashError::Mismatch)
+        ));
+        assert!(matches!(
+            ValidChainInformationRef::from(&info).verify_integrity_hash(
+                4,
+                &IntegrityHash {
+                    encoding_version: 0,
+                    ..hash.clone()
+                }
+            ),
+            Err(IntegrityHashError::UnsupportedVersion(0))
+        ));
+    }
+
+    #[test]
+    fn integrity_hash_of_older_encoding_version() {
+        // Hash calculated by a version of the library whose encoding didn't include the header
+        // hash function nor the session index.
+        let info = ValidChainInformation::try_from(ChainInformation {
+            finalized_block_header: Box::new(header::Header {
+                parent_hash: [0; 32],
+                number: 0,
+                state_root: [2; 32],
+                extrinsics_root: [3; 32],
+                digest: header::DigestRef::empty().into(),
+            }),
+            header_hash_function: trie::HashFunction::Blake2,
+            finalized_session_index: None,
+            finalized_beefy: None,
+            consensus: ChainInformationConsensus::Aura {
+                finalized_authorities_list: vec![header::AuraAuthority {
+                    public_key: [1; 32],
+                }],
+                slot_duration: NonZero::<u64>::new(6000).unwrap(),
+                finalized_disabled_authorities: Vec::new(),
+            },
+            finality: ChainInformationFinality::Outsourced,
+        })
+        .unwrap();
+
+        let mut v3_encoding = info.as_ref().scale_encoding_vec(4);
+        v3_encoding[0] = 3;
+        // Remove the header hash function and session index bytes that follow the header.
+        let header_len = info
+            .as_ref()
+            .finalized_block_header
+            .scale_encoding_vec(4)
+            .len();
+        let header_end = 1
+            + crate::util::encode_scale_compact_usize(header_len)
+                .as_ref()
+                .len()
+            + header_len;
+        v3_encoding.drain(header_end..header_end + 2);
+        // Remove the BEEFY information byte found at the end.
+        assert_eq!(v3_encoding.pop(), Some(0));
+
+        let v3_hash = IntegrityHash {
+            encoding_version: 3,
+            hash: blake2_rfc::blake2b::blake2b(32, &[], &v3_encoding)
+                .as_bytes()
+                .try_into()
+                .unwrap(),
+        };
+        assert!(ValidChainInformationRef::from(&info)
+            .verify_integrity_hash(4, &v3_hash)
+            .is_ok());
+    }
+}
@@ -19,14 +19,19 @@
 //!
 //! This module contains the [`ChainInformationBuild`] struct, a state machine that drives the
 //! process of building the chain information of a certain finalized point of a chain.
+//!
+//! The [`build_from_proof`] function is a convenience wrapper around [`ChainInformationBuild`]
+//! that answers all the storage accesses using a Merkle proof, such as the one returned by the
+//! `state_getReadProof` JSON-RPC function.
 
 use alloc::{boxed::Box, vec::Vec};
 use core::{fmt, iter, num::NonZero};
 
 use crate::{
     chain::chain_information,
-    executor::{host, runtime_call},
-    header, trie,
+    executor::{self, host, runtime_call, vm},
+    header,
+    trie::{self, proof_decode},
 };
 
 pub use runtime_call::{Nibble, TrieEntryVersion};
@@ -62,6 +67,34 @@ pub enum ConfigFinalizedBlockHeader {
     },
 }
 
+/// Configuration to provide to [`build_from_proof`].
+pub struct ProofConfig<TProof> {
+    /// SCALE-encoded header of the finalized block, whose chain information is to retrieve.
+    pub finalized_block_scale_encoded_header: Vec<u8>,
+
+    /// Can be used to pass information about the finality of the chain, if already known.
+    ///
+    /// See [`ConfigFinalizedBlockHeader::Any::known_finality`].
+    pub known_finality: Option<chain_information::ChainInformationFinality>,
+
+    /// List of trie node values of the storage of the finalized block, in no particular order.
+    ///
+    /// The proof must contain the nodes necessary to read the `:code` and `:heappages` keys, plus
+    /// all the nodes that the runtime accesses while determining the consensus and finality
+    /// authorities (for example the `Babe` and `Grandpa` pallets storage items).
+    pub storage_proof: TProof,
+
+    /// Number of bytes of the block number encoded in the block header.
+    pub block_number_bytes: usize,
+
+    /// Hint used when compiling the runtime found in the proof.
+    pub exec_hint: vm::ExecHint,
+
+    /// Passed to [`host::Config::allow_unresolved_imports`] when compiling the runtime found in
+    /// the proof.
+    pub allow_unresolved_imports: bool,
+}
+
 /// Current state of the operation.
 #[must_use]
 pub enum ChainInformationBuild {
@@ -131,6 +164,34 @@ pub enum Error {
     MultipleConsensusAlgorithms,
 }
 
+/// Error potentially returned by [`build_from_proof`].
+#[derive(Debug, derive_more::Display)]
+pub enum FromProofError {
+    /// Failed to decode the header of the finalized block.
+    #[display(fmt = "Failed to decode finalized block header: {_0}")]
+    InvalidHeader(header::Error),
+    /// The storage proof is invalid.
+    #[display(fmt = "Invalid storage proof: {_0}")]
+    InvalidProof(proof_decode::Error),
+    /// The storage proof doesn't contain the root node of the storage of the finalized block.
+    /// In other words, the proof doesn't match the state root found in the header.
+    StateRootNotInProof,
+    /// The storage proof doesn't contain an entry that is necessary to build the chain
+    /// information.
+    IncompleteProof,
+    /// The storage proof doesn't contain any runtime code.
+    RuntimeCodeNotFound,
+    /// Failed to decode the value of the `:heappages` key.
+    #[display(fmt = "Failed to decode heap pages: {_0}")]
+    HeapPagesDecode(executor::InvalidHeapPagesError),
+    /// Failed to compile the runtime found in the proof.
+    #[display(fmt = "Failed to compile runtime: {_0}")]
+    VmInitialization(host::NewErr),
+    /// Error while building the chain information from the runtime.
+    #[display(fmt = "{_0}")]
+    Build(Error),
+}
+
 /// Function call to perform or being performed.
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub enum RuntimeCall {
@@ -196,11 +257,7 @@ impl ChainInformationBuild {
             .apis
             .find_versions(["AuraApi", "BabeApi", "GrandpaApi"]);
         let runtime_has_aura = aura_version.map_or(false, |version_number| version_number == 1);
-        let runtime_babeapi_is_v1 = babe_version.and_then(|version_number| match version_number {
-            1 => Some(true),
-            2 => Some(false),
-            _ => None,
-        });
+        let runtime_babeapi_version = babe_version.and_then(BabeApiVersion::from_version_number);
         let runtime_grandpa_supports_currentsetid =
             grandpa_version.and_then(|version_number| match version_number {
                 // Version 1 is from 2019 and isn't used by any chain in production, so we don't
@@ -216,7 +273,7 @@ impl ChainInformationBuild {
             call_in_progress: None,
             virtual_machine: Some(config.runtime),
             runtime_has_aura,
-            runtime_babeapi_is_v1,
+            runtime_babeapi_version,
             runtime_grandpa_supports_currentsetid,
             aura_autorities_call_output: None,
             aura_slot_duration_call_output: None,
@@ -231,6 +288,128 @@ impl ChainInformationBuild {
     }
 }
 
+/// Builds the chain information of the given finalized block using only a Merkle proof of its
+/// storage.
+///
+/// The proof is verified against the state root found in the header before anything is read
+/// from it. The runtime found at the `:code` key of the proof is then compiled and used in order
+/// to determine the consensus and finality information, the same way as
+/// [`ChainInformationBuild`] does.
+///
+/// In addition to the chain information, also returns the compiled runtime of the finalized
+/// block.
+pub fn build_from_proof<TProof>(
+    config: ProofConfig<TProof>,
+) -> Result<
+    (
+        chain_information::ValidChainInformation,
+        host::HostVmPrototype,
+    ),
+    FromProofError,
+>
+where
+    TProof: IntoIterator,
+    TProof::Item: AsRef<[u8]>,
+{
+    let state_root = *header::decode(
+        &config.finalized_block_scale_encoded_header,
+        config.block_number_bytes,
+    )
+    .map_err(FromProofError::InvalidHeader)?
+    .state_root;
+
+    // The proof is provided as a list of nodes, while `proof_decode` expects a SCALE-encoded
+    // `Vec<Vec<u8>>`.
+    let proof = {
+        let nodes = config.storage_proof.into_iter().collect::<Vec<_>>();
+        let mut encoded =
+            Vec::with_capacity(nodes.iter().map(|n| n.as_ref().len() + 5).sum::<usize>() + 5);
+        encoded.extend_from_slice(crate::util::encode_scale_compact_usize(nodes.len()).as_ref());
+        for node in &nodes {
+            encoded.extend_from_slice(
+                crate::util::encode_scale_compact_usize(node.as_ref().len()).as_ref(),
+            );
+            encoded.extend_from_slice(node.as_ref());
+        }
+        proof_decode::decode_and_verify_proof(proof_decode::Config { proof: encoded })
+            .map_err(FromProofError::InvalidProof)?
+    };
+
+    if proof.trie_node_info(&state_root, iter::empty()).is_err() {
+        return Err(FromProofError::StateRootNotInProof);
+    }
+
+    let runtime = {
+        let code = proof
+            .storage_value(&state_root, b":code")
+            .map_err(|_| FromProofError::IncompleteProof)?
+            .ok_or(FromProofError::RuntimeCodeNotFound)?
+            .0;
+        let heap_pages = executor::storage_heap_pages_to_value(
+            proof
+                .storage_value(&state_root, b":heappages")
+                .map_err(|_| FromProofError::IncompleteProof)?
+                .map(|(v, _)| v),
+        )
+        .map_err(FromProofError::HeapPagesDecode)?;
+        host::HostVmPrototype::new(host::Config {
+            module: code,
+            heap_pages,
+            exec_hint: config.exec_hint,
+            allow_unresolved_imports: config.allow_unresolved_imports,
+        })
+        .map_err(FromProofError::VmInitialization)?
+    };
+
+    let mut chain_information_build = ChainInformationBuild::new(Config {
+        finalized_block_header: ConfigFinalizedBlockHeader::Any {
+            scale_encoded_header: config.finalized_block_scale_encoded_header,
+            known_finality: config.known_finality,
+        },
+        runtime,
+        block_number_bytes: config.block_number_bytes,
+    });
+
+    loop {
+        chain_information_build = match chain_information_build {
+            ChainInformationBuild::Finished {
+                result: Ok(chain_information),
+                virtual_machine,
+            } => return Ok((chain_information, virtual_machine)),
+            ChainInformationBuild::Finished {
+                result: Err(err), ..
+            } => return Err(FromProofError::Build(err)),
+            ChainInformationBuild::InProgress(InProgress::StorageGet(get)) => {
+                // TODO: child tries not supported
+                let value = proof
+                    .storage_value(&state_root, get.key().as_ref())
+                    .map_err(|_| FromProofError::IncompleteProof)?;
+                get.inject_value(value.map(|(val, ver)| (iter::once(val), ver)))
+            }
+            ChainInformationBuild::InProgress(InProgress::NextKey(nk)) => {
+                // TODO: child tries not supported
+                let value = proof
+                    .next_key(
+                        &state_root,
+                        nk.key(),
+                        nk.or_equal(),
+                        nk.prefix(),
+                        nk.branch_nodes(),
+                    )
+                    .map_err(|_| FromProofError::IncompleteProof)?;
+                nk.inject_key(value)
+            }
+            ChainInformationBuild::InProgress(InProgress::ClosestDescendantMerkleValue(mv)) => {
+                // TODO: child tries not supported
+                let value = proof
+                    .closest_descendant_merkle_value(&state_root, mv.key())
+                    .map_err(|_| FromProofError::IncompleteProof)?;
+                mv.inject_merkle_value(value)
+            }
+        };
+    }
+}
+
 impl InProgress {
     /// Returns the list of runtime calls that will be performed. Always includes the value
     /// returned by [`InProgress::call_in_progress`].
@@ -408,7 +587,7 @@ impl ChainInformationBuild {
                 ref scale_encoded_header,
                 ..
             } if header::decode(scale_encoded_header, inner.block_number_bytes).unwrap().number != 0
-        ) && inner.runtime_babeapi_is_v1.is_some()
+        ) && inner.runtime_babeapi_version.is_some()
             && inner.babe_current_epoch_call_output.is_none()
         {
             Some(RuntimeCall::BabeApiCurrentEpoch)
@@ -422,7 +601,7 @@ impl ChainInformationBuild {
                 ref scale_encoded_header,
                 ..
             } if header::decode(scale_encoded_header, inner.block_number_bytes).unwrap().number != 0
-        ) && inner.runtime_babeapi_is_v1.is_some()
+        ) && inner.runtime_babeapi_version.is_some()
             && inner.babe_next_epoch_call_output.is_none()
         {
             Some(RuntimeCall::BabeApiNextEpoch)
@@ -430,7 +609,7 @@ impl ChainInformationBuild {
             None
         };
 
-        let babe_configuration = if inner.runtime_babeapi_is_v1.is_some()
+        let babe_configuration = if inner.runtime_babeapi_version.is_some()
             && inner.babe_configuration_call_output.is_none()
         {
             Some(RuntimeCall::BabeApiConfiguration)
@@ -518,7 +697,7 @@ impl ChainInformationBuild {
 
             let consensus = match (
                 inner.runtime_has_aura,
-                inner.runtime_babeapi_is_v1,
+                inner.runtime_babeapi_version,
                 &inner.finalized_block_header,
             ) {
                 (true, Some(_), _) => {
@@ -540,6 +719,7 @@ impl ChainInformationBuild {
                     .number
                     != 0 =>
                 {
+                    let config = inner.babe_configuration_call_output.take().unwrap();
                     chain_information::ChainInformationConsensus::Babe {
                         finalized_block_epoch_information: Some(Box::new(
                             inner.babe_current_epoch_call_output.take().unwrap(),
@@ -547,33 +727,28 @@ impl ChainInformationBuild {
                         finalized_next_epoch_transition: Box::new(
                             inner.babe_next_epoch_call_output.take().unwrap(),
                         ),
-                        slots_per_epoch: inner
-                            .babe_configuration_call_output
-                            .take()
-                            .unwrap()
-                            .slots_per_epoch,
+                        slots_per_epoch: config.slots_per_epoch,
+                        slot_duration: config.slot_duration,
+                        finalized_disabled_authorities: Vec::new(),
                     }
                 }
                 (false, Some(_), _) => {
-                    let config = inner.babe_configuration_call_output.take().unwrap();
-                    chain_information::ChainInformationConsensus::Babe {
-                        slots_per_epoch: config.slots_per_epoch,
-                        finalized_block_epoch_information: None,
-                        finalized_next_epoch_transition: Box::new(
-                            chain_information::BabeEpochInformation {
-                                epoch_index: 0,
-                                start_slot_number: None,
-                                authorities: config.epoch0_information.authorities,
-                                randomness: config.epoch0_information.randomness,
-                                c: config.epoch0_configuration.c,
-                                allowed_slots: config.epoch0_configuration.allowed_slots,
-                            },
-                        ),
+                    match chain_information::ChainInformationConsensus::babe_from_configuration(
+                        inner.babe_configuration_call_output.take().unwrap(),
+                    ) {
+                        Ok(consensus) => consensus,
+                        Err(err) => {
+                            return ChainInformationBuild::Finished {
+                                result: Err(Error::InvalidChainInformation(err)),
+                                virtual_machine: inner.virtual_machine.take().unwrap(),
+                            }
+                        }
                     }
                 }
                 (true, None, _) => chain_information::ChainInformationConsensus::Aura {
                     finalized_authorities_list: inner.aura_autorities_call_output.take().unwrap(),
                     slot_duration: inner.aura_slot_duration_call_output.take().unwrap(),
+                    finalized_disabled_authorities: Vec::new(),
                 },
             };
 
@@ -654,6 +829,9 @@ impl ChainInformationBuild {
                             .unwrap()
                             .into(),
                     ),
+                    header_hash_function: trie::HashFunction::Blake2,
+                    finalized_session_index: None,
+                    finalized_beefy: None,
                     finality,
                     consensus,
                 },
@@ -754,7 +932,7 @@ impl ChainInformationBuild {
                         Some(RuntimeCall::BabeApiConfiguration) => {
                             let result = decode_babe_configuration_output(
                                 success.virtual_machine.value().as_ref(),
-                                inner.runtime_babeapi_is_v1.unwrap(),
+                                inner.runtime_babeapi_version.unwrap(),
                             );
                             let virtual_machine = success.virtual_machine.into_prototype();
                             match result {
@@ -875,9 +1053,8 @@ struct ChainInformationBuildInner {
 
     /// If `true`, the runtime supports `AuraApi` functions.
     runtime_has_aura: bool,
-    /// If `Some`, the runtime supports `BabeApi` functions. If `true`, the version is 1 (the old
-    /// version). If `false`, the version is 2.
-    runtime_babeapi_is_v1: Option<bool>,
+    /// If `Some`, the runtime supports `BabeApi` functions, and contains the version of the API.
+    runtime_babeapi_version: Option<BabeApiVersion>,
     /// If `Some`, the runtime supports `GrandpaApi` functions. If `true`, the API supports the
     /// `GrandpaApi_current_set_id` runtime call.
     runtime_grandpa_supports_currentsetid: Option<bool>,
@@ -916,17 +1093,50 @@ fn decode_aura_authorities_output(
     }
 }
 
+/// Version of the `BabeApi` runtime API, as reported by the runtime in its list of APIs.
+///
+/// The encoding of the output of `BabeApi_configuration` depends on this version.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum BabeApiVersion {
+    /// Old version of the API. The allowed slots of the configuration can't contain VRF
+    /// secondary slots.
+    V1,
+    /// Current version of the API.
+    V2,
+}
+
+impl BabeApiVersion {
+    /// Turns the version number found in the list of APIs of the runtime into a
+    /// [`BabeApiVersion`]. Returns `None` if the version isn't supported.
+    fn from_version_number(version_number: u32) -> Option<Self> {
+        match version_number {
+            1 => Some(BabeApiVersion::V1),
+            2 => Some(BabeApiVersion::V2),
+            _ => None,
+        }
+    }
+}
+
+/// Decoded output of a call to `BabeApi_configuration`.
+///
+/// Can be turned into a [`chain_information::ChainInformationConsensus`] using
+/// [`chain_information::ChainInformationConsensus::babe_from_configuration`].
 #[derive(Debug, Clone, PartialEq, Eq)]
-struct BabeGenesisConfiguration {
-    slots_per_epoch: NonZero<u64>,
-    epoch0_configuration: header::BabeNextConfig,
-    epoch0_information: header::BabeNextEpoch,
+pub struct BabeGenesisConfiguration {
+    /// Duration, in milliseconds, of a Babe slot. `None` if the runtime reports a duration of 0.
+    pub slot_duration: Option<NonZero<u64>>,
+    /// Number of slots per epoch.
+    pub slots_per_epoch: NonZero<u64>,
+    /// Configuration of the epoch number 0.
+    pub epoch0_configuration: header::BabeNextConfig,
+    /// Authorities and randomness of the epoch number 0.
+    pub epoch0_information: header::BabeNextEpoch,
 }
 
 /// Decodes the output of a call to `BabeApi_configuration`.
 fn decode_babe_configuration_output(
     bytes: &[u8],
-    is_babe_api_v1: bool,
+    api_version: BabeApiVersion,
 ) -> Result<BabeGenesisConfiguration, Error> {
     let result: nom::IResult<_, _> =
         nom::combinator::all_consuming(nom::combinator::complete(nom::combinator::map(
@@ -954,34 +1164,31 @@ fn decode_babe_configuration_output(
                 nom::combinator::map(nom::bytes::streaming::take(32u32), |b| {
                     <[u8; 32]>::try_from(b).unwrap()
                 }),
-                |b| {
-                    if is_babe_api_v1 {
-                        nom::branch::alt((
-                            nom::combinator::map(nom::bytes::streaming::tag(&[0]), |_| {
-                                header::BabeAllowedSlots::PrimarySlots
-                            }),
-                            nom::combinator::map(nom::bytes::streaming::tag(&[1]), |_| {
-                                header::BabeAllowedSlots::PrimaryAndSecondaryPlainSlots
-                            }),
-                        ))(b)
-                    } else {
-                        nom::branch::alt((
-                            nom::combinator::map(nom::bytes::streaming::tag(&[0]), |_| {
-                                header::BabeAllowedSlots::PrimarySlots
-                            }),
-                            nom::combinator::map(nom::bytes::streaming::tag(&[1]), |_| {
-                                header::BabeAllowedSlots::PrimaryAndSecondaryPlainSlots
-                            }),
-                            nom::combinator::map(nom::bytes::streaming::tag(&[2]), |_| {
-                                header::BabeAllowedSlots::PrimaryAndSecondaryVrfSlots
-                            }),
-                        ))(b)
-                    }
+                |b| match api_version {
+                    BabeApiVersion::V1 => nom::branch::alt((
+                        nom::combinator::map(nom::bytes::streaming::tag(&[0]), |_| {
+                            header::BabeAllowedSlots::PrimarySlots
+                        }),
+                        nom::combinator::map(nom::bytes::streaming::tag(&[1]), |_| {
+                            header::BabeAllowedSlots::PrimaryAndSecondaryPlainSlots
+                        }),
+                    ))(b),
+                    BabeApiVersion::V2 => nom::branch::alt((
+                        nom::combinator::map(nom::bytes::streaming::tag(&[0]), |_| {
+                            header::BabeAllowedSlots::PrimarySlots
+                        }),
+                        nom::combinator::map(nom::bytes::streaming::tag(&[1]), |_| {
+                            header::BabeAllowedSlots::PrimaryAndSecondaryPlainSlots
+                        }),
+                        nom::combinator::map(nom::bytes::streaming::tag(&[2]), |_| {
+                            header::BabeAllowedSlots::PrimaryAndSecondaryVrfSlots
+                        }),
+                    ))(b),
                 },
             )),
-            |(_slot_duration, slots_per_epoch, c0, c1, authorities, randomness, allowed_slots)| {
-                // Note that the slot duration is unused as it is not modifiable anyway.
+            |(slot_duration, slots_per_epoch, c0, c1, authorities, randomness, allowed_slots)| {
                 BabeGenesisConfiguration {
+                    slot_duration: NonZero::<u64>::new(slot_duration),
                     slots_per_epoch,
                     epoch0_configuration: header::BabeNextConfig {
                         c: (c0, c1),
@@ -1127,8 +1334,82 @@ fn decode_grandpa_current_set_id_output(bytes: &[u8]) -> Result<u64, Error> {
 
 #[cfg(test)]
 mod tests {
-    use crate::header;
-    use core::num::NonZero;
+    use crate::{chain::chain_information, executor::vm, header, trie};
+    use alloc::{boxed::Box, vec::Vec};
+    use core::{iter, num::NonZero};
+
+    fn header_with_state_root(state_root: [u8; 32]) -> Vec<u8> {
+        header::Header {
+            parent_hash: [0; 32],
+            number: 1,
+            state_root,
+            extrinsics_root: [0; 32],
+            digest: header::DigestRef::empty().into(),
+        }
+        .scale_encoding_vec(4)
+    }
+
+    fn proof_config(
+        finalized_block_scale_encoded_header: Vec<u8>,
+        storage_proof: Vec<Vec<u8>>,
+    ) -> super::ProofConfig<Vec<Vec<u8>>> {
+        super::ProofConfig {
+            finalized_block_scale_encoded_header,
+            known_finality: None,
+            storage_proof,
+            block_number_bytes: 4,
+            exec_hint: vm::ExecHint::ValidateAndExecuteOnce,
+            allow_unresolved_imports: true,
+        }
+    }
+
+    /// Returns the node value of a trie made of a single `foo` storage item.
+    fn single_item_trie_root_node() -> Vec<u8> {
+        trie::trie_node::encode_to_vec(trie::trie_node::Decoded {
+            partial_key: trie::bytes_to_nibbles(b"foo".iter().copied()),
+            children: [None::<&[u8]>; 16],
+            storage_value: trie::trie_node::StorageValue::Unhashed(b"bar"),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn build_from_proof_invalid_header() {
+        let result = super::build_from_proof(proof_config(vec![1, 2, 3], Vec::new()));
+        assert!(matches!(
+            result,
+            Err(super::FromProofError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn build_from_proof_wrong_state_root() {
+        let result = super::build_from_proof(proof_config(
+            header_with_state_root([0xaa; 32]),
+            iter::once(single_item_trie_root_node()).collect(),
+        ));
+        assert!(matches!(
+            result,
+            Err(super::FromProofError::StateRootNotInProof)
+        ));
+    }
+
+    #[test]
+    fn build_from_proof_no_runtime() {
+        let root_node = single_item_trie_root_node();
+        let state_root =
+            *<&[u8; 32]>::try_from(blake2_rfc::blake2b::blake2b(32, &[], &root_node).as_bytes())
+                .unwrap();
+
+        let result = super::build_from_proof(proof_config(
+            header_with_state_root(state_root),
+            iter::once(root_node).collect(),
+        ));
+        assert!(matches!(
+            result,
+            Err(super::FromProofError::RuntimeCodeNotFound)
+        ));
+    }
 
     #[test]
     fn decode_babe_epoch_output_sample_decode() {
@@ -1172,8 +1453,9 @@ mod tests {
         ];
 
         assert_eq!(
-            super::decode_babe_configuration_output(&data, true).unwrap(),
+            super::decode_babe_configuration_output(&data, super::BabeApiVersion::V1).unwrap(),
             super::BabeGenesisConfiguration {
+                slot_duration: Some(NonZero::<u64>::new(6000).unwrap()),
                 slots_per_epoch: NonZero::<u64>::new(600).unwrap(),
                 epoch0_configuration: header::BabeNextConfig {
                     allowed_slots: header::BabeAllowedSlots::PrimaryAndSecondaryPlainSlots,
@@ -1235,4 +1517,111 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn decode_babe_configuration_output_v2() {
+        // Configuration with 600 slots per epoch, a single authority, and VRF secondary slots,
+        // which only exist starting from version 2 of the API.
+        let mut data = Vec::new();
+        data.extend_from_slice(&6000u64.to_le_bytes());
+        data.extend_from_slice(&600u64.to_le_bytes());
+        data.extend_from_slice(&1u64.to_le_bytes());
+        data.extend_from_slice(&4u64.to_le_bytes());
+        data.push(4);
+        data.extend_from_slice(&[7; 32]);
+        data.extend_from_slice(&1u64.to_le_bytes());
+        data.extend_from_slice(&[9; 32]);
+        data.push(2);
+
+        assert_eq!(
+            super::decode_babe_configuration_output(&data, super::BabeApiVersion::V2).unwrap(),
+            super::BabeGenesisConfiguration {
+                slot_duration: Some(NonZero::<u64>::new(6000).unwrap()),
+                slots_per_epoch: NonZero::<u64>::new(600).unwrap(),
+                epoch0_configuration: header::BabeNextConfig {
+                    allowed_slots: header::BabeAllowedSlots::PrimaryAndSecondaryVrfSlots,
+                    c: (1, 4),
+                },
+                epoch0_information: header::BabeNextEpoch {
+                    authorities: vec![header::BabeAuthority {
+                        public_key: [7; 32],
+                        weight: 1
+                    }],
+                    randomness: [9; 32]
+                },
+            }
+        );
+
+        assert!(matches!(
+            super::decode_babe_configuration_output(&data, super::BabeApiVersion::V1),
+            Err(super::Error::BabeConfigurationOutputDecode)
+        ));
+    }
+
+    #[test]
+    fn babe_api_version_from_version_number() {
+        assert_eq!(super::BabeApiVersion::from_version_number(0), None);
+        assert_eq!(
+            super::BabeApiVersion::from_version_number(1),
+            Some(super::BabeApiVersion::V1)
+        );
+        assert_eq!(
+            super::BabeApiVersion::from_version_number(2),
+            Some(super::BabeApiVersion::V2)
+        );
+        assert_eq!(super::BabeApiVersion::from_version_number(3), None);
+    }
+
+    #[test]
+    fn babe_from_decoded_configuration_output() {
+        let encode = |authorities: &[[u8; 32]]| {
+            let mut data = Vec::new();
+            data.extend_from_slice(&6000u64.to_le_bytes());
+            data.extend_from_slice(&600u64.to_le_bytes());
+            data.extend_from_slice(&1u64.to_le_bytes());
+            data.extend_from_slice(&4u64.to_le_bytes());
+            data.push(u8::try_from(authorities.len() * 4).unwrap());
+            for public_key in authorities {
+                data.extend_from_slice(public_key);
+                data.extend_from_slice(&1u64.to_le_bytes());
+            }
+            data.extend_from_slice(&[9; 32]);
+            data.push(1);
+            data
+        };
+
+        let config = super::decode_babe_configuration_output(
+            &encode(&[[7; 32], [8; 32]]),
+            super::BabeApiVersion::V2,
+        )
+        .unwrap();
+        let consensus =
+            chain_information::ChainInformationConsensus::babe_from_configuration(config).unwrap();
+
+        let chain_information = chain_information::ChainInformation {
+            finalized_block_header: Box::new(header::Header {
+                parent_hash: [0; 32],
+                number: 0,
+                state_root: [0; 32],
+                extrinsics_root: [0; 32],
+                digest: header::DigestRef::empty().into(),
+            }),
+            header_hash_function: trie::HashFunction::Blake2,
+            finalized_session_index: None,
+            finalized_beefy: None,
+            consensus,
+            finality: chain_information::ChainInformationFinality::Outsourced,
+        };
+        chain_information::ValidChainInformation::try_from(chain_information).unwrap();
+
+        let config = super::decode_babe_configuration_output(
+            &encode(&[[7; 32], [7; 32]]),
+            super::BabeApiVersion::V2,
+        )
+        .unwrap();
+        assert!(matches!(
+            chain_information::ChainInformationConsensus::babe_from_configuration(config),
+            Err(chain_information::ValidityError::DuplicateBabeAuthority)
+        ));
+    }
 }
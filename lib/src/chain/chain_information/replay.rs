@@ -0,0 +1,232 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Re-derive the chain information of a chain by replaying its headers.
+//!
+//! The [`replay_headers`] function starts from a known chain information, typically the one of
+//! the genesis block, and applies one by one the consensus and finality digest items (Babe epoch
+//! changes, Grandpa authorities changes, etc.) found in the headers of the canonical chain that
+//! follow it.
+//!
+//! This is slow, as every single header has to be verified, but doesn't require anything other
+//! than the headers themselves. It is meant to be used as a fallback when the chain information
+//! of the finalized block is missing or corrupted, for example in a database.
+
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use crate::chain::{blocks_tree, chain_information};
+
+/// Verifies the given headers one by one on top of `chain_information`, and returns the chain
+/// information of the last header.
+///
+/// The headers must be SCALE-encoded, and must be in ascending order: the first header must be
+/// a child of the finalized block of `chain_information`, and each following header must be a
+/// child of the previous one. Each header is considered as finalized after having been verified.
+///
+/// If no header is provided, `chain_information` is returned as-is.
+///
+/// `now_from_unix_epoch` is used to check that the headers aren't in the future. Since the
+/// headers being replayed are already stored, passing the current time is appropriate.
+///
+/// The returned chain information is the same as the one that would have been obtained by
+/// tracking the chain incrementally as blocks get finalized.
+pub fn replay_headers(
+    chain_information: chain_information::ValidChainInformation,
+    block_number_bytes: usize,
+    scale_encoded_headers: impl IntoIterator<Item = Vec<u8>>,
+    now_from_unix_epoch: Duration,
+) -> Result<chain_information::ValidChainInformation, ReplayError> {
+    let mut tree = blocks_tree::NonFinalizedTree::<()>::new(blocks_tree::Config {
+        chain_information,
+        block_number_bytes,
+        blocks_capacity: 1,
+        allow_unknown_consensus_engines: false,
+    });
+
+    for (index, scale_encoded_header) in scale_encoded_headers.into_iter().enumerate() {
+        let verified_header = match tree.verify_header(scale_encoded_header, now_from_unix_epoch) {
+            Ok(blocks_tree::HeaderVerifySuccess::Verified {
+                verified_header, ..
+            }) => verified_header,
+            // The tree is emptied after each header by finalizing it, and thus can't contain
+            // any duplicate.
+            Ok(blocks_tree::HeaderVerifySuccess::Duplicate) => unreachable!(),
+            Err(error) => return Err(ReplayError::HeaderVerify { index, error }),
+        };
+
+        let hash = *verified_header.hash();
+        tree.insert_verified_header(verified_header, ());
+
+        let _ = tree
+            .set_finalized_block(&hash)
+            .map_err(|error| ReplayError::SetFinalized { index, error })?;
+        debug_assert!(tree.is_empty());
+    }
+
+    Ok(tree.as_chain_information().into())
+}
+
+/// Error potentially returned by [`replay_headers`].
+#[derive(Debug, derive_more::Display)]
+pub enum ReplayError {
+    /// Failed to verify one of the headers.
+    #[display(fmt = "Failed to verify header #{index}: {error}")]
+    HeaderVerify {
+        /// Index within the list of headers of the header that has failed to verify.
+        index: usize,
+        /// Error that happened.
+        error: blocks_tree::HeaderVerifyError,
+    },
+    /// Failed to finalize one of the headers after it has been verified.
+    #[display(fmt = "Failed to finalize header #{index}: {error}")]
+    SetFinalized {
+        /// Index within the list of headers of the header that has failed to be finalized.
+        index: usize,
+        /// Error that happened.
+        error: blocks_tree::SetFinalizedError,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::replay_headers;
+    use crate::{
+        chain::{blocks_tree, chain_information},
+        header, trie,
+    };
+
+    use alloc::{boxed::Box, vec, vec::Vec};
+    use core::{num::NonZero, time::Duration};
+
+    #[test]
+    fn replay_grandpa_change_blake2() {
+        replay_grandpa_change(trie::HashFunction::Blake2);
+    }
+
+    #[test]
+    fn replay_grandpa_change_keccak256() {
+        replay_grandpa_change(trie::HashFunction::Keccak256);
+    }
+
+    fn replay_grandpa_change(hash_function: trie::HashFunction) {
+        let aura_keypair = schnorrkel::MiniSecretKey::from_bytes(&[1; 32])
+            .unwrap()
+            .expand_to_keypair(schnorrkel::ExpansionMode::Ed25519);
+        let grandpa_authority = |byte: u8| header::GrandpaAuthority {
+            public_key: [byte; 32],
+            weight: NonZero::<u64>::new(1).unwrap(),
+        };
+
+        let genesis = chain_information::ValidChainInformation::try_from(
+            chain_information::ChainInformation {
+                finalized_block_header: Box::new(header::Header {
+                    parent_hash: [0; 32],
+                    number: 0,
+                    state_root: [2; 32],
+                    extrinsics_root: [3; 32],
+                    digest: header::DigestRef::empty().into(),
+                }),
+                header_hash_function: hash_function,
+                finalized_session_index: None,
+                finalized_beefy: None,
+                consensus: chain_information::ChainInformationConsensus::Aura {
+                    finalized_authorities_list: vec![header::AuraAuthority {
+                        public_key: aura_keypair.public.to_bytes(),
+                    }],
+                    slot_duration: NonZero::<u64>::new(6000).unwrap(),
+                    finalized_disabled_authorities: Vec::new(),
+                },
+                finality: chain_information::ChainInformationFinality::Grandpa {
+                    after_finalized_block_authorities_set_id: 0,
+                    finalized_triggered_authorities: vec![grandpa_authority(0xaa)],
+                    finalized_scheduled_change: None,
+                },
+            },
+        )
+        .unwrap();
+
+        // Build a chain of three blocks, the second of which schedules a Grandpa authorities
+        // change to `0xbb`.
+        let mut headers = Vec::new();
+        let mut parent_hash = genesis.as_ref().finalized_block_hash(4);
+        for number in 1..=3 {
+            let mut digest = vec![header::DigestItem::AuraPreDigest(header::AuraPreDigest {
+                slot_number: number,
+            })];
+            if number == 2 {
+                digest.push(header::DigestItem::GrandpaConsensus(
+                    header::GrandpaConsensusLog::ScheduledChange(header::GrandpaScheduledChange {
+                        next_authorities: vec![grandpa_authority(0xbb)],
+                        delay: 0,
+                    }),
+                ));
+            }
+            let unsealed = header::Header {
+                parent_hash,
+                number,
+                state_root: [4; 32],
+                extrinsics_root: [5; 32],
+                digest: header::DigestRef::from_slice(&digest).unwrap().into(),
+            };
+            let signature = aura_keypair
+                .sign_simple(b"substrate", &unsealed.hash_with_function(4, hash_function));
+            digest.push(header::DigestItem::AuraSeal(signature.to_bytes()));
+            let sealed = header::Header {
+                digest: header::DigestRef::from_slice(&digest).unwrap().into(),
+                ..unsealed
+            };
+            parent_hash = sealed.hash_with_function(4, hash_function);
+            headers.push(sealed.scale_encoding_vec(4));
+        }
+
+        let now = Duration::from_secs(3600);
+        let replayed = replay_headers(genesis.clone(), 4, headers.clone(), now).unwrap();
+
+        assert_eq!(replayed.as_ref().finalized_block_header.number, 3);
+        assert_eq!(
+            chain_information::ChainInformation::from(replayed.clone()).finality,
+            chain_information::ChainInformationFinality::Grandpa {
+                after_finalized_block_authorities_set_id: 1,
+                finalized_triggered_authorities: vec![grandpa_authority(0xbb)],
+                finalized_scheduled_change: None,
+            }
+        );
+
+        // Compare with inserting all the blocks, then finalizing only the last one.
+        let mut tree = blocks_tree::NonFinalizedTree::<()>::new(blocks_tree::Config {
+            chain_information: genesis,
+            block_number_bytes: 4,
+            blocks_capacity: 8,
+            allow_unknown_consensus_engines: false,
+        });
+        for header in headers {
+            let blocks_tree::HeaderVerifySuccess::Verified {
+                verified_header, ..
+            } = tree.verify_header(header, now).unwrap()
+            else {
+                panic!()
+            };
+            tree.insert_verified_header(verified_header, ());
+        }
+        let _ = tree.set_finalized_block(&parent_hash).unwrap();
+        assert_eq!(
+            chain_information::ValidChainInformation::from(tree.as_chain_information()),
+            replayed
+        );
+    }
+}
@@ -0,0 +1,800 @@
+// Smoldot
+// Copyright (C) 2019-2022  Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! SCALE encoding and decoding of a [`ChainInformation`].
+//!
+//! The encoding starts with a version byte, followed with the content whose format depends on
+//! the version. The version is increased every time the format changes, and the decoder is
+//! capable of decoding all the versions that it knows about, which makes it possible to reload
+//! data encoded by an older version of the code.
+//!
+//! # Version 1
+//!
+//! - The SCALE-compact-encoded length of the SCALE-encoded finalized block header, followed
+//!   with the SCALE-encoded finalized block header.
+//! - The consensus information: `0` for unknown, `1` for Aura followed with the list of
+//!   authorities and the slot duration, or `2` for Babe followed with the number of slots per
+//!   epoch, the optional finalized block epoch, and the next epoch.
+//! - The finality information: `0` for outsourced, or `1` for Grandpa followed with the
+//!   authorities set id, the list of triggered authorities, and the optional scheduled change.
+//!
+//! # Version 2
+//!
+//! Identical to version 1, except that the Babe consensus information contains the optional slot
+//! duration right after the number of slots per epoch.
+//!
+//! # Version 3
+//!
+//! Identical to version 2, except that the Aura and Babe consensus information contain the
+//! SCALE-compact-encoded number of disabled authorities followed with their indices, each as a
+//! little endian `u32`, right after the slot duration.
+//!
+//! # Version 4
+//!
+//! Identical to version 3, except that the finalized block header is followed with a byte
+//! indicating the hash function used to calculate block hashes: `0` for Blake2-256 or `1` for
+//! Keccak-256. Older versions are always decoded as using Blake2-256.
+//!
+//! # Version 5
+//!
+//! Identical to version 4, except that the header hash function is followed with the optional
+//! session index of the finalized block: `0` if unknown, or `1` followed with the session index
+//! as a little endian `u32`. Older versions are always decoded as having an unknown session
+//! index.
+//!
+//! # Version 6
+//!
+//! Identical to version 5, except that the finality information is followed with the optional
+//! BEEFY information: `0` if unknown, or `1` followed with the validator set id as a little
+//! endian `u64` and the SCALE-compact-encoded number of validators followed with their 33 bytes
+//! public keys. Older versions are always decoded as having unknown BEEFY information.
+//!
+
+use super::{
+    BabeEpochInformation, BabeEpochInformationRef, BeefyInformation, ChainInformation,
+    ChainInformationConsensus, ChainInformationConsensusRef, ChainInformationFinality,
+    ChainInformationFinalityRef, ChainInformationRef,
+};
+use crate::{header, trie, util};
+
+use alloc::{boxed::Box, vec::Vec};
+use core::num::NonZero;
+
+/// Version byte written at the start of the encoding by [`ChainInformationRef::scale_encoding_vec`].
+pub(super) const CURRENT_VERSION: u8 = 6;
+
+impl<'a> ChainInformationRef<'a> {
+    /// Returns the SCALE encoding of this chain information.
+    ///
+    /// The encoding can be decoded back using [`ChainInformation::from_scale_encoded`].
+    pub fn scale_encoding_vec(&self, block_number_bytes: usize) -> Vec<u8> {
+        self.scale_encoding_vec_with_version(block_number_bytes, CURRENT_VERSION)
+    }
+
+    /// Returns the SCALE encoding of this chain information, using the given version of the
+    /// encoding.
+    ///
+    /// The fields that don't exist in the given version are omitted, no matter their value.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `version` is `0` or superior to [`CURRENT_VERSION`].
+    ///
+    pub(super) fn scale_encoding_vec_with_version(
+        &self,
+        block_number_bytes: usize,
+        version: u8,
+    ) -> Vec<u8> {
+        assert!((1..=CURRENT_VERSION).contains(&version));
+
+        let mut out = Vec::with_capacity(1024);
+        out.push(version);
+
+        let header = self
+            .finalized_block_header
+            .scale_encoding_vec(block_number_bytes);
+        out.extend_from_slice(util::encode_scale_compact_usize(header.len()).as_ref());
+        out.extend_from_slice(&header);
+        if version >= 4 {
+            out.push(match self.header_hash_function {
+                trie::HashFunction::Blake2 => 0,
+                trie::HashFunction::Keccak256 => 1,
+            });
+        }
+        if version >= 5 {
+            match self.finalized_session_index {
+                Some(session_index) => {
+                    out.push(1);
+                    out.extend_from_slice(&session_index.to_le_bytes());
+                }
+                None => out.push(0),
+            }
+        }
+
+        match &self.consensus {
+            ChainInformationConsensusRef::Unknown => out.push(0),
+            ChainInformationConsensusRef::Aura {
+                finalized_authorities_list,
+                slot_duration,
+                finalized_disabled_authorities,
+            } => {
+                out.push(1);
+                out.extend_from_slice(
+                    util::encode_scale_compact_usize(finalized_authorities_list.len()).as_ref(),
+                );
+                for authority in finalized_authorities_list.clone() {
+                    out.extend_from_slice(authority.public_key);
+                }
+                out.extend_from_slice(&slot_duration.get().to_le_bytes());
+                if version >= 3 {
+                    encode_disabled_authorities(&mut out, finalized_disabled_authorities);
+                }
+            }
+            ChainInformationConsensusRef::Babe {
+                slots_per_epoch,
+                slot_duration,
+                finalized_block_epoch_information,
+                finalized_next_epoch_transition,
+                finalized_disabled_authorities,
+            } => {
+                out.push(2);
+                out.extend_from_slice(&slots_per_epoch.get().to_le_bytes());
+                if version >= 2 {
+                    match slot_duration {
+                        Some(slot_duration) => {
+                            out.push(1);
+                            out.extend_from_slice(&slot_duration.get().to_le_bytes());
+                        }
+                        None => out.push(0),
+                    }
+                }
+                if version >= 3 {
+                    encode_disabled_authorities(&mut out, finalized_disabled_authorities);
+                }
+                match finalized_block_epoch_information {
+                    Some(epoch) => {
+                        out.push(1);
+                        encode_babe_epoch(&mut out, epoch);
+                    }
+                    None => out.push(0),
+                }
+                encode_babe_epoch(&mut out, finalized_next_epoch_transition);
+            }
+        }
+
+        match &self.finality {
+            ChainInformationFinalityRef::Outsourced => out.push(0),
+            ChainInformationFinalityRef::Grandpa {
+                after_finalized_block_authorities_set_id,
+                finalized_triggered_authorities,
+                finalized_scheduled_change,
+            } => {
+                out.push(1);
+                out.extend_from_slice(&after_finalized_block_authorities_set_id.to_le_bytes());
+                encode_grandpa_authorities(&mut out, finalized_triggered_authorities);
+                match finalized_scheduled_change {
+                    Some((block_number, authorities)) => {
+                        out.push(1);
+                        out.extend_from_slice(&block_number.to_le_bytes());
+                        encode_grandpa_authorities(&mut out, authorities);
+                    }
+                    None => out.push(0),
+                }
+            }
+        }
+
+        if version >= 6 {
+            match &self.finalized_beefy {
+                Some(beefy) => {
+                    out.push(1);
+                    out.extend_from_slice(&beefy.validator_set_id.to_le_bytes());
+                    out.extend_from_slice(
+                        util::encode_scale_compact_usize(beefy.validators.len()).as_ref(),
+                    );
+                    for validator in beefy.validators {
+                        out.extend_from_slice(validator);
+                    }
+                }
+                None => out.push(0),
+            }
+        }
+
+        out
+    }
+}
+
+impl ChainInformation {
+    /// Returns the SCALE encoding of this chain information.
+    ///
+    /// See [`ChainInformationRef::scale_encoding_vec`].
+    pub fn scale_encoding_vec(&self, block_number_bytes: usize) -> Vec<u8> {
+        ChainInformationRef::from(self).scale_encoding_vec(block_number_bytes)
+    }
+
+    /// Decodes a chain information encoded with [`ChainInformationRef::scale_encoding_vec`].
+    ///
+    /// Encodings generated by previous versions of this library can be decoded as well.
+    ///
+    /// > **Note**: The decoded chain information isn't guaranteed to be coherent. Use
+    /// >           [`super::ValidChainInformation`] in order to verify it.
+    pub fn from_scale_encoded(
+        scale_encoded: &[u8],
+        block_number_bytes: usize,
+    ) -> Result<ChainInformation, DecodeError> {
+        let (&version, content) = scale_encoded.split_first().ok_or(DecodeError::Invalid)?;
+
+        match version {
+            1..=6 => decode(content, version, block_number_bytes),
+            _ => Err(DecodeError::UnsupportedVersion(version)),
+        }
+    }
+}
+
+/// Error potentially returned by [`ChainInformation::from_scale_encoded`].
+#[derive(Debug, derive_more::Display, Clone)]
+pub enum DecodeError {
+    /// The encoding uses a version that this decoder doesn't know about. This is the case of
+    /// versions more recent than what this decoder supports, and of version `0`, which has never
+    /// been used.
+    #[display(fmt = "Unsupported chain information encoding version: {_0}")]
+    UnsupportedVersion(u8),
+    /// Failed to decode the finalized block header.
+    #[display(fmt = "Failed to decode finalized block header: {_0}")]
+    InvalidHeader(header::Error),
+    /// The encoding is invalid.
+    Invalid,
+}
+
+fn encode_babe_epoch(out: &mut Vec<u8>, epoch: &BabeEpochInformationRef) {
+    out.extend_from_slice(&epoch.epoch_index.to_le_bytes());
+    match epoch.start_slot_number {
+        Some(slot) => {
+            out.push(1);
+            out.extend_from_slice(&slot.to_le_bytes());
+        }
+        None => out.push(0),
+    }
+    out.extend_from_slice(util::encode_scale_compact_usize(epoch.authorities.len()).as_ref());
+    for authority in epoch.authorities.clone() {
+        out.extend_from_slice(authority.public_key);
+        out.extend_from_slice(&authority.weight.to_le_bytes());
+    }
+    out.extend_from_slice(epoch.randomness);
+    out.extend_from_slice(&epoch.c.0.to_le_bytes());
+    out.extend_from_slice(&epoch.c.1.to_le_bytes());
+    out.push(match epoch.allowed_slots {
+        header::BabeAllowedSlots::PrimarySlots => 0,
+        header::BabeAllowedSlots::PrimaryAndSecondaryPlainSlots => 1,
+        header::BabeAllowedSlots::PrimaryAndSecondaryVrfSlots => 2,
+    });
+}
+
+fn encode_disabled_authorities(out: &mut Vec<u8>, disabled_authorities: &[u32]) {
+    out.extend_from_slice(util::encode_scale_compact_usize(disabled_authorities.len()).as_ref());
+    for index in disabled_authorities {
+        out.extend_from_slice(&index.to_le_bytes());
+    }
+}
+
+fn encode_grandpa_authorities(out: &mut Vec<u8>, authorities: &[header::GrandpaAuthority]) {
+    out.extend_from_slice(util::encode_scale_compact_usize(authorities.len()).as_ref());
+    for authority in authorities {
+        out.extend_from_slice(&authority.public_key);
+        out.extend_from_slice(&authority.weight.get().to_le_bytes());
+    }
+}
+
+/// Decodes the content following the version byte. Versions 1 to 6 only differ by the
+/// presence of the Babe slot duration, of the disabled authorities, of the header hash
+/// function, of the session index, and of the BEEFY information.
+fn decode(
+    scale_encoded: &[u8],
+    version: u8,
+    block_number_bytes: usize,
+) -> Result<ChainInformation, DecodeError> {
+    let (rest, header) = util::nom_bytes_decode::<nom::error::Error<&[u8]>>(scale_encoded)
+        .map_err(|_| DecodeError::Invalid)?;
+    let finalized_block_header = Box::new(
+        header::decode(header, block_number_bytes)
+            .map_err(DecodeError::InvalidHeader)?
+            .into(),
+    );
+
+    let (rest, header_hash_function) = if version >= 4 {
+        match rest.split_first() {
+            Some((0, rest)) => (rest, trie::HashFunction::Blake2),
+            Some((1, rest)) => (rest, trie::HashFunction::Keccak256),
+            _ => return Err(DecodeError::Invalid),
+        }
+    } else {
+        (rest, trie::HashFunction::Blake2)
+    };
+
+    let (rest, finalized_session_index) = if version >= 5 {
+        util::nom_option_decode::<_, nom::error::Error<&[u8]>>(nom::number::complete::le_u32)(rest)
+            .map_err(|_| DecodeError::Invalid)?
+    } else {
+        (rest, None)
+    };
+
+    let result: nom::IResult<_, _, nom::error::Error<&[u8]>> =
+        nom::combinator::all_consuming(nom::sequence::tuple((
+            nom::branch::alt((
+                nom::combinator::map(nom::bytes::complete::tag(&[0]), |_| {
+                    ChainInformationConsensus::Unknown
+                }),
+                nom::combinator::map(
+                    nom::sequence::preceded(
+                        nom::bytes::complete::tag(&[1]),
+                        nom::sequence::tuple((
+                            nom::multi::length_count(
+                                util::nom_scale_compact_usize,
+                                nom::combinator::map(
+                                    nom::bytes::complete::take(32u32),
+                                    |public_key: &[u8]| header::AuraAuthority {
+                                        public_key: <[u8; 32]>::try_from(public_key).unwrap(),
+                                    },
+                                ),
+                            ),
+                            nom::combinator::map_opt(
+                                nom::number::complete::le_u64,
+                                NonZero::<u64>::new,
+                            ),
+                            |b| decode_disabled_authorities(b, version),
+                        )),
+                    ),
+                    |(
+                        finalized_authorities_list,
+                        slot_duration,
+                        finalized_disabled_authorities,
+                    )| {
+                        ChainInformationConsensus::Aura {
+                            finalized_authorities_list,
+                            slot_duration,
+                            finalized_disabled_authorities,
+                        }
+                    },
+                ),
+                nom::combinator::map(
+                    nom::sequence::preceded(
+                        nom::bytes::complete::tag(&[2]),
+                        nom::sequence::tuple((
+                            nom::combinator::map_opt(
+                                nom::number::complete::le_u64,
+                                NonZero::<u64>::new,
+                            ),
+                            |b| {
+                                if version >= 2 {
+                                    util::nom_option_decode(nom::combinator::map_opt(
+                                        nom::number::complete::le_u64,
+                                        NonZero::<u64>::new,
+                                    ))(b)
+                                } else {
+                                    Ok((b, None))
+                                }
+                            },
+                            |b| decode_disabled_authorities(b, version),
+                            util::nom_option_decode(decode_babe_epoch),
+                            decode_babe_epoch,
+                        )),
+                    ),
+                    |(
+                        slots_per_epoch,
+                        slot_duration,
+                        finalized_disabled_authorities,
+                        finalized_block_epoch_information,
+                        finalized_next_epoch_transition,
+                    )| ChainInformationConsensus::Babe {
+                        slots_per_epoch,
+                        slot_duration,
+                        finalized_disabled_authorities,
+                        finalized_block_epoch_information: finalized_block_epoch_information
+                            .map(Box::new),
+                        finalized_next_epoch_transition: Box::new(finalized_next_epoch_transition),
+                    },
+                ),
+            )),
+            nom::branch::alt((
+                nom::combinator::map(nom::bytes::complete::tag(&[0]), |_| {
+                    ChainInformationFinality::Outsourced
+                }),
+                nom::combinator::map(
+                    nom::sequence::preceded(
+                        nom::bytes::complete::tag(&[1]),
+                        nom::sequence::tuple((
+                            nom::number::complete::le_u64,
+                            decode_grandpa_authorities,
+                            util::nom_option_decode(nom::sequence::tuple((
+                                nom::number::complete::le_u64,
+                                decode_grandpa_authorities,
+                            ))),
+                        )),
+                    ),
+                    |(
+                        after_finalized_block_authorities_set_id,
+                        finalized_triggered_authorities,
+                        finalized_scheduled_change,
+                    )| ChainInformationFinality::Grandpa {
+                        after_finalized_block_authorities_set_id,
+                        finalized_triggered_authorities,
+                        finalized_scheduled_change,
+                    },
+                ),
+            )),
+            |b| {
+                if version >= 6 {
+                    util::nom_option_decode(decode_beefy)(b)
+                } else {
+                    Ok((b, None))
+                }
+            },
+        )))(rest);
+
+    let (_, (consensus, finality, finalized_beefy)) = result.map_err(|_| DecodeError::Invalid)?;
+
+    Ok(ChainInformation {
+        finalized_block_header,
+        header_hash_function,
+        finalized_session_index,
+        finalized_beefy,
+        consensus,
+        finality,
+    })
+}
+
+fn decode_beefy<'a, E: nom::error::ParseError<&'a [u8]>>(
+    bytes: &'a [u8],
+) -> nom::IResult<&'a [u8], BeefyInformation, E> {
+    nom::combinator::map(
+        nom::sequence::tuple((
+            nom::number::complete::le_u64,
+            nom::multi::length_count(
+                util::nom_scale_compact_usize,
+                nom::combinator::map(nom::bytes::complete::take(33u32), |public_key: &[u8]| {
+                    <[u8; 33]>::try_from(public_key).unwrap()
+                }),
+            ),
+        )),
+        |(validator_set_id, validators)| BeefyInformation {
+            validator_set_id,
+            validators,
+        },
+    )(bytes)
+}
+
+fn decode_babe_epoch<'a, E: nom::error::ParseError<&'a [u8]>>(
+    bytes: &'a [u8],
+) -> nom::IResult<&'a [u8], BabeEpochInformation, E> {
+    nom::combinator::map(
+        nom::sequence::tuple((
+            nom::number::complete::le_u64,
+            util::nom_option_decode(nom::number::complete::le_u64),
+            nom::multi::length_count(
+                util::nom_scale_compact_usize,
+                nom::combinator::map(
+                    nom::sequence::tuple((
+                        nom::bytes::complete::take(32u32),
+                        nom::number::complete::le_u64,
+                    )),
+                    |(public_key, weight): (&[u8], _)| header::BabeAuthority {
+                        public_key: <[u8; 32]>::try_from(public_key).unwrap(),
+                        weight,
+                    },
+                ),
+            ),
+            nom::bytes::complete::take(32u32),
+            nom::number::complete::le_u64,
+            nom::number::complete::le_u64,
+            nom::branch::alt((
+                nom::combinator::map(nom::bytes::complete::tag(&[0]), |_| {
+                    header::BabeAllowedSlots::PrimarySlots
+                }),
+                nom::combinator::map(nom::bytes::complete::tag(&[1]), |_| {
+                    header::BabeAllowedSlots::PrimaryAndSecondaryPlainSlots
+                }),
+                nom::combinator::map(nom::bytes::complete::tag(&[2]), |_| {
+                    header::BabeAllowedSlots::PrimaryAndSecondaryVrfSlots
+                }),
+            )),
+        )),
+        |(epoch_index, start_slot_number, authorities, randomness, c0, c1, allowed_slots)| {
+            BabeEpochInformation {
+                epoch_index,
+                start_slot_number,
+                authorities,
+                randomness: <[u8; 32]>::try_from(randomness).unwrap(),
+                c: (c0, c1),
+                allowed_slots,
+            }
+        },
+    )(bytes)
+}
+
+/// Decodes the list of disabled authorities, which is only present starting from version 3.
+fn decode_disabled_authorities<'a, E: nom::error::ParseError<&'a [u8]>>(
+    bytes: &'a [u8],
+    version: u8,
+) -> nom::IResult<&'a [u8], Vec<u32>, E> {
+    if version >= 3 {
+        nom::multi::length_count(util::nom_scale_compact_usize, nom::number::complete::le_u32)(
+            bytes,
+        )
+    } else {
+        Ok((bytes, Vec::new()))
+    }
+}
+
+fn decode_grandpa_authorities<'a, E: nom::error::ParseError<&'a [u8]>>(
+    bytes: &'a [u8],
+) -> nom::IResult<&'a [u8], Vec<header::GrandpaAuthority>, E> {
+    nom::multi::length_count(
+        util::nom_scale_compact_usize,
+        nom::combinator::map_opt(
+            nom::sequence::tuple((
+                nom::bytes::complete::take(32u32),
+                nom::number::complete::le_u64,
+            )),
+            |(public_key, weight): (&[u8], _)| {
+                Some(header::GrandpaAuthority {
+                    public_key: <[u8; 32]>::try_from(public_key).unwrap(),
+                    weight: NonZero::<u64>::new(weight)?,
+                })
+            },
+        ),
+    )(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        BabeEpochInformation, BeefyInformation, ChainInformation, ChainInformationConsensus,
+        ChainInformationFinality, ChainInformationRef,
+    };
+    use super::DecodeError;
+    use crate::{header, trie};
+    use alloc::{boxed::Box, vec, vec::Vec};
+    use core::num::NonZero;
+
+    fn babe_example() -> ChainInformation {
+        let epoch = |epoch_index| BabeEpochInformation {
+            epoch_index,
+            start_slot_number: if epoch_index == 0 { None } else { Some(1000) },
+            authorities: vec![header::BabeAuthority {
+                public_key: [7; 32],
+                weight: 1,
+            }],
+            randomness: [8; 32],
+            c: (1, 4),
+            allowed_slots: header::BabeAllowedSlots::PrimaryAndSecondaryVrfSlots,
+        };
+
+        ChainInformation {
+            finalized_block_header: Box::new(header::Header {
+                parent_hash: [1; 32],
+                number: 12,
+                state_root: [2; 32],
+                extrinsics_root: [3; 32],
+                digest: header::DigestRef::empty().into(),
+            }),
+            header_hash_function: trie::HashFunction::Blake2,
+            finalized_session_index: None,
+            finalized_beefy: None,
+            consensus: ChainInformationConsensus::Babe {
+                slots_per_epoch: NonZero::<u64>::new(600).unwrap(),
+                slot_duration: Some(NonZero::<u64>::new(6000).unwrap()),
+                finalized_block_epoch_information: Some(Box::new(epoch(4))),
+                finalized_next_epoch_transition: Box::new(epoch(5)),
+                finalized_disabled_authorities: vec![0],
+            },
+            finality: ChainInformationFinality::Grandpa {
+                after_finalized_block_authorities_set_id: 3,
+                finalized_triggered_authorities: vec![header::GrandpaAuthority {
+                    public_key: [6; 32],
+                    weight: NonZero::<u64>::new(1).unwrap(),
+                }],
+                finalized_scheduled_change: Some((
+                    20,
+                    vec![header::GrandpaAuthority {
+                        public_key: [9; 32],
+                        weight: NonZero::<u64>::new(2).unwrap(),
+                    }],
+                )),
+            },
+        }
+    }
+
+    #[test]
+    fn babe_roundtrip() {
+        let info = babe_example();
+        let encoded = info.scale_encoding_vec(4);
+        assert_eq!(encoded[0], 6);
+        assert_eq!(
+            ChainInformation::from_scale_encoded(&encoded, 4).unwrap(),
+            info
+        );
+    }
+
+    #[test]
+    fn aura_roundtrip() {
+        let info = ChainInformation {
+            consensus: ChainInformationConsensus::Aura {
+                finalized_authorities_list: vec![header::AuraAuthority {
+                    public_key: [4; 32],
+                }],
+                slot_duration: NonZero::<u64>::new(6000).unwrap(),
+                finalized_disabled_authorities: vec![0],
+            },
+            finality: ChainInformationFinality::Outsourced,
+            ..babe_example()
+        };
+        let encoded = info.scale_encoding_vec(8);
+        assert_eq!(
+            ChainInformation::from_scale_encoded(&encoded, 8).unwrap(),
+            info
+        );
+    }
+
+    #[test]
+    fn keccak_roundtrip() {
+        let info = ChainInformation {
+            header_hash_function: trie::HashFunction::Keccak256,
+            ..babe_example()
+        };
+        let encoded = info.scale_encoding_vec(4);
+        assert_eq!(
+            ChainInformation::from_scale_encoded(&encoded, 4).unwrap(),
+            info
+        );
+    }
+
+    #[test]
+    fn session_index_roundtrip() {
+        let info = ChainInformation {
+            finalized_session_index: Some(1234),
+            ..babe_example()
+        };
+        let encoded = info.scale_encoding_vec(4);
+        let decoded = ChainInformation::from_scale_encoded(&encoded, 4).unwrap();
+        assert_eq!(decoded.finalized_session_index, Some(1234));
+        assert_eq!(decoded, info);
+        assert_ne!(decoded, babe_example());
+
+        let encoded = babe_example().scale_encoding_vec(4);
+        let decoded = ChainInformation::from_scale_encoded(&encoded, 4).unwrap();
+        assert_eq!(decoded.finalized_session_index, None);
+    }
+
+    #[test]
+    fn decode_v4_has_no_session_index() {
+        let info = ChainInformation {
+            finalized_session_index: Some(1234),
+            ..babe_example()
+        };
+        let encoded = ChainInformationRef::from(&info).scale_encoding_vec_with_version(4, 4);
+        assert_eq!(encoded[0], 4);
+        assert_eq!(
+            ChainInformation::from_scale_encoded(&encoded, 4).unwrap(),
+            babe_example()
+        );
+    }
+
+    #[test]
+    fn beefy_roundtrip() {
+        let info = ChainInformation {
+            finalized_beefy: Some(BeefyInformation {
+                validator_set_id: 7,
+                validators: vec![[4; 33], [5; 33]],
+            }),
+            ..babe_example()
+        };
+        let encoded = info.scale_encoding_vec(4);
+        assert_eq!(
+            ChainInformation::from_scale_encoded(&encoded, 4).unwrap(),
+            info
+        );
+
+        // Older versions don't contain the BEEFY information.
+        let encoded = ChainInformationRef::from(&info).scale_encoding_vec_with_version(4, 5);
+        assert_eq!(
+            ChainInformation::from_scale_encoded(&encoded, 4).unwrap(),
+            babe_example()
+        );
+    }
+
+    #[test]
+    fn decode_v1() {
+        // Encoding of a Babe chain information produced before the slot duration was added.
+        let mut encoded = vec![1];
+        encoded.extend_from_slice(&[137, 1]); // Length of the header: 98 bytes
+        encoded.extend_from_slice(&[1; 32]); // Parent hash
+        encoded.push(48); // Block number: 12
+        encoded.extend_from_slice(&[2; 32]); // State root
+        encoded.extend_from_slice(&[3; 32]); // Extrinsics root
+        encoded.push(0); // Empty digest
+        encoded.push(2); // Babe
+        encoded.extend_from_slice(&600u64.to_le_bytes()); // Slots per epoch
+        encoded.push(0); // No finalized block epoch
+        encoded.extend_from_slice(&5u64.to_le_bytes()); // Next epoch index
+        encoded.extend_from_slice(&[1, 232, 3, 0, 0, 0, 0, 0, 0]); // Start slot: 1000
+        encoded.push(4); // One authority
+        encoded.extend_from_slice(&[7; 32]);
+        encoded.extend_from_slice(&1u64.to_le_bytes());
+        encoded.extend_from_slice(&[8; 32]); // Randomness
+        encoded.extend_from_slice(&1u64.to_le_bytes());
+        encoded.extend_from_slice(&4u64.to_le_bytes());
+        encoded.push(2); // Primary and secondary VRF slots
+        encoded.push(0); // Outsourced finality
+
+        let decoded = ChainInformation::from_scale_encoded(&encoded, 4).unwrap();
+
+        let expected = {
+            let mut info = babe_example();
+            if let ChainInformationConsensus::Babe {
+                slot_duration,
+                finalized_block_epoch_information,
+                finalized_disabled_authorities,
+                ..
+            } = &mut info.consensus
+            {
+                *slot_duration = None;
+                *finalized_block_epoch_information = None;
+                *finalized_disabled_authorities = Vec::new();
+            }
+            info.finality = ChainInformationFinality::Outsourced;
+            info
+        };
+        assert_eq!(decoded, expected);
+
+        // Encoding with the old version reproduces the original encoding.
+        assert_eq!(
+            ChainInformationRef::from(&decoded).scale_encoding_vec_with_version(4, 1),
+            encoded
+        );
+
+        // Re-encoding uses the latest version.
+        assert_eq!(
+            ChainInformation::from_scale_encoded(&decoded.scale_encoding_vec(4), 4).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut encoded = babe_example().scale_encoding_vec(4);
+        encoded[0] = 0xff;
+        assert!(matches!(
+            ChainInformation::from_scale_encoded(&encoded, 4),
+            Err(DecodeError::UnsupportedVersion(0xff))
+        ));
+
+        encoded[0] = 0;
+        assert!(matches!(
+            ChainInformation::from_scale_encoded(&encoded, 4),
+            Err(DecodeError::UnsupportedVersion(0))
+        ));
+    }
+
+    #[test]
+    fn trailing_data() {
+        let mut encoded = babe_example().scale_encoding_vec(4);
+        encoded.push(0);
+        assert!(matches!(
+            ChainInformation::from_scale_encoded(&encoded, 4),
+            Err(DecodeError::Invalid)
+        ));
+    }
+}
@@ -37,7 +37,7 @@
 use crate::{
     chain::chain_information::{
         build, BabeEpochInformation, ChainInformation, ChainInformationConsensus,
-        ChainInformationFinality, ValidChainInformation, ValidityError,
+        ChainInformationFinality, ChainInformationRef, ValidChainInformation, ValidityError,
     },
     executor, libp2p, trie,
 };
@@ -50,9 +50,12 @@ use alloc::{
 use core::{iter, num::NonZero, ops::Bound};
 
 mod light_sync_state;
+mod properties;
 mod structs;
 mod tests;
 
+pub use properties::{ChainProperties, PropertiesParseError};
+
 /// A configuration of a chain. Can be used to build a genesis block.
 #[derive(Clone)]
 pub struct ChainSpec {
@@ -107,94 +110,11 @@ impl ChainSpec {
             }
         };
 
-        let wasm_code = genesis_storage
-            .value(b":code")
-            .ok_or(FromGenesisStorageError::RuntimeNotFound)?;
-        let heap_pages =
-            executor::storage_heap_pages_to_value(genesis_storage.value(b":heappages"))
-                .map_err(FromGenesisStorageError::HeapPagesDecode)?;
-        let vm_prototype = executor::host::HostVmPrototype::new(executor::host::Config {
-            module: &wasm_code,
-            heap_pages,
-            exec_hint: executor::vm::ExecHint::ValidateAndExecuteOnce,
-            allow_unresolved_imports: true,
-        })
-        .map_err(FromGenesisStorageError::VmInitialization)?;
-
-        let state_version = vm_prototype
-            .runtime_version()
-            .decode()
-            .state_version
-            .unwrap_or(trie::TrieEntryVersion::V0);
-
-        let mut chain_information_build = build::ChainInformationBuild::new(build::Config {
-            finalized_block_header: build::ConfigFinalizedBlockHeader::Genesis {
-                state_trie_root_hash: {
-                    match self.genesis_storage() {
-                        GenesisStorage::TrieRootHash(hash) => *hash,
-                        GenesisStorage::Items(genesis_storage) => {
-                            let mut calculation =
-                                trie::calculate_root::root_merkle_value(trie::HashFunction::Blake2);
-
-                            loop {
-                                match calculation {
-                                    trie::calculate_root::RootMerkleValueCalculation::Finished {
-                                        hash,
-                                        ..
-                                    } => break hash,
-                                    trie::calculate_root::RootMerkleValueCalculation::NextKey(next_key) => {
-                                        // TODO: borrowchecker erroneously thinks that `outcome` borrows `next_key`
-                                        let outcome = genesis_storage.next_key(next_key.key_before(), next_key.or_equal(), next_key.prefix()).map(|k| k.collect::<Vec<_>>().into_iter());
-                                        calculation = next_key.inject_key(outcome);
-                                    }
-                                    trie::calculate_root::RootMerkleValueCalculation::StorageValue(
-                                        val,
-                                    ) => {
-                                        let key: alloc::vec::Vec<u8> = val.key().collect();
-                                        let value = genesis_storage.value(&key[..]);
-                                        calculation = val.inject(value.map(move |v| (v, state_version)));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                },
-            },
-            block_number_bytes: usize::from(self.block_number_bytes()),
-            runtime: vm_prototype,
-        });
-
-        let (chain_info, vm_prototype) = loop {
-            match chain_information_build {
-                build::ChainInformationBuild::InProgress(build::InProgress::StorageGet(get)) => {
-                    // TODO: child tries not supported
-                    let value = genesis_storage.value(get.key().as_ref());
-                    chain_information_build =
-                        get.inject_value(value.map(|v| (iter::once(v), state_version)));
-                }
-                build::ChainInformationBuild::InProgress(build::InProgress::NextKey(_nk)) => {
-                    todo!() // TODO:
-                }
-                build::ChainInformationBuild::InProgress(
-                    build::InProgress::ClosestDescendantMerkleValue(_mv),
-                ) => {
-                    todo!() // TODO:
-                }
-                build::ChainInformationBuild::Finished {
-                    result: Err(err), ..
-                } => {
-                    return Err(FromGenesisStorageError::BuildChainInformation(err));
-                }
-                build::ChainInformationBuild::Finished {
-                    result: Ok(chain_info),
-                    virtual_machine,
-                } => {
-                    break (chain_info, virtual_machine);
-                }
-            }
-        };
-
-        Ok((chain_info, vm_prototype))
+        chain_information_from_genesis_storage(
+            genesis_storage.iter(),
+            |key| genesis_storage.value(key),
+            usize::from(self.block_number_bytes()),
+        )
     }
 
     /// Returns the name of the chain. Meant to be displayed to the user.
@@ -245,6 +165,17 @@ impl ChainSpec {
             .map(|h| &h.0)
     }
 
+    /// Returns the list of runtime code substitutes found in the chain spec.
+    ///
+    /// Each item contains a block number and the runtime code that must be used instead of the
+    /// on-chain runtime code when executing the block with that number.
+    pub fn code_substitutes(&'_ self) -> impl Iterator<Item = (u64, &'_ [u8])> + '_ {
+        self.client_spec
+            .code_substitutes
+            .iter()
+            .map(|(number, code)| (*number, &code.0[..]))
+    }
+
     /// Returns the list of bootnode addresses found in the chain spec.
     ///
     /// Bootnode addresses that have failed to be parsed are returned as well in the form of
@@ -324,7 +255,8 @@ impl ChainSpec {
     /// The value of these properties is never interpreted by the local node, but can be served
     /// to a UI.
     ///
-    /// The returned value is a JSON-formatted map, for example `{"foo":"bar"}`.
+    /// The returned value is a JSON-formatted map, for example `{"foo":"bar"}`. Use
+    /// [`ChainProperties::from_json`] in order to access the well-known properties.
     pub fn properties(&self) -> &str {
         self.client_spec
             .properties
@@ -332,6 +264,45 @@ impl ChainSpec {
             .map_or("{}", |p| p.get())
     }
 
+    /// Returns a copy of this chain specification whose light sync state (also known as
+    /// checkpoint) corresponds to the given chain information.
+    ///
+    /// Light clients can use the returned chain specification in order to start syncing from the
+    /// finalized block of the chain information rather than from the genesis block.
+    ///
+    /// Only chains using Babe and Grandpa are supported, as this is the only combination that
+    /// light sync states can represent.
+    pub fn with_light_sync_state(
+        &self,
+        chain_information: ChainInformationRef,
+    ) -> Result<ChainSpec, ToLightSyncStateError> {
+        chain_information
+            .validate()
+            .map_err(ToLightSyncStateError::InvalidChainInformation)?;
+
+        let light_sync_state = light_sync_state::LightSyncState::from_chain_information(
+            chain_information,
+            self.block_number_bytes().into(),
+        )?;
+
+        let mut client_spec = self.client_spec.clone();
+        client_spec.light_sync_state = Some(light_sync_state);
+        Ok(ChainSpec { client_spec })
+    }
+
+    /// Returns a copy of this chain specification whose genesis storage is replaced with only
+    /// the hash of the root of its trie, which must be passed as parameter.
+    ///
+    /// The returned chain specification is considerably smaller. It can't be used in order to
+    /// build the genesis block, but light clients that start syncing from a light sync state
+    /// (see [`ChainSpec::with_light_sync_state`]) don't need the genesis storage.
+    pub fn with_genesis_state_root_hash(&self, state_root_hash: &[u8; 32]) -> ChainSpec {
+        let mut client_spec = self.client_spec.clone();
+        client_spec.genesis =
+            structs::Genesis::StateRootHash(structs::HashHexString(*state_root_hash));
+        ChainSpec { client_spec }
+    }
+
     pub fn light_sync_state(&self) -> Option<LightSyncState> {
         self.client_spec
             .light_sync_state
@@ -503,11 +474,16 @@ impl LightSyncState {
 
         ChainInformation {
             finalized_block_header: Box::new(self.inner.finalized_block_header.clone()),
+            header_hash_function: trie::HashFunction::Blake2,
+            finalized_session_index: None,
+            finalized_beefy: None,
             consensus: ChainInformationConsensus::Babe {
                 slots_per_epoch: NonZero::<u64>::new(next_epoch.duration)
                     .ok_or(CheckpointToChainInformationError::InvalidBabeSlotsPerEpoch)?,
+                slot_duration: None,
                 finalized_block_epoch_information: Some(convert_epoch(current_epoch)),
                 finalized_next_epoch_transition: convert_epoch(next_epoch),
+                finalized_disabled_authorities: Vec::new(),
             },
             finality: ChainInformationFinality::Grandpa {
                 after_finalized_block_authorities_set_id: self.inner.grandpa_authority_set.set_id,
@@ -533,6 +509,25 @@ impl LightSyncState {
     }
 }
 
+/// Error potentially returned by [`ChainSpec::with_light_sync_state`].
+#[derive(Debug, derive_more::Display)]
+pub enum ToLightSyncStateError {
+    /// The chain information isn't coherent.
+    #[display(fmt = "Invalid chain information: {_0}")]
+    InvalidChainInformation(ValidityError),
+    /// The chain doesn't use the Babe consensus algorithm.
+    NotBabe,
+    /// The chain doesn't use the Grandpa finality algorithm.
+    NotGrandpa,
+    /// The finalized block is the genesis block or belongs to the first Babe epoch.
+    GenesisBlock,
+    /// The number of the finalized block doesn't fit in 32 bits.
+    BlockNumberOverflow,
+    /// A Grandpa authorities change is scheduled, which can't be represented in a light sync
+    /// state.
+    GrandpaScheduledChange,
+}
+
 /// Error that can happen when parsing a chain spec JSON.
 #[derive(Debug, derive_more::Display)]
 #[display(fmt = "Failed to parse chain spec")]
@@ -544,6 +539,82 @@ enum ParseErrorInner {
     Other,
 }
 
+/// Builds the [`ChainInformation`] corresponding to a genesis block whose storage is provided
+/// directly rather than through a chain specification.
+///
+/// `genesis_storage` must yield all the storage items of the genesis block, while `value` must
+/// return the value of the storage item with the given key, if any.
+///
+/// In addition to the information, also returns the virtual machine of the runtime of the
+/// genesis block.
+///
+/// See also [`ChainSpec::to_chain_information`].
+pub fn chain_information_from_genesis_storage<'a>(
+    genesis_storage: impl Iterator<Item = (&'a [u8], &'a [u8])>,
+    value: impl Fn(&[u8]) -> Option<&'a [u8]>,
+    block_number_bytes: usize,
+) -> Result<(ValidChainInformation, executor::host::HostVmPrototype), FromGenesisStorageError> {
+    let wasm_code = value(b":code").ok_or(FromGenesisStorageError::RuntimeNotFound)?;
+    let heap_pages = executor::storage_heap_pages_to_value(value(b":heappages"))
+        .map_err(FromGenesisStorageError::HeapPagesDecode)?;
+    let vm_prototype = executor::host::HostVmPrototype::new(executor::host::Config {
+        module: &wasm_code,
+        heap_pages,
+        exec_hint: executor::vm::ExecHint::ValidateAndExecuteOnce,
+        allow_unresolved_imports: true,
+    })
+    .map_err(FromGenesisStorageError::VmInitialization)?;
+
+    let state_version = vm_prototype
+        .runtime_version()
+        .decode()
+        .state_version
+        .unwrap_or(trie::TrieEntryVersion::V0);
+
+    let mut chain_information_build = build::ChainInformationBuild::new(build::Config {
+        finalized_block_header: build::ConfigFinalizedBlockHeader::Genesis {
+            state_trie_root_hash: trie::trie_root(
+                state_version,
+                trie::HashFunction::Blake2,
+                genesis_storage,
+            ),
+        },
+        block_number_bytes,
+        runtime: vm_prototype,
+    });
+
+    let (chain_info, vm_prototype) = loop {
+        match chain_information_build {
+            build::ChainInformationBuild::InProgress(build::InProgress::StorageGet(get)) => {
+                // TODO: child tries not supported
+                let value = value(get.key().as_ref());
+                chain_information_build =
+                    get.inject_value(value.map(|v| (iter::once(v), state_version)));
+            }
+            build::ChainInformationBuild::InProgress(build::InProgress::NextKey(_))
+            | build::ChainInformationBuild::InProgress(
+                build::InProgress::ClosestDescendantMerkleValue(_),
+            ) => {
+                // TODO: support these storage accesses
+                return Err(FromGenesisStorageError::UnsupportedStorageAccess);
+            }
+            build::ChainInformationBuild::Finished {
+                result: Err(err), ..
+            } => {
+                return Err(FromGenesisStorageError::BuildChainInformation(err));
+            }
+            build::ChainInformationBuild::Finished {
+                result: Ok(chain_info),
+                virtual_machine,
+            } => {
+                break (chain_info, virtual_machine);
+            }
+        }
+    };
+
+    Ok((chain_info, vm_prototype))
+}
+
 /// Error when building the chain information from the genesis storage.
 #[derive(Debug, derive_more::Display)]
 pub enum FromGenesisStorageError {
@@ -560,6 +631,9 @@ pub enum FromGenesisStorageError {
     VmInitialization(executor::host::NewErr),
     /// Chain specification doesn't contain the list of storage items.
     UnknownStorageItems,
+    /// The runtime has iterated over the storage keys or requested the Merkle value of a trie
+    /// node while building the chain information, which isn't supported.
+    UnsupportedStorageAccess,
 }
 
 /// Error when building the chain information corresponding to a checkpoint.
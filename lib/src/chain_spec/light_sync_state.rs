@@ -15,8 +15,15 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use super::{ParseError, ParseErrorInner};
-use crate::header::BabeNextConfig;
+use super::{ParseError, ParseErrorInner, ToLightSyncStateError};
+use crate::{
+    chain::chain_information::{
+        BabeEpochInformationRef, ChainInformationConsensusRef, ChainInformationFinalityRef,
+        ChainInformationRef,
+    },
+    header::BabeNextConfig,
+    util,
+};
 
 use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
 use serde::{Deserialize, Serialize};
@@ -32,6 +39,109 @@ pub(super) struct LightSyncState {
 }
 
 impl LightSyncState {
+    /// Builds the light sync state corresponding to the given chain information.
+    ///
+    /// The produced light sync state only contains the information that
+    /// [`LightSyncState::decode`] and [`super::LightSyncState::to_chain_information`] use. In
+    /// particular, the fork trees of the Babe epoch changes and of the Grandpa pending changes
+    /// are always empty.
+    pub(super) fn from_chain_information(
+        chain_information: ChainInformationRef,
+        block_number_bytes: usize,
+    ) -> Result<Self, ToLightSyncStateError> {
+        if chain_information.finalized_block_header.number == 0 {
+            return Err(ToLightSyncStateError::GenesisBlock);
+        }
+
+        // The epochs are indexed by block number, and when decoding the light sync state the
+        // two epochs with the highest block numbers that are inferior or equal to the finalized
+        // block are considered as the current and next epoch.
+        let finalized_block_number = u32::try_from(chain_information.finalized_block_header.number)
+            .map_err(|_| ToLightSyncStateError::BlockNumberOverflow)?;
+        let finalized_block_hash = chain_information
+            .finalized_block_header
+            .hash(block_number_bytes);
+
+        let babe_epoch_changes = match &chain_information.consensus {
+            ChainInformationConsensusRef::Babe {
+                slots_per_epoch,
+                finalized_block_epoch_information: Some(current_epoch),
+                finalized_next_epoch_transition,
+                ..
+            } => {
+                let mut out = Vec::with_capacity(1024);
+                // Empty fork tree of epoch headers, without best finalized number.
+                out.extend_from_slice(util::encode_scale_compact_usize(0).as_ref());
+                out.push(0);
+                // List of epochs.
+                out.extend_from_slice(util::encode_scale_compact_usize(2).as_ref());
+                for (block_number, epoch) in [
+                    (finalized_block_number - 1, current_epoch),
+                    (finalized_block_number, finalized_next_epoch_transition),
+                ] {
+                    out.extend_from_slice(&finalized_block_hash);
+                    out.extend_from_slice(&block_number.to_le_bytes());
+                    // `PersistedEpoch::Regular`.
+                    out.push(1);
+                    encode_babe_epoch(&mut out, epoch, slots_per_epoch.get())?;
+                }
+                out
+            }
+            ChainInformationConsensusRef::Babe {
+                finalized_block_epoch_information: None,
+                ..
+            } => return Err(ToLightSyncStateError::GenesisBlock),
+            ChainInformationConsensusRef::Aura { .. } | ChainInformationConsensusRef::Unknown => {
+                return Err(ToLightSyncStateError::NotBabe)
+            }
+        };
+
+        let grandpa_authority_set = match &chain_information.finality {
+            ChainInformationFinalityRef::Grandpa {
+                after_finalized_block_authorities_set_id,
+                finalized_triggered_authorities,
+                finalized_scheduled_change: None,
+            } => {
+                let mut out = Vec::with_capacity(1024);
+                out.extend_from_slice(
+                    util::encode_scale_compact_usize(finalized_triggered_authorities.len())
+                        .as_ref(),
+                );
+                for authority in finalized_triggered_authorities.iter() {
+                    out.extend_from_slice(&authority.public_key);
+                    out.extend_from_slice(&authority.weight.get().to_le_bytes());
+                }
+                out.extend_from_slice(&after_finalized_block_authorities_set_id.to_le_bytes());
+                // Empty fork tree of pending standard changes, without best finalized number.
+                out.extend_from_slice(util::encode_scale_compact_usize(0).as_ref());
+                out.push(0);
+                // Empty list of pending forced changes.
+                out.extend_from_slice(util::encode_scale_compact_usize(0).as_ref());
+                // Empty list of authority set changes.
+                out.extend_from_slice(util::encode_scale_compact_usize(0).as_ref());
+                out
+            }
+            ChainInformationFinalityRef::Grandpa {
+                finalized_scheduled_change: Some(_),
+                ..
+            } => return Err(ToLightSyncStateError::GrandpaScheduledChange),
+            ChainInformationFinalityRef::Outsourced => {
+                return Err(ToLightSyncStateError::NotGrandpa)
+            }
+        };
+
+        Ok(LightSyncState {
+            babe_epoch_changes: HexString(babe_epoch_changes),
+            babe_finalized_block_weight: 0,
+            finalized_block_header: HexString(
+                chain_information
+                    .finalized_block_header
+                    .scale_encoding_vec(block_number_bytes),
+            ),
+            grandpa_authority_set: HexString(grandpa_authority_set),
+        })
+    }
+
     pub(super) fn decode(
         &self,
         block_number_bytes: usize,
@@ -217,6 +327,37 @@ fn persisted_epoch<'a, E: nom::error::ParseError<&'a [u8]>>(
     ))(bytes)
 }
 
+/// Encodes an epoch in the format decoded by [`babe_epoch`].
+fn encode_babe_epoch(
+    out: &mut Vec<u8>,
+    epoch: &BabeEpochInformationRef,
+    slots_per_epoch: u64,
+) -> Result<(), ToLightSyncStateError> {
+    out.extend_from_slice(&epoch.epoch_index.to_le_bytes());
+    out.extend_from_slice(
+        &epoch
+            .start_slot_number
+            .ok_or(ToLightSyncStateError::GenesisBlock)?
+            .to_le_bytes(),
+    );
+    out.extend_from_slice(&slots_per_epoch.to_le_bytes());
+    out.extend_from_slice(util::encode_scale_compact_usize(epoch.authorities.len()).as_ref());
+    for authority in epoch.authorities.clone() {
+        out.extend_from_slice(authority.public_key);
+        out.extend_from_slice(&authority.weight.to_le_bytes());
+    }
+    out.extend_from_slice(epoch.randomness);
+    for buffer in (BabeNextConfig {
+        c: epoch.c,
+        allowed_slots: epoch.allowed_slots,
+    })
+    .scale_encoding()
+    {
+        out.extend_from_slice(buffer.as_ref());
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 pub(super) struct BabeEpoch {
     pub(super) epoch_index: u64,
@@ -0,0 +1,96 @@
+// Smoldot
+// Copyright (C) 2019-2022  Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use alloc::{string::String, vec, vec::Vec};
+use serde::Deserialize;
+
+/// Decoded version of the properties found in a chain specification.
+///
+/// See [`super::ChainSpec::properties`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainProperties {
+    /// Prefix of the SS58 addresses of the chain.
+    #[serde(default)]
+    pub ss58_format: Option<u16>,
+
+    /// Number of decimals of each token of the chain.
+    ///
+    /// Chain specifications can contain either a single value or a list. A single value is
+    /// turned into a list of one element.
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
+    pub token_decimals: Option<Vec<u8>>,
+
+    /// Symbol of each token of the chain.
+    ///
+    /// Chain specifications can contain either a single value or a list. A single value is
+    /// turned into a list of one element.
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
+    pub token_symbol: Option<Vec<String>>,
+
+    /// All the other properties, as they are found in the chain specification.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+
+    /// JSON-encoded properties, including the well-known ones, exactly as they are found in
+    /// the chain specification. See [`ChainProperties::as_json`].
+    #[serde(skip)]
+    raw: String,
+}
+
+impl ChainProperties {
+    /// Parses the JSON-encoded properties, as returned by [`super::ChainSpec::properties`].
+    pub fn from_json(json: &str) -> Result<Self, PropertiesParseError> {
+        let mut properties: ChainProperties =
+            serde_json::from_str(json).map_err(PropertiesParseError)?;
+        properties.raw = json.into();
+        Ok(properties)
+    }
+
+    /// Returns all the properties, including the well-known ones, JSON-encoded exactly as they
+    /// are found in the chain specification.
+    ///
+    /// Contrary to the typed fields, the token fields are left untouched, whether they contain a
+    /// single value or a list.
+    pub fn as_json(&self) -> &str {
+        &self.raw
+    }
+}
+
+/// Error that can happen when parsing the properties of a chain specification.
+#[derive(Debug, derive_more::Display)]
+#[display(fmt = "{_0}")]
+pub struct PropertiesParseError(serde_json::Error);
+
+fn deserialize_one_or_many<'de, D, T>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    Ok(match Option::<OneOrMany<T>>::deserialize(deserializer)? {
+        None => None,
+        Some(OneOrMany::One(v)) => Some(vec![v]),
+        Some(OneOrMany::Many(v)) => Some(v),
+    })
+}
@@ -43,7 +43,6 @@ pub(super) struct ClientSpec {
     /// the given block number until the `spec_version`
     /// ([`crate::executor::host::CoreVersionRef::spec_version`]) on chain changes.
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
-    // TODO: make use of this
     pub(super) code_substitutes: HashMap<u64, HexString, fnv::FnvBuildHasher>,
     pub(super) boot_nodes: Vec<String>,
     #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
@@ -17,7 +17,17 @@
 
 #![cfg(test)]
 
-use super::{Bootnode, ChainSpec, CheckpointToChainInformationError};
+use super::{
+    Bootnode, ChainProperties, ChainSpec, CheckpointToChainInformationError, ToLightSyncStateError,
+};
+use crate::{
+    chain::chain_information::{
+        BabeEpochInformation, ChainInformation, ChainInformationConsensus, ChainInformationFinality,
+    },
+    header, trie,
+};
+use alloc::boxed::Box;
+use core::num::NonZero;
 
 #[test]
 fn can_decode_polkadot_genesis() {
@@ -28,6 +38,10 @@ fn can_decode_polkadot_genesis() {
     // code_substitutes field
     assert_eq!(specs.client_spec.code_substitutes.get(&1), None);
     assert!(specs.client_spec.code_substitutes.get(&5203203).is_some());
+    assert_eq!(
+        specs.code_substitutes().map(|(n, _)| n).collect::<Vec<_>>(),
+        vec![5203203]
+    );
 
     // bootnodes field
     assert_eq!(
@@ -155,3 +169,173 @@ fn issue_598() {
         Err(CheckpointToChainInformationError::GenesisBlockCheckpoint)
     ));
 }
+
+#[test]
+fn properties_single_values() {
+    let properties = ChainProperties::from_json(
+        r#"{"ss58Format":0,"tokenDecimals":10,"tokenSymbol":"DOT","isEthereum":false}"#,
+    )
+    .unwrap();
+    assert_eq!(properties.ss58_format, Some(0));
+    assert_eq!(properties.token_decimals, Some(vec![10]));
+    assert_eq!(properties.token_symbol, Some(vec!["DOT".to_owned()]));
+    assert_eq!(properties.extra.len(), 1);
+    assert_eq!(
+        properties.extra["isEthereum"],
+        serde_json::Value::Bool(false)
+    );
+    assert_eq!(
+        properties.as_json(),
+        r#"{"ss58Format":0,"tokenDecimals":10,"tokenSymbol":"DOT","isEthereum":false}"#
+    );
+}
+
+#[test]
+fn properties_arrays() {
+    let properties = ChainProperties::from_json(
+        r#"{"ss58Format":42,"tokenDecimals":[12,18],"tokenSymbol":["UNIT","ETH"]}"#,
+    )
+    .unwrap();
+    assert_eq!(properties.ss58_format, Some(42));
+    assert_eq!(properties.token_decimals, Some(vec![12, 18]));
+    assert_eq!(
+        properties.token_symbol,
+        Some(vec!["UNIT".to_owned(), "ETH".to_owned()])
+    );
+    assert!(properties.extra.is_empty());
+}
+
+#[test]
+fn properties_missing() {
+    let properties = ChainProperties::from_json("{}").unwrap();
+    assert_eq!(properties.ss58_format, None);
+    assert_eq!(properties.token_decimals, None);
+    assert_eq!(properties.token_symbol, None);
+    assert!(properties.extra.is_empty());
+    assert_eq!(properties.as_json(), "{}");
+
+    assert!(ChainProperties::from_json(r#"{"ss58Format":"foo"}"#).is_err());
+}
+
+#[test]
+fn properties_of_example_chain_spec() {
+    let spec = ChainSpec::from_json_bytes(&include_bytes!("./tests/example.json")[..]).unwrap();
+    let properties = ChainProperties::from_json(spec.properties()).unwrap();
+    assert_eq!(properties.ss58_format, Some(0));
+    assert_eq!(properties.token_decimals, Some(vec![12]));
+    assert_eq!(properties.token_symbol, Some(vec!["DOT".to_owned()]));
+}
+
+fn babe_grandpa_chain_information() -> ChainInformation {
+    let epoch = |epoch_index, start_slot_number| BabeEpochInformation {
+        epoch_index,
+        start_slot_number: Some(start_slot_number),
+        authorities: vec![header::BabeAuthority {
+            public_key: [epoch_index as u8; 32],
+            weight: 1,
+        }],
+        randomness: [7; 32],
+        c: (1, 4),
+        allowed_slots: header::BabeAllowedSlots::PrimaryAndSecondaryVrfSlots,
+    };
+
+    // Header of block #1000, authored through a Babe secondary slot claim at slot 2500.
+    let mut header = Vec::new();
+    header.extend_from_slice(&[1; 32]); // Parent hash
+    header.extend_from_slice(&[0xa1, 0x0f]); // Block number
+    header.extend_from_slice(&[2; 32]); // State root
+    header.extend_from_slice(&[3; 32]); // Extrinsics root
+    header.push(8); // Two digest items
+    header.extend_from_slice(&[6, b'B', b'A', b'B', b'E', 52, 2, 0, 0, 0, 0]); // Pre-runtime
+    header.extend_from_slice(&2500u64.to_le_bytes());
+    header.extend_from_slice(&[5, b'B', b'A', b'B', b'E', 1, 1]); // Seal
+    header.extend_from_slice(&[0; 64]);
+
+    ChainInformation {
+        finalized_block_header: Box::new(header::decode(&header, 4).unwrap().into()),
+        header_hash_function: trie::HashFunction::Blake2,
+        finalized_session_index: None,
+        finalized_beefy: None,
+        consensus: ChainInformationConsensus::Babe {
+            slots_per_epoch: NonZero::<u64>::new(600).unwrap(),
+            slot_duration: None,
+            finalized_block_epoch_information: Some(Box::new(epoch(4, 2400))),
+            finalized_next_epoch_transition: Box::new(epoch(5, 3000)),
+            finalized_disabled_authorities: Vec::new(),
+        },
+        finality: ChainInformationFinality::Grandpa {
+            after_finalized_block_authorities_set_id: 3,
+            finalized_triggered_authorities: vec![header::GrandpaAuthority {
+                public_key: [9; 32],
+                weight: NonZero::<u64>::new(1).unwrap(),
+            }],
+            finalized_scheduled_change: None,
+        },
+    }
+}
+
+#[test]
+fn light_sync_state_roundtrip() {
+    let spec = ChainSpec::from_json_bytes(include_bytes!("./tests/example.json")).unwrap();
+    assert!(spec.light_sync_state().is_none());
+
+    let chain_information = babe_grandpa_chain_information();
+    let with_state = spec
+        .with_light_sync_state((&chain_information).into())
+        .unwrap();
+
+    // The generated chain specification must be loadable.
+    let reparsed = ChainSpec::from_json_bytes(with_state.serialize()).unwrap();
+    assert_eq!(reparsed.id(), spec.id());
+    let decoded = reparsed
+        .light_sync_state()
+        .unwrap()
+        .to_chain_information()
+        .unwrap();
+    assert_eq!(ChainInformation::from(decoded), chain_information);
+}
+
+#[test]
+fn genesis_state_root_hash_replaces_storage() {
+    let spec = ChainSpec::from_json_bytes(include_bytes!("./tests/example.json")).unwrap();
+    assert!(spec.genesis_storage().into_genesis_items().is_some());
+
+    let compact = spec.with_genesis_state_root_hash(&[5; 32]);
+    assert_eq!(
+        compact.genesis_storage().into_trie_root_hash(),
+        Some(&[5; 32])
+    );
+
+    // The generated chain specification must be loadable.
+    let reparsed = ChainSpec::from_json_bytes(compact.serialize()).unwrap();
+    assert_eq!(reparsed.id(), spec.id());
+    assert_eq!(
+        reparsed.genesis_storage().into_trie_root_hash(),
+        Some(&[5; 32])
+    );
+}
+
+#[test]
+fn light_sync_state_requires_babe_grandpa() {
+    let spec = ChainSpec::from_json_bytes(include_bytes!("./tests/example.json")).unwrap();
+
+    let mut chain_information = babe_grandpa_chain_information();
+    chain_information.finality = ChainInformationFinality::Outsourced;
+    assert!(matches!(
+        spec.with_light_sync_state((&chain_information).into()),
+        Err(ToLightSyncStateError::NotGrandpa)
+    ));
+
+    let mut chain_information = babe_grandpa_chain_information();
+    if let ChainInformationConsensus::Babe {
+        finalized_block_epoch_information,
+        ..
+    } = &mut chain_information.consensus
+    {
+        *finalized_block_epoch_information = None;
+    }
+    assert!(matches!(
+        spec.with_light_sync_state((&chain_information).into()),
+        Err(ToLightSyncStateError::InvalidChainInformation(_))
+    ));
+}
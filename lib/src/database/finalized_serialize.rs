@@ -59,8 +59,8 @@ pub fn encode_chain_storage<'a>(
 ) -> String {
     let information = information.into();
 
-    let decoded = defs::SerializedChainInformation::V1(defs::SerializedChainInformationV1::new(
-        information.as_ref(),
+    let decoded = defs::SerializedChainInformation::V2(defs::SerializedChainInformationV1::new(
+        information,
         block_number_bytes,
         finalized_storage,
     ));
@@ -71,6 +71,12 @@ pub fn encode_chain_storage<'a>(
 /// Deserializes the information about the chain.
 ///
 /// This is the invert operation of [`encode_chain_storage`].
+///
+/// The encoded string contains the integrity hash of the chain information, which is compared
+/// with the hash of the decoded chain information. An error is returned if they don't match.
+/// Strings written before the integrity hash was introduced don't contain it, in which case this
+/// check is skipped.
+/// See [`chain_information::ValidChainInformationRef::integrity_hash`].
 pub fn decode_chain(encoded: &str, block_number_bytes: usize) -> Result<Decoded, CorruptedError> {
     let encoded: defs::SerializedChainInformation =
         serde_json::from_str(encoded).map_err(|e| CorruptedError(CorruptedErrorInner::Serde(e)))?;
@@ -78,6 +84,7 @@ pub fn decode_chain(encoded: &str, block_number_bytes: usize) -> Result<Decoded,
     let defs::Decoded {
         chain_information,
         storage,
+        integrity_hash,
     } = encoded
         .decode(block_number_bytes)
         .map_err(|err| CorruptedError(CorruptedErrorInner::Deserialize(err)))?;
@@ -86,6 +93,13 @@ pub fn decode_chain(encoded: &str, block_number_bytes: usize) -> Result<Decoded,
         .map_err(CorruptedErrorInner::InvalidChain)
         .map_err(CorruptedError)?;
 
+    if let Some(integrity_hash) = integrity_hash {
+        chain_information::ValidChainInformationRef::from(&chain_information)
+            .verify_integrity_hash(block_number_bytes, &integrity_hash)
+            .map_err(CorruptedErrorInner::IntegrityHash)
+            .map_err(CorruptedError)?;
+    }
+
     Ok(Decoded {
         chain_information,
         storage,
@@ -113,4 +127,119 @@ enum CorruptedErrorInner {
     Deserialize(defs::DeserializeError),
     #[display(fmt = "Invalid chain information: {_0}")]
     InvalidChain(chain_information::ValidityError),
+    #[display(fmt = "Failed to verify the integrity hash: {_0}")]
+    IntegrityHash(chain_information::IntegrityHashError),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{chain::chain_information, header, trie};
+    use alloc::{boxed::Box, vec, vec::Vec};
+    use core::num::NonZero;
+
+    fn example() -> chain_information::ValidChainInformation {
+        chain_information::ValidChainInformation::try_from(chain_information::ChainInformation {
+            finalized_block_header: Box::new(header::Header {
+                parent_hash: [0; 32],
+                number: 0,
+                state_root: [2; 32],
+                extrinsics_root: [3; 32],
+                digest: header::DigestRef::empty().into(),
+            }),
+            header_hash_function: trie::HashFunction::Blake2,
+            finalized_session_index: None,
+            finalized_beefy: None,
+            consensus: chain_information::ChainInformationConsensus::Aura {
+                finalized_authorities_list: vec![header::AuraAuthority {
+                    public_key: [1; 32],
+                }],
+                slot_duration: NonZero::<u64>::new(6000).unwrap(),
+                finalized_disabled_authorities: Vec::new(),
+            },
+            finality: chain_information::ChainInformationFinality::Outsourced,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn integrity_hash_round_trip() {
+        let info = example();
+        let encoded = super::encode_chain(&info, 4);
+        assert!(encoded.contains("\"integrity_hash\""));
+
+        let decoded = super::decode_chain(&encoded, 4).unwrap();
+        assert!(decoded.chain_information.as_ref().eq_owned(&info.into()));
+    }
+
+    #[test]
+    fn session_index_round_trip() {
+        let mut info = chain_information::ChainInformation::from(example());
+        info.finalized_session_index = Some(42);
+        let info = chain_information::ValidChainInformation::try_from(info).unwrap();
+        let encoded = super::encode_chain(&info, 4);
+
+        let decoded = super::decode_chain(&encoded, 4).unwrap();
+        assert_eq!(
+            decoded.chain_information.as_ref().finalized_session_index,
+            Some(42)
+        );
+        assert!(decoded.chain_information.as_ref().eq_owned(&info.into()));
+    }
+
+    #[test]
+    fn beefy_round_trip() {
+        let mut info = chain_information::ChainInformation::from(example());
+        info.finalized_beefy = Some(chain_information::BeefyInformation {
+            validator_set_id: 0,
+            validators: vec![[2; 33], [3; 33]],
+        });
+        let info = chain_information::ValidChainInformation::try_from(info).unwrap();
+        let encoded = super::encode_chain(&info, 4);
+
+        let decoded = super::decode_chain(&encoded, 4).unwrap();
+        assert!(decoded.chain_information.as_ref().eq_owned(&info.into()));
+    }
+
+    #[test]
+    fn integrity_hash_mismatch_rejected() {
+        let encoded = super::encode_chain(&example(), 4);
+        let tampered = encoded.replace(
+            "\"aura_slot_duration\":6000",
+            "\"aura_slot_duration\":12000",
+        );
+        assert_ne!(encoded, tampered);
+        assert!(super::decode_chain(&tampered, 4).is_err());
+    }
+
+    #[test]
+    fn missing_integrity_hash_rejected() {
+        let encoded = super::encode_chain(&example(), 4);
+        let mut value: serde_json::Value = serde_json::from_str(&encoded).unwrap();
+        assert!(value
+            .as_object_mut()
+            .unwrap()
+            .remove("integrity_hash")
+            .is_some());
+
+        let stripped = serde_json::to_string(&value).unwrap();
+        assert!(super::decode_chain(&stripped, 4).is_err());
+    }
+
+    #[test]
+    fn v1_without_integrity_hash_decodes() {
+        // Written before the integrity hash was introduced.
+        let encoded = concat!(
+            r#"{"version":"1","#,
+            r#""finalized_block_header":"0000000000000000000000000000000000000000000000000000000000000000000202020202020202020202020202020202020202020202020202020202020202030303030303030303030303030303030303030303030303030303030303030300","#,
+            r#""aura_slot_duration":6000,"#,
+            r#""aura_finalized_authorities":[{"public_key":"0101010101010101010101010101010101010101010101010101010101010101"}]}"#
+        );
+
+        let decoded = super::decode_chain(encoded, 4).unwrap();
+        assert!(decoded
+            .chain_information
+            .as_ref()
+            .eq_owned(&example().into()));
+        assert!(decoded.storage.is_none());
+    }
 }
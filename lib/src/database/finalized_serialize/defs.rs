@@ -17,7 +17,7 @@
 
 //! Type definitions to help with serializing/deserializing from/to the local storage.
 
-use crate::{chain::chain_information, header};
+use crate::{chain::chain_information, header, trie};
 
 use alloc::{boxed::Box, vec::Vec};
 use core::{fmt, num::NonZero};
@@ -31,19 +31,34 @@ pub(super) enum DeserializeError {
     ConsensusAlgorithmsMismatch,
     /// Some Babe-related information is missing.
     MissingBabeInformation,
+    /// The integrity hash is missing or incomplete.
+    MissingIntegrityHash,
+    /// The public key of a BEEFY validator doesn't have the correct length.
+    InvalidBeefyPublicKey,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "version")]
 pub(super) enum SerializedChainInformation {
+    /// Format written before the integrity hash was introduced. The integrity hash is optional.
     #[serde(rename = "1")]
     V1(SerializedChainInformationV1),
+    /// Same as [`SerializedChainInformation::V1`], except that the integrity hash is mandatory.
+    #[serde(rename = "2")]
+    V2(SerializedChainInformationV1),
 }
 
 impl SerializedChainInformation {
     pub(super) fn decode(self, block_number_bytes: usize) -> Result<Decoded, DeserializeError> {
         Ok(match self {
             SerializedChainInformation::V1(from) => from.decode(block_number_bytes)?,
+            SerializedChainInformation::V2(from) => {
+                let decoded = from.decode(block_number_bytes)?;
+                if decoded.integrity_hash.is_none() {
+                    return Err(DeserializeError::MissingIntegrityHash);
+                }
+                decoded
+            }
         })
     }
 }
@@ -53,6 +68,9 @@ pub struct Decoded {
     pub chain_information: chain_information::ChainInformation,
     /// All the keys and values found in the database. `None` if no information was found.
     pub storage: Option<HashMap<Vec<u8>, Vec<u8>, fnv::FnvBuildHasher>>,
+    /// Hash of the chain information that was stored alongside of it. `None` if the data was
+    /// written by a version that didn't store the integrity hash.
+    pub integrity_hash: Option<chain_information::IntegrityHash>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -64,15 +82,23 @@ pub(super) struct SerializedChainInformationV1 {
     )]
     finalized_block_header: Vec<u8>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
+    header_hash_function: Option<SerializedHeaderHashFunctionV1>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    finalized_session_index: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     aura_slot_duration: Option<NonZero<u64>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     aura_finalized_authorities: Option<Vec<SerializedAuraAuthorityV1>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     babe_slots_per_epoch: Option<NonZero<u64>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
+    babe_slot_duration: Option<NonZero<u64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     babe_finalized_block_epoch_information: Option<SerializedBabeEpochInformationV1>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     babe_finalized_next_epoch_transition: Option<SerializedBabeEpochInformationV1>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    finalized_disabled_authorities: Vec<u32>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     grandpa_after_finalized_block_authorities_set_id: Option<u64>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -80,15 +106,26 @@ pub(super) struct SerializedChainInformationV1 {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     grandpa_finalized_scheduled_change: Option<SerializedFinalizedScheduledChangeV1>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
+    beefy_validator_set_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    beefy_validators: Vec<SerializedBeefyValidatorV1>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     finalized_storage: Option<Vec<SerializedFinalizedStorageEntryV1>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    integrity_hash: Option<SerializedHash32V1>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    integrity_hash_version: Option<u8>,
 }
 
 impl SerializedChainInformationV1 {
     pub(super) fn new(
-        from: chain_information::ChainInformationRef<'_>,
+        from: chain_information::ValidChainInformationRef<'_>,
         block_number_bytes: usize,
         finalized_storage: Option<impl Iterator<Item = (impl AsRef<[u8]>, impl AsRef<[u8]>)>>,
     ) -> Self {
+        let integrity_hash = from.integrity_hash(block_number_bytes);
+        let from = from.as_ref();
+
         SerializedChainInformationV1 {
             finalized_block_header: from
                 .finalized_block_header
@@ -97,6 +134,13 @@ impl SerializedChainInformationV1 {
                     a.extend_from_slice(b.as_ref());
                     a
                 }),
+            header_hash_function: match from.header_hash_function {
+                // Blake2 is the default and is omitted in order to keep the serialized format
+                // backwards-compatible.
+                trie::HashFunction::Blake2 => None,
+                trie::HashFunction::Keccak256 => Some(SerializedHeaderHashFunctionV1::Keccak256),
+            },
+            finalized_session_index: from.finalized_session_index,
             aura_slot_duration: if let chain_information::ChainInformationConsensusRef::Aura {
                 slot_duration,
                 ..
@@ -125,6 +169,15 @@ impl SerializedChainInformationV1 {
             } else {
                 None
             },
+            babe_slot_duration: if let chain_information::ChainInformationConsensusRef::Babe {
+                slot_duration,
+                ..
+            } = &from.consensus
+            {
+                *slot_duration
+            } else {
+                None
+            },
             babe_finalized_block_epoch_information:
                 if let chain_information::ChainInformationConsensusRef::Babe {
                     finalized_block_epoch_information,
@@ -145,6 +198,17 @@ impl SerializedChainInformationV1 {
                 } else {
                     None
                 },
+            finalized_disabled_authorities: match &from.consensus {
+                chain_information::ChainInformationConsensusRef::Aura {
+                    finalized_disabled_authorities,
+                    ..
+                }
+                | chain_information::ChainInformationConsensusRef::Babe {
+                    finalized_disabled_authorities,
+                    ..
+                } => finalized_disabled_authorities.to_vec(),
+                chain_information::ChainInformationConsensusRef::Unknown => Vec::new(),
+            },
             grandpa_after_finalized_block_authorities_set_id: match from.finality {
                 chain_information::ChainInformationFinalityRef::Outsourced => None,
                 chain_information::ChainInformationFinalityRef::Grandpa {
@@ -175,6 +239,15 @@ impl SerializedChainInformationV1 {
                     })
                 }
             },
+            beefy_validator_set_id: from.finalized_beefy.as_ref().map(|b| b.validator_set_id),
+            beefy_validators: from.finalized_beefy.as_ref().map_or(Vec::new(), |b| {
+                b.validators
+                    .iter()
+                    .map(|public_key| SerializedBeefyValidatorV1 {
+                        public_key: public_key.to_vec(),
+                    })
+                    .collect()
+            }),
             finalized_storage: finalized_storage.map(|storage| {
                 storage
                     .map(|(k, v)| SerializedFinalizedStorageEntryV1 {
@@ -183,26 +256,39 @@ impl SerializedChainInformationV1 {
                     })
                     .collect()
             }),
+            integrity_hash: Some(SerializedHash32V1(integrity_hash.hash)),
+            integrity_hash_version: Some(integrity_hash.encoding_version),
         }
     }
 }
 
 impl SerializedChainInformationV1 {
     pub(super) fn decode(self, block_number_bytes: usize) -> Result<Decoded, DeserializeError> {
+        let integrity_hash = match (self.integrity_hash, self.integrity_hash_version) {
+            (Some(hash), Some(encoding_version)) => Some(chain_information::IntegrityHash {
+                encoding_version,
+                hash: hash.0,
+            }),
+            (None, None) => None,
+            _ => return Err(DeserializeError::MissingIntegrityHash),
+        };
+
         let consensus = match (
             self.aura_finalized_authorities,
             self.aura_slot_duration,
             self.babe_slots_per_epoch,
+            self.babe_slot_duration,
             self.babe_finalized_block_epoch_information,
             self.babe_finalized_next_epoch_transition,
         ) {
-            (Some(aura_authorities), Some(slot_duration), None, None, None) => {
+            (Some(aura_authorities), Some(slot_duration), None, None, None, None) => {
                 chain_information::ChainInformationConsensus::Aura {
                     finalized_authorities_list: aura_authorities
                         .into_iter()
                         .map(Into::into)
                         .collect(),
                     slot_duration,
+                    finalized_disabled_authorities: self.finalized_disabled_authorities,
                 }
             }
 
@@ -210,11 +296,13 @@ impl SerializedChainInformationV1 {
                 None,
                 None,
                 babe_slots_per_epoch,
+                babe_slot_duration,
                 babe_finalized_block_epoch_information,
                 babe_finalized_next_epoch_transition,
             ) => chain_information::ChainInformationConsensus::Babe {
                 slots_per_epoch: babe_slots_per_epoch
                     .ok_or(DeserializeError::MissingBabeInformation)?,
+                slot_duration: babe_slot_duration,
                 finalized_block_epoch_information: babe_finalized_block_epoch_information
                     .map(|i| Box::new(i.into())),
                 finalized_next_epoch_transition: Box::new(
@@ -222,6 +310,7 @@ impl SerializedChainInformationV1 {
                         .map(Into::into)
                         .ok_or(DeserializeError::MissingBabeInformation)?,
                 ),
+                finalized_disabled_authorities: self.finalized_disabled_authorities,
             },
 
             _ => return Err(DeserializeError::ConsensusAlgorithmsMismatch),
@@ -233,6 +322,24 @@ impl SerializedChainInformationV1 {
                     .map_err(DeserializeError::Header)?
                     .into(),
             ),
+            header_hash_function: match self.header_hash_function {
+                None | Some(SerializedHeaderHashFunctionV1::Blake2) => trie::HashFunction::Blake2,
+                Some(SerializedHeaderHashFunctionV1::Keccak256) => trie::HashFunction::Keccak256,
+            },
+            finalized_session_index: self.finalized_session_index,
+            finalized_beefy: if let Some(validator_set_id) = self.beefy_validator_set_id {
+                Some(chain_information::BeefyInformation {
+                    validator_set_id,
+                    validators: self
+                        .beefy_validators
+                        .into_iter()
+                        .map(|v| <[u8; 33]>::try_from(v.public_key))
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| DeserializeError::InvalidBeefyPublicKey)?,
+                })
+            } else {
+                None
+            },
             consensus,
             finality: if let Some(set_id) = self.grandpa_after_finalized_block_authorities_set_id {
                 chain_information::ChainInformationFinality::Grandpa {
@@ -274,10 +381,30 @@ impl SerializedChainInformationV1 {
         Ok(Decoded {
             chain_information,
             storage: finalized_storage,
+            integrity_hash,
         })
     }
 }
 
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SerializedBeefyValidatorV1 {
+    #[serde(
+        serialize_with = "serialize_bytes",
+        deserialize_with = "deserialize_bytes"
+    )]
+    public_key: Vec<u8>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct SerializedHash32V1(
+    #[serde(
+        serialize_with = "serialize_bytes",
+        deserialize_with = "deserialize_hash32"
+    )]
+    [u8; 32],
+);
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(deny_unknown_fields)]
 struct SerializedFinalizedStorageEntryV1 {
@@ -399,6 +526,14 @@ struct SerializedBabeNextConfigConstantV1 {
     denom: u64,
 }
 
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+enum SerializedHeaderHashFunctionV1 {
+    #[serde(rename = "blake2-256")]
+    Blake2,
+    #[serde(rename = "keccak-256")]
+    Keccak256,
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 enum SerializedBabeAllowedSlotsV1 {
     #[serde(rename = "primary")]
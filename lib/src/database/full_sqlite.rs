@@ -35,10 +35,12 @@
 //! Any block that isn't an ancestor or descendant will be removed. Reverting finalization is
 //! not supported.
 //!
-//! In order to minimize disk usage, it is not possible to efficiently retrieve the storage items
-//! of blocks that are ancestors of the finalized block. When a block is finalized, the storage of
-//! its ancestors is lost, and the only way to reconstruct it is to execute all blocks starting
-//! from the genesis to the desired one.
+//! The storage of finalized blocks is kept until [`SqliteFullDatabase::prune_finalized_storage`]
+//! is called. This function removes the storage of the finalized blocks that are too far below
+//! the latest finalized block in order to minimize disk usage, while keeping their headers and
+//! bodies. Accessing the storage of such a block afterwards returns
+//! [`StorageAccessError::StoragePruned`], and the only way to reconstruct it is to execute all
+//! blocks starting from the genesis to the desired one.
 //!
 //! # About errors handling
 //!
@@ -74,11 +76,11 @@
 use crate::{
     chain::chain_information,
     executor::{self, host},
-    header, trie,
+    header, trie, util,
 };
 
 use alloc::borrow::Cow;
-use core::{fmt, iter};
+use core::{array, fmt, iter};
 use parking_lot::Mutex;
 use rusqlite::OptionalExtension as _;
 
@@ -105,6 +107,10 @@ pub struct SqliteFullDatabase {
 
     /// Number of bytes used to encode the block number.
     block_number_bytes: usize,
+
+    /// Blocks whose storage has been pinned with [`SqliteFullDatabase::pin_block_storage`], and
+    /// the number of times they have been pinned.
+    pinned_blocks: Mutex<hashbrown::HashMap<[u8; 32], usize, fnv::FnvBuildHasher>>,
 }
 
 impl SqliteFullDatabase {
@@ -151,6 +157,9 @@ impl SqliteFullDatabase {
 
     /// Returns the hash of the parent of the given block, or `None` if the block is unknown.
     ///
+    /// The parent of the genesis block is reported as a hash made of zeroes, in accordance with
+    /// its header.
+    ///
     /// > **Note**: If this method is called twice times in a row with the same block hash, it
     /// >           is possible for the first time to return `Some` and the second time to return
     /// >           `None`, in case the block has since been removed from the database.
@@ -160,11 +169,36 @@ impl SqliteFullDatabase {
         let out = connection
             .prepare_cached(r#"SELECT parent_hash FROM blocks WHERE hash = ?"#)
             .map_err(|err| CorruptedError::Internal(InternalError(err)))?
-            .query_row((&block_hash[..],), |row| row.get::<_, [u8; 32]>(0))
+            .query_row((&block_hash[..],), |row| row.get::<_, Option<[u8; 32]>>(0))
             .optional()
             .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
 
-        Ok(out)
+        // The `parent_hash` column is `NULL` for the genesis block.
+        Ok(out.map(|parent_hash| parent_hash.unwrap_or([0; 32])))
+    }
+
+    /// Returns the number of the given block, or `None` if the block is unknown.
+    ///
+    /// The number is read from a dedicated column and doesn't require decoding the header.
+    ///
+    /// > **Note**: If this method is called twice times in a row with the same block hash, it
+    /// >           is possible for the first time to return `Some` and the second time to return
+    /// >           `None`, in case the block has since been removed from the database.
+    pub fn block_number_by_hash(
+        &self,
+        block_hash: &[u8; 32],
+    ) -> Result<Option<u64>, CorruptedError> {
+        let connection = self.database.lock();
+
+        let out = connection
+            .prepare_cached(r#"SELECT number FROM blocks WHERE hash = ?"#)
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+            .query_row((&block_hash[..],), |row| row.get::<_, i64>(0))
+            .optional()
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        out.map(|number| u64::try_from(number).map_err(|_| CorruptedError::InvalidNumber))
+            .transpose()
     }
 
     /// Returns the list of extrinsics of the given block, or `None` if the block is unknown.
@@ -180,8 +214,6 @@ impl SqliteFullDatabase {
     ) -> Result<Option<impl ExactSizeIterator<Item = Vec<u8>>>, CorruptedError> {
         let connection = self.database.lock();
 
-        // TODO: doesn't detect if block is absent
-
         let result = connection
             .prepare_cached(r#"SELECT extrinsic FROM blocks_body WHERE hash = ? ORDER BY idx ASC"#)
             .map_err(|err| CorruptedError::Internal(InternalError(err)))?
@@ -190,9 +222,65 @@ impl SqliteFullDatabase {
             .collect::<Result<Vec<_>, _>>()
             .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
 
+        // A block without any extrinsic can't be distinguished from an unknown block by looking
+        // at the `blocks_body` table alone.
+        if result.is_empty() {
+            let block_known = connection
+                .prepare_cached(r#"SELECT 1 FROM blocks WHERE hash = ?"#)
+                .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+                .query_row((&block_hash[..],), |_| Ok(()))
+                .optional()
+                .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+                .is_some();
+            if !block_known {
+                return Ok(None);
+            }
+        }
+
         Ok(Some(result.into_iter()))
     }
 
+    /// Returns the SCALE encoding of the given block, or `None` if the block is unknown.
+    ///
+    /// The SCALE encoding of a block consists of its SCALE-encoded header, followed with the
+    /// SCALE-compact-encoded number of extrinsics, followed with the extrinsics in their encoded
+    /// form.
+    ///
+    /// > **Note**: If this method is called twice times in a row with the same block hash, it
+    /// >           is possible for the first time to return `Some` and the second time to return
+    /// >           `None`, in case the block has since been removed from the database.
+    pub fn block_scale_encoded(
+        &self,
+        block_hash: &[u8; 32],
+    ) -> Result<Option<Vec<u8>>, CorruptedError> {
+        let connection = self.database.lock();
+
+        let Some(mut out) = connection
+            .prepare_cached(r#"SELECT header FROM blocks WHERE hash = ?"#)
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+            .query_row((&block_hash[..],), |row| row.get::<_, Vec<u8>>(0))
+            .optional()
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+        else {
+            return Ok(None);
+        };
+
+        let extrinsics = connection
+            .prepare_cached(r#"SELECT extrinsic FROM blocks_body WHERE hash = ? ORDER BY idx ASC"#)
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+            .query_map((&block_hash[..],), |row| row.get::<_, Vec<u8>>(0))
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        out.extend_from_slice(util::encode_scale_compact_usize(extrinsics.len()).as_ref());
+        for extrinsic in extrinsics {
+            out.extend_from_slice(&extrinsic);
+        }
+
+        Ok(Some(out))
+    }
+
     /// Returns the hashes of the blocks given a block number.
     pub fn block_hash_by_number(
         &self,
@@ -204,6 +292,9 @@ impl SqliteFullDatabase {
     }
 
     /// Returns the hash of the block of the best chain given a block number.
+    ///
+    /// Returns `None` if the best chain doesn't contain any block with this number, which is the
+    /// case of all the numbers above the one of the best block.
     pub fn best_block_hash_by_number(
         &self,
         block_number: u64,
@@ -725,8 +816,64 @@ impl SqliteFullDatabase {
         Ok(())
     }
 
+    /// Pins the storage of the given block, preventing it from being removed by
+    /// [`SqliteFullDatabase::purge_finality_orphans`] until
+    /// [`SqliteFullDatabase::unpin_block_storage`] is called.
+    ///
+    /// A block can be pinned multiple times, in which case it must be unpinned the same number
+    /// of times.
+    ///
+    /// Returns an error if the block or its storage can't be found in the database.
+    ///
+    /// > **Note**: Calling [`SqliteFullDatabase::reset`] removes all blocks from the database,
+    /// >           including the pinned ones.
+    pub fn pin_block_storage(&self, block_hash: &[u8; 32]) -> Result<(), StorageAccessError> {
+        let connection = self.database.lock();
+
+        let has_storage = connection
+            .prepare_cached(r#"SELECT state_trie_root_hash IS NOT NULL FROM blocks WHERE hash = ?"#)
+            .map_err(|err| {
+                StorageAccessError::Corrupted(CorruptedError::Internal(InternalError(err)))
+            })?
+            .query_row((&block_hash[..],), |row| row.get::<_, i64>(0))
+            .optional()
+            .map_err(|err| {
+                StorageAccessError::Corrupted(CorruptedError::Internal(InternalError(err)))
+            })?
+            .ok_or(StorageAccessError::UnknownBlock)?
+            != 0;
+        if !has_storage {
+            return Err(StorageAccessError::StoragePruned);
+        }
+
+        *self.pinned_blocks.lock().entry(*block_hash).or_insert(0) += 1;
+        Ok(())
+    }
+
+    /// Undoes a call to [`SqliteFullDatabase::pin_block_storage`].
+    ///
+    /// This function doesn't perform any I/O operation, and can thus be called from any context.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the block isn't pinned.
+    ///
+    pub fn unpin_block_storage(&self, block_hash: &[u8; 32]) {
+        let mut pinned_blocks = self.pinned_blocks.lock();
+        let hashbrown::hash_map::Entry::Occupied(mut entry) = pinned_blocks.entry(*block_hash)
+        else {
+            panic!("unpinning a block that isn't pinned")
+        };
+        *entry.get_mut() -= 1;
+        if *entry.get() == 0 {
+            entry.remove();
+        }
+    }
+
     /// Removes from the database all blocks that aren't a descendant of the current finalized
     /// block.
+    ///
+    /// Blocks pinned with [`SqliteFullDatabase::pin_block_storage`] are left untouched.
     pub fn purge_finality_orphans(&self) -> Result<(), CorruptedError> {
         let mut database = self.database.lock();
 
@@ -756,7 +903,11 @@ impl SqliteFullDatabase {
             .collect::<Result<Vec<_>, _>>()
             .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
 
+        let pinned_blocks = self.pinned_blocks.lock();
         for block in blocks {
+            if <[u8; 32]>::try_from(&block[..]).is_ok_and(|b| pinned_blocks.contains_key(&b)) {
+                continue;
+            }
             purge_block(&transaction, &block)?;
         }
 
@@ -768,6 +919,55 @@ impl SqliteFullDatabase {
         Ok(())
     }
 
+    /// Removes from the database the storage of the blocks whose number is strictly inferior to
+    /// the number of the current finalized block minus `num_blocks_kept`. The headers, bodies,
+    /// and justifications of these blocks are left untouched.
+    ///
+    /// In other words, the storage of the finalized block and of its `num_blocks_kept` most
+    /// recent ancestors remain accessible.
+    ///
+    /// Accessing the storage of a block whose storage has been removed returns
+    /// [`StorageAccessError::StoragePruned`].
+    ///
+    /// Blocks pinned with [`SqliteFullDatabase::pin_block_storage`] are left untouched.
+    pub fn prune_finalized_storage(&self, num_blocks_kept: u64) -> Result<(), CorruptedError> {
+        let mut database = self.database.lock();
+
+        let transaction = database
+            .transaction()
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        let Some(threshold) = finalized_num(&transaction)?.checked_sub(num_blocks_kept) else {
+            return Ok(());
+        };
+
+        let blocks = transaction
+            .prepare_cached(
+                r#"SELECT hash FROM blocks WHERE number < ? AND state_trie_root_hash IS NOT NULL ORDER BY number ASC"#,
+            )
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+            .query_map((i64::try_from(threshold).unwrap_or(i64::MAX),), |row| {
+                row.get::<_, Vec<u8>>(0)
+            })
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        let pinned_blocks = self.pinned_blocks.lock();
+        for block in blocks {
+            if <[u8; 32]>::try_from(&block[..]).is_ok_and(|b| pinned_blocks.contains_key(&b)) {
+                continue;
+            }
+            purge_block_storage(&transaction, &block)?;
+        }
+
+        transaction
+            .commit()
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        Ok(())
+    }
+
     /// Returns the value associated with a node of the trie of the given block.
     ///
     /// `parent_tries_paths_nibbles` is a list of keys to follow in order to find the root of the
@@ -910,7 +1110,7 @@ impl SqliteFullDatabase {
         }
 
         if incomplete_storage {
-            return Err(StorageAccessError::IncompleteStorage);
+            return Err(incomplete_storage_error(&connection, block_hash));
         }
 
         let Some(value) = value else { return Ok(None) };
@@ -1210,7 +1410,7 @@ impl SqliteFullDatabase {
         }
 
         if incomplete_storage {
-            return Err(StorageAccessError::IncompleteStorage);
+            return Err(incomplete_storage_error(&connection, block_hash));
         }
 
         if parent_tries_paths_nibbles_length != 0 {
@@ -1378,12 +1578,222 @@ impl SqliteFullDatabase {
         }
 
         if incomplete_storage {
-            return Err(StorageAccessError::IncompleteStorage);
+            return Err(incomplete_storage_error(&connection, block_hash));
         }
 
         Ok(merkle_value)
     }
 
+    /// Builds a Merkle proof of the given keys in the main trie of the storage of the given
+    /// block.
+    ///
+    /// `keys` must yield the keys (as bytes, not nibbles) whose storage value or absence must be
+    /// proven.
+    ///
+    /// The proof is returned in the same format as the one found in the networking protocol,
+    /// and can be decoded using [`trie::proof_decode`].
+    ///
+    /// Returns an error if the block or its storage can't be found in the database.
+    pub fn block_storage_proof(
+        &self,
+        block_hash: &[u8; 32],
+        keys: impl Iterator<Item = impl AsRef<[u8]>>,
+    ) -> Result<Vec<u8>, StorageAccessError> {
+        // Process the iterator at the very beginning and before locking the database, in order
+        // to avoid a deadlock in case the `next()` function of the iterator accesses the
+        // database as well.
+        let keys_nibbles = keys
+            .map(|key| trie::bytes_to_nibbles(key.as_ref().iter().copied()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        let connection = self.database.lock();
+
+        let state_trie_root_hash = connection
+            .prepare_cached(r#"SELECT state_trie_root_hash FROM blocks WHERE hash = ?"#)
+            .map_err(|err| {
+                StorageAccessError::Corrupted(CorruptedError::Internal(InternalError(err)))
+            })?
+            .query_row((&block_hash[..],), |row| row.get::<_, Option<Vec<u8>>>(0))
+            .optional()
+            .map_err(|err| {
+                StorageAccessError::Corrupted(CorruptedError::Internal(InternalError(err)))
+            })?
+            .ok_or(StorageAccessError::UnknownBlock)?
+            .ok_or(StorageAccessError::StoragePruned)?;
+
+        let mut node_statement = connection
+            .prepare_cached(r#"SELECT partial_key FROM trie_node WHERE hash = ?"#)
+            .map_err(|err| {
+                StorageAccessError::Corrupted(CorruptedError::Internal(InternalError(err)))
+            })?;
+        let mut children_statement = connection
+            .prepare_cached(r#"SELECT child_num, child_hash FROM trie_node_child WHERE hash = ?"#)
+            .map_err(|err| {
+                StorageAccessError::Corrupted(CorruptedError::Internal(InternalError(err)))
+            })?;
+        let mut storage_statement = connection
+            .prepare_cached(
+                r#"SELECT COALESCE(value, trie_root_ref), trie_entry_version FROM trie_node_storage WHERE node_hash = ?"#,
+            )
+            .map_err(|err| {
+                StorageAccessError::Corrupted(CorruptedError::Internal(InternalError(err)))
+            })?;
+
+        let mut proof = trie::proof_encode::ProofBuilder::new();
+
+        for key_nibbles in keys_nibbles {
+            // Walk down the trie from the root, adding to the proof every node found on the way.
+            // Each iteration consumes at least one nibble of the key, meaning that this loop
+            // can't be infinite even if the database contains a loop.
+            let mut node_merkle_value = state_trie_root_hash.clone();
+            let mut node_key = Vec::with_capacity(key_nibbles.len());
+
+            loop {
+                let partial_key = node_statement
+                    .query_row((&node_merkle_value,), |row| row.get::<_, Vec<u8>>(0))
+                    .optional()
+                    .map_err(|err| {
+                        StorageAccessError::Corrupted(CorruptedError::Internal(InternalError(err)))
+                    })?
+                    .ok_or(StorageAccessError::IncompleteStorage)?
+                    .into_iter()
+                    .map(|n| {
+                        trie::Nibble::try_from(n).map_err(|_| {
+                            StorageAccessError::Corrupted(CorruptedError::InvalidTrieNode)
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let mut children: [Option<Vec<u8>>; 16] = Default::default();
+                for child in children_statement
+                    .query_map((&node_merkle_value,), |row| {
+                        Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Vec<u8>>(1)?))
+                    })
+                    .map_err(|err| {
+                        StorageAccessError::Corrupted(CorruptedError::Internal(InternalError(err)))
+                    })?
+                {
+                    let (child_num, child_hash) = child.map_err(|err| {
+                        StorageAccessError::Corrupted(CorruptedError::Internal(InternalError(err)))
+                    })?;
+                    let Some(slot) = child_num
+                        .first()
+                        .and_then(|n| children.get_mut(usize::from(*n)))
+                    else {
+                        return Err(StorageAccessError::Corrupted(
+                            CorruptedError::InvalidTrieNode,
+                        ));
+                    };
+                    *slot = Some(child_hash);
+                }
+
+                let storage = storage_statement
+                    .query_row((&node_merkle_value,), |row| {
+                        Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, i64>(1)?))
+                    })
+                    .optional()
+                    .map_err(|err| {
+                        StorageAccessError::Corrupted(CorruptedError::Internal(InternalError(err)))
+                    })?;
+
+                // In trie version 1, storage values of 33 bytes or more are hashed in the node
+                // value and the storage value itself is added to the proof separately.
+                let storage_value_hash = match &storage {
+                    Some((value, 1)) if value.len() >= 33 => {
+                        Some(blake2_rfc::blake2b::blake2b(32, &[], value))
+                    }
+                    Some((_, 0 | 1)) | None => None,
+                    Some(_) => {
+                        return Err(StorageAccessError::Corrupted(
+                            CorruptedError::InvalidTrieEntryVersion,
+                        ))
+                    }
+                };
+
+                let node_value = trie::trie_node::encode_to_vec(trie::trie_node::Decoded {
+                    partial_key: partial_key.iter().copied(),
+                    children: array::from_fn(|n| children[n].as_deref()),
+                    storage_value: match (&storage, &storage_value_hash) {
+                        (Some(_), Some(hash)) => trie::trie_node::StorageValue::Hashed(
+                            <&[u8; 32]>::try_from(hash.as_bytes())
+                                .unwrap_or_else(|_| unreachable!()),
+                        ),
+                        (Some((value, _)), None) => trie::trie_node::StorageValue::Unhashed(value),
+                        (None, _) => trie::trie_node::StorageValue::None,
+                    },
+                })
+                .map_err(|_| StorageAccessError::Corrupted(CorruptedError::InvalidTrieNode))?;
+
+                node_key.extend_from_slice(&partial_key);
+                proof.set_node_value(
+                    &node_key,
+                    &node_value,
+                    match (&storage, &storage_value_hash) {
+                        (Some((value, _)), Some(_)) => Some(&value[..]),
+                        _ => None,
+                    },
+                );
+
+                // Find out whether the key continues into one of the children of this node.
+                if !key_nibbles.starts_with(&node_key) || key_nibbles.len() == node_key.len() {
+                    break;
+                }
+                let next_nibble = key_nibbles[node_key.len()];
+                let Some(child_merkle_value) = children[usize::from(next_nibble)].take() else {
+                    break;
+                };
+                node_key.push(next_nibble);
+                node_merkle_value = child_merkle_value;
+            }
+        }
+
+        Ok(proof.build_to_vec())
+    }
+
+    /// Returns the value associated with the given prefix and key in the off-chain storage, or
+    /// `None` if there is no such value.
+    ///
+    /// The off-chain storage isn't tied to any block and is never automatically pruned.
+    pub fn offchain_storage_get(
+        &self,
+        prefix: &[u8],
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>, CorruptedError> {
+        let connection = self.database.lock();
+
+        let value = connection
+            .prepare_cached(r#"SELECT value FROM offchain_storage WHERE prefix = ? AND key = ?"#)
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+            .query_row((prefix, key), |row| row.get::<_, Vec<u8>>(0))
+            .optional()
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        Ok(value)
+    }
+
+    /// Sets the value associated with the given prefix and key in the off-chain storage,
+    /// overwriting the existing value if any.
+    ///
+    /// See also [`SqliteFullDatabase::offchain_storage_get`].
+    pub fn offchain_storage_set(
+        &self,
+        prefix: &[u8],
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<(), CorruptedError> {
+        let connection = self.database.lock();
+
+        connection
+            .prepare_cached(
+                r#"INSERT OR REPLACE INTO offchain_storage(prefix, key, value) VALUES(?, ?, ?)"#,
+            )
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+            .execute((prefix, key, value))
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        Ok(())
+    }
+
     /// Inserts a block in the database and sets it as the finalized block.
     ///
     /// The parent of the block doesn't need to be present in the database.
@@ -1561,6 +1971,9 @@ pub enum StorageAccessError {
     IncompleteStorage,
     /// Requested block couldn't be found in the database.
     UnknownBlock,
+    /// The header of the requested block is in the database, but its storage has been removed
+    /// by [`SqliteFullDatabase::prune_finalized_storage`].
+    StoragePruned,
 }
 
 /// Error in the content of the database.
@@ -1588,6 +2001,8 @@ pub enum CorruptedError {
     BlockHeaderCorrupted(header::Error),
     /// The version information about a storage entry has failed to decode.
     InvalidTrieEntryVersion,
+    /// A trie node found in the database is malformed or inconsistent.
+    InvalidTrieNode,
     #[display(fmt = "Internal error: {_0}")]
     Internal(InternalError),
 }
@@ -1790,12 +2205,10 @@ fn purge_block(database: &rusqlite::Connection, hash: &[u8]) -> Result<(), Corru
 }
 
 fn purge_block_storage(database: &rusqlite::Connection, hash: &[u8]) -> Result<(), CorruptedError> {
-    // TODO: untested
-
     let state_trie_root_hash = database
         .prepare_cached(r#"SELECT state_trie_root_hash FROM blocks WHERE hash = ?"#)
         .map_err(|err| CorruptedError::Internal(InternalError(err)))?
-        .query_row((hash,), |row| row.get::<_, Vec<u8>>(0))
+        .query_row((hash,), |row| row.get::<_, Option<Vec<u8>>>(0))
         .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
 
     database
@@ -1811,33 +2224,87 @@ fn purge_block_storage(database: &rusqlite::Connection, hash: &[u8]) -> Result<(
         })
         .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
 
-    // TODO: doesn't delete everything in the situation where a single node with a merkle value is referenced multiple times from the same trie
-    // TODO: currently doesn't follow `trie_root_ref`
-    database
-        .prepare_cached(r#"
-            WITH RECURSIVE
-                to_delete(node_hash) AS (
-                    SELECT trie_node.hash
-                        FROM trie_node
-                        LEFT JOIN blocks ON blocks.hash != :block_hash AND blocks.state_trie_root_hash = trie_node.hash
-                        LEFT JOIN trie_node_storage ON trie_node_storage.trie_root_ref = trie_node.hash
-                        WHERE trie_node.hash = :state_trie_root_hash AND blocks.hash IS NULL AND trie_node_storage.node_hash IS NULL
-                    UNION ALL
-                    SELECT trie_node_child.child_hash
-                        FROM to_delete
-                        JOIN trie_node_child ON trie_node_child.hash = to_delete.node_hash
-                        LEFT JOIN blocks ON blocks.state_trie_root_hash = trie_node_child.child_hash
-                        LEFT JOIN trie_node_storage ON trie_node_storage.trie_root_ref = to_delete.node_hash
-                        WHERE blocks.hash IS NULL AND trie_node_storage.node_hash IS NULL
-                )
-            DELETE FROM trie_node
-            WHERE hash IN (SELECT node_hash FROM to_delete)
-        "#)
-        .map_err(|err| CorruptedError::Internal(InternalError(err)))?
-        .execute(rusqlite::named_params! {
-            ":state_trie_root_hash": &state_trie_root_hash,
-            ":block_hash": hash,
-        })
+    // Trie nodes are shared between the tries of the various blocks, and between the various
+    // places of a single trie. A node is removed only if it is no longer the root of the trie of
+    // a block, the child of another node, or the root of a child trie. Removing a node removes
+    // the references to its children (thanks to `ON DELETE CASCADE`), which then become
+    // candidates for removal.
+    let mut is_referenced_statement = database
+        .prepare_cached(
+            r#"
+            SELECT
+                EXISTS(SELECT 1 FROM blocks WHERE state_trie_root_hash = :node_hash)
+                OR EXISTS(SELECT 1 FROM trie_node_child WHERE child_hash = :node_hash)
+                OR EXISTS(SELECT 1 FROM trie_node_storage WHERE trie_root_ref = :node_hash)
+        "#,
+        )
+        .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+    let mut children_statement = database
+        .prepare_cached(
+            r#"
+            SELECT child_hash FROM trie_node_child WHERE hash = :node_hash
+            UNION ALL
+            SELECT trie_root_ref FROM trie_node_storage WHERE node_hash = :node_hash AND trie_root_ref IS NOT NULL
+        "#,
+        )
         .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+    let mut delete_statement = database
+        .prepare_cached(r#"DELETE FROM trie_node WHERE hash = ?"#)
+        .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+    let mut candidates = state_trie_root_hash.into_iter().collect::<Vec<_>>();
+    while let Some(node_hash) = candidates.pop() {
+        let is_referenced = is_referenced_statement
+            .query_row(
+                rusqlite::named_params! { ":node_hash": &node_hash },
+                |row| row.get::<_, i64>(0),
+            )
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+            != 0;
+        if is_referenced {
+            continue;
+        }
+
+        candidates.extend(
+            children_statement
+                .query_map(
+                    rusqlite::named_params! { ":node_hash": &node_hash },
+                    |row| row.get::<_, Vec<u8>>(0),
+                )
+                .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| CorruptedError::Internal(InternalError(err)))?,
+        );
+
+        delete_statement
+            .execute((&node_hash,))
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+    }
+
     Ok(())
 }
+
+/// Returns the error to report when the storage of the given block has been found to be
+/// incomplete.
+///
+/// The storage of a block whose storage has been pruned is always incomplete. In that situation,
+/// [`StorageAccessError::StoragePruned`] is returned rather than
+/// [`StorageAccessError::IncompleteStorage`].
+fn incomplete_storage_error(
+    database: &rusqlite::Connection,
+    block_hash: &[u8; 32],
+) -> StorageAccessError {
+    let is_pruned = database
+        .prepare_cached(r#"SELECT state_trie_root_hash IS NULL FROM blocks WHERE hash = ?"#)
+        .and_then(|mut statement| {
+            statement
+                .query_row((&block_hash[..],), |row| row.get::<_, i64>(0))
+                .optional()
+        });
+
+    match is_pruned {
+        Ok(Some(is_pruned)) if is_pruned != 0 => StorageAccessError::StoragePruned,
+        Ok(_) => StorageAccessError::IncompleteStorage,
+        Err(err) => StorageAccessError::Corrupted(CorruptedError::Internal(InternalError(err))),
+    }
+}
@@ -199,6 +199,27 @@ PRAGMA user_version = 1;
             )
             .map_err(InternalError)?
     }
+    if user_version <= 1 {
+        database
+            .execute_batch(
+                r#"
+/*
+Off-chain storage of the `PERSISTENT` kind, shared between the JSON-RPC functions and off-chain
+workers. Entries are indexed by a prefix and a key, and never automatically removed.
+*/
+CREATE TABLE offchain_storage(
+    prefix BLOB NOT NULL,
+    key BLOB NOT NULL,
+    value BLOB NOT NULL,
+    PRIMARY KEY (prefix, key)
+);
+
+PRAGMA user_version = 2;
+
+        "#,
+            )
+            .map_err(InternalError)?
+    }
 
     let is_empty = database
         .prepare_cached("SELECT COUNT(*) FROM meta WHERE key = ?")
@@ -211,6 +232,7 @@ PRAGMA user_version = 1;
         DatabaseOpen::Open(SqliteFullDatabase {
             database: parking_lot::Mutex::new(database),
             block_number_bytes: config.block_number_bytes, // TODO: consider storing this value in the DB and check it when opening
+            pinned_blocks: parking_lot::Mutex::new(Default::default()),
         })
     } else {
         DatabaseOpen::Empty(DatabaseEmpty {
@@ -283,6 +305,7 @@ impl DatabaseEmpty {
         let database = SqliteFullDatabase {
             database: parking_lot::Mutex::new(self.database),
             block_number_bytes: self.block_number_bytes,
+            pinned_blocks: parking_lot::Mutex::new(Default::default()),
         };
 
         database.reset(
@@ -273,6 +273,44 @@ fn empty_database_fill_then_query() {
                 trie
             );
         }
+
+        // Ask random storage proofs and verify them.
+        let state_root = trie
+            .root_user_data()
+            .map(|n| *<&[u8; 32]>::try_from(n.1.as_ref().unwrap().as_ref()).unwrap())
+            .unwrap();
+        for _ in 0..256 {
+            let keys = (0..uniform_sample(1, 3))
+                .map(|_| {
+                    (0..uniform_sample(0, 4))
+                        .map(|_| uniform_sample(0, 255))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            let proof = open_db
+                .block_storage_proof(&block0_hash, keys.iter())
+                .unwrap();
+            let decoded = trie::proof_decode::decode_and_verify_proof(trie::proof_decode::Config {
+                proof: &proof[..],
+            })
+            .unwrap();
+            for key in &keys {
+                let actual = decoded
+                    .storage_value(&state_root, key)
+                    .unwrap()
+                    .map(|(value, _)| value.to_vec());
+                let expected = trie
+                    .node_by_full_key(trie::bytes_to_nibbles(key.iter().copied()))
+                    .and_then(|n| trie[n].0.clone());
+                assert_eq!(
+                    actual,
+                    expected,
+                    "\nkey = {:?}\ntrie = {:?}",
+                    key.iter().map(|n| format!("{:x}", n)).collect::<String>(),
+                    trie
+                );
+            }
+        }
     }
 }
 
@@ -336,6 +374,232 @@ fn unknown_block() {
         ),
         Err(StorageAccessError::UnknownBlock)
     ));
+
+    assert!(matches!(
+        db.block_storage_proof(&[0xff; 32], iter::once(&[][..])),
+        Err(StorageAccessError::UnknownBlock)
+    ));
+
+    assert!(db.block_extrinsics(&[0xff; 32]).unwrap().is_none());
+    // The genesis block is known but has an empty body.
+    let genesis_hash = db.finalized_block_hash().unwrap();
+    assert_eq!(
+        db.block_extrinsics(&genesis_hash).unwrap().unwrap().len(),
+        0
+    );
+}
+
+#[test]
+fn block_number_by_hash() {
+    let DatabaseOpen::Empty(empty_db) = open(Config {
+        block_number_bytes: 4,
+        cache_size: 2 * 1024 * 1024,
+        ty: ConfigTy::Memory,
+    })
+    .unwrap() else {
+        panic!()
+    };
+
+    let genesis_header = header::HeaderRef {
+        number: 0,
+        extrinsics_root: &[0; 32],
+        parent_hash: &[0; 32],
+        state_root: &[1; 32],
+        digest: header::DigestRef::empty(),
+    }
+    .scale_encoding_vec(4);
+    let genesis_hash = header::hash_from_scale_encoded_header(&genesis_header);
+
+    let db = empty_db
+        .initialize(&genesis_header, iter::empty(), None)
+        .unwrap();
+
+    let block1_header = header::HeaderRef {
+        number: 1,
+        extrinsics_root: &[0; 32],
+        parent_hash: &genesis_hash,
+        state_root: &[1; 32],
+        digest: header::DigestRef::empty(),
+    }
+    .scale_encoding_vec(4);
+    let block1_hash = header::hash_from_scale_encoded_header(&block1_header);
+    db.insert(&block1_header, true, iter::empty::<Vec<u8>>())
+        .unwrap();
+
+    assert_eq!(db.block_number_by_hash(&genesis_hash).unwrap(), Some(0));
+    assert_eq!(db.block_number_by_hash(&block1_hash).unwrap(), Some(1));
+    assert_eq!(db.block_number_by_hash(&[0xff; 32]).unwrap(), None);
+}
+
+#[test]
+fn block_parent() {
+    let DatabaseOpen::Empty(empty_db) = open(Config {
+        block_number_bytes: 4,
+        cache_size: 2 * 1024 * 1024,
+        ty: ConfigTy::Memory,
+    })
+    .unwrap() else {
+        panic!()
+    };
+
+    let genesis_header = header::HeaderRef {
+        number: 0,
+        extrinsics_root: &[0; 32],
+        parent_hash: &[0; 32],
+        state_root: &[1; 32],
+        digest: header::DigestRef::empty(),
+    }
+    .scale_encoding_vec(4);
+    let genesis_hash = header::hash_from_scale_encoded_header(&genesis_header);
+
+    let db = empty_db
+        .initialize(&genesis_header, iter::empty(), None)
+        .unwrap();
+
+    let block1_header = header::HeaderRef {
+        number: 1,
+        extrinsics_root: &[0; 32],
+        parent_hash: &genesis_hash,
+        state_root: &[1; 32],
+        digest: header::DigestRef::empty(),
+    }
+    .scale_encoding_vec(4);
+    let block1_hash = header::hash_from_scale_encoded_header(&block1_header);
+    db.insert(&block1_header, true, iter::empty::<Vec<u8>>())
+        .unwrap();
+
+    assert_eq!(db.block_parent(&genesis_hash).unwrap(), Some([0; 32]));
+    assert_eq!(db.block_parent(&block1_hash).unwrap(), Some(genesis_hash));
+    assert_eq!(db.block_parent(&[0xff; 32]).unwrap(), None);
+}
+
+#[test]
+fn pinned_blocks_not_purged() {
+    let DatabaseOpen::Empty(empty_db) = open(Config {
+        block_number_bytes: 4,
+        cache_size: 2 * 1024 * 1024,
+        ty: ConfigTy::Memory,
+    })
+    .unwrap() else {
+        panic!()
+    };
+
+    let genesis_header = header::HeaderRef {
+        number: 0,
+        extrinsics_root: &[0; 32],
+        parent_hash: &[0; 32],
+        state_root: &[1; 32],
+        digest: header::DigestRef::empty(),
+    }
+    .scale_encoding_vec(4);
+    let genesis_hash = header::hash_from_scale_encoded_header(&genesis_header);
+
+    let db = empty_db
+        .initialize(&genesis_header, iter::empty(), None)
+        .unwrap();
+
+    // Two competing children of the genesis block, only the first of which is finalized.
+    let block1a_header = header::HeaderRef {
+        number: 1,
+        extrinsics_root: &[0; 32],
+        parent_hash: &genesis_hash,
+        state_root: &[1; 32],
+        digest: header::DigestRef::empty(),
+    }
+    .scale_encoding_vec(4);
+    let block1a_hash = header::hash_from_scale_encoded_header(&block1a_header);
+    db.insert(&block1a_header, true, iter::empty::<Vec<u8>>())
+        .unwrap();
+
+    let block1b_header = header::HeaderRef {
+        number: 1,
+        extrinsics_root: &[1; 32],
+        parent_hash: &genesis_hash,
+        state_root: &[1; 32],
+        digest: header::DigestRef::empty(),
+    }
+    .scale_encoding_vec(4);
+    let block1b_hash = header::hash_from_scale_encoded_header(&block1b_header);
+    db.insert(&block1b_header, false, iter::empty::<Vec<u8>>())
+        .unwrap();
+
+    db.set_finalized(&block1a_hash).unwrap();
+
+    assert!(matches!(
+        db.pin_block_storage(&[0xff; 32]),
+        Err(StorageAccessError::UnknownBlock)
+    ));
+
+    // Pin twice, in order to check that pins are counted.
+    db.pin_block_storage(&block1b_hash).unwrap();
+    db.pin_block_storage(&block1b_hash).unwrap();
+
+    db.purge_finality_orphans().unwrap();
+    assert_eq!(db.block_number_by_hash(&block1b_hash).unwrap(), Some(1));
+
+    db.unpin_block_storage(&block1b_hash);
+    db.purge_finality_orphans().unwrap();
+    assert_eq!(db.block_number_by_hash(&block1b_hash).unwrap(), Some(1));
+
+    db.unpin_block_storage(&block1b_hash);
+    db.purge_finality_orphans().unwrap();
+    assert_eq!(db.block_number_by_hash(&block1b_hash).unwrap(), None);
+    assert_eq!(db.block_number_by_hash(&block1a_hash).unwrap(), Some(1));
+}
+
+#[test]
+fn offchain_storage_persists_across_restarts() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("database.sqlite");
+    let config = || Config {
+        block_number_bytes: 4,
+        cache_size: 2 * 1024 * 1024,
+        ty: ConfigTy::Disk {
+            path: &path,
+            memory_map_size: 0,
+        },
+    };
+
+    {
+        let DatabaseOpen::Empty(empty_db) = open(config()).unwrap() else {
+            panic!()
+        };
+
+        let db = empty_db
+            .initialize(
+                &header::HeaderRef {
+                    number: 0,
+                    extrinsics_root: &[0; 32],
+                    parent_hash: &[0; 32],
+                    state_root: &[1; 32],
+                    digest: header::DigestRef::empty(),
+                }
+                .scale_encoding_vec(4),
+                iter::empty(),
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(db.offchain_storage_get(b"prefix", b"key").unwrap(), None);
+        db.offchain_storage_set(b"prefix", b"key", b"hello")
+            .unwrap();
+        db.offchain_storage_set(b"prefix", b"key", b"world")
+            .unwrap();
+        db.offchain_storage_set(b"other", b"key", b"foo").unwrap();
+    }
+
+    let DatabaseOpen::Open(db) = open(config()).unwrap() else {
+        panic!()
+    };
+    assert_eq!(
+        db.offchain_storage_get(b"prefix", b"key").unwrap(),
+        Some(b"world".to_vec())
+    );
+    assert_eq!(
+        db.offchain_storage_get(b"other", b"key").unwrap(),
+        Some(b"foo".to_vec())
+    );
+    assert_eq!(db.offchain_storage_get(b"prefix", b"other").unwrap(), None);
 }
 
 #[test]
@@ -1064,3 +1328,166 @@ fn storage_closest_descendant_merkle_value_partial() {
         None
     );
 }
+
+#[test]
+fn finalized_storage_pruning() {
+    let DatabaseOpen::Empty(empty_db) = open(Config {
+        block_number_bytes: 4,
+        cache_size: 2 * 1024 * 1024,
+        ty: ConfigTy::Memory,
+    })
+    .unwrap() else {
+        panic!()
+    };
+
+    let genesis_header = header::HeaderRef {
+        number: 0,
+        extrinsics_root: &[0; 32],
+        parent_hash: &[0; 32],
+        state_root: &[1; 32],
+        digest: header::DigestRef::empty(),
+    }
+    .scale_encoding_vec(4);
+    let genesis_hash = header::hash_from_scale_encoded_header(&genesis_header);
+
+    let db = empty_db
+        .initialize(&genesis_header, iter::empty(), None)
+        .unwrap();
+
+    let block1_header = header::HeaderRef {
+        number: 1,
+        extrinsics_root: &[0; 32],
+        parent_hash: &genesis_hash,
+        state_root: &[2; 32],
+        digest: header::DigestRef::empty(),
+    }
+    .scale_encoding_vec(4);
+    let block1_hash = header::hash_from_scale_encoded_header(&block1_header);
+    db.insert(&block1_header, true, iter::empty::<Vec<u8>>())
+        .unwrap();
+
+    // The tries of the two blocks have a different root node, but share the same child node.
+    let mut children_merkle_values: [Option<Cow<[u8]>>; 16] = Default::default();
+    children_merkle_values[1] = Some(Cow::Borrowed(&[3; 32]));
+    db.insert_trie_nodes(
+        [
+            InsertTrieNode {
+                merkle_value: Cow::Borrowed(&[1; 32]),
+                partial_key_nibbles: Cow::Borrowed(&[1, 1]),
+                children_merkle_values: children_merkle_values.clone(),
+                storage_value: InsertTrieNodeStorageValue::Value {
+                    value: Cow::Borrowed(b"hello"),
+                    references_merkle_value: false,
+                },
+            },
+            InsertTrieNode {
+                merkle_value: Cow::Borrowed(&[2; 32]),
+                partial_key_nibbles: Cow::Borrowed(&[1, 1]),
+                children_merkle_values,
+                storage_value: InsertTrieNodeStorageValue::Value {
+                    value: Cow::Borrowed(b"world"),
+                    references_merkle_value: false,
+                },
+            },
+            InsertTrieNode {
+                merkle_value: Cow::Borrowed(&[3; 32]),
+                partial_key_nibbles: Cow::Borrowed(&[1, 1]),
+                children_merkle_values: Default::default(),
+                storage_value: InsertTrieNodeStorageValue::Value {
+                    value: Cow::Borrowed(b"shared"),
+                    references_merkle_value: false,
+                },
+            },
+        ]
+        .into_iter(),
+        0,
+    )
+    .unwrap();
+
+    db.set_finalized(&block1_hash).unwrap();
+
+    // Pinned blocks aren't pruned.
+    db.pin_block_storage(&genesis_hash).unwrap();
+    db.prune_finalized_storage(0).unwrap();
+    assert_eq!(
+        db.block_storage_get(
+            &genesis_hash,
+            iter::empty::<iter::Empty<_>>(),
+            [1, 1].into_iter()
+        )
+        .unwrap()
+        .unwrap()
+        .0,
+        b"hello"
+    );
+    db.unpin_block_storage(&genesis_hash);
+
+    // Keeping one ancestor of the finalized block doesn't prune anything.
+    db.prune_finalized_storage(1).unwrap();
+    assert!(db
+        .block_storage_get(
+            &genesis_hash,
+            iter::empty::<iter::Empty<_>>(),
+            [1, 1].into_iter()
+        )
+        .is_ok());
+
+    db.prune_finalized_storage(0).unwrap();
+
+    // Storage accesses on the pruned block fail, but its header is still available.
+    assert!(matches!(
+        db.block_storage_get(
+            &genesis_hash,
+            iter::empty::<iter::Empty<_>>(),
+            [1, 1].into_iter()
+        ),
+        Err(StorageAccessError::StoragePruned)
+    ));
+    assert!(matches!(
+        db.block_storage_next_key(
+            &genesis_hash,
+            iter::empty::<iter::Empty<_>>(),
+            iter::empty(),
+            iter::empty(),
+            true
+        ),
+        Err(StorageAccessError::StoragePruned)
+    ));
+    assert!(matches!(
+        db.block_storage_proof(&genesis_hash, iter::once(&[0x11][..])),
+        Err(StorageAccessError::StoragePruned)
+    ));
+    assert!(matches!(
+        db.pin_block_storage(&genesis_hash),
+        Err(StorageAccessError::StoragePruned)
+    ));
+    assert_eq!(
+        db.block_scale_encoded_header(&genesis_hash).unwrap(),
+        Some(genesis_header)
+    );
+
+    // The storage of the finalized block, including the node it shared with the pruned block,
+    // is still accessible.
+    assert_eq!(
+        db.block_storage_get(
+            &block1_hash,
+            iter::empty::<iter::Empty<_>>(),
+            [1, 1].into_iter()
+        )
+        .unwrap()
+        .unwrap()
+        .0,
+        b"world"
+    );
+    assert_eq!(
+        db.block_storage_get(
+            &block1_hash,
+            iter::empty::<iter::Empty<_>>(),
+            [1, 1, 1, 1, 1].into_iter()
+        )
+        .unwrap()
+        .unwrap()
+        .0,
+        b"shared"
+    );
+}
@@ -211,7 +211,7 @@ pub use runtime_version::{
 };
 pub use trie::TrieEntryVersion;
 pub use vm::HeapPages;
-pub use zstd::Error as ModuleFormatError;
+pub use zstd::{decompress_runtime_code, Error as ModuleFormatError};
 
 mod functions;
 mod tests;
@@ -309,12 +309,11 @@ struct VmCommon {
 impl HostVmPrototype {
     /// Creates a new [`HostVmPrototype`]. Parses and potentially JITs the module.
     pub fn new(config: Config<impl AsRef<[u8]>>) -> Result<Self, NewErr> {
-        // The maximum allowed size for the decompressed Wasm code needs to be the same amongst
-        // all implementations.
-        // See <https://github.com/paritytech/substrate/blob/f9d10fabe04d598d68f8b097cc4905adbb1ad630/primitives/maybe-compressed-blob/src/lib.rs#L37>.
-        // Hopefully, this value doesn't get changed without the Substrate team informing everyone.
-        let module_bytes = zstd::zstd_decode_if_necessary(config.module.as_ref(), 50 * 1024 * 1024)
-            .map_err(NewErr::BadFormat)?;
+        let module_bytes = zstd::zstd_decode_if_necessary(
+            config.module.as_ref(),
+            zstd::MAX_DECOMPRESSED_CODE_SIZE,
+        )
+        .map_err(NewErr::BadFormat)?;
 
         // Try to find the runtime version as Wasm custom sections.
         // An error is returned if the sections have a wrong format, in which case we fail the
@@ -2027,7 +2026,7 @@ impl ReadyToRun {
                             } else {
                                 trie::HashFunction::Keccak256
                             },
-                            &elements[..],
+                            elements,
                         )),
                         Err(_) => Err(()),
                     }
@@ -25,6 +25,21 @@ mod tests;
 /// This differs from the Wasm magic bytes, so real Wasm blobs will not have this prefix.
 pub(super) const ZSTD_PREFIX: [u8; 8] = [82, 188, 83, 118, 70, 219, 142, 5];
 
+/// Maximum allowed size of a runtime code after decompression.
+///
+/// This value needs to be the same amongst all implementations.
+/// See <https://github.com/paritytech/substrate/blob/f9d10fabe04d598d68f8b097cc4905adbb1ad630/primitives/maybe-compressed-blob/src/lib.rs#L37>.
+/// Hopefully, this value doesn't get changed without the Substrate team informing everyone.
+pub(super) const MAX_DECOMPRESSED_CODE_SIZE: usize = 50 * 1024 * 1024;
+
+/// Returns the Wasm bytecode of the given runtime code, as found in the `:code` storage item.
+///
+/// If the code starts with the zstandard compression prefix, it is decompressed. Otherwise, it
+/// is returned as-is.
+pub fn decompress_runtime_code(code: &[u8]) -> Result<Cow<'_, [u8]>, Error> {
+    zstd_decode_if_necessary(code, MAX_DECOMPRESSED_CODE_SIZE)
+}
+
 /// If the given blob starts with [`ZSTD_PREFIX`], decompresses it. Otherwise, passes it through.
 ///
 /// The output data shall not be larger than `max_allowed`, to avoid potential zip bombs.
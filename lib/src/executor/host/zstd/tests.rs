@@ -55,3 +55,20 @@ fn basic_works_with_header() {
     )
     .unwrap();
 }
+
+#[test]
+fn decompress_runtime_code_compressed() {
+    let code =
+        super::decompress_runtime_code(&include_bytes!("./polkadot-runtime-v9160.wasm.zstd")[..])
+            .unwrap();
+    assert!(code.starts_with(b"\0asm"));
+}
+
+#[test]
+fn decompress_runtime_code_passthrough() {
+    let code = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+    assert!(matches!(
+        super::decompress_runtime_code(&code),
+        Ok(alloc::borrow::Cow::Borrowed(c)) if c == code
+    ));
+}
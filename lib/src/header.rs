@@ -76,7 +76,7 @@
 use crate::{trie, util};
 
 use alloc::{vec, vec::Vec};
-use core::{fmt, iter, slice};
+use core::{cmp, fmt, iter, slice};
 
 mod aura;
 mod babe;
@@ -115,6 +115,27 @@ pub fn hash_from_scale_encoded_header_vectored(
     out
 }
 
+/// Returns a hash of a SCALE-encoded header, calculated using the given hash function.
+///
+/// Must be passed a list of buffers, which, when concatenated, form the SCALE-encoded header.
+///
+/// Does not verify the validity of the header.
+pub fn hash_from_scale_encoded_header_vectored_with_function(
+    hash_function: trie::HashFunction,
+    header: impl Iterator<Item = impl AsRef<[u8]>>,
+) -> [u8; 32] {
+    match hash_function {
+        trie::HashFunction::Blake2 => hash_from_scale_encoded_header_vectored(header),
+        trie::HashFunction::Keccak256 => {
+            let mut hasher = <sha3::Keccak256 as sha3::Digest>::new();
+            for buf in header {
+                sha3::Digest::update(&mut hasher, buf.as_ref());
+            }
+            sha3::Digest::finalize(hasher).into()
+        }
+    }
+}
+
 /// Returns the value appropriate for [`Header::extrinsics_root`]. Must be passed the list of
 /// transactions in that block.
 pub fn extrinsics_root(transactions: &[impl AsRef<[u8]>]) -> [u8; 32] {
@@ -227,7 +248,7 @@ pub enum Error {
 ///
 /// Note that the information in there are not guaranteed to be exact. The exactness of the
 /// information depends on the context.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct HeaderRef<'a> {
     /// Hash of the parent block stored in the header.
     pub parent_hash: &'a [u8; 32],
@@ -305,6 +326,20 @@ impl<'a> HeaderRef<'a> {
     pub fn hash(&self, block_number_bytes: usize) -> [u8; 32] {
         hash_from_scale_encoded_header_vectored(self.scale_encoding(block_number_bytes))
     }
+
+    /// Builds the hash of the header, using the given hash function.
+    ///
+    /// Identical to [`HeaderRef::hash`] if `hash_function` is [`trie::HashFunction::Blake2`].
+    pub fn hash_with_function(
+        &self,
+        block_number_bytes: usize,
+        hash_function: trie::HashFunction,
+    ) -> [u8; 32] {
+        hash_from_scale_encoded_header_vectored_with_function(
+            hash_function,
+            self.scale_encoding(block_number_bytes),
+        )
+    }
 }
 
 impl<'a> From<&'a Header> for HeaderRef<'a> {
@@ -358,6 +393,15 @@ impl Header {
     pub fn hash(&self, block_number_bytes: usize) -> [u8; 32] {
         HeaderRef::from(self).hash(block_number_bytes)
     }
+
+    /// Equivalent to [`HeaderRef::hash_with_function`].
+    pub fn hash_with_function(
+        &self,
+        block_number_bytes: usize,
+        hash_function: trie::HashFunction,
+    ) -> [u8; 32] {
+        HeaderRef::from(self).hash_with_function(block_number_bytes, hash_function)
+    }
 }
 
 impl<'a> From<HeaderRef<'a>> for Header {
@@ -852,6 +896,14 @@ impl<'a> fmt::Debug for DigestRef<'a> {
     }
 }
 
+impl<'a> cmp::PartialEq<DigestRef<'a>> for DigestRef<'a> {
+    fn eq(&self, other: &DigestRef<'a>) -> bool {
+        self.logs().eq(other.logs())
+    }
+}
+
+impl<'a> cmp::Eq for DigestRef<'a> {}
+
 impl<'a> From<&'a Digest> for DigestRef<'a> {
     fn from(digest: &'a Digest) -> DigestRef<'a> {
         DigestRef {
@@ -436,6 +436,16 @@ impl<'a> BabePreDigestRef<'a> {
         }
     }
 
+    /// Returns the index of the authority that has authored the block, within the list of
+    /// authorities of the epoch the block belongs to.
+    pub fn authority_index(&self) -> u32 {
+        match self {
+            BabePreDigestRef::Primary(digest) => digest.authority_index,
+            BabePreDigestRef::SecondaryPlain(digest) => digest.authority_index,
+            BabePreDigestRef::SecondaryVRF(digest) => digest.authority_index,
+        }
+    }
+
     /// Returns an iterator to list of buffers which, when concatenated, produces the SCALE
     /// encoding of that object.
     pub fn scale_encoding(
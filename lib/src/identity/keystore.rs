@@ -74,7 +74,9 @@ impl KeyNamespace {
         .into_iter()
     }
 
-    fn from_string(str: &str) -> Option<Self> {
+    /// Returns the namespace corresponding to the given four-letter key type identifier, such as
+    /// `aura` or `gran`. Returns `None` if the key type is unknown.
+    pub fn from_string(str: &str) -> Option<Self> {
         match str {
             "aura" => Some(KeyNamespace::Aura),
             "audi" => Some(KeyNamespace::AuthorityDiscovery),
@@ -304,6 +306,84 @@ impl Keystore {
         Ok(public_key)
     }
 
+    /// Derives a key from the given secret URI (for example `//Alice` or a BIP39 seed phrase
+    /// followed with a derivation path) and inserts it in the keystore.
+    ///
+    /// Similar to Substrate, the key uses the Ed25519 curve if the namespace is
+    /// [`KeyNamespace::Grandpa`], and the Sr25519 curve otherwise.
+    ///
+    /// An error is returned if the public key of the derived key isn't equal to
+    /// `expected_public_key`, in which case the key isn't inserted.
+    ///
+    /// If a path was provided to [`Keystore::new`], the secret URI is saved in the file system.
+    pub async fn insert_from_secret_uri(
+        &self,
+        namespace: KeyNamespace,
+        secret_uri: &str,
+        expected_public_key: &[u8; 32],
+    ) -> Result<(), InsertFromSecretUriError> {
+        let private_key = if matches!(namespace, KeyNamespace::Grandpa) {
+            let mut private_key = seed_phrase::decode_ed25519_private_key(secret_uri)
+                .map_err(InsertFromSecretUriError::InvalidSecretUri)?;
+            let zebra_key = zeroize::Zeroizing::new(ed25519_zebra::SigningKey::from(*private_key));
+            zeroize::Zeroize::zeroize(&mut *private_key);
+            if <[u8; 32]>::from(ed25519_zebra::VerificationKey::from(&*zebra_key))
+                != *expected_public_key
+            {
+                return Err(InsertFromSecretUriError::PublicKeyMismatch);
+            }
+            PrivateKey::MemoryEd25519(zebra_key)
+        } else {
+            let mut private_key = seed_phrase::decode_sr25519_private_key(secret_uri)
+                .map_err(InsertFromSecretUriError::InvalidSecretUri)?;
+            // `from_bytes` only panics if the key is of the wrong length, which we know can't
+            // happen here.
+            let keypair: zeroize::Zeroizing<schnorrkel::Keypair> = zeroize::Zeroizing::new(
+                schnorrkel::SecretKey::from_bytes(&*private_key)
+                    .unwrap()
+                    .into(),
+            );
+            zeroize::Zeroize::zeroize(&mut *private_key);
+            if keypair.public.to_bytes() != *expected_public_key {
+                return Err(InsertFromSecretUriError::PublicKeyMismatch);
+            }
+            PrivateKey::MemorySr25519(keypair)
+        };
+
+        let mut guarded = self.guarded.lock().await;
+
+        let private_key = match private_key {
+            PrivateKey::MemoryEd25519(_) => {
+                match self.path_of_key_ed25519(namespace, expected_public_key) {
+                    Some(path) => {
+                        Self::write_to_file(path, secret_uri.as_bytes())
+                            .await
+                            .map_err(InsertFromSecretUriError::Io)?;
+                        PrivateKey::FileEd25519
+                    }
+                    None => private_key,
+                }
+            }
+            PrivateKey::MemorySr25519(_) => {
+                match self.path_of_key_sr25519(namespace, expected_public_key) {
+                    Some(path) => {
+                        Self::write_to_file(path, secret_uri.as_bytes())
+                            .await
+                            .map_err(InsertFromSecretUriError::Io)?;
+                        PrivateKey::FileSr25519
+                    }
+                    None => private_key,
+                }
+            }
+            PrivateKey::FileEd25519 | PrivateKey::FileSr25519 => unreachable!(),
+        };
+
+        guarded
+            .keys
+            .insert((namespace, *expected_public_key), private_key);
+        Ok(())
+    }
+
     /// Returns the list of all keys known to this keystore.
     ///
     /// > **Note**: Keep in mind that this function is racy, as keys can be added and removed
@@ -523,8 +603,9 @@ impl Keystore {
         path: impl AsRef<path::Path>,
         key: &ed25519_zebra::SigningKey,
     ) -> Result<(), io::Error> {
-        let mut phrase = zeroize::Zeroizing::new(vec![0; key.as_ref().len() * 2]);
-        hex::encode_to_slice(key.as_ref(), &mut phrase).unwrap();
+        let mut phrase = zeroize::Zeroizing::new(vec![0; 2 + key.as_ref().len() * 2]);
+        phrase[..2].copy_from_slice(b"0x");
+        hex::encode_to_slice(key.as_ref(), &mut phrase[2..]).unwrap();
         Self::write_to_file(path, &phrase).await
     }
 
@@ -534,8 +615,9 @@ impl Keystore {
     ) -> Result<(), io::Error> {
         // TODO: `to_bytes` isn't zeroize-friendly
         let bytes = key.to_bytes();
-        let mut phrase = zeroize::Zeroizing::new(vec![0; bytes.len() * 2]);
-        hex::encode_to_slice(bytes, &mut phrase).unwrap();
+        let mut phrase = zeroize::Zeroizing::new(vec![0; 2 + bytes.len() * 2]);
+        phrase[..2].copy_from_slice(b"0x");
+        hex::encode_to_slice(bytes, &mut phrase[2..]).unwrap();
         Self::write_to_file(path, &phrase).await
     }
 
@@ -547,7 +629,6 @@ impl Keystore {
         // TODO: proper security flags on Windows?
         #[cfg(target_family = "unix")]
         file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o400))?;
-        io::Write::write_all(&mut file, b"0x")?;
         io::Write::write_all(&mut file, key_phrase)?;
         io::Write::flush(&mut file)?; // This call is generally useless, but doesn't hurt.
         file.sync_all()?;
@@ -624,6 +705,19 @@ pub enum SignError {
     KeyLoad(KeyLoadError),
 }
 
+/// Error potentially returned by [`Keystore::insert_from_secret_uri`].
+#[derive(Debug, derive_more::Display)]
+pub enum InsertFromSecretUriError {
+    /// Failed to derive a key from the secret URI.
+    #[display(fmt = "Invalid secret URI: {_0}")]
+    InvalidSecretUri(seed_phrase::ParsePrivateKeyError),
+    /// The public key of the derived key doesn't match the expected public key.
+    PublicKeyMismatch,
+    /// Error while writing the secret URI to the file system.
+    #[display(fmt = "{_0}")]
+    Io(io::Error),
+}
+
 #[derive(Debug, derive_more::Display)]
 pub enum KeyLoadError {
     /// Error reported by the operating system.
@@ -664,7 +758,7 @@ impl From<KeyLoadError> for SignVrfError {
 
 #[cfg(test)]
 mod tests {
-    use super::{KeyNamespace, Keystore};
+    use super::{InsertFromSecretUriError, KeyNamespace, Keystore};
 
     #[test]
     fn disk_storage_works_ed25519() {
@@ -737,4 +831,62 @@ mod tests {
                 .is_ok());
         });
     }
+
+    #[test]
+    fn insert_from_secret_uri_sr25519() {
+        futures_executor::block_on(async move {
+            let keystore = Keystore::new(None, rand::random()).await.unwrap();
+
+            let alice_public_key = [
+                212, 53, 147, 199, 21, 253, 211, 28, 97, 20, 26, 189, 4, 169, 159, 214, 130, 44,
+                133, 88, 133, 76, 205, 227, 154, 86, 132, 231, 165, 109, 162, 125,
+            ];
+
+            keystore
+                .insert_from_secret_uri(KeyNamespace::Aura, "//Alice", &alice_public_key)
+                .await
+                .unwrap();
+            assert_eq!(
+                keystore.keys().await.next(),
+                Some((KeyNamespace::Aura, alice_public_key))
+            );
+
+            assert!(matches!(
+                keystore
+                    .insert_from_secret_uri(KeyNamespace::Babe, "//Bob", &alice_public_key)
+                    .await,
+                Err(InsertFromSecretUriError::PublicKeyMismatch)
+            ));
+            assert_eq!(keystore.keys().await.count(), 1);
+        });
+    }
+
+    #[test]
+    fn insert_from_secret_uri_ed25519_disk() {
+        futures_executor::block_on(async move {
+            let path = tempfile::tempdir().unwrap();
+
+            let alice_public_key = [
+                136, 220, 52, 23, 213, 5, 142, 196, 180, 80, 62, 12, 18, 234, 26, 10, 137, 190, 32,
+                15, 233, 137, 34, 66, 61, 67, 52, 1, 79, 166, 176, 238,
+            ];
+
+            let keystore1 = Keystore::new(Some(path.path().to_owned()), rand::random())
+                .await
+                .unwrap();
+            keystore1
+                .insert_from_secret_uri(KeyNamespace::Grandpa, "//Alice", &alice_public_key)
+                .await
+                .unwrap();
+            drop(keystore1);
+
+            let keystore2 = Keystore::new(Some(path.path().to_owned()), rand::random())
+                .await
+                .unwrap();
+            assert_eq!(
+                keystore2.keys().await.next(),
+                Some((KeyNamespace::Grandpa, alice_public_key))
+            );
+        });
+    }
 }
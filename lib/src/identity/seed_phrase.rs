@@ -46,7 +46,8 @@ pub fn decode_sr25519_private_key(phrase: &str) -> Result<Box<[u8; 64]>, ParsePr
 
     for junction in parsed.path {
         secret_key = match junction {
-            DeriveJunction::Soft(_) => todo!(), // TODO: return error
+            // TODO: support soft derivations
+            DeriveJunction::Soft(_) => return Err(ParsePrivateKeyError::SoftDerivation),
             DeriveJunction::Hard(cc) => secret_key
                 .hard_derive_mini_secret_key(Some(schnorrkel::derive::ChainCode(cc)), b"")
                 .0
@@ -71,7 +72,7 @@ pub fn decode_ed25519_private_key(phrase: &str) -> Result<Box<[u8; 32]>, ParsePr
     let mut secret_key = parsed.seed;
     for junction in parsed.path {
         secret_key = match junction {
-            DeriveJunction::Soft(_) => todo!(), // TODO: return error
+            DeriveJunction::Soft(_) => return Err(ParsePrivateKeyError::SoftDerivation),
             DeriveJunction::Hard(cc) => {
                 let mut hash = blake2_rfc::blake2b::Blake2b::new(32);
                 hash.update(crate::util::encode_scale_compact_usize(11).as_ref()); // Length of `"Ed25519HDKD"`
@@ -188,6 +189,8 @@ pub enum ParsePrivateKeyError {
     InvalidFormat,
     /// Failed to decode the provided BIP39 seed phrase.
     Bip39Decode(Bip39ToSeedError),
+    /// The derivation path contains a soft derivation, which isn't supported.
+    SoftDerivation,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -200,6 +200,14 @@ macro_rules! define_methods {
                 [$(stringify!($name)),*].iter().copied()
             }
 
+            /// Returns a list of alternative RPC method names that are accepted in addition to
+            /// the ones returned by [`Self::method_names`]. These aliases are deprecated and are
+            /// only accepted for compatibility with older tooling.
+            pub fn method_aliases() -> impl ExactSizeIterator<Item = &'static str> {
+                let list: &[&'static str] = &[$($($(stringify!($alias),)*)*)*];
+                list.iter().copied()
+            }
+
             /// Returns the name of the method.
             pub fn name(&self) -> &'static str {
                 match self {
@@ -347,6 +355,13 @@ macro_rules! define_methods {
         }
 
         impl$(<$l>)* $rp_name$(<$l>)* {
+            /// Returns the name of the method this is a response to.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $($rp_name::$name(_) => stringify!($name),)*
+                }
+            }
+
             /// Serializes the response into a JSON string.
             ///
             /// `id_json` must be a valid JSON-formatted request identifier, the same the user
@@ -388,7 +403,7 @@ define_methods! {
     account_nextIndex() -> (), // TODO:
     author_hasKey() -> (), // TODO:
     author_hasSessionKeys() -> (), // TODO:
-    author_insertKey() -> (), // TODO:
+    author_insertKey(#[rename = "keyType"] key_type: String, suri: String, #[rename = "publicKey"] public_key: HexString) -> (),
     author_pendingExtrinsics() -> Vec<HexString>,  // TODO: what does the returned value mean?
     author_removeExtrinsic() -> (), // TODO:
     author_rotateKeys() -> HexString,
@@ -396,7 +411,9 @@ define_methods! {
     author_submitExtrinsic(transaction: HexString) -> HashHexString,
     author_unwatchExtrinsic(subscription: Cow<'a, str>) -> bool,
     babe_epochAuthorship() -> (), // TODO:
-    chain_getBlock(hash: Option<HashHexString>) -> Block,
+    chain_getBlock(hash: Option<HashHexString>, #[rename = "decodeExtrinsics"] decode_extrinsics: Option<bool>) -> Block,
+    // `chain_getHead` is a deprecated alias equivalent to calling `chain_getBlockHash` without
+    // a height, in other words to returning the hash of the current best block.
     chain_getBlockHash(height: Option<u64>) -> HashHexString [chain_getHead],
     chain_getFinalizedHead() -> HashHexString [chain_getFinalisedHead],
     chain_getHeader(hash: Option<HashHexString>) -> Header, // TODO: return type is guessed
@@ -411,13 +428,17 @@ define_methods! {
     childstate_getStorageHash() -> (), // TODO:
     childstate_getStorageSize() -> (), // TODO:
     grandpa_roundState() -> (), // TODO:
-    offchain_localStorageGet() -> (), // TODO:
-    offchain_localStorageSet() -> (), // TODO:
+    offchain_localStorageGet(kind: Cow<'a, str>, key: HexString) -> Option<HexString>,
+    offchain_localStorageSet(kind: Cow<'a, str>, key: HexString, value: HexString) -> (),
     payment_queryInfo(extrinsic: HexString, hash: Option<HashHexString>) -> RuntimeDispatchInfo,
-    /// Returns a list of all JSON-RPC methods that are available.
+    // Returns a list of all JSON-RPC methods that are available.
     rpc_methods() -> RpcMethods,
     state_call(name: Cow<'a, str>, parameters: HexString, hash: Option<HashHexString>) -> HexString [state_callAt],
     state_getKeys(prefix: HexString, hash: Option<HashHexString>) -> Vec<HexString>,
+    // The keys are returned in increasing order and `start_key` is exclusive. Clients paginate
+    // by passing the last key of the previous page as `start_key`. A page containing fewer than
+    // `count` keys is the last one. If the number of remaining keys is exactly `count`, the
+    // next call returns an empty list.
     state_getKeysPaged(prefix: Option<HexString>, count: u32, start_key: Option<HexString>, hash: Option<HashHexString>) -> Vec<HexString> [state_getKeysPagedAt],
     state_getMetadata(hash: Option<HashHexString>) -> HexString,
     state_getPairs() -> (), // TODO:
@@ -428,27 +449,44 @@ define_methods! {
     state_getStorageSize() -> () [state_getStorageSizeAt], // TODO:
     state_queryStorage() -> (), // TODO:
     state_queryStorageAt(keys: Vec<HexString>, at: Option<HashHexString>) -> Vec<StorageChangeSet>, // TODO:
+    // `chain_subscribeRuntimeVersion` and `chain_unsubscribeRuntimeVersion` are legacy aliases
+    // used by older versions of Polkadot-JS. Notifications are sent as `state_runtimeVersion`
+    // no matter which name has been used to subscribe, like Substrate does.
     state_subscribeRuntimeVersion() -> Cow<'a, str> [chain_subscribeRuntimeVersion],
     state_subscribeStorage(list: Vec<HexString>) -> Cow<'a, str>,
     state_unsubscribeRuntimeVersion(subscription: Cow<'a, str>) -> bool [chain_unsubscribeRuntimeVersion],
     state_unsubscribeStorage(subscription: Cow<'a, str>) -> bool,
+    // Returns the chain specification of the chain, augmented with a light sync state
+    // corresponding to the current finalized block. If `raw` is `false`, the genesis storage is
+    // replaced with the hash of its trie root.
+    sync_state_genSyncSpec(raw: bool) -> Box<serde_json::value::RawValue>,
     system_accountNextIndex(account: AccountId) -> u64,
-    system_addReservedPeer() -> (), // TODO:
+    // Adds a peer, as a multiaddress ending with `/p2p/<peer id>`, to the list of reserved
+    // peers of the node.
+    system_addReservedPeer(peer: Cow<'a, str>) -> (),
     system_chain() -> Cow<'a, str>,
     system_chainType() -> Cow<'a, str>,
     system_dryRun() -> () [system_dryRunAt], // TODO:
     system_health() -> SystemHealth,
     system_localListenAddresses() -> Vec<String>,
-    /// Returns the Base58 encoding of the network identity of the node on the peer-to-peer network.
+    // Returns the Base58 encoding of the network identity of the node on the peer-to-peer network.
     system_localPeerId() -> Cow<'a, str>,
-    /// Returns, as an opaque string, the name of the client serving these JSON-RPC requests.
+    // Returns, as an opaque string, the name of the client serving these JSON-RPC requests.
     system_name() -> Cow<'a, str>,
     system_networkState() -> (), // TODO:
     system_nodeRoles() -> Cow<'a, [NodeRole]>,
+    // Returns the list of peers the node is syncing with, sorted by ascending Base58-encoded
+    // peer id.
     system_peers() -> Vec<SystemPeer>,
     system_properties() -> Box<serde_json::value::RawValue>,
-    system_removeReservedPeer() -> (), // TODO:
-    /// Returns, as an opaque string, the version of the client serving these JSON-RPC requests.
+    // Removes a peer, identified by its Base58-encoded peer id, from the list of reserved peers
+    // of the node.
+    system_removeReservedPeer(peer_id: Cow<'a, str>) -> (),
+    // Returns the list of reserved peers of the node, as multiaddresses ending with
+    // `/p2p/<peer id>`.
+    system_reservedPeers() -> Vec<String>,
+    system_syncState() -> SystemSyncState,
+    // Returns, as an opaque string, the version of the client serving these JSON-RPC requests.
     system_version() -> Cow<'a, str>,
 
     // The functions below are experimental and are defined in the document https://github.com/paritytech/json-rpc-interface-spec/
@@ -494,6 +532,9 @@ define_methods! {
     chainSpec_v1_chainName() -> Cow<'a, str>,
     chainSpec_v1_genesisHash() -> HashHexString,
     chainSpec_v1_properties() -> Box<serde_json::value::RawValue>,
+    // This function is a custom addition in smoldot, and is unstable. It makes it possible for
+    // tooling to find out which relay chain a parachain is attached to.
+    chainSpec_unstable_relayChain() -> Option<Cow<'a, str>>,
 
     sudo_unstable_p2pDiscover(multiaddr: Cow<'a, str>) -> (),
     sudo_unstable_version() -> Cow<'a, str>,
@@ -510,6 +551,62 @@ define_methods! {
     sudo_network_unstable_watch() -> Cow<'a, str>,
     sudo_network_unstable_unwatch(subscription: Cow<'a, str>) -> (),
     chainHead_unstable_finalizedDatabase(#[rename = "maxSizeBytes"] max_size_bytes: Option<u64>) -> Cow<'a, str>,
+
+    // These functions are a custom addition in smoldot. They make it possible to export the
+    // blocks of the best chain in order to import them in another node.
+    chain_subscribeExportBlocks(from: u64, to: u64) -> Cow<'a, str>,
+    chain_unsubscribeExportBlocks(subscription: String) -> bool,
+
+    // These functions are a custom addition in smoldot. They make it possible to be notified
+    // when the number of peers the node is connected to changes.
+    system_subscribePeerCount() -> Cow<'a, str>,
+    system_unsubscribePeerCount(subscription: String) -> bool,
+
+    // These functions are a custom addition in smoldot. They make it possible for bridge relayers
+    // to be notified of each Grandpa authority set change, alongside with the justification and
+    // a proof of the new authority set.
+    grandpa_subscribeAuthoritySetChanges() -> Cow<'a, str>,
+    grandpa_unsubscribeAuthoritySetChanges(subscription: String) -> bool,
+
+    // These functions are a custom addition in smoldot. They make it possible for indexers to be
+    // notified of the hashes of the extrinsics included in each newly-finalized block.
+    chain_subscribeFinalizedExtrinsics() -> Cow<'a, str>,
+    chain_unsubscribeFinalizedExtrinsics(subscription: String) -> bool,
+
+    // This function is a custom addition in smoldot. It makes it possible for block explorers to
+    // know how full a block is.
+    chain_getBlockStats(hash: Option<HashHexString>) -> BlockStats,
+
+    // This function is a custom addition in smoldot, and is unstable. It makes it possible for
+    // dashboards to obtain in a single call what would otherwise require calling `system_health`,
+    // `chain_getHeader`, and `chain_getFinalizedHead`.
+    system_unstable_status() -> SystemStatus,
+
+    // This function is a custom addition in smoldot, and is unstable. It makes it possible to
+    // diagnose why the node can't connect to peers, as peers that use different networking
+    // protocol names can't be connected to.
+    system_unstable_networkState() -> SystemNetworkState,
+
+    // This function is a custom addition in smoldot, and is unstable. It makes it possible for
+    // node operators to diagnose situations where the node is tracking multiple competing forks.
+    chain_unstable_leaves() -> Vec<ChainLeaf>,
+
+    // This function is a custom addition in smoldot, and is unstable. It returns the finalized
+    // block and the non-finalized blocks known to the node, in order to diagnose re-orgs and why
+    // a certain block has been picked as the best block. The finalized block and the best
+    // chain are always returned. The number of other blocks returned is bounded, in which case
+    // `truncated` is `true`.
+    chain_unstable_blockTree() -> BlockTree,
+
+    // This function is a custom addition in smoldot, and is unstable. It returns the runtime
+    // code of the given block, or of the best block if no hash is provided, after it has been
+    // decompressed if necessary. `null` is returned if the block doesn't have any runtime code.
+    state_unstable_getRuntimeCode(hash: Option<HashHexString>) -> Option<HexString>,
+
+    // This function is a custom addition in smoldot, and is unstable. It returns the blocks of
+    // the best chain between `from` and `to` (inclusive) whose runtime specification version
+    // differs from the one of their parent, in increasing block number order.
+    state_unstable_runtimeUpgrades(from: u64, to: u64) -> Vec<RuntimeUpgrade>,
 }
 
 define_methods! {
@@ -529,6 +626,97 @@ define_methods! {
     // This function is a custom addition in smoldot. As of the writing of this comment, there is
     // no plan to standardize it. See https://github.com/paritytech/smoldot/issues/2245.
     sudo_networkState_event(subscription: Cow<'a, str>, result: NetworkEvent) -> (),
+
+    // This function is a custom addition in smoldot. See `chain_subscribeExportBlocks`.
+    chain_exportedBlocks(subscription: Cow<'a, str>, result: ExportBlocksEvent<'a>) -> (),
+
+    // This function is a custom addition in smoldot. See `system_subscribePeerCount`.
+    system_peerCount(subscription: Cow<'a, str>, result: u64) -> (),
+
+    // This function is a custom addition in smoldot. See `grandpa_subscribeAuthoritySetChanges`.
+    grandpa_authoritySetChange(subscription: Cow<'a, str>, result: GrandpaAuthoritySetChange) -> (),
+
+    // This function is a custom addition in smoldot. See `chain_subscribeFinalizedExtrinsics`.
+    chain_finalizedExtrinsics(subscription: Cow<'a, str>, result: FinalizedExtrinsics) -> (),
+}
+
+impl<'a> MethodCall<'a> {
+    /// Returns `true` if the method is considered unsafe, in other words if it can be used to
+    /// modify the state of the node or to access sensitive information. Unsafe methods should
+    /// only be exposed to trusted JSON-RPC clients.
+    pub fn is_unsafe(&self) -> bool {
+        matches!(
+            self,
+            MethodCall::author_insertKey { .. }
+                | MethodCall::chain_unstable_blockTree { .. }
+                | MethodCall::offchain_localStorageGet { .. }
+                | MethodCall::offchain_localStorageSet { .. }
+                | MethodCall::system_addReservedPeer { .. }
+                | MethodCall::system_removeReservedPeer { .. }
+        )
+    }
+
+    /// Returns `true` if answering the method requires executing the runtime or decompressing
+    /// the runtime code, which is expensive. JSON-RPC servers might want to rate-limit calls to
+    /// these methods in order to prevent abuse.
+    pub fn is_expensive(&self) -> bool {
+        matches!(
+            self,
+            MethodCall::state_call { .. }
+                | MethodCall::state_getMetadata { .. }
+                | MethodCall::state_unstable_getRuntimeCode { .. }
+                | MethodCall::system_dryRun { .. }
+                | MethodCall::payment_queryInfo { .. }
+                | MethodCall::chainHead_v1_call { .. }
+                | MethodCall::chain_getBlock {
+                    decode_extrinsics: Some(true),
+                    ..
+                }
+        )
+    }
+
+    /// Returns the consensus engine the method is specific to, if any.
+    ///
+    /// JSON-RPC servers should answer calls to these methods with an error if the chain uses a
+    /// different consensus engine or if its consensus engine is unknown, rather than attempting
+    /// to answer them.
+    pub fn consensus_engine(&self) -> Option<ConsensusEngine> {
+        match self {
+            MethodCall::babe_epochAuthorship { .. } => Some(ConsensusEngine::Babe),
+            _ => None,
+        }
+    }
+
+    /// Returns the finality engine the method is specific to, if any.
+    ///
+    /// Similar to [`MethodCall::consensus_engine`], JSON-RPC servers should answer calls to these
+    /// methods with an error if the chain doesn't use this finality engine.
+    pub fn finality_engine(&self) -> Option<FinalityEngine> {
+        match self {
+            MethodCall::grandpa_roundState { .. }
+            | MethodCall::grandpa_subscribeAuthoritySetChanges { .. }
+            | MethodCall::grandpa_unsubscribeAuthoritySetChanges { .. } => {
+                Some(FinalityEngine::Grandpa)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Consensus engine used by a chain to author blocks. See [`MethodCall::consensus_engine`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ConsensusEngine {
+    /// Aura consensus engine.
+    Aura,
+    /// Babe consensus engine.
+    Babe,
+}
+
+/// Finality engine used by a chain to finalize blocks. See [`MethodCall::finality_engine`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FinalityEngine {
+    /// Grandpa finality engine.
+    Grandpa,
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
@@ -673,6 +861,28 @@ pub struct Block {
     /// List of justifications. Each justification is made of a consensus engine id and of the
     /// actual SCALE-encoded justification.
     pub justifications: Option<Vec<([u8; 4], Vec<u8>)>>,
+    /// Extrinsics decoded using the metadata of the runtime of the block. Only present if
+    /// explicitly requested. Each entry is `None` if the corresponding extrinsic couldn't be
+    /// decoded.
+    pub decoded_extrinsics: Option<Vec<Option<DecodedExtrinsic>>>,
+}
+
+/// Extrinsic decoded using the runtime metadata.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DecodedExtrinsic {
+    pub pallet: String,
+    pub call: String,
+    pub args: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<DecodedExtrinsicSignature>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DecodedExtrinsicSignature {
+    pub address: serde_json::Value,
+    pub signature: serde_json::Value,
+    #[serde(rename = "signedExtensions")]
+    pub signed_extensions: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -739,7 +949,10 @@ pub enum FollowEvent<'a> {
         operation_id: Cow<'a, str>,
     },
     #[serde(rename = "operationWaitingForContinue")]
-    OperationWaitingForContinue,
+    OperationWaitingForContinue {
+        #[serde(rename = "operationId")]
+        operation_id: Cow<'a, str>,
+    },
     #[serde(rename = "operationError")]
     OperationError {
         #[serde(rename = "operationId")]
@@ -848,6 +1061,174 @@ pub struct TransactionWatchEventBlock {
     pub index: u32,
 }
 
+/// Return value of `chain_getBlockStats`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BlockStats {
+    /// Size in bytes of the SCALE-encoded block, in other words its header and body.
+    #[serde(rename = "encodedSize")]
+    pub encoded_size: u64,
+    /// Number of extrinsics in the body of the block.
+    #[serde(rename = "numExtrinsics")]
+    pub num_extrinsics: u64,
+}
+
+/// Return value of `system_unstable_status`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SystemStatus {
+    /// Hash of the current best block.
+    #[serde(rename = "bestBlockHash")]
+    pub best_block_hash: HashHexString,
+    /// Height of the current best block.
+    #[serde(rename = "bestBlockNumber")]
+    pub best_block_number: u64,
+    /// Hash of the current finalized block.
+    #[serde(rename = "finalizedBlockHash")]
+    pub finalized_block_hash: HashHexString,
+    /// Height of the current finalized block.
+    #[serde(rename = "finalizedBlockNumber")]
+    pub finalized_block_number: u64,
+    /// Number of peers the node is connected to for this chain.
+    pub peers: u64,
+    /// Same as [`SystemHealth::is_syncing`].
+    #[serde(rename = "isSyncing")]
+    pub is_syncing: bool,
+    /// Height of the highest best block announced by the peers of the node, or `None` if no
+    /// peer is connected.
+    #[serde(
+        rename = "highestBlockNumber",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub highest_block_number: Option<u64>,
+}
+
+/// Return value of `system_unstable_networkState`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SystemNetworkState {
+    /// Fork id of the chain, as found in the chain specification.
+    #[serde(rename = "forkId")]
+    pub fork_id: Option<String>,
+    /// Legacy protocol id of the chain, as found in the chain specification. Not used to build
+    /// the names of the networking protocols.
+    #[serde(rename = "protocolId")]
+    pub protocol_id: Option<String>,
+    /// Names of the networking protocols used for this chain.
+    #[serde(rename = "protocolNames")]
+    pub protocol_names: Vec<String>,
+    /// Reputation of the peers that have misbehaved. Peers with a neutral reputation aren't
+    /// included.
+    #[serde(rename = "peersReputation")]
+    pub peers_reputation: Vec<PeerReputation>,
+}
+
+/// Element of [`SystemNetworkState::peers_reputation`].
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PeerReputation {
+    /// Identity of the peer, encoded in base58.
+    #[serde(rename = "peerId")]
+    pub peer_id: String,
+    /// Current reputation of the peer. Negative values indicate a misbehaving peer.
+    pub reputation: i32,
+}
+
+/// Element of the return value of `chain_unstable_leaves`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ChainLeaf {
+    /// Hash of the block.
+    pub hash: HashHexString,
+    /// Height of the block.
+    pub number: u64,
+}
+
+/// Return value of `chain_unstable_blockTree`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BlockTree {
+    /// Blocks of the tree. The first block is the finalized block, followed with the blocks of
+    /// the best chain, followed with the other blocks. Parents always come before their
+    /// children.
+    pub blocks: Vec<BlockTreeBlock>,
+    /// `true` if some blocks have been omitted because the tree is too large.
+    pub truncated: bool,
+}
+
+/// Element of [`BlockTree::blocks`].
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BlockTreeBlock {
+    /// Hash of the block.
+    pub hash: HashHexString,
+    /// Hash of the parent of the block.
+    #[serde(rename = "parentHash")]
+    pub parent_hash: HashHexString,
+    /// Height of the block.
+    pub number: u64,
+    /// `true` if the block is the current best block.
+    #[serde(rename = "isBest")]
+    pub is_best: bool,
+    /// `true` if the block is the current finalized block.
+    #[serde(rename = "isFinalized")]
+    pub is_finalized: bool,
+}
+
+/// Element of the return value of `state_unstable_runtimeUpgrades`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RuntimeUpgrade {
+    /// Height of the block whose runtime specification version differs from its parent's.
+    #[serde(rename = "blockNumber")]
+    pub block_number: u64,
+    /// Hash of the block whose runtime specification version differs from its parent's.
+    #[serde(rename = "blockHash")]
+    pub block_hash: HashHexString,
+    /// Runtime specification version of the block.
+    #[serde(rename = "specVersion")]
+    pub spec_version: u64,
+}
+
+/// Event sent on a `chain_subscribeExportBlocks` subscription.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "event")]
+pub enum ExportBlocksEvent<'a> {
+    /// Next chunk of blocks of the requested range, in increasing block number order. Each block
+    /// is SCALE-encoded.
+    #[serde(rename = "blocks")]
+    Blocks { blocks: Vec<HexString> },
+    /// All the blocks of the requested range have been sent. No other event will be generated.
+    #[serde(rename = "done")]
+    Done {},
+    /// The export has failed. No other event will be generated.
+    #[serde(rename = "error")]
+    Error { error: Cow<'a, str> },
+}
+
+/// Notification sent on a `chain_subscribeFinalizedExtrinsics` subscription.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FinalizedExtrinsics {
+    /// Hash of the newly-finalized block.
+    #[serde(rename = "blockHash")]
+    pub block_hash: HashHexString,
+    /// BLAKE2 hashes of the extrinsics of the block, in the order in which they are found in
+    /// the block body.
+    pub extrinsics: Vec<HashHexString>,
+}
+
+/// Notification sent on a `grandpa_subscribeAuthoritySetChanges` subscription.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GrandpaAuthoritySetChange {
+    /// Hash of the finalized block at which the authority set has changed.
+    #[serde(rename = "blockHash")]
+    pub block_hash: HashHexString,
+    /// SCALE-encoded header of the block.
+    pub header: HexString,
+    /// SCALE-encoded Grandpa justification that has finalized the block.
+    pub justification: HexString,
+    /// Identifier of the new authority set.
+    #[serde(rename = "setId")]
+    pub set_id: u64,
+    /// Merkle proof of the storage entries of the block that contain the Grandpa authorities
+    /// and the current set id. `None` if the storage of the block isn't available.
+    #[serde(rename = "authoritySetProof")]
+    pub authority_set_proof: Option<HexString>,
+}
+
 /// Unstable event.
 /// See <https://github.com/paritytech/smoldot/issues/2245>.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -954,6 +1335,41 @@ pub struct HeaderDigest {
 #[derive(Debug, Clone)]
 pub struct RpcMethods {
     pub methods: Vec<String>,
+    /// List of API groups the methods belong to, such as `chainHead_v1` or `state`. Sorted and
+    /// without duplicates.
+    pub groups: Vec<String>,
+}
+
+impl RpcMethods {
+    /// Builds a [`RpcMethods`] from a list of method names. The list of groups is deduced from
+    /// the method names.
+    ///
+    /// The group of a method is its prefix up to and including its version (e.g. `_v1` or
+    /// `_unstable`), or, for legacy methods that don't have a version, its first segment (e.g.
+    /// `chain` or `state`).
+    pub fn from_method_names<'a>(methods: impl Iterator<Item = &'a str>) -> Self {
+        let methods = methods.map(String::from).collect::<Vec<_>>();
+
+        let mut groups = methods
+            .iter()
+            .map(|method| {
+                let mut segments = method.split('_').collect::<Vec<_>>();
+                segments.pop();
+                match segments.iter().position(|s| {
+                    *s == "unstable"
+                        || s.strip_prefix('v')
+                            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+                }) {
+                    Some(version_index) => segments[..=version_index].join("_"),
+                    None => segments.first().copied().unwrap_or(method).to_owned(),
+                }
+            })
+            .collect::<Vec<_>>();
+        groups.sort_unstable();
+        groups.dedup();
+
+        RpcMethods { methods, groups }
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -1038,6 +1454,21 @@ pub struct SystemHealth {
     pub should_have_peers: bool,
 }
 
+/// Return value of `system_syncState`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SystemSyncState {
+    /// Height of the best block when the node was started.
+    #[serde(rename = "startingBlock")]
+    pub starting_block: u64,
+    /// Height of the current best block.
+    #[serde(rename = "currentBlock")]
+    pub current_block: u64,
+    /// Height of the highest best block announced by the peers of the node, or of the current
+    /// best block if it is higher or if no peer is connected.
+    #[serde(rename = "highestBlock")]
+    pub highest_block: u64,
+}
+
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct SystemPeer {
     #[serde(rename = "peerId")]
@@ -1115,10 +1546,12 @@ impl serde::Serialize for RpcMethods {
         #[derive(serde::Serialize)]
         struct SerdeRpcMethods<'a> {
             methods: &'a [String],
+            groups: &'a [String],
         }
 
         SerdeRpcMethods {
             methods: &self.methods,
+            groups: &self.groups,
         }
         .serialize(serializer)
     }
@@ -1139,6 +1572,8 @@ impl serde::Serialize for Block {
             extrinsics: &'a [HexString],
             header: &'a Header,
             justifications: Option<Vec<Vec<Vec<u8>>>>,
+            #[serde(rename = "decodedExtrinsics", skip_serializing_if = "Option::is_none")]
+            decoded_extrinsics: Option<&'a [Option<DecodedExtrinsic>]>,
         }
 
         SerdeBlock {
@@ -1150,6 +1585,7 @@ impl serde::Serialize for Block {
                         .map(|(e, j)| vec![e.to_vec(), j.clone()])
                         .collect()
                 }),
+                decoded_extrinsics: self.decoded_extrinsics.as_deref(),
             },
         }
         .serialize(serializer)
@@ -1261,6 +1697,50 @@ mod tests {
         assert!(matches!(call, super::MethodCall::chainSpec_v1_chainName {}));
     }
 
+    #[test]
+    fn rpc_methods_groups() {
+        let methods = super::RpcMethods::from_method_names(
+            [
+                "chainHead_v1_follow",
+                "chainHead_v1_unfollow",
+                "chainHead_unstable_finalizedDatabase",
+                "sudo_network_unstable_watch",
+                "chain_getBlockHash",
+                "state_getMetadata",
+                "rpc_methods",
+            ]
+            .into_iter(),
+        );
+
+        assert_eq!(methods.methods.len(), 7);
+        assert_eq!(
+            methods.groups,
+            [
+                "chain",
+                "chainHead_unstable",
+                "chainHead_v1",
+                "rpc",
+                "state",
+                "sudo_network_unstable"
+            ]
+        );
+    }
+
+    #[test]
+    fn system_name_response() {
+        let (_, call) = super::parse_jsonrpc_client_to_server(
+            r#"{"jsonrpc":"2.0","id":1,"method":"system_name","params":[]}"#,
+        )
+        .unwrap();
+
+        let response = super::Response::system_name("smoldot".into());
+        assert_eq!(response.name(), call.name());
+        assert_eq!(
+            response.to_json_response("1"),
+            r#"{"jsonrpc":"2.0","id":1,"result":"smoldot"}"#
+        );
+    }
+
     #[test]
     fn no_params_refused() {
         // No `params` field in the request.
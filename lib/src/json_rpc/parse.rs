@@ -17,7 +17,7 @@
 
 //! Parse JSON-RPC method calls and notifications, and build responses messages.
 
-use alloc::{borrow::Cow, string::String};
+use alloc::{borrow::Cow, string::String, vec::Vec};
 
 /// Parses a JSON-encoded RPC method call or notification.
 pub fn parse_request(request_json: &str) -> Result<Request, ParseError> {
@@ -30,8 +30,13 @@ pub fn parse_request(request_json: &str) -> Result<Request, ParseError> {
         #[serde(deny_unknown_fields)]
         #[serde(untagged)]
         enum SerdeId<'a> {
-            Num(u64),
+            // Any number is accepted, including the ones that don't fit in a `u64`. The id is
+            // later echoed back exactly as it is found in the request.
+            Num(serde_json::Number),
             Str(Cow<'a, str>),
+            // The JSON-RPC specification discourages `null` ids, but allows them. Such a request
+            // isn't a notification and must be answered with a `null` id.
+            Null,
         }
 
         if let Err(err) = serde_json::from_str::<SerdeId>(id.get()) {
@@ -61,8 +66,10 @@ pub fn parse_response(response_json: &str) -> Result<Response, ParseError> {
             #[serde(deny_unknown_fields)]
             #[serde(untagged)]
             enum SerdeId<'a> {
-                Num(u64),
+                Num(serde_json::Number),
                 Str(Cow<'a, str>),
+                // Answer to a request whose id was `null`.
+                Null,
             }
 
             if let Err(err) = serde_json::from_str::<SerdeId>(id.get()) {
@@ -93,7 +100,7 @@ pub fn parse_response(response_json: &str) -> Result<Response, ParseError> {
             #[serde(deny_unknown_fields)]
             #[serde(untagged)]
             enum SerdeId<'a> {
-                Num(u64),
+                Num(serde_json::Number),
                 Str(Cow<'a, str>),
             }
 
@@ -366,10 +373,49 @@ pub fn build_parse_error_response() -> String {
     .unwrap()
 }
 
+/// Parses a JSON-encoded batch of RPC method calls and notifications, in other words a JSON
+/// array of requests.
+///
+/// Returns the JSON of each element of the array, in order. The elements themselves aren't
+/// parsed and can be parsed with [`parse_request`].
+///
+/// Returns an error if the JSON isn't an array. Note that an empty array is considered as valid
+/// by this function, despite being an invalid request according to the JSON-RPC specification.
+pub fn parse_batch_request(request_json: &str) -> Result<Vec<&str>, ParseError> {
+    let elements: Vec<&serde_json::value::RawValue> =
+        serde_json::from_str(request_json).map_err(ParseError)?;
+    Ok(elements.into_iter().map(|e| e.get()).collect())
+}
+
+/// Builds the JSON response to a batch of requests given the responses to each individual
+/// request of the batch.
+///
+/// # Panic
+///
+/// Panics if `responses` is empty. According to the JSON-RPC specification, no response must
+/// be sent back if none of the requests of the batch expects a response.
+pub fn build_batch_response<'a>(responses: impl Iterator<Item = &'a str>) -> String {
+    let mut out = String::from("[");
+    for response in responses {
+        if out.len() > 1 {
+            out.push(',');
+        }
+        out.push_str(response);
+    }
+    assert!(out.len() > 1, "empty batch response");
+    out.push(']');
+    out
+}
+
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 struct SerdeRequest<'a> {
     jsonrpc: SerdeVersion,
-    #[serde(borrow, skip_serializing_if = "Option::is_none")]
+    #[serde(
+        borrow,
+        default,
+        deserialize_with = "deserialize_present_id",
+        skip_serializing_if = "Option::is_none"
+    )]
     id: Option<&'a serde_json::value::RawValue>,
     #[serde(borrow)]
     method: &'a str,
@@ -377,6 +423,20 @@ struct SerdeRequest<'a> {
     params: Option<&'a serde_json::value::RawValue>,
 }
 
+/// Deserializes the `id` field of a request.
+///
+/// By default, serde turns a `null` value into `None`, which would make a request with a `null`
+/// id indistinguishable from a notification. A missing field is handled through
+/// `#[serde(default)]` instead.
+fn deserialize_present_id<'a, D>(
+    deserializer: D,
+) -> Result<Option<&'a serde_json::value::RawValue>, D::Error>
+where
+    D: serde::Deserializer<'a>,
+{
+    <&'a serde_json::value::RawValue as serde::Deserialize<'a>>::deserialize(deserializer).map(Some)
+}
+
 #[derive(Debug, PartialEq, Clone, Copy, Hash, Eq)]
 enum SerdeVersion {
     V2,
@@ -591,6 +651,69 @@ mod tests {
         assert_eq!(result, "[]");
     }
 
+    #[test]
+    fn parse_request_id_null() {
+        let request =
+            super::parse_request(r#"{"jsonrpc":"2.0","id":null,"method":"foo","params":[]}"#)
+                .unwrap();
+        assert_eq!(request.id_json, Some("null"));
+    }
+
+    #[test]
+    fn parse_request_id_large_number() {
+        let request = super::parse_request(
+            r#"{"jsonrpc":"2.0","id":123456789012345678901234567890,"method":"foo"}"#,
+        )
+        .unwrap();
+        assert_eq!(request.id_json, Some("123456789012345678901234567890"));
+
+        let request =
+            super::parse_request(r#"{"jsonrpc":"2.0","id":-1.5e3,"method":"foo"}"#).unwrap();
+        assert_eq!(request.id_json, Some("-1.5e3"));
+    }
+
+    #[test]
+    fn id_round_trip() {
+        for id in [
+            r#""abc""#,
+            r#""extern:\"health-checker:0\"""#,
+            "18446744073709551615",
+            "123456789012345678901234567890",
+            "null",
+        ] {
+            let request_json = format!(r#"{{"jsonrpc":"2.0","id":{id},"method":"foo"}}"#);
+            let request = super::parse_request(&request_json).unwrap();
+            assert_eq!(request.id_json, Some(id));
+
+            let success = super::build_success_response(request.id_json.unwrap(), "5");
+            assert_eq!(
+                success,
+                format!(r#"{{"jsonrpc":"2.0","id":{id},"result":5}}"#)
+            );
+            let (id_json, result_json) = super::parse_response(&success)
+                .unwrap()
+                .into_success()
+                .unwrap();
+            assert_eq!(id_json, id);
+            assert_eq!(result_json, "5");
+
+            let error = super::build_error_response(
+                request.id_json.unwrap(),
+                super::ErrorResponse::InvalidParams,
+                None,
+            );
+            assert!(error.starts_with(&format!(r#"{{"jsonrpc":"2.0","id":{id},"error":"#)));
+            // Error responses with a `null` id indicate a parse error.
+            if id != "null" {
+                let super::Response::Error { id_json, .. } = super::parse_response(&error).unwrap()
+                else {
+                    panic!()
+                };
+                assert_eq!(id_json, id);
+            }
+        }
+    }
+
     #[test]
     fn request_missing_params() {
         let request = super::parse_request(r#"{"jsonrpc":"2.0","id":2,"method":"foo"}"#).unwrap();
@@ -677,4 +800,37 @@ mod tests {
         let response = super::build_parse_error_response();
         assert_eq!(response, "{\"jsonrpc\":\"2.0\",\"id\":null,\"error\":{\"code\":-32700,\"message\":\"Parse error\"}}");
     }
+
+    #[test]
+    fn parse_batch_request_basic_works() {
+        let elements = super::parse_batch_request(
+            r#"[{"jsonrpc":"2.0","id":1,"method":"foo"}, {"jsonrpc":"2.0","method":"bar"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            elements,
+            [
+                r#"{"jsonrpc":"2.0","id":1,"method":"foo"}"#,
+                r#"{"jsonrpc":"2.0","method":"bar"}"#
+            ]
+        );
+
+        assert!(super::parse_batch_request("[]").unwrap().is_empty());
+        assert!(super::parse_batch_request(r#"{"jsonrpc":"2.0","id":1,"method":"foo"}"#).is_err());
+    }
+
+    #[test]
+    fn build_batch_response_basic_works() {
+        let response = super::build_batch_response(
+            [
+                r#"{"jsonrpc":"2.0","id":1,"result":true}"#,
+                r#"{"jsonrpc":"2.0","id":2,"result":null}"#,
+            ]
+            .into_iter(),
+        );
+        assert_eq!(
+            response,
+            r#"[{"jsonrpc":"2.0","id":1,"result":true},{"jsonrpc":"2.0","id":2,"result":null}]"#
+        );
+    }
 }
@@ -24,6 +24,7 @@ use alloc::{
     collections::VecDeque,
     string::{String, ToString as _},
     sync::{Arc, Weak},
+    vec::Vec,
 };
 use async_lock::Mutex;
 use core::{
@@ -61,6 +62,14 @@ struct Inner {
     /// subscription start requests are automatically denied.
     max_active_subscriptions: u32,
 
+    /// List of batches of requests sent by the client whose responses haven't all been
+    /// received yet.
+    pending_batches: Slab<PendingBatch>,
+
+    /// Requests that are part of a batch and that haven't been processed yet, in the order in
+    /// which they must be processed.
+    batch_requests_queue: VecDeque<(String, BatchEntry)>,
+
     /// Structure shared with the [`SerializedRequestsIo`].
     serialized_io: Arc<SerializedIo>,
 
@@ -74,8 +83,27 @@ struct Inner {
 struct InnerSubscription {
     /// Shared with the subscription. Used to notify the subscription that it should be killed.
     kill_channel: Arc<SubscriptionKillChannel>,
-    /// Response to an unsubscribe request that must be sent out once the subscription is killed.
-    unsubscribe_response: Option<String>,
+    /// Response to an unsubscribe request that must be sent out once the subscription is killed,
+    /// and the batch the unsubscribe request belongs to.
+    unsubscribe_response: Option<(String, Option<BatchEntry>)>,
+}
+
+/// See [`Inner::pending_batches`].
+struct PendingBatch {
+    /// Response to each request of the batch, in the order of the requests. `None` if the
+    /// response hasn't been received yet, or if the request is a notification.
+    responses: Vec<Option<String>>,
+    /// Number of requests of the batch that haven't been answered yet.
+    num_remaining: usize,
+}
+
+/// Identifies a request within a batch.
+#[derive(Debug, Copy, Clone)]
+struct BatchEntry {
+    /// Index of the batch within [`Inner::pending_batches`].
+    batch_index: usize,
+    /// Position of the request within the batch.
+    position: usize,
 }
 
 struct SerializedIo {
@@ -97,6 +125,9 @@ struct SerializedIo {
     /// Beyond this, no more request should be added to [`SerializedIo::requests_queue`].
     max_requests_in_fly: NonZero<u32>,
 
+    /// See [`Config::max_request_size`].
+    max_request_size: usize,
+
     /// Queue of responses.
     responses_queue: Mutex<SerializedIoResponses>,
 
@@ -108,9 +139,10 @@ struct SerializedIo {
 struct SerializedIoResponses {
     /// Unordered list of responses and notifications to send back to the client.
     ///
-    /// Each entry contains the response/notification, and a boolean equal to `true` if this is
-    /// a request response or `false` if this is a notification.
-    pending_serialized_responses: Slab<(String, bool)>,
+    /// Each entry contains the response/notification, and the number of requests that it
+    /// answers. This number is 0 for notifications, and can be superior to 1 for the response
+    /// to a batch of requests.
+    pending_serialized_responses: Slab<(String, u32)>,
 
     /// Ordered list of responses and notifications to send back to the client, as indices within
     /// [`SerializedIoResponses::pending_serialized_responses`].
@@ -137,7 +169,7 @@ struct ResponsesNotificationsQueue {
 
 // TODO: weird enum
 enum ToMainTask {
-    RequestResponse(String),
+    RequestResponse(String, Option<BatchEntry>),
     Notification(String),
     SubscriptionDestroyed { subscription_id: String },
 }
@@ -149,11 +181,18 @@ pub struct Config {
     ///
     /// If this limit is reached, it is not possible to send further requests without pulling
     /// responses first.
+    ///
+    /// Each request of a batch of requests counts as one request. Batches that contain more
+    /// requests than this limit are refused.
     pub max_pending_requests: NonZero<u32>,
 
     /// Maximum number of simultaneous subscriptions allowed. Trying to create a subscription will
     /// be automatically rejected if this limit is reached.
     pub max_active_subscriptions: u32,
+
+    /// Maximum size, in bytes, of a JSON-RPC request. Requests that are larger than this are
+    /// answered with an error without being parsed.
+    pub max_request_size: usize,
 }
 
 /// Creates a new [`ClientMainTask`] and a [`SerializedRequestsIo`] connected to it.
@@ -175,12 +214,15 @@ pub fn client_main_task(config: Config) -> (ClientMainTask, SerializedRequestsIo
                 Default::default(),
             ),
             max_active_subscriptions: config.max_active_subscriptions,
+            pending_batches: Slab::new(),
+            batch_requests_queue: VecDeque::new(),
             serialized_io: Arc::new(SerializedIo {
                 requests_queue: crossbeam_queue::SegQueue::new(),
                 on_request_pushed: event_listener::Event::new(),
                 on_request_pulled_or_task_destroyed: event_listener::Event::new(),
                 num_requests_in_fly: AtomicU32::new(0),
                 max_requests_in_fly: config.max_pending_requests,
+                max_request_size: config.max_request_size,
                 responses_queue: Mutex::new(SerializedIoResponses {
                     pending_serialized_responses_queue: VecDeque::with_capacity(cmp::min(
                         64,
@@ -217,10 +259,16 @@ impl ClientMainTask {
         loop {
             enum WakeUpReason {
                 NewRequest(String),
+                BatchRequest(String, BatchEntry),
                 Message(ToMainTask),
             }
 
-            let wake_up_reason = {
+            // Requests that are part of a batch are processed before pulling new requests.
+            let wake_up_reason = if let Some((request, entry)) =
+                self.inner.batch_requests_queue.pop_front()
+            {
+                WakeUpReason::BatchRequest(request, entry)
+            } else {
                 let serialized_requests_io_destroyed = async {
                     (&mut self.inner.on_serialized_requests_io_destroyed).await;
                     Err(())
@@ -270,8 +318,9 @@ impl ClientMainTask {
             };
 
             // Immediately handle every event apart from `NewRequest`.
-            let new_request = match wake_up_reason {
-                WakeUpReason::NewRequest(request) => request,
+            let (new_request, batch) = match wake_up_reason {
+                WakeUpReason::NewRequest(request) => (request, None),
+                WakeUpReason::BatchRequest(request, entry) => (request, Some(entry)),
                 WakeUpReason::Message(ToMainTask::SubscriptionDestroyed { subscription_id }) => {
                     let InnerSubscription {
                         unsubscribe_response,
@@ -282,19 +331,8 @@ impl ClientMainTask {
                         .remove(&subscription_id)
                         .unwrap();
                     // TODO: post a `stop`/`error` event for chainhead subscriptions
-                    if let Some(unsubscribe_response) = unsubscribe_response {
-                        let mut responses_queue =
-                            self.inner.serialized_io.responses_queue.lock().await;
-                        let pos = responses_queue
-                            .pending_serialized_responses
-                            .insert((unsubscribe_response, true));
-                        responses_queue
-                            .pending_serialized_responses_queue
-                            .push_back(pos);
-                        self.inner
-                            .serialized_io
-                            .on_response_pushed_or_task_destroyed
-                            .notify(usize::MAX);
+                    if let Some((unsubscribe_response, batch)) = unsubscribe_response {
+                        self.queue_response(Some(unsubscribe_response), batch).await;
                     }
 
                     // Shrink the list of active subscriptions if necessary.
@@ -309,18 +347,8 @@ impl ClientMainTask {
                         subscription_id,
                     };
                 }
-                WakeUpReason::Message(ToMainTask::RequestResponse(response)) => {
-                    let mut responses_queue = self.inner.serialized_io.responses_queue.lock().await;
-                    let pos = responses_queue
-                        .pending_serialized_responses
-                        .insert((response, true));
-                    responses_queue
-                        .pending_serialized_responses_queue
-                        .push_back(pos);
-                    self.inner
-                        .serialized_io
-                        .on_response_pushed_or_task_destroyed
-                        .notify(usize::MAX);
+                WakeUpReason::Message(ToMainTask::RequestResponse(response, batch)) => {
+                    self.queue_response(Some(response), batch).await;
                     continue;
                 }
                 WakeUpReason::Message(ToMainTask::Notification(notification)) => {
@@ -328,7 +356,7 @@ impl ClientMainTask {
                     let mut responses_queue = self.inner.serialized_io.responses_queue.lock().await;
                     let pos = responses_queue
                         .pending_serialized_responses
-                        .insert((notification, false));
+                        .insert((notification, 0));
                     responses_queue
                         .pending_serialized_responses_queue
                         .push_back(pos);
@@ -340,40 +368,88 @@ impl ClientMainTask {
                 }
             };
 
+            // Requests that are too large are rejected before being parsed. Since the request
+            // couldn't be parsed, its identifier is unknown and `null` is used instead.
+            // Requests within a batch have already been checked as part of the batch.
+            if batch.is_none() && new_request.len() > self.inner.serialized_io.max_request_size {
+                drop(new_request);
+                let response = parse::build_error_response(
+                    "null",
+                    ErrorResponse::ServerError(-32007, "Request is too big"),
+                    None,
+                );
+                self.queue_response(Some(response), None).await;
+                continue;
+            }
+
+            // Batches of requests are split into individual requests, which are then processed
+            // one by one. Their responses are gathered and sent back all at once.
+            // Batches nested within batches are invalid, and are treated like any other invalid
+            // request below.
+            if batch.is_none() {
+                if let Ok(batch) = parse::parse_batch_request(&new_request) {
+                    let max_requests_in_fly = self.inner.serialized_io.max_requests_in_fly;
+                    let error = if batch.is_empty() {
+                        Some(ErrorResponse::InvalidRequest)
+                    } else if batch.len()
+                        > usize::try_from(max_requests_in_fly.get()).unwrap_or(usize::MAX)
+                    {
+                        Some(ErrorResponse::ServerError(
+                            -32000,
+                            "Too many requests in batch",
+                        ))
+                    } else {
+                        None
+                    };
+
+                    if let Some(error) = error {
+                        let response = parse::build_error_response("null", error, None);
+                        self.push_response(
+                            response,
+                            batch_num_requests(batch.len(), max_requests_in_fly),
+                        )
+                        .await;
+                        continue;
+                    }
+
+                    let batch_index = self.inner.pending_batches.insert(PendingBatch {
+                        responses: (0..batch.len()).map(|_| None).collect(),
+                        num_remaining: batch.len(),
+                    });
+                    for (position, request) in batch.into_iter().enumerate() {
+                        self.inner.batch_requests_queue.push_back((
+                            request.to_owned(),
+                            BatchEntry {
+                                batch_index,
+                                position,
+                            },
+                        ));
+                    }
+                    continue;
+                }
+            }
+
             let (request_id, parsed_request) =
                 match methods::parse_jsonrpc_client_to_server(&new_request) {
                     Ok((request_id, method)) => (request_id, method),
                     Err(methods::ParseClientToServerError::Method { request_id, error }) => {
                         let response = error.to_json_error(request_id);
-                        let mut responses_queue =
-                            self.inner.serialized_io.responses_queue.lock().await;
-                        let pos = responses_queue
-                            .pending_serialized_responses
-                            .insert((response, true));
-                        responses_queue
-                            .pending_serialized_responses_queue
-                            .push_back(pos);
-                        self.inner
-                            .serialized_io
-                            .on_response_pushed_or_task_destroyed
-                            .notify(usize::MAX);
+                        self.queue_response(Some(response), batch).await;
+                        continue;
+                    }
+                    Err(methods::ParseClientToServerError::UnknownNotification(_)) => {
+                        self.queue_response(None, batch).await;
                         continue;
                     }
-                    Err(methods::ParseClientToServerError::UnknownNotification(_)) => continue,
                     Err(methods::ParseClientToServerError::JsonRpcParse(_)) => {
-                        let response = parse::build_parse_error_response();
-                        let mut responses_queue =
-                            self.inner.serialized_io.responses_queue.lock().await;
-                        let pos = responses_queue
-                            .pending_serialized_responses
-                            .insert((response, true));
-                        responses_queue
-                            .pending_serialized_responses_queue
-                            .push_back(pos);
-                        self.inner
-                            .serialized_io
-                            .on_response_pushed_or_task_destroyed
-                            .notify(usize::MAX);
+                        // Elements of a batch that aren't valid requests are answered with an
+                        // "invalid request" error, as mandated by the JSON-RPC specification.
+                        let response = if batch.is_some() {
+                            parse::build_error_response("null", ErrorResponse::InvalidRequest, None)
+                        } else {
+                            parse::build_parse_error_response()
+                        };
+                        self.queue_response(Some(response), batch).await;
                         continue;
                     }
                 };
@@ -396,7 +472,10 @@ impl ClientMainTask {
                 | methods::MethodCall::babe_epochAuthorship { .. }
                 | methods::MethodCall::chain_getBlock { .. }
                 | methods::MethodCall::chain_getBlockHash { .. }
+                | methods::MethodCall::chain_getBlockStats { .. }
                 | methods::MethodCall::chain_getFinalizedHead { .. }
+                | methods::MethodCall::chain_unstable_leaves { .. }
+                | methods::MethodCall::chain_unstable_blockTree { .. }
                 | methods::MethodCall::chain_getHeader { .. }
                 | methods::MethodCall::childstate_getKeys { .. }
                 | methods::MethodCall::childstate_getStorage { .. }
@@ -409,6 +488,8 @@ impl ClientMainTask {
                 | methods::MethodCall::state_call { .. }
                 | methods::MethodCall::state_getKeys { .. }
                 | methods::MethodCall::state_getKeysPaged { .. }
+                | methods::MethodCall::state_unstable_getRuntimeCode { .. }
+                | methods::MethodCall::state_unstable_runtimeUpgrades { .. }
                 | methods::MethodCall::state_getMetadata { .. }
                 | methods::MethodCall::state_getPairs { .. }
                 | methods::MethodCall::state_getReadProof { .. }
@@ -418,6 +499,7 @@ impl ClientMainTask {
                 | methods::MethodCall::state_getStorageSize { .. }
                 | methods::MethodCall::state_queryStorage { .. }
                 | methods::MethodCall::state_queryStorageAt { .. }
+                | methods::MethodCall::sync_state_genSyncSpec { .. }
                 | methods::MethodCall::system_accountNextIndex { .. }
                 | methods::MethodCall::system_addReservedPeer { .. }
                 | methods::MethodCall::system_chain { .. }
@@ -432,10 +514,15 @@ impl ClientMainTask {
                 | methods::MethodCall::system_peers { .. }
                 | methods::MethodCall::system_properties { .. }
                 | methods::MethodCall::system_removeReservedPeer { .. }
+                | methods::MethodCall::system_reservedPeers { .. }
+                | methods::MethodCall::system_syncState { .. }
                 | methods::MethodCall::system_version { .. }
+                | methods::MethodCall::system_unstable_status { .. }
+                | methods::MethodCall::system_unstable_networkState { .. }
                 | methods::MethodCall::chainSpec_v1_chainName { .. }
                 | methods::MethodCall::chainSpec_v1_genesisHash { .. }
                 | methods::MethodCall::chainSpec_v1_properties { .. }
+                | methods::MethodCall::chainSpec_unstable_relayChain { .. }
                 | methods::MethodCall::rpc_methods { .. }
                 | methods::MethodCall::sudo_unstable_p2pDiscover { .. }
                 | methods::MethodCall::sudo_unstable_version { .. }
@@ -455,6 +542,7 @@ impl ClientMainTask {
                                 .responses_notifications_queue
                                 .clone(),
                             request: new_request,
+                            batch,
                             has_sent_response: false,
                         },
                         task: self,
@@ -470,7 +558,11 @@ impl ClientMainTask {
                 | methods::MethodCall::transaction_v1_broadcast { .. }
                 | methods::MethodCall::transactionWatch_v1_submitAndWatch { .. }
                 | methods::MethodCall::sudo_network_unstable_watch { .. }
-                | methods::MethodCall::chainHead_v1_follow { .. } => {
+                | methods::MethodCall::chainHead_v1_follow { .. }
+                | methods::MethodCall::chain_subscribeExportBlocks { .. }
+                | methods::MethodCall::system_subscribePeerCount { .. }
+                | methods::MethodCall::grandpa_subscribeAuthoritySetChanges { .. }
+                | methods::MethodCall::chain_subscribeFinalizedExtrinsics { .. } => {
                     // Subscription starting requests.
 
                     // We must check the maximum number of subscriptions.
@@ -483,18 +575,7 @@ impl ClientMainTask {
                             ErrorResponse::ServerError(-32000, "Too many active subscriptions"),
                             None,
                         );
-                        let mut responses_queue =
-                            self.inner.serialized_io.responses_queue.lock().await;
-                        let pos = responses_queue
-                            .pending_serialized_responses
-                            .insert((response, true));
-                        responses_queue
-                            .pending_serialized_responses_queue
-                            .push_back(pos);
-                        self.inner
-                            .serialized_io
-                            .on_response_pushed_or_task_destroyed
-                            .notify(usize::MAX);
+                        self.queue_response(Some(response), batch).await;
                         continue;
                     }
 
@@ -527,6 +608,7 @@ impl ClientMainTask {
                                 .responses_notifications_queue
                                 .clone(),
                             request: new_request,
+                            batch,
                             kill_channel,
                             subscription_id,
                             has_sent_response: false,
@@ -553,7 +635,7 @@ impl ClientMainTask {
                             kill_channel,
                             unsubscribe_response,
                         }) if unsubscribe_response.is_none() => {
-                            *unsubscribe_response = Some(
+                            *unsubscribe_response = Some((
                                 match parsed_request {
                                     methods::MethodCall::author_unwatchExtrinsic { .. } => {
                                         methods::Response::author_unwatchExtrinsic(true)
@@ -579,7 +661,8 @@ impl ClientMainTask {
                                     _ => unreachable!(),
                                 }
                                 .to_json_response(request_id),
-                            );
+                                batch,
+                            ));
 
                             kill_channel.dead.store(true, Ordering::Release);
                             kill_channel.on_dead_changed.notify(usize::MAX);
@@ -605,24 +688,22 @@ impl ClientMainTask {
                                 ),
                             };
 
-                            let mut responses_queue =
-                                self.inner.serialized_io.responses_queue.lock().await;
-                            let pos = responses_queue
-                                .pending_serialized_responses
-                                .insert((response, true));
-                            responses_queue
-                                .pending_serialized_responses_queue
-                                .push_back(pos);
-                            self.inner
-                                .serialized_io
-                                .on_response_pushed_or_task_destroyed
-                                .notify(usize::MAX);
+                            self.queue_response(Some(response), batch).await;
                         }
                     }
                 }
                 methods::MethodCall::chain_unsubscribeAllHeads { subscription, .. }
                 | methods::MethodCall::chain_unsubscribeFinalizedHeads { subscription, .. }
-                | methods::MethodCall::chain_unsubscribeNewHeads { subscription, .. } => {
+                | methods::MethodCall::chain_unsubscribeNewHeads { subscription, .. }
+                | methods::MethodCall::chain_unsubscribeExportBlocks { subscription, .. }
+                | methods::MethodCall::system_unsubscribePeerCount { subscription, .. }
+                | methods::MethodCall::grandpa_unsubscribeAuthoritySetChanges {
+                    subscription,
+                    ..
+                }
+                | methods::MethodCall::chain_unsubscribeFinalizedExtrinsics {
+                    subscription, ..
+                } => {
                     // TODO: DRY with above
                     // TODO: must check whether type of subscription matches
                     match self.inner.active_subscriptions.get_mut(&**subscription) {
@@ -630,7 +711,8 @@ impl ClientMainTask {
                             unsubscribe_response,
                             kill_channel,
                         }) if unsubscribe_response.is_none() => {
-                            *unsubscribe_response = Some(match parsed_request {
+                            *unsubscribe_response = Some((
+                                match parsed_request {
                                 methods::MethodCall::chain_unsubscribeAllHeads { .. } => {
                                     methods::Response::chain_unsubscribeAllHeads(true)
                                         .to_json_response(request_id)
@@ -643,8 +725,30 @@ impl ClientMainTask {
                                     methods::Response::chain_unsubscribeNewHeads(true)
                                         .to_json_response(request_id)
                                 }
+                                methods::MethodCall::chain_unsubscribeExportBlocks { .. } => {
+                                    methods::Response::chain_unsubscribeExportBlocks(true)
+                                        .to_json_response(request_id)
+                                }
+                                methods::MethodCall::system_unsubscribePeerCount { .. } => {
+                                    methods::Response::system_unsubscribePeerCount(true)
+                                        .to_json_response(request_id)
+                                }
+                                methods::MethodCall::grandpa_unsubscribeAuthoritySetChanges {
+                                    ..
+                                } => {
+                                    methods::Response::grandpa_unsubscribeAuthoritySetChanges(true)
+                                        .to_json_response(request_id)
+                                }
+                                methods::MethodCall::chain_unsubscribeFinalizedExtrinsics {
+                                    ..
+                                } => {
+                                    methods::Response::chain_unsubscribeFinalizedExtrinsics(true)
+                                        .to_json_response(request_id)
+                                }
                                 _ => unreachable!(),
-                            });
+                                },
+                                batch,
+                            ));
 
                             kill_channel.dead.store(true, Ordering::Release);
                             kill_channel.on_dead_changed.notify(usize::MAX);
@@ -663,21 +767,28 @@ impl ClientMainTask {
                                     methods::Response::chain_unsubscribeNewHeads(false)
                                         .to_json_response(request_id)
                                 }
+                                methods::MethodCall::chain_unsubscribeExportBlocks { .. } => {
+                                    methods::Response::chain_unsubscribeExportBlocks(false)
+                                        .to_json_response(request_id)
+                                }
+                                methods::MethodCall::system_unsubscribePeerCount { .. } => {
+                                    methods::Response::system_unsubscribePeerCount(false)
+                                        .to_json_response(request_id)
+                                }
+                                methods::MethodCall::grandpa_unsubscribeAuthoritySetChanges {
+                                    ..
+                                } => {
+                                    methods::Response::grandpa_unsubscribeAuthoritySetChanges(false)
+                                        .to_json_response(request_id)
+                                }
+                                methods::MethodCall::chain_unsubscribeFinalizedExtrinsics {
+                                    ..
+                                } => methods::Response::chain_unsubscribeFinalizedExtrinsics(false)
+                                    .to_json_response(request_id),
                                 _ => unreachable!(),
                             };
 
-                            let mut responses_queue =
-                                self.inner.serialized_io.responses_queue.lock().await;
-                            let pos = responses_queue
-                                .pending_serialized_responses
-                                .insert((response, true));
-                            responses_queue
-                                .pending_serialized_responses_queue
-                                .push_back(pos);
-                            self.inner
-                                .serialized_io
-                                .on_response_pushed_or_task_destroyed
-                                .notify(usize::MAX);
+                            self.queue_response(Some(response), batch).await;
                         }
                     }
                 }
@@ -685,6 +796,71 @@ impl ClientMainTask {
         }
     }
 
+    /// Sends the given response back to the client, or, if the request is part of a batch,
+    /// stores it until all the responses of the batch are known.
+    ///
+    /// `response` must be `None` if the request is a notification, in which case no response is
+    /// sent back.
+    async fn queue_response(&mut self, response: Option<String>, batch: Option<BatchEntry>) {
+        let response = match (response, batch) {
+            (response, Some(entry)) => {
+                let pending_batch = &mut self.inner.pending_batches[entry.batch_index];
+                debug_assert!(pending_batch.responses[entry.position].is_none());
+                pending_batch.responses[entry.position] = response;
+                pending_batch.num_remaining -= 1;
+                if pending_batch.num_remaining != 0 {
+                    return;
+                }
+
+                let pending_batch = self.inner.pending_batches.remove(entry.batch_index);
+                let num_requests = batch_num_requests(
+                    pending_batch.responses.len(),
+                    self.inner.serialized_io.max_requests_in_fly,
+                );
+                if pending_batch.responses.iter().all(Option::is_none) {
+                    // A batch that only contains notifications doesn't lead to any response.
+                    // It must nonetheless be considered as answered.
+                    let _prev_val = self
+                        .inner
+                        .serialized_io
+                        .num_requests_in_fly
+                        .fetch_sub(num_requests, Ordering::Release);
+                    debug_assert!(_prev_val >= num_requests);
+                    self.inner
+                        .serialized_io
+                        .on_request_pulled_or_task_destroyed
+                        .notify(usize::MAX);
+                    return;
+                }
+
+                let response = parse::build_batch_response(
+                    pending_batch.responses.iter().flatten().map(|r| &r[..]),
+                );
+                (response, num_requests)
+            }
+            (Some(response), None) => (response, 1),
+            (None, None) => return,
+        };
+
+        self.push_response(response.0, response.1).await;
+    }
+
+    /// Sends the given response back to the client. `num_requests` is the number of requests
+    /// that this response answers.
+    async fn push_response(&mut self, response: String, num_requests: u32) {
+        let mut responses_queue = self.inner.serialized_io.responses_queue.lock().await;
+        let pos = responses_queue
+            .pending_serialized_responses
+            .insert((response, num_requests));
+        responses_queue
+            .pending_serialized_responses_queue
+            .push_back(pos);
+        self.inner
+            .serialized_io
+            .on_response_pushed_or_task_destroyed
+            .notify(usize::MAX);
+    }
+
     fn allocate_subscription_id(&mut self) -> String {
         let subscription_id = self.inner.next_subscription_id.to_string();
         self.inner.next_subscription_id += 1;
@@ -788,13 +964,15 @@ impl SerializedRequestsIo {
                     .pending_serialized_responses_queue
                     .pop_front()
                 {
-                    let (response_or_notif, is_response) = responses_queue
+                    let (response_or_notif, num_requests) = responses_queue
                         .pending_serialized_responses
                         .remove(response_index);
 
-                    if is_response {
-                        let _prev_val = queue.num_requests_in_fly.fetch_sub(1, Ordering::Release);
-                        debug_assert_ne!(_prev_val, u32::MAX); // Check underflows.
+                    if num_requests != 0 {
+                        let _prev_val = queue
+                            .num_requests_in_fly
+                            .fetch_sub(num_requests, Ordering::Release);
+                        debug_assert!(_prev_val >= num_requests); // Check underflows.
                     }
 
                     // Shrink containers if necessary in order to reduce memory usage after a
@@ -847,15 +1025,17 @@ impl SerializedRequestsIo {
                     cause: SendRequestErrorCause::ClientMainTaskDestroyed,
                 });
             };
+            let num_requests = request_num_requests(&request, &queue);
 
             if queue
                 .num_requests_in_fly
                 .fetch_update(Ordering::SeqCst, Ordering::Relaxed, |old_value| {
-                    if old_value < queue.max_requests_in_fly.get() {
-                        // Considering that `old_value < max`, and `max` fits in a `u32` by
-                        // definition, then `old_value + 1` also always fits in a `u32`. QED.
+                    if old_value <= queue.max_requests_in_fly.get() - num_requests {
+                        // Considering that `old_value <= max - num_requests`, and `max` fits in
+                        // a `u32` by definition, then `old_value + num_requests` also always
+                        // fits in a `u32`. QED.
                         // There's no risk of overflow.
-                        Some(old_value + 1)
+                        Some(old_value + num_requests)
                     } else {
                         None
                     }
@@ -891,14 +1071,16 @@ impl SerializedRequestsIo {
         };
 
         // Try to increment `num_requests_in_fly`. Return an error if it is past the maximum.
+        let num_requests = request_num_requests(&request, &queue);
         if queue
             .num_requests_in_fly
             .fetch_update(Ordering::SeqCst, Ordering::Relaxed, |old_value| {
-                if old_value < queue.max_requests_in_fly.get() {
-                    // Considering that `old_value < max`, and `max` fits in a `u32` by
-                    // definition, then `old_value + 1` also always fits in a `u32`. QED.
+                if old_value <= queue.max_requests_in_fly.get() - num_requests {
+                    // Considering that `old_value <= max - num_requests`, and `max` fits in
+                    // a `u32` by definition, then `old_value + num_requests` also always
+                    // fits in a `u32`. QED.
                     // There's no risk of overflow.
-                    Some(old_value + 1)
+                    Some(old_value + num_requests)
                 } else {
                     None
                 }
@@ -918,6 +1100,34 @@ impl SerializedRequestsIo {
     }
 }
 
+/// Returns the number of requests that `request` counts as in
+/// [`SerializedIo::num_requests_in_fly`].
+///
+/// Requests larger than [`SerializedIo::max_request_size`] are rejected as a whole without being
+/// parsed, and thus count as one request even if they are a batch.
+fn request_num_requests(request: &str, serialized_io: &SerializedIo) -> u32 {
+    if request.len() > serialized_io.max_request_size {
+        return 1;
+    }
+
+    match parse::parse_batch_request(request) {
+        Ok(batch) => batch_num_requests(batch.len(), serialized_io.max_requests_in_fly),
+        Err(_) => 1,
+    }
+}
+
+/// Returns the number of requests that a batch containing `len` requests counts as in
+/// [`SerializedIo::num_requests_in_fly`].
+///
+/// Each request of the batch counts as one request. Empty batches, which are invalid, count as
+/// one request. Batches that contain more than `max_requests_in_fly` requests are refused, and
+/// count as `max_requests_in_fly` requests.
+fn batch_num_requests(len: usize, max_requests_in_fly: NonZero<u32>) -> u32 {
+    u32::try_from(len)
+        .unwrap_or(u32::MAX)
+        .clamp(1, max_requests_in_fly.get())
+}
+
 impl fmt::Debug for SerializedRequestsIo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_tuple("SerializedRequestsIo").finish()
@@ -984,6 +1194,8 @@ pub struct RequestProcess {
     responses_notifications_queue: Arc<ResponsesNotificationsQueue>,
     /// Request in JSON form. Guaranteed to decode successfully.
     request: String,
+    /// If the request is part of a batch, identifies the request within the batch.
+    batch: Option<BatchEntry>,
     /// `true` if a response has already been sent.
     has_sent_response: bool,
 }
@@ -1009,7 +1221,7 @@ impl RequestProcess {
         let serialized = response.to_json_response(request_id);
         self.responses_notifications_queue
             .queue
-            .push(ToMainTask::RequestResponse(serialized));
+            .push(ToMainTask::RequestResponse(serialized, self.batch));
         self.responses_notifications_queue
             .on_pushed
             .notify(usize::MAX);
@@ -1027,7 +1239,7 @@ impl RequestProcess {
         let serialized = parse::build_success_response(request_id, "null");
         self.responses_notifications_queue
             .queue
-            .push(ToMainTask::RequestResponse(serialized));
+            .push(ToMainTask::RequestResponse(serialized, self.batch));
         self.responses_notifications_queue
             .on_pushed
             .notify(usize::MAX);
@@ -1044,7 +1256,7 @@ impl RequestProcess {
         let serialized = parse::build_error_response(request_id, error, None);
         self.responses_notifications_queue
             .queue
-            .push(ToMainTask::RequestResponse(serialized));
+            .push(ToMainTask::RequestResponse(serialized, self.batch));
         self.responses_notifications_queue
             .on_pushed
             .notify(usize::MAX);
@@ -1064,7 +1276,7 @@ impl RequestProcess {
         let serialized = parse::build_error_response(request_id, error, Some(json));
         self.responses_notifications_queue
             .queue
-            .push(ToMainTask::RequestResponse(serialized));
+            .push(ToMainTask::RequestResponse(serialized, self.batch));
         self.responses_notifications_queue
             .on_pushed
             .notify(usize::MAX);
@@ -1088,7 +1300,7 @@ impl Drop for RequestProcess {
                 parse::build_error_response(request_id, ErrorResponse::InternalError, None);
             self.responses_notifications_queue
                 .queue
-                .push(ToMainTask::RequestResponse(serialized));
+                .push(ToMainTask::RequestResponse(serialized, self.batch));
             self.responses_notifications_queue
                 .on_pushed
                 .notify(usize::MAX);
@@ -1109,6 +1321,8 @@ pub struct SubscriptionStartProcess {
     kill_channel: Arc<SubscriptionKillChannel>,
     /// Request in JSON form. Guaranteed to decode successfully.
     request: String,
+    /// If the request is part of a batch, identifies the request within the batch.
+    batch: Option<BatchEntry>,
     /// Identifier of the subscription. Assigned by the client task.
     subscription_id: String,
     /// `true` if a response has already been sent.
@@ -1171,13 +1385,29 @@ impl SubscriptionStartProcess {
             methods::MethodCall::chainHead_v1_follow { .. } => {
                 methods::Response::chainHead_v1_follow(Cow::Borrowed(&self.subscription_id))
             }
+            methods::MethodCall::chain_subscribeExportBlocks { .. } => {
+                methods::Response::chain_subscribeExportBlocks(Cow::Borrowed(&self.subscription_id))
+            }
+            methods::MethodCall::system_subscribePeerCount { .. } => {
+                methods::Response::system_subscribePeerCount(Cow::Borrowed(&self.subscription_id))
+            }
+            methods::MethodCall::grandpa_subscribeAuthoritySetChanges { .. } => {
+                methods::Response::grandpa_subscribeAuthoritySetChanges(Cow::Borrowed(
+                    &self.subscription_id,
+                ))
+            }
+            methods::MethodCall::chain_subscribeFinalizedExtrinsics { .. } => {
+                methods::Response::chain_subscribeFinalizedExtrinsics(Cow::Borrowed(
+                    &self.subscription_id,
+                ))
+            }
             _ => unreachable!(),
         }
         .to_json_response(request_id);
 
         self.responses_notifications_queue
             .queue
-            .push(ToMainTask::RequestResponse(serialized_response));
+            .push(ToMainTask::RequestResponse(serialized_response, self.batch));
         self.responses_notifications_queue
             .on_pushed
             .notify(usize::MAX);
@@ -1201,7 +1431,7 @@ impl SubscriptionStartProcess {
         let serialized = parse::build_error_response(request_id, error, None);
         self.responses_notifications_queue
             .queue
-            .push(ToMainTask::RequestResponse(serialized));
+            .push(ToMainTask::RequestResponse(serialized, self.batch));
         self.responses_notifications_queue
             .queue
             .push(ToMainTask::SubscriptionDestroyed {
@@ -1230,7 +1460,7 @@ impl Drop for SubscriptionStartProcess {
                 parse::build_error_response(request_id, ErrorResponse::InternalError, None);
             self.responses_notifications_queue
                 .queue
-                .push(ToMainTask::RequestResponse(serialized));
+                .push(ToMainTask::RequestResponse(serialized, self.batch));
             self.responses_notifications_queue
                 .queue
                 .push(ToMainTask::SubscriptionDestroyed {
@@ -1374,3 +1604,166 @@ impl Drop for Subscription {
             .notify(usize::MAX);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{client_main_task, Config, Event, TrySendRequestError, TrySendRequestErrorCause};
+    use crate::json_rpc::methods;
+    use core::num::NonZero;
+
+    #[test]
+    fn subscriptions_limit_enforced() {
+        futures_executor::block_on(async {
+            let (task, io) = client_main_task(Config {
+                max_active_subscriptions: 1,
+                max_pending_requests: NonZero::<u32>::new(8).unwrap(),
+                max_request_size: usize::MAX,
+            });
+
+            io.try_send_request(
+                r#"{"jsonrpc":"2.0","id":1,"method":"chain_subscribeNewHeads","params":[]}"#
+                    .to_owned(),
+            )
+            .unwrap();
+            let (task, _subscription) = match task.run_until_event().await {
+                Event::HandleSubscriptionStart {
+                    task,
+                    subscription_start,
+                } => (task, subscription_start.accept()),
+                _ => panic!(),
+            };
+
+            io.try_send_request(
+                r#"{"jsonrpc":"2.0","id":2,"method":"chain_subscribeNewHeads","params":[]}"#
+                    .to_owned(),
+            )
+            .unwrap();
+
+            // The second subscription is rejected by the task itself, and no event is generated.
+            let responses = futures_lite::future::or(
+                async {
+                    let first = io.wait_next_response().await.unwrap();
+                    let second = io.wait_next_response().await.unwrap();
+                    (first, second)
+                },
+                async {
+                    let _ = task.run_until_event().await;
+                    panic!()
+                },
+            )
+            .await;
+
+            // The order in which the responses are sent is unspecified.
+            let (accepted, rejected) = if responses.0.contains(r#""id":1,"#) {
+                responses
+            } else {
+                (responses.1, responses.0)
+            };
+            assert!(accepted.contains(r#""id":1,"result""#));
+            assert_eq!(
+                rejected,
+                r#"{"jsonrpc":"2.0","id":2,"error":{"code":-32000,"message":"Too many active subscriptions"}}"#
+            );
+        });
+    }
+
+    #[test]
+    fn batch_requests_count_against_pending_limit() {
+        futures_executor::block_on(async {
+            let (mut task, io) = client_main_task(Config {
+                max_active_subscriptions: 0,
+                max_pending_requests: NonZero::<u32>::new(2).unwrap(),
+                max_request_size: usize::MAX,
+            });
+
+            io.try_send_request(
+                r#"[{"jsonrpc":"2.0","id":1,"method":"system_name","params":[]},{"jsonrpc":"2.0","id":2,"method":"system_name","params":[]}]"#
+                    .to_owned(),
+            )
+            .unwrap();
+
+            // Each request of the batch counts against the limit.
+            assert!(matches!(
+                io.try_send_request(
+                    r#"{"jsonrpc":"2.0","id":3,"method":"system_name","params":[]}"#.to_owned(),
+                ),
+                Err(TrySendRequestError {
+                    cause: TrySendRequestErrorCause::TooManyPendingRequests,
+                    ..
+                })
+            ));
+
+            for _ in 0..2 {
+                match task.run_until_event().await {
+                    Event::HandleRequest {
+                        task: t,
+                        request_process,
+                    } => {
+                        request_process.respond(methods::Response::system_name("foo".into()));
+                        task = t;
+                    }
+                    _ => panic!(),
+                }
+            }
+
+            futures_lite::future::or(
+                async {
+                    let response = io.wait_next_response().await.unwrap();
+                    assert_eq!(
+                        response,
+                        r#"[{"jsonrpc":"2.0","id":1,"result":"foo"},{"jsonrpc":"2.0","id":2,"result":"foo"}]"#
+                    );
+
+                    // Once the response to the batch has been pulled, new requests can be sent.
+                    io.try_send_request(
+                        r#"{"jsonrpc":"2.0","id":3,"method":"system_name","params":[]}"#
+                            .to_owned(),
+                    )
+                    .unwrap();
+                },
+                async {
+                    let _ = task.run_until_event().await;
+                    panic!()
+                },
+            )
+            .await;
+        });
+    }
+    #[test]
+    fn oversized_batch_counts_as_one_request() {
+        futures_executor::block_on(async {
+            let (task, io) = client_main_task(Config {
+                max_active_subscriptions: 0,
+                max_pending_requests: NonZero::<u32>::new(2).unwrap(),
+                max_request_size: 100,
+            });
+
+            let batch = r#"[{"jsonrpc":"2.0","id":1,"method":"system_name","params":[]},{"jsonrpc":"2.0","id":2,"method":"system_name","params":[]}]"#;
+            assert!(batch.len() > 100);
+            io.try_send_request(batch.to_owned()).unwrap();
+
+            futures_lite::future::or(
+                async {
+                    let response = io.wait_next_response().await.unwrap();
+                    assert_eq!(
+                        response,
+                        r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32007,"message":"Request is too big"}}"#
+                    );
+
+                    // The entire capacity is available again.
+                    for id in 3..5 {
+                        io.try_send_request(format!(
+                            r#"{{"jsonrpc":"2.0","id":{id},"method":"system_name","params":[]}}"#
+                        ))
+                        .unwrap();
+                    }
+                },
+                async {
+                    let _ = task.run_until_event().await;
+                    panic!()
+                },
+            )
+            .await;
+        });
+    }
+}
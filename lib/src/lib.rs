@@ -207,6 +207,7 @@ pub mod identity;
 pub mod informant;
 pub mod json_rpc;
 pub mod libp2p;
+pub mod metadata;
 pub mod network;
 pub mod sync;
 pub mod transactions;
@@ -0,0 +1,1018 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Runtime metadata.
+//!
+//! The metadata of a runtime is a data structure returned by the `Metadata_metadata` runtime
+//! function. It describes, amongst other things, the list of pallets of the runtime, the calls
+//! that each pallet exposes, and the layout of all the types that these calls use.
+//!
+//! This module only supports versions 14 and 15 of the metadata format, and only decodes the
+//! parts of the metadata that are necessary in order to decode extrinsics. See
+//! [`Metadata::decode_extrinsic`].
+//!
+//! Decoded values are represented as JSON values, following these rules:
+//!
+//! - Booleans, strings, and integers up to 64 bits are represented as their JSON equivalent.
+//! - 128 bits integers are represented as strings containing their decimal representation, as
+//!   JSON numbers can't represent them precisely. 256 bits integers are represented as strings
+//!   containing their big endian hexadecimal representation.
+//! - Sequences and arrays of bytes are represented as hexadecimal strings.
+//! - Other sequences, arrays and tuples are represented as JSON arrays.
+//! - Structs are represented as JSON objects if their fields are named, as their inner value if
+//!   they contain a single unnamed field, and as JSON arrays otherwise.
+//! - Enum variants without fields are represented as a string containing the name of the
+//!   variant. Variants with fields are represented as a JSON object with a single key equal to the
+//!   name of the variant.
+
+use alloc::{
+    borrow::ToOwned as _,
+    string::{String, ToString as _},
+    vec::Vec,
+};
+use core::fmt::Write as _;
+
+/// Maximum number of nested types that can be decoded.
+///
+/// Types can be recursive. This limit prevents a malicious or invalid metadata from triggering
+/// an infinite recursion.
+const MAX_DECODE_DEPTH: u32 = 256;
+
+/// Decoded runtime metadata.
+#[derive(Debug, Clone)]
+pub struct Metadata<'a> {
+    /// List of types of the registry, indexed by their identifier.
+    types: hashbrown::HashMap<u32, TypeDef<'a>, fnv::FnvBuildHasher>,
+    /// List of pallets of the runtime.
+    pallets: Vec<Pallet<'a>>,
+    /// Format of the extrinsics.
+    extrinsic: ExtrinsicMetadata<'a>,
+}
+
+impl<'a> Metadata<'a> {
+    /// Decodes the given SCALE-encoded metadata, as returned by the `Metadata_metadata` runtime
+    /// function after its length prefix has been removed.
+    pub fn decode(scale_encoded_metadata: &'a [u8]) -> Result<Self, DecodeError> {
+        let bytes = scale_encoded_metadata
+            .strip_prefix(b"meta")
+            .ok_or(DecodeError::MagicNumberMismatch)?;
+        let (&version, bytes) = bytes.split_first().ok_or(DecodeError::InvalidFormat)?;
+        if version != 14 && version != 15 {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+
+        // Note that the fields that follow the extrinsic metadata are ignored, as they aren't
+        // necessary in order to decode extrinsics.
+        let result: nom::IResult<_, _> = nom::combinator::complete(nom::sequence::tuple((
+            scale_vec(portable_type),
+            scale_vec(move |b| pallet(version, b)),
+            move |b| extrinsic_metadata(version, b),
+        )))(bytes);
+        let Ok((_, (types, pallets, extrinsic))) = result else {
+            return Err(DecodeError::InvalidFormat);
+        };
+
+        // In version 14, the types of the address and signature aren't directly indicated and
+        // must be found in the type parameters of the type of the extrinsic.
+        let extrinsic = match extrinsic {
+            ExtrinsicMetadataRaw::V14 {
+                extrinsic_ty,
+                version,
+                signed_extensions,
+            } => {
+                let type_param = |param_name: &str| {
+                    types
+                        .iter()
+                        .find(|(id, _, _)| *id == extrinsic_ty)
+                        .and_then(|(_, params, _)| {
+                            params.iter().find(|(name, _)| *name == param_name)
+                        })
+                        .and_then(|(_, ty)| *ty)
+                };
+
+                ExtrinsicMetadata {
+                    version,
+                    address_ty: type_param("Address"),
+                    signature_ty: type_param("Signature"),
+                    signed_extensions,
+                }
+            }
+            ExtrinsicMetadataRaw::V15 {
+                version,
+                address_ty,
+                signature_ty,
+                signed_extensions,
+            } => ExtrinsicMetadata {
+                version,
+                address_ty: Some(address_ty),
+                signature_ty: Some(signature_ty),
+                signed_extensions,
+            },
+        };
+
+        Ok(Metadata {
+            types: types.into_iter().map(|(id, _, def)| (id, def)).collect(),
+            pallets,
+            extrinsic,
+        })
+    }
+
+    /// Decodes the given SCALE-encoded extrinsic, including its length prefix, into the pallet
+    /// and call it corresponds to and the arguments of this call.
+    pub fn decode_extrinsic(
+        &self,
+        scale_encoded_extrinsic: &[u8],
+    ) -> Result<DecodedExtrinsic<'a>, DecodeExtrinsicError> {
+        let (bytes, length) = crate::util::nom_scale_compact_usize::<nom::error::Error<&[u8]>>(
+            scale_encoded_extrinsic,
+        )
+        .map_err(|_| DecodeExtrinsicError::InvalidLengthPrefix)?;
+        if length != bytes.len() {
+            return Err(DecodeExtrinsicError::InvalidLengthPrefix);
+        }
+
+        let (&version, mut bytes) = bytes
+            .split_first()
+            .ok_or(DecodeExtrinsicError::UnexpectedEnd)?;
+        if version & 0x7f != self.extrinsic.version {
+            return Err(DecodeExtrinsicError::UnsupportedVersion(version & 0x7f));
+        }
+
+        let signature = if (version & 0x80) != 0 {
+            let (Some(address_ty), Some(signature_ty)) =
+                (self.extrinsic.address_ty, self.extrinsic.signature_ty)
+            else {
+                return Err(DecodeExtrinsicError::UnknownSignatureFormat);
+            };
+
+            let (rest, address) = self.decode_value(address_ty, bytes, 0)?;
+            let (rest, signature) = self.decode_value(signature_ty, rest, 0)?;
+            bytes = rest;
+
+            let mut signed_extensions = Vec::with_capacity(self.extrinsic.signed_extensions.len());
+            for (identifier, ty) in &self.extrinsic.signed_extensions {
+                let (rest, value) = self.decode_value(*ty, bytes, 0)?;
+                signed_extensions.push((*identifier, value));
+                bytes = rest;
+            }
+
+            Some(DecodedSignature {
+                address,
+                signature,
+                signed_extensions,
+            })
+        } else {
+            None
+        };
+
+        let [pallet_index, call_index, ref bytes @ ..] = *bytes else {
+            return Err(DecodeExtrinsicError::UnexpectedEnd);
+        };
+
+        let pallet = self
+            .pallets
+            .iter()
+            .find(|p| p.index == pallet_index)
+            .ok_or(DecodeExtrinsicError::UnknownPallet(pallet_index))?;
+        let calls_ty = pallet
+            .calls_ty
+            .ok_or(DecodeExtrinsicError::UnknownPallet(pallet_index))?;
+        let Some(TypeDef::Variant(calls)) = self.types.get(&calls_ty) else {
+            return Err(DecodeExtrinsicError::UnknownType(calls_ty));
+        };
+        let call = calls.iter().find(|v| v.index == call_index).ok_or(
+            DecodeExtrinsicError::UnknownCall {
+                pallet_index,
+                call_index,
+            },
+        )?;
+
+        let (rest, arguments) = self.decode_fields(&call.fields, bytes, 0)?;
+        if !rest.is_empty() {
+            return Err(DecodeExtrinsicError::TrailingBytes);
+        }
+
+        Ok(DecodedExtrinsic {
+            signature,
+            pallet_name: pallet.name,
+            call_name: call.name,
+            arguments,
+        })
+    }
+
+    /// Decodes a value of the given type at the start of `bytes`. Returns the remaining bytes
+    /// and the decoded value.
+    fn decode_value<'b>(
+        &self,
+        ty: u32,
+        bytes: &'b [u8],
+        depth: u32,
+    ) -> Result<(&'b [u8], serde_json::Value), DecodeExtrinsicError> {
+        if depth >= MAX_DECODE_DEPTH {
+            return Err(DecodeExtrinsicError::RecursionLimitReached);
+        }
+
+        match self
+            .types
+            .get(&ty)
+            .ok_or(DecodeExtrinsicError::UnknownType(ty))?
+        {
+            TypeDef::Composite(fields) => self.decode_fields(fields, bytes, depth + 1),
+            TypeDef::Variant(variants) => {
+                let (&index, bytes) = bytes
+                    .split_first()
+                    .ok_or(DecodeExtrinsicError::UnexpectedEnd)?;
+                let variant = variants
+                    .iter()
+                    .find(|v| v.index == index)
+                    .ok_or(DecodeExtrinsicError::InvalidValue)?;
+                if variant.fields.is_empty() {
+                    return Ok((bytes, serde_json::Value::String(variant.name.to_owned())));
+                }
+                let (bytes, value) = self.decode_fields(&variant.fields, bytes, depth + 1)?;
+                let mut object = serde_json::Map::new();
+                object.insert(variant.name.to_owned(), value);
+                Ok((bytes, serde_json::Value::Object(object)))
+            }
+            TypeDef::Sequence(elem_ty) => {
+                let (bytes, len) =
+                    crate::util::nom_scale_compact_usize::<nom::error::Error<&[u8]>>(bytes)
+                        .map_err(|_| DecodeExtrinsicError::UnexpectedEnd)?;
+                self.decode_elements(*elem_ty, len, bytes, depth + 1)
+            }
+            TypeDef::Array { len, elem_ty } => {
+                let len = usize::try_from(*len).map_err(|_| DecodeExtrinsicError::UnexpectedEnd)?;
+                self.decode_elements(*elem_ty, len, bytes, depth + 1)
+            }
+            TypeDef::Tuple(elems) => {
+                if elems.is_empty() {
+                    return Ok((bytes, serde_json::Value::Null));
+                }
+                let mut bytes = bytes;
+                let mut values = Vec::with_capacity(elems.len());
+                for elem_ty in elems {
+                    let (rest, value) = self.decode_value(*elem_ty, bytes, depth + 1)?;
+                    values.push(value);
+                    bytes = rest;
+                }
+                Ok((bytes, serde_json::Value::Array(values)))
+            }
+            TypeDef::Primitive(primitive) => decode_primitive(*primitive, bytes),
+            TypeDef::Compact(inner_ty) => {
+                let primitive = self.compact_primitive(*inner_ty)?;
+                let (bytes, value) = decode_compact_u128(bytes)?;
+                let value = match primitive {
+                    Primitive::U8 | Primitive::U16 | Primitive::U32 | Primitive::U64 => {
+                        u64::try_from(value)
+                            .map(serde_json::Value::from)
+                            .map_err(|_| DecodeExtrinsicError::InvalidValue)?
+                    }
+                    Primitive::U128 => serde_json::Value::String(value.to_string()),
+                    _ => return Err(DecodeExtrinsicError::InvalidValue),
+                };
+                Ok((bytes, value))
+            }
+            TypeDef::BitSequence { store_ty } => {
+                let store_size = match self.types.get(store_ty) {
+                    Some(TypeDef::Primitive(Primitive::U8)) => 1,
+                    Some(TypeDef::Primitive(Primitive::U16)) => 2,
+                    Some(TypeDef::Primitive(Primitive::U32)) => 4,
+                    Some(TypeDef::Primitive(Primitive::U64)) => 8,
+                    _ => return Err(DecodeExtrinsicError::UnknownType(*store_ty)),
+                };
+                let (bytes, num_bits) =
+                    crate::util::nom_scale_compact_usize::<nom::error::Error<&[u8]>>(bytes)
+                        .map_err(|_| DecodeExtrinsicError::UnexpectedEnd)?;
+                let num_bytes = num_bits.div_ceil(store_size * 8) * store_size;
+                if bytes.len() < num_bytes {
+                    return Err(DecodeExtrinsicError::UnexpectedEnd);
+                }
+                let (value, bytes) = bytes.split_at(num_bytes);
+                Ok((bytes, hex_value(value)))
+            }
+        }
+    }
+
+    /// Decodes the given list of fields at the start of `bytes`.
+    fn decode_fields<'b>(
+        &self,
+        fields: &[Field<'a>],
+        bytes: &'b [u8],
+        depth: u32,
+    ) -> Result<(&'b [u8], serde_json::Value), DecodeExtrinsicError> {
+        let mut bytes = bytes;
+        let mut values = Vec::with_capacity(fields.len());
+        for field in fields {
+            let (rest, value) = self.decode_value(field.ty, bytes, depth)?;
+            values.push((field.name, value));
+            bytes = rest;
+        }
+
+        let value = if values.is_empty() {
+            serde_json::Value::Null
+        } else if values.iter().all(|(name, _)| name.is_some()) {
+            serde_json::Value::Object(
+                values
+                    .into_iter()
+                    .map(|(name, value)| (name.unwrap().to_owned(), value))
+                    .collect(),
+            )
+        } else if values.len() == 1 {
+            values.pop().unwrap().1
+        } else {
+            serde_json::Value::Array(values.into_iter().map(|(_, value)| value).collect())
+        };
+
+        Ok((bytes, value))
+    }
+
+    /// Decodes `len` elements of the given type at the start of `bytes`.
+    fn decode_elements<'b>(
+        &self,
+        elem_ty: u32,
+        len: usize,
+        bytes: &'b [u8],
+        depth: u32,
+    ) -> Result<(&'b [u8], serde_json::Value), DecodeExtrinsicError> {
+        // Every element is assumed to occupy at least one byte. This avoids allocating a huge
+        // list in case of a malicious length.
+        if bytes.len() < len {
+            return Err(DecodeExtrinsicError::UnexpectedEnd);
+        }
+
+        if let Some(TypeDef::Primitive(Primitive::U8)) = self.types.get(&elem_ty) {
+            let (value, bytes) = bytes.split_at(len);
+            return Ok((bytes, hex_value(value)));
+        }
+
+        let mut bytes = bytes;
+        let mut values = Vec::with_capacity(len);
+        for _ in 0..len {
+            let (rest, value) = self.decode_value(elem_ty, bytes, depth)?;
+            values.push(value);
+            bytes = rest;
+        }
+        Ok((bytes, serde_json::Value::Array(values)))
+    }
+
+    /// Returns the primitive type wrapped within the given type, which is the type parameter
+    /// of a `Compact`.
+    fn compact_primitive(&self, mut ty: u32) -> Result<Primitive, DecodeExtrinsicError> {
+        for _ in 0..MAX_DECODE_DEPTH {
+            match self.types.get(&ty) {
+                Some(TypeDef::Primitive(primitive)) => return Ok(*primitive),
+                Some(TypeDef::Composite(fields)) if fields.len() == 1 => ty = fields[0].ty,
+                Some(TypeDef::Tuple(elems)) if elems.len() == 1 => ty = elems[0],
+                Some(_) => return Err(DecodeExtrinsicError::InvalidValue),
+                None => return Err(DecodeExtrinsicError::UnknownType(ty)),
+            }
+        }
+
+        Err(DecodeExtrinsicError::RecursionLimitReached)
+    }
+}
+
+/// Error potentially returned by [`Metadata::decode`].
+#[derive(Debug, derive_more::Display, Clone)]
+pub enum DecodeError {
+    /// Metadata doesn't start with the expected magic number.
+    MagicNumberMismatch,
+    /// Version of the metadata format isn't supported.
+    #[display(fmt = "Unsupported metadata version: {_0}")]
+    UnsupportedVersion(u8),
+    /// Failed to decode the metadata.
+    InvalidFormat,
+}
+
+/// Extrinsic decoded by [`Metadata::decode_extrinsic`].
+#[derive(Debug, Clone)]
+pub struct DecodedExtrinsic<'a> {
+    /// Signature of the extrinsic. `None` if the extrinsic is unsigned.
+    pub signature: Option<DecodedSignature<'a>>,
+    /// Name of the pallet the call belongs to.
+    pub pallet_name: &'a str,
+    /// Name of the call within the pallet.
+    pub call_name: &'a str,
+    /// Arguments of the call. Typically a JSON object whose keys are the names of the arguments.
+    pub arguments: serde_json::Value,
+}
+
+/// Signature of an extrinsic decoded by [`Metadata::decode_extrinsic`].
+#[derive(Debug, Clone)]
+pub struct DecodedSignature<'a> {
+    /// Address of the sender of the extrinsic.
+    pub address: serde_json::Value,
+    /// Signature of the extrinsic.
+    pub signature: serde_json::Value,
+    /// Identifier and value of each signed extension, in the order indicated by the metadata.
+    pub signed_extensions: Vec<(&'a str, serde_json::Value)>,
+}
+
+/// Error potentially returned by [`Metadata::decode_extrinsic`].
+#[derive(Debug, derive_more::Display, Clone)]
+pub enum DecodeExtrinsicError {
+    /// Length prefix of the extrinsic is invalid or doesn't match its length.
+    InvalidLengthPrefix,
+    /// Version of the extrinsic doesn't match the version indicated by the metadata.
+    #[display(fmt = "Unsupported extrinsic version: {_0}")]
+    UnsupportedVersion(u8),
+    /// Extrinsic is signed, but the metadata doesn't indicate the format of the signature.
+    UnknownSignatureFormat,
+    /// Pallet of the call isn't in the metadata or doesn't have any call.
+    #[display(fmt = "Unknown pallet index: {_0}")]
+    UnknownPallet(u8),
+    /// Call isn't in the metadata.
+    #[display(fmt = "Unknown call index {call_index} in pallet {pallet_index}")]
+    UnknownCall {
+        /// Index of the pallet of the call.
+        pallet_index: u8,
+        /// Index of the call within the pallet.
+        call_index: u8,
+    },
+    /// Metadata refers to a type that it doesn't contain or that isn't appropriate.
+    #[display(fmt = "Unknown or invalid type: {_0}")]
+    UnknownType(u32),
+    /// Extrinsic is shorter than expected.
+    UnexpectedEnd,
+    /// Extrinsic contains an invalid value.
+    InvalidValue,
+    /// Extrinsic is longer than expected.
+    TrailingBytes,
+    /// Types are nested too deeply.
+    RecursionLimitReached,
+}
+
+/// Identifier, type parameters, and definition of a type of the metadata.
+type PortableType<'a> = (u32, Vec<(&'a str, Option<u32>)>, TypeDef<'a>);
+
+#[derive(Debug, Clone)]
+enum TypeDef<'a> {
+    Composite(Vec<Field<'a>>),
+    Variant(Vec<Variant<'a>>),
+    Sequence(u32),
+    Array { len: u32, elem_ty: u32 },
+    Tuple(Vec<u32>),
+    Primitive(Primitive),
+    Compact(u32),
+    BitSequence { store_ty: u32 },
+}
+
+#[derive(Debug, Clone)]
+struct Field<'a> {
+    name: Option<&'a str>,
+    ty: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Variant<'a> {
+    name: &'a str,
+    index: u8,
+    fields: Vec<Field<'a>>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Primitive {
+    Bool,
+    Char,
+    Str,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    U256,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    I256,
+}
+
+#[derive(Debug, Clone)]
+struct Pallet<'a> {
+    name: &'a str,
+    index: u8,
+    calls_ty: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+struct ExtrinsicMetadata<'a> {
+    version: u8,
+    address_ty: Option<u32>,
+    signature_ty: Option<u32>,
+    signed_extensions: Vec<(&'a str, u32)>,
+}
+
+enum ExtrinsicMetadataRaw<'a> {
+    V14 {
+        extrinsic_ty: u32,
+        version: u8,
+        signed_extensions: Vec<(&'a str, u32)>,
+    },
+    V15 {
+        version: u8,
+        address_ty: u32,
+        signature_ty: u32,
+        signed_extensions: Vec<(&'a str, u32)>,
+    },
+}
+
+fn decode_primitive(
+    primitive: Primitive,
+    bytes: &[u8],
+) -> Result<(&[u8], serde_json::Value), DecodeExtrinsicError> {
+    fn take<const N: usize>(bytes: &[u8]) -> Result<(&[u8], [u8; N]), DecodeExtrinsicError> {
+        if bytes.len() < N {
+            return Err(DecodeExtrinsicError::UnexpectedEnd);
+        }
+        let (value, bytes) = bytes.split_at(N);
+        Ok((bytes, <[u8; N]>::try_from(value).unwrap()))
+    }
+
+    Ok(match primitive {
+        Primitive::Bool => match take::<1>(bytes)? {
+            (bytes, [0]) => (bytes, serde_json::Value::Bool(false)),
+            (bytes, [1]) => (bytes, serde_json::Value::Bool(true)),
+            _ => return Err(DecodeExtrinsicError::InvalidValue),
+        },
+        Primitive::Char => {
+            let (bytes, value) = take::<4>(bytes)?;
+            let value = char::from_u32(u32::from_le_bytes(value))
+                .ok_or(DecodeExtrinsicError::InvalidValue)?;
+            (bytes, serde_json::Value::String(value.to_string()))
+        }
+        Primitive::Str => {
+            let (bytes, value) = crate::util::nom_string_decode::<nom::error::Error<&[u8]>>(bytes)
+                .map_err(|_| DecodeExtrinsicError::InvalidValue)?;
+            (bytes, serde_json::Value::String(value.to_owned()))
+        }
+        Primitive::U8 => {
+            let (bytes, value) = take::<1>(bytes)?;
+            (bytes, serde_json::Value::from(u8::from_le_bytes(value)))
+        }
+        Primitive::U16 => {
+            let (bytes, value) = take::<2>(bytes)?;
+            (bytes, serde_json::Value::from(u16::from_le_bytes(value)))
+        }
+        Primitive::U32 => {
+            let (bytes, value) = take::<4>(bytes)?;
+            (bytes, serde_json::Value::from(u32::from_le_bytes(value)))
+        }
+        Primitive::U64 => {
+            let (bytes, value) = take::<8>(bytes)?;
+            (bytes, serde_json::Value::from(u64::from_le_bytes(value)))
+        }
+        Primitive::U128 => {
+            let (bytes, value) = take::<16>(bytes)?;
+            (
+                bytes,
+                serde_json::Value::String(u128::from_le_bytes(value).to_string()),
+            )
+        }
+        Primitive::I8 => {
+            let (bytes, value) = take::<1>(bytes)?;
+            (bytes, serde_json::Value::from(i8::from_le_bytes(value)))
+        }
+        Primitive::I16 => {
+            let (bytes, value) = take::<2>(bytes)?;
+            (bytes, serde_json::Value::from(i16::from_le_bytes(value)))
+        }
+        Primitive::I32 => {
+            let (bytes, value) = take::<4>(bytes)?;
+            (bytes, serde_json::Value::from(i32::from_le_bytes(value)))
+        }
+        Primitive::I64 => {
+            let (bytes, value) = take::<8>(bytes)?;
+            (bytes, serde_json::Value::from(i64::from_le_bytes(value)))
+        }
+        Primitive::I128 => {
+            let (bytes, value) = take::<16>(bytes)?;
+            (
+                bytes,
+                serde_json::Value::String(i128::from_le_bytes(value).to_string()),
+            )
+        }
+        Primitive::U256 | Primitive::I256 => {
+            let (bytes, mut value) = take::<32>(bytes)?;
+            value.reverse();
+            (bytes, hex_value(&value))
+        }
+    })
+}
+
+/// Decodes a SCALE-compact-encoded integer that fits in a `u128`.
+fn decode_compact_u128(bytes: &[u8]) -> Result<(&[u8], u128), DecodeExtrinsicError> {
+    let (&first_byte, _) = bytes
+        .split_first()
+        .ok_or(DecodeExtrinsicError::UnexpectedEnd)?;
+
+    let num_bytes = match first_byte & 0b11 {
+        0b00 => 1,
+        0b01 => 2,
+        0b10 => 4,
+        _ => usize::from(first_byte >> 2) + 5,
+    };
+
+    if num_bytes > 17 {
+        return Err(DecodeExtrinsicError::InvalidValue);
+    }
+    if bytes.len() < num_bytes {
+        return Err(DecodeExtrinsicError::UnexpectedEnd);
+    }
+
+    let (value, rest) = bytes.split_at(num_bytes);
+    let value = if first_byte & 0b11 == 0b11 {
+        let mut buffer = [0; 16];
+        buffer[..value.len() - 1].copy_from_slice(&value[1..]);
+        u128::from_le_bytes(buffer)
+    } else {
+        let mut buffer = [0; 16];
+        buffer[..value.len()].copy_from_slice(value);
+        u128::from_le_bytes(buffer) >> 2
+    };
+
+    Ok((rest, value))
+}
+
+fn hex_value(bytes: &[u8]) -> serde_json::Value {
+    let mut out = String::with_capacity(2 + bytes.len() * 2);
+    out.push_str("0x");
+    for byte in bytes {
+        let _ = write!(out, "{byte:02x}");
+    }
+    serde_json::Value::String(out)
+}
+
+/// Returns a parser that decodes a SCALE-encoded vector of elements.
+fn scale_vec<'a, O>(
+    inner: impl FnMut(&'a [u8]) -> nom::IResult<&'a [u8], O> + Copy,
+) -> impl FnMut(&'a [u8]) -> nom::IResult<&'a [u8], Vec<O>> {
+    nom::combinator::flat_map(crate::util::nom_scale_compact_usize, move |num_elems| {
+        nom::multi::many_m_n(num_elems, num_elems, inner)
+    })
+}
+
+fn type_id(bytes: &[u8]) -> nom::IResult<&[u8], u32> {
+    nom::combinator::map_opt(crate::util::nom_scale_compact_u64, |id| {
+        u32::try_from(id).ok()
+    })(bytes)
+}
+
+fn docs(bytes: &[u8]) -> nom::IResult<&[u8], ()> {
+    nom::combinator::map(scale_vec(crate::util::nom_string_decode), |_| ())(bytes)
+}
+
+fn portable_type(bytes: &[u8]) -> nom::IResult<&[u8], PortableType<'_>> {
+    nom::combinator::map(
+        nom::sequence::tuple((
+            type_id,
+            scale_vec(crate::util::nom_string_decode),
+            scale_vec(type_param),
+            type_def,
+            docs,
+        )),
+        |(id, _path, params, def, ())| (id, params, def),
+    )(bytes)
+}
+
+fn type_param(bytes: &[u8]) -> nom::IResult<&[u8], (&str, Option<u32>)> {
+    nom::sequence::tuple((
+        crate::util::nom_string_decode,
+        crate::util::nom_option_decode(type_id),
+    ))(bytes)
+}
+
+fn type_def(bytes: &[u8]) -> nom::IResult<&[u8], TypeDef<'_>> {
+    let (bytes, discriminant) = nom::number::streaming::le_u8(bytes)?;
+    match discriminant {
+        0 => nom::combinator::map(scale_vec(field), TypeDef::Composite)(bytes),
+        1 => nom::combinator::map(scale_vec(variant), TypeDef::Variant)(bytes),
+        2 => nom::combinator::map(type_id, TypeDef::Sequence)(bytes),
+        3 => nom::combinator::map(
+            nom::sequence::tuple((nom::number::streaming::le_u32, type_id)),
+            |(len, elem_ty)| TypeDef::Array { len, elem_ty },
+        )(bytes),
+        4 => nom::combinator::map(scale_vec(type_id), TypeDef::Tuple)(bytes),
+        5 => nom::combinator::map_opt(nom::number::streaming::le_u8, |primitive| {
+            Some(TypeDef::Primitive(match primitive {
+                0 => Primitive::Bool,
+                1 => Primitive::Char,
+                2 => Primitive::Str,
+                3 => Primitive::U8,
+                4 => Primitive::U16,
+                5 => Primitive::U32,
+                6 => Primitive::U64,
+                7 => Primitive::U128,
+                8 => Primitive::U256,
+                9 => Primitive::I8,
+                10 => Primitive::I16,
+                11 => Primitive::I32,
+                12 => Primitive::I64,
+                13 => Primitive::I128,
+                14 => Primitive::I256,
+                _ => return None,
+            }))
+        })(bytes),
+        6 => nom::combinator::map(type_id, TypeDef::Compact)(bytes),
+        7 => nom::combinator::map(
+            nom::sequence::tuple((type_id, type_id)),
+            |(store_ty, _order_ty)| TypeDef::BitSequence { store_ty },
+        )(bytes),
+        _ => Err(nom::Err::Error(nom::error::make_error(
+            bytes,
+            nom::error::ErrorKind::Tag,
+        ))),
+    }
+}
+
+fn field(bytes: &[u8]) -> nom::IResult<&[u8], Field<'_>> {
+    nom::combinator::map(
+        nom::sequence::tuple((
+            crate::util::nom_option_decode(crate::util::nom_string_decode),
+            type_id,
+            crate::util::nom_option_decode(crate::util::nom_string_decode),
+            docs,
+        )),
+        |(name, ty, _type_name, ())| Field { name, ty },
+    )(bytes)
+}
+
+fn variant(bytes: &[u8]) -> nom::IResult<&[u8], Variant<'_>> {
+    nom::combinator::map(
+        nom::sequence::tuple((
+            crate::util::nom_string_decode,
+            scale_vec(field),
+            nom::number::streaming::le_u8,
+            docs,
+        )),
+        |(name, fields, index, ())| Variant {
+            name,
+            index,
+            fields,
+        },
+    )(bytes)
+}
+
+fn pallet(version: u8, bytes: &[u8]) -> nom::IResult<&[u8], Pallet<'_>> {
+    nom::combinator::map(
+        nom::sequence::tuple((
+            crate::util::nom_string_decode,
+            crate::util::nom_option_decode(pallet_storage),
+            crate::util::nom_option_decode(type_id),
+            crate::util::nom_option_decode(type_id),
+            scale_vec(pallet_constant),
+            crate::util::nom_option_decode(type_id),
+            nom::number::streaming::le_u8,
+            nom::combinator::cond(version >= 15, docs),
+        )),
+        |(name, _storage, calls_ty, _event_ty, _constants, _error_ty, index, _docs)| Pallet {
+            name,
+            index,
+            calls_ty,
+        },
+    )(bytes)
+}
+
+fn pallet_storage(bytes: &[u8]) -> nom::IResult<&[u8], ()> {
+    nom::combinator::map(
+        nom::sequence::tuple((crate::util::nom_string_decode, scale_vec(storage_entry))),
+        |_| (),
+    )(bytes)
+}
+
+fn storage_entry(bytes: &[u8]) -> nom::IResult<&[u8], ()> {
+    nom::combinator::map(
+        nom::sequence::tuple((
+            crate::util::nom_string_decode,
+            nom::number::streaming::le_u8,
+            nom::branch::alt((
+                nom::combinator::map(
+                    nom::sequence::preceded(nom::bytes::streaming::tag(&[0]), type_id),
+                    |_| (),
+                ),
+                nom::combinator::map(
+                    nom::sequence::preceded(
+                        nom::bytes::streaming::tag(&[1]),
+                        nom::sequence::tuple((
+                            scale_vec(nom::number::streaming::le_u8),
+                            type_id,
+                            type_id,
+                        )),
+                    ),
+                    |_| (),
+                ),
+            )),
+            crate::util::nom_bytes_decode,
+            docs,
+        )),
+        |_| (),
+    )(bytes)
+}
+
+fn pallet_constant(bytes: &[u8]) -> nom::IResult<&[u8], ()> {
+    nom::combinator::map(
+        nom::sequence::tuple((
+            crate::util::nom_string_decode,
+            type_id,
+            crate::util::nom_bytes_decode,
+            docs,
+        )),
+        |_| (),
+    )(bytes)
+}
+
+fn extrinsic_metadata(version: u8, bytes: &[u8]) -> nom::IResult<&[u8], ExtrinsicMetadataRaw<'_>> {
+    if version >= 15 {
+        nom::combinator::map(
+            nom::sequence::tuple((
+                nom::number::streaming::le_u8,
+                type_id,
+                type_id,
+                type_id,
+                type_id,
+                scale_vec(signed_extension),
+            )),
+            |(version, address_ty, _call_ty, signature_ty, _extra_ty, signed_extensions)| {
+                ExtrinsicMetadataRaw::V15 {
+                    version,
+                    address_ty,
+                    signature_ty,
+                    signed_extensions,
+                }
+            },
+        )(bytes)
+    } else {
+        nom::combinator::map(
+            nom::sequence::tuple((
+                type_id,
+                nom::number::streaming::le_u8,
+                scale_vec(signed_extension),
+            )),
+            |(extrinsic_ty, version, signed_extensions)| ExtrinsicMetadataRaw::V14 {
+                extrinsic_ty,
+                version,
+                signed_extensions,
+            },
+        )(bytes)
+    }
+}
+
+fn signed_extension(bytes: &[u8]) -> nom::IResult<&[u8], (&str, u32)> {
+    nom::combinator::map(
+        nom::sequence::tuple((crate::util::nom_string_decode, type_id, type_id)),
+        |(identifier, ty, _additional_signed_ty)| (identifier, ty),
+    )(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodeExtrinsicError, Metadata};
+
+    fn node_template_metadata() -> Vec<u8> {
+        hex::decode(
+            include_str!("./metadata/substrate-node-template-metadata.hex")
+                .trim()
+                .trim_start_matches("0x"),
+        )
+        .unwrap()
+    }
+
+    fn with_length_prefix(extrinsic: &[u8]) -> Vec<u8> {
+        assert!(extrinsic.len() < 64);
+        let mut out = vec![u8::try_from(extrinsic.len() << 2).unwrap()];
+        out.extend_from_slice(extrinsic);
+        out
+    }
+
+    #[test]
+    fn unsupported_version() {
+        assert!(matches!(
+            Metadata::decode(b"meta\x0d"),
+            Err(super::DecodeError::UnsupportedVersion(13))
+        ));
+        assert!(matches!(
+            Metadata::decode(b"atem\x0e"),
+            Err(super::DecodeError::MagicNumberMismatch)
+        ));
+    }
+
+    #[test]
+    fn timestamp_set() {
+        let metadata = node_template_metadata();
+        let metadata = Metadata::decode(&metadata).unwrap();
+
+        // `Timestamp::set` with a compact-encoded `now` equal to 1000000.
+        let extrinsic = with_length_prefix(&[0x04, 0x02, 0x00, 0x02, 0x09, 0x3d, 0x00]);
+        let decoded = metadata.decode_extrinsic(&extrinsic).unwrap();
+        assert!(decoded.signature.is_none());
+        assert_eq!(decoded.pallet_name, "Timestamp");
+        assert_eq!(decoded.call_name, "set");
+        assert_eq!(decoded.arguments, serde_json::json!({ "now": 1000000 }));
+    }
+
+    #[test]
+    fn signed_balances_transfer() {
+        let metadata = node_template_metadata();
+        let metadata = Metadata::decode(&metadata).unwrap();
+
+        let mut extrinsic = vec![0x84];
+        // Address: `MultiAddress::Id`.
+        extrinsic.push(0x00);
+        extrinsic.extend_from_slice(&[0x11; 32]);
+        // Signature: `MultiSignature::Sr25519`.
+        extrinsic.push(0x01);
+        extrinsic.extend_from_slice(&[0x22; 64]);
+        // Signed extensions: immortal era, nonce equal to 1, and tip equal to 0.
+        extrinsic.extend_from_slice(&[0x00, 0x04, 0x00]);
+        // Call: `Balances::transfer` to `MultiAddress::Id` with a value equal to 12345.
+        extrinsic.extend_from_slice(&[0x05, 0x00, 0x00]);
+        extrinsic.extend_from_slice(&[0x33; 32]);
+        extrinsic.extend_from_slice(&[0xe5, 0xc0]);
+
+        assert_eq!(extrinsic.len(), 139);
+        let mut scale_encoded = vec![0x2d, 0x02];
+        scale_encoded.extend_from_slice(&extrinsic);
+
+        let decoded = metadata.decode_extrinsic(&scale_encoded).unwrap();
+        assert_eq!(decoded.pallet_name, "Balances");
+        assert_eq!(decoded.call_name, "transfer");
+        assert_eq!(
+            decoded.arguments,
+            serde_json::json!({
+                "dest": { "Id": format!("0x{}", "33".repeat(32)) },
+                "value": "12345",
+            })
+        );
+
+        let signature = decoded.signature.unwrap();
+        assert_eq!(
+            signature.address,
+            serde_json::json!({ "Id": format!("0x{}", "11".repeat(32)) })
+        );
+        assert_eq!(
+            signature.signature,
+            serde_json::json!({ "Sr25519": format!("0x{}", "22".repeat(64)) })
+        );
+        assert_eq!(
+            signature.signed_extensions,
+            vec![
+                ("CheckSpecVersion", serde_json::Value::Null),
+                ("CheckTxVersion", serde_json::Value::Null),
+                ("CheckGenesis", serde_json::Value::Null),
+                ("CheckMortality", serde_json::json!("Immortal")),
+                ("CheckNonce", serde_json::json!(1)),
+                ("CheckWeight", serde_json::Value::Null),
+                ("ChargeTransactionPayment", serde_json::json!("0")),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_extrinsics() {
+        let metadata = node_template_metadata();
+        let metadata = Metadata::decode(&metadata).unwrap();
+
+        assert!(matches!(
+            metadata.decode_extrinsic(&[0x10, 0x04, 0x02, 0x00]),
+            Err(DecodeExtrinsicError::InvalidLengthPrefix)
+        ));
+        assert!(matches!(
+            metadata.decode_extrinsic(&with_length_prefix(&[0x05, 0x02, 0x00, 0x00])),
+            Err(DecodeExtrinsicError::UnsupportedVersion(5))
+        ));
+        assert!(matches!(
+            metadata.decode_extrinsic(&with_length_prefix(&[0x04, 0x01, 0x00])),
+            Err(DecodeExtrinsicError::UnknownPallet(1))
+        ));
+        assert!(matches!(
+            metadata.decode_extrinsic(&with_length_prefix(&[0x04, 0x02, 0x09])),
+            Err(DecodeExtrinsicError::UnknownCall {
+                pallet_index: 2,
+                call_index: 9
+            })
+        ));
+        assert!(matches!(
+            metadata.decode_extrinsic(&with_length_prefix(&[0x04, 0x02, 0x00, 0x00, 0x00])),
+            Err(DecodeExtrinsicError::TrailingBytes)
+        ));
+        assert!(matches!(
+            metadata.decode_extrinsic(&with_length_prefix(&[0x04, 0x02, 0x00, 0x02])),
+            Err(DecodeExtrinsicError::UnexpectedEnd)
+        ));
+    }
+}
@@ -641,6 +641,29 @@ where
         }
     }
 
+    /// Returns the moment when the ban of the given peer on the given chain expires, or `None`
+    /// if the peer isn't banned on this chain at the given moment.
+    ///
+    /// Returns `None` if the peer isn't assigned to the given chain.
+    pub fn ban_expiration(
+        &self,
+        chain: &TChainId,
+        peer_id: &PeerId,
+        now: &TInstant,
+    ) -> Option<&TInstant> {
+        let (Some(&peer_id_index), Some(&chain_index)) = (
+            self.peer_ids_indices.get(peer_id),
+            self.chains_indices.get(chain),
+        ) else {
+            return None;
+        };
+
+        match self.peers_chains.get(&(peer_id_index, chain_index))? {
+            PeerChainState::Banned { expires } if expires > now => Some(expires),
+            _ => None,
+        }
+    }
+
     /// Picks an address from the list with zero connections, and sets the number of connections
     /// to one. Returns `None` if no such address is available.
     pub fn pick_address_and_add_connection(&mut self, peer_id: &PeerId) -> Option<&[u8]> {
@@ -1130,5 +1153,32 @@ mod tests {
         assert_eq!(bps.peer_addresses(&peer_id).count(), 1);
     }
 
+    #[test]
+    fn ban_expiration() {
+        let mut bps = BasicPeeringStrategy::<u32, Duration>::new(Config {
+            randomness_seed: [0; 32],
+            peers_capacity: 0,
+            chains_capacity: 0,
+        });
+
+        let peer_id = PeerId::from_public_key(&PublicKey::Ed25519([0; 32]));
+        let now = Duration::from_secs(10);
+
+        assert!(bps.ban_expiration(&0, &peer_id, &now).is_none());
+
+        bps.assign_slot(&0, &peer_id);
+        assert!(bps.ban_expiration(&0, &peer_id, &now).is_none());
+
+        bps.unassign_slot_and_ban(&0, &peer_id, Duration::from_secs(15));
+        assert_eq!(
+            bps.ban_expiration(&0, &peer_id, &now),
+            Some(&Duration::from_secs(15))
+        );
+        assert!(bps.ban_expiration(&1, &peer_id, &now).is_none());
+        assert!(bps
+            .ban_expiration(&0, &peer_id, &Duration::from_secs(15))
+            .is_none());
+    }
+
     // TODO: more tests
 }
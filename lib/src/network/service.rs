@@ -654,6 +654,41 @@ where
         self.chains.iter().map(|(idx, _)| ChainId(idx))
     }
 
+    /// Returns the names of the protocols of the given chain that the [`ChainNetwork`] can open
+    /// substreams with, in no particular order.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`ChainId`] is out of range.
+    ///
+    pub fn chain_protocol_names(&'_ self, chain_id: ChainId) -> impl Iterator<Item = String> + '_ {
+        let chain_index = chain_id.0;
+        let chain = &self.chains[chain_index];
+
+        [
+            Protocol::Notifications(NotificationsProtocol::BlockAnnounces { chain_index }),
+            Protocol::Notifications(NotificationsProtocol::Transactions { chain_index }),
+        ]
+        .into_iter()
+        .chain(
+            chain
+                .grandpa_protocol_config
+                .is_some()
+                .then_some(Protocol::Notifications(NotificationsProtocol::Grandpa {
+                    chain_index,
+                })),
+        )
+        .chain([
+            Protocol::Sync { chain_index },
+            // `LightStorage` and `LightCall` share the same name as `LightUnknown`.
+            Protocol::LightUnknown { chain_index },
+            Protocol::Kad { chain_index },
+            Protocol::SyncWarp { chain_index },
+            Protocol::State { chain_index },
+        ])
+        .map(|protocol| codec::encode_protocol_name_string(self.protocol_name(protocol)))
+    }
+
     /// Returns the value passed as [`ChainConfig::block_number_bytes`] for the given chain.
     ///
     /// # Panic
@@ -3050,84 +3085,7 @@ where
             })
             .ok_or(StartRequestError::NoConnection)?;
 
-        let protocol_name = {
-            let protocol_name = match protocol {
-                Protocol::Identify => codec::ProtocolName::Identify,
-                Protocol::Ping => codec::ProtocolName::Ping,
-                Protocol::Notifications(NotificationsProtocol::BlockAnnounces { chain_index }) => {
-                    let chain_info = &self.chains[chain_index];
-                    codec::ProtocolName::BlockAnnounces {
-                        genesis_hash: chain_info.genesis_hash,
-                        fork_id: chain_info.fork_id.as_deref(),
-                    }
-                }
-                Protocol::Notifications(NotificationsProtocol::Transactions { chain_index }) => {
-                    let chain_info = &self.chains[chain_index];
-                    codec::ProtocolName::Transactions {
-                        genesis_hash: chain_info.genesis_hash,
-                        fork_id: chain_info.fork_id.as_deref(),
-                    }
-                }
-                Protocol::Notifications(NotificationsProtocol::Grandpa { chain_index }) => {
-                    let chain_info = &self.chains[chain_index];
-                    codec::ProtocolName::Grandpa {
-                        genesis_hash: chain_info.genesis_hash,
-                        fork_id: chain_info.fork_id.as_deref(),
-                    }
-                }
-                Protocol::Sync { chain_index } => {
-                    let chain_info = &self.chains[chain_index];
-                    codec::ProtocolName::Sync {
-                        genesis_hash: chain_info.genesis_hash,
-                        fork_id: chain_info.fork_id.as_deref(),
-                    }
-                }
-                Protocol::LightUnknown { chain_index } => {
-                    let chain_info = &self.chains[chain_index];
-                    codec::ProtocolName::Light {
-                        genesis_hash: chain_info.genesis_hash,
-                        fork_id: chain_info.fork_id.as_deref(),
-                    }
-                }
-                Protocol::LightStorage { chain_index } => {
-                    let chain_info = &self.chains[chain_index];
-                    codec::ProtocolName::Light {
-                        genesis_hash: chain_info.genesis_hash,
-                        fork_id: chain_info.fork_id.as_deref(),
-                    }
-                }
-                Protocol::LightCall { chain_index } => {
-                    let chain_info = &self.chains[chain_index];
-                    codec::ProtocolName::Light {
-                        genesis_hash: chain_info.genesis_hash,
-                        fork_id: chain_info.fork_id.as_deref(),
-                    }
-                }
-                Protocol::Kad { chain_index } => {
-                    let chain_info = &self.chains[chain_index];
-                    codec::ProtocolName::Kad {
-                        genesis_hash: chain_info.genesis_hash,
-                        fork_id: chain_info.fork_id.as_deref(),
-                    }
-                }
-                Protocol::SyncWarp { chain_index } => {
-                    let chain_info = &self.chains[chain_index];
-                    codec::ProtocolName::SyncWarp {
-                        genesis_hash: chain_info.genesis_hash,
-                        fork_id: chain_info.fork_id.as_deref(),
-                    }
-                }
-                Protocol::State { chain_index } => {
-                    let chain_info = &self.chains[chain_index];
-                    codec::ProtocolName::State {
-                        genesis_hash: chain_info.genesis_hash,
-                        fork_id: chain_info.fork_id.as_deref(),
-                    }
-                }
-            };
-
-            codec::encode_protocol_name_string(protocol_name)
-        };
+        let protocol_name = codec::encode_protocol_name_string(self.protocol_name(protocol));
 
         let substream_id = self.inner.start_request(
             connection_id,
@@ -3149,6 +3107,84 @@ where
         Ok(substream_id)
     }
 
+    /// Returns the name of the given protocol.
+    fn protocol_name(&self, protocol: Protocol) -> codec::ProtocolName<'_> {
+        match protocol {
+            Protocol::Identify => codec::ProtocolName::Identify,
+            Protocol::Ping => codec::ProtocolName::Ping,
+            Protocol::Notifications(NotificationsProtocol::BlockAnnounces { chain_index }) => {
+                let chain_info = &self.chains[chain_index];
+                codec::ProtocolName::BlockAnnounces {
+                    genesis_hash: chain_info.genesis_hash,
+                    fork_id: chain_info.fork_id.as_deref(),
+                }
+            }
+            Protocol::Notifications(NotificationsProtocol::Transactions { chain_index }) => {
+                let chain_info = &self.chains[chain_index];
+                codec::ProtocolName::Transactions {
+                    genesis_hash: chain_info.genesis_hash,
+                    fork_id: chain_info.fork_id.as_deref(),
+                }
+            }
+            Protocol::Notifications(NotificationsProtocol::Grandpa { chain_index }) => {
+                let chain_info = &self.chains[chain_index];
+                codec::ProtocolName::Grandpa {
+                    genesis_hash: chain_info.genesis_hash,
+                    fork_id: chain_info.fork_id.as_deref(),
+                }
+            }
+            Protocol::Sync { chain_index } => {
+                let chain_info = &self.chains[chain_index];
+                codec::ProtocolName::Sync {
+                    genesis_hash: chain_info.genesis_hash,
+                    fork_id: chain_info.fork_id.as_deref(),
+                }
+            }
+            Protocol::LightUnknown { chain_index } => {
+                let chain_info = &self.chains[chain_index];
+                codec::ProtocolName::Light {
+                    genesis_hash: chain_info.genesis_hash,
+                    fork_id: chain_info.fork_id.as_deref(),
+                }
+            }
+            Protocol::LightStorage { chain_index } => {
+                let chain_info = &self.chains[chain_index];
+                codec::ProtocolName::Light {
+                    genesis_hash: chain_info.genesis_hash,
+                    fork_id: chain_info.fork_id.as_deref(),
+                }
+            }
+            Protocol::LightCall { chain_index } => {
+                let chain_info = &self.chains[chain_index];
+                codec::ProtocolName::Light {
+                    genesis_hash: chain_info.genesis_hash,
+                    fork_id: chain_info.fork_id.as_deref(),
+                }
+            }
+            Protocol::Kad { chain_index } => {
+                let chain_info = &self.chains[chain_index];
+                codec::ProtocolName::Kad {
+                    genesis_hash: chain_info.genesis_hash,
+                    fork_id: chain_info.fork_id.as_deref(),
+                }
+            }
+            Protocol::SyncWarp { chain_index } => {
+                let chain_info = &self.chains[chain_index];
+                codec::ProtocolName::SyncWarp {
+                    genesis_hash: chain_info.genesis_hash,
+                    fork_id: chain_info.fork_id.as_deref(),
+                }
+            }
+            Protocol::State { chain_index } => {
+                let chain_info = &self.chains[chain_index];
+                codec::ProtocolName::State {
+                    genesis_hash: chain_info.genesis_hash,
+                    fork_id: chain_info.fork_id.as_deref(),
+                }
+            }
+        }
+    }
+
     /// Responds to an identify request. Call this function in response to
     /// a [`Event::IdentifyRequestIn`].
     ///
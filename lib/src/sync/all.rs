@@ -2010,6 +2010,11 @@ impl<TRq, TSrc, TBl> BlockVerify<TRq, TSrc, TBl> {
         *self.inner.hash()
     }
 
+    /// Returns the height of the block to be verified.
+    pub fn height(&self) -> u64 {
+        self.inner.height()
+    }
+
     /// Returns the list of SCALE-encoded extrinsics of the block to verify.
     ///
     /// This is `Some` if and only if [`Config::download_bodies`] is `true`
@@ -2203,6 +2208,12 @@ impl<TRq, TSrc, TBl> FinalityProofVerify<TRq, TSrc, TBl> {
         )
     }
 
+    /// Returns the consensus engine id and SCALE-encoded justification that is going to be
+    /// verified, or `None` if the finality proof to verify is a Grandpa commit message.
+    pub fn justification(&self) -> Option<([u8; 4], &[u8])> {
+        self.inner.justification()
+    }
+
     /// Perform the verification.
     ///
     /// A randomness seed must be provided and will be used during the verification. Note that the
@@ -1899,6 +1899,11 @@ impl<TBl, TRq, TSrc> BlockVerify<TBl, TRq, TSrc> {
         &self.block_to_verify.block_hash
     }
 
+    /// Returns the height of the block to be verified.
+    pub fn height(&self) -> u64 {
+        self.block_to_verify.block_number
+    }
+
     /// Returns the list of SCALE-encoded extrinsics of the block to verify.
     ///
     /// This is `Some` if and only if [`Config::download_bodies`] is `true`
@@ -2154,6 +2159,17 @@ impl<TBl, TRq, TSrc> FinalityProofVerify<TBl, TRq, TSrc> {
         (self.source_id, &self.parent[self.source_id])
     }
 
+    /// Returns the consensus engine id and SCALE-encoded justification that is going to be
+    /// verified, or `None` if the finality proof to verify is a Grandpa commit message.
+    pub fn justification(&self) -> Option<([u8; 4], &[u8])> {
+        match &self.finality_proof_to_verify {
+            FinalityProof::GrandpaCommit(_) => None,
+            FinalityProof::Justification((engine_id, justification)) => {
+                Some((*engine_id, &justification[..]))
+            }
+        }
+    }
+
     /// Perform the verification.
     ///
     /// A randomness seed must be provided and will be used during the verification. Note that the
@@ -109,6 +109,7 @@ use crate::{
 
 use alloc::{
     borrow::{Cow, ToOwned as _},
+    boxed::Box,
     collections::{BTreeSet, VecDeque},
     vec,
     vec::Vec,
@@ -188,7 +189,7 @@ pub struct ConfigCodeTrieNodeHint {
 /// On error, returns the [`ValidChainInformation`] that was provided in the configuration.
 pub fn start_warp_sync<TSrc, TRq>(
     config: Config,
-) -> Result<WarpSync<TSrc, TRq>, (ValidChainInformation, WarpSyncInitError)> {
+) -> Result<WarpSync<TSrc, TRq>, (Box<ValidChainInformation>, WarpSyncInitError)> {
     match config.start_chain_information.as_ref().finality {
         // TODO: we make sure that `finalized_scheduled_change` is `None` because it seems complicated to support, but ideally it would be supported
         ChainInformationFinalityRef::Grandpa {
@@ -197,7 +198,7 @@ pub fn start_warp_sync<TSrc, TRq>(
         } => {}
         _ => {
             return Err((
-                config.start_chain_information,
+                Box::new(config.start_chain_information),
                 WarpSyncInitError::NotGrandpa,
             ))
         }
@@ -207,7 +208,7 @@ pub fn start_warp_sync<TSrc, TRq>(
         ChainInformationConsensusRef::Babe { .. } | ChainInformationConsensusRef::Aura { .. } => {}
         ChainInformationConsensusRef::Unknown => {
             return Err((
-                config.start_chain_information,
+                Box::new(config.start_chain_information),
                 WarpSyncInitError::UnknownConsensus,
             ))
         }
@@ -556,7 +557,7 @@ impl<TSrc, TRq> WarpSync<TSrc, TRq> {
         // The implementation simply resets the state machine, apart from the fragment download
         // and verification queue.
         // TODO: what if the new chain doesn't support grandpa?
-        if self.warped_header_number <= chain_information.as_ref().finalized_block_header.number {
+        if self.warped_header_number <= chain_information.finalized_block_number() {
             self.warped_header = chain_information
                 .as_ref()
                 .finalized_block_header
@@ -571,7 +572,7 @@ impl<TSrc, TRq> WarpSync<TSrc, TRq> {
                 .as_ref()
                 .finalized_block_header
                 .extrinsics_root;
-            self.warped_header_number = chain_information.as_ref().finalized_block_header.number;
+            self.warped_header_number = chain_information.finalized_block_number();
             self.warped_finality = chain_information.as_ref().finality.into();
             self.warped_block_ty = WarpedBlockTy::AlreadyVerified;
 
@@ -2218,8 +2219,7 @@ impl<TSrc, TRq> BuildChainInformation<TSrc, TRq> {
                     // This `if` is necessary as in principle we might have continued warp syncing
                     // after downloading everything needed but before building the chain
                     // information.
-                    if self.inner.warped_header_number
-                        == chain_information.as_ref().finalized_block_header.number
+                    if self.inner.warped_header_number == chain_information.finalized_block_number()
                     {
                         self.inner.warped_block_ty = WarpedBlockTy::AlreadyVerified;
                     }
@@ -179,12 +179,17 @@ pub const EMPTY_KECCAK256_TRIE_MERKLE_VALUE: [u8; 32] = [
 /// Returns the Merkle value of a trie containing the entries passed as parameter. The entries
 /// passed as parameter are `(key, value)`.
 ///
-/// The entries do not need to be ordered.
+/// The entries do not need to be ordered. If the same key is found multiple times, the last
+/// value is used.
+///
+/// This can notably be used in order to verify that a set of storage entries, for example the
+/// genesis storage found in a chain specification, matches the `state_root` of a block header.
 pub fn trie_root(
     version: TrieEntryVersion,
     hash_function: HashFunction,
-    unordered_entries: &[(impl AsRef<[u8]>, impl AsRef<[u8]>)],
+    unordered_entries: impl IntoIterator<Item = (impl AsRef<[u8]>, impl AsRef<[u8]>)>,
 ) -> [u8; 32] {
+    let unordered_entries = unordered_entries.into_iter().collect::<Vec<_>>();
     let ordered_entries = unordered_entries
         .iter()
         .map(|(k, v)| (k.as_ref(), v.as_ref()))
@@ -349,7 +354,7 @@ mod tests {
         let obtained = super::trie_root(
             super::TrieEntryVersion::V0,
             super::HashFunction::Blake2,
-            &[(&b"foo"[..], &b"bar"[..]), (&b"foobar"[..], &b"baz"[..])],
+            [(&b"foo"[..], &b"bar"[..]), (&b"foobar"[..], &b"baz"[..])],
         );
 
         assert_eq!(
@@ -366,7 +371,7 @@ mod tests {
         let obtained = super::trie_root(
             super::TrieEntryVersion::V0,
             super::HashFunction::Keccak256,
-            &[(&b"foo"[..], &b"bar"[..]), (&b"foobar"[..], &b"baz"[..])],
+            [(&b"foo"[..], &b"bar"[..]), (&b"foobar"[..], &b"baz"[..])],
         );
 
         assert_eq!(
@@ -378,12 +383,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn trie_root_genesis_storage() {
+        // Genesis storage of a small Substrate chain, whose runtime uses the V0 trie entry
+        // version, and the `state_root` of its genesis block header.
+        let chain_spec = crate::chain_spec::ChainSpec::from_json_bytes(
+            &include_bytes!("author/runtime/example-chain-specs.json")[..],
+        )
+        .unwrap();
+        let genesis_storage = chain_spec.genesis_storage().into_genesis_items().unwrap();
+        assert_eq!(genesis_storage.iter().count(), 88);
+
+        let obtained = super::trie_root(
+            super::TrieEntryVersion::V0,
+            super::HashFunction::Blake2,
+            genesis_storage.iter(),
+        );
+
+        assert_eq!(
+            obtained,
+            [
+                198, 191, 193, 169, 11, 165, 39, 243, 41, 184, 77, 117, 139, 249, 215, 210, 15, 92,
+                216, 172, 8, 247, 98, 83, 63, 214, 235, 147, 26, 23, 250, 122
+            ]
+        );
+
+        // The order of the entries doesn't matter.
+        let mut reversed = genesis_storage.iter().collect::<alloc::vec::Vec<_>>();
+        reversed.reverse();
+        assert_eq!(
+            super::trie_root(
+                super::TrieEntryVersion::V0,
+                super::HashFunction::Blake2,
+                reversed
+            ),
+            obtained
+        );
+
+        // Removing an entry changes the root.
+        assert_ne!(
+            super::trie_root(
+                super::TrieEntryVersion::V0,
+                super::HashFunction::Blake2,
+                genesis_storage.iter().skip(1)
+            ),
+            obtained
+        );
+    }
+
     #[test]
     fn trie_root_example_v1_blake2() {
         let obtained = super::trie_root(
             super::TrieEntryVersion::V1,
             super::HashFunction::Blake2,
-            &[
+            [
                 (&b"bar"[..], &b"foo"[..]),
                 (&b"barfoo"[..], &b"hello"[..]),
                 (&b"anotheritem"[..], &b"anothervalue"[..]),
@@ -404,7 +457,7 @@ mod tests {
         let obtained = super::trie_root(
             super::TrieEntryVersion::V1,
             super::HashFunction::Keccak256,
-            &[
+            [
                 (&b"bar"[..], &b"foo"[..]),
                 (&b"barfoo"[..], &b"hello"[..]),
                 (&b"anotheritem"[..], &b"anothervalue"[..]),
@@ -45,7 +45,7 @@
 //! the block header (with the exclusion of the seal itself) made using the public key in question.
 //!
 
-use crate::header;
+use crate::{header, trie};
 
 use alloc::vec::Vec;
 use core::{num::NonZero, time::Duration};
@@ -58,6 +58,10 @@ pub struct VerifyConfig<'a, TAuthList> {
     /// Number of bytes used to encode the block number in the header.
     pub block_number_bytes: usize,
 
+    /// Hash function used to calculate the hash of the headers of the chain. The seal is a
+    /// signature of the hash of the header, calculated with this function.
+    pub header_hash_function: trie::HashFunction,
+
     /// Header of the parent of the block to verify.
     ///
     /// [`verify_header`] assumes that this block has been successfully verified before.
@@ -113,7 +117,7 @@ pub enum VerifyError {
 
 /// Verifies whether a block header provides a correct proof of the legitimacy of the authorship.
 pub fn verify_header<'a>(
-    mut config: VerifyConfig<'a, impl ExactSizeIterator<Item = header::AuraAuthorityRef<'a>>>,
+    config: VerifyConfig<'a, impl ExactSizeIterator<Item = header::AuraAuthorityRef<'a>>>,
 ) -> Result<VerifySuccess, VerifyError> {
     // TODO: handle OnDisabled
 
@@ -169,10 +173,33 @@ pub fn verify_header<'a>(
         }
     }
 
+    verify_seal(
+        config.header,
+        config.block_number_bytes,
+        config.header_hash_function,
+        slot_number,
+        config.current_authorities,
+    )?;
+
+    // Success! 🚀
+    Ok(VerifySuccess { authorities_change })
+}
+
+/// Verifies that the seal of the given header is a valid signature of the authority that is
+/// allowed to author a block during the given slot.
+///
+/// The header must contain a seal, which is the last digest item.
+pub(crate) fn verify_seal<'a>(
+    header: header::HeaderRef<'a>,
+    block_number_bytes: usize,
+    header_hash_function: trie::HashFunction,
+    slot_number: u64,
+    mut authorities: impl ExactSizeIterator<Item = header::AuraAuthorityRef<'a>>,
+) -> Result<(), VerifyError> {
     // The signature in the seal applies to the header from where the signature isn't present.
     // Extract the signature and build the hash that is expected to be signed.
     let (seal_signature, pre_seal_hash) = {
-        let mut unsealed_header = config.header;
+        let mut unsealed_header = header;
         let seal_signature = match unsealed_header.digest.pop_seal() {
             Some(header::Seal::Aura(seal)) => {
                 schnorrkel::Signature::from_bytes(seal).map_err(|_| VerifyError::BadSignature)?
@@ -181,41 +208,34 @@ pub fn verify_header<'a>(
         };
         (
             seal_signature,
-            unsealed_header.hash(config.block_number_bytes),
+            unsealed_header.hash_with_function(block_number_bytes, header_hash_function),
         )
     };
 
     // Fetch the authority that has supposedly signed the block.
-    if config.current_authorities.len() == 0 {
+    if authorities.len() == 0 {
         // Checked beforehand in order to not do a modulo 0 operation.
         return Err(VerifyError::EmptyAuthorities);
     }
     // About overflows:
-    // If `config.current_authorities.len()` doesn't fit in a `u64`, then
-    // `slot_number % config.current_authorities.len()` is necessarily equal to `slot_number`.
+    // If `authorities.len()` doesn't fit in a `u64`, then
+    // `slot_number % authorities.len()` is necessarily equal to `slot_number`.
     // Since we're using a modulo operation, `signing_authority` is always inferior to
-    // `current_authorities.len()`, meaning that it always fits in a `usize`.
-    let signing_authority = usize::try_from(
-        slot_number % u64::try_from(config.current_authorities.len()).unwrap_or(u64::MAX),
-    )
-    .unwrap_or_else(|_| unreachable!());
+    // `authorities.len()`, meaning that it always fits in a `usize`.
+    let signing_authority =
+        usize::try_from(slot_number % u64::try_from(authorities.len()).unwrap_or(u64::MAX))
+            .unwrap_or_else(|_| unreachable!());
 
     // This `unwrap()` can only panic if `public_key` is the wrong length, which we know can't
     // happen as it's of type `[u8; 32]`.
-    let authority_public_key = schnorrkel::PublicKey::from_bytes(
-        config
-            .current_authorities
-            .nth(signing_authority)
-            .unwrap()
-            .public_key,
-    )
-    .unwrap();
+    let authority_public_key =
+        schnorrkel::PublicKey::from_bytes(authorities.nth(signing_authority).unwrap().public_key)
+            .unwrap();
 
     // Now verifying the signature in the seal.
     authority_public_key
         .verify_simple(b"substrate", &pre_seal_hash, &seal_signature)
         .map_err(|_| VerifyError::BadSignature)?;
 
-    // Success! 🚀
-    Ok(VerifySuccess { authorities_change })
+    Ok(())
 }
@@ -129,7 +129,7 @@
 //!
 //! See also the [`crate::chain::chain_information`] module for more help.
 
-use crate::{chain::chain_information, header};
+use crate::{chain::chain_information, header, trie};
 
 use core::{num::NonZero, time::Duration};
 
@@ -141,6 +141,10 @@ pub struct VerifyConfig<'a> {
     /// Number of bytes used to encode the block number in the header.
     pub block_number_bytes: usize,
 
+    /// Hash function used to calculate the hash of the headers of the chain. The seal is a
+    /// signature of the hash of the header, calculated with this function.
+    pub header_hash_function: trie::HashFunction,
+
     /// Header of the parent of the block to verify.
     ///
     /// [`verify_header`] assumes that this block has been successfully verified before.
@@ -444,7 +448,7 @@ pub fn verify_header(config: VerifyConfig) -> Result<VerifySuccess, VerifyError>
         let mut unsealed_header = config.header;
         let _popped = unsealed_header.digest.pop_seal();
         debug_assert!(matches!(_popped, Some(header::Seal::Babe(_))));
-        unsealed_header.hash(config.block_number_bytes)
+        unsealed_header.hash_with_function(config.block_number_bytes, config.header_hash_function)
     };
 
     // Fetch the authority that has supposedly signed the block.
@@ -17,7 +17,7 @@
 
 use crate::{
     chain::chain_information,
-    header,
+    header, trie,
     verify::{aura, babe},
 };
 
@@ -40,6 +40,9 @@ pub struct Config<'a> {
     /// Number of bytes used to encode the block number in the header.
     pub block_number_bytes: usize,
 
+    /// Hash function used to calculate the hash of the headers of the chain.
+    pub header_hash_function: trie::HashFunction,
+
     /// Configuration items related to the consensus engine.
     pub consensus: ConfigConsensus<'a>,
 
@@ -183,7 +186,9 @@ pub fn verify(config: Config) -> Result<Success, Error> {
     // unnecessary and introduces an overhead.
     // However this check is performed anyway, as the consequences of a failure here could be
     // potentially quite high.
-    if config.parent_block_header.hash(config.block_number_bytes)
+    if config
+        .parent_block_header
+        .hash_with_function(config.block_number_bytes, config.header_hash_function)
         != *config.block_header.parent_hash
     {
         return Err(Error::BadParentHash);
@@ -240,6 +245,7 @@ pub fn verify(config: Config) -> Result<Success, Error> {
             let result = aura::verify_header(aura::VerifyConfig {
                 header: config.block_header.clone(),
                 block_number_bytes: config.block_number_bytes,
+                header_hash_function: config.header_hash_function,
                 parent_block_header: config.parent_block_header,
                 now_from_unix_epoch,
                 current_authorities,
@@ -266,6 +272,7 @@ pub fn verify(config: Config) -> Result<Success, Error> {
             let result = babe::verify_header(babe::VerifyConfig {
                 header: config.block_header.clone(),
                 block_number_bytes: config.block_number_bytes,
+                header_hash_function: config.header_hash_function,
                 parent_block_header: config.parent_block_header,
                 parent_block_epoch,
                 parent_block_next_epoch,
@@ -104,6 +104,9 @@ pub struct Config<TPlat: PlatformRef> {
     pub chain_properties_json: String,
     /// Whether the chain is a live network. Found in the chain specification.
     pub chain_is_live: bool,
+    /// Identifier of the relay chain, as found in the chain specification. `None` if the chain
+    /// isn't a parachain.
+    pub relay_chain_id: Option<String>,
 
     /// Value to return when the `system_name` RPC is called. Should be set to the name of the
     /// final executable.
@@ -150,6 +153,7 @@ pub fn service<TPlat: PlatformRef>(config: Config<TPlat>) -> Frontend<TPlat> {
                 chain_ty: config.chain_ty,
                 chain_properties_json: config.chain_properties_json,
                 chain_is_live: config.chain_is_live,
+                relay_chain_id: config.relay_chain_id,
                 system_name: config.system_name,
                 system_version: config.system_version,
                 genesis_block_hash: config.genesis_block_hash,
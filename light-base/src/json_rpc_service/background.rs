@@ -74,6 +74,9 @@ pub(super) struct Config<TPlat: PlatformRef> {
     pub chain_properties_json: String,
     /// Whether the chain is a live network. Found in the chain specification.
     pub chain_is_live: bool,
+    /// Identifier of the relay chain, as found in the chain specification. `None` if the chain
+    /// isn't a parachain.
+    pub relay_chain_id: Option<String>,
 
     /// Value to return when the `system_name` RPC is called. Should be set to the name of the
     /// final executable.
@@ -103,6 +106,9 @@ struct Background<TPlat: PlatformRef> {
     chain_properties_json: String,
     /// Whether the chain is a live network. Found in the chain specification.
     chain_is_live: bool,
+    /// Identifier of the relay chain, as found in the chain specification. `None` if the chain
+    /// isn't a parachain.
+    relay_chain_id: Option<String>,
     /// Value to return when the `system_name` RPC is called.
     system_name: String,
     /// Value to return when the `system_version` RPC is called.
@@ -281,6 +287,12 @@ struct ChainHeadFollow {
     /// For each pinned block hash, the SCALE-encoded header of the block.
     pinned_blocks_headers: hashbrown::HashMap<[u8; 32], Vec<u8>, fnv::FnvBuildHasher>,
 
+    /// Maximum number of entries in [`ChainHeadFollow::pinned_blocks_headers`]. If a new block
+    /// would exceed this limit, the subscription is stopped.
+    ///
+    /// When `withRuntime` is `true`, the runtime service enforces its own limit as well.
+    max_pinned_blocks: usize,
+
     /// List of body/call/storage operations currently in progress. Keys are operation IDs.
     operations_in_progress: hashbrown::HashMap<String, ChainHeadOperation, fnv::FnvBuildHasher>,
 
@@ -488,6 +500,7 @@ pub(super) async fn run<TPlat: PlatformRef>(
         chain_ty: config.chain_ty,
         chain_is_live: config.chain_is_live,
         chain_properties_json: config.chain_properties_json,
+        relay_chain_id: config.relay_chain_id,
         system_name: config.system_name.clone(),
         system_version: config.system_version.clone(),
         randomness: ChaCha20Rng::from_seed({
@@ -764,6 +777,7 @@ pub(super) async fn run<TPlat: PlatformRef>(
                     | methods::MethodCall::state_subscribeStorage { .. }
                     | methods::MethodCall::state_unsubscribeRuntimeVersion { .. }
                     | methods::MethodCall::state_unsubscribeStorage { .. }
+                    | methods::MethodCall::sync_state_genSyncSpec { .. }
                     | methods::MethodCall::system_accountNextIndex { .. }
                     | methods::MethodCall::system_addReservedPeer { .. }
                     | methods::MethodCall::system_chain { .. }
@@ -778,6 +792,8 @@ pub(super) async fn run<TPlat: PlatformRef>(
                     | methods::MethodCall::system_peers { .. }
                     | methods::MethodCall::system_properties { .. }
                     | methods::MethodCall::system_removeReservedPeer { .. }
+                    | methods::MethodCall::system_reservedPeers { .. }
+                    | methods::MethodCall::system_syncState { .. }
                     | methods::MethodCall::system_version { .. } => {
                         if !me.printed_legacy_json_rpc_warning {
                             me.printed_legacy_json_rpc_warning = true;
@@ -812,6 +828,7 @@ pub(super) async fn run<TPlat: PlatformRef>(
                     | methods::MethodCall::chainSpec_v1_chainName { .. }
                     | methods::MethodCall::chainSpec_v1_genesisHash { .. }
                     | methods::MethodCall::chainSpec_v1_properties { .. }
+                    | methods::MethodCall::chainSpec_unstable_relayChain { .. }
                     | methods::MethodCall::rpc_methods { .. }
                     | methods::MethodCall::sudo_unstable_p2pDiscover { .. }
                     | methods::MethodCall::sudo_unstable_version { .. }
@@ -821,7 +838,22 @@ pub(super) async fn run<TPlat: PlatformRef>(
                     | methods::MethodCall::transactionWatch_v1_unwatch { .. }
                     | methods::MethodCall::sudo_network_unstable_watch { .. }
                     | methods::MethodCall::sudo_network_unstable_unwatch { .. }
-                    | methods::MethodCall::chainHead_unstable_finalizedDatabase { .. } => {}
+                    | methods::MethodCall::chainHead_unstable_finalizedDatabase { .. }
+                    | methods::MethodCall::chain_subscribeExportBlocks { .. }
+                    | methods::MethodCall::chain_unsubscribeExportBlocks { .. }
+                    | methods::MethodCall::system_subscribePeerCount { .. }
+                    | methods::MethodCall::system_unsubscribePeerCount { .. }
+                    | methods::MethodCall::grandpa_subscribeAuthoritySetChanges { .. }
+                    | methods::MethodCall::grandpa_unsubscribeAuthoritySetChanges { .. }
+                    | methods::MethodCall::chain_subscribeFinalizedExtrinsics { .. }
+                    | methods::MethodCall::chain_unsubscribeFinalizedExtrinsics { .. }
+                    | methods::MethodCall::chain_getBlockStats { .. }
+                    | methods::MethodCall::system_unstable_status { .. }
+                    | methods::MethodCall::system_unstable_networkState { .. }
+                    | methods::MethodCall::chain_unstable_leaves { .. }
+                    | methods::MethodCall::chain_unstable_blockTree { .. }
+                    | methods::MethodCall::state_unstable_getRuntimeCode { .. }
+                    | methods::MethodCall::state_unstable_runtimeUpgrades { .. } => {}
                 }
 
                 // Actual requests handler.
@@ -944,7 +976,33 @@ pub(super) async fn run<TPlat: PlatformRef>(
                         // renewing itself the next time it generates a notification.
                     }
 
-                    methods::MethodCall::chain_getBlock { hash } => {
+                    methods::MethodCall::chain_getBlock {
+                        hash,
+                        decode_extrinsics,
+                    } => {
+                        if decode_extrinsics == Some(true) {
+                            // TODO: implement this
+                            let _ = me
+                                .responses_tx
+                                .send(parse::build_error_response(
+                                    request_id_json,
+                                    parse::ErrorResponse::ServerError(
+                                        -32000,
+                                        "Decoding extrinsics isn't supported yet",
+                                    ),
+                                    None,
+                                ))
+                                .await;
+                            log!(
+                                &me.platform,
+                                Warn,
+                                &me.log_target,
+                                "chain_getBlock has been called with decodeExtrinsics set to \
+                                true. This isn't supported by smoldot yet."
+                            );
+                            continue;
+                        }
+
                         // Because this request requires asynchronous operations, we push it
                         // to a list of "multi-stage requests" that are processed later.
                         me.multistage_requests_to_advance.push_back((
@@ -1249,11 +1307,12 @@ pub(super) async fn run<TPlat: PlatformRef>(
                         let _ = me
                             .responses_tx
                             .send(
-                                methods::Response::rpc_methods(methods::RpcMethods {
-                                    methods: methods::MethodCall::method_names()
-                                        .map(|n| n.into())
-                                        .collect(),
-                                })
+                                methods::Response::rpc_methods(
+                                    methods::RpcMethods::from_method_names(
+                                        methods::MethodCall::method_names()
+                                            .chain(methods::MethodCall::method_aliases()),
+                                    ),
+                                )
                                 .to_json_response(request_id_json),
                             )
                             .await;
@@ -1625,34 +1684,34 @@ pub(super) async fn run<TPlat: PlatformRef>(
                     }
 
                     methods::MethodCall::system_peers {} => {
+                        // The peers are enumerated in an unspecified order. They are sorted in
+                        // order for the output to be deterministic.
+                        let mut peers = me
+                            .sync_service
+                            .syncing_peers()
+                            .await
+                            .map(
+                                |(peer_id, role, best_number, best_hash)| methods::SystemPeer {
+                                    peer_id: peer_id.to_string(),
+                                    roles: match role {
+                                        sync_service::Role::Authority => {
+                                            methods::SystemPeerRole::Authority
+                                        }
+                                        sync_service::Role::Full => methods::SystemPeerRole::Full,
+                                        sync_service::Role::Light => methods::SystemPeerRole::Light,
+                                    },
+                                    best_hash: methods::HashHexString(best_hash),
+                                    best_number,
+                                },
+                            )
+                            .collect::<Vec<_>>();
+                        peers.sort_unstable_by(|a, b| a.peer_id.cmp(&b.peer_id));
+
                         let _ = me
                             .responses_tx
                             .send(
-                                methods::Response::system_peers(
-                                    me.sync_service
-                                        .syncing_peers()
-                                        .await
-                                        .map(|(peer_id, role, best_number, best_hash)| {
-                                            methods::SystemPeer {
-                                                peer_id: peer_id.to_string(),
-                                                roles: match role {
-                                                    sync_service::Role::Authority => {
-                                                        methods::SystemPeerRole::Authority
-                                                    }
-                                                    sync_service::Role::Full => {
-                                                        methods::SystemPeerRole::Full
-                                                    }
-                                                    sync_service::Role::Light => {
-                                                        methods::SystemPeerRole::Light
-                                                    }
-                                                },
-                                                best_hash: methods::HashHexString(best_hash),
-                                                best_number,
-                                            }
-                                        })
-                                        .collect(),
-                                )
-                                .to_json_response(request_id_json),
+                                methods::Response::system_peers(peers)
+                                    .to_json_response(request_id_json),
                             )
                             .await;
                     }
@@ -2233,6 +2292,7 @@ pub(super) async fn run<TPlat: PlatformRef>(
                                     0,
                                     Default::default(),
                                 ), // TODO: capacity?
+                                max_pinned_blocks: 512, // TODO: make configurable?
                                 operations_in_progress:
                                     hashbrown::HashMap::with_capacity_and_hasher(
                                         32,
@@ -2503,6 +2563,18 @@ pub(super) async fn run<TPlat: PlatformRef>(
                             .await;
                     }
 
+                    methods::MethodCall::chainSpec_unstable_relayChain {} => {
+                        let _ = me
+                            .responses_tx
+                            .send(
+                                methods::Response::chainSpec_unstable_relayChain(
+                                    me.relay_chain_id.as_deref().map(Into::into),
+                                )
+                                .to_json_response(request_id_json),
+                            )
+                            .await;
+                    }
+
                     methods::MethodCall::sudo_unstable_p2pDiscover { multiaddr } => {
                         match multiaddr.parse::<multiaddr::Multiaddr>() {
                             Ok(mut addr)
@@ -2736,13 +2808,39 @@ pub(super) async fn run<TPlat: PlatformRef>(
                     | methods::MethodCall::state_getStorageHash { .. }
                     | methods::MethodCall::state_getStorageSize { .. }
                     | methods::MethodCall::state_queryStorage { .. }
+                    | methods::MethodCall::sync_state_genSyncSpec { .. }
                     | methods::MethodCall::system_addReservedPeer { .. }
                     | methods::MethodCall::system_dryRun { .. }
                     | methods::MethodCall::system_localPeerId { .. }
                     | methods::MethodCall::system_networkState { .. }
                     | methods::MethodCall::system_removeReservedPeer { .. }
+                    | methods::MethodCall::system_reservedPeers { .. }
+                    | methods::MethodCall::system_syncState { .. }
                     | methods::MethodCall::sudo_network_unstable_watch { .. }
-                    | methods::MethodCall::sudo_network_unstable_unwatch { .. }) => {
+                    | methods::MethodCall::sudo_network_unstable_unwatch { .. }
+                    | methods::MethodCall::chain_subscribeExportBlocks { .. }
+                    | methods::MethodCall::chain_unsubscribeExportBlocks { .. }
+                    | methods::MethodCall::system_subscribePeerCount { .. }
+                    | methods::MethodCall::system_unsubscribePeerCount { .. }
+                    | methods::MethodCall::grandpa_subscribeAuthoritySetChanges {
+                        ..
+                    }
+                    | methods::MethodCall::grandpa_unsubscribeAuthoritySetChanges {
+                        ..
+                    }
+                    | methods::MethodCall::chain_subscribeFinalizedExtrinsics {
+                        ..
+                    }
+                    | methods::MethodCall::chain_unsubscribeFinalizedExtrinsics {
+                        ..
+                    }
+                    | methods::MethodCall::chain_getBlockStats { .. }
+                    | methods::MethodCall::system_unstable_status { .. }
+                    | methods::MethodCall::system_unstable_networkState { .. }
+                    | methods::MethodCall::chain_unstable_leaves { .. }
+                    | methods::MethodCall::chain_unstable_blockTree { .. }
+                    | methods::MethodCall::state_unstable_getRuntimeCode { .. }
+                    | methods::MethodCall::state_unstable_runtimeUpgrades { .. }) => {
                         // TODO: implement the ones that make sense to implement ^
                         log!(
                             &me.platform,
@@ -4123,6 +4221,19 @@ pub(super) async fn run<TPlat: PlatformRef>(
                             .await;
                     }
                     sync_service::Notification::Block(block) => {
+                        if subscription_info.pinned_blocks_headers.len()
+                            >= subscription_info.max_pinned_blocks
+                        {
+                            // The JSON-RPC client doesn't unpin blocks quickly enough. Rather
+                            // than keeping an unbounded number of blocks pinned, the
+                            // subscription is stopped. Dropping `stream` unsubscribes from the
+                            // sync service.
+                            me.background_tasks.push(Box::pin(async move {
+                                Event::ChainHeadSubscriptionDeadSubcription { subscription_id }
+                            }));
+                            continue;
+                        }
+
                         // TODO: pass hash through notification
                         let block_hash =
                             header::hash_from_scale_encoded_header(&block.scale_encoded_header);
@@ -5368,6 +5479,7 @@ pub(super) async fn run<TPlat: PlatformRef>(
                                 // There's no way to verify the correctness of the justifications, consequently
                                 // we always return an empty list.
                                 justifications: None,
+                                decoded_extrinsics: None,
                             })
                             .to_json_response(&request_id_json),
                         )
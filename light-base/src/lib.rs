@@ -467,9 +467,7 @@ impl<TPlat: platform::PlatformRef, TChain> Client<TPlat, TChain> {
                         runtime_code_hint,
                         ..
                     }),
-                ) if db_ci.as_ref().finalized_block_header.number
-                    >= checkpoint.as_ref().finalized_block_header.number =>
-                {
+                ) if db_ci.finalized_block_number() >= checkpoint.finalized_block_number() => {
                     (Some(db_ci), true, known_nodes, runtime_code_hint)
                 }
 
@@ -779,7 +777,7 @@ impl<TPlat: platform::PlatformRef, TChain> Client<TPlat, TChain> {
                             ),
                             chain_information
                                 .as_ref()
-                                .map(|ci| ci.as_ref().finalized_block_header.number)
+                                .map(|ci| ci.finalized_block_number())
                                 .unwrap_or(0)
                         )
                     );
@@ -942,6 +940,7 @@ impl<TPlat: platform::PlatformRef, TChain> Client<TPlat, TChain> {
                 chain_ty: chain_spec.chain_type().to_owned(),
                 chain_is_live: chain_spec.has_live_network(),
                 chain_properties_json: chain_spec.properties().to_owned(),
+                relay_chain_id: chain_spec.relay_chain().map(|(id, _)| id.to_owned()),
                 system_name: self.platform.client_name().into_owned(),
                 system_version: self.platform.client_version().into_owned(),
                 genesis_block_hash,
@@ -1156,7 +1155,7 @@ fn start_services<TPlat: platform::PlatformRef>(
                 chain_information.as_ref().finality,
                 chain::chain_information::ChainInformationFinalityRef::Grandpa { .. }
             ) {
-                Some(chain_information.as_ref().finalized_block_header.number)
+                Some(chain_information.finalized_block_number())
             } else {
                 None
             }
@@ -1169,7 +1168,7 @@ fn start_services<TPlat: platform::PlatformRef>(
         ),
         best_block: match &config {
             StartServicesChainTy::RelayChain { chain_information } => (
-                chain_information.as_ref().finalized_block_header.number,
+                chain_information.finalized_block_number(),
                 chain_information
                     .as_ref()
                     .finalized_block_header